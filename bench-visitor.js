@@ -0,0 +1,40 @@
+const css = require('./');
+
+// Compares the number of calls into JS made by a visitor with and without a filter.
+// Pass a CSS file to use it as the fixture. Otherwise, a large style sheet is generated.
+let file = process.argv[2];
+let code = file
+  ? require('fs').readFileSync(file)
+  : Buffer.from(
+    Array.from({ length: 20000 }, (_, i) =>
+      `.${i % 20 ? 'button' : 'icon'}-${i} { color: red; background-image: url(${i}.png); margin: ${i % 10}px }`
+    ).join('\n')
+  );
+
+function run(name, filter) {
+  let calls = 0;
+  console.time(name);
+  css.transform({
+    filename: file || 'bench.css',
+    code,
+    minify: true,
+    visitor: {
+      Rule() {
+        calls++;
+      },
+      Declaration() {
+        calls++;
+      },
+      filter
+    }
+  });
+  console.timeEnd(name);
+  console.log(calls + ' calls');
+  console.log('');
+}
+
+run('unfiltered');
+run('filtered', {
+  rules: { selectorContains: '.icon-' },
+  declarations: { properties: ['background', 'background-image'] }
+});
@@ -75,10 +75,12 @@ pub struct ParseOptions {
   filename: *const c_char,
   nesting: bool,
   custom_media: bool,
+  scope: bool,
   css_modules: bool,
   css_modules_pattern: *const c_char,
   css_modules_dashed_idents: bool,
   error_recovery: bool,
+  strict_preludes: bool,
 }
 
 #[repr(C)]
@@ -185,6 +187,7 @@ impl Into<MinifyOptions> for TransformOptions {
         None
       },
       unused_symbols,
+      ..MinifyOptions::default()
     }
   }
 }
@@ -261,7 +264,9 @@ pub extern "C" fn lightningcss_stylesheet_parse(
       unsafe { std::str::from_utf8_unchecked(CStr::from_ptr(options.filename).to_bytes()).to_owned() }
     },
     nesting: options.nesting,
+    nesting_spec: lightningcss::stylesheet::NestingSpec::Legacy,
     custom_media: options.custom_media,
+    scope: options.scope,
     css_modules: if options.css_modules {
       let pattern = if !options.css_modules_pattern.is_null() {
         let pattern =
@@ -277,13 +282,26 @@ pub extern "C" fn lightningcss_stylesheet_parse(
       Some(lightningcss::css_modules::Config {
         pattern,
         dashed_idents: options.css_modules_dashed_idents,
+        ..Default::default()
       })
     } else {
       None
     },
     error_recovery: options.error_recovery,
+    strict_preludes: options.strict_preludes,
+    strict: false,
+    strict_exceptions: None,
+    preserve_calc: false,
+    preserve_var_fallbacks: false,
+    deep_selectors: false,
+    custom_at_rules: None,
+    ignore_properties: None,
+    verbatim_values: false,
     source_index: 0,
     warnings: Some(warnings.clone()),
+    warnings_limit: None,
+    warnings_truncated: Default::default(),
+    comments: Default::default(),
   };
 
   let stylesheet = unwrap!(StyleSheet::parse(code, opts), error, std::ptr::null_mut());
@@ -335,6 +353,8 @@ pub extern "C" fn lightningcss_stylesheet_to_css(
     } else {
       None
     },
+    source_map_url: None,
+    ..PrinterOptions::default()
   };
 
   let res = unwrap!(wrapper.stylesheet.to_css(opts), error, ToCssResult::default());
@@ -0,0 +1,41 @@
+import type { TransformOptions, CSSModuleExports, Dependency } from './index';
+
+export interface BundlerResult {
+  /** The transformed code, or an empty string if an error occurred. */
+  code: string,
+  /** The generated source map, if enabled. */
+  map: string | null,
+  /** CSS module exports, if enabled. */
+  exports: CSSModuleExports | null,
+  /** `@import` and `url()` dependencies, if enabled. */
+  dependencies: Dependency[],
+  /** Errors and warnings that occurred during compilation. */
+  diagnostics: Diagnostic[]
+}
+
+export interface Diagnostic {
+  severity: 'error' | 'warning',
+  message: string,
+  /** A stable code identifying the type of diagnostic, e.g. `UnexpectedToken`. */
+  code: string | null,
+  /** The file in which the diagnostic occurred. */
+  file: string | null,
+  /** The start of the diagnostic. */
+  start: Position | null,
+  /** The end of the token at the start of the diagnostic (exclusive), if it is in the transformed file. */
+  end: Position | null
+}
+
+export interface Position {
+  /** The line number (1-based). */
+  line: number,
+  /** The column number (1-based), counted in UTF-16 code units. */
+  column: number
+}
+
+/**
+ * Compiles a CSS file like `transform()`, returning the result in a shape that does not
+ * depend on a particular bundler. Errors are returned as diagnostics rather than thrown.
+ * Imports are inlined when `inlineImports` is enabled.
+ */
+export declare function transformForBundler(options: TransformOptions): BundlerResult;
@@ -0,0 +1,3 @@
+const { transformForBundler } = require('./index');
+
+module.exports.transformForBundler = transformForBundler;
@@ -0,0 +1,4 @@
+import adapters from './adapters.js';
+
+const { transformForBundler } = adapters;
+export { transformForBundler };
@@ -5,16 +5,50 @@ export interface TransformOptions {
   filename: string,
   /** The source code to transform. */
   code: Buffer,
-  /** Whether to enable minification. */
-  minify?: boolean,
-  /** Whether to output a source map. */
+  /**
+   * The character encoding of `code`. Defaults to the encoding declared by a `@charset` rule at the
+   * start of the code, or UTF-8. A UTF-8 byte order mark is removed. Code that is not valid UTF-8
+   * throws an error with the byte offset of the first invalid sequence.
+   */
+  encoding?: 'utf-8' | 'latin1',
+  /**
+   * Whether to enable minification. An object enables minification, and controls
+   * which optimizations are applied, as with `minifyOptions`.
+   */
+  minify?: boolean | MinifyOptions,
+  /** Fine grained control over optimizations, which are all enabled by default. */
+  minifyOptions?: MinifyOptions,
+  /**
+   * Whether to output a source map. Inline source maps in `sourceMappingURL` comments are chained into it.
+   * When bundling, source maps referenced by a relative URL are also read through the resolver.
+   * `sourceMappingURL` comments are never included in the output code.
+   */
   sourceMap?: boolean,
+  /**
+   * Whether to include the content of the source files in the source map, as `sourcesContent`.
+   * Defaults to `true`. When disabled, content from the input source map is also omitted.
+   */
+  sourceMapIncludeContent?: boolean,
   /** An input source map to extend. */
   inputSourceMap?: string,
+  /**
+   * A URL to reference in a `/*# sourceMappingURL=... *\/` comment appended to the output code.
+   * It is written exactly as given, so it should be relative to where the CSS file is served from.
+   * Not applied to `codeLegacy` when `dualTargets` is set.
+   */
+  sourceMapUrl?: string,
   /** The browser targets for the generated code. */
   targets?: Targets,
+  /**
+   * Compiles the stylesheet twice from a single parse, once for modern browsers and
+   * once for legacy browsers. The legacy output is returned as `codeLegacy` and `mapLegacy`.
+   * When set, `targets` is ignored.
+   */
+  dualTargets?: DualTargets,
   /** Whether to enable various draft syntax. */
   drafts?: Drafts,
+  /** Whether to enable various non-standard syntax used by frameworks. */
+  nonStandard?: NonStandard,
   /** Whether to compile this file as a CSS module. */
   cssModules?: boolean | CSSModulesConfig,
   /**
@@ -30,17 +64,304 @@ export interface TransformOptions {
    */
   pseudoClasses?: PseudoClasses,
   /**
-   * A list of class names, ids, and custom identifiers (e.g. @keyframes) that are known
-   * to be unused. These will be removed during minification. Note that these are not
-   * selectors but individual names (without any . or # prefixes).
+   * A list of class names, ids, custom property names (e.g. `--color`), and custom identifiers
+   * (e.g. @keyframes) that are known to be unused. These will be removed during minification.
+   * Note that these are not selectors but individual names (without any . or # prefixes).
+   *
+   * Names containing `*` are glob-style patterns, e.g. `legacy-*` matches all names
+   * starting with `legacy-`. Patterns may also be given explicitly as `{ pattern: '...' }`.
    */
-  unusedSymbols?: string[],
+  unusedSymbols?: (string | UnusedSymbolPattern)[],
   /**
    * Whether to ignore invalid rules and declarations rather than erroring.
    * When enabled, warnings are returned, and the invalid rule or declaration is
-   * omitted from the output code.
+   * omitted from the output code. Declarations with an unterminated string, unmatched
+   * brackets, or an unclosed function are omitted without losing the declarations after them.
    */
-  errorRecovery?: boolean
+  errorRecovery?: boolean,
+  /**
+   * The maximum number of warnings to return. Further warnings are dropped, and `warningsTruncated`
+   * is set in the result. Identical warnings at the same location are returned once, and count once.
+   * By default, all warnings are returned.
+   */
+  warningsLimit?: number,
+  /**
+   * Which warnings to return, applied after all warnings have been collected, including those
+   * generated during minification. Either a list of warning types to remove, e.g. `['AtRuleInvalid']`,
+   * or a function that is called with each warning and returns whether to keep it.
+   */
+  warningFilter?: string[] | ((warning: Warning) => boolean),
+  /**
+   * Whether to re-parse the output code and report any errors, which indicate a bug in the output.
+   * The errors are returned as `verificationErrors`, rather than thrown.
+   */
+  verifyOutput?: boolean,
+  /**
+   * With `errorRecovery`, `@media`, `@supports`, and `@container` rules with an invalid prelude
+   * are kept with the prelude as written. When enabled, the whole rule is omitted instead.
+   */
+  strictPreludes?: boolean,
+  /**
+   * Whether to throw an error for unknown properties, invalid values of known properties, and unknown at-rules,
+   * which are otherwise kept as written. This takes precedence over `errorRecovery`, so that it can be used for linting.
+   */
+  strict?: boolean,
+  /**
+   * Names of properties, and of at-rules including the `@` (e.g. `@tailwind`), that are allowed in `strict` mode.
+   */
+  strictExceptions?: string[],
+  /**
+   * Whether to output the fallbacks of `var()` references exactly as written,
+   * rather than minifying them along with the rest of the value.
+   */
+  preserveVarFallbacks?: boolean,
+  /**
+   * Names of properties to print exactly as written, e.g. made-up properties consumed by a runtime.
+   * They are not minified, prefixed, or removed as duplicates.
+   */
+  ignoreProperties?: string[],
+  /**
+   * Definitions of at-rules that are not part of CSS, e.g. Tailwind directives, keyed by name without the `@`.
+   * Matching rules are kept and printed as written, including within style rules, where they stay in place
+   * between the declarations.
+   * Declarations within a `declaration-list` body are minified and analyzed for dependencies as usual.
+   */
+  customAtRules?: { [name: string]: CustomAtRuleDefinition },
+  /**
+   * Whether to only remove whitespace and comments, without rewriting any value, merging or reordering rules
+   * and declarations, or compiling for the targets. Takes precedence over `minify`.
+   */
+  minifyWhitespaceOnly?: boolean,
+  /**
+   * Whether to only add vendor prefixes for the targets, e.g. as a replacement for Autoprefixer.
+   * Prefixed copies of properties that need them, such as `-webkit-user-select`, are added before
+   * the unprefixed declaration, unless already present. All other transforms are disabled: values,
+   * selectors and at-rules are not compiled for the targets, and rules and declarations are not
+   * merged or minified. `minify` only removes whitespace.
+   */
+  prefixOnly?: boolean,
+  /**
+   * Whether to throw an error when a feature that is not supported by the targets, and cannot be compiled,
+   * is used, e.g. `:has()`. The error lists the feature and the targets that do not support it.
+   */
+  errorOnUnsupported?: boolean,
+  /**
+   * Whether to return the style sheet as a JSON AST in addition to the code.
+   * The AST reflects any minification and lowering for the targets.
+   */
+  returnAst?: boolean,
+  /**
+   * Whether to return `exportsHash`, a hash of the CSS module exports. The hash only changes when
+   * the exported names, their compiled names, or their composed names change, not when rule bodies do.
+   */
+  exportsHash?: boolean,
+  /**
+   * Whether to inline the contents of `@import` rules, as when bundling. Imported files
+   * are loaded with the `resolver` if provided, and from the file system otherwise.
+   * In WebAssembly builds, imports are left as is, and a warning is returned.
+   */
+  inlineImports?: boolean,
+  /** Custom resolver to use when loading imported files with `inlineImports`. */
+  resolver?: SyncResolver,
+  /**
+   * Functions called with the rules and declarations of the style sheet after it is parsed, and
+   * before it is minified. Only supported by `transform()` in native builds, and not with `inlineImports`.
+   */
+  visitor?: Visitor,
+  /** Options for the formatting of non-minified output. */
+  pretty?: PrettyOptions,
+  /** An alias for `pretty`. */
+  format?: PrettyOptions,
+  /**
+   * The maximum number of decimal places to print for each kind of numeric value.
+   * By default, numbers are printed with up to six significant digits.
+   */
+  precision?: PrecisionOptions,
+  /**
+   * The format to print colors in, regardless of the targets. Alpha channels are preserved.
+   * Colors outside the sRGB gamut are only converted to `oklch`, and `currentColor` and system
+   * colors are left as is. By default, or with `keep`, colors are printed in the shortest form
+   * supported by the targets.
+   */
+  colorFormat?: 'hex' | 'rgb' | 'hsl' | 'oklch' | 'keep',
+  /**
+   * Which quotes to print strings and `url()` values with. `minimal` uses single quotes only when that
+   * requires fewer escapes, and prints `url()` values without quotes unless characters such as spaces or
+   * parentheses would need to be escaped. By default, strings use double quotes, and `url()` values are
+   * printed without quotes when minifying if that is shorter.
+   */
+  quotes?: 'double' | 'single' | 'minimal',
+  /**
+   * Features to always compile, even if all targets support them or no targets are set.
+   * Either a list of feature names, or a bit mask of `Features` flags.
+   */
+  include?: FeatureName[] | number,
+  /**
+   * Features to never compile, even if some targets do not support them.
+   * Either a list of feature names, or a bit mask of `Features` flags.
+   */
+  exclude?: FeatureName[] | number,
+  /**
+   * Which comments between rules and declarations to keep in the output, including when minifying.
+   * With `"preserve-license"`, comments starting with `!` or containing a `@license` or `@preserve` annotation
+   * are kept. License comments at the top of the file are then also kept unless `removeComments` is set.
+   * Defaults to `"none"`.
+   */
+  comments?: 'none' | 'preserve-license' | 'all',
+  /**
+   * Whether to lower logical properties such as `margin-inline-start` to physical properties. With `"auto"`,
+   * they are lowered only when some targets do not support them. `"always"` and `"never"` are equivalent to
+   * including or excluding the `logicalProperties` feature. Defaults to `"auto"`.
+   */
+  logicalProperties?: 'auto' | 'always' | 'never'
+}
+
+/** A feature that can be included or excluded regardless of the browser targets. */
+export type FeatureName = 'nesting' | 'mediaRangeSyntax' | 'customMediaQueries' | 'hexAlphaColors' | 'logicalProperties' | 'scope';
+
+/** Bit flags for the features that can be passed to the `include` and `exclude` options. */
+export declare const Features: {
+  /** Nested style rules, which are compiled to separate rules. */
+  Nesting: 1,
+  /** Media query range syntax, e.g. `(width >= 600px)`, which is compiled to `min-` and `max-` features. */
+  MediaRangeSyntax: 2,
+  /** `@custom-media` rules, which are substituted into the media queries that reference them. */
+  CustomMediaQueries: 4,
+  /** `#rrggbbaa` hex colors, which are compiled to `rgba()`. */
+  HexAlphaColors: 8,
+  /**
+   * Logical properties, e.g. `margin-inline-start`, which are compiled to physical properties,
+   * with `:dir()` rules for values that depend on the direction.
+   */
+  LogicalProperties: 16,
+  /** `@scope` rules with a scope start, which are compiled to style rules with `:where()` selectors. */
+  Scope: 32
+};
+
+export interface UnusedSymbolPattern {
+  /** A glob-style pattern, in which `*` matches any sequence of characters. */
+  pattern: string
+}
+
+export interface PrettyOptions {
+  /**
+   * The string to indent each level with, e.g. `'\t'`, or a number of spaces.
+   * The default is two spaces.
+   */
+  indent?: string | number,
+  /**
+   * The maximum line width. Selector lists and comma separated values which would extend
+   * past it are written with one item per line.
+   */
+  maxLineWidth?: number,
+  /** Whether to write an empty line between rules. Defaults to `true`. */
+  blankLinesBetweenRules?: boolean
+}
+
+export interface PrecisionOptions {
+  /** The number of decimal places for lengths, e.g. `px` or `em`. */
+  lengths?: number,
+  /** The number of decimal places for percentages. */
+  percentages?: number,
+  /** The number of decimal places for times, i.e. `s` and `ms`. */
+  times?: number,
+  /** The number of decimal places for angles, e.g. `deg` or `turn`. */
+  angles?: number,
+  /** The number of decimal places for unitless numbers, and dimensions with other units, e.g. `fr`. */
+  numbers?: number
+}
+
+export interface MinifyOptions {
+  /** Whether to remove license comments (`/*! ... *\/`). Other comments are always removed. */
+  removeComments?: boolean,
+  /** Whether to merge adjacent rules with the same selectors, declarations, or conditions. */
+  mergeRules?: boolean,
+  /**
+   * Whether to combine longhand declarations into shorthands, and remove overridden declarations.
+   * When disabled, declarations are only transformed individually, e.g. for the browser targets.
+   */
+  mergeLonghands?: boolean,
+  /** An alias for `mergeLonghands`. */
+  shorthands?: boolean,
+  /** Whether to remove rules with no declarations or nested rules. */
+  removeEmptyRules?: boolean,
+  /**
+   * Whether to remove declarations that are overridden by a later declaration of the same property,
+   * or a shorthand that includes it, within a rule, e.g. after merging adjacent rules with the same selectors.
+   * Disabled by default, because it also removes fallbacks for older browsers, such as `display: block; display: grid`.
+   */
+  deduplicateDeclarations?: boolean,
+  /**
+   * Whether to sort the declarations within each rule alphabetically, which improves compression.
+   * Declarations are only reordered when their relative order does not matter, so shorthands, longhands
+   * and logical properties of the same property, as well as custom properties and values with `var()`,
+   * keep their order.
+   */
+  sortDeclarations?: boolean,
+  /**
+   * Whether to also merge `@media`, `@supports`, `@container`, and `@layer` blocks with the same
+   * condition or name when they are not adjacent. Blocks are only merged when the rules in between
+   * do not declare any of the same properties, so that the cascade order is preserved.
+   */
+  mergeNonAdjacentAtRules?: boolean,
+  /**
+   * Whether to remove `@keyframes` rules that are identical to an earlier rule with a different name,
+   * and update `animation` and `animation-name` declarations to reference the remaining rule.
+   * The replaced names are returned as `renamedKeyframes`. Ignored when CSS modules are enabled.
+   */
+  deduplicateKeyframes?: boolean,
+  /**
+   * Whether to rename `@keyframes` rules to short generated names (`a`, `b`, ...), and update `animation`
+   * and `animation-name` declarations to match. The new names are returned as `renamedKeyframes`.
+   * Ignored when CSS modules are enabled.
+   */
+  shortenKeyframesNames?: boolean,
+  /** A list of `@keyframes` names that are not renamed by `shortenKeyframesNames`, e.g. because they are referenced from JavaScript. */
+  preservedKeyframesNames?: string[],
+  /**
+   * Whether classes in `unusedSymbols` are removed even when they are composed by a class that is kept.
+   * By default, such classes are kept, because they are still referenced by the exports of the composing
+   * class. When enabled, they are removed along with the `composes` references to them, and a warning is emitted.
+   */
+  strictUnusedSymbols?: boolean,
+  /**
+   * Whether to inline custom properties defined on `:root`, replacing their `var()` references with the
+   * defined value, or a list of the custom property names to inline. Only custom properties that are defined
+   * once, in a top-level `:root` rule, are inlined. This assumes they are not changed at runtime, e.g. from
+   * JavaScript. The inlined names are returned as `inlinedCustomProperties`.
+   */
+  inlineCustomProperties?: boolean | string[],
+  /**
+   * Whether to remove declarations of custom properties that are not referenced by any `var()` in the style sheet,
+   * directly or through other referenced custom properties. This assumes they are not read from JavaScript
+   * or other style sheets.
+   */
+  removeUnusedCustomProperties?: boolean,
+  /**
+   * Whether to add a fallback declaration before each declaration that uses `var()`, when the targets
+   * include browsers without custom properties support. Custom properties are replaced with their value
+   * if they are only defined in top-level `:root` rules; otherwise, a warning is emitted.
+   */
+  customPropertyFallbacks?: boolean,
+  /**
+   * Whether to print colors in their shortest form, e.g. `red` instead of `#ff0000`.
+   * When disabled, RGB colors are printed as hex colors.
+   */
+  colors?: boolean,
+  /**
+   * Whether to simplify math functions such as `calc()`. When disabled, declarations
+   * containing math functions are printed as written.
+   */
+  calc?: boolean,
+  /** Whether to remove quotes from attribute selector values where possible. */
+  selectors?: boolean
+}
+
+export interface DualTargets {
+  /** The browser targets for the modern output. */
+  modern: Targets,
+  /** The browser targets for the legacy output. */
+  legacy: Targets
 }
 
 export interface DependencyOptions {
@@ -48,13 +369,56 @@ export interface DependencyOptions {
   preserveImports?: boolean
 }
 
-export type BundleOptions = Omit<TransformOptions, 'code'>;
+export interface BundleOptions extends Omit<TransformOptions, 'code' | 'encoding' | 'dualTargets' | 'returnAst' | 'exportsHash' | 'inlineImports' | 'visitor'> {
+  /**
+   * The maximum number of threads used to read and parse files concurrently.
+   * Defaults to the number of CPUs, shared with other work in the process.
+   * Set to `1` for fully sequential, reproducible bundling, e.g. when debugging.
+   */
+  threads?: number,
+  /**
+   * How to handle license comments (`/*! ... *\/`) in bundled files. By default they are removed.
+   * With `"license-hoist"`, the license comments from all files are printed once at the top of the
+   * bundle, and returned as `licenseComments`.
+   */
+  preserveComments?: 'license-hoist',
+  /**
+   * Whether to move `:root` rules that only declare custom properties from imported files to the top
+   * of the bundle, after any `@layer` statements, so that the variables are defined regardless of the
+   * import order. An error is thrown if a rule would be moved before a different definition of a variable.
+   */
+  hoistRootVariables?: boolean,
+  /**
+   * Whether to combine the top-level `@layer` statements from all bundled files into a single statement
+   * at the top of the bundle, declaring the layers in the order they are first seen, and to merge
+   * top-level `@layer` blocks with the same name.
+   */
+  hoistLayerStatements?: boolean,
+  /**
+   * Whether to also return the bundle split into one chunk per top-level cascade layer, as `chunks`.
+   * The first chunk contains the `@import` rules and a statement declaring the order of the named layers,
+   * and concatenating the chunks results in the same cascade as the whole bundle. Anonymous layers, layers
+   * within conditional rules, and the rules after them are kept in order in the last chunk.
+   */
+  splitByLayer?: boolean,
+  /** Custom resolver to use when loading CSS files. Functions must return synchronously. */
+  resolver?: SyncResolver
+}
 
-export interface BundleAsyncOptions extends BundleOptions {
+export interface BundleAsyncOptions extends Omit<BundleOptions, 'resolver'> {
   resolver?: Resolver;
+  /**
+   * The maximum time in milliseconds to wait for each call to the resolver's `read` or `resolve` functions.
+   * If a call does not return or settle its promise in time, bundling fails with a timeout error.
+   * By default, calls are waited on indefinitely.
+   */
+  resolveTimeoutMs?: number
 }
 
-/** Custom resolver to use when loading CSS files. */
+/**
+ * Custom resolver to use when loading CSS files. If a returned promise is rejected,
+ * bundling fails with the message and stack of the rejection reason.
+ */
 export interface Resolver {
   /** Read the given file and return its contents as a string. */
   read?: (file: string) => string | Promise<string>;
@@ -66,11 +430,82 @@ export interface Resolver {
   resolve?: (specifier: string, originatingFile: string) => string | Promise<string>;
 }
 
+/** Custom resolver to use when loading CSS files with the synchronous `bundle()` function. */
+export interface SyncResolver {
+  /** Read the given file and return its contents as a string. */
+  read?: (file: string) => string;
+
+  /**
+   * Resolve the given CSS import specifier from the provided originating file to a
+   * path which gets passed to `read()`.
+   */
+  resolve?: (specifier: string, originatingFile: string) => string;
+}
+
+/**
+ * Functions called with the JSON AST of rules and declarations, in the same format as `returnAst`.
+ * A function may return a node to replace the one it was called with.
+ */
+export interface Visitor {
+  /** Called with each rule, including nested rules. */
+  Rule?: (rule: any) => any | void,
+  /** Called with each declaration in a style rule. */
+  Declaration?: (declaration: any) => any | void,
+  /**
+   * Which rules and declarations to call the functions for. The filter is evaluated before
+   * converting nodes to JS, so nodes that do not match cost no calls.
+   */
+  filter?: VisitorFilter
+}
+
+export interface VisitorFilter {
+  rules?: {
+    /** Only visit style rules with a selector list containing this string, e.g. `.icon-`. */
+    selectorContains?: string
+  },
+  declarations?: {
+    /** Only visit declarations of these properties, by their unprefixed name, e.g. `background`. */
+    properties?: string[]
+  }
+}
+
 export interface Drafts {
   /** Whether to enable CSS nesting. */
   nesting?: boolean,
   /** Whether to enable @custom-media rules. */
-  customMedia?: boolean
+  customMedia?: boolean,
+  /** Whether to enable @scope rules. */
+  scope?: boolean,
+  /**
+   * Which version of the CSS nesting spec to follow. In `legacy` mode, nested style rules must
+   * start with `&`, and `@nest` is used otherwise. In `current` mode, nested selectors without `&`
+   * are relative to the parent rule, and may start with a combinator. Defaults to `legacy`.
+   */
+  nestingSpec?: 'legacy' | 'current'
+}
+
+export interface CustomAtRuleDefinition {
+  /** The syntax of the prelude. If omitted, the rule must not have a prelude. */
+  prelude?: 'ident' | 'string' | 'custom-ident-list',
+  /**
+   * The syntax of the block. `none` means the rule ends with a semicolon, and `raw` keeps
+   * any tokens as written. Defaults to `none`.
+   */
+  body?: 'none' | 'declaration-list' | 'rule-list' | 'raw'
+}
+
+export interface NonStandard {
+  /**
+   * Whether to parse the `:deep()`, `::v-deep`, and `::ng-deep` selectors, and the legacy `>>>` and
+   * `/deep/` combinators, used by Vue and Angular. The combinators are printed as written, and rules
+   * containing them are never merged.
+   */
+  deepSelectors?: boolean,
+  /**
+   * Whether to remove deep selectors from the output, replacing them with a descendant combinator,
+   * e.g. `.a :deep(.b)` becomes `.a .b`. Useful for production output once component styles are scoped.
+   */
+  stripDeepSelectors?: boolean
 }
 
 export interface PseudoClasses {
@@ -86,28 +521,136 @@ export interface TransformResult {
   code: Buffer,
   /** The generated source map, if enabled. */
   map: Buffer | void,
+  /** The code compiled for the legacy targets, if `dualTargets` is set. */
+  codeLegacy?: Buffer,
+  /** The source map for the legacy code, if `dualTargets` and `sourceMap` are set. */
+  mapLegacy?: Buffer | void,
   /** CSS module exports, if enabled. */
   exports: CSSModuleExports | void,
-  /** CSS module references, if `dashedIdents` is enabled. */
+  /** A 16 character hex hash of `exports`, if `exportsHash` is enabled and CSS modules are enabled. */
+  exportsHash?: string,
+  /** Maps `@keyframes` names to their scoped names, if CSS modules are enabled. These are also included in `exports`. */
+  animations: { [name: string]: string } | void,
+  /**
+   * CSS module references, if `dashedIdents` is enabled. When bundling, classes composed from
+   * other files are also included, keyed by their compiled name.
+   */
   references: CSSModuleReferences,
   /** `@import` and `url()` dependencies, if enabled. */
   dependencies: Dependency[] | void,
+  /** Maps removed or renamed `@keyframes` names to the names they were replaced with, if `deduplicateKeyframes` or `shortenKeyframesNames` is enabled. */
+  renamedKeyframes: { [name: string]: string },
+  /** The names of the custom properties that were inlined, if `inlineCustomProperties` is enabled. */
+  inlinedCustomProperties: string[],
+  /** The names and patterns in `unusedSymbols` that caused at least one rule or declaration to be removed. */
+  removedSymbols: string[],
+  /**
+   * The names and patterns in `unusedSymbols` that did not cause anything to be removed, e.g. because
+   * they do not appear in the style sheet. These may indicate stale entries.
+   */
+  unmatchedSymbols: string[],
+  /** The features that were compiled because the targets do not support them, or because they were included. */
+  featuresUsed: FeatureName[],
+  /** The license comments printed at the top of the bundle, if `preserveComments` is `"license-hoist"`. */
+  licenseComments?: string[],
   /** Warnings that occurred during compilation. */
-  warnings: Warning[]
+  warnings: Warning[],
+  /** Whether warnings were dropped because `warningsLimit` was reached. */
+  warningsTruncated: boolean,
+  /** Errors found by re-parsing the output code, if `verifyOutput` is enabled. */
+  verificationErrors?: VerificationError[],
+  /** The style sheet AST, if `returnAst` is enabled. */
+  ast?: StyleSheetAst,
+  /** The bundle split by cascade layer, if `splitByLayer` is enabled. */
+  chunks?: LayerChunk[]
+}
+
+export interface LayerChunk {
+  /** The name of the layer, or `null` for the preamble and the unlayered rules. */
+  layer: string | null,
+  /** The code of the chunk. */
+  code: Buffer,
+  /** The source map of the chunk, if enabled. */
+  map: Buffer | null
+}
+
+/**
+ * A serialized style sheet. Rules are objects of the form `{type, value}`,
+ * mirroring the Rust types in the `lightningcss` crate.
+ */
+export interface StyleSheetAst {
+  rules: any[],
+  sources: string[],
+  [key: string]: any
 }
 
 export interface Warning {
   message: string,
+  /** A stable code identifying the type of warning, e.g. `UnexpectedToken`. */
+  code: string,
+  /** The type of warning, which is the same as `code`. */
   type: string,
+  /**
+   * How serious the warning is. `warning` means something is invalid and was ignored or removed, so the output
+   * may not be what was intended. `info` means something was not understood but kept as written, e.g. an
+   * unknown at-rule, or that a change was made as requested.
+   */
+  severity: 'warning' | 'info',
+  /**
+   * The step of the compilation that emitted the warning. For example, `print` warnings are emitted when a feature
+   * that is not supported by the targets, such as `:has()`, cannot be compiled.
+   */
+  stage: 'parse' | 'minify' | 'print',
+  /** The number of identical warnings at this location that were collapsed into this one, if more than one. */
+  count?: number,
   value?: any,
   loc: ErrorLocation
 }
 
+export interface VerificationError {
+  message: string,
+  /** A stable code identifying the type of error, e.g. `UnexpectedToken`. */
+  code: string,
+  /** The location of the error in the output code. */
+  loc: ErrorLocation | null,
+  /** The location in the input that the erroneous output was generated from, if a source map is enabled. */
+  sourceLoc: ErrorLocation | null,
+  /** Whether the error is in `codeLegacy`, when `dualTargets` is set. */
+  legacy: boolean
+}
+
 export interface CSSModulesConfig {
   /** The pattern to use when renaming class names and other identifiers. Default is `[hash]_[local]`. */
   pattern: string,
   /** Whether to rename dashed identifiers, e.g. custom properties. */
-  dashedIdents: boolean
+  dashedIdents: boolean,
+  /**
+   * The algorithm used to generate `[hash]` values. Version 1 (the default) may differ between
+   * platforms and releases. Version 2 hashes the filename with FNV-1a after normalizing path
+   * separators, and is stable across platforms and releases.
+   */
+  hashVersion?: 1 | 2,
+  /**
+   * Whether to include the maximum specificity of the selectors each exported class appears in,
+   * as the `specificity` of its export.
+   */
+  specificity?: boolean,
+  /**
+   * Whether to include the printed selectors each exported class appears in,
+   * as the `selectors` of its export. Identical selectors are only included once.
+   */
+  exportSelectors?: boolean,
+  /**
+   * Whether to rename class names and ids within deep selectors, i.e. within `:deep()` and `::v-deep()`,
+   * or after `::v-deep` and `::ng-deep`. By default, they are left as is, like within `:global()`.
+   */
+  scopeDeepSelectors?: boolean,
+  /**
+   * A function to generate renamed identifiers, called with the original name, the filename
+   * it was declared in, and a hash of the filename. Overrides `pattern` when present.
+   * Only supported by `bundleAsync()`.
+   */
+  generateName?: (name: string, filename: string, hash: string) => string | Promise<string>
 }
 
 export type CSSModuleExports = {
@@ -121,7 +664,11 @@ export interface CSSModuleExport {
   /** Whether the export is referenced in this file. */
   isReferenced: boolean,
   /** Other names that are composed by this export. */
-  composes: CSSModuleReference[]
+  composes: CSSModuleReference[],
+  /** The maximum specificity of the selectors this class appears in, as `[a, b, c]`, if `specificity` is enabled. */
+  specificity?: [number, number, number],
+  /** The printed selectors this class appears in, if `exportSelectors` is enabled. */
+  selectors?: string[]
 }
 
 export type CSSModuleReferences = {
@@ -157,12 +704,20 @@ export interface ImportDependency {
   type: 'import',
   /** The url of the `@import` dependency. */
   url: string,
-  /** The media query for the `@import` rule. */
+  /** The media query list of the `@import` rule, e.g. `screen and (min-width: 100px)`. */
   media: string | null,
-  /** The `supports()` query for the `@import` rule. */
+  /**
+   * The `supports()` condition of the `@import` rule, without the `supports()` function,
+   * e.g. `(display: grid)`.
+   */
   supports: string | null,
   /** The source location where the `@import` rule was found. */
-  loc: SourceLocation
+  loc: SourceLocation,
+  /**
+   * Whether the import was skipped while bundling because none of the targets
+   * support its `supports()` condition. Skipped files are not read.
+   */
+  skipped: boolean
 }
 
 export interface UrlDependency {
@@ -172,7 +727,9 @@ export interface UrlDependency {
   /** The source location where the `url()` was found. */
   loc: SourceLocation,
   /** The placeholder that the url was replaced with. */
-  placeholder: string
+  placeholder: string,
+  /** The `format()` hint of an `@font-face` `src` url, if any. */
+  format?: string
 }
 
 export interface SourceLocation {
@@ -201,11 +758,32 @@ export interface ErrorLocation extends Location {
  */
 export declare function transform(options: TransformOptions): TransformResult;
 
+export interface ValidateResult {
+  /** Warnings emitted while parsing. With `errorRecovery`, these include invalid rules and declarations. */
+  warnings: Warning[],
+  /** The error that stopped parsing, if any. */
+  errors: Warning[],
+  /** Whether warnings were dropped because `warningsLimit` was reached. */
+  warningsTruncated: boolean
+}
+
+/**
+ * Parses a CSS file and returns the warnings and errors, without minifying or printing it.
+ * This is useful for linting, e.g. along with `errorRecovery` to report every invalid rule.
+ */
+export declare function validate(options: TransformOptions): ValidateResult;
+
 export interface TransformAttributeOptions {
   /** The filename in which the style attribute appeared. Used for error messages and dependencies. */
   filename?: string,
   /** The source code to transform. */
   code: Buffer,
+  /**
+   * The character encoding of `code`. Defaults to the encoding declared by a `@charset` rule at the
+   * start of the code, or UTF-8. A UTF-8 byte order mark is removed. Code that is not valid UTF-8
+   * throws an error with the byte offset of the first invalid sequence.
+   */
+  encoding?: 'utf-8' | 'latin1',
   /** Whether to enable minification. */
   minify?: boolean,
   /** The browser targets for the generated code. */
@@ -220,9 +798,38 @@ export interface TransformAttributeOptions {
   /**
    * Whether to ignore invalid rules and declarations rather than erroring.
    * When enabled, warnings are returned, and the invalid rule or declaration is
-   * omitted from the output code.
+   * omitted from the output code. Declarations with an unterminated string, unmatched
+   * brackets, or an unclosed function are omitted without losing the declarations after them.
    */
-  errorRecovery?: boolean
+  errorRecovery?: boolean,
+  /**
+   * The maximum number of warnings to return. Further warnings are dropped, and `warningsTruncated`
+   * is set in the result. Identical warnings at the same location are returned once, and count once.
+   * By default, all warnings are returned.
+   */
+  warningsLimit?: number,
+  /**
+   * Which warnings to return, applied after all warnings have been collected, including those
+   * generated during minification. Either a list of warning types to remove, e.g. `['AtRuleInvalid']`,
+   * or a function that is called with each warning and returns whether to keep it.
+   */
+  warningFilter?: string[] | ((warning: Warning) => boolean),
+  /**
+   * Whether to re-parse the output code and report any errors, which indicate a bug in the output.
+   * The errors are returned as `verificationErrors`, rather than thrown.
+   */
+  verifyOutput?: boolean,
+  /**
+   * Whether the code is an attribute value extracted from HTML as written, e.g. containing `&quot;`.
+   * When enabled, character references are decoded and raw tabs and newlines are replaced with spaces
+   * before parsing. Error and warning locations refer to the code as given.
+   */
+  decodeHtmlEntities?: boolean,
+  /**
+   * The location of the start of the attribute value within `filename`, e.g. an HTML document.
+   * Error and warning locations are reported relative to the file rather than the attribute.
+   */
+  sourceOffset?: Location
 }
 
 export interface TransformAttributeResult {
@@ -231,7 +838,11 @@ export interface TransformAttributeResult {
   /** `@import` and `url()` dependencies, if enabled. */
   dependencies: Dependency[] | void,
   /** Warnings that occurred during compilation. */
-  warnings: Warning[]
+  warnings: Warning[],
+  /** Whether warnings were dropped because `warningsLimit` was reached. */
+  warningsTruncated: boolean,
+  /** Errors found by re-parsing the output code, if `verifyOutput` is enabled. */
+  verificationErrors?: VerificationError[]
 }
 
 /**
@@ -239,6 +850,185 @@ export interface TransformAttributeResult {
  */
 export declare function transformStyleAttribute(options: TransformAttributeOptions): TransformAttributeResult;
 
+export interface TransformDeclarationOptions {
+  /** The name of the property, e.g. `user-select`. */
+  property: string,
+  /** The value of the property, optionally followed by `!important`. */
+  value: string,
+  /** The browser targets for the generated declarations. */
+  targets?: Targets,
+  /** Whether to enable minification. */
+  minify?: boolean
+}
+
+export interface TransformDeclarationResult {
+  /** The name of the property, including any vendor prefix. */
+  property: string,
+  /** The transformed value, without `!important`. */
+  value: string,
+  /** Whether the declaration is `!important`. */
+  important: boolean
+}
+
+/**
+ * Compiles a single CSS declaration, e.g. from a CSS-in-JS style object. Several declarations may be
+ * returned, e.g. when vendor prefixes or fallbacks are added for the targets. Invalid values throw an error.
+ */
+export declare function transformDeclaration(options: TransformDeclarationOptions): TransformDeclarationResult[];
+
+export interface TransformRuleOptions {
+  /** The filename being transformed. Used for error messages. */
+  filename?: string,
+  /** The source code, e.g. of a whole style sheet. */
+  code: Buffer,
+  /** The byte offset in `code` where the rule starts. */
+  ruleStartOffset: number,
+  /** The byte offset in `code` where the rule ends. */
+  ruleEndOffset: number,
+  /** The browser targets for the generated code. */
+  targets?: Targets,
+  /** Whether to enable minification. */
+  minify?: boolean,
+  /**
+   * Whether to ignore invalid rules and declarations rather than erroring.
+   * When enabled, warnings are returned, and the invalid rule or declaration is
+   * omitted from the output code. Declarations with an unterminated string, unmatched
+   * brackets, or an unclosed function are omitted without losing the declarations after them.
+   */
+  errorRecovery?: boolean
+}
+
+export interface TransformRuleResult {
+  /** The transformed rule. */
+  code: string,
+  /** Warnings that occurred during compilation. */
+  warnings: Warning[]
+}
+
+/**
+ * Compiles the single rule between the given offsets of the code, e.g. to re-compile a rule after it was
+ * edited without compiling the rest of the style sheet. Line numbers in errors and warnings are relative
+ * to the whole code.
+ */
+export declare function transformRule(options: TransformRuleOptions): TransformRuleResult;
+
+/**
+ * A serialized media query, mirroring the `MediaQuery` type in the `lightningcss` crate.
+ * Conditions, features, operators and values are objects of the form `{type, value}`.
+ */
+export interface MediaQueryAst {
+  qualifier: 'only' | 'not' | null,
+  media_type: { type: 'all' | 'print' | 'screen' } | { type: 'custom', value: string },
+  condition: { type: string, value: any } | null
+}
+
+export interface TransformMediaQueryResult {
+  /** The minified media query list. */
+  code: string,
+  /** The media queries in the transformed list. */
+  mediaQueries: MediaQueryAst[]
+}
+
+/**
+ * Parses a media query list, lowers syntax such as media query ranges and legacy resolution features
+ * for the given targets, and minifies it. Invalid queries throw an error.
+ */
+export declare function transformMediaQuery(query: string, targets?: Targets): TransformMediaQueryResult;
+
+export interface TransformFromAstOptions {
+  /** The style sheet AST to print, e.g. as returned by `transform()` with `returnAst` enabled. */
+  ast: StyleSheetAst,
+  /** Whether to enable minification. */
+  minify?: boolean,
+  /** Whether to output a source map. */
+  sourceMap?: boolean,
+  /** A URL to reference in a `/*# sourceMappingURL=... *\/` comment appended to the output code. */
+  sourceMapUrl?: string,
+  /** The browser targets for the generated code. */
+  targets?: Targets,
+  /** Whether to analyze dependencies (e.g. `@import` and `url()`). */
+  analyzeDependencies?: boolean | DependencyOptions,
+  /** Replaces user action pseudo classes with class names that can be applied from JavaScript. */
+  pseudoClasses?: PseudoClasses,
+  /**
+   * Whether to re-parse the output code and report any errors, which indicate a bug in the output.
+   * The errors are returned as `verificationErrors`, rather than thrown.
+   */
+  verifyOutput?: boolean
+}
+
+/**
+ * Prints a style sheet AST to CSS. The AST is validated first, and an error is thrown if it is malformed.
+ * It is printed as is, without minifying or lowering syntax beyond what printing for `targets` does.
+ */
+export declare function transformFromAst(options: TransformFromAstOptions): TransformResult;
+
+export interface SnapshotHashOptions {
+  /** The filename being parsed. Used for error messages. */
+  filename?: string,
+  /** Whether to enable various draft syntax. */
+  drafts?: Drafts
+}
+
+/**
+ * Returns a hash of the parsed AST of a style sheet, as a 16 character hex string.
+ * The hash is computed from a canonical serialization of the AST rather than the printed output,
+ * so it is not affected by comments, whitespace, or source locations, but changes whenever the
+ * parsed values do. Hashes are stable within a major version of lightningcss, so they can be used to
+ * check whether a style sheet parses identically across versions.
+ */
+export declare function snapshotHash(code: Buffer, options?: SnapshotHashOptions): string;
+
+export interface ExportsDiff {
+  /** Names that are only exported by `after`. */
+  added: string[],
+  /** Names that are only exported by `before`. */
+  removed: string[],
+  /** Names exported by both, with a different compiled name or composed names. */
+  changed: string[]
+}
+
+/**
+ * Compares the CSS module exports from two builds of a file, e.g. to decide whether JavaScript
+ * importers need to be rebuilt. Only the compiled names and composed names of each export are
+ * compared, so `isReferenced`, `specificity` and `selectors` are ignored. Each list of names is sorted.
+ */
+export declare function compareExports(before: CSSModuleExports, after: CSSModuleExports): ExportsDiff;
+
+export interface SelectorComponent {
+  /** The kind of component. */
+  type: 'combinator' | 'namespace' | 'universal' | 'type' | 'id' | 'class' | 'attribute' | 'pseudo-class' | 'pseudo-element' | 'nesting',
+  /**
+   * The serialized component, e.g. `.foo` or `:hover`. For combinators, one of
+   * `descendant`, `child`, `next-sibling` or `later-sibling`, or the non-standard
+   * `deep` (`>>>`) and `deep-descendant` (`/deep/`).
+   */
+  value: string
+}
+
+export interface ParsedSelector {
+  /** The compound selectors and combinators making up the selector, from left to right. */
+  components: SelectorComponent[],
+  /** The specificity of the selector, as `[a, b, c]`. */
+  specificity: [number, number, number]
+}
+
+export interface SerializeSelectorOptions {
+  /** Whether to minify the output. */
+  minify?: boolean
+}
+
+/**
+ * Parses a selector list into its components and the specificity of each selector.
+ * Errors report the line and column within the selector string.
+ */
+export declare function parseSelector(selector: string): ParsedSelector[];
+
+/**
+ * Serializes a selector, or a list of selectors, as returned by `parseSelector` back into a string.
+ */
+export declare function serializeSelector(selector: ParsedSelector | ParsedSelector[], options?: SerializeSelectorOptions): string;
+
 /**
  * Converts a browserslist result into targets that can be passed to lightningcss.
  * @param browserslist the result of calling `browserslist`
@@ -247,6 +1037,7 @@ export declare function browserslistToTargets(browserslist: string[]): Targets;
 
 /**
  * Bundles a CSS file and its dependencies, inlining @import rules.
+ * In WebAssembly builds, there is no file system, so a `resolver` with a `read` function is required.
  */
 export declare function bundle(options: BundleOptions): TransformResult;
 
@@ -254,3 +1045,18 @@ export declare function bundle(options: BundleOptions): TransformResult;
  * Bundles a CSS file and its dependencies asynchronously, inlining @import rules.
  */
 export declare function bundleAsync(options: BundleAsyncOptions): Promise<TransformResult>;
+
+export interface Bundler {
+  /**
+   * Bundles a CSS file and its dependencies, reusing files parsed by previous calls
+   * unless they were invalidated. Options that affect parsing clear the cache when changed.
+   */
+  bundle(options: BundleOptions): TransformResult,
+  /** Marks a resolved file path as changed, so it is read again by the next call to `bundle()`. */
+  invalidate(file: string): void
+}
+
+/**
+ * Creates a bundler which caches parsed files between calls, e.g. for rebuilding in watch mode.
+ */
+export declare function createBundler(): Bundler;
@@ -23,3 +23,11 @@ if (process.env.CSS_TRANSFORMER_WASM) {
 }
 
 module.exports.browserslistToTargets = require('./browserslistToTargets');
+module.exports.Features = {
+  Nesting: 1,
+  MediaRangeSyntax: 2,
+  CustomMediaQueries: 4,
+  HexAlphaColors: 8,
+  LogicalProperties: 16,
+  Scope: 32
+};
@@ -1,4 +1,4 @@
 import index from './index.js';
 
-const { transform, transformStyleAttribute, bundle, bundleAsync, browserslistToTargets } = index;
-export { transform, transformStyleAttribute, bundle, bundleAsync, browserslistToTargets };
+const { transform, validate, transformStyleAttribute, transformDeclaration, transformRule, transformMediaQuery, transformFromAst, snapshotHash, compareExports, parseSelector, serializeSelector, bundle, bundleAsync, createBundler, browserslistToTargets, Features } = index;
+export { transform, validate, transformStyleAttribute, transformDeclaration, transformRule, transformMediaQuery, transformFromAst, snapshotHash, compareExports, parseSelector, serializeSelector, bundle, bundleAsync, createBundler, browserslistToTargets, Features };
@@ -2,17 +2,36 @@
 #[global_allocator]
 static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
-use lightningcss::bundler::{BundleErrorKind, Bundler, FileProvider, SourceProvider};
-use lightningcss::css_modules::{CssModuleExports, CssModuleReferences, PatternParseError};
+use lightningcss::bundler::{BundleCache, BundleErrorKind, Bundler, FileProvider, SourceProvider};
+use lightningcss::css_modules::{
+  diff_exports, exports_hash, CssModuleExports, CssModuleReferences, NameGenerator, PatternParseError,
+};
+use lightningcss::declaration::DeclarationBlock;
 use lightningcss::dependencies::{Dependency, DependencyOptions};
-use lightningcss::error::{Error, ErrorLocation, MinifyErrorKind, ParserError, PrinterErrorKind};
+use lightningcss::error::{
+  Error, ErrorLocation, MinifyErrorKind, ParserError, PrinterError, PrinterErrorKind, Severity,
+};
+use lightningcss::media_query::MediaList;
+use lightningcss::properties::{Property, PropertyId};
+use lightningcss::rules::custom_at_rule::{
+  CustomAtRuleBodySyntax, CustomAtRuleDefinition, CustomAtRulePreludeSyntax,
+};
+use lightningcss::rules::style::{
+  parse_selectors, selector_components, selector_from_components, selectors_to_css_string, specificity,
+  SelectorComponent, StyleRule,
+};
+use lightningcss::rules::{CssRule, CssRuleList};
 use lightningcss::stylesheet::{
-  MinifyOptions, ParserOptions, PrinterOptions, PseudoClasses, StyleAttribute, StyleSheet,
+  ColorFormat, Comments, InlineCustomProperties, MinifyOptions, NestingSpec, ParserOptions, Precision,
+  PrinterOptions, PseudoClasses, QuoteStyle, StyleAttribute, StyleSheet, ToCssResult,
 };
-use lightningcss::targets::Browsers;
+use lightningcss::targets::{Browsers, Features};
+use lightningcss::traits::ToCss;
+use lightningcss::vendor_prefix::VendorPrefix;
 use parcel_sourcemap::SourceMap;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::ffi::c_void;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -28,12 +47,75 @@ use serde_wasm_bindgen::{from_value, Serializer};
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+// Deserializes an options object, returning a TypeError if it is invalid.
+#[cfg(target_arch = "wasm32")]
+fn options<T: serde::de::DeserializeOwned>(val: JsValue) -> Result<T, JsValue> {
+  from_value(val).map_err(|err| js_sys::TypeError::new(&err.to_string()).into())
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub fn transform(config_val: JsValue) -> Result<JsValue, JsValue> {
-  let config: Config = from_value(config_val).map_err(JsValue::from)?;
-  let code = unsafe { std::str::from_utf8_unchecked(&config.code) };
-  let res = compile(code, &config)?;
+  let config: Config = options(config_val.clone())?;
+  let code = decode_code(&config.code, config.encoding)?;
+  let res = compile::<FileProvider>(&code, &config, None, None).map_err(|e| e.into_js_error(Some(&code)))?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  filter_warnings(&config_val, res.serialize(&serializer)?)
+}
+
+// Applies the `warningFilter` option to the `warnings` of a result. It is either a list of warning
+// types to remove, or a function that is called with each warning and returns whether to keep it.
+#[cfg(target_arch = "wasm32")]
+fn filter_warnings(config_val: &JsValue, result: JsValue) -> Result<JsValue, JsValue> {
+  let filter = js_sys::Reflect::get(config_val, &"warningFilter".into())?;
+  if filter.is_undefined() || filter.is_null() {
+    return Ok(result);
+  }
+
+  let types: HashSet<String> = if filter.is_function() {
+    HashSet::new()
+  } else {
+    options(filter.clone())?
+  };
+  let warnings = js_sys::Array::from(&js_sys::Reflect::get(&result, &"warnings".into())?);
+  let filtered = js_sys::Array::new();
+  for warning in warnings.iter() {
+    let keep = match filter.dyn_ref::<js_sys::Function>() {
+      Some(function) => function.call1(&JsValue::NULL, &warning)?.is_truthy(),
+      None => {
+        let kind = js_sys::Reflect::get(&warning, &"type".into())?.as_string();
+        !kind.map_or(false, |kind| types.contains(&kind))
+      }
+    };
+    if keep {
+      filtered.push(&warning);
+    }
+  }
+
+  js_sys::Reflect::set(&result, &"warnings".into(), &filtered)?;
+  Ok(result)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn validate(config_val: JsValue) -> Result<JsValue, JsValue> {
+  let config: Config = options(config_val.clone())?;
+  let code = decode_code(&config.code, config.encoding)?;
+  let res = validate_code(&code, &config).map_err(|e| e.into_js_error(Some(&code)))?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  filter_warnings(&config_val, res.serialize(&serializer)?)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "transformForBundler")]
+pub fn transform_for_bundler(config_val: JsValue) -> Result<JsValue, JsValue> {
+  let config: Config = options(config_val)?;
+  let code = decode_code(&config.code, config.encoding)?;
+  let res = BundlerResult::new(
+    compile::<FileProvider>(&code, &config, None, None),
+    Some(&*code),
+    &config,
+  );
   let serializer = Serializer::new().serialize_maps_as_objects(true);
   res.serialize(&serializer).map_err(JsValue::from)
 }
@@ -41,13 +123,226 @@ pub fn transform(config_val: JsValue) -> Result<JsValue, JsValue> {
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(js_name = "transformStyleAttribute")]
 pub fn transform_style_attribute(config_val: JsValue) -> Result<JsValue, JsValue> {
-  let config: AttrConfig = from_value(config_val).map_err(JsValue::from)?;
-  let code = unsafe { std::str::from_utf8_unchecked(&config.code) };
-  let res = compile_attr(code, &config)?;
+  let config: AttrConfig = options(config_val.clone())?;
+  let code = decode_code(&config.code, config.encoding)?;
+  let decoded = config.decode_html_entities.then(|| DecodedAttr::new(&code));
+  let res = compile_attr(&code, &config, decoded.as_ref()).map_err(|e| e.into_js_error(Some(&code)))?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  filter_warnings(&config_val, res.serialize(&serializer)?)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "transformDeclaration")]
+pub fn transform_declaration(config_val: JsValue) -> Result<JsValue, JsValue> {
+  let config: DeclarationConfig = options(config_val)?;
+  let res = compile_declaration(&config)?;
   let serializer = Serializer::new().serialize_maps_as_objects(true);
   res.serialize(&serializer).map_err(JsValue::from)
 }
 
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "transformRule")]
+pub fn transform_rule(config_val: JsValue) -> Result<JsValue, JsValue> {
+  let config: RuleConfig = options(config_val)?;
+  let code = utf8_code(&config.code)?;
+  let res = compile_rule(code, &config).map_err(|e| e.into_js_error(Some(code)))?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  res.serialize(&serializer).map_err(JsValue::from)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "transformMediaQuery")]
+pub fn transform_media_query(query: &str, targets: JsValue) -> Result<JsValue, JsValue> {
+  let targets: MediaQueryTargets = if targets.is_undefined() {
+    MediaQueryTargets(None)
+  } else {
+    from_value(targets).map_err(JsValue::from)?
+  };
+  let res = compile_media_query(query, targets.0).map_err(|e| e.into_js_error(Some(query)))?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  res.serialize(&serializer).map_err(JsValue::from)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "transformFromAst")]
+pub fn transform_from_ast(config_val: JsValue) -> Result<JsValue, JsValue> {
+  let config: AstConfig = options(config_val)?;
+  let res = compile_ast(&config)?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  res.serialize(&serializer).map_err(JsValue::from)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "snapshotHash")]
+pub fn snapshot_hash(code: &[u8], config_val: JsValue) -> Result<JsValue, JsValue> {
+  let config: SnapshotHashConfig = if config_val.is_undefined() {
+    SnapshotHashConfig::default()
+  } else {
+    from_value(config_val).map_err(JsValue::from)?
+  };
+  let code = utf8_code(code)?;
+  let hash = snapshot_hash_code(code, &config)?;
+  Ok(JsValue::from_str(&hash))
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "parseSelector")]
+pub fn parse_selector(selector: &str) -> Result<JsValue, JsValue> {
+  let res = parse_selector_code(selector)?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  res.serialize(&serializer).map_err(JsValue::from)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "serializeSelector")]
+pub fn serialize_selector(selector: JsValue, config_val: JsValue) -> Result<JsValue, JsValue> {
+  let selector: SelectorInput = from_value(selector).map_err(JsValue::from)?;
+  let config: SerializeSelectorConfig = if config_val.is_undefined() {
+    SerializeSelectorConfig::default()
+  } else {
+    options(config_val)?
+  };
+  let code = selector.to_code();
+  let res = serialize_selector_code(&code, &config)?;
+  Ok(JsValue::from_str(&res))
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(js_name = "compareExports")]
+pub fn compare_exports(before: JsValue, after: JsValue) -> Result<JsValue, JsValue> {
+  let before: CssModuleExports = from_value(before).map_err(JsValue::from)?;
+  let after: CssModuleExports = from_value(after).map_err(JsValue::from)?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  diff_exports(&before, &after).serialize(&serializer).map_err(JsValue::from)
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn bundle(config_val: JsValue) -> Result<JsValue, JsValue> {
+  let config: BundleConfig = options(config_val.clone())?;
+  let resolver = js_sys::Reflect::get(&config_val, &"resolver".into())?;
+  let function = |name: &str| -> Result<Option<js_sys::Function>, JsValue> {
+    if resolver.is_undefined() || resolver.is_null() {
+      return Ok(None);
+    }
+    let value = js_sys::Reflect::get(&resolver, &name.into())?;
+    if value.is_undefined() {
+      return Ok(None);
+    }
+    value
+      .dyn_into::<js_sys::Function>()
+      .map(Some)
+      .map_err(|_| js_sys::Error::new(&format!("resolver.{} must be a function", name)).into())
+  };
+
+  // There is no file system in WebAssembly, so files can only be read using the resolver.
+  let read = match function("read")? {
+    Some(read) => read,
+    None => {
+      return Err(js_sys::Error::new("bundle() in WebAssembly requires a resolver with a `read` function").into())
+    }
+  };
+  let provider = WasmSourceProvider {
+    read,
+    resolve: function("resolve")?,
+    inputs: Mutex::new(Vec::new()),
+  };
+
+  let res = compile_bundle(&provider, &config, None, None)?;
+  let serializer = Serializer::new().serialize_maps_as_objects(true);
+  filter_warnings(&config_val, res.serialize(&serializer)?)
+}
+
+/// A SourceProvider which calls synchronous JavaScript functions to resolve and read files.
+#[cfg(target_arch = "wasm32")]
+struct WasmSourceProvider {
+  read: js_sys::Function,
+  resolve: Option<js_sys::Function>,
+  // The sources returned by `read()`, which are borrowed by the parsed style sheets. They are
+  // boxed so their addresses are stable, and only freed when the provider is dropped.
+  inputs: Mutex<Vec<Box<str>>>,
+}
+
+// SAFETY: WebAssembly has no threads, and the bundler does not use rayon when compiled to WebAssembly,
+// so the JS functions are only ever called from the thread that created them.
+#[cfg(target_arch = "wasm32")]
+unsafe impl Send for WasmSourceProvider {}
+#[cfg(target_arch = "wasm32")]
+unsafe impl Sync for WasmSourceProvider {}
+
+/// An error thrown by, or an invalid value returned from, a resolver function.
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug)]
+struct ResolverError(String);
+
+#[cfg(target_arch = "wasm32")]
+impl std::fmt::Display for ResolverError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl std::error::Error for ResolverError {}
+
+#[cfg(target_arch = "wasm32")]
+impl WasmSourceProvider {
+  fn call(f: &js_sys::Function, args: &[&str]) -> Result<String, ResolverError> {
+    let args: js_sys::Array = args.iter().map(|arg| JsValue::from_str(arg)).collect();
+    let result = f.apply(&JsValue::NULL, &args).map_err(|err| {
+      let message = match err.dyn_ref::<js_sys::Error>() {
+        Some(err) => String::from(err.message()),
+        None => err.as_string().unwrap_or_else(|| format!("{:?}", err)),
+      };
+      ResolverError(message)
+    })?;
+
+    if result.is_instance_of::<js_sys::Promise>() {
+      return Err(ResolverError(
+        "Resolver functions passed to `bundle()` must be synchronous.".into(),
+      ));
+    }
+
+    result
+      .as_string()
+      .ok_or_else(|| ResolverError("Resolver functions must return a string.".into()))
+  }
+
+  fn path(path: &Path) -> Result<&str, ResolverError> {
+    path
+      .to_str()
+      .ok_or_else(|| ResolverError(format!("{} is not a valid UTF-8 path.", path.display())))
+  }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl SourceProvider for WasmSourceProvider {
+  type Error = ResolverError;
+
+  fn read<'a>(&'a self, file: &Path) -> Result<&'a str, Self::Error> {
+    let mut source = Self::call(&self.read, &[Self::path(file)?])?;
+    if source.starts_with('\u{feff}') {
+      source.drain(..'\u{feff}'.len_utf8());
+    }
+    let source = source.into_boxed_str();
+    let ptr: *const str = &*source;
+    self.inputs.lock().unwrap().push(source);
+    // SAFETY: the source is boxed, so its address is stable, and it is never removed
+    // from the list until the provider is dropped.
+    Ok(unsafe { &*ptr })
+  }
+
+  fn resolve(&self, specifier: &str, originating_file: &Path) -> Result<PathBuf, Self::Error> {
+    match &self.resolve {
+      Some(resolve) => Ok(PathBuf::from(Self::call(
+        resolve,
+        &[specifier, Self::path(originating_file)?],
+      )?)),
+      None => Ok(originating_file.with_file_name(specifier)),
+    }
+  }
+}
+
 // ---------------------------------------------
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -62,10 +357,41 @@ struct TransformResult<'i> {
   code: Vec<u8>,
   #[serde(with = "serde_bytes")]
   map: Option<Vec<u8>>,
+  #[serde(with = "serde_bytes")]
+  code_legacy: Option<Vec<u8>>,
+  #[serde(with = "serde_bytes")]
+  map_legacy: Option<Vec<u8>>,
   exports: Option<CssModuleExports>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  exports_hash: Option<String>,
+  animations: Option<HashMap<String, String>>,
   references: Option<CssModuleReferences>,
   dependencies: Option<Vec<Dependency>>,
+  renamed_keyframes: HashMap<String, String>,
+  inlined_custom_properties: Vec<String>,
+  removed_symbols: Vec<String>,
+  unmatched_symbols: Vec<String>,
+  features_used: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  license_comments: Option<Vec<String>>,
   warnings: Vec<Warning<'i>>,
+  warnings_truncated: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  verification_errors: Option<Vec<VerificationError>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  ast: Option<serde_json::Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  chunks: Option<Vec<LayerChunkResult>>,
+}
+
+/// A chunk of a bundle split by `splitByLayer`.
+#[derive(Serialize)]
+struct LayerChunkResult {
+  layer: Option<String>,
+  #[serde(with = "serde_bytes")]
+  code: Vec<u8>,
+  #[serde(with = "serde_bytes")]
+  map: Option<Vec<u8>>,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -85,733 +411,3431 @@ impl<'i> TransformResult<'i> {
         env.get_null()?.into_unknown()
       },
     )?;
+    if let Some(code_legacy) = self.code_legacy {
+      let buf = env.create_buffer_with_data(code_legacy)?;
+      obj.set_named_property("codeLegacy", buf.into_raw())?;
+      obj.set_named_property(
+        "mapLegacy",
+        if let Some(map) = self.map_legacy {
+          let buf = env.create_buffer_with_data(map)?;
+          buf.into_raw().into_unknown()
+        } else {
+          env.get_null()?.into_unknown()
+        },
+      )?;
+    }
     obj.set_named_property("exports", env.to_js_value(&self.exports)?)?;
+    if let Some(exports_hash) = self.exports_hash {
+      obj.set_named_property("exportsHash", env.create_string(&exports_hash)?)?;
+    }
+    obj.set_named_property("animations", env.to_js_value(&self.animations)?)?;
     obj.set_named_property("references", env.to_js_value(&self.references)?)?;
     obj.set_named_property("dependencies", env.to_js_value(&self.dependencies)?)?;
+    obj.set_named_property("renamedKeyframes", env.to_js_value(&self.renamed_keyframes)?)?;
+    obj.set_named_property(
+      "inlinedCustomProperties",
+      env.to_js_value(&self.inlined_custom_properties)?,
+    )?;
+    obj.set_named_property("removedSymbols", env.to_js_value(&self.removed_symbols)?)?;
+    obj.set_named_property("unmatchedSymbols", env.to_js_value(&self.unmatched_symbols)?)?;
+    obj.set_named_property("featuresUsed", env.to_js_value(&self.features_used)?)?;
     obj.set_named_property("warnings", env.to_js_value(&self.warnings)?)?;
+    obj.set_named_property("warningsTruncated", env.get_boolean(self.warnings_truncated)?)?;
+    if let Some(verification_errors) = self.verification_errors {
+      obj.set_named_property("verificationErrors", env.to_js_value(&verification_errors)?)?;
+    }
+    if let Some(license_comments) = self.license_comments {
+      obj.set_named_property("licenseComments", env.to_js_value(&license_comments)?)?;
+    }
+    if let Some(ast) = self.ast {
+      obj.set_named_property("ast", env.to_js_value(&ast)?)?;
+    }
+    if let Some(chunks) = self.chunks {
+      let mut arr = env.create_array_with_length(chunks.len())?;
+      for (i, chunk) in chunks.into_iter().enumerate() {
+        let mut chunk_obj = env.create_object()?;
+        chunk_obj.set_named_property("layer", env.to_js_value(&chunk.layer)?)?;
+        let buf = env.create_buffer_with_data(chunk.code)?;
+        chunk_obj.set_named_property("code", buf.into_raw())?;
+        chunk_obj.set_named_property(
+          "map",
+          if let Some(map) = chunk.map {
+            let buf = env.create_buffer_with_data(map)?;
+            buf.into_raw().into_unknown()
+          } else {
+            env.get_null()?.into_unknown()
+          },
+        )?;
+        arr.set_element(i as u32, chunk_obj)?;
+      }
+      obj.set_named_property("chunks", arr)?;
+    }
     Ok(obj.into_unknown())
   }
 }
 
+/// The result of `transformForBundler()`, in a shape that does not depend on a particular bundler.
+/// Errors are returned as diagnostics rather than thrown.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BundlerResult {
+  code: String,
+  map: Option<String>,
+  exports: Option<CssModuleExports>,
+  dependencies: Vec<Dependency>,
+  diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DiagnosticSeverity {
+  Error,
+  Warning,
+}
+
+/// A warning or error. Lines and columns are 1-based, and columns are counted in UTF-16 code units,
+/// as in JavaScript strings. The end position is exclusive, and covers the token at the start position.
+#[derive(Serialize)]
+struct Diagnostic {
+  severity: DiagnosticSeverity,
+  message: String,
+  code: Option<&'static str>,
+  file: Option<String>,
+  start: Option<Position>,
+  end: Option<Position>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+struct Position {
+  line: u32,
+  column: u32,
+}
+
+impl BundlerResult {
+  fn new<'i, E: std::error::Error>(
+    res: Result<TransformResult<'i>, CompileError<'i, E>>,
+    code: Option<&str>,
+    config: &Config,
+  ) -> BundlerResult {
+    let filename = config.filename.as_deref().unwrap_or_default();
+    // Ends can only be found for locations in the given code.
+    let diagnostic = |severity, message, error_code: Option<&'static str>, loc: Option<ErrorLocation>| {
+      let start = loc.as_ref().map(|loc| Position {
+        line: loc.line,
+        column: loc.column,
+      });
+      let end = match (code, &loc, start) {
+        (Some(code), Some(loc), Some(start)) if loc.filename == filename => Some(token_end(code, start)),
+        _ => None,
+      };
+      Diagnostic {
+        severity,
+        message,
+        code: error_code,
+        file: loc.map(|loc| loc.filename),
+        start,
+        end,
+      }
+    };
+
+    match res {
+      Ok(res) => BundlerResult {
+        code: String::from_utf8(res.code).unwrap_or_default(),
+        map: res.map.and_then(|map| String::from_utf8(map).ok()),
+        exports: res.exports,
+        dependencies: res.dependencies.unwrap_or_default(),
+        diagnostics: res
+          .warnings
+          .into_iter()
+          .map(|w| diagnostic(DiagnosticSeverity::Warning, w.message, Some(w.code), w.loc))
+          .collect(),
+      },
+      Err(mut err) => {
+        let message = err.to_string();
+        let error_code = err.code();
+        // Convert to 1-based line numbers.
+        let loc = err.loc_mut().map(|loc| ErrorLocation {
+          filename: loc.filename.clone(),
+          line: loc.line + 1,
+          column: loc.column,
+        });
+        BundlerResult {
+          code: String::new(),
+          map: None,
+          exports: None,
+          dependencies: Vec::new(),
+          diagnostics: vec![diagnostic(DiagnosticSeverity::Error, message, error_code, loc)],
+        }
+      }
+    }
+  }
+}
+
+/// Returns the position after the token at the given position in the code.
+fn token_end(code: &str, start: Position) -> Position {
+  let offset = location_to_offset(code, start.line.saturating_sub(1), start.column);
+  let mut input = cssparser::ParserInput::new(&code[offset..]);
+  let mut parser = cssparser::Parser::new(&mut input);
+  let len = match parser.next_including_whitespace_and_comments() {
+    Ok(_) => parser.position().byte_index(),
+    Err(_) => 0,
+  };
+  let (line, column) = offset_to_location(code, offset + len);
+  Position { line: line + 1, column }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 #[js_function(1)]
 fn transform(ctx: CallContext) -> napi::Result<JsUnknown> {
   let opts = ctx.get::<JsObject>(0)?;
-  let config: Config = ctx.env.from_js_value(opts)?;
-  let code = unsafe { std::str::from_utf8_unchecked(&config.code) };
-  let res = compile(code, &config);
+  check_sync_css_modules(&opts)?;
+  let config: Config = options(ctx.env, &opts)?;
+  let code = match decode_code(&config.code, config.encoding) {
+    Ok(code) => code,
+    Err(err) => return err.throw(*ctx.env, None),
+  };
+  let mut visitor = JsVisitor::new(*ctx.env, &opts)?;
+  if config.inline_imports.unwrap_or_default() {
+    if visitor.is_some() {
+      return Err(napi::Error::from_reason(
+        "`visitor` is not supported with `inlineImports`.",
+      ));
+    }
+    let res = bundle::transform_inline_imports(*ctx.env, &opts, &code, &config, false)?;
+    return filter_warnings(*ctx.env, &opts, res);
+  }
+
+  let res = compile::<FileProvider>(
+    &code,
+    &config,
+    None,
+    visitor.as_mut().map(|visitor| visitor as &mut dyn Visitor),
+  );
+  if let Some(exception) = visitor.and_then(|visitor| visitor.exception) {
+    return Err(exception);
+  }
 
   match res {
-    Ok(res) => res.into_js(*ctx.env),
-    Err(err) => err.throw(*ctx.env, Some(code)),
+    Ok(res) => filter_warnings(*ctx.env, &opts, res.into_js(*ctx.env)?),
+    Err(err) => err.throw(*ctx.env, Some(&*code)),
   }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[js_function(1)]
-fn transform_style_attribute(ctx: CallContext) -> napi::Result<JsUnknown> {
+fn validate(ctx: CallContext) -> napi::Result<JsUnknown> {
   let opts = ctx.get::<JsObject>(0)?;
-  let config: AttrConfig = ctx.env.from_js_value(opts)?;
-  let code = unsafe { std::str::from_utf8_unchecked(&config.code) };
-  let res = compile_attr(code, &config);
+  let config: Config = options(ctx.env, &opts)?;
+  let code = match decode_code(&config.code, config.encoding) {
+    Ok(code) => code,
+    Err(err) => return err.throw(*ctx.env, None),
+  };
+  let res = validate_code(&code, &config);
 
   match res {
-    Ok(res) => res.into_js(ctx),
-    Err(err) => err.throw(*ctx.env, Some(code)),
+    Ok(res) => filter_warnings(*ctx.env, &opts, ctx.env.to_js_value(&res)?),
+    Err(err) => err.throw(*ctx.env, Some(&*code)),
   }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-mod bundle {
-  use super::*;
-  use crossbeam_channel::{self, Receiver, Sender};
-  use napi::{Env, JsFunction, JsString, NapiRaw, NapiValue};
-  use threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+#[js_function(1)]
+fn transform_for_bundler(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let opts = ctx.get::<JsObject>(0)?;
+  check_sync_css_modules(&opts)?;
+  let config: Config = options(ctx.env, &opts)?;
+  let code = match decode_code(&config.code, config.encoding) {
+    Ok(code) => code,
+    Err(err) => return err.throw(*ctx.env, None),
+  };
+  if config.inline_imports.unwrap_or_default() {
+    return bundle::transform_inline_imports(*ctx.env, &opts, &code, &config, true);
+  }
 
-  #[js_function(1)]
-  pub fn bundle(ctx: CallContext) -> napi::Result<JsUnknown> {
-    let opts = ctx.get::<JsObject>(0)?;
-    let config: BundleConfig = ctx.env.from_js_value(opts)?;
-    let fs = FileProvider::new();
-    let res = compile_bundle(&fs, &config);
+  let res = compile::<FileProvider>(&code, &config, None, None);
+  ctx.env.to_js_value(&BundlerResult::new(res, Some(&*code), &config))
+}
 
-    match res {
-      Ok(res) => res.into_js(*ctx.env),
-      Err(err) => {
-        let code = match &err {
-          CompileError::ParseError(Error {
-            loc: Some(ErrorLocation { filename, .. }),
-            ..
-          })
-          | CompileError::PrinterError(Error {
-            loc: Some(ErrorLocation { filename, .. }),
-            ..
-          })
-          | CompileError::MinifyError(Error {
-            loc: Some(ErrorLocation { filename, .. }),
-            ..
-          })
-          | CompileError::BundleError(Error {
-            loc: Some(ErrorLocation { filename, .. }),
-            ..
-          }) => Some(fs.read(Path::new(filename))?),
-          _ => None,
-        };
-        err.throw(*ctx.env, code)
+// Deserializes an options object, throwing a TypeError if it is invalid.
+#[cfg(not(target_arch = "wasm32"))]
+fn options<T: serde::de::DeserializeOwned>(env: &Env, opts: &JsObject) -> napi::Result<T> {
+  env
+    .from_js_value(opts)
+    .map_err(|err| match env.throw_type_error(&err.reason, None) {
+      Ok(()) => napi::Error::new(napi::Status::PendingException, err.reason),
+      Err(err) => err,
+    })
+}
+
+// Applies the `warningFilter` option to the `warnings` of a result. It is either a list of warning
+// types to remove, or a function that is called with each warning and returns whether to keep it.
+#[cfg(not(target_arch = "wasm32"))]
+fn filter_warnings(env: Env, opts: &JsObject, result: JsUnknown) -> napi::Result<JsUnknown> {
+  // When an error was thrown, the result is undefined.
+  if result.get_type()? != napi::ValueType::Object || !opts.has_named_property("warningFilter")? {
+    return Ok(result);
+  }
+
+  let filter = opts.get_named_property::<JsUnknown>("warningFilter")?;
+  let (function, types) = match filter.get_type()? {
+    napi::ValueType::Undefined | napi::ValueType::Null => return Ok(result),
+    napi::ValueType::Function => (Some(unsafe { filter.cast::<napi::JsFunction>() }), HashSet::new()),
+    _ => (None, env.from_js_value::<HashSet<String>, _>(filter)?),
+  };
+
+  let mut result = unsafe { result.cast::<JsObject>() };
+  let warnings = result.get_named_property::<JsObject>("warnings")?;
+  let mut filtered = env.create_array_with_length(0)?;
+  let mut len = 0;
+  for i in 0..warnings.get_array_length()? {
+    let warning = warnings.get_element::<JsObject>(i)?;
+    let keep = match &function {
+      Some(function) => function.call(None, &[warning])?.coerce_to_bool()?.get_value()?,
+      None => {
+        let kind = warning.get_named_property::<napi::JsString>("type")?.into_utf8()?;
+        !types.contains(kind.as_str()?)
       }
+    };
+    if keep {
+      filtered.set_element(len, warnings.get_element::<JsObject>(i)?)?;
+      len += 1;
     }
   }
 
-  // A SourceProvider which calls JavaScript functions to resolve and read files.
-  struct JsSourceProvider {
-    resolve: Option<ThreadsafeFunction<ResolveMessage>>,
-    read: Option<ThreadsafeFunction<ReadMessage>>,
-    inputs: Mutex<Vec<*mut String>>,
+  result.set_named_property("warnings", filtered)?;
+  Ok(result.into_unknown())
+}
+
+// Returns the `cssModules.generateName` function, if any.
+#[cfg(not(target_arch = "wasm32"))]
+fn generate_name_function(opts: &JsObject) -> napi::Result<Option<napi::JsFunction>> {
+  if let Ok(css_modules) = opts.get_named_property::<JsObject>("cssModules") {
+    if css_modules.has_named_property("generateName")? {
+      return Ok(Some(css_modules.get_named_property("generateName")?));
+    }
   }
 
-  unsafe impl Sync for JsSourceProvider {}
-  unsafe impl Send for JsSourceProvider {}
+  Ok(None)
+}
 
-  // Allocate a single channel per thread to communicate with the JS thread.
-  thread_local! {
-    static CHANNEL: (Sender<napi::Result<String>>, Receiver<napi::Result<String>>) = crossbeam_channel::unbounded();
+// Calling `generateName` requires the JS thread to be free while compiling,
+// which is only the case in `bundleAsync()`.
+#[cfg(not(target_arch = "wasm32"))]
+fn check_sync_css_modules(opts: &JsObject) -> napi::Result<()> {
+  if generate_name_function(opts)?.is_some() {
+    return Err(napi::Error::from_reason(
+      "`cssModules.generateName` is only supported by `bundleAsync()`.",
+    ));
   }
 
-  impl SourceProvider for JsSourceProvider {
-    type Error = napi::Error;
+  Ok(())
+}
 
-    fn read<'a>(&'a self, file: &Path) -> Result<&'a str, Self::Error> {
-      let source = if let Some(read) = &self.read {
-        CHANNEL.with(|channel| {
-          let message = ReadMessage {
-            file: file.to_str().unwrap().to_owned(),
-            tx: channel.0.clone(),
-          };
+/// The `visitor` option of `transform()`. Its functions are called with the rules and
+/// declarations that match its filter, which is evaluated before converting them to JS.
+#[cfg(not(target_arch = "wasm32"))]
+struct JsVisitor {
+  env: Env,
+  rule: Option<napi::JsFunction>,
+  declaration: Option<napi::JsFunction>,
+  filter: VisitorFilter,
+  /// The exception thrown by a visitor function, which is rethrown rather than the error it caused.
+  exception: Option<napi::Error>,
+}
 
-          read.call(message, ThreadsafeFunctionCallMode::Blocking);
-          channel.1.recv().unwrap()
-        })
-      } else {
-        Ok(std::fs::read_to_string(file)?)
-      };
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize, Default)]
+struct VisitorFilter {
+  rules: Option<RuleFilter>,
+  declarations: Option<DeclarationFilter>,
+}
 
-      match source {
-        Ok(source) => {
-          // cache the result
-          let ptr = Box::into_raw(Box::new(source));
-          self.inputs.lock().unwrap().push(ptr);
-          // SAFETY: this is safe because the pointer is not dropped
-          // until the JsSourceProvider is, and we never remove from the
-          // list of pointers stored in the vector.
-          Ok(unsafe { &*ptr })
-        }
-        Err(e) => Err(e),
-      }
-    }
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuleFilter {
+  /// Only visit style rules with a selector list containing this string, as printed without minification.
+  selector_contains: Option<String>,
+}
 
-    fn resolve(&self, specifier: &str, originating_file: &Path) -> Result<PathBuf, Self::Error> {
-      if let Some(resolve) = &self.resolve {
-        return CHANNEL.with(|channel| {
-          let message = ResolveMessage {
-            specifier: specifier.to_owned(),
-            originating_file: originating_file.to_str().unwrap().to_owned(),
-            tx: channel.0.clone(),
-          };
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize)]
+struct DeclarationFilter {
+  /// Only visit declarations of these properties, by their unprefixed name.
+  properties: Option<HashSet<String>>,
+}
 
-          resolve.call(message, ThreadsafeFunctionCallMode::Blocking);
-          let result = channel.1.recv().unwrap();
-          match result {
-            Ok(result) => Ok(PathBuf::from_str(&result).unwrap()),
-            Err(e) => Err(e),
-          }
-        });
+#[cfg(not(target_arch = "wasm32"))]
+impl JsVisitor {
+  fn new(env: Env, opts: &JsObject) -> napi::Result<Option<JsVisitor>> {
+    let visitor = match opts.get_named_property::<JsObject>("visitor") {
+      Ok(visitor) => visitor,
+      Err(_) => return Ok(None),
+    };
+
+    let function = |name: &str| -> napi::Result<Option<napi::JsFunction>> {
+      let function = visitor.get_named_property::<JsUnknown>(name)?;
+      match function.get_type()? {
+        napi::ValueType::Undefined | napi::ValueType::Null => Ok(None),
+        _ => Ok(Some(function.try_into()?)),
       }
+    };
 
-      Ok(originating_file.with_file_name(specifier))
-    }
-  }
+    let filter = match visitor.get_named_property::<JsObject>("filter") {
+      Ok(filter) => options(&env, &filter)?,
+      Err(_) => VisitorFilter::default(),
+    };
 
-  struct ResolveMessage {
-    specifier: String,
-    originating_file: String,
-    tx: Sender<napi::Result<String>>,
+    Ok(Some(JsVisitor {
+      env,
+      rule: function("Rule")?,
+      declaration: function("Declaration")?,
+      filter,
+      exception: None,
+    }))
   }
 
-  struct ReadMessage {
-    file: String,
-    tx: Sender<napi::Result<String>>,
-  }
+  // Calls a visitor function with a node, and deserializes the node it returns, if any.
+  fn call<'i, T: Serialize + Deserialize<'i>>(
+    &mut self,
+    function: &napi::JsFunction,
+    node: &T,
+    kind: &str,
+  ) -> Result<Option<T>, String> {
+    let value = serde_json::to_value(node).map_err(|e| e.to_string())?;
+    let result = self
+      .env
+      .to_js_value(&value)
+      .and_then(|value| function.call(None, &[value]))
+      .and_then(|result| match result.get_type()? {
+        napi::ValueType::Undefined | napi::ValueType::Null => Ok(None),
+        _ => Ok(Some(self.env.from_js_value::<serde_json::Value, _>(result)?)),
+      });
 
-  fn await_promise(env: Env, result: JsUnknown, tx: Sender<napi::Result<String>>) -> napi::Result<()> {
-    // If the result is a promise, wait for it to resolve, and send the result to the channel.
-    // Otherwise, send the result immediately.
-    if result.is_promise()? {
-      let result: JsObject = result.try_into()?;
-      let then: JsFunction = result.get_named_property("then")?;
-      let tx2 = tx.clone();
-      let cb = env.create_function_from_closure("callback", move |ctx| {
-        let res = ctx.get::<JsString>(0)?.into_utf8()?;
-        let s = res.into_owned()?;
-        tx.send(Ok(s)).unwrap();
-        ctx.env.get_undefined()
-      })?;
-      let eb = env.create_function_from_closure("error_callback", move |ctx| {
-        // TODO: need a way to convert a JsUnknown to an Error
-        tx2.send(Err(napi::Error::from_reason("Promise rejected"))).unwrap();
-        ctx.env.get_undefined()
-      })?;
-      then.call(Some(&result), &[cb, eb])?;
-    } else {
-      let result: JsString = result.try_into()?;
-      let utf8 = result.into_utf8()?;
-      let s = utf8.into_owned()?;
-      tx.send(Ok(s)).unwrap();
+    match result {
+      Ok(Some(value)) => T::deserialize(value)
+        .map(Some)
+        .map_err(|e| format!("Invalid {} returned by the visitor: {}", kind, e)),
+      Ok(None) => Ok(None),
+      Err(err) => {
+        let message = err.reason.clone();
+        self.exception = Some(err);
+        Err(message)
+      }
     }
+  }
+}
 
-    Ok(())
+#[cfg(not(target_arch = "wasm32"))]
+impl<'i> Visitor<'i> for JsVisitor {
+  fn visits_rule(&self, rule: &CssRule<'i>) -> bool {
+    let selector_contains = match &self.filter.rules {
+      _ if self.rule.is_none() => return false,
+      Some(RuleFilter {
+        selector_contains: Some(selector_contains),
+      }) => selector_contains,
+      _ => return true,
+    };
+
+    let selectors = match rule {
+      CssRule::Style(style) => &style.selectors,
+      CssRule::Nesting(nesting) => &nesting.style.selectors,
+      _ => return false,
+    };
+    selectors_to_css_string(selectors, PrinterOptions::default())
+      .map_or(false, |selectors| selectors.contains(selector_contains.as_str()))
   }
 
-  fn resolve_on_js_thread(ctx: ThreadSafeCallContext<ResolveMessage>) -> napi::Result<()> {
-    let specifier = ctx.env.create_string(&ctx.value.specifier)?;
-    let originating_file = ctx.env.create_string(&ctx.value.originating_file)?;
-    let result = ctx.callback.call(None, &[specifier, originating_file])?;
-    await_promise(ctx.env, result, ctx.value.tx)
+  fn visit_rule(&mut self, rule: &CssRule<'i>) -> Result<Option<CssRule<'i>>, String> {
+    let function = self.rule.take().unwrap();
+    let res = self.call(&function, rule, "rule");
+    self.rule = Some(function);
+    res
   }
 
-  fn handle_error(tx: Sender<napi::Result<String>>, res: napi::Result<()>) -> napi::Result<()> {
-    match res {
-      Ok(_) => Ok(()),
-      Err(e) => {
-        tx.send(Err(e)).expect("send error");
-        Ok(())
-      }
+  fn visits_declaration(&self, property: &Property<'i>) -> bool {
+    match &self.filter.declarations {
+      _ if self.declaration.is_none() => false,
+      Some(DeclarationFilter {
+        properties: Some(properties),
+      }) => properties.contains(property.property_id().name()),
+      _ => true,
     }
   }
 
-  fn resolve_on_js_thread_wrapper(ctx: ThreadSafeCallContext<ResolveMessage>) -> napi::Result<()> {
-    let tx = ctx.value.tx.clone();
-    handle_error(tx, resolve_on_js_thread(ctx))
+  fn visit_declaration(&mut self, property: &Property<'i>) -> Result<Option<Property<'i>>, String> {
+    let function = self.declaration.take().unwrap();
+    let res = self.call(&function, property, "declaration");
+    self.declaration = Some(function);
+    res
   }
+}
 
-  fn read_on_js_thread(ctx: ThreadSafeCallContext<ReadMessage>) -> napi::Result<()> {
-    let file = ctx.env.create_string(&ctx.value.file)?;
-    let result = ctx.callback.call(None, &[file])?;
-    await_promise(ctx.env, result, ctx.value.tx)
-  }
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(1)]
+fn transform_style_attribute(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let opts = ctx.get::<JsObject>(0)?;
+  let config: AttrConfig = options(ctx.env, &opts)?;
+  let code = match decode_code(&config.code, config.encoding) {
+    Ok(code) => code,
+    Err(err) => return err.throw(*ctx.env, None),
+  };
+  let decoded = config.decode_html_entities.then(|| DecodedAttr::new(&code));
+  let res = compile_attr(&code, &config, decoded.as_ref());
 
-  fn read_on_js_thread_wrapper(ctx: ThreadSafeCallContext<ReadMessage>) -> napi::Result<()> {
-    let tx = ctx.value.tx.clone();
-    handle_error(tx, read_on_js_thread(ctx))
+  match res {
+    Ok(res) => {
+      let env = *ctx.env;
+      filter_warnings(env, &opts, res.into_js(ctx)?)
+    }
+    Err(err) => err.throw(*ctx.env, Some(&*code)),
   }
+}
 
-  #[cfg(not(target_arch = "wasm32"))]
-  #[js_function(1)]
-  pub fn bundle_async(ctx: CallContext) -> napi::Result<JsUnknown> {
-    let opts = ctx.get::<JsObject>(0)?;
-    let config: BundleConfig = ctx.env.from_js_value(&opts)?;
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(1)]
+fn transform_declaration(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let opts = ctx.get::<JsObject>(0)?;
+  let config: DeclarationConfig = options(ctx.env, &opts)?;
+  let res = compile_declaration(&config);
 
-    if let Ok(resolver) = opts.get_named_property::<JsObject>("resolver") {
-      let read = if resolver.has_named_property("read")? {
-        let read = resolver.get_named_property::<JsFunction>("read")?;
-        Some(ThreadsafeFunction::create(
-          ctx.env.raw(),
-          unsafe { read.raw() },
-          0,
-          read_on_js_thread_wrapper,
-        )?)
-      } else {
-        None
-      };
+  match res {
+    Ok(res) => ctx.env.to_js_value(&res),
+    Err(err) => err.throw(*ctx.env, Some(config.value.as_str())),
+  }
+}
 
-      let resolve = if resolver.has_named_property("resolve")? {
-        let resolve = resolver.get_named_property::<JsFunction>("resolve")?;
-        Some(ThreadsafeFunction::create(
-          ctx.env.raw(),
-          unsafe { resolve.raw() },
-          0,
-          resolve_on_js_thread_wrapper,
-        )?)
-      } else {
-        None
-      };
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(1)]
+fn transform_rule(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let opts = ctx.get::<JsObject>(0)?;
+  let config: RuleConfig = options(ctx.env, &opts)?;
+  let code = match utf8_code(&config.code) {
+    Ok(code) => code,
+    Err(err) => return err.throw(*ctx.env, None),
+  };
+  let res = compile_rule(code, &config);
 
-      let provider = JsSourceProvider {
-        resolve,
-        read,
-        inputs: Mutex::new(Vec::new()),
-      };
+  match res {
+    Ok(res) => ctx.env.to_js_value(&res),
+    Err(err) => err.throw(*ctx.env, Some(code)),
+  }
+}
 
-      run_bundle_task(provider, config, *ctx.env)
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(2)]
+fn transform_media_query(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let query = ctx.get::<napi::JsString>(0)?.into_utf8()?;
+  let query = query.as_str()?;
+  let targets: MediaQueryTargets = if ctx.length > 1 {
+    let targets = ctx.get::<JsUnknown>(1)?;
+    if targets.get_type()? == napi::ValueType::Undefined {
+      MediaQueryTargets(None)
     } else {
-      let provider = FileProvider::new();
-      run_bundle_task(provider, config, *ctx.env)
+      ctx.env.from_js_value(targets)?
     }
+  } else {
+    MediaQueryTargets(None)
+  };
+  let res = compile_media_query(query, targets.0);
+
+  match res {
+    Ok(res) => ctx.env.to_js_value(&res),
+    Err(err) => err.throw(*ctx.env, Some(query)),
   }
+}
 
-  struct TSFNValue(napi::sys::napi_threadsafe_function);
-  unsafe impl Send for TSFNValue {}
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(1)]
+fn transform_from_ast(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let opts = ctx.get::<JsObject>(0)?;
+  let config: AstConfig = options(ctx.env, &opts)?;
+  let res = compile_ast(&config);
 
-  // Runs bundling on a background thread managed by rayon. This is similar to AsyncTask from napi-rs, however,
-  // because we call back into the JS thread, which might call other tasks in the node threadpool (e.g. fs.readFile),
-  // we may end up deadlocking if the number of rayon threads exceeds node's threadpool size. Therefore, we must
-  // run bundling from a thread not managed by Node.
-  fn run_bundle_task<P: 'static + SourceProvider>(
-    provider: P,
-    config: BundleConfig,
-    env: Env,
-  ) -> napi::Result<JsUnknown> {
-    // Create a promise.
-    let mut raw_promise = std::ptr::null_mut();
-    let mut deferred = std::ptr::null_mut();
-    let status = unsafe { napi::sys::napi_create_promise(env.raw(), &mut deferred, &mut raw_promise) };
-    assert_eq!(napi::Status::from(status), napi::Status::Ok);
+  match res {
+    Ok(res) => res.into_js(*ctx.env),
+    Err(err) => err.throw(*ctx.env, None),
+  }
+}
 
-    // Create a threadsafe function so we can call back into the JS thread when we are done.
-    let async_resource_name = env.create_string("run_bundle_task").unwrap();
-    let mut tsfn = std::ptr::null_mut();
-    napi::check_status! {unsafe {
-      napi::sys::napi_create_threadsafe_function(
-        env.raw(),
-        std::ptr::null_mut(),
-        std::ptr::null_mut(),
-        async_resource_name.raw(),
-        0,
-        1,
-        std::ptr::null_mut(),
-        None,
-        deferred as *mut c_void,
-        Some(bundle_task_cb),
-        &mut tsfn,
-      )
-    }}?;
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(2)]
+fn snapshot_hash(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let buf = ctx.get::<napi::JsBuffer>(0)?.into_value()?;
+  let config: SnapshotHashConfig = if ctx.length > 1 {
+    let opts = ctx.get::<JsUnknown>(1)?;
+    if opts.get_type()? == napi::ValueType::Undefined {
+      SnapshotHashConfig::default()
+    } else {
+      ctx.env.from_js_value(opts)?
+    }
+  } else {
+    SnapshotHashConfig::default()
+  };
+  let code = match utf8_code(&buf) {
+    Ok(code) => code,
+    Err(err) => return err.throw(*ctx.env, None),
+  };
+  let res = snapshot_hash_code(code, &config);
 
-    // Wrap raw pointer so it is Send compatible.
-    let tsfn_value = TSFNValue(tsfn);
+  match res {
+    Ok(hash) => Ok(ctx.env.create_string(&hash)?.into_unknown()),
+    Err(err) => err.throw(*ctx.env, Some(code)),
+  }
+}
 
-    // Run bundling task in rayon threadpool.
-    rayon::spawn(move || {
-      let provider = provider;
-      let result = compile_bundle(unsafe { std::mem::transmute::<&'_ P, &'static P>(&provider) }, &config)
-        .map_err(|e| e.into());
-      resolve_task(result, tsfn_value);
-    });
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(1)]
+fn parse_selector(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let selector = ctx.get::<napi::JsString>(0)?.into_utf8()?;
+  let code = selector.as_str()?;
+  let res = parse_selector_code(code);
 
-    Ok(unsafe { JsUnknown::from_raw_unchecked(env.raw(), raw_promise) })
+  match res {
+    Ok(res) => ctx.env.to_js_value(&res),
+    Err(err) => err.throw(*ctx.env, Some(code)),
   }
+}
 
-  fn resolve_task(result: napi::Result<TransformResult<'static>>, tsfn_value: TSFNValue) {
-    // Call back into the JS thread via a threadsafe function. This results in bundle_task_cb being called.
-    let status = unsafe {
-      napi::sys::napi_call_threadsafe_function(
-        tsfn_value.0,
-        Box::into_raw(Box::from(result)) as *mut c_void,
-        napi::sys::ThreadsafeFunctionCallMode::nonblocking,
-      )
-    };
-    assert_eq!(napi::Status::from(status), napi::Status::Ok);
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(2)]
+fn serialize_selector(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let selector: SelectorInput = ctx.env.from_js_value(ctx.get::<JsUnknown>(0)?)?;
+  let config: SerializeSelectorConfig = if ctx.length > 1 {
+    let opts = ctx.get::<JsUnknown>(1)?;
+    if opts.get_type()? == napi::ValueType::Undefined {
+      SerializeSelectorConfig::default()
+    } else {
+      ctx.env.from_js_value(opts)?
+    }
+  } else {
+    SerializeSelectorConfig::default()
+  };
+  let code = selector.to_code();
+  let res = serialize_selector_code(&code, &config);
 
-    let status = unsafe {
-      napi::sys::napi_release_threadsafe_function(tsfn_value.0, napi::sys::ThreadsafeFunctionReleaseMode::release)
-    };
-    assert_eq!(napi::Status::from(status), napi::Status::Ok);
+  match res {
+    Ok(res) => Ok(ctx.env.create_string(&res)?.into_unknown()),
+    Err(err) => err.throw(*ctx.env, Some(&code)),
   }
+}
 
-  extern "C" fn bundle_task_cb(
-    env: napi::sys::napi_env,
-    _js_callback: napi::sys::napi_value,
-    context: *mut c_void,
-    data: *mut c_void,
-  ) {
-    let deferred = context as napi::sys::napi_deferred;
-    let value = unsafe { Box::from_raw(data as *mut napi::Result<TransformResult<'static>>) };
-    let value = value.and_then(|res| res.into_js(unsafe { Env::from_raw(env) }));
+#[cfg(not(target_arch = "wasm32"))]
+#[js_function(2)]
+fn compare_exports(ctx: CallContext) -> napi::Result<JsUnknown> {
+  let before: CssModuleExports = ctx.env.from_js_value(ctx.get::<JsObject>(0)?)?;
+  let after: CssModuleExports = ctx.env.from_js_value(ctx.get::<JsObject>(1)?)?;
+  ctx.env.to_js_value(&diff_exports(&before, &after))
+}
 
-    // Resolve or reject the promise based on the result.
-    match value {
-      Ok(res) => {
-        let status = unsafe { napi::sys::napi_resolve_deferred(env, deferred, res.raw()) };
-        assert_eq!(napi::Status::from(status), napi::Status::Ok);
-      }
-      Err(e) => {
-        let status =
-          unsafe { napi::sys::napi_reject_deferred(env, deferred, napi::JsError::from(e).into_value(env)) };
-        assert_eq!(napi::Status::from(status), napi::Status::Ok);
+#[cfg(not(target_arch = "wasm32"))]
+mod bundle {
+  use super::*;
+  use crossbeam_channel::{self, Receiver, RecvTimeoutError, Sender};
+  use lightningcss::bundler::decode_source;
+  use napi::{check_status, Env, JsFunction, JsString, JsUndefined, NapiRaw, NapiValue};
+  use std::time::Duration;
+  use threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+
+  #[js_function(1)]
+  pub fn bundle(ctx: CallContext) -> napi::Result<JsUnknown> {
+    let opts = ctx.get::<JsObject>(0)?;
+    check_sync_css_modules(&opts)?;
+    let config: BundleConfig = options(ctx.env, &opts)?;
+
+    if let Ok(resolver) = opts.get_named_property::<JsObject>("resolver") {
+      let res = bundle_with_resolver(*ctx.env, &resolver, &config)?;
+      return filter_warnings(*ctx.env, &opts, res);
+    }
+
+    let fs = FileProvider::new();
+    let res = match thread_pool(&config)? {
+      Some(pool) => pool.install(|| compile_bundle(&fs, &config, None, None)),
+      None => compile_bundle(&fs, &config, None, None),
+    };
+
+    match res {
+      Ok(res) => filter_warnings(*ctx.env, &opts, res.into_js(*ctx.env)?),
+      Err(err) => {
+        let code = match &err {
+          CompileError::ParseError(Error {
+            loc: Some(ErrorLocation { filename, .. }),
+            ..
+          })
+          | CompileError::PrinterError(Error {
+            loc: Some(ErrorLocation { filename, .. }),
+            ..
+          })
+          | CompileError::MinifyError(Error {
+            loc: Some(ErrorLocation { filename, .. }),
+            ..
+          })
+          | CompileError::BundleError(Error {
+            loc: Some(ErrorLocation { filename, .. }),
+            ..
+          }) => Some(fs.read(Path::new(filename))?),
+          _ => None,
+        };
+        err.throw(*ctx.env, code)
+      }
+    }
+  }
+
+  // Bundles synchronously using JS resolver functions.
+  fn bundle_with_resolver(env: Env, resolver: &JsObject, config: &BundleConfig) -> napi::Result<JsUnknown> {
+    let mut provider = JsSourceProvider {
+      resolve: None,
+      read: None,
+      inputs: Mutex::new(Vec::new()),
+      entry: None,
+      timeout: None,
+    };
+    let pool = thread_pool(config)?;
+
+    let res = with_resolver(env, resolver, &mut provider, |provider| match &pool {
+      Some(pool) => pool.install(|| compile_bundle(provider, config, None, None)),
+      None => compile_bundle(provider, config, None, None),
+    })?;
+
+    match res {
+      Ok(res) => res.into_js(env),
+      Err(err) => err.throw(env, None),
+    }
+  }
+
+  // Compiles a single file passed to `transform()`, inlining its imports. Imported files
+  // are loaded using the JS resolver if provided, and from the file system otherwise.
+  // With `for_bundler`, the result is returned as a `BundlerResult`, including any error.
+  pub fn transform_inline_imports(
+    env: Env,
+    opts: &JsObject,
+    code: &str,
+    config: &Config,
+    for_bundler: bool,
+  ) -> napi::Result<JsUnknown> {
+    let mut provider = JsSourceProvider {
+      resolve: None,
+      read: None,
+      inputs: Mutex::new(Vec::new()),
+      entry: Some((
+        PathBuf::from(config.filename.as_deref().unwrap_or_default()),
+        code.to_owned(),
+      )),
+      timeout: None,
+    };
+
+    let res = if let Ok(resolver) = opts.get_named_property::<JsObject>("resolver") {
+      with_resolver(env, &resolver, &mut provider, |provider| {
+        compile(code, config, Some(provider), None)
+      })?
+    } else {
+      compile(code, config, Some(&provider), None)
+    };
+
+    if for_bundler {
+      return env.to_js_value(&BundlerResult::new(res, Some(code), config));
+    }
+
+    match res {
+      Ok(res) => res.into_js(env),
+      Err(err) => err.throw(env, None),
+    }
+  }
+
+  // Runs `f` on a background thread, while the JS thread is blocked servicing read and resolve
+  // requests from the provider until it completes. No threadsafe functions are needed because
+  // the JS functions are only called from here.
+  fn with_resolver<'p, T: Send>(
+    env: Env,
+    resolver: &JsObject,
+    provider: &'p mut JsSourceProvider,
+    f: impl FnOnce(&'p JsSourceProvider) -> T + Send,
+  ) -> napi::Result<T> {
+    let read = if resolver.has_named_property("read")? {
+      Some(resolver.get_named_property::<JsFunction>("read")?)
+    } else {
+      None
+    };
+
+    let resolve = if resolver.has_named_property("resolve")? {
+      Some(resolver.get_named_property::<JsFunction>("resolve")?)
+    } else {
+      None
+    };
+
+    let (tx, rx) = crossbeam_channel::unbounded();
+    provider.resolve = resolve.as_ref().map(|_| JsCallback::Channel(tx.clone()));
+    provider.read = read.as_ref().map(|_| JsCallback::Channel(tx.clone()));
+    let provider: &'p JsSourceProvider = provider;
+
+    std::thread::scope(|scope| {
+      let handle = scope.spawn(|| {
+        let _done = DoneGuard(tx.clone());
+        f(provider)
+      });
+
+      for request in rx.iter() {
+        match request {
+          JsRequest::Read(message) => {
+            let res = call_sync(env, read.as_ref().unwrap(), &[message.file.as_str()]);
+            message.tx.send(res).unwrap();
+          }
+          JsRequest::Resolve(message) => {
+            let res = call_sync(
+              env,
+              resolve.as_ref().unwrap(),
+              &[message.specifier.as_str(), message.originating_file.as_str()],
+            );
+            message.tx.send(res).unwrap();
+          }
+          JsRequest::Done => break,
+        }
+      }
+
+      let res = handle
+        .join()
+        .map_err(|_| napi::Error::from_reason("Bundling panicked while using a resolver"))?;
+
+      // The exception thrown by a resolver is rethrown when returning to JS, rather than the error it caused.
+      if is_exception_pending(env)? {
+        return Err(pending_exception());
+      }
+      Ok(res)
+    })
+  }
+
+  // Tells the JS thread to stop waiting for requests when bundling finishes, even if it panics.
+  struct DoneGuard(Sender<JsRequest>);
+
+  impl Drop for DoneGuard {
+    fn drop(&mut self) {
+      let _ = self.0.send(JsRequest::Done);
+    }
+  }
+
+  fn is_exception_pending(env: Env) -> napi::Result<bool> {
+    let mut pending = false;
+    check_status!(unsafe { napi::sys::napi_is_exception_pending(env.raw(), &mut pending) })?;
+    Ok(pending)
+  }
+
+  fn pending_exception() -> napi::Error {
+    napi::Error::new(napi::Status::PendingException, "A resolver function threw an exception")
+  }
+
+  // The native state of a bundler returned by `createBundler()`.
+  struct BundlerHandle {
+    // Cached style sheets borrow from the sources stored in the provider,
+    // so the cache must be declared first to be dropped first.
+    cache: BundleCache<'static>,
+    provider: JsSourceProvider,
+  }
+
+  // Returns the cache of a bundler with the lifetime of a borrow of its provider, so that style sheets
+  // parsed from the sources it reads can be added to the cache.
+  fn borrow_cache<'p>(cache: &'p BundleCache<'static>, _provider: &'p JsSourceProvider) -> &'p BundleCache<'p> {
+    // SAFETY: sources read by the provider are only freed when the handle is finalized, after the cache
+    // is dropped, or by `evict()` once the cache no longer borrows from them.
+    unsafe { std::mem::transmute::<&'p BundleCache<'static>, &'p BundleCache<'p>>(cache) }
+  }
+
+  #[js_function(0)]
+  pub fn create_bundler(ctx: CallContext) -> napi::Result<JsObject> {
+    let mut bundler = ctx.env.create_object()?;
+    bundler.create_named_method("bundle", bundler_bundle)?;
+    bundler.create_named_method("invalidate", bundler_invalidate)?;
+    ctx.env.wrap(
+      &mut bundler,
+      BundlerHandle {
+        cache: BundleCache::new(),
+        provider: JsSourceProvider {
+          resolve: None,
+          read: None,
+          inputs: Mutex::new(Vec::new()),
+          entry: None,
+          timeout: None,
+        },
+      },
+    )?;
+    Ok(bundler)
+  }
+
+  #[js_function(1)]
+  fn bundler_bundle(ctx: CallContext) -> napi::Result<JsUnknown> {
+    let this = ctx.this_unchecked::<JsObject>();
+    let BundlerHandle { cache, provider } = ctx.env.unwrap::<BundlerHandle>(&this)?;
+    let opts = ctx.get::<JsObject>(0)?;
+    check_sync_css_modules(&opts)?;
+    let config: BundleConfig = options(ctx.env, &opts)?;
+    let pool = thread_pool(&config)?;
+
+    let compile = |provider| {
+      let cache = borrow_cache(cache, provider);
+      match &pool {
+        Some(pool) => pool.install(|| compile_bundle(provider, &config, Some(cache), None)),
+        None => compile_bundle(provider, &config, Some(cache), None),
+      }
+    };
+
+    // The provider is only borrowed until the result has been converted to JS.
+    let res = {
+      let res = if let Ok(resolver) = opts.get_named_property::<JsObject>("resolver") {
+        with_resolver(*ctx.env, &resolver, provider, compile)?
+      } else {
+        // Clear callbacks from a previous call with a resolver.
+        provider.resolve = None;
+        provider.read = None;
+        compile(&*provider)
+      };
+
+      match res {
+        Ok(res) => res.into_js(*ctx.env),
+        Err(err) => err.throw(*ctx.env, None),
+      }
+    };
+
+    // Only the cache still borrows from the sources.
+    provider.evict(cache);
+    res
+  }
+
+  #[js_function(1)]
+  fn bundler_invalidate(ctx: CallContext) -> napi::Result<JsUndefined> {
+    let this = ctx.this_unchecked::<JsObject>();
+    let handle = ctx.env.unwrap::<BundlerHandle>(&this)?;
+    let file = ctx.get::<JsString>(0)?.into_utf8()?;
+    handle.cache.invalidate(Path::new(file.as_str()?));
+    ctx.env.get_undefined()
+  }
+
+  // Calls a JS resolver function on the current thread, and returns its string result.
+  fn call_sync(env: Env, f: &JsFunction, args: &[&str]) -> napi::Result<String> {
+    // No JS can be called until a pending exception is rethrown, so later requests fail instead.
+    if is_exception_pending(env)? {
+      return Err(pending_exception());
+    }
+
+    let args = args
+      .iter()
+      .map(|arg| env.create_string(arg))
+      .collect::<napi::Result<Vec<_>>>()?;
+    let result = f.call(None, &args)?;
+    if result.is_promise()? {
+      return Err(napi::Error::from_reason(
+        "Resolver functions passed to `bundle()` must be synchronous. Use `bundleAsync()` to return a promise.",
+      ));
+    }
+
+    let result: JsString = result.try_into()?;
+    result.into_utf8()?.into_owned()
+  }
+
+  // Dedicated thread pools by number of threads, which are reused by later calls with the same `threads` option.
+  static THREAD_POOLS: Mutex<Option<HashMap<usize, Arc<rayon::ThreadPool>>>> = Mutex::new(None);
+
+  // Returns a dedicated thread pool if the `threads` option is set. Otherwise, bundling
+  // runs on the global rayon pool, which has one thread per CPU.
+  fn thread_pool(config: &BundleConfig) -> napi::Result<Option<Arc<rayon::ThreadPool>>> {
+    let threads = match config.threads {
+      Some(threads) if threads > 0 => threads,
+      _ => return Ok(None),
+    };
+
+    let mut pools = THREAD_POOLS.lock().unwrap();
+    let pools = pools.get_or_insert_with(HashMap::new);
+    if let Some(pool) = pools.get(&threads) {
+      return Ok(Some(pool.clone()));
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+      .num_threads(threads)
+      .thread_name(|i| format!("lightningcss-bundle-{}", i))
+      .build()
+      .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    let pool = Arc::new(pool);
+    pools.insert(threads, pool.clone());
+    Ok(Some(pool))
+  }
+
+  // A SourceProvider which calls JavaScript functions to resolve and read files.
+  struct JsSourceProvider {
+    resolve: Option<JsCallback<ResolveMessage>>,
+    read: Option<JsCallback<ReadMessage>>,
+    // The sources returned by `read()`, which are borrowed by the parsed style sheets and the result of bundling.
+    // Each pointer is created by `Box::into_raw`, and only freed when the provider is dropped, or by `evict()`
+    // once it is no longer cached, so the provider must outlive everything that borrows from it.
+    inputs: Mutex<Vec<*mut String>>,
+    // The path and source of the file passed to `transform()`, which is not read again.
+    entry: Option<(PathBuf, String)>,
+    // How long to wait for each call to the resolver, set by `resolveTimeoutMs` in `bundleAsync()`.
+    timeout: Option<Duration>,
+  }
+
+  // How a resolver message is delivered to the JS thread.
+  enum JsCallback<T: 'static> {
+    // Queued onto the JS event loop, used by `bundleAsync()`.
+    Threadsafe(ThreadsafeFunction<T>),
+    // Sent to the JS thread, which is blocked inside `bundle()` waiting for requests.
+    Channel(Sender<JsRequest>),
+  }
+
+  impl<T: Into<JsRequest>> JsCallback<T> {
+    fn call(&self, message: T) {
+      match self {
+        JsCallback::Threadsafe(f) => {
+          f.call(message, ThreadsafeFunctionCallMode::Blocking);
+        }
+        JsCallback::Channel(tx) => tx.send(message.into()).unwrap(),
+      }
+    }
+  }
+
+  // A request from a bundling thread to the JS thread during `bundle()`.
+  enum JsRequest {
+    Read(ReadMessage),
+    Resolve(ResolveMessage),
+    Done,
+  }
+
+  impl From<ReadMessage> for JsRequest {
+    fn from(message: ReadMessage) -> JsRequest {
+      JsRequest::Read(message)
+    }
+  }
+
+  impl From<ResolveMessage> for JsRequest {
+    fn from(message: ResolveMessage) -> JsRequest {
+      JsRequest::Resolve(message)
+    }
+  }
+
+  unsafe impl Sync for JsSourceProvider {}
+  unsafe impl Send for JsSourceProvider {}
+
+  impl Drop for JsSourceProvider {
+    fn drop(&mut self) {
+      for ptr in self.inputs.get_mut().unwrap().drain(..) {
+        // SAFETY: each pointer was created by `Box::into_raw` in `read()`, and is only freed here.
+        std::mem::drop(unsafe { Box::from_raw(ptr) })
+      }
+    }
+  }
+
+  // Allocate a single channel per thread to communicate with the JS thread.
+  thread_local! {
+    static CHANNEL: (Sender<napi::Result<String>>, Receiver<napi::Result<String>>) = crossbeam_channel::unbounded();
+  }
+
+  impl JsSourceProvider {
+    // Frees the sources that are not borrowed by the cache, e.g. the previous contents of changed files,
+    // so that they do not accumulate when rebuilding in watch mode. Nothing else may borrow from them.
+    fn evict(&mut self, cache: &BundleCache) {
+      let cached: HashSet<*const u8> = cache.sources().iter().map(|source| source.as_ptr()).collect();
+      self.inputs.get_mut().unwrap().retain(|&ptr| {
+        // SAFETY: each pointer was created by `Box::into_raw` in `read()`, and is only freed here or on drop.
+        if cached.contains(&unsafe { &*ptr }.as_ptr()) {
+          return true;
+        }
+        std::mem::drop(unsafe { Box::from_raw(ptr) });
+        false
+      });
+    }
+
+    // Sends a message to the JS resolver and waits for its result.
+    fn call_js<T: Into<JsRequest>>(
+      &self,
+      callback: &JsCallback<T>,
+      message: impl FnOnce(Sender<napi::Result<String>>) -> T,
+      description: impl FnOnce() -> String,
+    ) -> napi::Result<String> {
+      if let Some(timeout) = self.timeout {
+        // Use a new channel for each call so that a late response from a timed out call
+        // is dropped rather than received by the next call on this thread.
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        callback.call(message(tx));
+        return match rx.recv_timeout(timeout) {
+          Ok(result) => result,
+          Err(RecvTimeoutError::Timeout) => Err(napi::Error::from_reason(format!(
+            "Resolver timed out after {}ms while {}",
+            timeout.as_millis(),
+            description()
+          ))),
+          Err(RecvTimeoutError::Disconnected) => Err(napi::Error::from_reason(format!(
+            "Resolver did not respond while {}",
+            description()
+          ))),
+        };
+      }
+
+      CHANNEL.with(|channel| {
+        callback.call(message(channel.0.clone()));
+        channel.1.recv().unwrap()
+      })
+    }
+  }
+
+  /// Removes a UTF-8 byte order mark from a source returned by a JS `read` function. A UTF-16 or UTF-32
+  /// byte order mark decoded as UTF-8 becomes two replacement characters, so report an error rather than
+  /// failing to parse the file.
+  fn js_source(mut source: String, file: &Path) -> napi::Result<String> {
+    if source.starts_with("\u{fffd}\u{fffd}") && source.contains('\0') {
+      return Err(napi::Error::new(
+        napi::Status::GenericFailure,
+        format!(
+          "{} is not UTF-8 encoded (detected a UTF-16 or UTF-32 byte order mark)",
+          file.display()
+        ),
+      ));
+    }
+    if source.starts_with('\u{feff}') {
+      source.drain(..'\u{feff}'.len_utf8());
+    }
+    Ok(source)
+  }
+
+  impl SourceProvider for JsSourceProvider {
+    type Error = napi::Error;
+
+    fn read<'a>(&'a self, file: &Path) -> Result<&'a str, Self::Error> {
+      if let Some((entry, code)) = &self.entry {
+        if entry == file {
+          return Ok(code);
+        }
+      }
+
+      let source = if let Some(read) = &self.read {
+        self
+          .call_js(
+            read,
+            |tx| ReadMessage {
+              file: file.to_str().unwrap().to_owned(),
+              tx,
+            },
+            || format!("reading {}", file.display()),
+          )
+          .and_then(|source| js_source(source, file))
+      } else {
+        Ok(decode_source(std::fs::read(file)?, file, true)?)
+      };
+
+      match source {
+        Ok(source) => {
+          // cache the result
+          let ptr = Box::into_raw(Box::new(source));
+          self.inputs.lock().unwrap().push(ptr);
+          // SAFETY: this is safe because the pointer is not dropped
+          // until the JsSourceProvider is, and we never remove from the
+          // list of pointers stored in the vector.
+          Ok(unsafe { &*ptr })
+        }
+        Err(e) => Err(e),
+      }
+    }
+
+    fn resolve(&self, specifier: &str, originating_file: &Path) -> Result<PathBuf, Self::Error> {
+      if let Some(resolve) = &self.resolve {
+        let result = self.call_js(
+          resolve,
+          |tx| ResolveMessage {
+            specifier: specifier.to_owned(),
+            originating_file: originating_file.to_str().unwrap().to_owned(),
+            tx,
+          },
+          || format!("resolving {:?} from {}", specifier, originating_file.display()),
+        );
+        return match result {
+          Ok(result) => Ok(PathBuf::from_str(&result).unwrap()),
+          Err(e) => Err(e),
+        };
+      }
+
+      Ok(originating_file.with_file_name(specifier))
+    }
+  }
+
+  struct ResolveMessage {
+    specifier: String,
+    originating_file: String,
+    tx: Sender<napi::Result<String>>,
+  }
+
+  struct ReadMessage {
+    file: String,
+    tx: Sender<napi::Result<String>>,
+  }
+
+  fn await_promise(env: Env, result: JsUnknown, tx: Sender<napi::Result<String>>) -> napi::Result<()> {
+    // If the result is a promise, wait for it to resolve, and send the result to the channel.
+    // Otherwise, send the result immediately. Sending fails if the bundler already timed out
+    // waiting for the result, in which case it is ignored.
+    if result.is_promise()? {
+      let result: JsObject = result.try_into()?;
+      let then: JsFunction = result.get_named_property("then")?;
+      let tx2 = tx.clone();
+      let cb = env.create_function_from_closure("callback", move |ctx| {
+        let res = ctx.get::<JsString>(0)?.into_utf8()?;
+        let s = res.into_owned()?;
+        let _ = tx.send(Ok(s));
+        ctx.env.get_undefined()
+      })?;
+      let eb = env.create_function_from_closure("error_callback", move |ctx| {
+        let error = ctx.get::<JsUnknown>(0).and_then(rejection_error).unwrap_or_else(|e| e);
+        let _ = tx2.send(Err(error));
+        ctx.env.get_undefined()
+      })?;
+      then.call(Some(&result), &[cb, eb])?;
+    } else {
+      let result: JsString = result.try_into()?;
+      let utf8 = result.into_utf8()?;
+      let s = utf8.into_owned()?;
+      let _ = tx.send(Ok(s));
+    }
+
+    Ok(())
+  }
+
+  // Converts the value a promise was rejected with into an error. Error objects use their message,
+  // followed by the call frames from their stack if it has any. Other values are converted to strings.
+  fn rejection_error(value: JsUnknown) -> napi::Result<napi::Error> {
+    match value.get_type()? {
+      napi::ValueType::Undefined | napi::ValueType::Null => {
+        return Ok(napi::Error::from_reason("Promise rejected"));
+      }
+      napi::ValueType::Object => {
+        let object: JsObject = value.try_into()?;
+        if let Some(message) = string_property(&object, "message")? {
+          let frames = string_property(&object, "stack")?.map_or(Vec::new(), |stack| {
+            stack
+              .lines()
+              .skip_while(|line| !line.trim_start().starts_with("at "))
+              .map(|line| line.to_owned())
+              .collect()
+          });
+          let reason = if frames.is_empty() {
+            message
+          } else {
+            format!("{}\n{}", message, frames.join("\n"))
+          };
+          return Ok(napi::Error::from_reason(reason));
+        }
+        let reason = object.coerce_to_string()?.into_utf8()?.into_owned()?;
+        return Ok(napi::Error::from_reason(reason));
+      }
+      _ => {}
+    }
+
+    let reason = value.coerce_to_string()?.into_utf8()?.into_owned()?;
+    Ok(napi::Error::from_reason(reason))
+  }
+
+  fn string_property(object: &JsObject, name: &str) -> napi::Result<Option<String>> {
+    let value = object.get_named_property::<JsUnknown>(name)?;
+    if value.get_type()? != napi::ValueType::String {
+      return Ok(None);
+    }
+
+    let value: JsString = value.try_into()?;
+    Ok(Some(value.into_utf8()?.into_owned()?))
+  }
+
+  fn resolve_on_js_thread(ctx: ThreadSafeCallContext<ResolveMessage>) -> napi::Result<()> {
+    let specifier = ctx.env.create_string(&ctx.value.specifier)?;
+    let originating_file = ctx.env.create_string(&ctx.value.originating_file)?;
+    let result = ctx.callback.call(None, &[specifier, originating_file])?;
+    await_promise(ctx.env, result, ctx.value.tx)
+  }
+
+  fn handle_error(tx: Sender<napi::Result<String>>, res: napi::Result<()>) -> napi::Result<()> {
+    match res {
+      Ok(_) => Ok(()),
+      Err(e) => {
+        let _ = tx.send(Err(e));
+        Ok(())
+      }
+    }
+  }
+
+  fn resolve_on_js_thread_wrapper(ctx: ThreadSafeCallContext<ResolveMessage>) -> napi::Result<()> {
+    let tx = ctx.value.tx.clone();
+    handle_error(tx, resolve_on_js_thread(ctx))
+  }
+
+  fn read_on_js_thread(ctx: ThreadSafeCallContext<ReadMessage>) -> napi::Result<()> {
+    let file = ctx.env.create_string(&ctx.value.file)?;
+    let result = ctx.callback.call(None, &[file])?;
+    await_promise(ctx.env, result, ctx.value.tx)
+  }
+
+  fn read_on_js_thread_wrapper(ctx: ThreadSafeCallContext<ReadMessage>) -> napi::Result<()> {
+    let tx = ctx.value.tx.clone();
+    handle_error(tx, read_on_js_thread(ctx))
+  }
+
+  #[cfg(not(target_arch = "wasm32"))]
+  #[js_function(1)]
+  pub fn bundle_async(ctx: CallContext) -> napi::Result<JsUnknown> {
+    let opts = ctx.get::<JsObject>(0)?;
+    let config: BundleConfig = options(ctx.env, &opts)?;
+
+    let generate_name = if let Some(generate_name) = generate_name_function(&opts)? {
+      Some(JsNameGenerator::new(ThreadsafeFunction::create(
+        ctx.env.raw(),
+        unsafe { generate_name.raw() },
+        0,
+        generate_name_on_js_thread_wrapper,
+      )?))
+    } else {
+      None
+    };
+
+    if let Ok(resolver) = opts.get_named_property::<JsObject>("resolver") {
+      let read = if resolver.has_named_property("read")? {
+        let read = resolver.get_named_property::<JsFunction>("read")?;
+        Some(JsCallback::Threadsafe(ThreadsafeFunction::create(
+          ctx.env.raw(),
+          unsafe { read.raw() },
+          0,
+          read_on_js_thread_wrapper,
+        )?))
+      } else {
+        None
+      };
+
+      let resolve = if resolver.has_named_property("resolve")? {
+        let resolve = resolver.get_named_property::<JsFunction>("resolve")?;
+        Some(JsCallback::Threadsafe(ThreadsafeFunction::create(
+          ctx.env.raw(),
+          unsafe { resolve.raw() },
+          0,
+          resolve_on_js_thread_wrapper,
+        )?))
+      } else {
+        None
+      };
+
+      let provider = JsSourceProvider {
+        resolve,
+        read,
+        inputs: Mutex::new(Vec::new()),
+        entry: None,
+        timeout: config.resolve_timeout_ms.map(Duration::from_millis),
+      };
+
+      run_bundle_task(provider, config, generate_name, &opts, *ctx.env)
+    } else {
+      let provider = FileProvider::new();
+      run_bundle_task(provider, config, generate_name, &opts, *ctx.env)
+    }
+  }
+
+  struct GenerateNameMessage {
+    local: String,
+    filename: String,
+    hash: String,
+    tx: Sender<napi::Result<String>>,
+  }
+
+  fn generate_name_on_js_thread(ctx: ThreadSafeCallContext<GenerateNameMessage>) -> napi::Result<()> {
+    let local = ctx.env.create_string(&ctx.value.local)?;
+    let filename = ctx.env.create_string(&ctx.value.filename)?;
+    let hash = ctx.env.create_string(&ctx.value.hash)?;
+    let result = ctx.callback.call(None, &[local, filename, hash])?;
+    await_promise(ctx.env, result, ctx.value.tx)
+  }
+
+  fn generate_name_on_js_thread_wrapper(ctx: ThreadSafeCallContext<GenerateNameMessage>) -> napi::Result<()> {
+    let tx = ctx.value.tx.clone();
+    handle_error(tx, generate_name_on_js_thread(ctx))
+  }
+
+  // A CSS modules name generator which calls a JS function. Names are generated while printing,
+  // which cannot fail, so the first error thrown by the function is stored and reported once
+  // bundling completes.
+  struct JsNameGenerator {
+    generator: NameGenerator,
+    error: Arc<Mutex<Option<napi::Error>>>,
+  }
+
+  impl JsNameGenerator {
+    fn new(generate_name: ThreadsafeFunction<GenerateNameMessage>) -> Self {
+      let error = Arc::new(Mutex::new(None));
+      let generator_error = error.clone();
+      // Each name is generated once per file, rather than each time it is printed.
+      let names: Mutex<HashMap<(String, String), String>> = Mutex::new(HashMap::new());
+      let generator = NameGenerator::new(move |local, filename, hash| {
+        let key = (local.to_owned(), filename.to_owned());
+        if let Some(name) = names.lock().unwrap().get(&key) {
+          return name.clone();
+        }
+
+        let result = CHANNEL.with(|channel| {
+          let message = GenerateNameMessage {
+            local: local.to_owned(),
+            filename: filename.to_owned(),
+            hash: hash.to_owned(),
+            tx: channel.0.clone(),
+          };
+
+          generate_name.call(message, ThreadsafeFunctionCallMode::Blocking);
+          channel.1.recv().unwrap()
+        });
+
+        match result {
+          Ok(name) => {
+            names.lock().unwrap().insert(key, name.clone());
+            name
+          }
+          Err(err) => {
+            generator_error.lock().unwrap().get_or_insert(err);
+            local.to_owned()
+          }
+        }
+      });
+
+      JsNameGenerator { generator, error }
+    }
+  }
+
+  struct TSFNValue(napi::sys::napi_threadsafe_function);
+  unsafe impl Send for TSFNValue {}
+
+  // A reference to the options object, which keeps it alive until bundling completes,
+  // so that `warningFilter` can be applied to the result.
+  struct OptionsRef(napi::sys::napi_ref);
+  unsafe impl Send for OptionsRef {}
+
+  // Runs bundling on a background thread managed by rayon. This is similar to AsyncTask from napi-rs, however,
+  // because we call back into the JS thread, which might call other tasks in the node threadpool (e.g. fs.readFile),
+  // we may end up deadlocking if the number of rayon threads exceeds node's threadpool size. Therefore, we must
+  // run bundling from a thread not managed by Node.
+  fn run_bundle_task<P: 'static + SourceProvider>(
+    provider: P,
+    config: BundleConfig,
+    generate_name: Option<JsNameGenerator>,
+    opts: &JsObject,
+    env: Env,
+  ) -> napi::Result<JsUnknown> {
+    let pool = thread_pool(&config)?;
+
+    let mut options_ref = std::ptr::null_mut();
+    napi::check_status! {unsafe {
+      napi::sys::napi_create_reference(env.raw(), opts.raw(), 1, &mut options_ref)
+    }}?;
+    let options_ref = OptionsRef(options_ref);
+
+    // Create a promise.
+    let mut raw_promise = std::ptr::null_mut();
+    let mut deferred = std::ptr::null_mut();
+    let status = unsafe { napi::sys::napi_create_promise(env.raw(), &mut deferred, &mut raw_promise) };
+    assert_eq!(napi::Status::from(status), napi::Status::Ok);
+
+    // Create a threadsafe function so we can call back into the JS thread when we are done.
+    let async_resource_name = env.create_string("run_bundle_task").unwrap();
+    let mut tsfn = std::ptr::null_mut();
+    napi::check_status! {unsafe {
+      napi::sys::napi_create_threadsafe_function(
+        env.raw(),
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+        async_resource_name.raw(),
+        0,
+        1,
+        std::ptr::null_mut(),
+        None,
+        deferred as *mut c_void,
+        Some(bundle_task_cb),
+        &mut tsfn,
+      )
+    }}?;
+
+    // Wrap raw pointer so it is Send compatible.
+    let tsfn_value = TSFNValue(tsfn);
+
+    // Run bundling task in rayon threadpool.
+    let task = move || {
+      let provider = provider;
+      let result: napi::Result<TransformResult<'static>> = compile_bundle(
+        unsafe { std::mem::transmute::<&'_ P, &'static P>(&provider) },
+        &config,
+        None,
+        generate_name.as_ref().map(|g| g.generator.clone()),
+      )
+      .map_err(|e| e.into());
+
+      // An error thrown by `generateName` takes precedence, since fallback names were used.
+      let result = match generate_name.as_ref().and_then(|g| g.error.lock().unwrap().take()) {
+        Some(err) => Err(err),
+        None => result,
+      };
+      resolve_task(
+        BundleTaskResult {
+          result,
+          options: options_ref,
+          _provider: Box::new(provider),
+        },
+        tsfn_value,
+      );
+    };
+
+    match pool {
+      Some(pool) => pool.spawn(task),
+      None => rayon::spawn(task),
+    }
+
+    Ok(unsafe { JsUnknown::from_raw_unchecked(env.raw(), raw_promise) })
+  }
+
+  // The result of a bundling task, sent to the JS thread along with the provider it was bundled with.
+  // Warnings in the result may borrow from the sources read by the provider, so it must not be dropped
+  // until the result has been converted to JS values.
+  struct BundleTaskResult {
+    result: napi::Result<TransformResult<'static>>,
+    options: OptionsRef,
+    _provider: Box<dyn Send>,
+  }
+
+  fn resolve_task(result: BundleTaskResult, tsfn_value: TSFNValue) {
+    // Call back into the JS thread via a threadsafe function. This results in bundle_task_cb being called.
+    let status = unsafe {
+      napi::sys::napi_call_threadsafe_function(
+        tsfn_value.0,
+        Box::into_raw(Box::from(result)) as *mut c_void,
+        napi::sys::ThreadsafeFunctionCallMode::nonblocking,
+      )
+    };
+    assert_eq!(napi::Status::from(status), napi::Status::Ok);
+
+    let status = unsafe {
+      napi::sys::napi_release_threadsafe_function(tsfn_value.0, napi::sys::ThreadsafeFunctionReleaseMode::release)
+    };
+    assert_eq!(napi::Status::from(status), napi::Status::Ok);
+  }
+
+  extern "C" fn bundle_task_cb(
+    env: napi::sys::napi_env,
+    _js_callback: napi::sys::napi_value,
+    context: *mut c_void,
+    data: *mut c_void,
+  ) {
+    let deferred = context as napi::sys::napi_deferred;
+    let task = unsafe { Box::from_raw(data as *mut BundleTaskResult) };
+    let mut opts = std::ptr::null_mut();
+    let status = unsafe { napi::sys::napi_get_reference_value(env, task.options.0, &mut opts) };
+    assert_eq!(napi::Status::from(status), napi::Status::Ok);
+    let value = task.result.and_then(|res| {
+      let env = unsafe { Env::from_raw(env) };
+      let opts = unsafe { JsObject::from_raw_unchecked(env.raw(), opts) };
+      filter_warnings(env, &opts, res.into_js(env)?)
+    });
+    let status = unsafe { napi::sys::napi_delete_reference(env, task.options.0) };
+    assert_eq!(napi::Status::from(status), napi::Status::Ok);
+
+    // Resolve or reject the promise based on the result.
+    match value {
+      Ok(res) => {
+        let status = unsafe { napi::sys::napi_resolve_deferred(env, deferred, res.raw()) };
+        assert_eq!(napi::Status::from(status), napi::Status::Ok);
+      }
+      Err(e) => {
+        let status =
+          unsafe { napi::sys::napi_reject_deferred(env, deferred, napi::JsError::from(e).into_value(env)) };
+        assert_eq!(napi::Status::from(status), napi::Status::Ok);
+      }
+    }
+  }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[module_exports]
+fn init(mut exports: JsObject) -> napi::Result<()> {
+  exports.create_named_method("transform", transform)?;
+  exports.create_named_method("validate", validate)?;
+  exports.create_named_method("transformForBundler", transform_for_bundler)?;
+  exports.create_named_method("transformStyleAttribute", transform_style_attribute)?;
+  exports.create_named_method("transformDeclaration", transform_declaration)?;
+  exports.create_named_method("transformRule", transform_rule)?;
+  exports.create_named_method("transformFromAst", transform_from_ast)?;
+  exports.create_named_method("transformMediaQuery", transform_media_query)?;
+  exports.create_named_method("snapshotHash", snapshot_hash)?;
+  exports.create_named_method("compareExports", compare_exports)?;
+  exports.create_named_method("parseSelector", parse_selector)?;
+  exports.create_named_method("serializeSelector", serialize_selector)?;
+  exports.create_named_method("bundle", bundle::bundle)?;
+  exports.create_named_method("bundleAsync", bundle::bundle_async)?;
+  exports.create_named_method("createBundler", bundle::create_bundler)?;
+
+  Ok(())
+}
+
+// ---------------------------------------------
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Config {
+  pub filename: Option<String>,
+  #[serde(with = "serde_bytes")]
+  pub code: Vec<u8>,
+  pub encoding: Option<Encoding>,
+  #[serde(default, deserialize_with = "deserialize_targets")]
+  pub targets: Option<Browsers>,
+  pub minify: Option<MinifyOption>,
+  pub source_map: Option<bool>,
+  pub source_map_include_content: Option<bool>,
+  pub input_source_map: Option<String>,
+  pub drafts: Option<Drafts>,
+  pub non_standard: Option<NonStandard>,
+  pub css_modules: Option<CssModulesOption>,
+  pub analyze_dependencies: Option<AnalyzeDependenciesOption>,
+  pub pseudo_classes: Option<OwnedPseudoClasses>,
+  pub unused_symbols: Option<Vec<UnusedSymbolOption>>,
+  pub error_recovery: Option<bool>,
+  pub warnings_limit: Option<usize>,
+  pub verify_output: Option<bool>,
+  pub strict_preludes: Option<bool>,
+  pub strict: Option<bool>,
+  pub strict_exceptions: Option<HashSet<String>>,
+  pub preserve_var_fallbacks: Option<bool>,
+  pub ignore_properties: Option<HashSet<String>>,
+  pub custom_at_rules: Option<HashMap<String, CustomAtRuleConfig>>,
+  pub minify_whitespace_only: Option<bool>,
+  pub prefix_only: Option<bool>,
+  pub error_on_unsupported: Option<bool>,
+  pub minify_options: Option<MinifyOptionsConfig>,
+  pub dual_targets: Option<DualTargets>,
+  pub source_map_url: Option<String>,
+  pub return_ast: Option<bool>,
+  pub exports_hash: Option<bool>,
+  pub inline_imports: Option<bool>,
+  #[serde(alias = "format")]
+  pub pretty: Option<PrettyConfig>,
+  pub precision: Option<PrecisionConfig>,
+  pub color_format: Option<ColorFormatOption>,
+  pub quotes: Option<QuoteStyleOption>,
+  pub include: Option<FeaturesOption>,
+  pub exclude: Option<FeaturesOption>,
+  pub comments: Option<CommentsOption>,
+  pub logical_properties: Option<LogicalPropertiesOption>,
+}
+
+/// Features to compile regardless of the targets, or to never compile, given as a
+/// list of names or as a bit mask of `Features`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum FeaturesOption {
+  Bits(u32),
+  Names(Vec<String>),
+}
+
+fn features<'i, E: std::error::Error>(option: &Option<FeaturesOption>) -> Result<Features, CompileError<'i, E>> {
+  match option {
+    None => Ok(Features::empty()),
+    Some(FeaturesOption::Bits(bits)) => {
+      Features::from_bits(*bits).ok_or_else(|| CompileError::FeatureError(format!("{:#x}", bits)))
+    }
+    Some(FeaturesOption::Names(names)) => names.iter().try_fold(Features::empty(), |features, name| {
+      match FEATURE_NAMES.iter().find(|(n, _)| n == name) {
+        Some((_, feature)) => Ok(features | *feature),
+        None => Err(CompileError::FeatureError(name.clone())),
+      }
+    }),
+  }
+}
+
+const FEATURE_NAMES: &[(&str, Features)] = &[
+  ("nesting", Features::NESTING),
+  ("mediaRangeSyntax", Features::MEDIA_RANGE_SYNTAX),
+  ("customMediaQueries", Features::CUSTOM_MEDIA_QUERIES),
+  ("hexAlphaColors", Features::HEX_ALPHA_COLORS),
+  ("logicalProperties", Features::LOGICAL_PROPERTIES),
+  ("scope", Features::SCOPE),
+];
+
+fn feature_names(features: Features) -> Vec<String> {
+  FEATURE_NAMES
+    .iter()
+    .filter(|(_, feature)| features.contains(*feature))
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+/// A browser version, either encoded as `major << 16 | minor << 8 | patch`, or as a string such as `"95.1"`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TargetVersion {
+  Encoded(f64),
+  String(String),
+}
+
+const BROWSER_NAMES: &str = "android, chrome, edge, firefox, ie, ios_saf, opera, safari, samsung";
+
+fn deserialize_targets<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Browsers>, D::Error> {
+  let targets: Option<HashMap<String, Option<TargetVersion>>> = Deserialize::deserialize(deserializer)?;
+  targets.map(browsers).transpose()
+}
+
+fn deserialize_browsers<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Browsers, D::Error> {
+  browsers(Deserialize::deserialize(deserializer)?)
+}
+
+fn browsers<E: serde::de::Error>(targets: HashMap<String, Option<TargetVersion>>) -> Result<Browsers, E> {
+  let mut browsers = Browsers::default();
+  for (name, version) in targets {
+    let browser = match name.as_str() {
+      "android" => &mut browsers.android,
+      "chrome" => &mut browsers.chrome,
+      "edge" => &mut browsers.edge,
+      "firefox" => &mut browsers.firefox,
+      "ie" => &mut browsers.ie,
+      "ios_saf" => &mut browsers.ios_saf,
+      "opera" => &mut browsers.opera,
+      "safari" => &mut browsers.safari,
+      "samsung" => &mut browsers.samsung,
+      _ => {
+        return Err(E::custom(format!(
+          "Unknown browser target \"{}\". Valid targets are: {}.",
+          name, BROWSER_NAMES
+        )))
+      }
+    };
+
+    *browser = match version {
+      None => None,
+      Some(TargetVersion::Encoded(v)) if v.fract() == 0.0 && (0.0..=16777215.0).contains(&v) => Some(v as u32),
+      Some(TargetVersion::Encoded(v)) => {
+        return Err(E::custom(format!(
+          "Invalid version for browser target \"{}\": {}. Versions are encoded as `major << 16 | minor << 8 | patch`, e.g. `95 << 16` for version 95, or can be given as a string such as \"95.1\".",
+          name, v
+        )))
+      }
+      Some(TargetVersion::String(v)) => Some(parse_version(&v).ok_or_else(|| {
+        E::custom(format!(
+          "Invalid version for browser target \"{}\": \"{}\". Expected a version such as \"95\" or \"95.1\".",
+          name, v
+        ))
+      })?),
+    };
+  }
+
+  Ok(browsers)
+}
+
+/// Parses a `major[.minor[.patch]]` version string into the encoding used by `Browsers`.
+fn parse_version(version: &str) -> Option<u32> {
+  let mut parts = version.trim().split('.');
+  let mut v = 0;
+  for shift in [16, 8, 0] {
+    let part = match parts.next() {
+      Some(part) => part.parse::<u8>().ok()?,
+      None if shift < 16 => 0,
+      None => return None,
+    };
+    v |= (part as u32) << shift;
+  }
+
+  if parts.next().is_some() {
+    return None;
+  }
+
+  Some(v)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PrettyConfig {
+  indent: Option<IndentOption>,
+  max_line_width: Option<usize>,
+  blank_lines_between_rules: Option<bool>,
+}
+
+/// The indentation for each level, either as a string such as `"\t"`, or as a number of spaces.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum IndentOption {
+  Spaces(u8),
+  String(String),
+}
+
+fn indent(config: &Option<PrettyConfig>) -> Cow<'_, str> {
+  match config.as_ref().and_then(|p| p.indent.as_ref()) {
+    Some(IndentOption::Spaces(spaces)) => Cow::Owned(" ".repeat(*spaces as usize)),
+    Some(IndentOption::String(indent)) => Cow::Borrowed(indent),
+    None => Cow::Borrowed("  "),
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct PrecisionConfig {
+  lengths: Option<u8>,
+  percentages: Option<u8>,
+  times: Option<u8>,
+  angles: Option<u8>,
+  numbers: Option<u8>,
+}
+
+fn precision(config: &Option<PrecisionConfig>) -> Precision {
+  match config {
+    Some(c) => Precision {
+      lengths: c.lengths,
+      percentages: c.percentages,
+      times: c.times,
+      angles: c.angles,
+      numbers: c.numbers,
+    },
+    None => Precision::default(),
+  }
+}
+
+/// The format to print colors in. `keep` uses the default, which depends on the targets.
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum ColorFormatOption {
+  Hex,
+  Rgb,
+  Hsl,
+  Oklch,
+  Keep,
+}
+
+fn color_format(option: Option<ColorFormatOption>) -> Option<ColorFormat> {
+  match option? {
+    ColorFormatOption::Hex => Some(ColorFormat::Hex),
+    ColorFormatOption::Rgb => Some(ColorFormat::Rgb),
+    ColorFormatOption::Hsl => Some(ColorFormat::Hsl),
+    ColorFormatOption::Oklch => Some(ColorFormat::Oklch),
+    ColorFormatOption::Keep => None,
+  }
+}
+
+/// The quotes to print strings and `url()` values with.
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum QuoteStyleOption {
+  Double,
+  Single,
+  Minimal,
+}
+
+impl From<QuoteStyleOption> for QuoteStyle {
+  fn from(option: QuoteStyleOption) -> QuoteStyle {
+    match option {
+      QuoteStyleOption::Double => QuoteStyle::Double,
+      QuoteStyleOption::Single => QuoteStyle::Single,
+      QuoteStyleOption::Minimal => QuoteStyle::Minimal,
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MinifyOption {
+  Bool(bool),
+  Config(MinifyOptionsConfig),
+}
+
+impl MinifyOption {
+  fn is_enabled(&self) -> bool {
+    match self {
+      MinifyOption::Bool(minify) => *minify,
+      MinifyOption::Config(_) => true,
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MinifyOptionsConfig {
+  remove_comments: Option<bool>,
+  merge_rules: Option<bool>,
+  #[serde(alias = "shorthands")]
+  merge_longhands: Option<bool>,
+  remove_empty_rules: Option<bool>,
+  deduplicate_declarations: Option<bool>,
+  sort_declarations: Option<bool>,
+  merge_non_adjacent_at_rules: Option<bool>,
+  deduplicate_keyframes: Option<bool>,
+  shorten_keyframes_names: Option<bool>,
+  preserved_keyframes_names: Option<HashSet<String>>,
+  strict_unused_symbols: Option<bool>,
+  inline_custom_properties: Option<InlineCustomPropertiesOption>,
+  remove_unused_custom_properties: Option<bool>,
+  custom_property_fallbacks: Option<bool>,
+  colors: Option<bool>,
+  calc: Option<bool>,
+  selectors: Option<bool>,
+}
+
+/// An entry in `unusedSymbols`: either a name, which is treated as a pattern if it contains `*`,
+/// or an explicit glob-style pattern.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum UnusedSymbolOption {
+  Name(String),
+  Pattern { pattern: String },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum InlineCustomPropertiesOption {
+  Bool(bool),
+  Names(HashSet<String>),
+}
+
+/// Returns the fine grained minify options, which may either be passed as an object
+/// to `minify`, or separately as `minifyOptions`.
+fn minify_config<'a>(
+  minify: &'a Option<MinifyOption>,
+  minify_options: &'a Option<MinifyOptionsConfig>,
+) -> Option<&'a MinifyOptionsConfig> {
+  match minify {
+    Some(MinifyOption::Config(config)) => Some(config),
+    _ => minify_options.as_ref(),
+  }
+}
+
+fn minify_options(
+  targets: Option<Browsers>,
+  unused_symbols: &Option<Vec<UnusedSymbolOption>>,
+  config: Option<&MinifyOptionsConfig>,
+) -> MinifyOptions {
+  let defaults = MinifyOptions::default();
+  let mut names = HashSet::new();
+  let mut patterns = Vec::new();
+  for symbol in unused_symbols.iter().flatten() {
+    match symbol {
+      UnusedSymbolOption::Name(name) if !name.contains('*') => {
+        names.insert(name.clone());
+      }
+      UnusedSymbolOption::Name(pattern) | UnusedSymbolOption::Pattern { pattern } => {
+        patterns.push(pattern.clone())
+      }
+    }
+  }
+  MinifyOptions {
+    targets,
+    unused_symbols: names,
+    unused_symbol_patterns: patterns,
+    remove_comments: config.and_then(|c| c.remove_comments).unwrap_or(defaults.remove_comments),
+    merge_rules: config.and_then(|c| c.merge_rules).unwrap_or(defaults.merge_rules),
+    merge_longhands: config.and_then(|c| c.merge_longhands).unwrap_or(defaults.merge_longhands),
+    remove_empty_rules: config.and_then(|c| c.remove_empty_rules).unwrap_or(defaults.remove_empty_rules),
+    deduplicate_declarations: config
+      .and_then(|c| c.deduplicate_declarations)
+      .unwrap_or(defaults.deduplicate_declarations),
+    sort_declarations: config.and_then(|c| c.sort_declarations).unwrap_or(defaults.sort_declarations),
+    merge_non_adjacent_at_rules: config
+      .and_then(|c| c.merge_non_adjacent_at_rules)
+      .unwrap_or(defaults.merge_non_adjacent_at_rules),
+    deduplicate_keyframes: config
+      .and_then(|c| c.deduplicate_keyframes)
+      .unwrap_or(defaults.deduplicate_keyframes),
+    shorten_keyframes_names: config
+      .and_then(|c| c.shorten_keyframes_names)
+      .unwrap_or(defaults.shorten_keyframes_names),
+    preserved_keyframes_names: config
+      .and_then(|c| c.preserved_keyframes_names.clone())
+      .unwrap_or(defaults.preserved_keyframes_names),
+    strict_unused_symbols: config
+      .and_then(|c| c.strict_unused_symbols)
+      .unwrap_or(defaults.strict_unused_symbols),
+    inline_custom_properties: match config.and_then(|c| c.inline_custom_properties.as_ref()) {
+      Some(InlineCustomPropertiesOption::Bool(true)) => InlineCustomProperties::All,
+      Some(InlineCustomPropertiesOption::Names(names)) => InlineCustomProperties::Only(names.clone()),
+      _ => defaults.inline_custom_properties,
+    },
+    remove_unused_custom_properties: config
+      .and_then(|c| c.remove_unused_custom_properties)
+      .unwrap_or(defaults.remove_unused_custom_properties),
+    custom_property_fallbacks: config
+      .and_then(|c| c.custom_property_fallbacks)
+      .unwrap_or(defaults.custom_property_fallbacks),
+    ..defaults
+  }
+}
+
+#[derive(Debug, Deserialize)]
+struct DualTargets {
+  #[serde(deserialize_with = "deserialize_browsers")]
+  modern: Browsers,
+  #[serde(deserialize_with = "deserialize_browsers")]
+  legacy: Browsers,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum AnalyzeDependenciesOption {
+  Bool(bool),
+  Config(AnalyzeDependenciesConfig),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AnalyzeDependenciesConfig {
+  preserve_imports: bool,
+}
+
+#[derive(Debug)]
+enum CssModulesOption {
+  Bool(bool),
+  Config(CssModulesConfig),
+}
+
+// Not untagged, so that the config is deserialized field by field rather than buffered,
+// which fails for fields that are not deserialized, such as the `generateName` function.
+impl<'de> Deserialize<'de> for CssModulesOption {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct CssModulesVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for CssModulesVisitor {
+      type Value = CssModulesOption;
+
+      fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a boolean or a CSS modules config")
+      }
+
+      fn visit_bool<E: serde::de::Error>(self, value: bool) -> Result<Self::Value, E> {
+        Ok(CssModulesOption::Bool(value))
+      }
+
+      fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let config = Deserialize::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+        Ok(CssModulesOption::Config(config))
+      }
+    }
+
+    deserializer.deserialize_any(CssModulesVisitor)
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CssModulesConfig {
+  pattern: Option<String>,
+  dashed_idents: Option<bool>,
+  hash_version: Option<u8>,
+  specificity: Option<bool>,
+  export_selectors: Option<bool>,
+  scope_deep_selectors: Option<bool>,
+}
+
+fn hash_version<'i, E: std::error::Error>(
+  version: Option<u8>,
+) -> Result<lightningcss::css_modules::HashVersion, CompileError<'i, E>> {
+  match version {
+    None | Some(1) => Ok(lightningcss::css_modules::HashVersion::V1),
+    Some(2) => Ok(lightningcss::css_modules::HashVersion::V2),
+    Some(v) => Err(CompileError::HashVersionError(v)),
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleConfig {
+  pub filename: String,
+  #[serde(default, deserialize_with = "deserialize_targets")]
+  pub targets: Option<Browsers>,
+  pub minify: Option<MinifyOption>,
+  pub source_map: Option<bool>,
+  pub source_map_include_content: Option<bool>,
+  pub drafts: Option<Drafts>,
+  pub non_standard: Option<NonStandard>,
+  pub css_modules: Option<CssModulesOption>,
+  pub analyze_dependencies: Option<AnalyzeDependenciesOption>,
+  pub pseudo_classes: Option<OwnedPseudoClasses>,
+  pub unused_symbols: Option<Vec<UnusedSymbolOption>>,
+  pub error_recovery: Option<bool>,
+  pub warnings_limit: Option<usize>,
+  pub verify_output: Option<bool>,
+  pub strict_preludes: Option<bool>,
+  pub strict: Option<bool>,
+  pub strict_exceptions: Option<HashSet<String>>,
+  pub preserve_var_fallbacks: Option<bool>,
+  pub ignore_properties: Option<HashSet<String>>,
+  pub custom_at_rules: Option<HashMap<String, CustomAtRuleConfig>>,
+  pub minify_whitespace_only: Option<bool>,
+  pub prefix_only: Option<bool>,
+  pub error_on_unsupported: Option<bool>,
+  pub minify_options: Option<MinifyOptionsConfig>,
+  pub threads: Option<usize>,
+  pub source_map_url: Option<String>,
+  #[serde(alias = "format")]
+  pub pretty: Option<PrettyConfig>,
+  pub precision: Option<PrecisionConfig>,
+  pub color_format: Option<ColorFormatOption>,
+  pub quotes: Option<QuoteStyleOption>,
+  pub include: Option<FeaturesOption>,
+  pub exclude: Option<FeaturesOption>,
+  pub preserve_comments: Option<PreserveComments>,
+  pub comments: Option<CommentsOption>,
+  pub logical_properties: Option<LogicalPropertiesOption>,
+  pub hoist_root_variables: Option<bool>,
+  pub hoist_layer_statements: Option<bool>,
+  pub split_by_layer: Option<bool>,
+  pub resolve_timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum PreserveComments {
+  /// Hoists license comments from all bundled files to the top of the bundle.
+  LicenseHoist,
+}
+
+/// Which comments between rules and declarations to keep in the output.
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum CommentsOption {
+  None,
+  PreserveLicense,
+  All,
+}
+
+fn comments(option: Option<CommentsOption>) -> Comments {
+  match option {
+    None | Some(CommentsOption::None) => Comments::None,
+    Some(CommentsOption::PreserveLicense) => Comments::License,
+    Some(CommentsOption::All) => Comments::All,
+  }
+}
+
+/// The shape of an at-rule that is not part of CSS, e.g. `{ prelude: 'ident', body: 'none' }` for `@tailwind`.
+#[derive(Debug, Deserialize, Clone, Copy)]
+struct CustomAtRuleConfig {
+  prelude: Option<CustomAtRulePreludeOption>,
+  #[serde(default)]
+  body: CustomAtRuleBodyOption,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+enum CustomAtRulePreludeOption {
+  Ident,
+  String,
+  CustomIdentList,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+enum CustomAtRuleBodyOption {
+  #[default]
+  None,
+  DeclarationList,
+  RuleList,
+  Raw,
+}
+
+fn custom_at_rules(
+  option: Option<&HashMap<String, CustomAtRuleConfig>>,
+) -> Option<HashMap<String, CustomAtRuleDefinition>> {
+  let definitions = option?.iter().map(|(name, config)| {
+    let prelude = config.prelude.map(|prelude| match prelude {
+      CustomAtRulePreludeOption::Ident => CustomAtRulePreludeSyntax::Ident,
+      CustomAtRulePreludeOption::String => CustomAtRulePreludeSyntax::String,
+      CustomAtRulePreludeOption::CustomIdentList => CustomAtRulePreludeSyntax::CustomIdentList,
+    });
+    let body = match config.body {
+      CustomAtRuleBodyOption::None => CustomAtRuleBodySyntax::None,
+      CustomAtRuleBodyOption::DeclarationList => CustomAtRuleBodySyntax::DeclarationList,
+      CustomAtRuleBodyOption::RuleList => CustomAtRuleBodySyntax::RuleList,
+      CustomAtRuleBodyOption::Raw => CustomAtRuleBodySyntax::Raw,
+    };
+    (name.clone(), CustomAtRuleDefinition { prelude, body })
+  });
+  Some(definitions.collect())
+}
+
+/// Whether to lower logical properties to physical properties.
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum LogicalPropertiesOption {
+  Auto,
+  Always,
+  Never,
+}
+
+/// Adds the logical properties feature to the included or excluded features, unless lowering is based on the targets.
+fn logical_properties(option: Option<LogicalPropertiesOption>, include: &mut Features, exclude: &mut Features) {
+  match option {
+    None | Some(LogicalPropertiesOption::Auto) => {}
+    Some(LogicalPropertiesOption::Always) => *include |= Features::LOGICAL_PROPERTIES,
+    Some(LogicalPropertiesOption::Never) => *exclude |= Features::LOGICAL_PROPERTIES,
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OwnedPseudoClasses {
+  pub hover: Option<String>,
+  pub active: Option<String>,
+  pub focus: Option<String>,
+  pub focus_visible: Option<String>,
+  pub focus_within: Option<String>,
+}
+
+impl<'a> Into<PseudoClasses<'a>> for &'a OwnedPseudoClasses {
+  fn into(self) -> PseudoClasses<'a> {
+    PseudoClasses {
+      hover: self.hover.as_deref(),
+      active: self.active.as_deref(),
+      focus: self.focus.as_deref(),
+      focus_visible: self.focus_visible.as_deref(),
+      focus_within: self.focus_within.as_deref(),
+    }
+  }
+}
+
+#[derive(Serialize, Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Drafts {
+  #[serde(default)]
+  nesting: bool,
+  #[serde(default)]
+  custom_media: bool,
+  #[serde(default)]
+  scope: bool,
+  nesting_spec: Option<NestingSpecOption>,
+}
+
+#[derive(Serialize, Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum NestingSpecOption {
+  Legacy,
+  Current,
+}
+
+/// Non-standard syntax used by frameworks, which is not parsed by default.
+#[derive(Serialize, Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct NonStandard {
+  #[serde(default)]
+  deep_selectors: bool,
+  #[serde(default)]
+  strip_deep_selectors: bool,
+}
+
+impl Drafts {
+  fn nesting_spec(&self) -> NestingSpec {
+    match self.nesting_spec {
+      Some(NestingSpecOption::Current) => NestingSpec::Current,
+      Some(NestingSpecOption::Legacy) | None => NestingSpec::Legacy,
+    }
+  }
+}
+
+/// The options that affect parsing, shared by every function that parses CSS so that
+/// they are converted to [ParserOptions] in one place.
+#[derive(Default)]
+struct ParserConfig<'a> {
+  filename: Option<&'a str>,
+  drafts: Option<&'a Drafts>,
+  non_standard: Option<&'a NonStandard>,
+  css_modules: Option<&'a CssModulesOption>,
+  error_recovery: bool,
+  strict_preludes: bool,
+  strict: bool,
+  strict_exceptions: Option<&'a HashSet<String>>,
+  preserve_calc: bool,
+  preserve_var_fallbacks: bool,
+  ignore_properties: Option<&'a HashSet<String>>,
+  custom_at_rules: Option<&'a HashMap<String, CustomAtRuleConfig>>,
+  verbatim_values: bool,
+  comments: Option<CommentsOption>,
+}
+
+impl<'a> From<&'a Config> for ParserConfig<'a> {
+  fn from(config: &'a Config) -> Self {
+    ParserConfig {
+      filename: config.filename.as_deref(),
+      drafts: config.drafts.as_ref(),
+      non_standard: config.non_standard.as_ref(),
+      css_modules: config.css_modules.as_ref(),
+      error_recovery: config.error_recovery.unwrap_or_default(),
+      strict_preludes: config.strict_preludes.unwrap_or_default(),
+      strict: config.strict.unwrap_or_default(),
+      strict_exceptions: config.strict_exceptions.as_ref(),
+      preserve_calc: minify_config(&config.minify, &config.minify_options).and_then(|c| c.calc) == Some(false),
+      preserve_var_fallbacks: config.preserve_var_fallbacks.unwrap_or_default(),
+      ignore_properties: config.ignore_properties.as_ref(),
+      custom_at_rules: config.custom_at_rules.as_ref(),
+      verbatim_values: config.minify_whitespace_only.unwrap_or_default(),
+      comments: config.comments,
+    }
+  }
+}
+
+impl<'a> From<&'a BundleConfig> for ParserConfig<'a> {
+  fn from(config: &'a BundleConfig) -> Self {
+    ParserConfig {
+      filename: Some(config.filename.as_str()),
+      drafts: config.drafts.as_ref(),
+      non_standard: config.non_standard.as_ref(),
+      css_modules: config.css_modules.as_ref(),
+      error_recovery: config.error_recovery.unwrap_or_default(),
+      strict_preludes: config.strict_preludes.unwrap_or_default(),
+      strict: config.strict.unwrap_or_default(),
+      strict_exceptions: config.strict_exceptions.as_ref(),
+      preserve_calc: minify_config(&config.minify, &config.minify_options).and_then(|c| c.calc) == Some(false),
+      preserve_var_fallbacks: config.preserve_var_fallbacks.unwrap_or_default(),
+      ignore_properties: config.ignore_properties.as_ref(),
+      custom_at_rules: config.custom_at_rules.as_ref(),
+      verbatim_values: config.minify_whitespace_only.unwrap_or_default(),
+      comments: config.comments,
+    }
+  }
+}
+
+/// Returns the parser options for the given options.
+fn parser_options<'o, 'i, E: std::error::Error>(
+  config: ParserConfig<'o>,
+  warnings: &Option<Arc<RwLock<Vec<Error<ParserError<'i>>>>>>,
+) -> Result<ParserOptions<'o, 'i>, CompileError<'i, E>> {
+  let drafts = config.drafts;
+  Ok(ParserOptions {
+    filename: config.filename.unwrap_or_default().into(),
+    nesting: matches!(drafts, Some(d) if d.nesting),
+    nesting_spec: drafts.map_or(NestingSpec::Legacy, |d| d.nesting_spec()),
+    custom_media: matches!(drafts, Some(d) if d.custom_media),
+    scope: matches!(drafts, Some(d) if d.scope),
+    css_modules: if let Some(css_modules) = config.css_modules {
+      match css_modules {
+        CssModulesOption::Bool(true) => Some(lightningcss::css_modules::Config::default()),
+        CssModulesOption::Bool(false) => None,
+        CssModulesOption::Config(c) => Some(lightningcss::css_modules::Config {
+          pattern: if let Some(pattern) = c.pattern.as_ref() {
+            match lightningcss::css_modules::Pattern::parse(pattern) {
+              Ok(p) => p,
+              Err(e) => return Err(CompileError::PatternError(e)),
+            }
+          } else {
+            Default::default()
+          },
+          dashed_idents: c.dashed_idents.unwrap_or_default(),
+          generate_name: None,
+          hash_version: hash_version(c.hash_version)?,
+          specificity: c.specificity.unwrap_or_default(),
+          export_selectors: c.export_selectors.unwrap_or_default(),
+          scope_deep_selectors: c.scope_deep_selectors.unwrap_or_default(),
+        }),
+      }
+    } else {
+      None
+    },
+    source_index: 0,
+    error_recovery: config.error_recovery,
+    strict_preludes: config.strict_preludes,
+    strict: config.strict,
+    strict_exceptions: config.strict_exceptions.cloned(),
+    preserve_calc: config.preserve_calc,
+    preserve_var_fallbacks: config.preserve_var_fallbacks,
+    ignore_properties: config.ignore_properties.cloned(),
+    custom_at_rules: custom_at_rules(config.custom_at_rules),
+    verbatim_values: config.verbatim_values,
+    deep_selectors: matches!(config.non_standard, Some(n) if n.deep_selectors),
+    warnings: warnings.clone(),
+    comments: comments(config.comments),
+    ..ParserOptions::default()
+  })
+}
+
+/// The result of `validate()`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ValidateResult<'i> {
+  warnings: Vec<Warning<'i>>,
+  /// The error that stopped parsing, if any. With error recovery, invalid rules and
+  /// declarations are reported as warnings instead.
+  errors: Vec<Warning<'i>>,
+  warnings_truncated: bool,
+}
+
+/// Parses the code and collects warnings and errors, without minifying or printing it.
+fn validate_code<'i>(
+  code: &'i str,
+  config: &Config,
+) -> Result<ValidateResult<'i>, CompileError<'i, std::io::Error>> {
+  let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+  let errors = match StyleSheet::parse(code, parser_options(config.into(), &warnings)?) {
+    Ok(_) => Vec::new(),
+    Err(err) => vec![Warning::from(err)],
+  };
+
+  let parsed = warning_count(&warnings);
+  let mut warnings = collect_warnings(warnings, parsed, Arc::new(RwLock::new(Vec::new())));
+  let warnings_truncated = limit_warnings(&mut warnings, config.warnings_limit);
+  Ok(ValidateResult {
+    warnings,
+    errors,
+    warnings_truncated,
+  })
+}
+
+/// Called with the rules and declarations of a style sheet after it is parsed, and before it is
+/// minified. Nodes that are not visited are not converted to JS at all.
+trait Visitor<'i> {
+  /// Whether to call `visit_rule` for a rule.
+  fn visits_rule(&self, rule: &CssRule<'i>) -> bool;
+  /// Returns a rule to replace the given one with, if any.
+  fn visit_rule(&mut self, rule: &CssRule<'i>) -> Result<Option<CssRule<'i>>, String>;
+  /// Whether to call `visit_declaration` for a declaration.
+  fn visits_declaration(&self, property: &Property<'i>) -> bool;
+  /// Returns a declaration to replace the given one with, if any.
+  fn visit_declaration(&mut self, property: &Property<'i>) -> Result<Option<Property<'i>>, String>;
+}
+
+/// Visits the rules in a list, followed by their declarations and nested rules.
+fn visit_rules<'i>(rules: &mut CssRuleList<'i>, visitor: &mut dyn Visitor<'i>) -> Result<(), String> {
+  for rule in &mut rules.0 {
+    if visitor.visits_rule(rule) {
+      if let Some(replacement) = visitor.visit_rule(rule)? {
+        *rule = replacement;
+      }
+    }
+
+    match rule {
+      CssRule::Style(style) => visit_style_rule(style, visitor)?,
+      CssRule::Nesting(nesting) => visit_style_rule(&mut nesting.style, visitor)?,
+      CssRule::Media(media) => visit_rules(&mut media.rules, visitor)?,
+      CssRule::Supports(supports) => visit_rules(&mut supports.rules, visitor)?,
+      CssRule::Container(container) => visit_rules(&mut container.rules, visitor)?,
+      CssRule::LayerBlock(layer) => visit_rules(&mut layer.rules, visitor)?,
+      CssRule::Scope(scope) => visit_rules(&mut scope.rules, visitor)?,
+      CssRule::MozDocument(document) => visit_rules(&mut document.rules, visitor)?,
+      _ => {}
+    }
+  }
+
+  Ok(())
+}
+
+fn visit_style_rule<'i>(style: &mut StyleRule<'i>, visitor: &mut dyn Visitor<'i>) -> Result<(), String> {
+  let declarations = &mut style.declarations;
+  for property in declarations
+    .important_declarations
+    .iter_mut()
+    .chain(declarations.declarations.iter_mut())
+  {
+    if visitor.visits_declaration(property) {
+      if let Some(replacement) = visitor.visit_declaration(property)? {
+        *property = replacement;
+      }
+    }
+  }
+
+  visit_rules(&mut style.rules, visitor)
+}
+
+/// Compiles a single file. When a provider is given, `@import` rules are inlined by
+/// bundling the file, which the provider must return as the entry. When a visitor is
+/// given, it is called with the parsed style sheet before it is minified.
+fn compile<'i, P: SourceProvider>(
+  code: &'i str,
+  config: &Config,
+  fs: Option<&'i P>,
+  visitor: Option<&mut dyn Visitor<'i>>,
+) -> Result<TransformResult<'i>, CompileError<'i, P::Error>> {
+  let drafts = config.drafts.as_ref();
+  let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+  let printer_warnings = Arc::new(RwLock::new(Vec::new()));
+
+  let filename = config.filename.clone().unwrap_or_default();
+  let mut source_map = if config.source_map.unwrap_or_default() {
+    let mut sm = SourceMap::new("/");
+    // The bundler adds a source for each file it loads.
+    if fs.is_none() {
+      sm.add_source(&filename);
+      if config.source_map_include_content.unwrap_or(true) {
+        sm.set_source_content(0, code)?;
+      }
+    }
+    Some(sm)
+  } else {
+    None
+  };
+  let mut legacy_source_map = None;
+
+  let (res, legacy, ast, parsed) = {
+    let parser_options = parser_options(config.into(), &warnings)?;
+
+    let mut stylesheet = if let Some(fs) = fs {
+      let mut bundler = Bundler::new(fs, source_map.as_mut(), parser_options);
+      bundler.bundle(Path::new(&filename))?
+    } else {
+      StyleSheet::parse(&code, parser_options)?
+    };
+    let parsed = warning_count(&warnings);
+    if let Some(visitor) = visitor {
+      visit_rules(&mut stylesheet.rules, visitor).map_err(CompileError::VisitorError)?;
+    }
+
+    // When dual targets are requested, the parsed style sheet is shared, and only
+    // minification and printing are repeated for the legacy targets.
+    let (res, legacy, ast) = if let Some(dual_targets) = &config.dual_targets {
+      let mut legacy_stylesheet = stylesheet.clone();
+      legacy_source_map = source_map.clone();
+      let legacy = minify_and_print(
+        &mut legacy_stylesheet,
+        config,
+        Some(dual_targets.legacy),
+        legacy_source_map.as_mut(),
+        None,
+        &printer_warnings,
+      )?;
+      let res = minify_and_print(
+        &mut stylesheet,
+        config,
+        Some(dual_targets.modern),
+        source_map.as_mut(),
+        config.source_map_url.as_deref(),
+        &printer_warnings,
+      )?;
+      (res, Some(legacy), to_ast(&stylesheet, config)?)
+    } else {
+      let res = minify_and_print(
+        &mut stylesheet,
+        config,
+        config.targets,
+        source_map.as_mut(),
+        config.source_map_url.as_deref(),
+        &printer_warnings,
+      )?;
+      (res, None, to_ast(&stylesheet, config)?)
+    };
+    (res, legacy, ast, parsed)
+  };
+
+  let verification_errors = if config.verify_output.unwrap_or_default() {
+    let mut errors = verify_output(&res.code, &filename, drafts, false, source_map.as_mut(), false);
+    if let Some(legacy) = &legacy {
+      errors.extend(verify_output(
+        &legacy.code,
+        &filename,
+        drafts,
+        false,
+        legacy_source_map.as_mut(),
+        true,
+      ));
+    }
+    Some(errors)
+  } else {
+    None
+  };
+
+  let include_content = config.source_map_include_content.unwrap_or(true);
+  let map = source_map_to_json(source_map, &config.input_source_map, include_content);
+  let map_legacy = source_map_to_json(legacy_source_map, &config.input_source_map, include_content);
+
+  let mut warnings = collect_warnings(warnings, parsed, printer_warnings);
+
+  // Without a way to read files, e.g. in WebAssembly, imports are left as is.
+  if fs.is_none() && config.inline_imports.unwrap_or_default() {
+    warnings.push(Warning {
+      message: "`inlineImports` requires a way to read files, so @import rules were not inlined".into(),
+      code: "InlineImportsUnavailable",
+      severity: Severity::Warning,
+      stage: WarningStage::Parse,
+      count: None,
+      kind: Some("InlineImportsUnavailable"),
+      data: None,
+      loc: None,
+    });
+  }
+
+  let exports_hash = match &res.exports {
+    Some(exports) if config.exports_hash.unwrap_or_default() => Some(exports_hash(exports)),
+    _ => None,
+  };
+  let warnings_truncated = limit_warnings(&mut warnings, config.warnings_limit);
+
+  Ok(TransformResult {
+    code: res.code.into_bytes(),
+    map: map.map(|m| m.into_bytes()),
+    code_legacy: legacy.map(|res| res.code.into_bytes()),
+    map_legacy: map_legacy.map(|m| m.into_bytes()),
+    exports: res.exports,
+    exports_hash,
+    animations: res.animations,
+    references: res.references,
+    dependencies: res.dependencies,
+    renamed_keyframes: res.renamed_keyframes,
+    inlined_custom_properties: res.inlined_custom_properties,
+    removed_symbols: res.removed_symbols,
+    unmatched_symbols: res.unmatched_symbols,
+    features_used: feature_names(res.features_used),
+    license_comments: None,
+    warnings,
+    warnings_truncated,
+    verification_errors,
+    ast,
+    chunks: None,
+  })
+}
+
+// Serializes the minified style sheet, if `returnAst` is enabled.
+fn to_ast<'i, E: std::error::Error>(
+  stylesheet: &StyleSheet<'i, '_>,
+  config: &Config,
+) -> Result<Option<serde_json::Value>, CompileError<'i, E>> {
+  if config.return_ast.unwrap_or_default() {
+    Ok(Some(serde_json::to_value(stylesheet).map_err(CompileError::AstError)?))
+  } else {
+    Ok(None)
+  }
+}
+
+fn minify_and_print<'i, E: std::error::Error>(
+  stylesheet: &mut StyleSheet<'i, '_>,
+  config: &Config,
+  targets: Option<Browsers>,
+  source_map: Option<&mut SourceMap>,
+  source_map_url: Option<&str>,
+  warnings: &Arc<RwLock<Vec<PrinterError>>>,
+) -> Result<ToCssResult, CompileError<'i, E>> {
+  let comments = comments(config.comments);
+  // Whitespace-only minification prints the style sheet as parsed, so that no value is rewritten.
+  if config.minify_whitespace_only.unwrap_or_default() {
+    return Ok(stylesheet.to_css(PrinterOptions {
+      source_map,
+      analyze_dependencies: dependency_options(&config.analyze_dependencies),
+      pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
+      source_map_url,
+      comments,
+      warnings: Some(warnings.clone()),
+      ..PrinterOptions::whitespace_only()
+    })?);
+  }
+
+  // Prefix-only mode adds vendor prefixes for the targets, and prints without targets so that nothing else is compiled.
+  if config.prefix_only.unwrap_or_default() {
+    stylesheet.minify(MinifyOptions {
+      targets,
+      prefix_only: true,
+      ..MinifyOptions::default()
+    })?;
+    return Ok(stylesheet.to_css(PrinterOptions {
+      minify: config.minify.as_ref().map_or(false, MinifyOption::is_enabled),
+      source_map,
+      analyze_dependencies: dependency_options(&config.analyze_dependencies),
+      pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
+      source_map_url,
+      minify_colors: false,
+      minify_selectors: false,
+      indent: &indent(&config.pretty),
+      comments,
+      warnings: Some(warnings.clone()),
+      ..PrinterOptions::default()
+    })?);
+  }
+
+  let minify_config = minify_config(&config.minify, &config.minify_options);
+  let mut include = features(&config.include)?;
+  let mut exclude = features(&config.exclude)?;
+  logical_properties(config.logical_properties, &mut include, &mut exclude);
+  let mut options = minify_options(targets, &config.unused_symbols, minify_config);
+  options.include = include;
+  options.exclude = exclude;
+  options.strip_deep_selectors = matches!(&config.non_standard, Some(n) if n.strip_deep_selectors);
+  options.error_on_unsupported = config.error_on_unsupported.unwrap_or_default();
+  // License comments at the top of the file are kept along with the other comments, unless set explicitly.
+  if comments != Comments::None && minify_config.and_then(|c| c.remove_comments).is_none() {
+    options.remove_comments = false;
+  }
+  stylesheet.minify(options)?;
+
+  Ok(stylesheet.to_css(PrinterOptions {
+    minify: config.minify.as_ref().map_or(false, MinifyOption::is_enabled),
+    source_map,
+    targets,
+    analyze_dependencies: dependency_options(&config.analyze_dependencies),
+    pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
+    source_map_url,
+    minify_colors: minify_config.and_then(|c| c.colors).unwrap_or(true),
+    color_format: color_format(config.color_format),
+    quotes: config.quotes.map(QuoteStyle::from),
+    minify_selectors: minify_config.and_then(|c| c.selectors).unwrap_or(true),
+    indent: &indent(&config.pretty),
+    max_line_width: config.pretty.as_ref().and_then(|p| p.max_line_width),
+    blank_lines_between_rules: config.pretty.as_ref().and_then(|p| p.blank_lines_between_rules).unwrap_or(true),
+    precision: precision(&config.precision),
+    include,
+    exclude,
+    comments,
+    warnings: Some(warnings.clone()),
+  })?)
+}
+
+fn dependency_options(analyze_dependencies: &Option<AnalyzeDependenciesOption>) -> Option<DependencyOptions> {
+  match analyze_dependencies {
+    Some(AnalyzeDependenciesOption::Bool(true)) => Some(DependencyOptions { remove_imports: true }),
+    Some(AnalyzeDependenciesOption::Config(c)) => Some(DependencyOptions {
+      remove_imports: !c.preserve_imports,
+    }),
+    _ => None,
+  }
+}
+
+fn source_map_to_json(
+  source_map: Option<SourceMap>,
+  input_source_map: &Option<String>,
+  include_content: bool,
+) -> Option<String> {
+  if let Some(mut source_map) = source_map {
+    if let Some(input_source_map) = input_source_map {
+      if let Ok(mut sm) = SourceMap::from_json("/", input_source_map) {
+        let _ = source_map.extends(&mut sm);
+      }
+    }
+
+    let json = source_map.to_json(None).ok()?;
+    if include_content {
+      return Some(json);
+    }
+
+    // Source content may also come from input source maps and bundled files, so remove it from the output.
+    let mut value: serde_json::Value = serde_json::from_str(&json).ok()?;
+    if let Some(map) = value.as_object_mut() {
+      map.remove("sourcesContent");
+    }
+    serde_json::to_string(&value).ok()
+  } else {
+    None
+  }
+}
+
+fn compile_bundle<'i, P: SourceProvider>(
+  fs: &'i P,
+  config: &BundleConfig,
+  cache: Option<&BundleCache<'i>>,
+  generate_name: Option<NameGenerator>,
+) -> Result<TransformResult<'i>, CompileError<'i, P::Error>> {
+  let mut source_map = if config.source_map.unwrap_or_default() {
+    Some(SourceMap::new("/"))
+  } else {
+    None
+  };
+  let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+  let printer_warnings = Arc::new(RwLock::new(Vec::new()));
+  let parsed;
+  let minify_config = minify_config(&config.minify, &config.minify_options);
+  let hoist_license_comments = config.preserve_comments == Some(PreserveComments::LicenseHoist);
+  let mut license_comments = None;
+  let mut chunks = None;
+  let res = {
+    let mut parser_options = parser_options(config.into(), &warnings)?;
+    if let Some(css_modules) = &mut parser_options.css_modules {
+      css_modules.generate_name = generate_name;
+    }
+
+    let mut bundler = match cache {
+      Some(cache) => Bundler::with_cache(fs, source_map.as_mut(), parser_options, cache),
+      None => Bundler::new(fs, source_map.as_mut(), parser_options),
+    };
+    bundler.hoist_license_comments(hoist_license_comments);
+    bundler.hoist_root_variables(config.hoist_root_variables.unwrap_or_default());
+    bundler.hoist_layer_statements(config.hoist_layer_statements.unwrap_or_default());
+    bundler.targets(config.targets);
+    let mut stylesheet = bundler.bundle(Path::new(&config.filename))?;
+    let skipped_imports = bundler.take_skipped_imports();
+    parsed = warning_count(&warnings);
+
+    let mut include = features(&config.include)?;
+    let mut exclude = features(&config.exclude)?;
+    logical_properties(config.logical_properties, &mut include, &mut exclude);
+    let comments = comments(config.comments);
+    let mut options = minify_options(config.targets, &config.unused_symbols, minify_config);
+    options.include = include;
+    options.exclude = exclude;
+    options.strip_deep_selectors = matches!(&config.non_standard, Some(n) if n.strip_deep_selectors);
+    options.prefix_only = config.prefix_only.unwrap_or_default();
+    options.error_on_unsupported = config.error_on_unsupported.unwrap_or_default();
+    if comments != Comments::None && minify_config.and_then(|c| c.remove_comments).is_none() {
+      options.remove_comments = false;
+    }
+    if hoist_license_comments {
+      options.remove_comments = false;
+      license_comments = Some(stylesheet.license_comments.iter().map(|c| c.to_string()).collect());
+    }
+    if !config.minify_whitespace_only.unwrap_or_default() {
+      stylesheet.minify(options)?;
+    }
+
+    let indent = indent(&config.pretty);
+    if config.split_by_layer.unwrap_or_default() {
+      // Each chunk gets its own source map, with the same sources as the whole bundle.
+      let mut results = Vec::new();
+      for (layer, chunk) in stylesheet.split_by_layer() {
+        let mut chunk_source_map = source_map.clone();
+        let res = chunk.to_css(bundle_printer_options(
+          config,
+          &indent,
+          include,
+          exclude,
+          comments,
+          chunk_source_map.as_mut(),
+        ))?;
+        results.push(LayerChunkResult {
+          layer: layer.map(|layer| layer.to_css_string(PrinterOptions::default()).unwrap()),
+          code: res.code.into_bytes(),
+          map: source_map_to_json(
+            chunk_source_map,
+            &None,
+            config.source_map_include_content.unwrap_or(true),
+          )
+          .map(|m| m.into_bytes()),
+        });
       }
+      chunks = Some(results);
     }
-  }
-}
 
-#[cfg(not(target_arch = "wasm32"))]
-#[module_exports]
-fn init(mut exports: JsObject) -> napi::Result<()> {
-  exports.create_named_method("transform", transform)?;
-  exports.create_named_method("transformStyleAttribute", transform_style_attribute)?;
-  exports.create_named_method("bundle", bundle::bundle)?;
-  exports.create_named_method("bundleAsync", bundle::bundle_async)?;
+    let mut options = bundle_printer_options(config, &indent, include, exclude, comments, source_map.as_mut());
+    options.source_map_url = config.source_map_url.as_deref();
+    options.warnings = Some(printer_warnings.clone());
+    let mut res = stylesheet.to_css(options)?;
 
-  Ok(())
+    // Report the imports skipped by evaluating their supports() conditions as dependencies.
+    if let Some(dependencies) = &mut res.dependencies {
+      dependencies.extend(skipped_imports.into_iter().map(Dependency::Import));
+    }
+    res
+  };
+
+  let verification_errors = if config.verify_output.unwrap_or_default() {
+    Some(verify_output(
+      &res.code,
+      &config.filename,
+      config.drafts.as_ref(),
+      false,
+      source_map.as_mut(),
+      false,
+    ))
+  } else {
+    None
+  };
+
+  let map = source_map_to_json(source_map, &None, config.source_map_include_content.unwrap_or(true));
+  let mut warnings = collect_warnings(warnings, parsed, printer_warnings);
+  let warnings_truncated = limit_warnings(&mut warnings, config.warnings_limit);
+
+  Ok(TransformResult {
+    code: res.code.into_bytes(),
+    map: map.map(|m| m.into_bytes()),
+    code_legacy: None,
+    map_legacy: None,
+    exports: res.exports,
+    exports_hash: None,
+    animations: res.animations,
+    references: res.references,
+    dependencies: res.dependencies,
+    renamed_keyframes: res.renamed_keyframes,
+    inlined_custom_properties: res.inlined_custom_properties,
+    removed_symbols: res.removed_symbols,
+    unmatched_symbols: res.unmatched_symbols,
+    features_used: feature_names(res.features_used),
+    license_comments,
+    warnings,
+    warnings_truncated,
+    verification_errors,
+    ast: None,
+    chunks,
+  })
 }
 
-// ---------------------------------------------
+fn bundle_printer_options<'a>(
+  config: &'a BundleConfig,
+  indent: &'a str,
+  include: Features,
+  exclude: Features,
+  comments: Comments,
+  source_map: Option<&'a mut SourceMap>,
+) -> PrinterOptions<'a> {
+  if config.minify_whitespace_only.unwrap_or_default() {
+    return PrinterOptions {
+      source_map,
+      analyze_dependencies: dependency_options(&config.analyze_dependencies),
+      pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
+      comments,
+      ..PrinterOptions::whitespace_only()
+    };
+  }
+
+  // Prefix-only mode prints without targets, so that nothing else is compiled.
+  if config.prefix_only.unwrap_or_default() {
+    return PrinterOptions {
+      minify: config.minify.as_ref().map_or(false, MinifyOption::is_enabled),
+      source_map,
+      analyze_dependencies: dependency_options(&config.analyze_dependencies),
+      pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
+      minify_colors: false,
+      minify_selectors: false,
+      indent,
+      comments,
+      ..PrinterOptions::default()
+    };
+  }
+
+  let minify_config = minify_config(&config.minify, &config.minify_options);
+  PrinterOptions {
+    minify: config.minify.as_ref().map_or(false, MinifyOption::is_enabled),
+    source_map,
+    targets: config.targets,
+    analyze_dependencies: dependency_options(&config.analyze_dependencies),
+    pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
+    source_map_url: None,
+    minify_colors: minify_config.and_then(|c| c.colors).unwrap_or(true),
+    color_format: color_format(config.color_format),
+    quotes: config.quotes.map(QuoteStyle::from),
+    minify_selectors: minify_config.and_then(|c| c.selectors).unwrap_or(true),
+    indent,
+    max_line_width: config.pretty.as_ref().and_then(|p| p.max_line_width),
+    blank_lines_between_rules: config.pretty.as_ref().and_then(|p| p.blank_lines_between_rules).unwrap_or(true),
+    precision: precision(&config.precision),
+    include,
+    exclude,
+    comments,
+    warnings: None,
+  }
+}
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Config {
+struct AttrConfig {
   pub filename: Option<String>,
   #[serde(with = "serde_bytes")]
   pub code: Vec<u8>,
+  #[serde(default, deserialize_with = "deserialize_targets")]
   pub targets: Option<Browsers>,
-  pub minify: Option<bool>,
-  pub source_map: Option<bool>,
-  pub input_source_map: Option<String>,
-  pub drafts: Option<Drafts>,
-  pub css_modules: Option<CssModulesOption>,
-  pub analyze_dependencies: Option<AnalyzeDependenciesOption>,
-  pub pseudo_classes: Option<OwnedPseudoClasses>,
-  pub unused_symbols: Option<HashSet<String>>,
-  pub error_recovery: Option<bool>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-enum AnalyzeDependenciesOption {
-  Bool(bool),
-  Config(AnalyzeDependenciesConfig),
+  #[serde(default)]
+  pub minify: bool,
+  #[serde(default)]
+  pub analyze_dependencies: bool,
+  #[serde(default)]
+  pub error_recovery: bool,
+  pub warnings_limit: Option<usize>,
+  pub encoding: Option<Encoding>,
+  #[serde(default)]
+  pub decode_html_entities: bool,
+  #[serde(default)]
+  pub verify_output: bool,
+  pub source_offset: Option<SourceOffset>,
 }
 
+/// The location of the start of a style attribute within the file it came from, as a one-based
+/// line and column in the same form as reported error locations.
 #[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct AnalyzeDependenciesConfig {
-  preserve_imports: bool,
+struct SourceOffset {
+  line: u32,
+  column: u32,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(untagged)]
-enum CssModulesOption {
-  Bool(bool),
-  Config(CssModulesConfig),
+impl SourceOffset {
+  /// Maps a location within the attribute to the corresponding location in the file.
+  fn apply(&self, loc: &mut ErrorLocation) {
+    if loc.line == 0 {
+      loc.column += self.column.saturating_sub(1);
+    }
+    loc.line += self.line.saturating_sub(1);
+  }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
-struct CssModulesConfig {
-  pattern: Option<String>,
-  dashed_idents: Option<bool>,
+struct AttrResult<'i> {
+  #[serde(with = "serde_bytes")]
+  code: Vec<u8>,
+  dependencies: Option<Vec<Dependency>>,
+  warnings: Vec<Warning<'i>>,
+  warnings_truncated: bool,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  verification_errors: Option<Vec<VerificationError>>,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct BundleConfig {
-  pub filename: String,
-  pub targets: Option<Browsers>,
-  pub minify: Option<bool>,
-  pub source_map: Option<bool>,
-  pub drafts: Option<Drafts>,
-  pub css_modules: Option<CssModulesOption>,
-  pub analyze_dependencies: Option<AnalyzeDependenciesOption>,
-  pub pseudo_classes: Option<OwnedPseudoClasses>,
-  pub unused_symbols: Option<HashSet<String>>,
-  pub error_recovery: Option<bool>,
+#[cfg(not(target_arch = "wasm32"))]
+impl<'i> AttrResult<'i> {
+  fn into_js(self, ctx: CallContext) -> napi::Result<JsUnknown> {
+    // Manually construct buffers so we avoid a copy and work around
+    // https://github.com/napi-rs/napi-rs/issues/1124.
+    let mut obj = ctx.env.create_object()?;
+    let buf = ctx.env.create_buffer_with_data(self.code)?;
+    obj.set_named_property("code", buf.into_raw())?;
+    obj.set_named_property("dependencies", ctx.env.to_js_value(&self.dependencies)?)?;
+    obj.set_named_property("warnings", ctx.env.to_js_value(&self.warnings)?)?;
+    obj.set_named_property("warningsTruncated", ctx.env.get_boolean(self.warnings_truncated)?)?;
+    if let Some(verification_errors) = self.verification_errors {
+      obj.set_named_property("verificationErrors", ctx.env.to_js_value(&verification_errors)?)?;
+    }
+    Ok(obj.into_unknown())
+  }
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct OwnedPseudoClasses {
-  pub hover: Option<String>,
-  pub active: Option<String>,
-  pub focus: Option<String>,
-  pub focus_visible: Option<String>,
-  pub focus_within: Option<String>,
+/// Style attribute code extracted from HTML, with character references decoded, and the offset
+/// in the original code of each byte.
+struct DecodedAttr {
+  code: String,
+  offsets: Vec<usize>,
 }
 
-impl<'a> Into<PseudoClasses<'a>> for &'a OwnedPseudoClasses {
-  fn into(self) -> PseudoClasses<'a> {
-    PseudoClasses {
-      hover: self.hover.as_deref(),
-      active: self.active.as_deref(),
-      focus: self.focus.as_deref(),
-      focus_visible: self.focus_visible.as_deref(),
-      focus_within: self.focus_within.as_deref(),
+const NAMED_CHARACTER_REFERENCES: &[(&str, char)] = &[
+  ("amp", '&'),
+  ("lt", '<'),
+  ("gt", '>'),
+  ("quot", '"'),
+  ("apos", '\''),
+  ("nbsp", '\u{a0}'),
+  ("Tab", '\t'),
+  ("NewLine", '\n'),
+];
+
+impl DecodedAttr {
+  /// Decodes named and numeric character references, and replaces raw tabs and newlines
+  /// with spaces, as in HTML attribute value normalization.
+  fn new(original: &str) -> DecodedAttr {
+    let mut code = String::with_capacity(original.len());
+    let mut offsets = Vec::with_capacity(original.len() + 1);
+    let mut i = 0;
+    while i < original.len() {
+      let rest = &original[i..];
+      let (c, len) = if rest.starts_with("\r\n") {
+        (' ', 2)
+      } else if let Some((c, len)) = rest.strip_prefix('&').and_then(decode_character_reference) {
+        (c, len + 1)
+      } else {
+        let c = rest.chars().next().unwrap();
+        (
+          if matches!(c, '\t' | '\n' | '\r' | '\x0c') {
+            ' '
+          } else {
+            c
+          },
+          c.len_utf8(),
+        )
+      };
+
+      offsets.extend(std::iter::repeat(i).take(c.len_utf8()));
+      code.push(c);
+      i += len;
     }
+
+    offsets.push(original.len());
+    DecodedAttr { code, offsets }
   }
-}
 
-#[derive(Serialize, Debug, Deserialize, Default)]
-#[serde(rename_all = "camelCase")]
-struct Drafts {
-  #[serde(default)]
-  nesting: bool,
-  #[serde(default)]
-  custom_media: bool,
+  /// Maps a location in the decoded code to the corresponding location in the original code.
+  fn original_location(&self, original: &str, loc: &mut ErrorLocation) {
+    let offset = self.offsets[location_to_offset(&self.code, loc.line, loc.column)];
+    let (line, column) = offset_to_location(original, offset);
+    loc.line = line;
+    loc.column = column;
+  }
 }
 
-fn compile<'i>(code: &'i str, config: &Config) -> Result<TransformResult<'i>, CompileError<'i, std::io::Error>> {
-  let drafts = config.drafts.as_ref();
-  let warnings = Some(Arc::new(RwLock::new(Vec::new())));
-
-  let filename = config.filename.clone().unwrap_or_default();
-  let mut source_map = if config.source_map.unwrap_or_default() {
-    let mut sm = SourceMap::new("/");
-    sm.add_source(&filename);
-    sm.set_source_content(0, code)?;
-    Some(sm)
+/// Decodes a character reference following a `&`, returning the character and the length of the reference.
+fn decode_character_reference(s: &str) -> Option<(char, usize)> {
+  let end = s.bytes().take(32).position(|b| b == b';')?;
+  let name = &s[..end];
+  let c = if let Some(number) = name.strip_prefix('#') {
+    let value = match number.strip_prefix(|c| c == 'x' || c == 'X') {
+      Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+      None => number.parse().ok()?,
+    };
+    char::from_u32(value).filter(|c| *c != '\0')?
   } else {
-    None
+    NAMED_CHARACTER_REFERENCES.iter().find(|(n, _)| *n == name)?.1
   };
+  Some((c, end + 1))
+}
 
-  let res = {
-    let mut stylesheet = StyleSheet::parse(
-      &code,
-      ParserOptions {
-        filename: filename.clone(),
-        nesting: matches!(drafts, Some(d) if d.nesting),
-        custom_media: matches!(drafts, Some(d) if d.custom_media),
-        css_modules: if let Some(css_modules) = &config.css_modules {
-          match css_modules {
-            CssModulesOption::Bool(true) => Some(lightningcss::css_modules::Config::default()),
-            CssModulesOption::Bool(false) => None,
-            CssModulesOption::Config(c) => Some(lightningcss::css_modules::Config {
-              pattern: if let Some(pattern) = c.pattern.as_ref() {
-                match lightningcss::css_modules::Pattern::parse(pattern) {
-                  Ok(p) => p,
-                  Err(e) => return Err(CompileError::PatternError(e)),
-                }
-              } else {
-                Default::default()
-              },
-              dashed_idents: c.dashed_idents.unwrap_or_default(),
-            }),
-          }
-        } else {
-          None
-        },
-        source_index: 0,
-        error_recovery: config.error_recovery.unwrap_or_default(),
-        warnings: warnings.clone(),
-      },
-    )?;
-    stylesheet.minify(MinifyOptions {
-      targets: config.targets,
-      unused_symbols: config.unused_symbols.clone().unwrap_or_default(),
-    })?;
+/// The character encoding of the `code` buffer passed to `transform` and `transformStyleAttribute`.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+enum Encoding {
+  #[serde(rename = "utf-8", alias = "utf8")]
+  Utf8,
+  /// ISO-8859-1, in which each byte is the code point of a character.
+  #[serde(rename = "latin1", alias = "iso-8859-1")]
+  Latin1,
+}
 
-    stylesheet.to_css(PrinterOptions {
-      minify: config.minify.unwrap_or_default(),
-      source_map: source_map.as_mut(),
-      targets: config.targets,
-      analyze_dependencies: if let Some(d) = &config.analyze_dependencies {
-        match d {
-          AnalyzeDependenciesOption::Bool(b) if *b => Some(DependencyOptions { remove_imports: true }),
-          AnalyzeDependenciesOption::Config(c) => Some(DependencyOptions {
-            remove_imports: !c.preserve_imports,
-          }),
-          _ => None,
-        }
-      } else {
-        None
-      },
-      pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
-    })?
+/// Decodes the `code` buffer passed to a transform function. A UTF-8 byte order mark is removed.
+/// When no encoding is given, the one declared by a `@charset` rule at the start of the code is used,
+/// and UTF-8 otherwise.
+fn decode_code(
+  code: &[u8],
+  encoding: Option<Encoding>,
+) -> Result<Cow<'_, str>, CompileError<'static, std::io::Error>> {
+  const BOM: &[u8] = b"\xEF\xBB\xBF";
+  let (code, bom_len) = match code.strip_prefix(BOM) {
+    Some(code) => (code, BOM.len()),
+    None => (code, 0),
   };
 
-  let map = if let Some(mut source_map) = source_map {
-    if let Some(input_source_map) = &config.input_source_map {
-      if let Ok(mut sm) = SourceMap::from_json("/", input_source_map) {
-        let _ = source_map.extends(&mut sm);
+  match encoding.or_else(|| charset(code)) {
+    Some(Encoding::Latin1) => Ok(Cow::Owned(code.iter().map(|b| *b as char).collect())),
+    _ => match utf8_code(code) {
+      Ok(code) => Ok(Cow::Borrowed(code)),
+      Err(CompileError::EncodingError(offset)) => Err(CompileError::EncodingError(bom_len + offset)),
+      Err(err) => Err(err),
+    },
+  }
+}
+
+/// Validates that a code buffer is UTF-8, returning an error with the byte offset of the first invalid sequence.
+fn utf8_code(code: &[u8]) -> Result<&str, CompileError<'static, std::io::Error>> {
+  std::str::from_utf8(code).map_err(|err| CompileError::EncodingError(err.valid_up_to()))
+}
+
+/// Returns the encoding declared by a `@charset` rule at the start of the code, if it is supported.
+fn charset(code: &[u8]) -> Option<Encoding> {
+  // Per spec, the rule must be written exactly in this form to be recognized.
+  let name = code.strip_prefix(b"@charset \"")?;
+  let name = &name[..name.iter().position(|b| *b == b'"')?];
+  match name.to_ascii_lowercase().as_slice() {
+    b"utf-8" | b"utf8" => Some(Encoding::Utf8),
+    b"iso-8859-1" | b"latin1" => Some(Encoding::Latin1),
+    _ => None,
+  }
+}
+
+/// Returns the byte offset of a location reported by the parser, i.e. a zero-based line
+/// and a one-based column in UTF-16 code units.
+fn location_to_offset(code: &str, line: u32, column: u32) -> usize {
+  let mut cur_line = 0;
+  let mut cur_column = 1;
+  let mut chars = code.char_indices().peekable();
+  while let Some((i, c)) = chars.next() {
+    if cur_line > line || (cur_line == line && cur_column >= column) {
+      return i;
+    }
+
+    if matches!(c, '\n' | '\r' | '\x0c') {
+      if c == '\r' && matches!(chars.peek(), Some((_, '\n'))) {
+        chars.next();
       }
+      cur_line += 1;
+      cur_column = 1;
+    } else {
+      cur_column += c.len_utf16() as u32;
     }
+  }
 
-    source_map.to_json(None).ok()
-  } else {
-    None
-  };
+  code.len()
+}
 
-  Ok(TransformResult {
-    code: res.code.into_bytes(),
-    map: map.map(|m| m.into_bytes()),
-    exports: res.exports,
-    references: res.references,
-    dependencies: res.dependencies,
-    warnings: warnings.map_or(Vec::new(), |w| {
-      Arc::try_unwrap(w)
-        .unwrap()
-        .into_inner()
-        .unwrap()
-        .into_iter()
-        .map(|w| w.into())
-        .collect()
-    }),
-  })
+/// The inverse of `location_to_offset`.
+fn offset_to_location(code: &str, offset: usize) -> (u32, u32) {
+  let mut line = 0;
+  let mut column = 1;
+  let mut chars = code.char_indices().peekable();
+  while let Some((i, c)) = chars.next() {
+    if i >= offset {
+      break;
+    }
+
+    if matches!(c, '\n' | '\r' | '\x0c') {
+      if c == '\r' && matches!(chars.peek(), Some((_, '\n'))) {
+        chars.next();
+      }
+      line += 1;
+      column = 1;
+    } else {
+      column += c.len_utf16() as u32;
+    }
+  }
+
+  (line, column)
 }
 
-fn compile_bundle<'i, P: SourceProvider>(
-  fs: &'i P,
-  config: &BundleConfig,
-) -> Result<TransformResult<'i>, CompileError<'i, P::Error>> {
-  let mut source_map = if config.source_map.unwrap_or_default() {
-    Some(SourceMap::new("/"))
+/// Renders the line of a location reported by the parser along with the lines before and after it,
+/// and a caret under the column. Tabs before the column are repeated in the caret line so that it
+/// lines up with the code.
+fn code_frame(code: &str, line: u32, column: u32) -> String {
+  // Split lines the same way as the parser.
+  let mut lines = Vec::new();
+  let mut start = 0;
+  let mut chars = code.char_indices().peekable();
+  while let Some((i, c)) = chars.next() {
+    if matches!(c, '\n' | '\r' | '\x0c') {
+      lines.push(&code[start..i]);
+      start = i + 1;
+      if c == '\r' && matches!(chars.peek(), Some((_, '\n'))) {
+        chars.next();
+        start += 1;
+      }
+    }
+  }
+  lines.push(&code[start..]);
+
+  let line = (line as usize).min(lines.len() - 1);
+  let first = line.saturating_sub(1);
+  let last = (line + 1).min(lines.len() - 1);
+  let width = (last + 1).to_string().len();
+
+  let mut caret = String::new();
+  let mut cur_column = 1;
+  for c in lines[line].chars() {
+    if cur_column >= column {
+      break;
+    }
+    caret.push(if c == '\t' { '\t' } else { ' ' });
+    cur_column += c.len_utf16() as u32;
+  }
+  caret.push('^');
+
+  let mut frame = String::new();
+  for (i, text) in lines.iter().enumerate().take(last + 1).skip(first) {
+    let marker = if i == line { '>' } else { ' ' };
+    frame.push_str(format!("{} {:>width$} | {}", marker, i + 1, text, width = width).trim_end());
+    frame.push('\n');
+    if i == line {
+      frame.push_str(&format!("  {:width$} | {}\n", "", caret, width = width));
+    }
+  }
+  frame.pop();
+  frame
+}
+
+/// Compiles a style attribute. When `decoded` is given, it is compiled instead of `code`,
+/// and the locations of errors and warnings are mapped back to `code`.
+fn compile_attr<'i>(
+  code: &'i str,
+  config: &AttrConfig,
+  decoded: Option<&'i DecodedAttr>,
+) -> Result<AttrResult<'i>, CompileError<'i, std::io::Error>> {
+  let warnings = if config.error_recovery {
+    Some(Arc::new(RwLock::new(Vec::new())))
   } else {
     None
   };
-  let warnings = Some(Arc::new(RwLock::new(Vec::new())));
-  let res = {
-    let drafts = config.drafts.as_ref();
-    let parser_options = ParserOptions {
-      nesting: matches!(drafts, Some(d) if d.nesting),
-      custom_media: matches!(drafts, Some(d) if d.custom_media),
-      css_modules: if let Some(css_modules) = &config.css_modules {
-        match css_modules {
-          CssModulesOption::Bool(true) => Some(lightningcss::css_modules::Config::default()),
-          CssModulesOption::Bool(false) => None,
-          CssModulesOption::Config(c) => Some(lightningcss::css_modules::Config {
-            pattern: if let Some(pattern) = c.pattern.as_ref() {
-              match lightningcss::css_modules::Pattern::parse(pattern) {
-                Ok(p) => p,
-                Err(e) => return Err(CompileError::PatternError(e)),
-              }
-            } else {
-              Default::default()
-            },
-            dashed_idents: c.dashed_idents.unwrap_or_default(),
-          }),
-        }
-      } else {
-        None
-      },
-      error_recovery: config.error_recovery.unwrap_or_default(),
-      warnings: warnings.clone(),
-      ..ParserOptions::default()
-    };
-
-    let mut bundler = Bundler::new(fs, source_map.as_mut(), parser_options);
-    let mut stylesheet = bundler.bundle(Path::new(&config.filename))?;
-
-    stylesheet.minify(MinifyOptions {
+  let original_location = |loc: Option<&mut ErrorLocation>| {
+    if let Some(loc) = loc {
+      if let Some(decoded) = decoded {
+        decoded.original_location(code, loc);
+      }
+      if let Some(offset) = &config.source_offset {
+        offset.apply(loc);
+      }
+    }
+  };
+  let compile = || -> Result<ToCssResult, CompileError<'i, std::io::Error>> {
+    let mut attr = StyleAttribute::parse(
+      decoded.map_or(code, |decoded| decoded.code.as_str()),
+      parser_options(
+        ParserConfig {
+          filename: config.filename.as_deref(),
+          error_recovery: config.error_recovery,
+          ..ParserConfig::default()
+        },
+        &warnings,
+      )?,
+    )?;
+    attr.minify(MinifyOptions {
       targets: config.targets,
-      unused_symbols: config.unused_symbols.clone().unwrap_or_default(),
-    })?;
-
-    stylesheet.to_css(PrinterOptions {
-      minify: config.minify.unwrap_or_default(),
-      source_map: source_map.as_mut(),
+      ..MinifyOptions::default()
+    });
+    Ok(attr.to_css(PrinterOptions {
+      minify: config.minify,
+      source_map: None,
       targets: config.targets,
-      analyze_dependencies: if let Some(d) = &config.analyze_dependencies {
-        match d {
-          AnalyzeDependenciesOption::Bool(b) if *b => Some(DependencyOptions { remove_imports: true }),
-          AnalyzeDependenciesOption::Config(c) => Some(DependencyOptions {
-            remove_imports: !c.preserve_imports,
-          }),
-          _ => None,
-        }
+      analyze_dependencies: if config.analyze_dependencies {
+        Some(DependencyOptions::default())
       } else {
         None
       },
-      pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
-    })?
+      pseudo_classes: None,
+      source_map_url: None,
+      ..PrinterOptions::default()
+    })?)
   };
-
-  let map = if let Some(source_map) = &mut source_map {
-    source_map.to_json(None).ok()
+  let res = compile().map_err(|mut err| {
+    original_location(err.loc_mut());
+    err
+  })?;
+  let verification_errors = if config.verify_output {
+    let filename = config.filename.as_deref().unwrap_or_default();
+    Some(verify_output(&res.code, filename, None, true, None, false))
   } else {
     None
   };
-
-  Ok(TransformResult {
+  let mut warnings = dedup_warnings(warnings.map_or(Vec::new(), |w| {
+    Arc::try_unwrap(w)
+      .unwrap()
+      .into_inner()
+      .unwrap()
+      .into_iter()
+      .map(|mut w| {
+        original_location(w.loc.as_mut());
+        w.into()
+      })
+      .collect()
+  }));
+  let warnings_truncated = limit_warnings(&mut warnings, config.warnings_limit);
+  Ok(AttrResult {
     code: res.code.into_bytes(),
-    map: map.map(|m| m.into_bytes()),
-    exports: res.exports,
-    references: res.references,
     dependencies: res.dependencies,
-    warnings: warnings.map_or(Vec::new(), |w| {
-      Arc::try_unwrap(w)
-        .unwrap()
-        .into_inner()
-        .unwrap()
-        .into_iter()
-        .map(|w| w.into())
-        .collect()
-    }),
+    warnings,
+    warnings_truncated,
+    verification_errors,
   })
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct AttrConfig {
+struct DeclarationConfig {
+  pub property: String,
+  pub value: String,
+  #[serde(default, deserialize_with = "deserialize_targets")]
+  pub targets: Option<Browsers>,
+  #[serde(default)]
+  pub minify: bool,
+}
+
+/// A declaration returned by `transformDeclaration`.
+#[derive(Serialize)]
+struct DeclarationResult {
+  property: String,
+  value: String,
+  important: bool,
+}
+
+fn compile_declaration<'i>(
+  config: &'i DeclarationConfig,
+) -> Result<Vec<DeclarationResult>, CompileError<'i, std::io::Error>> {
+  let parser_options = ParserOptions::default();
+  let mut input = cssparser::ParserInput::new(&config.value);
+  let mut parser = cssparser::Parser::new(&mut input);
+  let property_id = PropertyId::from(config.property.as_str());
+  let (property, important) = parser
+    .parse_entirely(|input| {
+      let property = input.parse_until_before(cssparser::Delimiter::Bang, |input| {
+        Property::parse(property_id, input, &parser_options)
+      })?;
+      let important = input
+        .try_parse(|input| {
+          input.expect_delim('!')?;
+          input.expect_ident_matching("important")
+        })
+        .is_ok();
+      Ok((property, important))
+    })
+    .map_err(|e| Error::from(e, String::new()))?;
+
+  // Values of known properties that fail to parse are kept as raw tokens, which is only
+  // valid when they contain variables or are CSS-wide keywords.
+  if matches!(&property, Property::Unparsed(unparsed) if !unparsed.may_be_valid()) {
+    return Err(CompileError::ParseError(Error {
+      kind: ParserError::InvalidValue,
+      loc: Some(ErrorLocation {
+        filename: String::new(),
+        line: 0,
+        column: 1,
+      }),
+    }));
+  }
+
+  let (declarations, important_declarations) = if important {
+    (vec![], vec![property])
+  } else {
+    (vec![property], vec![])
+  };
+  let mut attr = StyleAttribute::new(DeclarationBlock {
+    important_declarations,
+    declarations,
+    ..DeclarationBlock::default()
+  });
+  attr.minify(MinifyOptions {
+    targets: config.targets,
+    ..MinifyOptions::default()
+  });
+
+  let printer_options = || PrinterOptions {
+    minify: config.minify,
+    targets: config.targets,
+    ..PrinterOptions::default()
+  };
+  let mut res = Vec::new();
+  for (property, important) in attr.declarations.iter() {
+    // A property with several vendor prefixes is printed as one declaration per prefix.
+    let property_id = property.property_id();
+    let value = property.value_to_css_string(printer_options())?;
+    for prefix in [
+      VendorPrefix::WebKit,
+      VendorPrefix::Moz,
+      VendorPrefix::Ms,
+      VendorPrefix::O,
+      VendorPrefix::None,
+    ] {
+      if property_id.prefix().contains(prefix) {
+        res.push(DeclarationResult {
+          property: property_id.with_prefix(prefix).to_css_string(printer_options())?,
+          value: value.clone(),
+          important,
+        });
+      }
+    }
+  }
+  Ok(res)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuleConfig {
   pub filename: Option<String>,
   #[serde(with = "serde_bytes")]
   pub code: Vec<u8>,
+  pub rule_start_offset: usize,
+  pub rule_end_offset: usize,
+  #[serde(default, deserialize_with = "deserialize_targets")]
   pub targets: Option<Browsers>,
   #[serde(default)]
   pub minify: bool,
   #[serde(default)]
-  pub analyze_dependencies: bool,
-  #[serde(default)]
   pub error_recovery: bool,
 }
 
+/// A rule returned by `transformRule`.
 #[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-struct AttrResult<'i> {
-  #[serde(with = "serde_bytes")]
-  code: Vec<u8>,
-  dependencies: Option<Vec<Dependency>>,
+struct RuleResult<'i> {
+  code: String,
   warnings: Vec<Warning<'i>>,
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-impl<'i> AttrResult<'i> {
-  fn into_js(self, ctx: CallContext) -> napi::Result<JsUnknown> {
-    // Manually construct buffers so we avoid a copy and work around
-    // https://github.com/napi-rs/napi-rs/issues/1124.
-    let mut obj = ctx.env.create_object()?;
-    let buf = ctx.env.create_buffer_with_data(self.code)?;
-    obj.set_named_property("code", buf.into_raw())?;
-    obj.set_named_property("dependencies", ctx.env.to_js_value(&self.dependencies)?)?;
-    obj.set_named_property("warnings", ctx.env.to_js_value(&self.warnings)?)?;
-    Ok(obj.into_unknown())
+/// Parses, minifies and prints the single rule between the given offsets of the code.
+fn compile_rule<'i>(
+  code: &'i str,
+  config: &RuleConfig,
+) -> Result<RuleResult<'i>, CompileError<'i, std::io::Error>> {
+  let (start, end) = (config.rule_start_offset, config.rule_end_offset);
+  if start > end || !code.is_char_boundary(start) || !code.is_char_boundary(end) {
+    return Err(CompileError::RangeError(start, end));
   }
-}
 
-fn compile_attr<'i>(
-  code: &'i str,
-  config: &AttrConfig,
-) -> Result<AttrResult<'i>, CompileError<'i, std::io::Error>> {
+  let filename = config.filename.clone().unwrap_or_default();
   let warnings = if config.error_recovery {
     Some(Arc::new(RwLock::new(Vec::new())))
   } else {
     None
   };
-  let res = {
-    let filename = config.filename.clone().unwrap_or_default();
-    let mut attr = StyleAttribute::parse(
-      &code,
-      ParserOptions {
-        filename,
-        error_recovery: config.error_recovery,
-        warnings: warnings.clone(),
-        ..ParserOptions::default()
-      },
-    )?;
-    attr.minify(MinifyOptions {
-      targets: config.targets,
-      ..MinifyOptions::default()
-    });
-    attr.to_css(PrinterOptions {
-      minify: config.minify,
-      source_map: None,
-      targets: config.targets,
-      analyze_dependencies: if config.analyze_dependencies {
-        Some(DependencyOptions::default())
-      } else {
-        None
-      },
-      pseudo_classes: None,
-    })?
-  };
-  Ok(AttrResult {
-    code: res.code.into_bytes(),
-    dependencies: res.dependencies,
-    warnings: warnings.map_or(Vec::new(), |w| {
+  let options = parser_options(
+    ParserConfig {
+      filename: config.filename.as_deref(),
+      error_recovery: config.error_recovery,
+      ..ParserConfig::default()
+    },
+    &warnings,
+  )?;
+  let rule = CssRule::parse_range(code, start, end, options.clone())?;
+  let mut stylesheet = StyleSheet::new(vec![filename], CssRuleList(vec![rule]), options);
+  stylesheet.minify(MinifyOptions {
+    targets: config.targets,
+    ..MinifyOptions::default()
+  })?;
+  let res = stylesheet.to_css(PrinterOptions {
+    minify: config.minify,
+    targets: config.targets,
+    ..PrinterOptions::default()
+  })?;
+  // The style sheet holds a reference to the warnings through its parser options.
+  drop(stylesheet);
+
+  Ok(RuleResult {
+    code: res.code,
+    warnings: dedup_warnings(warnings.map_or(Vec::new(), |w| {
       Arc::try_unwrap(w)
         .unwrap()
         .into_inner()
@@ -819,10 +3843,238 @@ fn compile_attr<'i>(
         .into_iter()
         .map(|w| w.into())
         .collect()
-    }),
+    })),
+  })
+}
+
+#[derive(Debug, Deserialize)]
+struct MediaQueryTargets(#[serde(deserialize_with = "deserialize_targets")] Option<Browsers>);
+
+/// A media query list returned by `transformMediaQuery`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MediaQueryResult {
+  code: String,
+  media_queries: serde_json::Value,
+}
+
+fn compile_media_query<'i>(
+  query: &'i str,
+  targets: Option<Browsers>,
+) -> Result<MediaQueryResult, CompileError<'i, std::io::Error>> {
+  let mut input = cssparser::ParserInput::new(query);
+  let mut parser = cssparser::Parser::new(&mut input);
+  let mut media = parser
+    .parse_entirely(MediaList::parse)
+    .map_err(|e| Error::from(e, String::new()))?;
+  if let Some(targets) = targets {
+    media.transform_resolution(targets);
+  }
+
+  // Range syntax is lowered for the targets while printing, so the structured representation
+  // is parsed from the output rather than the original query.
+  let code = media.to_css_string(PrinterOptions {
+    minify: true,
+    targets,
+    ..PrinterOptions::default()
+  })?;
+  let media_queries = {
+    let mut input = cssparser::ParserInput::new(&code);
+    let mut parser = cssparser::Parser::new(&mut input);
+    match parser.parse_entirely(MediaList::parse) {
+      Ok(lowered) => serde_json::to_value(&lowered.media_queries),
+      // The printed query always parses, but fall back to the original structure just in case.
+      Err(_) => serde_json::to_value(&media.media_queries),
+    }
+    .unwrap()
+  };
+
+  Ok(MediaQueryResult { code, media_queries })
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotHashConfig {
+  pub filename: Option<String>,
+  pub drafts: Option<Drafts>,
+}
+
+fn snapshot_hash_code<'i>(
+  code: &'i str,
+  config: &SnapshotHashConfig,
+) -> Result<String, CompileError<'i, std::io::Error>> {
+  let stylesheet = StyleSheet::parse(
+    &code,
+    parser_options(
+      ParserConfig {
+        filename: config.filename.as_deref(),
+        drafts: config.drafts.as_ref(),
+        ..ParserConfig::default()
+      },
+      &None,
+    )?,
+  )?;
+  Ok(stylesheet.snapshot_hash())
+}
+
+/// A selector returned by `parseSelector`.
+#[derive(Serialize)]
+struct ParsedSelector {
+  components: Vec<SelectorComponent>,
+  specificity: (u32, u32, u32),
+}
+
+fn parse_selector_code<'i>(code: &'i str) -> Result<Vec<ParsedSelector>, CompileError<'i, std::io::Error>> {
+  let selectors = parse_selectors(code, &ParserOptions::default())?;
+  selectors
+    .0
+    .iter()
+    .map(|selector| {
+      Ok(ParsedSelector {
+        components: selector_components(selector)?,
+        specificity: specificity(selector),
+      })
+    })
+    .collect()
+}
+
+/// The components of a selector, e.g. as returned by `parseSelector`.
+#[derive(Debug, Deserialize)]
+struct SelectorComponents {
+  components: Vec<SelectorComponent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SelectorInput {
+  Selector(SelectorComponents),
+  List(Vec<SelectorComponents>),
+}
+
+impl SelectorInput {
+  /// Builds the source of the selector list, which is parsed again to validate it.
+  fn to_code(&self) -> String {
+    match self {
+      SelectorInput::Selector(selector) => selector_from_components(&selector.components),
+      SelectorInput::List(selectors) => selectors
+        .iter()
+        .map(|selector| selector_from_components(&selector.components))
+        .collect::<Vec<_>>()
+        .join(", "),
+    }
+  }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct SerializeSelectorConfig {
+  #[serde(default)]
+  pub minify: bool,
+}
+
+fn serialize_selector_code<'i>(
+  code: &'i str,
+  config: &SerializeSelectorConfig,
+) -> Result<String, CompileError<'i, std::io::Error>> {
+  let selectors = parse_selectors(code, &ParserOptions::default())?;
+  Ok(selectors_to_css_string(
+    &selectors,
+    PrinterOptions {
+      minify: config.minify,
+      ..PrinterOptions::default()
+    },
+  )?)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AstConfig {
+  pub ast: serde_json::Value,
+  #[serde(default, deserialize_with = "deserialize_targets")]
+  pub targets: Option<Browsers>,
+  pub minify: Option<bool>,
+  pub source_map: Option<bool>,
+  pub source_map_url: Option<String>,
+  pub analyze_dependencies: Option<AnalyzeDependenciesOption>,
+  pub pseudo_classes: Option<OwnedPseudoClasses>,
+  pub verify_output: Option<bool>,
+}
+
+fn compile_ast<'i>(config: &'i AstConfig) -> Result<TransformResult<'i>, CompileError<'i, std::io::Error>> {
+  let stylesheet = StyleSheet::deserialize(&config.ast).map_err(CompileError::AstError)?;
+  check_source_indexes(&config.ast, stylesheet.sources.len()).map_err(CompileError::AstError)?;
+
+  let mut source_map = if config.source_map.unwrap_or_default() {
+    let mut sm = SourceMap::new("/");
+    for source in &stylesheet.sources {
+      sm.add_source(source);
+    }
+    Some(sm)
+  } else {
+    None
+  };
+
+  let res = stylesheet.to_css(PrinterOptions {
+    minify: config.minify.unwrap_or_default(),
+    source_map: source_map.as_mut(),
+    targets: config.targets,
+    analyze_dependencies: dependency_options(&config.analyze_dependencies),
+    pseudo_classes: config.pseudo_classes.as_ref().map(|p| p.into()),
+    source_map_url: config.source_map_url.as_deref(),
+    ..PrinterOptions::default()
+  })?;
+
+  let verification_errors = if config.verify_output.unwrap_or_default() {
+    Some(verify_output(&res.code, "", None, false, source_map.as_mut(), false))
+  } else {
+    None
+  };
+
+  let map = source_map_to_json(source_map, &None, true);
+
+  Ok(TransformResult {
+    code: res.code.into_bytes(),
+    map: map.map(|m| m.into_bytes()),
+    code_legacy: None,
+    map_legacy: None,
+    exports: res.exports,
+    exports_hash: None,
+    animations: res.animations,
+    references: res.references,
+    dependencies: res.dependencies,
+    renamed_keyframes: res.renamed_keyframes,
+    inlined_custom_properties: res.inlined_custom_properties,
+    removed_symbols: res.removed_symbols,
+    unmatched_symbols: res.unmatched_symbols,
+    features_used: feature_names(res.features_used),
+    license_comments: None,
+    warnings: Vec::new(),
+    warnings_truncated: false,
+    verification_errors,
+    ast: None,
+    chunks: None,
   })
 }
 
+// Rules refer to their source file by index, which must exist to print and generate source maps.
+fn check_source_indexes(value: &serde_json::Value, sources: usize) -> Result<(), serde_json::Error> {
+  match value {
+    serde_json::Value::Object(object) => {
+      if let Some(source_index) = object.get("source_index") {
+        if !matches!(source_index.as_u64(), Some(i) if (i as usize) < sources) {
+          return Err(serde::de::Error::custom(format!(
+            "invalid source_index {}, expected an index into `sources` (length {})",
+            source_index, sources
+          )));
+        }
+      }
+      object.values().try_for_each(|v| check_source_indexes(v, sources))
+    }
+    serde_json::Value::Array(array) => array.iter().try_for_each(|v| check_source_indexes(v, sources)),
+    _ => Ok(()),
+  }
+}
+
 enum CompileError<'i, E: std::error::Error> {
   ParseError(Error<ParserError<'i>>),
   MinifyError(Error<MinifyErrorKind>),
@@ -830,6 +4082,12 @@ enum CompileError<'i, E: std::error::Error> {
   SourceMapError(parcel_sourcemap::SourceMapError),
   BundleError(Error<BundleErrorKind<'i, E>>),
   PatternError(PatternParseError),
+  HashVersionError(u8),
+  FeatureError(String),
+  AstError(serde_json::Error),
+  VisitorError(String),
+  RangeError(usize, usize),
+  EncodingError(usize),
 }
 
 impl<'i, E: std::error::Error> std::fmt::Display for CompileError<'i, E> {
@@ -840,12 +4098,44 @@ impl<'i, E: std::error::Error> std::fmt::Display for CompileError<'i, E> {
       CompileError::PrinterError(err) => err.kind.fmt(f),
       CompileError::BundleError(err) => err.kind.fmt(f),
       CompileError::PatternError(err) => err.fmt(f),
+      CompileError::HashVersionError(v) => write!(f, "Unknown CSS modules hash version: {}", v),
+      CompileError::FeatureError(feature) => write!(f, "Unknown feature: {}", feature),
+      CompileError::AstError(err) => err.fmt(f),
+      CompileError::VisitorError(err) => write!(f, "{}", err),
+      CompileError::RangeError(start, end) => write!(f, "Invalid rule offsets: {}..{}", start, end),
+      CompileError::EncodingError(offset) => write!(
+        f,
+        "Invalid UTF-8 sequence at byte offset {}. Use the `encoding` option if the code is not UTF-8 encoded.",
+        offset
+      ),
       CompileError::SourceMapError(err) => write!(f, "{}", err.to_string()), // TODO: switch to `fmt::Display` once parcel_sourcemap supports this
     }
   }
 }
 
 impl<'i, E: std::error::Error> CompileError<'i, E> {
+  /// Returns the location of the error, if it has one.
+  fn loc_mut(&mut self) -> Option<&mut ErrorLocation> {
+    match self {
+      CompileError::ParseError(Error { loc, .. }) => loc.as_mut(),
+      CompileError::PrinterError(Error { loc, .. }) => loc.as_mut(),
+      CompileError::MinifyError(Error { loc, .. }) => loc.as_mut(),
+      CompileError::BundleError(Error { loc, .. }) => loc.as_mut(),
+      _ => None,
+    }
+  }
+
+  /// Returns a stable code for the type of error, if it has one.
+  fn code(&self) -> Option<&'static str> {
+    match self {
+      CompileError::ParseError(Error { kind, .. }) => Some(kind.code()),
+      CompileError::PrinterError(Error { kind, .. }) => Some(kind.code()),
+      CompileError::MinifyError(Error { kind, .. }) => Some(kind.code()),
+      CompileError::BundleError(Error { kind, .. }) => Some(kind.code()),
+      _ => None,
+    }
+  }
+
   #[cfg(not(target_arch = "wasm32"))]
   fn throw(self, env: Env, code: Option<&str>) -> napi::Result<JsUnknown> {
     let reason = self.to_string();
@@ -856,6 +4146,7 @@ impl<'i, E: std::error::Error> CompileError<'i, E> {
       CompileError::BundleError(Error { kind, .. }) => env.to_js_value(kind)?,
       _ => env.get_null()?.into_unknown(),
     };
+    let error_code = self.code();
 
     match self {
       CompileError::ParseError(Error { loc, .. })
@@ -874,6 +4165,8 @@ impl<'i, E: std::error::Error> CompileError<'i, E> {
           if let Some(code) = code {
             let source = env.create_string(code)?;
             obj.set_named_property("source", source)?;
+            let frame = env.create_string_from_std(code_frame(code, loc.line, loc.column))?;
+            obj.set_named_property("codeFrame", frame)?;
           }
           let mut loc = env.create_object()?;
           loc.set_named_property("line", line)?;
@@ -881,12 +4174,46 @@ impl<'i, E: std::error::Error> CompileError<'i, E> {
           obj.set_named_property("loc", loc)?;
         }
         obj.set_named_property("data", data)?;
+        if let Some(error_code) = error_code {
+          obj.set_named_property("code", env.create_string(error_code)?)?;
+        }
         env.throw(obj)?;
         Ok(env.get_undefined()?.into_unknown())
       }
       _ => Err(self.into()),
     }
   }
+
+  /// Converts the error to a JS error. When the code is given, errors with a location
+  /// also include the source and a code frame, as in the native `throw`.
+  #[cfg(target_arch = "wasm32")]
+  fn into_js_error(self, code: Option<&str>) -> wasm_bindgen::JsValue {
+    let set = |err: &js_sys::Error, key: &str, value: &JsValue| {
+      let _ = js_sys::Reflect::set(err, &key.into(), value);
+    };
+    match self {
+      CompileError::SourceMapError(e) => js_sys::Error::new(&e.to_string()).into(),
+      CompileError::PatternError(e) => js_sys::Error::new(&e.to_string()).into(),
+      mut e => {
+        let err = js_sys::Error::new(&e.to_string());
+        if let Some(error_code) = e.code() {
+          set(&err, "code", &error_code.into());
+        }
+        if let Some(loc) = e.loc_mut() {
+          set(&err, "fileName", &loc.filename.as_str().into());
+          if let Some(code) = code {
+            set(&err, "source", &code.into());
+            set(&err, "codeFrame", &code_frame(code, loc.line, loc.column).into());
+          }
+          let js_loc = js_sys::Object::new();
+          let _ = js_sys::Reflect::set(&js_loc, &"line".into(), &(loc.line + 1).into());
+          let _ = js_sys::Reflect::set(&js_loc, &"column".into(), &loc.column.into());
+          set(&err, "loc", &js_loc);
+        }
+        err.into()
+      }
+    }
+  }
 }
 
 impl<'i, E: std::error::Error> From<Error<ParserError<'i>>> for CompileError<'i, E> {
@@ -933,22 +4260,43 @@ impl<'i, E: std::error::Error> From<CompileError<'i, E>> for napi::Error {
 #[cfg(target_arch = "wasm32")]
 impl<'i, E: std::error::Error> From<CompileError<'i, E>> for wasm_bindgen::JsValue {
   fn from(e: CompileError<'i, E>) -> wasm_bindgen::JsValue {
-    match e {
-      CompileError::SourceMapError(e) => js_sys::Error::new(&e.to_string()).into(),
-      CompileError::PatternError(e) => js_sys::Error::new(&e.to_string()).into(),
-      _ => js_sys::Error::new(&e.to_string()).into(),
-    }
+    e.into_js_error(None)
   }
 }
 
 #[derive(Serialize)]
 struct Warning<'i> {
   message: String,
+  code: &'static str,
+  severity: Severity,
+  stage: WarningStage,
+  /// The number of identical warnings at the same location that were collapsed into this one, if more than one.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  count: Option<usize>,
+  /// The type of a warning that is not a parser or printer error. Otherwise, it is serialized along with the data of the error.
+  #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+  kind: Option<&'static str>,
   #[serde(flatten)]
-  data: ParserError<'i>,
+  data: Option<WarningData<'i>>,
   loc: Option<ErrorLocation>,
 }
 
+/// The step of the compilation that a warning was emitted by.
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum WarningStage {
+  Parse,
+  Minify,
+  Print,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum WarningData<'i> {
+  Parser(ParserError<'i>),
+  Printer(PrinterErrorKind),
+}
+
 impl<'i> From<Error<ParserError<'i>>> for Warning<'i> {
   fn from(mut e: Error<ParserError<'i>>) -> Self {
     // Convert to 1-based line numbers.
@@ -957,8 +4305,179 @@ impl<'i> From<Error<ParserError<'i>>> for Warning<'i> {
     }
     Warning {
       message: e.kind.to_string(),
-      data: e.kind,
+      code: e.kind.code(),
+      severity: e.kind.severity(),
+      stage: WarningStage::Parse,
+      count: None,
+      kind: None,
+      data: Some(WarningData::Parser(e.kind)),
+      loc: e.loc,
+    }
+  }
+}
+
+impl<'i> From<PrinterError> for Warning<'i> {
+  fn from(mut e: PrinterError) -> Self {
+    if let Some(loc) = &mut e.loc {
+      loc.line += 1;
+    }
+    Warning {
+      message: e.kind.to_string(),
+      code: e.kind.code(),
+      severity: Severity::Warning,
+      stage: WarningStage::Print,
+      count: None,
+      kind: None,
+      data: Some(WarningData::Printer(e.kind)),
       loc: e.loc,
     }
   }
 }
+
+/// Converts the warnings emitted while compiling. Parser warnings after the first `parsed` were
+/// emitted while minifying, and are followed by the printer warnings.
+fn collect_warnings<'i>(
+  warnings: Option<Arc<RwLock<Vec<Error<ParserError<'i>>>>>>,
+  parsed: usize,
+  printer_warnings: Arc<RwLock<Vec<PrinterError>>>,
+) -> Vec<Warning<'i>> {
+  let mut result: Vec<Warning> = warnings.map_or(Vec::new(), |w| {
+    Arc::try_unwrap(w)
+      .unwrap()
+      .into_inner()
+      .unwrap()
+      .into_iter()
+      .enumerate()
+      .map(|(i, w)| {
+        let mut warning = Warning::from(w);
+        if i >= parsed {
+          warning.stage = WarningStage::Minify;
+        }
+        warning
+      })
+      .collect()
+  });
+  result.extend(
+    Arc::try_unwrap(printer_warnings)
+      .unwrap()
+      .into_inner()
+      .unwrap()
+      .into_iter()
+      .map(Warning::from),
+  );
+  dedup_warnings(result)
+}
+
+/// Collapses identical warnings at the same location into the first one, counting the occurrences.
+fn dedup_warnings<'i>(warnings: Vec<Warning<'i>>) -> Vec<Warning<'i>> {
+  let mut result: Vec<Warning> = Vec::with_capacity(warnings.len());
+  let mut seen: HashMap<_, usize> = HashMap::new();
+  for warning in warnings {
+    let key = (
+      warning.code,
+      warning.message.clone(),
+      warning.loc.as_ref().map(|loc| (loc.filename.clone(), loc.line, loc.column)),
+    );
+    match seen.get(&key) {
+      Some(&i) => {
+        let existing = &mut result[i];
+        existing.count = Some(existing.count.unwrap_or(1) + 1);
+      }
+      None => {
+        seen.insert(key, result.len());
+        result.push(warning);
+      }
+    }
+  }
+  result
+}
+
+/// Drops the warnings after the first `limit`, once identical warnings have been collapsed by
+/// [dedup_warnings]. Returns whether any warnings were dropped.
+fn limit_warnings(warnings: &mut Vec<Warning>, limit: Option<usize>) -> bool {
+  match limit {
+    Some(limit) if warnings.len() > limit => {
+      warnings.truncate(limit);
+      true
+    }
+    _ => false,
+  }
+}
+
+/// Returns the number of warnings emitted so far, i.e. while parsing when called before minifying.
+fn warning_count(warnings: &Option<Arc<RwLock<Vec<Error<ParserError<'_>>>>>>) -> usize {
+  warnings.as_ref().map_or(0, |w| w.read().unwrap().len())
+}
+
+/// An error found by re-parsing the output when `verifyOutput` is enabled.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerificationError {
+  message: String,
+  code: &'static str,
+  /// The location of the error in the output.
+  loc: Option<ErrorLocation>,
+  /// The location in the input that the erroneous output was generated from, if a source map is available.
+  source_loc: Option<ErrorLocation>,
+  /// Whether the error is in the output for the legacy targets.
+  legacy: bool,
+}
+
+/// Re-parses printed code, with error recovery so that every error is reported, and maps the
+/// location of each error back to the input using the source map.
+fn verify_output(
+  code: &str,
+  filename: &str,
+  drafts: Option<&Drafts>,
+  style_attribute: bool,
+  mut source_map: Option<&mut SourceMap>,
+  legacy: bool,
+) -> Vec<VerificationError> {
+  let warnings = Arc::new(RwLock::new(Vec::new()));
+  let config = ParserConfig {
+    filename: Some(filename),
+    drafts,
+    error_recovery: true,
+    ..ParserConfig::default()
+  };
+  // Without CSS modules, there is no pattern that could fail to parse.
+  let options = match parser_options::<std::io::Error>(config, &Some(warnings.clone())) {
+    Ok(options) => options,
+    Err(_) => unreachable!(),
+  };
+  let res = if style_attribute {
+    StyleAttribute::parse(code, options).map(|_| ())
+  } else {
+    StyleSheet::parse(code, options).map(|_| ())
+  };
+
+  let mut errors = std::mem::take(&mut *warnings.write().unwrap());
+  errors.extend(res.err());
+  errors
+    .into_iter()
+    .map(|e| {
+      let source_loc = match (&mut source_map, &e.loc) {
+        (Some(source_map), Some(loc)) => source_map
+          .find_closest_mapping(loc.line, loc.column.saturating_sub(1))
+          .and_then(|mapping| mapping.original)
+          .map(|original| ErrorLocation {
+            filename: source_map.get_source(original.source).unwrap_or_default().into(),
+            line: original.original_line + 1,
+            column: original.original_column + 1,
+          }),
+        _ => None,
+      };
+      VerificationError {
+        message: e.kind.to_string(),
+        code: e.kind.code(),
+        // Convert to 1-based line numbers.
+        loc: e.loc.map(|loc| ErrorLocation {
+          line: loc.line + 1,
+          ..loc
+        }),
+        source_loc,
+        legacy,
+      }
+    })
+    .collect()
+}
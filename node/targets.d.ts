@@ -1,13 +1,13 @@
 // This file is autogenerated by build-prefixes.js. DO NOT EDIT!
 
 export interface Targets {
-  android?: number,
-  chrome?: number,
-  edge?: number,
-  firefox?: number,
-  ie?: number,
-  ios_saf?: number,
-  opera?: number,
-  safari?: number,
-  samsung?: number
+  android?: number | string,
+  chrome?: number | string,
+  edge?: number | string,
+  firefox?: number | string,
+  ie?: number | string,
+  ios_saf?: number | string,
+  opera?: number | string,
+  safari?: number | string,
+  samsung?: number | string
 }
@@ -169,7 +169,9 @@ let cssFeatures = [
   'css-nesting',
   'css-not-sel-list',
   'css-has',
-  'font-family-system-ui'
+  'font-family-system-ui',
+  'css-grid',
+  'css-variables'
 ];
 
 let compat = new Map();
@@ -212,6 +214,7 @@ let mdnFeatures = {
   overflowShorthand: mdn.css.properties['overflow'].multiple_keywords.__compat.support,
   mediaRangeSyntax: mdn.css['at-rules'].media.range_syntax.__compat.support,
   mediaIntervalSyntax: {}, // currently no browsers
+  scopeRule: {}, // currently no browsers
   logicalBorders: mdn.css.properties['border-inline-start'].__compat.support,
   logicalBorderShorthand: mdn.css.properties['border-inline'].__compat.support,
   logicalBorderRadius: mdn.css.properties['border-start-start-radius'].__compat.support,
@@ -337,7 +340,7 @@ fs.writeFileSync('src/targets.rs', targets);
 let targets_dts = `// This file is autogenerated by build-prefixes.js. DO NOT EDIT!
 
 export interface Targets {
-  ${allBrowsers.join('?: number,\n  ')}?: number
+  ${allBrowsers.join('?: number | string,\n  ')}?: number | string
 }
 `;
 
@@ -9,7 +9,7 @@ try {
 } catch (err) { }
 
 exec(`cp -R ${dir}/artifacts/wasm ${dir}/npm/.`);
-fs.writeFileSync(`${dir}/npm/wasm/index.js`, `export {default, transform, transformStyleAttribute} from './lightningcss_node.js';\nexport {browserslistToTargets} from './browserslistToTargets.js'`);
+fs.writeFileSync(`${dir}/npm/wasm/index.js`, `export {default, transform, bundle, validate, transformStyleAttribute, transformDeclaration, transformRule, transformMediaQuery, parseSelector, serializeSelector} from './lightningcss_node.js';\nexport {browserslistToTargets} from './browserslistToTargets.js'`);
 
 let b = fs.readFileSync(`${dir}/node/browserslistToTargets.js`, 'utf8');
 b = b.replace('module.exports = browserslistToTargets;', 'export {browserslistToTargets};');
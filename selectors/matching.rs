@@ -408,7 +408,7 @@ where
 {
   match combinator {
     Combinator::NextSibling | Combinator::LaterSibling => element.prev_sibling_element(),
-    Combinator::Child | Combinator::Descendant => {
+    Combinator::Child | Combinator::Descendant | Combinator::Deep | Combinator::DeepDescendant => {
       match element.parent_element() {
         Some(e) => return Some(e),
         None => {}
@@ -490,6 +490,8 @@ where
     }
     Combinator::Child
     | Combinator::Descendant
+    | Combinator::Deep
+    | Combinator::DeepDescendant
     | Combinator::SlotAssignment
     | Combinator::Part
     | Combinator::PseudoElement => SelectorMatchingResult::NotMatchedGlobally,
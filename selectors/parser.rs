@@ -40,6 +40,12 @@ pub trait PseudoElement<'i>: Sized + ToCss {
   fn is_webkit_scrollbar(&self) -> bool {
     false
   }
+
+  /// Whether a combinator may follow this pseudo-element, e.g. for non-standard
+  /// pseudo-elements that act as a combinator themselves.
+  fn valid_before_combinator(&self) -> bool {
+    false
+  }
 }
 
 /// A trait that represents a pseudo-class.
@@ -262,6 +268,11 @@ pub trait Parser<'i> {
     false
   }
 
+  /// Whether to parse the non-standard `>>>` and `/deep/` combinators.
+  fn parse_deep_combinators(&self) -> bool {
+    false
+  }
+
   /// Whether to parse the `:where` pseudo-class.
   fn parse_is_and_where(&self) -> bool {
     false
@@ -980,11 +991,12 @@ impl<'a, 'i, Impl: 'a + SelectorImpl<'i>> AncestorIter<'a, 'i, Impl> {
       // If this is ever changed to stop at the "pseudo-element"
       // combinator, we will need to fix the way we compute hashes for
       // revalidation selectors.
-      if self
-        .0
-        .next_sequence()
-        .map_or(true, |x| matches!(x, Combinator::Child | Combinator::Descendant))
-      {
+      if self.0.next_sequence().map_or(true, |x| {
+        matches!(
+          x,
+          Combinator::Child | Combinator::Descendant | Combinator::Deep | Combinator::DeepDescendant
+        )
+      }) {
         break;
       }
     }
@@ -1002,7 +1014,10 @@ impl<'a, 'i, Impl: SelectorImpl<'i>> Iterator for AncestorIter<'a, 'i, Impl> {
 
     // See if there are more sequences. If so, skip any non-ancestor sequences.
     if let Some(combinator) = self.0.next_sequence() {
-      if !matches!(combinator, Combinator::Child | Combinator::Descendant) {
+      if !matches!(
+        combinator,
+        Combinator::Child | Combinator::Descendant | Combinator::Deep | Combinator::DeepDescendant
+      ) {
         self.skip_until_ancestor();
       }
     }
@@ -1030,6 +1045,10 @@ pub enum Combinator {
   /// Another combinator used for `::part()`, which represents the jump from
   /// the part to the containing shadow host.
   Part,
+  /// The non-standard `>>>` deep combinator. Matches like a descendant combinator.
+  Deep,
+  /// The non-standard `/deep/` deep combinator. Matches like a descendant combinator.
+  DeepDescendant,
 }
 
 impl Combinator {
@@ -1038,7 +1057,12 @@ impl Combinator {
   pub fn is_ancestor(&self) -> bool {
     matches!(
       *self,
-      Combinator::Child | Combinator::Descendant | Combinator::PseudoElement | Combinator::SlotAssignment
+      Combinator::Child
+        | Combinator::Descendant
+        | Combinator::Deep
+        | Combinator::DeepDescendant
+        | Combinator::PseudoElement
+        | Combinator::SlotAssignment
     )
   }
 
@@ -1107,7 +1131,7 @@ pub enum Component<'i, Impl: SelectorImpl<'i>> {
   Scope,
   NthChild(i32, i32),
   NthLastChild(i32, i32),
-  NthCol(i32, i32), // https://www.w3.org/TR/selectors-4/#the-nth-col-pseudo
+  NthCol(i32, i32),     // https://www.w3.org/TR/selectors-4/#the-nth-col-pseudo
   NthLastCol(i32, i32), // https://www.w3.org/TR/selectors-4/#the-nth-last-col-pseudo
   NthOfType(i32, i32),
   NthLastOfType(i32, i32),
@@ -1490,6 +1514,8 @@ impl ToCss for Combinator {
       Combinator::Descendant => dest.write_str(" "),
       Combinator::NextSibling => dest.write_str(" + "),
       Combinator::LaterSibling => dest.write_str(" ~ "),
+      Combinator::Deep => dest.write_str(" >>> "),
+      Combinator::DeepDescendant => dest.write_str(" /deep/ "),
       Combinator::PseudoElement | Combinator::Part | Combinator::SlotAssignment => Ok(()),
     }
   }
@@ -1606,7 +1632,12 @@ impl<'i, Impl: SelectorImpl<'i>> ToCss for Component<'i, Impl> {
       FirstOfType => dest.write_str(":first-of-type"),
       LastOfType => dest.write_str(":last-of-type"),
       OnlyOfType => dest.write_str(":only-of-type"),
-      NthChild(a, b) | NthLastChild(a, b) | NthOfType(a, b) | NthLastOfType(a, b) | NthCol(a, b) | NthLastCol(a, b) => {
+      NthChild(a, b)
+      | NthLastChild(a, b)
+      | NthOfType(a, b)
+      | NthLastOfType(a, b)
+      | NthCol(a, b)
+      | NthLastCol(a, b) => {
         match *self {
           NthChild(_, _) => dest.write_str(":nth-child(")?,
           NthLastChild(_, _) => dest.write_str(":nth-last-child(")?,
@@ -1739,21 +1770,31 @@ where
     let mut any_whitespace = false;
     loop {
       let before_this_token = input.state();
-      match input.next_including_whitespace() {
+      match input.next_including_whitespace().cloned() {
         Err(_e) => break 'outer_loop,
-        Ok(&Token::WhiteSpace(_)) => any_whitespace = true,
-        Ok(&Token::Delim('>')) => {
-          combinator = Combinator::Child;
+        Ok(Token::WhiteSpace(_)) => any_whitespace = true,
+        Ok(Token::Delim('>')) => {
+          combinator = if parser.parse_deep_combinators() && input.try_parse(parse_deep_child_combinator).is_ok() {
+            Combinator::Deep
+          } else {
+            Combinator::Child
+          };
           break;
         }
-        Ok(&Token::Delim('+')) => {
+        Ok(Token::Delim('+')) => {
           combinator = Combinator::NextSibling;
           break;
         }
-        Ok(&Token::Delim('~')) => {
+        Ok(Token::Delim('~')) => {
           combinator = Combinator::LaterSibling;
           break;
         }
+        Ok(Token::Delim('/'))
+          if parser.parse_deep_combinators() && input.try_parse(parse_deep_descendant_combinator).is_ok() =>
+        {
+          combinator = Combinator::DeepDescendant;
+          break;
+        }
         Ok(_) => {
           input.reset(&before_this_token);
           if any_whitespace {
@@ -1781,6 +1822,32 @@ where
   Ok(Selector(spec, components))
 }
 
+/// Parses the remaining `>>` of a `>>>` combinator, after the first `>`.
+fn parse_deep_child_combinator<'i, 't>(input: &mut CssParser<'i, 't>) -> Result<(), BasicParseError<'i>> {
+  for _ in 0..2 {
+    let location = input.current_source_location();
+    match *input.next_including_whitespace()? {
+      Token::Delim('>') => {}
+      ref t => return Err(location.new_basic_unexpected_token_error(t.clone())),
+    }
+  }
+  Ok(())
+}
+
+/// Parses the remaining `deep/` of a `/deep/` combinator, after the first `/`.
+fn parse_deep_descendant_combinator<'i, 't>(input: &mut CssParser<'i, 't>) -> Result<(), BasicParseError<'i>> {
+  let location = input.current_source_location();
+  match *input.next_including_whitespace()? {
+    Token::Ident(ref name) if name.eq_ignore_ascii_case("deep") => {}
+    ref t => return Err(location.new_basic_unexpected_token_error(t.clone())),
+  }
+  let location = input.current_source_location();
+  match *input.next_including_whitespace()? {
+    Token::Delim('/') => Ok(()),
+    ref t => Err(location.new_basic_unexpected_token_error(t.clone())),
+  }
+}
+
 impl<'i, Impl: SelectorImpl<'i>> Selector<'i, Impl> {
   /// Parse a selector, without any pseudo-element.
   #[inline]
@@ -2309,6 +2376,10 @@ where
         builder.push_combinator(Combinator::SlotAssignment);
         builder.push_simple_selector(Component::Slotted(selector));
       }
+      SimpleSelectorParseResult::PseudoElement(p) if p.valid_before_combinator() => {
+        builder.push_combinator(Combinator::PseudoElement);
+        builder.push_simple_selector(Component::PseudoElement(p));
+      }
       SimpleSelectorParseResult::PseudoElement(p) => {
         state.insert(SelectorParsingState::AFTER_PSEUDO_ELEMENT);
         if !p.accepts_state_pseudo_classes() {
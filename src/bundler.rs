@@ -2,9 +2,9 @@
 //!
 //! A [Bundler](Bundler) can be used to combine a CSS file and all of its dependencies
 //! into a single merged style sheet. It works together with a [SourceProvider](SourceProvider)
-//! (e.g. [FileProvider](FileProvider)) to read files from the file system or another source,
-//! and returns a [StyleSheet](super::stylesheet::StyleSheet) containing the rules from all
-//! of the dependencies of the entry file, recursively.
+//! (e.g. [FileProvider](FileProvider) or [MemoryProvider](MemoryProvider)) to read files from
+//! the file system or another source, and returns a [StyleSheet](super::stylesheet::StyleSheet)
+//! containing the rules from all of the dependencies of the entry file, recursively.
 //!
 //! Rules are bundled following `@import` order, and wrapped in the necessary `@media`, `@supports`,
 //! and `@layer` rules as appropriate to preserve the authored behavior.
@@ -22,7 +22,29 @@
 //! let mut bundler = Bundler::new(&fs, None, ParserOptions::default());
 //! let stylesheet = bundler.bundle(Path::new("style.css")).unwrap();
 //! ```
+//!
+//! Sources can also be loaded asynchronously, e.g. over the network, by implementing
+//! [AsyncSourceProvider](AsyncSourceProvider) and using [Bundler::bundle_async](Bundler::bundle_async)
+//! with an [AsyncProvider](AsyncProvider).
+//!
+//! When bundling repeatedly, e.g. in watch mode, a [BundleCache](BundleCache) can be shared between
+//! bundlers so that files which have not changed are not parsed again.
 
+use crate::{
+  declaration::DeclarationBlock,
+  dependencies::ImportDependency,
+  error::{Error, ParserError},
+  media_query::MediaList,
+  rules::{
+    custom_at_rule::{CustomAtRule, CustomAtRuleBody, CustomAtRuleDefinition},
+    import::ImportRule,
+    media::MediaRule,
+    supports::{SupportsCondition, SupportsRule},
+    CssRule, CssRuleList,
+  },
+  stylesheet::{Comments, NestingSpec, ParserOptions, StyleSheet},
+  targets::Browsers,
+};
 use crate::{
   error::ErrorLocation,
   properties::{
@@ -31,33 +53,63 @@ use crate::{
     Property,
   },
   rules::{
-    layer::{LayerBlockRule, LayerName},
+    layer::{add_layer_name, collect_layer_names, LayerBlockRule, LayerName, LayerStatementRule},
+    style::StyleRule,
     Location,
   },
-  values::ident::DashedIdentReference,
-};
-use crate::{
-  error::{Error, ParserError},
-  media_query::MediaList,
-  rules::{
-    import::ImportRule,
-    media::MediaRule,
-    supports::{SupportsCondition, SupportsRule},
-    CssRule, CssRuleList,
-  },
-  stylesheet::{ParserOptions, StyleSheet},
+  values::{ident::DashedIdentReference, string::CowArcStr},
 };
 use dashmap::DashMap;
+use parallel::*;
+use parcel_selectors::parser::Component as SelectorComponent;
 use parcel_sourcemap::SourceMap;
-use rayon::prelude::*;
 use serde::Serialize;
 use std::{
-  collections::HashSet,
+  collections::{HashMap, HashSet},
   fs,
+  future::Future,
   path::{Path, PathBuf},
-  sync::Mutex,
+  pin::Pin,
+  sync::{Arc, Mutex, RwLock},
+  task::{Context, Poll, Waker},
 };
 
+// Rayon panics when it tries to start its thread pool in WebAssembly, which has no threads,
+// so files are loaded one at a time there, using sequential iterators with the same methods.
+#[cfg(not(target_arch = "wasm32"))]
+mod parallel {
+  pub use rayon::iter::{once, Either};
+  pub use rayon::prelude::*;
+}
+
+#[cfg(target_arch = "wasm32")]
+mod parallel {
+  pub use itertools::Either;
+  pub use std::iter::once;
+
+  pub trait ParallelIterator: Iterator {}
+
+  impl<I: Iterator> ParallelIterator for I {}
+
+  pub trait ParallelBridge: Iterator + Sized {
+    fn par_bridge(self) -> Self {
+      self
+    }
+  }
+
+  impl<I: Iterator> ParallelBridge for I {}
+
+  pub trait ParallelSliceMut<T> {
+    fn par_iter_mut(&mut self) -> std::slice::IterMut<'_, T>;
+  }
+
+  impl<T> ParallelSliceMut<T> for [T] {
+    fn par_iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+      self.iter_mut()
+    }
+  }
+}
+
 /// A Bundler combines a CSS file and all imported dependencies together into
 /// a single merged style sheet.
 pub struct Bundler<'a, 'o, 's, P> {
@@ -66,6 +118,13 @@ pub struct Bundler<'a, 'o, 's, P> {
   source_indexes: DashMap<PathBuf, u32>,
   stylesheets: Mutex<Vec<BundleStyleSheet<'a, 'o>>>,
   options: ParserOptions<'o, 'a>,
+  cache: Option<&'s BundleCache<'a>>,
+  preloaded: BundleCache<'a>,
+  hoist_license_comments: bool,
+  hoist_root_variables: bool,
+  hoist_layer_statements: bool,
+  targets: Option<Browsers>,
+  skipped_imports: Mutex<Vec<ImportDependency>>,
 }
 
 #[derive(Debug)]
@@ -81,7 +140,261 @@ struct BundleStyleSheet<'i, 'o> {
   loc: Location,
 }
 
-/// A trait to provide the contents of files to a Bundler.
+/// A cache of parsed style sheets, which can be shared between bundles to avoid
+/// parsing files that have not changed again.
+///
+/// Files are keyed by their resolved path. Once cached, a file is reused without being
+/// read again until it is [invalidated](BundleCache::invalidate). Invalidated files are
+/// read again on the next bundle, and only reparsed if their contents changed.
+///
+/// The cache is cleared when it is used with parser options that parse differently from
+/// the ones it was filled with. Warnings emitted while parsing a file are stored along with
+/// it, and reported again each time the file is reused.
+pub struct BundleCache<'a> {
+  entries: DashMap<PathBuf, CacheEntry<'a>>,
+  options: Mutex<Option<CacheOptions>>,
+}
+
+struct CacheEntry<'a> {
+  code: &'a str,
+  rules: CssRuleList<'a>,
+  license_comments: Vec<CowArcStr<'a>>,
+  source_map_url: Option<String>,
+  warnings: Vec<Error<ParserError<'a>>>,
+  source_index: u32,
+  invalidated: bool,
+}
+
+// The parser options that affect the parsed rules.
+#[derive(PartialEq)]
+struct CacheOptions {
+  nesting: bool,
+  nesting_spec: NestingSpec,
+  custom_media: bool,
+  scope: bool,
+  css_modules: Option<bool>,
+  error_recovery: bool,
+  strict: bool,
+  strict_exceptions: Option<HashSet<String>>,
+  strict_preludes: bool,
+  preserve_calc: bool,
+  preserve_var_fallbacks: bool,
+  deep_selectors: bool,
+  ignore_properties: Option<HashSet<String>>,
+  custom_at_rules: Option<HashMap<String, CustomAtRuleDefinition>>,
+  verbatim_values: bool,
+  comments: Comments,
+}
+
+impl<'a> Default for BundleCache<'a> {
+  fn default() -> Self {
+    BundleCache::new()
+  }
+}
+
+impl<'a> BundleCache<'a> {
+  /// Creates a new empty cache.
+  pub fn new() -> Self {
+    BundleCache {
+      entries: DashMap::new(),
+      options: Mutex::new(None),
+    }
+  }
+
+  /// Marks the given file as changed, so it is read again the next time it is bundled.
+  pub fn invalidate(&self, file: &Path) {
+    if let Some(mut entry) = self.entries.get_mut(file) {
+      entry.invalidated = true;
+    }
+  }
+
+  /// Removes all files from the cache.
+  pub fn clear(&self) {
+    self.entries.clear();
+  }
+
+  /// Returns the sources of the cached files. Other sources read by a provider, e.g. the previous
+  /// contents of a file that changed, are no longer borrowed by the cache, and may be freed once
+  /// the bundles that borrow from them are dropped.
+  pub fn sources(&self) -> Vec<&'a str> {
+    self.entries.iter().map(|entry| entry.code).collect()
+  }
+
+  fn set_options(&self, options: &ParserOptions) {
+    let cache_options = CacheOptions {
+      nesting: options.nesting,
+      nesting_spec: options.nesting_spec,
+      custom_media: options.custom_media,
+      scope: options.scope,
+      css_modules: options.css_modules.as_ref().map(|c| c.dashed_idents),
+      error_recovery: options.error_recovery,
+      strict: options.strict,
+      strict_exceptions: options.strict_exceptions.clone(),
+      strict_preludes: options.strict_preludes,
+      preserve_calc: options.preserve_calc,
+      preserve_var_fallbacks: options.preserve_var_fallbacks,
+      deep_selectors: options.deep_selectors,
+      ignore_properties: options.ignore_properties.clone(),
+      custom_at_rules: options.custom_at_rules.clone(),
+      verbatim_values: options.verbatim_values,
+      comments: options.comments,
+    };
+
+    let mut current = self.options.lock().unwrap();
+    if current.as_ref() != Some(&cache_options) {
+      self.entries.clear();
+      *current = Some(cache_options);
+    }
+  }
+
+  // Returns the cached source for a file, unless it was invalidated.
+  fn source(&self, file: &Path) -> Option<&'a str> {
+    self
+      .entries
+      .get(file)
+      .filter(|entry| !entry.invalidated)
+      .map(|entry| entry.code)
+  }
+
+  // Returns a copy of the cached style sheet for a file, if its source is unchanged.
+  fn get<'o>(
+    &self,
+    file: &Path,
+    code: &str,
+    source_index: u32,
+    options: ParserOptions<'o, 'a>,
+  ) -> Option<StyleSheet<'a, 'o>> {
+    let mut entry = self.entries.get_mut(file)?;
+    if entry.code != code {
+      return None;
+    }
+
+    entry.invalidated = false;
+    let mut rules = entry.rules.clone();
+    if entry.source_index != source_index {
+      set_source_index(&mut rules, source_index);
+    }
+
+    for warning in &entry.warnings {
+      options.push_warning(|| warning.clone());
+    }
+
+    let mut stylesheet = StyleSheet::new(vec![options.filename.clone()], rules, options);
+    stylesheet.source_map_urls = vec![entry.source_map_url.clone()];
+    stylesheet.license_comments = entry.license_comments.clone();
+    Some(stylesheet)
+  }
+
+  // Parses a file and adds it to the cache, along with the warnings emitted while parsing it.
+  fn parse<'o>(
+    &self,
+    file: &Path,
+    code: &'a str,
+    source_index: u32,
+    options: ParserOptions<'o, 'a>,
+  ) -> Result<StyleSheet<'a, 'o>, Error<ParserError<'a>>> {
+    let file_warnings = Arc::new(RwLock::new(Vec::new()));
+    let mut file_options = options.clone();
+    file_options.warnings = Some(file_warnings.clone());
+    let result = StyleSheet::parse(code, file_options);
+
+    let file_warnings = std::mem::take(&mut *file_warnings.write().unwrap());
+    for warning in &file_warnings {
+      options.push_warning(|| warning.clone());
+    }
+
+    let mut stylesheet = result?;
+    stylesheet.options = options;
+
+    self.entries.insert(
+      file.to_owned(),
+      CacheEntry {
+        code,
+        rules: stylesheet.rules.clone(),
+        license_comments: stylesheet.license_comments.clone(),
+        source_map_url: stylesheet.source_map_url(0).cloned(),
+        warnings: file_warnings,
+        source_index,
+        invalidated: false,
+      },
+    );
+    Ok(stylesheet)
+  }
+}
+
+// A cached file may end up at a different position in the bundle than the one it was
+// parsed at, so the source index of every rule location must be updated.
+fn set_source_index(rules: &mut CssRuleList, source_index: u32) {
+  for rule in &mut rules.0 {
+    match rule {
+      CssRule::Media(media) => set_source_index(&mut media.rules, source_index),
+      CssRule::Style(style) => {
+        set_at_rules_source_index(&mut style.declarations, source_index);
+        set_source_index(&mut style.rules, source_index)
+      }
+      CssRule::Supports(supports) => set_source_index(&mut supports.rules, source_index),
+      CssRule::MozDocument(document) => set_source_index(&mut document.rules, source_index),
+      CssRule::Nesting(nesting) => {
+        nesting.style.loc.source_index = source_index;
+        set_at_rules_source_index(&mut nesting.style.declarations, source_index);
+        set_source_index(&mut nesting.style.rules, source_index)
+      }
+      CssRule::LayerBlock(layer) => set_source_index(&mut layer.rules, source_index),
+      CssRule::Container(container) => set_source_index(&mut container.rules, source_index),
+      CssRule::Scope(scope) => set_source_index(&mut scope.rules, source_index),
+      CssRule::Custom(CustomAtRule {
+        body: Some(CustomAtRuleBody::RuleList(rules)),
+        ..
+      }) => set_source_index(rules, source_index),
+      CssRule::Custom(CustomAtRule {
+        body: Some(CustomAtRuleBody::DeclarationList(declarations)),
+        ..
+      }) => set_at_rules_source_index(declarations, source_index),
+      _ => {}
+    }
+
+    let loc = match rule {
+      CssRule::Media(rule) => &mut rule.loc,
+      CssRule::Import(rule) => &mut rule.loc,
+      CssRule::Style(rule) => &mut rule.loc,
+      CssRule::Keyframes(rule) => &mut rule.loc,
+      CssRule::FontFace(rule) => &mut rule.loc,
+      CssRule::FontPaletteValues(rule) => &mut rule.loc,
+      CssRule::Page(rule) => &mut rule.loc,
+      CssRule::Supports(rule) => &mut rule.loc,
+      CssRule::CounterStyle(rule) => &mut rule.loc,
+      CssRule::Namespace(rule) => &mut rule.loc,
+      CssRule::MozDocument(rule) => &mut rule.loc,
+      CssRule::Nesting(rule) => &mut rule.loc,
+      CssRule::Viewport(rule) => &mut rule.loc,
+      CssRule::CustomMedia(rule) => &mut rule.loc,
+      CssRule::LayerStatement(rule) => &mut rule.loc,
+      CssRule::LayerBlock(rule) => &mut rule.loc,
+      CssRule::Property(rule) => &mut rule.loc,
+      CssRule::Container(rule) => &mut rule.loc,
+      CssRule::Scope(rule) => &mut rule.loc,
+      CssRule::Comment(rule) => &mut rule.loc,
+      CssRule::Unknown(rule) => &mut rule.loc,
+      CssRule::Custom(rule) => &mut rule.loc,
+      CssRule::Ignored => continue,
+    };
+    loc.source_index = source_index;
+  }
+}
+
+fn set_at_rules_source_index(declarations: &mut DeclarationBlock, source_index: u32) {
+  for (_, rule) in &mut declarations.at_rules {
+    rule.loc.source_index = source_index;
+    match &mut rule.body {
+      Some(CustomAtRuleBody::RuleList(rules)) => set_source_index(rules, source_index),
+      Some(CustomAtRuleBody::DeclarationList(declarations)) => {
+        set_at_rules_source_index(declarations, source_index)
+      }
+      _ => {}
+    }
+  }
+}
+
 ///
 /// See [FileProvider](FileProvider) for an implementation that uses the
 /// file system.
@@ -99,8 +412,13 @@ pub trait SourceProvider: Send + Sync {
 
 /// Provides an implementation of [SourceProvider](SourceProvider)
 /// that reads files from the file system.
+///
+/// Files are decoded with [decode_source](decode_source): a UTF-8 byte order mark is removed,
+/// and files with a UTF-16 or UTF-32 byte order mark are transcoded to UTF-8 unless
+/// [transcode](FileProvider::transcode) is disabled.
 pub struct FileProvider {
   inputs: Mutex<Vec<*mut String>>,
+  transcode: bool,
 }
 
 impl FileProvider {
@@ -108,10 +426,114 @@ impl FileProvider {
   pub fn new() -> FileProvider {
     FileProvider {
       inputs: Mutex::new(Vec::new()),
+      transcode: true,
+    }
+  }
+
+  /// Sets whether files with a UTF-16 or UTF-32 byte order mark are transcoded to UTF-8.
+  /// If disabled, reading such a file returns an error naming the detected encoding.
+  /// Enabled by default.
+  pub fn transcode(&mut self, transcode: bool) {
+    self.transcode = transcode;
+  }
+}
+
+/// The encoding of a source file, detected from its byte order mark.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SourceEncoding {
+  Utf16Le,
+  Utf16Be,
+  Utf32Le,
+  Utf32Be,
+}
+
+impl SourceEncoding {
+  fn detect(bytes: &[u8]) -> Option<(SourceEncoding, usize)> {
+    // UTF-32LE must be checked first, since its BOM starts with the UTF-16LE BOM.
+    match bytes {
+      [0xff, 0xfe, 0, 0, ..] => Some((SourceEncoding::Utf32Le, 4)),
+      [0, 0, 0xfe, 0xff, ..] => Some((SourceEncoding::Utf32Be, 4)),
+      [0xff, 0xfe, ..] => Some((SourceEncoding::Utf16Le, 2)),
+      [0xfe, 0xff, ..] => Some((SourceEncoding::Utf16Be, 2)),
+      _ => None,
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    match self {
+      SourceEncoding::Utf16Le => "UTF-16LE",
+      SourceEncoding::Utf16Be => "UTF-16BE",
+      SourceEncoding::Utf32Le => "UTF-32LE",
+      SourceEncoding::Utf32Be => "UTF-32BE",
+    }
+  }
+
+  fn decode(&self, bytes: &[u8]) -> Option<String> {
+    match self {
+      SourceEncoding::Utf16Le | SourceEncoding::Utf16Be => {
+        let chunks = bytes.chunks_exact(2);
+        if !chunks.remainder().is_empty() {
+          return None;
+        }
+        let units = chunks.map(|c| match self {
+          SourceEncoding::Utf16Le => u16::from_le_bytes([c[0], c[1]]),
+          _ => u16::from_be_bytes([c[0], c[1]]),
+        });
+        char::decode_utf16(units).collect::<Result<String, _>>().ok()
+      }
+      SourceEncoding::Utf32Le | SourceEncoding::Utf32Be => {
+        let chunks = bytes.chunks_exact(4);
+        if !chunks.remainder().is_empty() {
+          return None;
+        }
+        chunks
+          .map(|c| {
+            let c = [c[0], c[1], c[2], c[3]];
+            char::from_u32(match self {
+              SourceEncoding::Utf32Le => u32::from_le_bytes(c),
+              _ => u32::from_be_bytes(c),
+            })
+          })
+          .collect()
+      }
     }
   }
 }
 
+/// Decodes the contents of a source file to a string.
+///
+/// A UTF-8 byte order mark is removed. Files starting with a UTF-16 or UTF-32 byte order mark
+/// are transcoded to UTF-8 if `transcode` is true. Otherwise, or if the file is not valid in
+/// the detected encoding, an [InvalidData](std::io::ErrorKind::InvalidData) error naming the file
+/// and the encoding is returned, e.g. `style.css is not UTF-8 encoded (detected UTF-16LE)`.
+pub fn decode_source(bytes: Vec<u8>, file: &Path, transcode: bool) -> Result<String, std::io::Error> {
+  let invalid = |reason: String| {
+    std::io::Error::new(
+      std::io::ErrorKind::InvalidData,
+      format!("{} is not UTF-8 encoded ({})", file.display(), reason),
+    )
+  };
+
+  if let Some((encoding, bom_len)) = SourceEncoding::detect(&bytes) {
+    if !transcode {
+      return Err(invalid(format!("detected {}", encoding.name())));
+    }
+    return encoding.decode(&bytes[bom_len..]).ok_or_else(|| {
+      invalid(format!(
+        "detected {}, but the file is not valid {}",
+        encoding.name(),
+        encoding.name()
+      ))
+    });
+  }
+
+  let mut source = String::from_utf8(bytes).map_err(|e| invalid(e.utf8_error().to_string()))?;
+  if source.starts_with('\u{feff}') {
+    source.drain(..'\u{feff}'.len_utf8());
+  }
+  Ok(source)
+}
+
 unsafe impl Sync for FileProvider {}
 unsafe impl Send for FileProvider {}
 
@@ -119,7 +541,7 @@ impl SourceProvider for FileProvider {
   type Error = std::io::Error;
 
   fn read<'a>(&'a self, file: &Path) -> Result<&'a str, Self::Error> {
-    let source = fs::read_to_string(file)?;
+    let source = decode_source(fs::read(file)?, file, self.transcode)?;
     let ptr = Box::into_raw(Box::new(source));
     self.inputs.lock().unwrap().push(ptr);
     // SAFETY: this is safe because the pointer is not dropped
@@ -142,6 +564,363 @@ impl Drop for FileProvider {
   }
 }
 
+/// Provides an implementation of [SourceProvider](SourceProvider)
+/// that reads files from memory, e.g. for tests, virtual file systems,
+/// or environments without file system access such as WebAssembly.
+///
+/// Specifiers are resolved relative to the directory of the originating file,
+/// and `.` and `..` components are normalized so that they match added paths.
+///
+/// # Example
+///
+/// ```
+/// use std::path::Path;
+/// use lightningcss::{
+///   bundler::{Bundler, MemoryProvider},
+///   stylesheet::{ParserOptions, PrinterOptions}
+/// };
+///
+/// let fs = MemoryProvider::new();
+/// fs.add("/styles/main.css", "@import '../theme.css'; .a { color: red }");
+/// fs.add("/theme.css", ".b { color: green }");
+/// let mut bundler = Bundler::new(&fs, None, ParserOptions::default());
+/// let stylesheet = bundler.bundle(Path::new("/styles/main.css")).unwrap();
+/// ```
+pub struct MemoryProvider {
+  files: Mutex<HashMap<PathBuf, Box<str>>>,
+  replaced: Mutex<Vec<Box<str>>>,
+}
+
+impl Default for MemoryProvider {
+  fn default() -> MemoryProvider {
+    MemoryProvider::new()
+  }
+}
+
+impl MemoryProvider {
+  /// Creates a new, empty MemoryProvider.
+  pub fn new() -> MemoryProvider {
+    MemoryProvider {
+      files: Mutex::new(HashMap::new()),
+      replaced: Mutex::new(Vec::new()),
+    }
+  }
+
+  /// Adds a file with the given contents, replacing any previous contents at the same path.
+  pub fn add<P: Into<PathBuf>, S: Into<String>>(&self, path: P, contents: S) {
+    let path = normalize_path(&path.into());
+    let previous = self.files.lock().unwrap().insert(path, contents.into().into_boxed_str());
+    if let Some(previous) = previous {
+      // Keep the previous contents alive, since they may still be borrowed.
+      self.replaced.lock().unwrap().push(previous);
+    }
+  }
+}
+
+impl SourceProvider for MemoryProvider {
+  type Error = std::io::Error;
+
+  fn read<'a>(&'a self, file: &Path) -> Result<&'a str, Self::Error> {
+    let files = self.files.lock().unwrap();
+    match files.get(&normalize_path(file)) {
+      // SAFETY: contents are boxed, so their address is stable, and they are
+      // not dropped until the MemoryProvider is, even if they are replaced.
+      Some(source) => Ok(unsafe { &*(&**source as *const str) }),
+      None => Err(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{} not found", file.display()),
+      )),
+    }
+  }
+
+  fn resolve(&self, specifier: &str, originating_file: &Path) -> Result<PathBuf, Self::Error> {
+    let dir = originating_file.parent().unwrap_or_else(|| Path::new(""));
+    Ok(normalize_path(&dir.join(specifier)))
+  }
+}
+
+// Removes `.` and `..` components from a path without accessing the file system.
+fn normalize_path(path: &Path) -> PathBuf {
+  use std::path::Component;
+
+  let mut result = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir => match result.components().next_back() {
+        Some(Component::Normal(_)) => {
+          result.pop();
+        }
+        // `..` cannot go above the root.
+        Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+        _ => result.push(".."),
+      },
+      c => result.push(c),
+    }
+  }
+  result
+}
+
+/// A boxed future returned by an [AsyncSourceProvider](AsyncSourceProvider).
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// An asynchronous version of [SourceProvider](SourceProvider), for sources that
+/// are loaded over the network or from a database, for example.
+///
+/// Use it with an [AsyncProvider](AsyncProvider) and [Bundler::bundle_async](Bundler::bundle_async).
+pub trait AsyncSourceProvider: Send + Sync {
+  /// A custom error.
+  type Error: std::error::Error + Send + Sync;
+
+  /// Reads the contents of the given file path to a string.
+  fn read<'a>(&'a self, file: &'a Path) -> BoxFuture<'a, Result<String, Self::Error>>;
+
+  /// Resolves the given import specifier to a file path given the file
+  /// which the import originated from.
+  fn resolve<'a>(
+    &'a self,
+    specifier: &'a str,
+    originating_file: &'a Path,
+  ) -> BoxFuture<'a, Result<PathBuf, Self::Error>>;
+}
+
+impl AsyncSourceProvider for FileProvider {
+  type Error = std::io::Error;
+
+  fn read<'a>(&'a self, file: &'a Path) -> BoxFuture<'a, Result<String, Self::Error>> {
+    let file = file.to_owned();
+    let transcode = self.transcode;
+    Box::pin(spawn_blocking(move || {
+      decode_source(fs::read(&file)?, &file, transcode)
+    }))
+  }
+
+  fn resolve<'a>(
+    &'a self,
+    specifier: &'a str,
+    originating_file: &'a Path,
+  ) -> BoxFuture<'a, Result<PathBuf, Self::Error>> {
+    Box::pin(std::future::ready(SourceProvider::resolve(
+      self,
+      specifier,
+      originating_file,
+    )))
+  }
+}
+
+/// A [SourceProvider](SourceProvider) backed by an [AsyncSourceProvider](AsyncSourceProvider).
+///
+/// Files are loaded into memory ahead of bundling by [Bundler::bundle_async](Bundler::bundle_async),
+/// which reads and resolves all files at the same depth of the import tree concurrently.
+pub struct AsyncProvider<P> {
+  provider: P,
+  sources: Mutex<HashMap<PathBuf, Box<str>>>,
+  resolutions: Mutex<HashMap<(String, PathBuf), PathBuf>>,
+}
+
+impl<P: AsyncSourceProvider> AsyncProvider<P> {
+  /// Creates a new AsyncProvider.
+  pub fn new(provider: P) -> Self {
+    AsyncProvider {
+      provider,
+      sources: Mutex::new(HashMap::new()),
+      resolutions: Mutex::new(HashMap::new()),
+    }
+  }
+
+  fn insert_source(&self, file: &Path, source: String) -> &str {
+    let mut sources = self.sources.lock().unwrap();
+    let source = sources.entry(file.to_owned()).or_insert_with(|| source.into_boxed_str());
+    // SAFETY: sources are boxed, so their address is stable, and they are never
+    // removed or replaced until the AsyncProvider is dropped.
+    unsafe { &*(&**source as *const str) }
+  }
+}
+
+impl<P: AsyncSourceProvider> SourceProvider for AsyncProvider<P> {
+  type Error = AsyncProviderError<P::Error>;
+
+  fn read<'a>(&'a self, file: &Path) -> Result<&'a str, Self::Error> {
+    let sources = self.sources.lock().unwrap();
+    match sources.get(file) {
+      // SAFETY: see `insert_source`.
+      Some(source) => Ok(unsafe { &*(&**source as *const str) }),
+      None => Err(AsyncProviderError::NotLoaded(file.to_owned())),
+    }
+  }
+
+  fn resolve(&self, specifier: &str, originating_file: &Path) -> Result<PathBuf, Self::Error> {
+    let resolutions = self.resolutions.lock().unwrap();
+    match resolutions.get(&(specifier.to_owned(), originating_file.to_owned())) {
+      Some(path) => Ok(path.clone()),
+      None => Err(AsyncProviderError::NotResolved(
+        specifier.to_owned(),
+        originating_file.to_owned(),
+      )),
+    }
+  }
+}
+
+/// An error returned by an [AsyncProvider](AsyncProvider).
+#[derive(Debug)]
+pub enum AsyncProviderError<E> {
+  /// An error returned by the [AsyncSourceProvider](AsyncSourceProvider).
+  Provider(E),
+  /// A file was read that was not loaded by [Bundler::bundle_async](Bundler::bundle_async).
+  NotLoaded(PathBuf),
+  /// A specifier was resolved from the given file that was not resolved by
+  /// [Bundler::bundle_async](Bundler::bundle_async).
+  NotResolved(String, PathBuf),
+}
+
+impl<E: std::error::Error> std::fmt::Display for AsyncProviderError<E> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      AsyncProviderError::Provider(err) => std::fmt::Display::fmt(err, f),
+      AsyncProviderError::NotLoaded(file) => {
+        write!(f, "{} was not loaded by Bundler::bundle_async", file.display())
+      }
+      AsyncProviderError::NotResolved(specifier, file) => write!(
+        f,
+        "`{}` from {} was not resolved by Bundler::bundle_async",
+        specifier,
+        file.display()
+      ),
+    }
+  }
+}
+
+impl<E: std::error::Error> std::error::Error for AsyncProviderError<E> {}
+
+// Returns the specifiers of the files the bundler will load as dependencies of a style sheet,
+// along with the location of the rule that references them. This must match `Bundler::load_file`.
+fn collect_dependencies(
+  stylesheet: &mut StyleSheet,
+  options: &ParserOptions,
+  targets: Option<Browsers>,
+) -> Vec<(String, Location)> {
+  let mut dependencies = Vec::new();
+  for rule in &mut stylesheet.rules.0 {
+    match rule {
+      CssRule::Import(import) => {
+        let supported = targets.and_then(|targets| import.supports.as_ref().and_then(|s| s.evaluate(targets)));
+        if supported != Some(false) {
+          dependencies.push((import.url.to_string(), import.loc))
+        }
+      }
+      CssRule::Style(style) if options.css_modules.is_some() => {
+        let dashed_idents = matches!(&options.css_modules, Some(css_modules) if css_modules.dashed_idents);
+        for property in style
+          .declarations
+          .declarations
+          .iter_mut()
+          .chain(style.declarations.important_declarations.iter_mut())
+        {
+          match property {
+            Property::Composes(composes) => {
+              if let Some(Specifier::File(file)) = &composes.from {
+                dependencies.push((file.to_string(), style.loc));
+              }
+            }
+            Property::Custom(CustomProperty { value, .. })
+            | Property::Unparsed(UnparsedProperty { value, .. })
+              if dashed_idents =>
+            {
+              for name in visit_vars(value).collect::<Vec<_>>() {
+                if let Some(Specifier::File(file)) = &name.from {
+                  dependencies.push((file.to_string(), style.loc));
+                }
+              }
+            }
+            _ => {}
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+  dependencies
+}
+
+// A future which polls a list of futures concurrently, and resolves with their outputs in order.
+struct JoinAll<'a, T> {
+  futures: Vec<Option<BoxFuture<'a, T>>>,
+  outputs: Vec<Option<T>>,
+}
+
+impl<'a, T> JoinAll<'a, T> {
+  fn new(futures: Vec<BoxFuture<'a, T>>) -> Self {
+    JoinAll {
+      outputs: futures.iter().map(|_| None).collect(),
+      futures: futures.into_iter().map(Some).collect(),
+    }
+  }
+}
+
+// The futures are boxed, and outputs are never pinned.
+impl<'a, T> Unpin for JoinAll<'a, T> {}
+
+impl<'a, T> Future for JoinAll<'a, T> {
+  type Output = Vec<T>;
+
+  fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let this = &mut *self;
+    let mut done = true;
+    for (future, output) in this.futures.iter_mut().zip(this.outputs.iter_mut()) {
+      if let Some(f) = future {
+        match f.as_mut().poll(cx) {
+          Poll::Ready(value) => {
+            *output = Some(value);
+            *future = None;
+          }
+          Poll::Pending => done = false,
+        }
+      }
+    }
+
+    if done {
+      Poll::Ready(this.outputs.drain(..).map(|output| output.unwrap()).collect())
+    } else {
+      Poll::Pending
+    }
+  }
+}
+
+// Runs a blocking function on the rayon thread pool, and returns a future which resolves with its result.
+// This keeps file system reads from blocking the async runtime, without depending on a specific one.
+fn spawn_blocking<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> BlockingTask<T> {
+  let state = Arc::new(Mutex::new((None, None::<Waker>)));
+  let thread_state = state.clone();
+  rayon::spawn(move || {
+    let result = f();
+    let mut state = thread_state.lock().unwrap();
+    state.0 = Some(result);
+    if let Some(waker) = state.1.take() {
+      waker.wake();
+    }
+  });
+  BlockingTask { state }
+}
+
+struct BlockingTask<T> {
+  state: Arc<Mutex<(Option<T>, Option<Waker>)>>,
+}
+
+impl<T> Future for BlockingTask<T> {
+  type Output = T;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+    let mut state = self.state.lock().unwrap();
+    match state.0.take() {
+      Some(result) => Poll::Ready(result),
+      None => {
+        state.1 = Some(cx.waker().clone());
+        Poll::Pending
+      }
+    }
+  }
+}
+
 /// An error that could occur during bundling.
 #[derive(Debug, Serialize)]
 pub enum BundleErrorKind<'i, T: std::error::Error> {
@@ -153,6 +932,9 @@ pub enum BundleErrorKind<'i, T: std::error::Error> {
   UnsupportedLayerCombination,
   /// Unsupported media query boolean logic was encountered.
   UnsupportedMediaBooleanLogic,
+  /// Hoisting a `:root` rule with custom properties would move it before a different
+  /// definition of the given custom property.
+  RootVariableConflict(String),
   /// A custom resolver error.
   ResolverError(#[serde(skip)] T),
 }
@@ -174,12 +956,31 @@ impl<'i, T: std::error::Error> std::fmt::Display for BundleErrorKind<'i, T> {
       UnsupportedImportCondition => write!(f, "Unsupported import condition"),
       UnsupportedLayerCombination => write!(f, "Unsupported layer combination in @import"),
       UnsupportedMediaBooleanLogic => write!(f, "Unsupported boolean logic in @import media query"),
+      RootVariableConflict(name) => write!(
+        f,
+        "Cannot hoist :root variables because {} is defined differently by an earlier rule",
+        name
+      ),
       ResolverError(err) => std::fmt::Display::fmt(&err, f),
     }
   }
 }
 
 impl<'i, T: std::error::Error> BundleErrorKind<'i, T> {
+  /// Returns a stable code identifying the type of error, i.e. the name of the variant.
+  /// Parser errors return the code of the underlying [ParserError](ParserError).
+  pub fn code(&self) -> &'static str {
+    use BundleErrorKind::*;
+    match self {
+      ParserError(err) => err.code(),
+      UnsupportedImportCondition => "UnsupportedImportCondition",
+      UnsupportedLayerCombination => "UnsupportedLayerCombination",
+      UnsupportedMediaBooleanLogic => "UnsupportedMediaBooleanLogic",
+      RootVariableConflict(..) => "RootVariableConflict",
+      ResolverError(..) => "ResolverError",
+    }
+  }
+
   #[deprecated(note = "use `BundleErrorKind::to_string()` or `std::fmt::Display` instead")]
   #[allow(missing_docs)]
   pub fn reason(&self) -> String {
@@ -198,10 +999,75 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
       source_indexes: DashMap::new(),
       stylesheets: Mutex::new(Vec::new()),
       options,
+      cache: None,
+      preloaded: BundleCache::new(),
+      hoist_license_comments: false,
+      hoist_root_variables: false,
+      hoist_layer_statements: false,
+      targets: None,
+      skipped_imports: Mutex::new(Vec::new()),
     }
   }
 
-  /// Bundles the given entry file and all dependencies into a single style sheet.
+  /// Creates a new Bundler which reuses parsed files from the given cache, and
+  /// adds newly parsed files to it. See [BundleCache](BundleCache) for details.
+  pub fn with_cache(
+    fs: &'a P,
+    source_map: Option<&'s mut SourceMap>,
+    options: ParserOptions<'o, 'a>,
+    cache: &'s BundleCache<'a>,
+  ) -> Self {
+    let mut bundler = Bundler::new(fs, source_map, options);
+    cache.set_options(&bundler.options);
+    bundler.cache = Some(cache);
+    bundler
+  }
+
+  /// Sets whether license comments (`/*! ... */`) at the top of each bundled file are hoisted
+  /// to the top of the bundle. Identical comments are only included once. Otherwise, license
+  /// comments are removed from the bundle.
+  pub fn hoist_license_comments(&mut self, hoist: bool) {
+    self.hoist_license_comments = hoist;
+  }
+
+  /// Sets whether `:root` rules that only declare custom properties are moved from imported files
+  /// to the top of the bundle, after any `@layer` statements, so that the variables are defined
+  /// before they are used regardless of the import order. An error is returned if this would move
+  /// a rule before a different definition of the same custom property.
+  pub fn hoist_root_variables(&mut self, hoist: bool) {
+    self.hoist_root_variables = hoist;
+  }
+
+  /// Sets whether top-level `@layer` statements from all bundled files are combined into a single
+  /// statement at the top of the bundle, declaring the layers in the order they are first seen, and
+  /// top-level `@layer` blocks with the same name are merged.
+  pub fn hoist_layer_statements(&mut self, hoist: bool) {
+    self.hoist_layer_statements = hoist;
+  }
+
+  /// Sets the browser targets used to evaluate the `supports()` conditions of `@import` rules.
+  /// Imports with a condition that no target supports are skipped without reading the file, and
+  /// imports with a condition that all targets support are inlined without an `@supports` rule.
+  /// Conditions that cannot be evaluated are preserved.
+  pub fn targets(&mut self, targets: Option<Browsers>) {
+    self.targets = targets;
+  }
+
+  /// Returns the `@import` rules skipped during the last bundle because their `supports()`
+  /// condition is not supported by any target, sorted by location. See [targets](Bundler::targets).
+  pub fn take_skipped_imports(&mut self) -> Vec<ImportDependency> {
+    let mut skipped = std::mem::take(self.skipped_imports.get_mut().unwrap());
+    skipped.sort_by(|a, b| {
+      (&a.loc.file_path, a.loc.start.line, a.loc.start.column).cmp(&(
+        &b.loc.file_path,
+        b.loc.start.line,
+        b.loc.start.column,
+      ))
+    });
+    skipped
+  }
+
+  /// Bundles the given entry file and all dependencies into a single style sheet.
   pub fn bundle<'e>(
     &mut self,
     entry: &'e Path,
@@ -227,7 +1093,18 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
 
     // Phase 3: concatenate.
     let mut rules: Vec<CssRule<'a>> = Vec::new();
-    self.inline(&mut rules);
+    let mut license_comments = Vec::new();
+    self.inline(&mut rules, &mut license_comments);
+    if self.hoist_layer_statements {
+      hoist_layer_statements(&mut rules);
+    }
+
+    if self.hoist_root_variables {
+      rules = hoist_root_variables(rules).map_err(|(name, loc)| Error {
+        kind: BundleErrorKind::RootVariableConflict(name),
+        loc: Some(ErrorLocation::new(loc, self.find_filename(loc.source_index))),
+      })?;
+    }
 
     let sources = self
       .stylesheets
@@ -238,6 +1115,9 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
       .collect();
 
     let mut stylesheet = StyleSheet::new(sources, CssRuleList(rules), self.options.clone());
+    if self.hoist_license_comments {
+      stylesheet.license_comments = license_comments;
+    }
 
     stylesheet.source_map_urls = self
       .stylesheets
@@ -329,17 +1209,43 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
 
     drop(stylesheets); // ensure we aren't holding the lock anymore
 
-    let code = self.fs.read(file).map_err(|e| Error {
-      kind: BundleErrorKind::ResolverError(e),
-      loc: Some(ErrorLocation::new(rule.loc, self.find_filename(rule.loc.source_index))),
-    })?;
+    // Files in the cache are not read again unless they were invalidated.
+    let code = match self.cache.and_then(|cache| cache.source(file)) {
+      Some(code) => code,
+      None => self.fs.read(file).map_err(|e| Error {
+        kind: BundleErrorKind::ResolverError(e),
+        loc: Some(ErrorLocation::new(rule.loc, self.find_filename(rule.loc.source_index))),
+      })?,
+    };
 
     let mut opts = self.options.clone();
     let filename = file.to_str().unwrap();
     opts.filename = filename.to_owned();
     opts.source_index = source_index;
 
-    let mut stylesheet = StyleSheet::parse(code, opts)?;
+    let cached = self
+      .cache
+      .and_then(|cache| cache.get(file, code, source_index, opts.clone()))
+      .or_else(|| self.preloaded.get(file, code, source_index, opts.clone()));
+    let mut stylesheet = match (cached, self.cache) {
+      (Some(stylesheet), _) => stylesheet,
+      (None, Some(cache)) => cache.parse(file, code, source_index, opts)?,
+      (None, None) => StyleSheet::parse(code, opts)?,
+    };
+
+    // Source maps referenced by URL, e.g. in Sass output, are read through the source provider,
+    // and stored as data URLs so that they are chained like inline source maps.
+    if self.source_map.is_some() {
+      if let Some(url) = stylesheet.source_map_url(0).filter(|url| !url.contains(':')) {
+        let map = self.fs.resolve(url, file).ok().and_then(|path| self.fs.read(&path).ok());
+        if let Some(map) = map.filter(|map| SourceMap::from_json("/", map).is_ok()) {
+          stylesheet.source_map_urls[0] = Some(format!(
+            "data:application/json;base64,{}",
+            data_encoding::BASE64.encode(map.as_bytes())
+          ));
+        }
+      }
+    }
 
     if let Some(source_map) = &self.source_map {
       // Only add source if we don't have an input source map.
@@ -368,6 +1274,18 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
         }
 
         if let CssRule::Import(import) = r {
+          // Skip imports that no target supports, and drop conditions that all targets support.
+          let supported = self
+            .targets
+            .and_then(|targets| import.supports.as_ref().and_then(|supports| supports.evaluate(targets)));
+          if supported == Some(false) {
+            let mut dependency = ImportDependency::new(import, filename);
+            dependency.skipped = true;
+            self.skipped_imports.lock().unwrap().push(dependency);
+            *r = CssRule::Ignored;
+            return None;
+          }
+
           let specifier = &import.url;
 
           // Combine media queries and supports conditions from parent
@@ -414,7 +1332,11 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
               ImportRule {
                 layer,
                 media,
-                supports: combine_supports(rule.supports.clone(), &import.supports),
+                supports: if supported == Some(true) {
+                  rule.supports.clone()
+                } else {
+                  combine_supports(rule.supports.clone(), &import.supports)
+                },
                 url: "".into(),
                 loc: import.loc,
               },
@@ -452,14 +1374,14 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
                 .filter_map(|d| match d {
                   Property::Composes(composes) => self
                     .add_css_module_dep(file, &rule, style.loc, composes.loc, &mut composes.from)
-                    .map(|result| rayon::iter::Either::Left(rayon::iter::once(result))),
+                    .map(|result| Either::Left(once(result))),
 
                   // Handle variable references if the dashed_idents option is present.
                   Property::Custom(CustomProperty { value, .. })
                   | Property::Unparsed(UnparsedProperty { value, .. })
                     if matches!(&self.options.css_modules, Some(css_modules) if css_modules.dashed_idents) =>
                   {
-                    Some(rayon::iter::Either::Right(visit_vars(value).filter_map(|name| {
+                    Some(Either::Right(visit_vars(value).filter_map(|name| {
                       self.add_css_module_dep(
                         file,
                         &rule,
@@ -580,17 +1502,25 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
     }
   }
 
-  fn inline(&mut self, dest: &mut Vec<CssRule<'a>>) {
-    process(self.stylesheets.get_mut().unwrap(), 0, dest);
+  fn inline(&mut self, dest: &mut Vec<CssRule<'a>>, license_comments: &mut Vec<CowArcStr<'a>>) {
+    process(self.stylesheets.get_mut().unwrap(), 0, dest, license_comments);
 
     fn process<'a>(
       stylesheets: &mut Vec<BundleStyleSheet<'a, '_>>,
       source_index: u32,
       dest: &mut Vec<CssRule<'a>>,
+      license_comments: &mut Vec<CowArcStr<'a>>,
     ) {
       let stylesheet = &mut stylesheets[source_index as usize];
       let mut rules = std::mem::take(&mut stylesheet.stylesheet.as_mut().unwrap().rules.0);
 
+      // Collect license comments in the order the files are concatenated, without duplicates.
+      for comment in std::mem::take(&mut stylesheet.stylesheet.as_mut().unwrap().license_comments) {
+        if !license_comments.contains(&comment) {
+          license_comments.push(comment);
+        }
+      }
+
       // Hoist css modules deps
       let mut dep_index = 0;
       for i in 0..stylesheet.css_modules_deps.len() {
@@ -599,7 +1529,7 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
 
         // Include the dependency if this is the first instance as computed earlier.
         if resolved.parent_source_index == source_index && resolved.parent_dep_index == dep_index as u32 {
-          process(stylesheets, dep_source_index, dest);
+          process(stylesheets, dep_source_index, dest, license_comments);
         }
 
         dep_index += 1;
@@ -614,7 +1544,7 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
 
             // Include the dependency if this is the last instance as computed earlier.
             if resolved.parent_source_index == source_index && resolved.parent_dep_index == dep_index {
-              process(stylesheets, dep_source_index, dest);
+              process(stylesheets, dep_source_index, dest, license_comments);
             }
 
             *rule = CssRule::Ignored;
@@ -635,6 +1565,16 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
       // Wrap rules in the appropriate @layer, @media, and @supports rules.
       let stylesheet = &mut stylesheets[source_index as usize];
 
+      // @custom-media rules are global, and are only collected at the top level,
+      // so hoist them out of any conditional wrappers.
+      if stylesheet.layer.is_some() || !stylesheet.media.media_queries.is_empty() || stylesheet.supports.is_some()
+      {
+        let (custom_media, other): (Vec<_>, Vec<_>) =
+          rules.into_iter().partition(|rule| matches!(rule, CssRule::CustomMedia(_)));
+        dest.extend(custom_media);
+        rules = other;
+      }
+
       if stylesheet.layer.is_some() {
         rules = vec![CssRule::LayerBlock(LayerBlockRule {
           name: stylesheet.layer.take().unwrap(),
@@ -664,6 +1604,134 @@ impl<'a, 'o, 's, P: SourceProvider> Bundler<'a, 'o, 's, P> {
   }
 }
 
+/// Replaces the top-level `@layer` statements in the bundle with a single statement at the top, which
+/// declares the layers in the order they are first seen across all of the bundled files, and merges
+/// top-level `@layer` blocks with the same name. Once all layers are declared up front, moving rules
+/// between blocks of the same layer cannot change the layer order. Rules after an anonymous layer, or
+/// a layer within a conditional rule, are left in place, since the layer order would otherwise change.
+fn hoist_layer_statements<'a>(rules: &mut Vec<CssRule<'a>>) {
+  let mut names = Vec::new();
+  let mut blocks: Vec<usize> = Vec::new();
+  let mut loc = None;
+  for i in 0..rules.len() {
+    if let CssRule::LayerStatement(statement) = &rules[i] {
+      for name in &statement.names {
+        add_layer_name(&mut names, name.clone());
+      }
+      loc.get_or_insert(statement.loc);
+      rules[i] = CssRule::Ignored;
+      continue;
+    }
+
+    if !collect_layer_names(&rules[i], &[], &mut names) {
+      break;
+    }
+
+    if let CssRule::LayerBlock(LayerBlockRule { name: Some(name), .. }) = &rules[i] {
+      let existing = blocks
+        .iter()
+        .copied()
+        .find(|idx| matches!(&rules[*idx], CssRule::LayerBlock(block) if block.name.as_ref() == Some(name)));
+      match existing {
+        Some(idx) => {
+          if let CssRule::LayerBlock(block) = std::mem::replace(&mut rules[i], CssRule::Ignored) {
+            if let CssRule::LayerBlock(existing) = &mut rules[idx] {
+              existing.rules.0.extend(block.rules.0);
+              loc.get_or_insert(existing.loc);
+            }
+          }
+        }
+        None => blocks.push(i),
+      }
+    }
+  }
+
+  if let Some(loc) = loc {
+    rules.retain(|rule| !matches!(rule, CssRule::Ignored));
+    rules.insert(0, CssRule::LayerStatement(LayerStatementRule { names, loc }));
+  }
+}
+
+/// Moves `:root` rules that only declare custom properties from imported files to the top of the bundle,
+/// after any `@layer` and `@namespace` statements. Returns the name and location of the custom property
+/// if a rule would be moved before a different definition of it.
+fn hoist_root_variables<'a>(rules: Vec<CssRule<'a>>) -> Result<Vec<CssRule<'a>>, (String, Location)> {
+  let start = rules
+    .iter()
+    .position(|rule| !matches!(rule, CssRule::LayerStatement(_) | CssRule::Namespace(_)))
+    .unwrap_or(rules.len());
+
+  let mut result = Vec::with_capacity(rules.len());
+  let mut hoisted = Vec::new();
+  let mut rest = Vec::new();
+  let mut defined: Vec<CustomProperty<'a>> = Vec::new();
+  for (i, rule) in rules.into_iter().enumerate() {
+    if i < start {
+      result.push(rule);
+      continue;
+    }
+
+    match rule {
+      CssRule::Style(style) if style.loc.source_index != 0 && is_root_variables(&style) => {
+        for (property, _) in style.declarations.iter() {
+          if let Property::Custom(custom) = property {
+            if defined.iter().any(|d| d.name == custom.name && d.value != custom.value) {
+              return Err((custom.name.to_string(), style.loc));
+            }
+          }
+        }
+        hoisted.push(CssRule::Style(style));
+      }
+      rule => {
+        collect_custom_properties(&rule, &mut defined);
+        rest.push(rule);
+      }
+    }
+  }
+
+  result.extend(hoisted);
+  result.extend(rest);
+  Ok(result)
+}
+
+fn is_root_variables(style: &StyleRule) -> bool {
+  style.selectors.0.len() == 1
+    && style.selectors.0[0].len() == 1
+    && matches!(
+      style.selectors.0[0].iter_raw_match_order().next(),
+      Some(SelectorComponent::Root)
+    )
+    && style.rules.0.is_empty()
+    && !style.declarations.is_empty()
+    && style
+      .declarations
+      .iter()
+      .all(|(property, _)| matches!(property, Property::Custom(_)))
+}
+
+fn collect_custom_properties<'a>(rule: &CssRule<'a>, defined: &mut Vec<CustomProperty<'a>>) {
+  let rules = match rule {
+    CssRule::Style(style) => {
+      for (property, _) in style.declarations.iter() {
+        if let Property::Custom(custom) = property {
+          defined.push(custom.clone());
+        }
+      }
+      &style.rules
+    }
+    CssRule::Media(media) => &media.rules,
+    CssRule::Supports(supports) => &supports.rules,
+    CssRule::LayerBlock(layer) => &layer.rules,
+    CssRule::Container(container) => &container.rules,
+    CssRule::Scope(scope) => &scope.rules,
+    _ => return,
+  };
+
+  for rule in &rules.0 {
+    collect_custom_properties(rule, defined);
+  }
+}
+
 fn combine_supports<'a>(
   a: Option<SupportsCondition<'a>>,
   b: &Option<SupportsCondition<'a>>,
@@ -678,6 +1746,118 @@ fn combine_supports<'a>(
   }
 }
 
+impl<'a, 'o, 's, P: AsyncSourceProvider> Bundler<'a, 'o, 's, AsyncProvider<P>> {
+  /// Bundles the given entry file and all dependencies into a single style sheet, after loading
+  /// them asynchronously. Reads and resolves run concurrently on the executor awaiting the result.
+  pub async fn bundle_async<'e>(
+    &mut self,
+    entry: &'e Path,
+  ) -> Result<StyleSheet<'a, 'o>, Error<BundleErrorKind<'a, AsyncProviderError<P::Error>>>> {
+    self.load_async(entry).await?;
+    self.bundle(entry)
+  }
+
+  // Reads all files in the import tree of the entry, one level of the tree at a time. Each file is
+  // parsed to find its dependencies, and the style sheet is reused by `load_file` when bundling.
+  async fn load_async(
+    &self,
+    entry: &Path,
+  ) -> Result<(), Error<BundleErrorKind<'a, AsyncProviderError<P::Error>>>> {
+    let fs = self.fs;
+    let cache = self.cache.unwrap_or(&self.preloaded);
+    let mut seen = HashSet::new();
+    seen.insert(entry.to_owned());
+
+    // Each file to load, and the location of the rule that referenced it.
+    let mut pending: Vec<(PathBuf, Option<ErrorLocation>)> = Vec::new();
+    if !fs.sources.lock().unwrap().contains_key(entry) {
+      pending.push((entry.to_owned(), None));
+    }
+
+    while !pending.is_empty() {
+      let sources = JoinAll::new(pending.iter().map(|(file, _)| fs.provider.read(file)).collect()).await;
+
+      let mut dependencies = Vec::new();
+      let mut source_maps = Vec::new();
+      for ((file, loc), source) in pending.drain(..).zip(sources) {
+        let source = source.map_err(|e| Error {
+          kind: BundleErrorKind::ResolverError(AsyncProviderError::Provider(e)),
+          loc,
+        })?;
+
+        let code = fs.insert_source(&file, source);
+        let filename = file.to_string_lossy().into_owned();
+        let mut opts = self.options.clone();
+        opts.filename = filename.clone();
+        // Warnings are stored in the cache, and reported when `load_file` reuses the style sheet.
+        opts.warnings = None;
+
+        // Parse errors are reported while bundling, which stops before reading any dependencies.
+        if let Ok(mut stylesheet) = cache.parse(&file, code, 0, opts) {
+          for (specifier, loc) in collect_dependencies(&mut stylesheet, &self.options, self.targets) {
+            dependencies.push((specifier, file.clone(), ErrorLocation::new(loc, filename.clone())));
+          }
+          // Source maps referenced by URL are chained by `load_file`, so they must be loaded too.
+          if self.source_map.is_some() {
+            if let Some(url) = stylesheet.source_map_url(0).filter(|url| !url.contains(':')) {
+              source_maps.push((url.clone(), file.clone()));
+            }
+          }
+        }
+      }
+
+      self.load_source_maps(source_maps).await;
+
+      let resolved = JoinAll::new(
+        dependencies
+          .iter()
+          .map(|(specifier, file, _)| fs.provider.resolve(specifier, file))
+          .collect(),
+      )
+      .await;
+
+      for ((specifier, file, loc), result) in dependencies.into_iter().zip(resolved) {
+        let path = result.map_err(|e| Error {
+          kind: BundleErrorKind::ResolverError(AsyncProviderError::Provider(e)),
+          loc: Some(loc.clone()),
+        })?;
+
+        if seen.insert(path.clone()) && !fs.sources.lock().unwrap().contains_key(&path) {
+          pending.push((path.clone(), Some(loc)));
+        }
+        fs.resolutions.lock().unwrap().insert((specifier, file), path);
+      }
+    }
+
+    Ok(())
+  }
+
+  // Reads the source maps referenced by the given URLs and files. Like `load_file`, source maps
+  // that cannot be resolved or read are ignored, so they are left out of the provider.
+  async fn load_source_maps(&self, source_maps: Vec<(String, PathBuf)>) {
+    let fs = self.fs;
+    let resolved =
+      JoinAll::new(source_maps.iter().map(|(url, file)| fs.provider.resolve(url, file)).collect()).await;
+
+    let mut paths = Vec::new();
+    for ((url, file), result) in source_maps.into_iter().zip(resolved) {
+      if let Ok(path) = result {
+        if !fs.sources.lock().unwrap().contains_key(&path) {
+          paths.push(path.clone());
+        }
+        fs.resolutions.lock().unwrap().insert((url, file), path);
+      }
+    }
+
+    let sources = JoinAll::new(paths.iter().map(|path| fs.provider.read(path)).collect()).await;
+    for (path, source) in paths.into_iter().zip(sources) {
+      if let Ok(source) = source {
+        fs.insert_source(&path, source);
+      }
+    }
+  }
+}
+
 fn visit_vars<'a, 'b>(
   token_list: &'b mut TokenList<'a>,
 ) -> impl ParallelIterator<Item = &'b mut DashedIdentReference<'a>> {
@@ -713,8 +1893,9 @@ mod tests {
   use super::*;
   use crate::{
     css_modules::{self, CssModuleExports, CssModuleReference},
-    stylesheet::{MinifyOptions, PrinterOptions},
+    stylesheet::{Comments, MinifyOptions, PrinterOptions},
     targets::Browsers,
+    traits::ToCss,
   };
   use indoc::indoc;
   use std::collections::HashMap;
@@ -735,6 +1916,26 @@ mod tests {
     }
   }
 
+  /// A provider whose files can change between bundles, and which counts reads.
+  struct ChangingProvider {
+    map: Mutex<HashMap<PathBuf, String>>,
+    reads: std::sync::atomic::AtomicUsize,
+  }
+
+  impl SourceProvider for ChangingProvider {
+    type Error = std::io::Error;
+
+    fn read<'a>(&'a self, file: &Path) -> Result<&'a str, Self::Error> {
+      self.reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+      let source = self.map.lock().unwrap().get(file).unwrap().clone();
+      Ok(Box::leak(source.into_boxed_str()))
+    }
+
+    fn resolve(&self, specifier: &str, originating_file: &Path) -> Result<PathBuf, Self::Error> {
+      Ok(originating_file.with_file_name(specifier))
+    }
+  }
+
   /// Stand-in for a user-authored `SourceProvider` with application-specific logic.
   struct CustomProvider {
     map: HashMap<PathBuf, String>,
@@ -767,6 +1968,54 @@ mod tests {
     }
   }
 
+  /// An asynchronous in-memory provider, which reads files on other threads.
+  struct AsyncTestProvider {
+    map: Arc<HashMap<PathBuf, String>>,
+  }
+
+  impl AsyncSourceProvider for AsyncTestProvider {
+    type Error = std::io::Error;
+
+    fn read<'a>(&'a self, file: &'a Path) -> BoxFuture<'a, Result<String, Self::Error>> {
+      let map = self.map.clone();
+      let file = file.to_owned();
+      Box::pin(spawn_blocking(move || Ok(map.get(&file).unwrap().clone())))
+    }
+
+    fn resolve<'a>(
+      &'a self,
+      specifier: &'a str,
+      originating_file: &'a Path,
+    ) -> BoxFuture<'a, Result<PathBuf, Self::Error>> {
+      let result = if specifier.starts_with("missing") {
+        Err(std::io::Error::new(std::io::ErrorKind::NotFound, specifier.to_owned()))
+      } else {
+        Ok(originating_file.with_file_name(specifier))
+      };
+      Box::pin(std::future::ready(result))
+    }
+  }
+
+  fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(std::thread::Thread);
+
+    impl std::task::Wake for ThreadWaker {
+      fn wake(self: Arc<Self>) {
+        self.0.unpark();
+      }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+      match future.as_mut().poll(&mut cx) {
+        Poll::Ready(output) => return output,
+        Poll::Pending => std::thread::park(),
+      }
+    }
+  }
+
   macro_rules! fs(
     { $($key:literal: $value:expr),* } => {
       {
@@ -1204,6 +2453,129 @@ mod tests {
     "#}
     );
 
+    let res = bundle_custom_media(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "media.css";
+          @media (--foo) {
+            .a { color: green }
+          }
+        "#,
+          "/media.css": r#"
+          @custom-media --foo print;
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      res,
+      indoc! { r#"
+      @media print {
+        .a {
+          color: green;
+        }
+      }
+    "#}
+    );
+
+    let res = bundle_custom_media(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "b.css";
+          @media (--foo) {
+            .a { color: green }
+          }
+        "#,
+          "/b.css": r#"
+          @import "media.css";
+          .b { color: red }
+        "#,
+          "/media.css": r#"
+          @custom-media --foo print;
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      res,
+      indoc! { r#"
+      .b {
+        color: red;
+      }
+
+      @media print {
+        .a {
+          color: green;
+        }
+      }
+    "#}
+    );
+
+    let res = bundle_custom_media(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "media.css" screen;
+          @media (--foo) {
+            .a { color: green }
+          }
+        "#,
+          "/media.css": r#"
+          @custom-media --foo print;
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      res,
+      indoc! { r#"
+      @media print {
+        .a {
+          color: green;
+        }
+      }
+    "#}
+    );
+
+    let res = bundle_custom_media(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "media.css" supports(display: grid);
+          @media (--foo) {
+            .a { color: green }
+          }
+        "#,
+          "/media.css": r#"
+          @custom-media --foo print;
+          .b { color: red }
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      res,
+      indoc! { r#"
+      @supports (display: grid) {
+        .b {
+          color: red;
+        }
+      }
+
+      @media print {
+        .a {
+          color: green;
+        }
+      }
+    "#}
+    );
+
     let res = bundle(
       TestProvider {
         map: fs! {
@@ -1663,163 +3035,673 @@ mod tests {
   }
 
   #[test]
-  fn test_css_module() {
-    macro_rules! map {
-      { $($key:expr => $val:expr),* } => {
-        HashMap::from([
-          $(($key.to_owned(), $val.to_owned()),)*
-        ])
-      };
-    }
+  fn test_empty_files() {
+    let res = bundle(
+      TestProvider {
+        map: fs! {
+          "/a.css": ""
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(res, "");
 
-    let (code, exports) = bundle_css_module(
+    let res = bundle(
       TestProvider {
         map: fs! {
           "/a.css": r#"
-          @import "b.css";
-          .a { color: red }
-        "#,
-          "/b.css": r#"
-          .a { color: green }
-        "#
+            /* comment */
+            @import "b.css";
+          "#,
+          "/b.css": "  \n  "
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(res, "");
+
+    let (code, exports) = bundle_css_module(
+      TestProvider {
+        map: fs! {
+          "/a.css": "/* comment */"
         },
       },
       "/a.css",
     );
+    assert_eq!(code, "");
+    assert!(exports.is_empty());
+  }
+
+  #[test]
+  fn test_license_comments() {
+    fn bundle_license_comments<P: SourceProvider>(fs: P, entry: &str, hoist: bool) -> String {
+      let mut bundler = Bundler::new(&fs, None, ParserOptions::default());
+      bundler.hoist_license_comments(hoist);
+      let mut stylesheet = bundler.bundle(Path::new(entry)).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          remove_comments: false,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      stylesheet.to_css(PrinterOptions::default()).unwrap().code
+    }
+
+    let fs = || TestProvider {
+      map: fs! {
+        "/a.css": r#"
+        /*! a.css license */
+        @import "b.css";
+        @import "c.css";
+        .a { color: red }
+      "#,
+        "/b.css": r#"
+        /*! shared license */
+        /*! b.css license */
+        .b { color: green }
+      "#,
+        "/c.css": r#"
+        /*! shared license */
+        .c { color: blue }
+      "#
+      },
+    };
+
+    let res = bundle_license_comments(fs(), "/a.css", true);
     assert_eq!(
-      code,
+      res,
       indoc! { r#"
-      ._9z6RGq_a {
+      /*! a.css license */
+      /*! shared license */
+      /*! b.css license */
+      .b {
         color: green;
       }
 
-      ._6lixEq_a {
+      .c {
+        color: #00f;
+      }
+
+      .a {
         color: red;
       }
     "#}
     );
+
+    let res = bundle_license_comments(fs(), "/a.css", false);
     assert_eq!(
-      flatten_exports(exports),
-      map! {
-        "a" => "_6lixEq_a"
+      res,
+      indoc! { r#"
+      .b {
+        color: green;
+      }
+
+      .c {
+        color: #00f;
+      }
+
+      .a {
+        color: red;
       }
+    "#}
     );
+  }
 
-    let (code, exports) = bundle_css_module(
+  #[test]
+  fn test_hoist_layer_statements() {
+    fn bundle_hoist<P: SourceProvider>(fs: P, entry: &str) -> String {
+      let mut bundler = Bundler::new(&fs, None, ParserOptions::default());
+      bundler.hoist_layer_statements(true);
+      let stylesheet = bundler.bundle(Path::new(entry)).unwrap();
+      stylesheet.to_css(PrinterOptions::default()).unwrap().code
+    }
+
+    // Nested layers declared by imports are combined with the parent statement.
+    let res = bundle_hoist(
       TestProvider {
         map: fs! {
           "/a.css": r#"
-          .a { composes: x from './b.css'; color: red; }
-          .b { color: yellow }
+          @layer bar, foo;
+          @import "b.css" layer(foo);
         "#,
           "/b.css": r#"
-          .x { composes: y; background: green }
-          .y { font: Helvetica }
+          @layer qux, baz;
+          .b { color: red }
         "#
         },
       },
       "/a.css",
     );
     assert_eq!(
-      code,
+      res,
       indoc! { r#"
-      ._8Cs9ZG_x {
-        background: green;
+      @layer bar, foo, foo.qux, foo.baz;
+
+      @layer foo {
+        .b {
+          color: red;
+        }
       }
+    "#}
+    );
 
-      ._8Cs9ZG_y {
-        font: Helvetica;
+    // Layer statements are combined in first-seen order, and blocks of the same layer are merged.
+    let res = bundle_hoist(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "b.css";
+          @import "c.css";
+
+          @layer utilities {
+            .a { color: red }
+          }
+        "#,
+          "/b.css": r#"
+          @layer reset, base;
+
+          @layer base {
+            .b { color: green }
+          }
+        "#,
+          "/c.css": r#"
+          @layer base, components, utilities;
+
+          @layer components {
+            .c { color: blue }
+          }
+
+          @layer base {
+            .c { color: yellow }
+          }
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      res,
+      indoc! { r#"
+      @layer reset, base, components, utilities;
+
+      @layer base {
+        .b {
+          color: green;
+        }
+
+        .c {
+          color: #ff0;
+        }
       }
 
-      ._6lixEq_a {
-        color: red;
+      @layer components {
+        .c {
+          color: #00f;
+        }
       }
 
-      ._6lixEq_b {
-        color: #ff0;
+      @layer utilities {
+        .a {
+          color: red;
+        }
       }
     "#}
     );
+
+    // Statements after an anonymous layer are not hoisted.
+    let res = bundle_hoist(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "b.css";
+          @layer bar, foo;
+        "#,
+          "/b.css": r#"
+          @layer foo;
+          @layer {
+            .b { color: green }
+          }
+        "#
+        },
+      },
+      "/a.css",
+    );
     assert_eq!(
-      flatten_exports(exports),
-      map! {
-        "a" => "_6lixEq_a _8Cs9ZG_x _8Cs9ZG_y",
-        "b" => "_6lixEq_b"
+      res,
+      indoc! { r#"
+      @layer foo;
+
+      @layer {
+        .b {
+          color: green;
+        }
       }
+
+      @layer bar, foo;
+    "#}
     );
+  }
 
-    let (code, exports) = bundle_css_module(
+  #[test]
+  fn test_hoist_root_variables() {
+    fn bundle_hoist<P: SourceProvider>(fs: P, entry: &str) -> Result<String, String> {
+      let mut bundler = Bundler::new(&fs, None, ParserOptions::default());
+      bundler.hoist_root_variables(true);
+      let stylesheet = bundler.bundle(Path::new(entry)).map_err(|e| e.to_string())?;
+      Ok(stylesheet.to_css(PrinterOptions::default()).unwrap().code)
+    }
+
+    let res = bundle_hoist(
       TestProvider {
         map: fs! {
           "/a.css": r#"
-          .a { composes: x from './b.css'; background: red; }
+          @layer base, components;
+          @import "components.css";
+          @import "theme.css";
+          :root { --spacing: 4px }
+          .a { color: var(--primary) }
         "#,
-          "/b.css": r#"
-          .a { background: red }
+          "/components.css": r#"
+          .button { color: var(--primary) }
+        "#,
+          "/theme.css": r#"
+          :root { --primary: red; --secondary: green }
+          .theme { color: var(--secondary) }
         "#
         },
       },
       "/a.css",
-    );
+    )
+    .unwrap();
     assert_eq!(
-      code,
+      res,
       indoc! { r#"
-      ._8Cs9ZG_a {
-        background: red;
+      @layer base, components;
+
+      :root {
+        --primary: red;
+        --secondary: green;
       }
 
-      ._6lixEq_a {
-        background: red;
+      .button {
+        color: var(--primary);
+      }
+
+      .theme {
+        color: var(--secondary);
+      }
+
+      :root {
+        --spacing: 4px;
+      }
+
+      .a {
+        color: var(--primary);
       }
     "#}
     );
+
+    // Rules with other declarations are not hoisted.
+    let res = bundle_hoist(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "b.css";
+          @import "c.css";
+        "#,
+          "/b.css": r#"
+          .b { color: var(--primary) }
+        "#,
+          "/c.css": r#"
+          :root { --primary: red; color: black }
+        "#
+        },
+      },
+      "/a.css",
+    )
+    .unwrap();
     assert_eq!(
-      flatten_exports(exports),
-      map! {
-        "a" => "_6lixEq_a"
+      res,
+      indoc! { r#"
+      .b {
+        color: var(--primary);
+      }
+
+      :root {
+        --primary: red;
+        color: #000;
       }
+    "#}
     );
 
-    let (code, exports) = bundle_css_module(
+    // Identical definitions do not conflict.
+    let res = bundle_hoist(
       TestProvider {
         map: fs! {
           "/a.css": r#"
-          .a {
-            background: var(--bg from "./b.css", var(--fallback from "./b.css"));
-            color: rgb(255 255 255 / var(--opacity from "./b.css"));
-          }
+          @import "b.css";
+          @import "c.css";
         "#,
           "/b.css": r#"
-          .b {
-            --bg: red;
-            --fallback: yellow;
-            --opacity: 0.5;
-          }
+          .b { --primary: red }
+        "#,
+          "/c.css": r#"
+          :root { --primary: red }
         "#
         },
       },
       "/a.css",
-    );
+    )
+    .unwrap();
     assert_eq!(
-      code,
+      res,
       indoc! { r#"
-      ._8Cs9ZG_b {
-        --_8Cs9ZG_bg: red;
-        --_8Cs9ZG_fallback: yellow;
-        --_8Cs9ZG_opacity: .5;
+      :root {
+        --primary: red;
       }
 
-      ._6lixEq_a {
-        background: var(--_8Cs9ZG_bg, var(--_8Cs9ZG_fallback));
-        color: rgb(255 255 255 / var(--_8Cs9ZG_opacity));
+      .b {
+        --primary: red;
       }
     "#}
     );
+
+    let res = bundle_hoist(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "b.css";
+          @import "c.css";
+        "#,
+          "/b.css": r#"
+          @media (prefers-color-scheme: dark) {
+            .b { --primary: blue }
+          }
+        "#,
+          "/c.css": r#"
+          :root { --primary: red }
+        "#
+        },
+      },
+      "/a.css",
+    );
     assert_eq!(
-      flatten_exports(exports),
-      map! {
-        "a" => "_6lixEq_a"
-      }
+      res.unwrap_err(),
+      "Cannot hoist :root variables because --primary is defined differently by an earlier rule at /c.css:1:11"
+    );
+  }
+
+  #[test]
+  fn test_split_by_layer() {
+    let fs = TestProvider {
+      map: fs! {
+        "/a.css": r#"
+          @import "reset.css" layer(reset);
+          @import "components.css";
+          .a { color: red }
+          @layer components { .c { color: green } }
+        "#,
+        "/reset.css": r#"
+          * { margin: 0 }
+        "#,
+        "/components.css": r#"
+          @layer components { .b { color: blue } }
+          .d { color: yellow }
+          @media print { @layer utilities { .e { color: black } } }
+        "#
+      },
+    };
+    let mut bundler = Bundler::new(&fs, None, ParserOptions::default());
+    let mut stylesheet = bundler.bundle(Path::new("/a.css")).unwrap();
+    stylesheet.minify(MinifyOptions::default()).unwrap();
+
+    let options = || PrinterOptions {
+      minify: true,
+      ..PrinterOptions::default()
+    };
+    let split = |stylesheet: &StyleSheet| -> Vec<(Option<String>, String)> {
+      stylesheet
+        .split_by_layer()
+        .iter()
+        .map(|(name, chunk)| {
+          (
+            name.as_ref().map(|name| name.to_css_string(PrinterOptions::default()).unwrap()),
+            chunk.to_css(options()).unwrap().code,
+          )
+        })
+        .collect()
+    };
+
+    // The layer within @media is only declared if the media query matches, so it is kept in place.
+    let code = stylesheet.to_css(options()).unwrap().code;
+    assert_eq!(
+      split(&stylesheet),
+      vec![
+        (None, "@layer reset,components;".into()),
+        (Some("reset".into()), "@layer reset{*{margin:0}}".into()),
+        (
+          Some("components".into()),
+          "@layer components{.b{color:#00f}.c{color:green}}".into()
+        ),
+        (
+          None,
+          ".d{color:#ff0}@media print{@layer utilities{.e{color:#000}}}.a{color:red}".into()
+        ),
+      ]
+    );
+    assert_eq!(stylesheet.to_css(options()).unwrap().code, code);
+
+    // Anonymous layers keep their position in the layer order.
+    let stylesheet = StyleSheet::parse(
+      "@layer a { .a { color: red } } @layer { .b { color: red } } @layer c { .c { color: red } } @layer a { .d { color: red } }",
+      ParserOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+      split(&stylesheet),
+      vec![
+        (None, "@layer a;".into()),
+        (Some("a".into()), "@layer a{.a{color:red}.d{color:red}}".into()),
+        (None, "@layer{.b{color:red}}@layer c{.c{color:red}}".into()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_css_module() {
+    macro_rules! map {
+      { $($key:expr => $val:expr),* } => {
+        HashMap::from([
+          $(($key.to_owned(), $val.to_owned()),)*
+        ])
+      };
+    }
+
+    let (code, exports) = bundle_css_module(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          @import "b.css";
+          .a { color: red }
+        "#,
+          "/b.css": r#"
+          .a { color: green }
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      code,
+      indoc! { r#"
+      ._9z6RGq_a {
+        color: green;
+      }
+
+      ._6lixEq_a {
+        color: red;
+      }
+    "#}
+    );
+    assert_eq!(
+      flatten_exports(exports),
+      map! {
+        "a" => "_6lixEq_a"
+      }
+    );
+
+    let (code, exports) = bundle_css_module(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          .a { composes: x from './b.css'; color: red; }
+          .b { color: yellow }
+        "#,
+          "/b.css": r#"
+          .x { composes: y; background: green }
+          .y { font: Helvetica }
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      code,
+      indoc! { r#"
+      ._8Cs9ZG_x {
+        background: green;
+      }
+
+      ._8Cs9ZG_y {
+        font: Helvetica;
+      }
+
+      ._6lixEq_a {
+        color: red;
+      }
+
+      ._6lixEq_b {
+        color: #ff0;
+      }
+    "#}
+    );
+    assert_eq!(
+      flatten_exports(exports),
+      map! {
+        "a" => "_6lixEq_a _8Cs9ZG_x _8Cs9ZG_y",
+        "b" => "_6lixEq_b"
+      }
+    );
+
+    let (code, exports) = bundle_css_module(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          .a { composes: x from './b.css'; background: red; }
+        "#,
+          "/b.css": r#"
+          .a { background: red }
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      code,
+      indoc! { r#"
+      ._8Cs9ZG_a {
+        background: red;
+      }
+
+      ._6lixEq_a {
+        background: red;
+      }
+    "#}
+    );
+    assert_eq!(
+      flatten_exports(exports),
+      map! {
+        "a" => "_6lixEq_a"
+      }
+    );
+
+    let (code, exports) = bundle_css_module(
+      TestProvider {
+        map: fs! {
+          "/a.css": r#"
+          .a {
+            background: var(--bg from "./b.css", var(--fallback from "./b.css"));
+            color: rgb(255 255 255 / var(--opacity from "./b.css"));
+          }
+        "#,
+          "/b.css": r#"
+          .b {
+            --bg: red;
+            --fallback: yellow;
+            --opacity: 0.5;
+          }
+        "#
+        },
+      },
+      "/a.css",
+    );
+    assert_eq!(
+      code,
+      indoc! { r#"
+      ._8Cs9ZG_b {
+        --_8Cs9ZG_bg: red;
+        --_8Cs9ZG_fallback: yellow;
+        --_8Cs9ZG_opacity: .5;
+      }
+
+      ._6lixEq_a {
+        background: var(--_8Cs9ZG_bg, var(--_8Cs9ZG_fallback));
+        color: rgb(255 255 255 / var(--_8Cs9ZG_opacity));
+      }
+    "#}
+    );
+    assert_eq!(
+      flatten_exports(exports),
+      map! {
+        "a" => "_6lixEq_a"
+      }
+    );
+
+    let fs = TestProvider {
+      map: fs! {
+        "/a.css": r#"
+          .a { composes: x from './b.css'; color: red; }
+        "#,
+        "/b.css": r#"
+          .x { background: green }
+        "#
+      },
+    };
+    let mut bundler = Bundler::new(
+      &fs,
+      None,
+      ParserOptions {
+        css_modules: Some(Default::default()),
+        ..ParserOptions::default()
+      },
+    );
+    let stylesheet = bundler.bundle(Path::new("/a.css")).unwrap();
+    let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
+    assert_eq!(
+      res.exports.unwrap()["a"].composes,
+      vec![CssModuleReference::Local {
+        name: "_8Cs9ZG_x".into()
+      }]
+    );
+    assert_eq!(
+      res.references.unwrap(),
+      HashMap::from([(
+        "_8Cs9ZG_x".to_string(),
+        CssModuleReference::Dependency {
+          name: "x".into(),
+          specifier: "/./b.css".into()
+        }
+      )])
     );
   }
 
@@ -1867,4 +3749,562 @@ mod tests {
       r#"{"version":3,"sourceRoot":null,"mappings":"ACAA,uCCGA,2CAAA,8BFDQ","sources":["a.css","sass/_demo.scss","stdin"],"sourcesContent":["\n        @import \"/b.css\";\n        .a { color: red; }\n      ",".imported {\n  content: \"yay, file support!\";\n}","@import \"_variables\";\n@import \"_demo\";\n\n.selector {\n  margin: $size;\n  background-color: $brandColor;\n\n  .nested {\n    margin: $size / 2;\n  }\n}"],"names":[]}"#
     );
   }
+
+  #[test]
+  fn test_source_map_url() {
+    let fs = TestProvider {
+      map: fs! {
+        "/a.css": r#"
+        @import "b.css";
+        .a { color: red; }
+      "#,
+        "/b.css": r#".imported {
+  content: "yay, file support!";
+}
+
+/*# sourceMappingURL=b.css.map */"#,
+        "/b.css.map": r#"{"version":3,"sources":["_demo.scss"],"sourcesContent":[".imported {\n  content: \"yay, file support!\";\n}"],"mappings":"AAAA,SAAS,CAAC;EACR,OAAO,EAAE,oBAAqB;CAC/B","names":[]}"#
+      },
+    };
+
+    let mut sm = parcel_sourcemap::SourceMap::new("/");
+    let mut bundler = Bundler::new(
+      &fs,
+      Some(&mut sm),
+      ParserOptions {
+        comments: Comments::All,
+        ..ParserOptions::default()
+      },
+    );
+    let stylesheet = bundler.bundle(Path::new("/a.css")).unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        source_map: Some(&mut sm),
+        comments: Comments::All,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert!(!res.code.contains("sourceMappingURL"));
+    let map = sm.to_json(None).unwrap();
+    assert!(map.contains(r#""sources":["a.css","_demo.scss"]"#), "{}", map);
+
+    let async_fs = AsyncProvider::new(AsyncTestProvider {
+      map: Arc::new(fs.map.clone()),
+    });
+    let mut async_sm = parcel_sourcemap::SourceMap::new("/");
+    let mut bundler = Bundler::new(
+      &async_fs,
+      Some(&mut async_sm),
+      ParserOptions {
+        comments: Comments::All,
+        ..ParserOptions::default()
+      },
+    );
+    let stylesheet = block_on(bundler.bundle_async(Path::new("/a.css"))).unwrap();
+    let async_res = stylesheet
+      .to_css(PrinterOptions {
+        source_map: Some(&mut async_sm),
+        comments: Comments::All,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(async_res.code, res.code);
+    assert_eq!(async_sm.to_json(None).unwrap(), map);
+  }
+
+  #[test]
+  fn test_single_thread() {
+    // Bundling a deep import tree must not deadlock when only one thread is available.
+    let depth = 50;
+    let mut map = HashMap::new();
+    for i in 0..depth {
+      map.insert(
+        PathBuf::from(format!("/{}.css", i)),
+        format!("@import \"{}.css\";\n.a{} {{ color: red }}", i + 1, i),
+      );
+    }
+    map.insert(
+      PathBuf::from(format!("/{}.css", depth)),
+      format!(".a{} {{ color: red }}", depth),
+    );
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+    let res = pool.install(|| bundle(TestProvider { map }, "/0.css"));
+    let expected: String = (0..=depth)
+      .rev()
+      .map(|i| format!(".a{} {{\n  color: red;\n}}\n", i))
+      .collect::<Vec<_>>()
+      .join("\n");
+    assert_eq!(res, expected);
+  }
+
+  #[test]
+  fn test_import_supports_targets() {
+    fn bundle_targets<P: SourceProvider>(
+      fs: P,
+      entry: &str,
+      targets: Browsers,
+    ) -> (String, Vec<ImportDependency>) {
+      let mut bundler = Bundler::new(&fs, None, ParserOptions::default());
+      bundler.targets(Some(targets));
+      let stylesheet = bundler.bundle(Path::new(entry)).unwrap();
+      let code = stylesheet.to_css(PrinterOptions::default()).unwrap().code;
+      (code, bundler.take_skipped_imports())
+    }
+
+    // The fallback files are not in the map, so reading them would panic.
+    let fs = || TestProvider {
+      map: fs! {
+        "/a.css": r#"
+          @import "fallback.css" supports(not (display: grid));
+          @import "grid.css" supports(display: grid);
+          @import "place.css" supports((display: grid) and (selector(:has(a))));
+          .a { color: red }
+        "#,
+        "/grid.css": ".grid { display: grid }",
+        "/place.css": ".place { place-items: center }"
+      },
+    };
+
+    let chrome = Browsers {
+      chrome: Some(90 << 16),
+      ..Browsers::default()
+    };
+    let (res, skipped) = bundle_targets(fs(), "/a.css", chrome);
+    assert_eq!(
+      res,
+      indoc! { r#"
+      .grid {
+        display: grid;
+      }
+
+      @supports (display: grid) and (selector(:has(a))) {
+        .place {
+          place-items: center;
+        }
+      }
+
+      .a {
+        color: red;
+      }
+    "#}
+    );
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].url, "fallback.css");
+    assert_eq!(skipped[0].supports.as_deref(), Some("not (display: grid)"));
+    assert!(skipped[0].skipped);
+
+    let async_fs = AsyncProvider::new(AsyncTestProvider {
+      map: Arc::new(fs().map),
+    });
+    let mut bundler = Bundler::new(&async_fs, None, ParserOptions::default());
+    bundler.targets(Some(chrome));
+    let stylesheet = block_on(bundler.bundle_async(Path::new("/a.css"))).unwrap();
+    assert_eq!(stylesheet.to_css(PrinterOptions::default()).unwrap().code, res);
+
+    // No version of IE supports grid, so the fallback is inlined without a condition.
+    let fs = TestProvider {
+      map: fs! {
+        "/a.css": r#"
+          @import "fallback.css" supports(not (display: grid));
+          @import "grid.css" supports(display: grid);
+        "#,
+        "/fallback.css": ".fallback { float: left }"
+      },
+    };
+    let ie = Browsers {
+      ie: Some(11 << 16),
+      ..Browsers::default()
+    };
+    let (res, skipped) = bundle_targets(fs, "/a.css", ie);
+    assert_eq!(
+      res,
+      indoc! { r#"
+      .fallback {
+        float: left;
+      }
+    "#}
+    );
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].url, "grid.css");
+
+    // Older versions of Chrome don't support grid, but newer ones do, so the condition is preserved.
+    let fs = TestProvider {
+      map: fs! {
+        "/a.css": r#"
+          @import "fallback.css" supports(not (display: grid));
+        "#,
+        "/fallback.css": ".fallback { float: left }"
+      },
+    };
+    let chrome = Browsers {
+      chrome: Some(50 << 16),
+      ..Browsers::default()
+    };
+    let (res, skipped) = bundle_targets(fs, "/a.css", chrome);
+    assert_eq!(
+      res,
+      indoc! { r#"
+      @supports not (display: grid) {
+        .fallback {
+          float: left;
+        }
+      }
+    "#}
+    );
+    assert!(skipped.is_empty());
+  }
+
+  #[test]
+  fn test_cache() {
+    let fs = ChangingProvider {
+      map: Mutex::new(fs! {
+        "/a.css": r#"
+          @import "b.css";
+          @import "c.css";
+          .a { color: red }
+        "#,
+        "/b.css": r#"
+          .b { color: green }
+        "#,
+        "/c.css": r#"
+          .c { color: blue }
+        "#
+      }),
+      reads: std::sync::atomic::AtomicUsize::new(0),
+    };
+    let cache = BundleCache::new();
+
+    let bundle = |expected_reads: usize, files: [&str; 3]| {
+      fs.reads.store(0, std::sync::atomic::Ordering::SeqCst);
+      let mut bundler = Bundler::with_cache(&fs, None, ParserOptions::default(), &cache);
+      let stylesheet = bundler.bundle(Path::new("/a.css")).unwrap();
+      assert_eq!(fs.reads.load(std::sync::atomic::Ordering::SeqCst), expected_reads);
+
+      // Every rule must point to the file it came from, even if it was cached at a different position.
+      for (rule, file) in stylesheet.rules.0.iter().zip(files) {
+        if let CssRule::Style(style) = rule {
+          assert_eq!(stylesheet.sources[style.loc.source_index as usize], file);
+        }
+      }
+
+      stylesheet.to_css(PrinterOptions::default()).unwrap().code
+    };
+
+    let expected = indoc! { r#"
+      .b {
+        color: green;
+      }
+
+      .c {
+        color: #00f;
+      }
+
+      .a {
+        color: red;
+      }
+    "#};
+
+    let files = ["/b.css", "/c.css", "/a.css"];
+    assert_eq!(bundle(3, files), expected);
+    assert_eq!(bundle(0, files), expected);
+
+    // Unchanged files are read again after being invalidated, but not reparsed.
+    cache.invalidate(Path::new("/c.css"));
+    assert_eq!(bundle(1, files), expected);
+
+    fs.map.lock().unwrap().insert(
+      PathBuf::from("/a.css"),
+      r#"
+        @import "c.css";
+        @import "b.css";
+        .a { color: red }
+      "#
+      .into(),
+    );
+    fs.map
+      .lock()
+      .unwrap()
+      .insert(PathBuf::from("/b.css"), ".b { color: yellow }".into());
+
+    // Changes are not seen until the files are invalidated.
+    assert_eq!(bundle(0, files), expected);
+
+    cache.invalidate(Path::new("/a.css"));
+    cache.invalidate(Path::new("/b.css"));
+    assert_eq!(
+      bundle(2, ["/c.css", "/b.css", "/a.css"]),
+      indoc! { r#"
+        .c {
+          color: #00f;
+        }
+
+        .b {
+          color: #ff0;
+        }
+
+        .a {
+          color: red;
+        }
+      "#}
+    );
+
+    // Changing parser options clears the cache.
+    fs.reads.store(0, std::sync::atomic::Ordering::SeqCst);
+    let mut bundler = Bundler::with_cache(
+      &fs,
+      None,
+      ParserOptions {
+        nesting: true,
+        ..ParserOptions::default()
+      },
+      &cache,
+    );
+    bundler.bundle(Path::new("/a.css")).unwrap();
+    assert_eq!(fs.reads.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+    fs.reads.store(0, std::sync::atomic::Ordering::SeqCst);
+    let mut bundler = Bundler::with_cache(
+      &fs,
+      None,
+      ParserOptions {
+        nesting: true,
+        preserve_calc: true,
+        ..ParserOptions::default()
+      },
+      &cache,
+    );
+    bundler.bundle(Path::new("/a.css")).unwrap();
+    assert_eq!(fs.reads.load(std::sync::atomic::Ordering::SeqCst), 3);
+  }
+
+  #[test]
+  fn test_cache_warnings() {
+    let fs = ChangingProvider {
+      map: Mutex::new(fs! {
+        "/a.css": r#"
+          @import "b.css";
+          .a { color: red }
+        "#,
+        "/b.css": r#"
+          @font-face { font-family: serif; src: url(b.woff) }
+        "#
+      }),
+      reads: std::sync::atomic::AtomicUsize::new(0),
+    };
+    let cache = BundleCache::new();
+
+    // Warnings from cached files are reported again each time they are bundled.
+    for _ in 0..2 {
+      let warnings = Arc::new(RwLock::new(Vec::new()));
+      let mut bundler = Bundler::with_cache(
+        &fs,
+        None,
+        ParserOptions {
+          warnings: Some(warnings.clone()),
+          ..ParserOptions::default()
+        },
+        &cache,
+      );
+      bundler.bundle(Path::new("/a.css")).unwrap();
+      let warnings = warnings.read().unwrap();
+      assert_eq!(warnings.len(), 1);
+      assert_eq!(warnings[0].loc.as_ref().unwrap().filename, "/b.css");
+    }
+    assert_eq!(fs.reads.load(std::sync::atomic::Ordering::SeqCst), 2);
+    assert_eq!(cache.sources().len(), 2);
+  }
+
+  #[test]
+  fn test_bundle_async() {
+    let map = fs! {
+      "/a.css": r#"
+        @import "b.css";
+        @import "c.css";
+        .a { color: red }
+      "#,
+      "/b.css": r#"
+        @import "c.css";
+        .b { color: green }
+      "#,
+      "/c.css": r#"
+        .c { composes: d from "d.css"; --x: var(--y from "e.css") }
+      "#,
+      "/d.css": ".d { color: yellow }",
+      "/e.css": ".e { --y: 2px }"
+    };
+
+    let async_fs = AsyncProvider::new(AsyncTestProvider {
+      map: Arc::new(map.clone()),
+    });
+    let sync_fs = TestProvider { map };
+    let options = || ParserOptions {
+      css_modules: Some(css_modules::Config {
+        dashed_idents: true,
+        ..Default::default()
+      }),
+      ..ParserOptions::default()
+    };
+
+    let mut bundler = Bundler::new(&async_fs, None, options());
+    let stylesheet = block_on(bundler.bundle_async(Path::new("/a.css"))).unwrap();
+    let res = stylesheet.to_css(PrinterOptions::default()).unwrap().code;
+
+    let mut bundler = Bundler::new(&sync_fs, None, options());
+    let stylesheet = bundler.bundle(Path::new("/a.css")).unwrap();
+    assert_eq!(res, stylesheet.to_css(PrinterOptions::default()).unwrap().code);
+
+    let async_fs = AsyncProvider::new(AsyncTestProvider {
+      map: Arc::new(fs! {
+        "/a.css": r#"
+          @import "b.css";
+          .a { color: red }
+        "#,
+        "/b.css": r#"
+          @import "missing.css";
+          .b { color: green }
+        "#
+      }),
+    });
+    let mut bundler = Bundler::new(&async_fs, None, ParserOptions::default());
+    let error = block_on(bundler.bundle_async(Path::new("/a.css"))).unwrap_err();
+    assert!(matches!(
+      error.kind,
+      BundleErrorKind::ResolverError(AsyncProviderError::Provider(_))
+    ));
+    assert_eq!(error.loc.unwrap().filename, "/b.css");
+
+    // Bundling synchronously does not load any files.
+    let mut bundler = Bundler::new(&async_fs, None, ParserOptions::default());
+    let error = bundler.bundle(Path::new("/c.css")).unwrap_err();
+    assert!(matches!(
+      error.kind,
+      BundleErrorKind::ResolverError(AsyncProviderError::NotLoaded(_))
+    ));
+  }
+
+  #[test]
+  fn test_memory_provider() {
+    let fs = MemoryProvider::new();
+    fs.add(
+      "/styles/main.css",
+      r#"
+        @import "./nested/a.css";
+        @import "../theme.css";
+        .main { color: red }
+      "#,
+    );
+    fs.add(
+      "/styles/nested/a.css",
+      r#"@import "../../theme.css"; .a { color: green }"#,
+    );
+    fs.add("/theme.css", ".theme { color: blue }");
+
+    assert_eq!(
+      fs.resolve("../theme.css", Path::new("/styles/main.css")).unwrap(),
+      PathBuf::from("/theme.css")
+    );
+    assert_eq!(
+      fs.resolve("../../../theme.css", Path::new("/styles/nested/a.css")).unwrap(),
+      PathBuf::from("/theme.css")
+    );
+    assert_eq!(
+      fs.resolve("b.css", Path::new("styles/a.css")).unwrap(),
+      PathBuf::from("styles/b.css")
+    );
+    assert_eq!(
+      fs.resolve("../b.css", Path::new("a.css")).unwrap(),
+      PathBuf::from("../b.css")
+    );
+
+    let res = bundle(fs, "/styles/main.css");
+    assert_eq!(
+      res,
+      indoc! { r#"
+        .a {
+          color: green;
+        }
+
+        .theme {
+          color: #00f;
+        }
+
+        .main {
+          color: red;
+        }
+      "#}
+    );
+
+    // Contents stay borrowed after being replaced.
+    let fs = MemoryProvider::new();
+    fs.add("/a.css", ".a { color: red }");
+    let source = fs.read(Path::new("/a.css")).unwrap();
+    fs.add("/./a.css", ".a { color: green }");
+    assert_eq!(source, ".a { color: red }");
+    assert_eq!(fs.read(Path::new("/a.css")).unwrap(), ".a { color: green }");
+
+    let error = fs.read(Path::new("/b.css")).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+  }
+
+  #[test]
+  fn test_file_provider_encodings() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testdata/encoding");
+    let res = bundle(FileProvider::new(), dir.join("index.css").to_str().unwrap());
+    assert_eq!(
+      res,
+      indoc! { r#"
+        .utf16le:after {
+          content: "é😀";
+        }
+
+        .utf16be:after {
+          content: "é😀";
+        }
+
+        .utf32le:after {
+          content: "é😀";
+        }
+
+        .utf32be:after {
+          content: "é😀";
+        }
+
+        .index:before {
+          content: "→";
+        }
+      "#}
+    );
+
+    let mut fs = FileProvider::new();
+    fs.transcode(false);
+    let file = dir.join("utf16le.css");
+    let error = SourceProvider::read(&fs, &file).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+      error.to_string(),
+      format!("{} is not UTF-8 encoded (detected UTF-16LE)", file.display())
+    );
+
+    let mut bundler = Bundler::new(&fs, None, ParserOptions::default());
+    let error = bundler.bundle(&dir.join("index.css")).unwrap_err();
+    match error.kind {
+      BundleErrorKind::ResolverError(e) => assert_eq!(
+        e.to_string(),
+        format!("{} is not UTF-8 encoded (detected UTF-16LE)", file.display())
+      ),
+      _ => unreachable!(),
+    }
+
+    let file = Path::new("style.css");
+    assert_eq!(decode_source(vec![0xef, 0xbb, 0xbf, b'a'], file, true).unwrap(), "a");
+    assert_eq!(
+      decode_source(vec![0xfe, 0xff, 0xd8, 0x00], file, true).unwrap_err().to_string(),
+      "style.css is not UTF-8 encoded (detected UTF-16BE, but the file is not valid UTF-16BE)"
+    );
+    assert_eq!(
+      decode_source(vec![b'a', 0xff], file, true).unwrap_err().to_string(),
+      "style.css is not UTF-8 encoded (invalid utf-8 sequence of 1 bytes from index 1)"
+    );
+  }
 }
@@ -17,6 +17,7 @@ pub enum Feature {
   CssFocusVisible,
   CssFocusWithin,
   CssGencontent,
+  CssGrid,
   CssHas,
   CssInOutOfRange,
   CssIndeterminatePseudo,
@@ -33,6 +34,7 @@ pub enum Feature {
   CssSel2,
   CssSel3,
   CssSelection,
+  CssVariables,
   Cue,
   CueFunction,
   CustomMediaQueries,
@@ -62,6 +64,7 @@ pub enum Feature {
   PlaceContent,
   PlaceItems,
   PlaceSelf,
+  ScopeRule,
   Shadowdomv1,
   SpaceSeparatedColorFunction,
   TextDecorationThicknessPercent,
@@ -1208,9 +1211,11 @@ impl Feature {
           return false;
         }
       }
-      Feature::CssNesting | Feature::CustomMediaQueries | Feature::MediaIntervalSyntax | Feature::OklabColors => {
-        return false
-      }
+      Feature::CssNesting
+      | Feature::CustomMediaQueries
+      | Feature::MediaIntervalSyntax
+      | Feature::OklabColors
+      | Feature::ScopeRule => return false,
       Feature::CssNotSelList => {
         if let Some(version) = browsers.edge {
           if version < 5767168 {
@@ -1327,6 +1332,96 @@ impl Feature {
           return false;
         }
       }
+      Feature::CssGrid => {
+        if let Some(version) = browsers.edge {
+          if version < 1048576 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.firefox {
+          if version < 3407872 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.chrome {
+          if version < 3735552 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.safari {
+          if version < 655616 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.opera {
+          if version < 2883584 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.ios_saf {
+          if version < 656128 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.android {
+          if version < 6750208 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.samsung {
+          if version < 393728 {
+            return false;
+          }
+        }
+        if browsers.ie.is_some() {
+          return false;
+        }
+      }
+      Feature::CssVariables => {
+        if let Some(version) = browsers.edge {
+          if version < 1048576 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.firefox {
+          if version < 2031616 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.chrome {
+          if version < 3211264 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.safari {
+          if version < 590080 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.opera {
+          if version < 2359296 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.ios_saf {
+          if version < 590592 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.android {
+          if version < 6750208 {
+            return false;
+          }
+        }
+        if let Some(version) = browsers.samsung {
+          if version < 327680 {
+            return false;
+          }
+        }
+        if browsers.ie.is_some() {
+          return false;
+        }
+      }
       Feature::DoublePositionGradients => {
         if let Some(version) = browsers.chrome {
           if version < 4653056 {
@@ -1,5 +1,3 @@
-use std::collections::HashSet;
-
 use crate::compat::Feature;
 use crate::declaration::DeclarationBlock;
 use crate::properties::custom::UnparsedProperty;
@@ -7,9 +5,11 @@ use crate::properties::Property;
 use crate::rules::supports::{SupportsCondition, SupportsRule};
 use crate::rules::{style::StyleRule, CssRule, CssRuleList};
 use crate::selector::{Direction, PseudoClass};
-use crate::targets::Browsers;
+use crate::stylesheet::UnusedSymbols;
+use crate::targets::{should_compile, Browsers, Features};
 use crate::vendor_prefix::VendorPrefix;
 use parcel_selectors::parser::Component;
+use std::collections::HashSet;
 
 #[derive(Debug)]
 pub(crate) struct SupportsEntry<'i> {
@@ -34,11 +34,18 @@ pub(crate) struct PropertyHandlerContext<'i, 'o> {
   ltr: Vec<Property<'i>>,
   rtl: Vec<Property<'i>>,
   pub context: DeclarationContext,
-  pub unused_symbols: &'o HashSet<String>,
+  pub unused_symbols: &'o UnusedSymbols,
+  pub removed_symbols: HashSet<String>,
+  pub merge_longhands: bool,
+  pub deduplicate_declarations: bool,
+  pub sort_declarations: bool,
+  pub include: Features,
+  pub exclude: Features,
+  pub features_used: Features,
 }
 
 impl<'i, 'o> PropertyHandlerContext<'i, 'o> {
-  pub fn new(targets: Option<Browsers>, unused_symbols: &'o HashSet<String>) -> Self {
+  pub fn new(targets: Option<Browsers>, unused_symbols: &'o UnusedSymbols) -> Self {
     PropertyHandlerContext {
       targets,
       is_important: false,
@@ -47,6 +54,13 @@ impl<'i, 'o> PropertyHandlerContext<'i, 'o> {
       rtl: Vec::new(),
       context: DeclarationContext::None,
       unused_symbols,
+      removed_symbols: HashSet::new(),
+      merge_longhands: true,
+      deduplicate_declarations: false,
+      sort_declarations: false,
+      include: Features::empty(),
+      exclude: Features::empty(),
+      features_used: Features::empty(),
     }
   }
 
@@ -57,6 +71,28 @@ impl<'i, 'o> PropertyHandlerContext<'i, 'o> {
       return true;
     }
 
+    if matches!(
+      feature,
+      Feature::LogicalBorders
+        | Feature::LogicalBorderShorthand
+        | Feature::LogicalBorderRadius
+        | Feature::LogicalInset
+        | Feature::LogicalMargin
+        | Feature::LogicalMarginShorthand
+        | Feature::LogicalPadding
+        | Feature::LogicalPaddingShorthand
+        | Feature::LogicalSize
+        | Feature::LogicalTextAlign
+    ) {
+      return !should_compile(
+        self.targets,
+        self.include,
+        self.exclude,
+        Features::LOGICAL_PROPERTIES,
+        feature,
+      );
+    }
+
     if let Some(targets) = self.targets {
       feature.is_compatible(targets)
     } else {
@@ -64,6 +100,16 @@ impl<'i, 'o> PropertyHandlerContext<'i, 'o> {
     }
   }
 
+  /// Records that a rule or declaration was removed because `name` is an unused symbol.
+  pub fn add_removed_symbol(&mut self, name: &str) {
+    let unused_symbols = self.unused_symbols;
+    if let Some(entry) = unused_symbols.matching_entry(name) {
+      if !self.removed_symbols.contains(entry) {
+        self.removed_symbols.insert(entry.to_owned());
+      }
+    }
+  }
+
   pub fn add_logical_rule(&mut self, ltr: Property<'i>, rtl: Property<'i>) {
     self.ltr.push(ltr);
     self.rtl.push(rtl);
@@ -85,7 +131,7 @@ impl<'i, 'o> PropertyHandlerContext<'i, 'o> {
           vendor_prefix: VendorPrefix::None,
           declarations: DeclarationBlock {
             declarations: std::mem::take(&mut self.$decls),
-            important_declarations: vec![],
+            ..DeclarationBlock::default()
           },
           rules: CssRuleList(vec![]),
           loc: style_rule.loc.clone(),
@@ -168,6 +214,7 @@ impl<'i, 'o> PropertyHandlerContext<'i, 'o> {
           declarations: DeclarationBlock {
             declarations: entry.declarations,
             important_declarations: entry.important_declarations,
+            ..DeclarationBlock::default()
           },
           rules: CssRuleList(vec![]),
           loc: style_rule.loc.clone(),
@@ -7,20 +7,47 @@
 //! CSS modules can be enabled using the `css_modules` option when parsing a style sheet. When the
 //! style sheet is printed, hashes will be added to any declared names, and references to those names
 //! will be updated accordingly. A map of the original names to compiled (hashed) names will be returned.
+//!
+//! # Hashes
+//!
+//! The `[hash]` placeholder is derived from the filename of the style sheet only, exactly as it was passed
+//! to the parser (e.g. the `filename` parser option, or the path given to the bundler). The contents of the
+//! file are not included. Hashed dashed identifiers referenced from another file use the string
+//! `<file hash>_<name>_<specifier>` as input instead.
+//!
+//! The algorithm is selected with the `hash_version` option:
+//!
+//! * [HashVersion::V1] (the default) uses the Rust standard library's `DefaultHasher`, truncated to 32 bits.
+//!   Its output is not guaranteed to be stable between Rust versions, and paths are hashed as given, so
+//!   Windows paths produce different hashes than their Unix equivalents.
+//! * [HashVersion::V2] first replaces every `\` in the path with `/`, and then computes a 32-bit
+//!   [FNV-1a](http://www.isthe.com/chongo/tech/comp/fnv/) hash of its UTF-8 bytes. This is reproducible
+//!   across platforms and releases.
+//!
+//! In both cases, the 32-bit hash is encoded as little endian bytes using the URL-safe alphabet
+//! `a-zA-Z0-9_-` without padding. When the hash begins the name and starts with a digit, it is prefixed
+//! with `_` so that the result is a valid identifier. Any future changes to the algorithm will be introduced
+//! as a new hash version.
 
-use crate::error::PrinterErrorKind;
+use crate::error::{Error, ErrorLocation, ParserError, PrinterErrorKind};
+use crate::parser::ParserOptions;
 use crate::properties::css_modules::{Composes, Specifier};
-use crate::selector::Selectors;
+use crate::properties::Property;
+use crate::rules::{CssRule, CssRuleList};
+use crate::selector::{is_unused, Selectors};
+use crate::stylesheet::{fnv1a, UnusedSymbols};
 use data_encoding::{Encoding, Specification};
 use lazy_static::lazy_static;
+use parcel_selectors::parser::{Component, Selector};
 use parcel_selectors::SelectorList;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use smallvec::{smallvec, SmallVec};
 use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 use std::hash::{Hash, Hasher};
 use std::path::Path;
+use std::sync::Arc;
 
 /// Configuration for CSS modules.
 #[derive(Default, Clone, Debug)]
@@ -30,6 +57,111 @@ pub struct Config<'i> {
   pub pattern: Pattern<'i>,
   /// Whether to rename dashed identifiers, e.g. custom properties.
   pub dashed_idents: bool,
+  /// A custom function to generate renamed identifiers. Overrides `pattern` when present.
+  pub generate_name: Option<NameGenerator>,
+  /// The algorithm used to generate `[hash]` values.
+  pub hash_version: HashVersion,
+  /// Whether to include the maximum specificity of the selectors each exported class appears in.
+  pub specificity: bool,
+  /// Whether to include the printed selectors that each exported class appears in.
+  pub export_selectors: bool,
+  /// Whether to rename class names and ids within deep selectors, i.e. within `:deep()` and
+  /// `::v-deep()`, or after `::v-deep` and `::ng-deep`. By default, they are left as is, like `:global()`.
+  pub scope_deep_selectors: bool,
+}
+
+/// The algorithm used to generate `[hash]` values in CSS modules.
+///
+/// See the [module documentation](self#hashes) for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashVersion {
+  /// The original hash. Not stable across platforms or Rust versions.
+  V1,
+  /// A stable FNV-1a hash of the filename, with path separators normalized.
+  V2,
+}
+
+impl Default for HashVersion {
+  fn default() -> Self {
+    HashVersion::V1
+  }
+}
+
+impl HashVersion {
+  fn hash(&self, s: &str, at_start: bool) -> String {
+    match self {
+      HashVersion::V1 => hash(s, at_start),
+      HashVersion::V2 => {
+        let mut hash: u32 = 0x811c9dc5;
+        for b in s.bytes() {
+          hash ^= b as u32;
+          hash = hash.wrapping_mul(0x01000193);
+        }
+        encode_hash(hash, at_start)
+      }
+    }
+  }
+
+  fn hash_path(&self, path: &str, at_start: bool) -> String {
+    match self {
+      HashVersion::V1 => hash(path, at_start),
+      HashVersion::V2 => self.hash(&path.replace('\\', "/"), at_start),
+    }
+  }
+}
+
+impl<'i> Config<'i> {
+  /// Write the renamed identifier to a destination, using either the custom
+  /// name generator or the pattern.
+  pub(crate) fn write_name<W, E>(&self, hash: &str, path: &Path, local: &str, mut write: W) -> Result<(), E>
+  where
+    W: FnMut(&str) -> Result<(), E>,
+    E: From<std::fmt::Error>,
+  {
+    if let Some(generate_name) = &self.generate_name {
+      // The generator is passed the filename as a string, so it must be valid UTF-8.
+      let filename = path.to_str().ok_or(std::fmt::Error)?;
+      return write(&generate_name.generate(local, filename, hash));
+    }
+
+    self.pattern.write(hash, path, local, write)
+  }
+
+  #[inline]
+  fn write_name_to_string(
+    &self,
+    mut res: String,
+    hash: &str,
+    path: &Path,
+    local: &str,
+  ) -> Result<String, std::fmt::Error> {
+    self.write_name(hash, path, local, |s| res.write_str(s))?;
+    Ok(res)
+  }
+}
+
+/// A custom function to generate renamed identifiers in CSS modules.
+///
+/// The function is called with the original name, the filename it was declared in,
+/// and a hash of the filename, and returns the new name.
+#[derive(Clone)]
+pub struct NameGenerator(Arc<dyn Fn(&str, &str, &str) -> String + Send + Sync>);
+
+impl NameGenerator {
+  /// Creates a name generator from a function.
+  pub fn new<F: Fn(&str, &str, &str) -> String + Send + Sync + 'static>(f: F) -> Self {
+    NameGenerator(Arc::new(f))
+  }
+
+  fn generate(&self, local: &str, filename: &str, hash: &str) -> String {
+    (self.0)(local, filename, hash)
+  }
+}
+
+impl std::fmt::Debug for NameGenerator {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("NameGenerator")
+  }
 }
 
 /// A CSS modules class name pattern.
@@ -131,18 +263,6 @@ impl<'i> Pattern<'i> {
     }
     Ok(())
   }
-
-  #[inline]
-  fn write_to_string(
-    &self,
-    mut res: String,
-    hash: &str,
-    path: &Path,
-    local: &str,
-  ) -> Result<String, std::fmt::Error> {
-    self.write(hash, path, local, |s| res.write_str(s))?;
-    Ok(res)
-  }
 }
 
 /// A segment in a CSS modules class name pattern.
@@ -163,7 +283,7 @@ pub enum Segment<'i> {
 /// A referenced name within a CSS module, e.g. via the `composes` property.
 ///
 /// See [CssModuleExport](CssModuleExport).
-#[derive(PartialEq, Debug, Clone, Serialize)]
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum CssModuleReference {
   /// A local reference.
@@ -186,23 +306,121 @@ pub enum CssModuleReference {
 }
 
 /// An exported value from a CSS module.
-#[derive(PartialEq, Debug, Clone, Serialize)]
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CssModuleExport {
   /// The local (compiled) name for this export.
   pub name: String,
   /// Other names that are composed by this export.
+  #[serde(default)]
   pub composes: Vec<CssModuleReference>,
   /// Whether the export is referenced in this file.
+  #[serde(default)]
   pub is_referenced: bool,
+  /// The maximum specificity of the selectors this class appears in, as `[a, b, c]`,
+  /// if the `specificity` option is enabled.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub specificity: Option<[u32; 3]>,
+  /// The printed selectors this class appears in, if the `export_selectors` option is enabled.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub selectors: Option<Vec<String>>,
 }
 
 /// A map of exported names to values.
 pub type CssModuleExports = HashMap<String, CssModuleExport>;
 
-/// A map of placeholders to references.
+/// A map of placeholders, or of the compiled names of classes composed from other files
+/// in a bundle, to references.
 pub type CssModuleReferences = HashMap<String, CssModuleReference>;
 
+/// The differences between two sets of CSS module exports, as returned by [diff_exports].
+#[derive(PartialEq, Debug, Clone, Default, Serialize)]
+pub struct ExportsDiff {
+  /// Names that are only exported after the change.
+  pub added: Vec<String>,
+  /// Names that are only exported before the change.
+  pub removed: Vec<String>,
+  /// Names that are exported both before and after the change, but with a different
+  /// compiled name or composed names.
+  pub changed: Vec<String>,
+}
+
+impl ExportsDiff {
+  /// Returns whether the exports are the same.
+  pub fn is_empty(&self) -> bool {
+    self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+  }
+}
+
+/// Compares two sets of CSS module exports, e.g. from successive builds of the same file.
+///
+/// Only the compiled name and composed names of each export are compared, as these are what
+/// importers of the module see. Changes to `is_referenced`, `specificity` and `selectors` only
+/// depend on the rules within the file, and are ignored. Each list of names in the result is sorted.
+pub fn diff_exports(before: &CssModuleExports, after: &CssModuleExports) -> ExportsDiff {
+  let mut diff = ExportsDiff::default();
+  for (name, export) in after {
+    match before.get(name) {
+      None => diff.added.push(name.clone()),
+      Some(prev) if prev.name != export.name || prev.composes != export.composes => {
+        diff.changed.push(name.clone())
+      }
+      Some(_) => {}
+    }
+  }
+
+  for name in before.keys() {
+    if !after.contains_key(name) {
+      diff.removed.push(name.clone());
+    }
+  }
+
+  diff.added.sort();
+  diff.removed.sort();
+  diff.changed.sort();
+  diff
+}
+
+/// Returns a hash of a set of CSS module exports, as a 16 character hex string.
+///
+/// The hash covers the same parts of each export that [diff_exports] compares, so two sets of exports
+/// have the same hash when there are no differences between them. It does not depend on the order of
+/// the map, and is reproducible across platforms and releases.
+pub fn exports_hash(exports: &CssModuleExports) -> String {
+  let mut names: Vec<&String> = exports.keys().collect();
+  names.sort();
+
+  // Fields are separated by NUL bytes, which cannot occur in CSS identifiers or specifiers.
+  let mut canonical = String::new();
+  for name in names {
+    let export = &exports[name];
+    canonical.push_str(name);
+    canonical.push('\0');
+    canonical.push_str(&export.name);
+    for reference in &export.composes {
+      match reference {
+        CssModuleReference::Local { name } => {
+          canonical.push_str("\0l");
+          canonical.push_str(name);
+        }
+        CssModuleReference::Global { name } => {
+          canonical.push_str("\0g");
+          canonical.push_str(name);
+        }
+        CssModuleReference::Dependency { name, specifier } => {
+          canonical.push_str("\0d");
+          canonical.push_str(name);
+          canonical.push('\0');
+          canonical.push_str(specifier);
+        }
+      }
+    }
+    canonical.push('\n');
+  }
+
+  format!("{:016x}", fnv1a(canonical.as_bytes()))
+}
+
 lazy_static! {
   static ref ENCODER: Encoding = {
     let mut spec = Specification::new();
@@ -218,6 +436,7 @@ pub(crate) struct CssModule<'a, 'b, 'c> {
   pub sources: Vec<&'c Path>,
   pub hashes: Vec<String>,
   pub exports_by_source_index: Vec<CssModuleExports>,
+  pub animations_by_source_index: Vec<HashMap<String, String>>,
   pub references: &'a mut HashMap<String, CssModuleReference>,
 }
 
@@ -232,9 +451,14 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
       sources: sources.iter().map(|filename| Path::new(filename)).collect(),
       hashes: sources
         .iter()
-        .map(|source| hash(&source, matches!(config.pattern.segments[0], Segment::Hash)))
+        .map(|source| {
+          config
+            .hash_version
+            .hash_path(&source, matches!(config.pattern.segments[0], Segment::Hash))
+        })
         .collect(),
       exports_by_source_index: sources.iter().map(|_| HashMap::new()).collect(),
+      animations_by_source_index: sources.iter().map(|_| HashMap::new()).collect(),
       references,
     }
   }
@@ -245,8 +469,7 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
       .or_insert_with(|| CssModuleExport {
         name: self
           .config
-          .pattern
-          .write_to_string(
+          .write_name_to_string(
             String::new(),
             &self.hashes[source_index as usize],
             &self.sources[source_index as usize],
@@ -255,17 +478,25 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
           .unwrap(),
         composes: vec![],
         is_referenced: false,
+        specificity: None,
+        selectors: None,
       });
   }
 
+  pub fn add_animation(&mut self, name: &str, source_index: u32) {
+    // Names that must be quoted, e.g. "none", are not scoped, so they have no export.
+    if let Some(export) = self.exports_by_source_index[source_index as usize].get(name) {
+      self.animations_by_source_index[source_index as usize].insert(name.into(), export.name.clone());
+    }
+  }
+
   pub fn add_dashed(&mut self, local: &str, source_index: u32) {
     self.exports_by_source_index[source_index as usize]
       .entry(local.into())
       .or_insert_with(|| CssModuleExport {
         name: self
           .config
-          .pattern
-          .write_to_string(
+          .write_name_to_string(
             "--".into(),
             &self.hashes[source_index as usize],
             &self.sources[source_index as usize],
@@ -274,6 +505,8 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
           .unwrap(),
         composes: vec![],
         is_referenced: false,
+        specificity: None,
+        selectors: None,
       });
   }
 
@@ -286,8 +519,7 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
         entry.insert(CssModuleExport {
           name: self
             .config
-            .pattern
-            .write_to_string(
+            .write_name_to_string(
               String::new(),
               &self.hashes[source_index as usize],
               &self.sources[source_index as usize],
@@ -296,6 +528,8 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
             .unwrap(),
           composes: vec![],
           is_referenced: true,
+          specificity: None,
+          selectors: None,
         });
       }
     }
@@ -315,8 +549,7 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
         return Some(
           self
             .config
-            .pattern
-            .write_to_string(
+            .write_name_to_string(
               String::new(),
               &self.hashes[*source_index as usize],
               &self.sources[*source_index as usize],
@@ -335,8 +568,7 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
             entry.insert(CssModuleExport {
               name: self
                 .config
-                .pattern
-                .write_to_string(
+                .write_name_to_string(
                   "--".into(),
                   &self.hashes[source_index as usize],
                   &self.sources[source_index as usize],
@@ -345,6 +577,8 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
                 .unwrap(),
               composes: vec![],
               is_referenced: true,
+              specificity: None,
+              selectors: None,
             });
           }
         }
@@ -352,7 +586,7 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
       }
     };
 
-    let hash = hash(
+    let hash = self.config.hash_version.hash(
       &format!("{}_{}_{}", self.hashes[source_index as usize], name, key),
       false,
     );
@@ -377,8 +611,7 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
                 None => CssModuleReference::Local {
                   name: self
                     .config
-                    .pattern
-                    .write_to_string(
+                    .write_name_to_string(
                       String::new(),
                       &self.hashes[source_index as usize],
                       &self.sources[source_index as usize],
@@ -390,14 +623,26 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
                   if let Some(entry) =
                     self.exports_by_source_index[*dep_source_index as usize].get(&name.0.as_ref().to_owned())
                   {
-                    let name = entry.name.clone();
+                    let local_name = entry.name.clone();
                     let composes = entry.composes.clone();
                     let export = self.exports_by_source_index[source_index as usize]
                       .get_mut(&id.0.as_ref().to_owned())
                       .unwrap();
 
-                    export.composes.push(CssModuleReference::Local { name });
+                    export.composes.push(CssModuleReference::Local {
+                      name: local_name.clone(),
+                    });
                     export.composes.extend(composes);
+
+                    // The specifier was resolved to a file in the bundle, so record which file
+                    // the composed class comes from by its compiled name.
+                    self.references.insert(
+                      local_name,
+                      CssModuleReference::Dependency {
+                        name: name.0.to_string(),
+                        specifier: self.sources[*dep_source_index as usize].to_string_lossy().into_owned(),
+                      },
+                    );
                   }
                   continue;
                 }
@@ -429,13 +674,75 @@ impl<'a, 'b, 'c> CssModule<'a, 'b, 'c> {
 
     Ok(())
   }
+
+  /// Records the specificity of each selector on the exports of the classes it contains,
+  /// if the `specificity` option is enabled. Must be called after the selectors are printed.
+  pub fn add_specificity(&mut self, selectors: &SelectorList<Selectors>, source_index: u32) {
+    if !self.config.specificity {
+      return;
+    }
+
+    let exports = &mut self.exports_by_source_index[source_index as usize];
+    for selector in &selectors.0 {
+      let specificity = selector.specificity();
+      let specificity = [specificity >> 20, (specificity >> 10) & 0x3ff, specificity & 0x3ff];
+      for_each_class(selector, &mut |name| {
+        if let Some(export) = exports.get_mut(name) {
+          if export.specificity.map_or(true, |s| s < specificity) {
+            export.specificity = Some(specificity);
+          }
+        }
+      });
+    }
+  }
+
+  /// Records the printed form of each selector on the exports of the classes it contains,
+  /// if the `export_selectors` option is enabled. `printed` holds one string per selector in the list.
+  pub fn add_selectors(&mut self, selectors: &SelectorList<Selectors>, printed: Vec<String>, source_index: u32) {
+    if !self.config.export_selectors {
+      return;
+    }
+
+    let exports = &mut self.exports_by_source_index[source_index as usize];
+    for (selector, printed) in selectors.0.iter().zip(printed) {
+      for_each_class(selector, &mut |name| {
+        if let Some(export) = exports.get_mut(name) {
+          let selectors = export.selectors.get_or_insert_with(Vec::new);
+          if !selectors.contains(&printed) {
+            selectors.push(printed.clone());
+          }
+        }
+      });
+    }
+  }
+}
+
+/// Calls `f` with the name of each class in a selector, including within `:not()`, `:is()`, etc.
+fn for_each_class<F: FnMut(&str)>(selector: &Selector<Selectors>, f: &mut F) {
+  for component in selector.iter_raw_match_order() {
+    match component {
+      Component::Class(name) => f(name.0.as_ref()),
+      Component::Negation(selectors)
+      | Component::Is(selectors)
+      | Component::Where(selectors)
+      | Component::Has(selectors)
+      | Component::Any(_, selectors) => {
+        for selector in selectors.iter() {
+          for_each_class(selector, f);
+        }
+      }
+      _ => {}
+    }
+  }
 }
 
 pub(crate) fn hash(s: &str, at_start: bool) -> String {
   let mut hasher = DefaultHasher::new();
   s.hash(&mut hasher);
-  let hash = hasher.finish() as u32;
+  encode_hash(hasher.finish() as u32, at_start)
+}
 
+fn encode_hash(hash: u32, at_start: bool) -> String {
   let hash = ENCODER.encode(&hash.to_le_bytes());
   if at_start && matches!(hash.as_bytes()[0], b'0'..=b'9') {
     format!("_{}", hash)
@@ -443,3 +750,118 @@ pub(crate) fn hash(s: &str, at_start: bool) -> String {
     hash
   }
 }
+
+/// Returns the classes in `unused_symbols` that are composed by a class which is kept,
+/// either directly or through a chain of `composes` declarations.
+pub(crate) fn composed_unused_symbols(rules: &CssRuleList, unused_symbols: &UnusedSymbols) -> HashSet<String> {
+  let mut composes: HashMap<String, Vec<String>> = HashMap::new();
+  collect_composes(rules, &mut composes);
+
+  let mut composed = HashSet::new();
+  let mut stack: Vec<&String> = composes.keys().filter(|name| !unused_symbols.contains(*name)).collect();
+  while let Some(name) = stack.pop() {
+    if let Some(names) = composes.get(name) {
+      for name in names {
+        if unused_symbols.contains(name) && composed.insert(name.clone()) {
+          stack.push(name);
+        }
+      }
+    }
+  }
+
+  composed
+}
+
+fn collect_composes(rules: &CssRuleList, composes: &mut HashMap<String, Vec<String>>) {
+  for rule in &rules.0 {
+    match rule {
+      CssRule::Style(style) => {
+        for (property, _) in style.declarations.iter() {
+          if let Property::Composes(Composes { names, from: None, .. }) = property {
+            for class in composing_classes(&style.selectors) {
+              composes
+                .entry(class)
+                .or_default()
+                .extend(names.iter().map(|name| name.0.to_string()));
+            }
+          }
+        }
+      }
+      CssRule::Media(media) => collect_composes(&media.rules, composes),
+      CssRule::Supports(supports) => collect_composes(&supports.rules, composes),
+      CssRule::Container(container) => collect_composes(&container.rules, composes),
+      CssRule::Scope(scope) => collect_composes(&scope.rules, composes),
+      CssRule::LayerBlock(layer) => collect_composes(&layer.rules, composes),
+      _ => {}
+    }
+  }
+}
+
+/// Removes references to classes in `unused_symbols` from `composes` declarations in rules that are kept,
+/// and emits a warning for each of them. Declarations that no longer reference any classes are removed.
+pub(crate) fn remove_unused_composes<'i>(
+  rules: &mut CssRuleList<'i>,
+  unused_symbols: &UnusedSymbols,
+  options: &ParserOptions<'_, 'i>,
+  sources: &[String],
+) {
+  for rule in &mut rules.0 {
+    match rule {
+      CssRule::Style(style) => {
+        if is_unused(&mut style.selectors.0.iter(), unused_symbols, false) {
+          continue;
+        }
+
+        let filename = &sources[style.loc.source_index as usize];
+        for decls in [
+          &mut style.declarations.declarations,
+          &mut style.declarations.important_declarations,
+        ] {
+          decls.retain_mut(|property| match property {
+            Property::Composes(composes) if composes.from.is_none() => {
+              let loc = composes.loc;
+              composes.names.retain(|name| {
+                if !unused_symbols.contains(name.0.as_ref()) {
+                  return true;
+                }
+
+                options.push_warning(|| Error {
+                  kind: ParserError::UnusedComposes(name.0.clone()),
+                  loc: Some(ErrorLocation {
+                    filename: filename.clone(),
+                    line: loc.line - 1,
+                    column: loc.column,
+                  }),
+                });
+                false
+              });
+              !composes.names.is_empty()
+            }
+            _ => true,
+          });
+        }
+      }
+      CssRule::Media(media) => remove_unused_composes(&mut media.rules, unused_symbols, options, sources),
+      CssRule::Supports(supports) => remove_unused_composes(&mut supports.rules, unused_symbols, options, sources),
+      CssRule::Container(container) => {
+        remove_unused_composes(&mut container.rules, unused_symbols, options, sources)
+      }
+      CssRule::Scope(scope) => remove_unused_composes(&mut scope.rules, unused_symbols, options, sources),
+      CssRule::LayerBlock(layer) => remove_unused_composes(&mut layer.rules, unused_symbols, options, sources),
+      _ => {}
+    }
+  }
+}
+
+// Returns the class names of the simple class selectors in a rule that may use `composes`.
+fn composing_classes(selectors: &SelectorList<Selectors>) -> Vec<String> {
+  selectors
+    .0
+    .iter()
+    .filter(|sel| sel.len() == 1)
+    .filter_map(|sel| match sel.iter_raw_match_order().next() {
+      Some(parcel_selectors::parser::Component::Class(id)) => Some(id.0.to_string()),
+      _ => None,
+    })
+    .collect()
+}
@@ -1,13 +1,18 @@
 //! CSS declarations.
 
 use std::borrow::Cow;
-use std::ops::Range;
+use std::ops::{Range, RangeBounds};
 
 use crate::context::PropertyHandlerContext;
 use crate::error::{ParserError, PrinterError};
-use crate::parser::ParserOptions;
+use crate::logical::PropertyCategory;
+use crate::parser::{
+  custom_at_rule_without_block, parse_custom_at_rule, parse_declaration_comments, parse_rules_in_declarations,
+  skip_invalid_declaration, AtRulePrelude, ParserOptions,
+};
 use crate::printer::Printer;
 use crate::properties::box_shadow::BoxShadowHandler;
+use crate::properties::custom::CustomProperty;
 use crate::properties::masking::MaskHandler;
 use crate::properties::{
   align::AlignHandler,
@@ -31,9 +36,14 @@ use crate::properties::{
   transition::TransitionHandler,
 };
 use crate::properties::{Property, PropertyId};
-use crate::targets::Browsers;
+use crate::rules::comment::CommentRule;
+use crate::rules::custom_at_rule::{CustomAtRule, CustomAtRuleBody};
+use crate::rules::ToCssWithContext;
+use crate::targets::{Browsers, Features};
 use crate::traits::{PropertyHandler, ToCss};
+use crate::values::calc::contains_math_function;
 use crate::values::string::CowArcStr;
+use crate::vendor_prefix::VendorPrefix;
 use cssparser::*;
 
 /// A CSS declaration block.
@@ -41,7 +51,7 @@ use cssparser::*;
 /// Properties are separated into a list of `!important` declararations,
 /// and a list of normal declarations. This reduces memory usage compared
 /// with storing a boolean along with each property.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DeclarationBlock<'i> {
   /// A list of `!important` declarations in the block.
@@ -49,6 +59,16 @@ pub struct DeclarationBlock<'i> {
   pub important_declarations: Vec<Property<'i>>,
   /// A list of normal declarations in the block.
   pub declarations: Vec<Property<'i>>,
+  /// Comments between the declarations, retained according to the `comments` option of
+  /// [ParserOptions](crate::stylesheet::ParserOptions), along with the number of declarations
+  /// that precede each one. Declarations are not combined or reordered across them.
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+  pub comments: Vec<(usize, CommentRule<'i>)>,
+  /// At-rules defined by the `custom_at_rules` option of [ParserOptions](crate::stylesheet::ParserOptions)
+  /// between the declarations, e.g. `@apply`, along with the number of normal declarations that precede
+  /// each one. Declarations are not combined or reordered across them.
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Vec::is_empty"))]
+  pub at_rules: Vec<(usize, CustomAtRule<'i>)>,
 }
 
 impl<'i> DeclarationBlock<'i> {
@@ -59,17 +79,30 @@ impl<'i> DeclarationBlock<'i> {
   ) -> Result<Self, ParseError<'i, ParserError<'i>>> {
     let mut important_declarations = DeclarationList::new();
     let mut declarations = DeclarationList::new();
+    let mut comments = Vec::new();
+    let mut at_rules = Vec::new();
     let mut parser = DeclarationListParser::new(
       input,
       PropertyDeclarationParser {
         important_declarations: &mut important_declarations,
         declarations: &mut declarations,
+        at_rules: &mut at_rules,
         options,
       },
     );
-    while let Some(res) = parser.next() {
+    loop {
+      let index = parser.parser.declarations.len() + parser.parser.important_declarations.len();
+      parse_declaration_comments(parser.input, options, index, &mut comments);
+      if options.error_recovery && !options.strict && skip_invalid_declaration(parser.input, options, false) {
+        continue;
+      }
+      let res = match parser.next() {
+        Some(res) => res,
+        None => break,
+      };
+
       if let Err((err, _)) = res {
-        if options.error_recovery {
+        if options.error_recovery && !options.strict {
           options.warn(err);
           continue;
         }
@@ -80,6 +113,8 @@ impl<'i> DeclarationBlock<'i> {
     Ok(DeclarationBlock {
       important_declarations,
       declarations,
+      comments,
+      at_rules,
     })
   }
 
@@ -106,9 +141,19 @@ impl<'i> ToCss for DeclarationBlock<'i> {
 
     macro_rules! write {
       ($decls: expr, $important: literal) => {
-        for decl in &$decls {
+        for (index, decl) in $decls.iter().enumerate() {
+          for comment in self.comments_in(i..=i, dest) {
+            comment.to_css(dest)?;
+            dest.whitespace()?;
+          }
+          if !$important {
+            for rule in self.at_rules_in(index..=index) {
+              rule.to_css_with_context(dest, None)?;
+              dest.whitespace()?;
+            }
+          }
           decl.to_css(dest, $important)?;
-          if i != len - 1 {
+          if i != len - 1 || self.has_trailing_at_rules() {
             dest.write_char(';')?;
             dest.whitespace()?;
           }
@@ -118,7 +163,19 @@ impl<'i> ToCss for DeclarationBlock<'i> {
     }
 
     write!(self.declarations, false);
+    for rule in self.at_rules_in(self.declarations.len()..) {
+      rule.to_css_with_context(dest, None)?;
+      dest.whitespace()?;
+    }
     write!(self.important_declarations, true);
+
+    for comment in self.comments_in(len.., dest) {
+      if i > 0 {
+        dest.whitespace()?;
+      }
+      comment.to_css(dest)?;
+      i += 1;
+    }
     Ok(())
   }
 }
@@ -137,10 +194,14 @@ impl<'i> DeclarationBlock<'i> {
 
     macro_rules! write {
       ($decls: expr, $important: literal) => {
-        for decl in &$decls {
+        for (index, decl) in $decls.iter().enumerate() {
+          self.write_comments(i..=i, dest)?;
+          if !$important {
+            self.write_at_rules(index..=index, dest)?;
+          }
           dest.newline()?;
           decl.to_css(dest, $important)?;
-          if i != len - 1 || !dest.minify {
+          if i != len - 1 || !dest.minify || self.has_trailing_at_rules() {
             dest.write_char(';')?;
           }
           i += 1;
@@ -149,7 +210,9 @@ impl<'i> DeclarationBlock<'i> {
     }
 
     write!(self.declarations, false);
+    self.write_at_rules(self.declarations.len().., dest)?;
     write!(self.important_declarations, true);
+    self.write_comments(len.., dest)?;
 
     dest.dedent();
     dest.newline()?;
@@ -173,22 +236,296 @@ impl<'i> DeclarationBlock<'i> {
           if !handled {
             $handler.decls.push(decl.clone());
           }
+
+          // Flush after each declaration so that handlers cannot combine them.
+          if !context.merge_longhands {
+            $handler.finalize(context);
+          }
         }
       };
     }
 
-    handle!(self.important_declarations, important_handler, true);
-    handle!(self.declarations, handler, false);
+    if context.deduplicate_declarations {
+      // Important declarations override all normal declarations of the same property, regardless of order.
+      let indices = deduplicate(&mut self.important_declarations, &[]);
+      update_comment_indices(&mut self.comments, self.declarations.len(), &indices);
+      let indices = deduplicate(&mut self.declarations, &self.important_declarations);
+      update_comment_indices(&mut self.comments, 0, &indices);
+      for (index, _) in &mut self.at_rules {
+        *index = indices[*index];
+      }
+    }
+
+    let track_logical_properties = !context.exclude.contains(Features::LOGICAL_PROPERTIES)
+      && (context.targets.is_some() || context.include.contains(Features::LOGICAL_PROPERTIES));
+    let logical_properties = if track_logical_properties {
+      self.logical_properties()
+    } else {
+      0
+    };
+
+    // The declarations between each comment and custom at-rule are handled separately, so that they stay on
+    // the same side. Comments before the important declarations are indexed relative to them until the end.
+    let normal_len = self.declarations.len();
+    let split = self.comments.partition_point(|(index, _)| *index < normal_len);
+    let (normal_comments, important_comments) = self.comments.split_at_mut(split);
+
+    let important_declarations = std::mem::take(&mut self.important_declarations);
+    let mut comments = important_comments.iter_mut().peekable();
+    let mut start = 0;
+    loop {
+      let end = comments
+        .peek()
+        .map_or(important_declarations.len(), |(index, _)| *index - normal_len)
+        .min(important_declarations.len());
+      handle!(important_declarations[start..end], important_handler, true);
+
+      // The last important declarations are finalized after the normal declarations.
+      if end == important_declarations.len() {
+        break;
+      }
+
+      important_handler.finalize(context);
+      let mut handled = std::mem::take(&mut important_handler.decls);
+      if context.sort_declarations {
+        sort(&mut handled);
+      }
+      self.important_declarations.extend(handled);
+
+      while let Some((index, _)) = comments.next_if(|(index, _)| *index - normal_len == end) {
+        *index = self.important_declarations.len();
+      }
+      start = end;
+    }
+
+    let declarations = std::mem::take(&mut self.declarations);
+    let mut at_rules = self.at_rules.iter_mut().peekable();
+    let mut normal_comments = normal_comments.iter_mut().peekable();
+    let mut start = 0;
+    loop {
+      let end = at_rules
+        .peek()
+        .map(|(index, _)| *index)
+        .into_iter()
+        .chain(normal_comments.peek().map(|(index, _)| *index))
+        .fold(declarations.len(), usize::min);
+      handle!(declarations[start..end], handler, false);
+      handler.finalize(context);
+      let mut handled = std::mem::take(&mut handler.decls);
+      if context.sort_declarations {
+        sort(&mut handled);
+      }
+      self.declarations.extend(handled);
+
+      while let Some((index, _)) = at_rules.next_if(|(index, _)| *index == end) {
+        *index = self.declarations.len();
+      }
+      while let Some((index, _)) = normal_comments.next_if(|(index, _)| *index == end) {
+        *index = self.declarations.len();
+      }
+
+      if end == declarations.len() && at_rules.peek().is_none() && normal_comments.peek().is_none() {
+        break;
+      }
+      start = end;
+    }
 
-    handler.finalize(context);
     important_handler.finalize(context);
-    self.important_declarations = std::mem::take(&mut important_handler.decls);
-    self.declarations = std::mem::take(&mut handler.decls);
+    let mut handled = std::mem::take(&mut important_handler.decls);
+    if context.sort_declarations {
+      sort(&mut handled);
+    }
+    self.important_declarations.extend(handled);
+    for (index, _) in comments {
+      *index = self.important_declarations.len();
+    }
+    for (index, _) in important_comments {
+      *index += self.declarations.len();
+    }
+
+    // Logical properties are compiled when they are replaced with physical properties.
+    if track_logical_properties && self.logical_properties() < logical_properties {
+      context.features_used |= Features::LOGICAL_PROPERTIES;
+    }
+  }
+
+  /// Adds vendor prefixed copies of the declarations that need them for the given browser targets,
+  /// before the unprefixed declaration. Prefixed declarations that are already present are not duplicated,
+  /// and no other changes are made.
+  pub(crate) fn add_prefixes(&mut self, targets: Browsers) {
+    // The new index of each declaration, for the comments that precede it.
+    let mut indices = Vec::with_capacity(self.declarations.len() + self.important_declarations.len());
+    let mut len = 0;
+    let normal_len = self.declarations.len();
+    for list in [&mut self.declarations, &mut self.important_declarations] {
+      let existing: Vec<PropertyId> = list.iter().map(|property| property.property_id()).collect();
+      let mut result: Vec<Property<'i>> = Vec::with_capacity(list.len());
+      for property in list.drain(..) {
+        indices.push(len + result.len());
+        let mut property_id = property.property_id();
+        property_id.set_prefixes_for_targets(Some(targets));
+        let prefixes = property_id.prefix();
+        if prefixes.contains(VendorPrefix::None) {
+          for prefix in [
+            VendorPrefix::WebKit,
+            VendorPrefix::Moz,
+            VendorPrefix::Ms,
+            VendorPrefix::O,
+          ] {
+            if prefixes.contains(prefix) && !existing.contains(&property_id.with_prefix(prefix)) {
+              result.push(property.with_prefix(prefix));
+            }
+          }
+        }
+        result.push(property);
+      }
+      len += result.len();
+      *list = result;
+    }
+    for (index, _) in &mut self.comments {
+      *index = indices.get(*index).copied().unwrap_or(len);
+    }
+    for (index, rule) in &mut self.at_rules {
+      *index = if *index < normal_len {
+        indices[*index]
+      } else {
+        self.declarations.len()
+      };
+      match &mut rule.body {
+        Some(CustomAtRuleBody::DeclarationList(declarations)) => declarations.add_prefixes(targets),
+        Some(CustomAtRuleBody::RuleList(rules)) => rules.add_prefixes(targets),
+        _ => {}
+      }
+    }
+  }
+
+  /// Removes the normal declarations for which the predicate returns false, keeping comments and
+  /// custom at-rules between the same declarations.
+  pub(crate) fn retain_declarations(&mut self, f: impl FnMut(&Property<'i>) -> bool) {
+    let indices = retain(&mut self.declarations, f);
+    update_comment_indices(&mut self.comments, 0, &indices);
+    for (index, _) in &mut self.at_rules {
+      *index = indices[(*index).min(indices.len() - 1)];
+    }
+  }
+
+  /// Removes the important declarations for which the predicate returns false, keeping comments
+  /// between the same declarations.
+  pub(crate) fn retain_important_declarations(&mut self, f: impl FnMut(&Property<'i>) -> bool) {
+    let indices = retain(&mut self.important_declarations, f);
+    update_comment_indices(&mut self.comments, self.declarations.len(), &indices);
+  }
+
+  /// Appends the declarations and comments of another block, e.g. when merging rules with the same selectors.
+  pub(crate) fn append(&mut self, other: &mut DeclarationBlock<'i>) {
+    let normal_len = self.declarations.len();
+    let important_len = self.important_declarations.len();
+    let other_normal_len = other.declarations.len();
+    for (index, _) in &mut self.comments {
+      if *index >= normal_len {
+        *index += other_normal_len;
+      }
+    }
+    for (index, comment) in other.comments.drain(..) {
+      let index = if index < other_normal_len {
+        normal_len + index
+      } else {
+        normal_len + important_len + index
+      };
+      self.comments.push((index, comment));
+    }
+    // The sort is stable, so comments at the same index keep their order.
+    self.comments.sort_by_key(|(index, _)| *index);
+    self.declarations.append(&mut other.declarations);
+    self.important_declarations.append(&mut other.important_declarations);
+  }
+
+  /// Returns the comments to print that precede the declarations within the given range of indices.
+  fn comments_in<'a, W, R: RangeBounds<usize> + 'a>(
+    &'a self,
+    range: R,
+    dest: &Printer<W>,
+  ) -> impl Iterator<Item = &'a CommentRule<'i>> + 'a {
+    let comments = dest.comments;
+    self
+      .comments
+      .iter()
+      .filter(move |(index, comment)| range.contains(index) && comment.is_included(comments))
+      .map(|(_, comment)| comment)
+  }
+
+  /// Returns the custom at-rules that precede the normal declarations within the given range of indices.
+  fn at_rules_in<'a, R: RangeBounds<usize> + 'a>(
+    &'a self,
+    range: R,
+  ) -> impl Iterator<Item = &'a CustomAtRule<'i>> + 'a {
+    self
+      .at_rules
+      .iter()
+      .filter(move |(index, _)| range.contains(index))
+      .map(|(_, rule)| rule)
+  }
+
+  /// Writes the custom at-rules that precede the normal declarations within the given range of indices,
+  /// each on its own line.
+  pub(crate) fn write_at_rules<W, R: RangeBounds<usize>>(
+    &self,
+    range: R,
+    dest: &mut Printer<W>,
+  ) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    for rule in self.at_rules_in(range) {
+      dest.newline()?;
+      rule.to_css_with_context(dest, None)?;
+    }
+    Ok(())
+  }
+
+  /// Returns whether custom at-rules are printed after the last declaration, so that it must end with a semicolon.
+  pub(crate) fn has_trailing_at_rules(&self) -> bool {
+    self.important_declarations.is_empty()
+      && self
+        .at_rules
+        .last()
+        .map_or(false, |(index, _)| *index >= self.declarations.len())
   }
 
-  /// Returns whether the declaration block is empty.
+  /// Writes the comments that precede the declarations within the given range of indices, each on its own line.
+  pub(crate) fn write_comments<W, R: RangeBounds<usize>>(
+    &self,
+    range: R,
+    dest: &mut Printer<W>,
+  ) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    for comment in self.comments_in(range, dest) {
+      dest.newline()?;
+      comment.to_css(dest)?;
+    }
+    Ok(())
+  }
+
+  /// Returns the number of logical longhand properties in the block, including those set by shorthands.
+  fn logical_properties(&self) -> usize {
+    let is_logical = |id: &PropertyId| id.category() == Some(PropertyCategory::Logical);
+    self
+      .iter()
+      .map(|(property, _)| {
+        let id = property.property_id();
+        match id.longhands() {
+          Some(longhands) => longhands.iter().filter(|id| is_logical(id)).count(),
+          None => is_logical(&id) as usize,
+        }
+      })
+      .sum()
+  }
+
+  /// Returns whether the declaration block is empty, including custom at-rules.
   pub fn is_empty(&self) -> bool {
-    return self.declarations.is_empty() && self.important_declarations.is_empty();
+    return self.declarations.is_empty() && self.important_declarations.is_empty() && self.at_rules.is_empty();
   }
 
   pub(crate) fn property_location<'t>(
@@ -382,6 +719,7 @@ impl<'i> DeclarationBlock<'i> {
 struct PropertyDeclarationParser<'a, 'o, 'i> {
   important_declarations: &'a mut Vec<Property<'i>>,
   declarations: &'a mut Vec<Property<'i>>,
+  at_rules: &'a mut Vec<(usize, CustomAtRule<'i>)>,
   options: &'a ParserOptions<'o, 'i>,
 }
 
@@ -405,11 +743,42 @@ impl<'a, 'o, 'i> cssparser::DeclarationParser<'i> for PropertyDeclarationParser<
   }
 }
 
-/// Default methods reject all at rules.
+/// Only at-rules defined by the `custom_at_rules` option are accepted.
 impl<'a, 'o, 'i> AtRuleParser<'i> for PropertyDeclarationParser<'a, 'o, 'i> {
-  type Prelude = ();
+  type Prelude = AtRulePrelude<'i>;
   type AtRule = ();
   type Error = ParserError<'i>;
+
+  fn parse_prelude<'t>(
+    &mut self,
+    name: CowRcStr<'i>,
+    input: &mut Parser<'i, 't>,
+  ) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
+    match self.options.parse_custom_at_rule_prelude(&name, input) {
+      Some(prelude) => prelude,
+      None => Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name))),
+    }
+  }
+
+  fn parse_block<'t>(
+    &mut self,
+    prelude: Self::Prelude,
+    start: &ParserState,
+    input: &mut Parser<'i, 't>,
+  ) -> Result<Self::AtRule, ParseError<'i, Self::Error>> {
+    let options = self.options;
+    let rule = parse_custom_at_rule(prelude, start, input, options, |input| {
+      parse_rules_in_declarations(input, options)
+    })?;
+    self.at_rules.push((self.declarations.len(), rule));
+    Ok(())
+  }
+
+  fn rule_without_block(&mut self, prelude: Self::Prelude, start: &ParserState) -> Result<Self::AtRule, ()> {
+    let rule = custom_at_rule_without_block(prelude, start, self.options)?;
+    self.at_rules.push((self.declarations.len(), rule));
+    Ok(())
+  }
 }
 
 pub(crate) fn parse_declaration<'i, 't>(
@@ -420,7 +789,31 @@ pub(crate) fn parse_declaration<'i, 't>(
   options: &ParserOptions,
 ) -> Result<(), cssparser::ParseError<'i, ParserError<'i>>> {
   let property = input.parse_until_before(Delimiter::Bang, |input| {
-    Property::parse(PropertyId::from(CowArcStr::from(name)), input, options)
+    if options.is_ignored_property(&name) {
+      return Ok(Property::Custom(CustomProperty::parse(name.into(), input, options)?));
+    }
+    let start = input.state();
+    let property = Property::parse(PropertyId::from(CowArcStr::from(name.clone())), input, options)?;
+    if options.strict && !options.is_strict_exception(&name) {
+      let error = match &property {
+        Property::Custom(custom) if !custom.name.starts_with("--") => Some(ParserError::InvalidDeclaration),
+        Property::Unparsed(unparsed) if !unparsed.may_be_valid() => {
+          // Valid values containing math functions are also kept unparsed when `preserve_calc` is enabled.
+          let end = input.state();
+          input.reset(&start);
+          let has_math_function = options.preserve_calc && contains_math_function(input);
+          input.reset(&end);
+          (!has_math_function).then_some(ParserError::InvalidValue)
+        }
+        _ => None,
+      };
+      if let Some(error) = error {
+        input.reset(&start);
+        input.skip_whitespace();
+        return Err(input.new_custom_error(error));
+      }
+    }
+    Ok(property)
   })?;
   let important = input
     .try_parse(|input| {
@@ -438,6 +831,124 @@ pub(crate) fn parse_declaration<'i, 't>(
 
 pub(crate) type DeclarationList<'i> = Vec<Property<'i>>;
 
+/// Removes declarations that are overridden by a later declaration of the same property or
+/// a shorthand that includes it, or by one of the given declarations. Custom properties and
+/// values containing `var()` are never removed, because they cannot be analyzed statically.
+/// Returns the new indices, as returned by [retain].
+fn deduplicate<'i>(decls: &mut DeclarationList<'i>, overrides: &[Property<'i>]) -> Vec<usize> {
+  let mut ids: Vec<PropertyId<'i>> = overrides.iter().map(|decl| decl.property_id()).collect();
+  let mut keep = vec![true; decls.len()];
+  for (i, decl) in decls.iter().enumerate().rev() {
+    let id = decl.property_id();
+    if !matches!(decl, Property::Unparsed(_) | Property::Custom(_)) && is_overridden(&id, &ids) {
+      keep[i] = false;
+    } else {
+      ids.push(id);
+    }
+  }
+
+  let mut keep = keep.into_iter();
+  retain(decls, |_| keep.next().unwrap())
+}
+
+/// Removes the declarations for which the predicate returns false. Returns the new index of each
+/// declaration, for the comments and custom at-rules that precede it, followed by the new length.
+fn retain<'i>(decls: &mut DeclarationList<'i>, mut f: impl FnMut(&Property<'i>) -> bool) -> Vec<usize> {
+  let mut indices = Vec::with_capacity(decls.len() + 1);
+  let mut len = 0;
+  decls.retain(|property| {
+    indices.push(len);
+    let retain = f(property);
+    len += retain as usize;
+    retain
+  });
+  indices.push(len);
+  indices
+}
+
+/// Updates the indices of comments after declarations were removed by [retain], starting at the given offset
+/// within the block. Comments after the removed declarations move back by the number of removed declarations.
+fn update_comment_indices(comments: &mut [(usize, CommentRule)], offset: usize, indices: &[usize]) {
+  let removed = indices.len() - 1 - indices[indices.len() - 1];
+  for (index, _) in comments.iter_mut().filter(|(index, _)| *index >= offset) {
+    *index = match indices.get(*index - offset) {
+      Some(new_index) => offset + new_index,
+      None => *index - removed,
+    };
+  }
+}
+
+/// Returns whether a property is fully overridden by one of the given properties.
+fn is_overridden(id: &PropertyId, overrides: &[PropertyId]) -> bool {
+  let longhands = id.longhands();
+  overrides.iter().any(|o| {
+    if o == id {
+      return true;
+    }
+
+    // Logical and physical properties are distinct, so e.g. `margin` does not override `margin-inline-start`.
+    // A shorthand is not overridden by a combination of longhands, because it may also reset properties
+    // that are not listed as its longhands, e.g. `font` resets `font-kerning`.
+    match (&longhands, o.longhands()) {
+      (Some(longhands), Some(o_longhands)) => longhands.iter().all(|l| o_longhands.contains(l)),
+      (None, Some(o_longhands)) => o_longhands.iter().any(|l| l == id),
+      _ => false,
+    }
+  })
+}
+
+/// Sorts declarations by property name, without moving declarations past others that
+/// they interact with. Custom properties and values containing `var()` are not moved,
+/// and declarations are not moved past them.
+fn sort<'i>(decls: &mut DeclarationList<'i>) {
+  let mut start = 0;
+  while start < decls.len() {
+    let end = decls[start..]
+      .iter()
+      .position(|decl| matches!(decl, Property::Unparsed(_) | Property::Custom(_)))
+      .map_or(decls.len(), |i| start + i);
+
+    // Insertion sort, where each declaration moves before all previous ones it can be swapped with.
+    let ids: Vec<PropertyId> = decls[start..end].iter().map(|decl| decl.property_id()).collect();
+    let mut order: Vec<usize> = Vec::with_capacity(ids.len());
+    for i in 0..ids.len() {
+      let mut pos = order.len();
+      while pos > 0 && ids[order[pos - 1]].name() > ids[i].name() && !interacts(&ids[order[pos - 1]], &ids[i]) {
+        pos -= 1;
+      }
+      order.insert(pos, i);
+    }
+
+    let mut segment: Vec<Option<Property<'i>>> = decls.drain(start..end).map(Some).collect();
+    decls.splice(start..start, order.iter().map(|i| segment[*i].take().unwrap()));
+    start = end + 1;
+  }
+}
+
+/// Returns whether the relative order of two properties may affect the result.
+fn interacts(a: &PropertyId, b: &PropertyId) -> bool {
+  if *a == PropertyId::All || *b == PropertyId::All {
+    return true;
+  }
+
+  // Shorthands may reset properties that are not listed as their longhands, e.g. `font` resets `font-kerning`.
+  let (a_name, b_name) = (a.name(), b.name());
+  if a_name == b_name
+    || (b_name.starts_with(a_name) && b_name.as_bytes()[a_name.len()] == b'-')
+    || (a_name.starts_with(b_name) && a_name.as_bytes()[b_name.len()] == b'-')
+  {
+    return true;
+  }
+
+  let a_longhands = a.longhands().unwrap_or_else(|| vec![a.clone()]);
+  let b_longhands = b.longhands().unwrap_or_else(|| vec![b.clone()]);
+  a_longhands.iter().any(|a| {
+    b_longhands
+      .iter()
+      .any(|b| a == b || (a.logical_group().is_some() && a.logical_group() == b.logical_group()))
+  })
+}
+
 pub(crate) struct DeclarationHandler<'i> {
   background: BackgroundHandler<'i>,
   border: BorderHandler<'i>,
@@ -506,10 +1017,13 @@ impl<'i> DeclarationHandler<'i> {
     property: &Property<'i>,
     context: &mut PropertyHandlerContext<'i, '_>,
   ) -> bool {
-    if !context.unused_symbols.is_empty()
-      && matches!(property, Property::Custom(custom) if context.unused_symbols.contains(custom.name.as_ref()))
-    {
-      return true;
+    if !context.unused_symbols.is_empty() {
+      if let Property::Custom(custom) = property {
+        if context.unused_symbols.contains(custom.name.as_ref()) {
+          context.add_removed_symbol(custom.name.as_ref());
+          return true;
+        }
+      }
     }
 
     self.background.handle_property(property, &mut self.decls, context)
@@ -40,12 +40,17 @@ pub struct ImportDependency {
   pub url: String,
   /// The placeholder that the URL was replaced with.
   pub placeholder: String,
-  /// An optional `supports()` condition.
+  /// The `supports()` condition of the `@import` rule, if any, serialized without the
+  /// `supports()` function so that it can be used as the prelude of an `@supports` rule.
   pub supports: Option<String>,
-  /// A media query.
+  /// The media query list of the `@import` rule, if any, serialized so that it can be used
+  /// as the prelude of an `@media` rule.
   pub media: Option<String>,
   /// The location of the dependency in the source file.
   pub loc: SourceRange,
+  /// Whether the bundler skipped the import because no browser target supports its
+  /// `supports()` condition.
+  pub skipped: bool,
 }
 
 impl ImportDependency {
@@ -81,6 +86,7 @@ impl ImportDependency {
         8,
         rule.url.len() + 2,
       ), // TODO: what about @import url(...)?
+      skipped: false,
     }
   }
 }
@@ -94,6 +100,9 @@ pub struct UrlDependency {
   pub placeholder: String,
   /// The location of the dependency in the source file.
   pub loc: SourceRange,
+  /// The `format()` hint of an `@font-face` `src` url, if any.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub format: Option<String>,
 }
 
 impl UrlDependency {
@@ -104,6 +113,7 @@ impl UrlDependency {
       url: url.url.to_string(),
       placeholder,
       loc: SourceRange::new(filename, url.loc, 4, url.url.len()),
+      format: None,
     }
   }
 }
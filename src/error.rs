@@ -91,6 +91,29 @@ pub enum ParserError<'i> {
   UnexpectedToken(#[serde(skip)] Token<'i>),
   /// Maximum nesting depth was reached.
   MaximumNestingDepth,
+  /// A class referenced by `composes` was removed because it was listed in `unused_symbols`,
+  /// and the reference was removed. This is emitted as a warning during minification.
+  UnusedComposes(CowArcStr<'i>),
+  /// A `@counter-style` rule is missing the `symbols` or `additive-symbols` descriptor required by its
+  /// system, or has one that is not allowed, and so does not define a counter style. This is emitted as a warning.
+  InvalidCounterStyle(CowArcStr<'i>),
+  /// The `font-family` descriptor of a `@font-face` rule is a generic family keyword such as `serif`,
+  /// or a name spelled the same way as one. This is emitted as a warning.
+  FontFamilyShadowsGeneric(CowArcStr<'i>),
+  /// A fallback for browsers without custom properties could not be added for a declaration, because it
+  /// references a custom property that is not only defined in `:root`. This is emitted as a warning.
+  UnresolvedCustomProperty(CowArcStr<'i>),
+}
+
+/// How serious a [ParserError] is when it is emitted as a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+  /// Something is invalid, and was ignored or removed, so the output may not be what was intended.
+  Warning,
+  /// Something is not understood but was kept as written, e.g. an unknown at-rule, or a change
+  /// was made as requested, e.g. a `composes` reference to a removed class was removed.
+  Info,
 }
 
 impl<'i> fmt::Display for ParserError<'i> {
@@ -117,6 +140,22 @@ impl<'i> fmt::Display for ParserError<'i> {
       ),
       UnexpectedToken(token) => write!(f, "Unexpected token {:?}", token),
       MaximumNestingDepth => write!(f, "Overflowed the maximum nesting depth"),
+      UnusedComposes(name) => write!(f, "Removed composes reference to unused class {}", name),
+      InvalidCounterStyle(name) => write!(
+        f,
+        "@counter-style {} does not define a counter style, because its symbols are invalid for its system",
+        name
+      ),
+      FontFamilyShadowsGeneric(name) => write!(
+        f,
+        "@font-face font-family {} has the same name as a generic font family keyword",
+        name
+      ),
+      UnresolvedCustomProperty(name) => write!(
+        f,
+        "Cannot add a fallback for browsers without custom properties, because {} is not statically defined on :root",
+        name
+      ),
     }
   }
 }
@@ -153,6 +192,39 @@ impl<'i> From<SelectorParseErrorKind<'i>> for ParserError<'i> {
 }
 
 impl<'i> ParserError<'i> {
+  /// Returns a stable code identifying the type of error, i.e. the name of the variant.
+  pub fn code(&self) -> &'static str {
+    use ParserError::*;
+    match self {
+      AtRuleBodyInvalid => "AtRuleBodyInvalid",
+      AtRuleInvalid(..) => "AtRuleInvalid",
+      EndOfInput => "EndOfInput",
+      InvalidDeclaration => "InvalidDeclaration",
+      InvalidMediaQuery => "InvalidMediaQuery",
+      InvalidNesting => "InvalidNesting",
+      InvalidPageSelector => "InvalidPageSelector",
+      InvalidValue => "InvalidValue",
+      QualifiedRuleInvalid => "QualifiedRuleInvalid",
+      SelectorError(..) => "SelectorError",
+      UnexpectedImportRule => "UnexpectedImportRule",
+      UnexpectedNamespaceRule => "UnexpectedNamespaceRule",
+      UnexpectedToken(..) => "UnexpectedToken",
+      MaximumNestingDepth => "MaximumNestingDepth",
+      UnusedComposes(..) => "UnusedComposes",
+      InvalidCounterStyle(..) => "InvalidCounterStyle",
+      FontFamilyShadowsGeneric(..) => "FontFamilyShadowsGeneric",
+      UnresolvedCustomProperty(..) => "UnresolvedCustomProperty",
+    }
+  }
+
+  /// Returns how serious the error is when it is emitted as a warning.
+  pub fn severity(&self) -> Severity {
+    match self {
+      ParserError::AtRuleInvalid(..) | ParserError::UnusedComposes(..) => Severity::Info,
+      _ => Severity::Warning,
+    }
+  }
+
   #[deprecated(note = "use `ParserError::to_string()` or `fmt::Display` instead")]
   #[allow(missing_docs)]
   pub fn reason(&self) -> String {
@@ -295,6 +367,8 @@ pub enum MinifyErrorKind {
   CircularCustomMedia {
     /// The name of the `@custom-media` rule that was referenced circularly.
     name: String,
+    /// The chain of `@custom-media` references forming the cycle, starting and ending with `name`.
+    cycle: Vec<String>,
   },
   /// Attempted to reference a custom media rule that doesn't exist.
   CustomMediaNotDefined {
@@ -306,23 +380,53 @@ pub enum MinifyErrorKind {
     /// The source location of the `@custom-media` rule with unsupported boolean logic.
     custom_media_loc: Location,
   },
+  /// A feature that is not supported by the browser targets, and cannot be compiled, was used.
+  /// This is only emitted when the `error_on_unsupported` option is enabled.
+  UnsupportedFeature {
+    /// The name of the feature, e.g. `:has()`.
+    feature: String,
+    /// The browser targets that do not support the feature, e.g. `safari 14`.
+    targets: Vec<String>,
+  },
 }
 
 impl fmt::Display for MinifyErrorKind {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     use MinifyErrorKind::*;
     match self {
-      CircularCustomMedia { name } => write!(f, "Circular custom media query {} detected", name),
+      CircularCustomMedia { name, cycle } => write!(
+        f,
+        "Circular custom media query {} detected: {}",
+        name,
+        cycle.join(" -> ")
+      ),
       CustomMediaNotDefined { name } => write!(f, "Custom media query {} is not defined", name),
       UnsupportedCustomMediaBooleanLogic { .. } => write!(
         f,
         "Boolean logic with media types in @custom-media rules is not supported by Lightning CSS"
       ),
+      UnsupportedFeature { feature, targets } => write!(
+        f,
+        "{} is not supported by the browser targets ({}), and cannot be compiled",
+        feature,
+        targets.join(", ")
+      ),
     }
   }
 }
 
 impl MinifyErrorKind {
+  /// Returns a stable code identifying the type of error, i.e. the name of the variant.
+  pub fn code(&self) -> &'static str {
+    use MinifyErrorKind::*;
+    match self {
+      CircularCustomMedia { .. } => "CircularCustomMedia",
+      CustomMediaNotDefined { .. } => "CustomMediaNotDefined",
+      UnsupportedCustomMediaBooleanLogic { .. } => "UnsupportedCustomMediaBooleanLogic",
+      UnsupportedFeature { .. } => "UnsupportedFeature",
+    }
+  }
+
   #[deprecated(note = "use `MinifyErrorKind::to_string()` or `fmt::Display` instead")]
   #[allow(missing_docs)]
   pub fn reason(&self) -> String {
@@ -350,6 +454,12 @@ pub enum PrinterErrorKind {
   InvalidComposesSelector,
   /// The CSS modules pattern must end with `[local]` for use in CSS grid.
   InvalidCssModulesPatternInGrid,
+  /// A feature that is not supported by the browser targets, and cannot be compiled, was printed.
+  /// This is only emitted as a warning.
+  UnsupportedFeature {
+    /// The name of the feature, e.g. `:has()`.
+    feature: String,
+  },
 }
 
 impl From<fmt::Error> for PrinterError {
@@ -370,11 +480,25 @@ impl fmt::Display for PrinterErrorKind {
       InvalidComposesNesting => write!(f, "The `composes` property cannot be used within nested rules"),
       InvalidComposesSelector => write!(f, "The `composes` property cannot be used with a simple class selector"),
       InvalidCssModulesPatternInGrid => write!(f, "The CSS modules `pattern` config must end with `[local]` for use in CSS grid line names."),
+      UnsupportedFeature { feature } => write!(f, "{} is not supported by the browser targets, and cannot be compiled", feature),
     }
   }
 }
 
 impl PrinterErrorKind {
+  /// Returns a stable code identifying the type of error, i.e. the name of the variant.
+  pub fn code(&self) -> &'static str {
+    use PrinterErrorKind::*;
+    match self {
+      AmbiguousUrlInCustomProperty { .. } => "AmbiguousUrlInCustomProperty",
+      FmtError => "FmtError",
+      InvalidComposesNesting => "InvalidComposesNesting",
+      InvalidComposesSelector => "InvalidComposesSelector",
+      InvalidCssModulesPatternInGrid => "InvalidCssModulesPatternInGrid",
+      UnsupportedFeature { .. } => "UnsupportedFeature",
+    }
+  }
+
   #[deprecated(note = "use `PrinterErrorKind::to_string()` or `fmt::Display` instead")]
   #[allow(missing_docs)]
   pub fn reason(&self) -> String {
@@ -42,7 +42,9 @@ pub mod vendor_prefix;
 mod tests {
   use crate::css_modules::{CssModuleExport, CssModuleExports, CssModuleReference, CssModuleReferences};
   use crate::dependencies::Dependency;
-  use crate::error::{Error, ErrorLocation, MinifyErrorKind, ParserError, PrinterErrorKind, SelectorError};
+  use crate::error::{
+    Error, ErrorLocation, MinifyErrorKind, ParserError, PrinterErrorKind, SelectorError, Severity,
+  };
   use crate::properties::custom::Token;
   use crate::properties::Property;
   use crate::rules::CssRule;
@@ -53,7 +55,7 @@ mod tests {
   use crate::values::color::CssColor;
   use cssparser::SourceLocation;
   use indoc::indoc;
-  use std::collections::HashMap;
+  use std::collections::{HashMap, HashSet};
 
   fn test(source: &str, expected: &str) {
     test_with_options(source, expected, ParserOptions::default())
@@ -198,6 +200,31 @@ mod tests {
     assert_eq!(res.code, expected);
   }
 
+  fn scope_test(source: &str, expected: &str, targets: Option<Browsers>) {
+    let mut stylesheet = StyleSheet::parse(
+      &source,
+      ParserOptions {
+        scope: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    stylesheet
+      .minify(MinifyOptions {
+        targets,
+        ..MinifyOptions::default()
+      })
+      .unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        minify: true,
+        targets,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(res.code, expected);
+  }
+
   fn error_test(source: &str, error: ParserError) {
     let res = StyleSheet::parse(&source, ParserOptions::default());
     match res {
@@ -249,7 +276,9 @@ mod tests {
           m.insert($key.into(), CssModuleExport {
             name: $name.into(),
             composes: v,
-            is_referenced: is_referenced!($($referenced)?)
+            is_referenced: is_referenced!($($referenced)?),
+            specificity: None,
+            selectors: None
           });
         )*
         m
@@ -5154,6 +5183,18 @@ mod tests {
       ".foo{font-family:\"revert\",foo,sans-serif}",
     );
     minify_test(".foo { font-family: ''; }", ".foo{font-family:\"\"}");
+    minify_test(
+      ".foo { font-family: \"Inter\", inter, INTER, sans-serif, sans-serif; }",
+      ".foo{font-family:Inter,sans-serif}",
+    );
+    minify_test(
+      ".foo { font-family: Open Sans, 'open sans', 'serif', serif; }",
+      ".foo{font-family:Open Sans,\"serif\",serif}",
+    );
+    minify_test(
+      ".foo { font: 12px/1.2 \"Inter\", Inter, sans-serif; }",
+      ".foo{font:12px/1.2 Inter,sans-serif}",
+    );
 
     // font-family in @font-face
     minify_test(
@@ -5582,6 +5623,359 @@ mod tests {
     minify_test("::foo(*) { color: yellow }", "::foo(*){color:#ff0}");
   }
 
+  #[test]
+  fn test_deep_selectors() {
+    fn deep_test(source: &str, expected: &str, strip_deep_selectors: bool) {
+      let mut stylesheet = StyleSheet::parse(
+        &source,
+        ParserOptions {
+          deep_selectors: true,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          strip_deep_selectors,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    deep_test(".a :deep(.b) { color: red }", ".a :deep(.b){color:red}", false);
+    deep_test(".a:deep(.b .c) { color: red }", ".a:deep(.b .c){color:red}", false);
+    deep_test(".a ::v-deep .b { color: red }", ".a ::v-deep .b{color:red}", false);
+    deep_test(".a::v-deep > .b { color: red }", ".a::v-deep>.b{color:red}", false);
+    deep_test(
+      ".a ::v-deep(.b) .c { color: red }",
+      ".a ::v-deep(.b) .c{color:red}",
+      false,
+    );
+    deep_test(".a ::ng-deep .b { color: red }", ".a ::ng-deep .b{color:red}", false);
+    deep_test("::v-deep .b { color: red }", "::v-deep .b{color:red}", false);
+    deep_test(
+      ".a :deep(.b) { color: red } .c { color: red }",
+      ".a :deep(.b),.c{color:red}",
+      false,
+    );
+
+    deep_test(".a :deep(.b) { color: red }", ".a .b{color:red}", true);
+    deep_test(".a:deep(.b .c) { color: red }", ".a .b .c{color:red}", true);
+    deep_test(".a > :deep(.b) { color: red }", ".a>.b{color:red}", true);
+    deep_test(".a ::v-deep .b { color: red }", ".a .b{color:red}", true);
+    deep_test(".a::v-deep > .b { color: red }", ".a>.b{color:red}", true);
+    deep_test(".a ::v-deep(.b) .c { color: red }", ".a .b .c{color:red}", true);
+    deep_test(".a ::ng-deep .b { color: red }", ".a .b{color:red}", true);
+    deep_test("::v-deep .b { color: red }", ".b{color:red}", true);
+    deep_test(".a ::v-deep { color: red }", ".a{color:red}", true);
+
+    // Without the option, deep selectors are unknown pseudo classes and elements.
+    minify_test(".a :deep(.b) { color: red }", ".a :deep(.b){color:red}");
+    assert!(StyleSheet::parse(".a ::v-deep .b { color: red }", ParserOptions::default()).is_err());
+
+    for (scope_deep_selectors, expected) in [
+      (
+        false,
+        ".EgL3uq_a :deep(.b) .EgL3uq_c,.EgL3uq_a ::v-deep .b .c{color:red}",
+      ),
+      (
+        true,
+        ".EgL3uq_a :deep(.EgL3uq_b) .EgL3uq_c,.EgL3uq_a ::v-deep .EgL3uq_b .EgL3uq_c{color:red}",
+      ),
+    ] {
+      let stylesheet = StyleSheet::parse(
+        ".a :deep(.b) .c, .a ::v-deep .b .c { color: red }",
+        ParserOptions {
+          filename: "test.css".into(),
+          css_modules: Some(crate::css_modules::Config {
+            scope_deep_selectors,
+            ..Default::default()
+          }),
+          deep_selectors: true,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+      assert_eq!(res.exports.unwrap().contains_key("b"), scope_deep_selectors);
+    }
+  }
+
+  #[test]
+  fn test_custom_at_rules() {
+    use crate::rules::custom_at_rule::{
+      CustomAtRuleBodySyntax, CustomAtRuleDefinition, CustomAtRulePreludeSyntax,
+    };
+
+    fn options<'o, 'i>(nesting: bool) -> ParserOptions<'o, 'i> {
+      let definitions = [
+        (
+          "tailwind",
+          Some(CustomAtRulePreludeSyntax::Ident),
+          CustomAtRuleBodySyntax::None,
+        ),
+        (
+          "config",
+          Some(CustomAtRulePreludeSyntax::String),
+          CustomAtRuleBodySyntax::None,
+        ),
+        (
+          "apply",
+          Some(CustomAtRulePreludeSyntax::CustomIdentList),
+          CustomAtRuleBodySyntax::None,
+        ),
+        (
+          "screen",
+          Some(CustomAtRulePreludeSyntax::Ident),
+          CustomAtRuleBodySyntax::RuleList,
+        ),
+        (
+          "mixin",
+          Some(CustomAtRulePreludeSyntax::Ident),
+          CustomAtRuleBodySyntax::DeclarationList,
+        ),
+        ("raw", None, CustomAtRuleBodySyntax::Raw),
+      ];
+      ParserOptions {
+        filename: "test.css".into(),
+        nesting,
+        custom_at_rules: Some(
+          definitions
+            .into_iter()
+            .map(|(name, prelude, body)| (name.into(), CustomAtRuleDefinition { prelude, body }))
+            .collect(),
+        ),
+        ..ParserOptions::default()
+      }
+    }
+
+    fn custom_test(source: &str, expected: &str, minify: bool, targets: Option<Browsers>) {
+      for nesting in [false, true] {
+        let mut stylesheet = StyleSheet::parse(&source, options(nesting)).unwrap();
+        stylesheet
+          .minify(MinifyOptions {
+            targets,
+            ..MinifyOptions::default()
+          })
+          .unwrap();
+        let res = stylesheet
+          .to_css(PrinterOptions {
+            minify,
+            targets,
+            ..PrinterOptions::default()
+          })
+          .unwrap();
+        assert_eq!(res.code, expected);
+      }
+    }
+
+    custom_test(
+      "@tailwind base;\n@tailwind components;\n@config \"./tailwind.config.js\";",
+      "@tailwind base;\n@tailwind components;\n@config \"./tailwind.config.js\";\n",
+      false,
+      None,
+    );
+    custom_test(
+      ".btn { @apply flex items-center; color: #ff0000 }",
+      ".btn{@apply flex items-center;color:red}",
+      true,
+      None,
+    );
+    custom_test(
+      ".btn { color: #ff0000; @apply flex; background: blue !important; @apply items-center }",
+      ".btn {\n  color: red;\n  @apply flex;\n  @apply items-center;\n  background: #00f !important;\n}\n",
+      false,
+      None,
+    );
+    custom_test(
+      ".a { padding-top: 1px; padding-right: 1px; @apply p-0; padding-bottom: 1px; padding-left: 1px }",
+      ".a{padding-top:1px;padding-right:1px;@apply p-0;padding-bottom:1px;padding-left:1px}",
+      true,
+      None,
+    );
+    custom_test(
+      ".btn { color: #ff0000; @apply flex; }",
+      ".btn{color:red;@apply flex;}",
+      true,
+      Some(Browsers {
+        chrome: Some(90 << 16),
+        ..Browsers::default()
+      }),
+    );
+    custom_test(
+      ".a { @apply flex } .b { @apply flex }",
+      ".a{@apply flex;}.b{@apply flex;}",
+      true,
+      None,
+    );
+    custom_test(
+      "@screen md { .a { color: #ff0000 } .b { color: #ff0000 } }",
+      "@screen md{.a,.b{color:red}}",
+      true,
+      None,
+    );
+    custom_test(
+      "@mixin big { font-size: 20px; color: #ff0000 }",
+      "@mixin big{color:red;font-size:20px}",
+      true,
+      None,
+    );
+    custom_test("@raw { a b; c }", "@raw{a b; c}", true, None);
+
+    let invalid = [
+      "@tailwind;",
+      "@tailwind base { }",
+      "@apply 10px;",
+      "@screen md;",
+      "@raw foo {}",
+    ];
+    for source in invalid {
+      assert!(StyleSheet::parse(source, options(false)).is_err(), "{}", source);
+    }
+
+    // Custom at-rules stay within the parent rule when nesting is compiled.
+    let mut stylesheet = StyleSheet::parse(
+      ".btn { @apply flex; color: red; &:hover { @apply underline } }",
+      options(true),
+    )
+    .unwrap();
+    let targets = Some(Browsers {
+      chrome: Some(90 << 16),
+      ..Browsers::default()
+    });
+    stylesheet
+      .minify(MinifyOptions {
+        targets,
+        ..MinifyOptions::default()
+      })
+      .unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        minify: true,
+        targets,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(res.code, ".btn{@apply flex;color:red}.btn:hover{@apply underline;}");
+
+    // Nested rules other than custom at-rules still require nesting.
+    assert!(StyleSheet::parse(".a { & .b { color: red } }", options(false)).is_err());
+    assert!(StyleSheet::parse(".a { @media print { color: red } }", options(false)).is_err());
+
+    let mut stylesheet = StyleSheet::parse("@mixin bg { background: url(foo.png) }", options(false)).unwrap();
+    stylesheet.minify(MinifyOptions::default()).unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        analyze_dependencies: Some(Default::default()),
+        minify: true,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(res.code, "@mixin bg{background:url(\"Vwkwkq\")}");
+    assert_eq!(res.dependencies.unwrap().len(), 1);
+  }
+
+  #[test]
+  fn test_deep_selector_combinator() {
+    fn deep_test(source: &str, expected: &str, minify: bool, strip_deep_selectors: bool) {
+      let mut stylesheet = StyleSheet::parse(
+        &source,
+        ParserOptions {
+          deep_selectors: true,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          strip_deep_selectors,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    deep_test(
+      ".a >>> .b { color: red }",
+      ".a >>> .b {\n  color: red;\n}\n",
+      false,
+      false,
+    );
+    deep_test(
+      ".a /deep/ .b { color: red }",
+      ".a /deep/ .b {\n  color: red;\n}\n",
+      false,
+      false,
+    );
+    deep_test(
+      ".a::v-deep .b { color: red }",
+      ".a::v-deep .b {\n  color: red;\n}\n",
+      false,
+      false,
+    );
+    deep_test(".a>>>.b { color: red }", ".a>>>.b{color:red}", true, false);
+    deep_test(".a /DEEP/ .b { color: red }", ".a/deep/.b{color:red}", true, false);
+    deep_test(".a > .b { color: red }", ".a>.b{color:red}", true, false);
+    deep_test(
+      ".a >>> .b { color: red } .c { color: red }",
+      ".a>>>.b{color:red}.c{color:red}",
+      true,
+      false,
+    );
+    deep_test(".a >>> .b { color: red }", ".a .b{color:red}", true, true);
+    deep_test(".a /deep/ .b > .c { color: red }", ".a .b>.c{color:red}", true, true);
+
+    // Deep combinators and deep pseudo classes and elements may be used together.
+    deep_test(
+      ".a >>> .b :deep(.c) { color: red } .d ::v-deep .e /deep/ .f { color: red }",
+      ".a>>>.b :deep(.c){color:red}.d ::v-deep .e/deep/.f{color:red}",
+      true,
+      false,
+    );
+    deep_test(
+      ".a >>> .b :deep(.c) { color: red } .d ::v-deep .e /deep/ .f { color: red }",
+      ".a .b .c,.d .e .f{color:red}",
+      true,
+      true,
+    );
+
+    let invalid = [
+      ".a >>> .b { color: red }",
+      ".a /deep/ .b { color: red }",
+      ".a > > .b { color: red }",
+    ];
+    for source in invalid {
+      assert!(StyleSheet::parse(source, ParserOptions::default()).is_err());
+    }
+    assert!(StyleSheet::parse(
+      ".a > > .b { color: red }",
+      ParserOptions {
+        deep_selectors: true,
+        ..ParserOptions::default()
+      }
+    )
+    .is_err());
+  }
+
   #[test]
   fn test_keyframes() {
     minify_test(
@@ -5938,56 +6332,172 @@ mod tests {
         ..Browsers::default()
       },
     );
-  }
 
-  #[test]
-  fn test_important() {
-    test(
+    // Keyframes within a conditional rule override earlier ones when the condition matches,
+    // so they must not be merged across it.
+    minify_test(
       r#"
-      .foo {
-        align-items: center;
-        justify-items: center !important;
+      @keyframes foo {
+        from { opacity: 0 }
+        to { opacity: 1 }
       }
-    "#,
-      indoc! {r#"
-      .foo {
-        align-items: center;
-        justify-items: center !important;
+      @media (prefers-reduced-motion) {
+        @keyframes foo {
+          from { opacity: 1 }
+          to { opacity: 1 }
+        }
       }
-    "#},
-    );
-
-    test(
-      r#"
-      .foo {
-        justify-items: center !important;
-        align-items: center;
+      @keyframes foo {
+        from { opacity: 0 }
+        to { opacity: 1 }
       }
     "#,
-      indoc! {r#"
-      .foo {
-        align-items: center;
-        justify-items: center !important;
-      }
-    "#},
+      "@keyframes foo{0%{opacity:0}to{opacity:1}}@media (prefers-reduced-motion){@keyframes foo{0%{opacity:1}to{opacity:1}}}@keyframes foo{0%{opacity:0}to{opacity:1}}",
     );
-
     minify_test(
       r#"
-      .foo {
-        font-family: SFMono-Regular, Menlo, Monaco, Consolas, "Liberation Mono", "Courier New", monospace !important;
+      @keyframes foo {
+        from { opacity: 0 }
+        to { opacity: 1 }
+      }
+      @supports (display: grid) {
+        @keyframes foo {
+          from { opacity: 1 }
+          to { opacity: 1 }
+        }
+      }
+      @keyframes foo {
+        from { opacity: 0 }
+        to { opacity: 1 }
       }
     "#,
-      ".foo{font-family:SFMono-Regular,Menlo,Monaco,Consolas,Liberation Mono,Courier New,monospace!important}",
+      "@keyframes foo{0%{opacity:0}to{opacity:1}}@supports (display: grid){@keyframes foo{0%{opacity:1}to{opacity:1}}}@keyframes foo{0%{opacity:0}to{opacity:1}}",
     );
-  }
+    prefix_test(
+      r#"
+      @-webkit-keyframes foo {
+        from { opacity: 0 }
+        to { opacity: 1 }
+      }
+      @media (prefers-reduced-motion) {
+        @keyframes foo {
+          from { opacity: 1 }
+          to { opacity: 1 }
+        }
+      }
+      @keyframes foo {
+        from { opacity: 0 }
+        to { opacity: 1 }
+      }
+    "#,
+      indoc! { r#"
+      @-webkit-keyframes foo {
+        from {
+          opacity: 0;
+        }
 
-  #[test]
-  fn test_calc() {
-    minify_test(".foo { width: calc(20px * 2) }", ".foo{width:40px}");
-    minify_test(".foo { font-size: calc(100vw / 35) }", ".foo{font-size:2.85714vw}");
-    minify_test(".foo { width: calc(20px * 2 * 3) }", ".foo{width:120px}");
-    minify_test(".foo { width: calc(20px + 30px) }", ".foo{width:50px}");
+        to {
+          opacity: 1;
+        }
+      }
+
+      @media (prefers-reduced-motion) {
+        @-webkit-keyframes foo {
+          from {
+            opacity: 1;
+          }
+
+          to {
+            opacity: 1;
+          }
+        }
+
+        @keyframes foo {
+          from {
+            opacity: 1;
+          }
+
+          to {
+            opacity: 1;
+          }
+        }
+      }
+
+      @-webkit-keyframes foo {
+        from {
+          opacity: 0;
+        }
+
+        to {
+          opacity: 1;
+        }
+      }
+
+      @keyframes foo {
+        from {
+          opacity: 0;
+        }
+
+        to {
+          opacity: 1;
+        }
+      }
+    "#},
+      Browsers {
+        safari: Some(8 << 16),
+        ..Browsers::default()
+      },
+    );
+  }
+
+  #[test]
+  fn test_important() {
+    test(
+      r#"
+      .foo {
+        align-items: center;
+        justify-items: center !important;
+      }
+    "#,
+      indoc! {r#"
+      .foo {
+        align-items: center;
+        justify-items: center !important;
+      }
+    "#},
+    );
+
+    test(
+      r#"
+      .foo {
+        justify-items: center !important;
+        align-items: center;
+      }
+    "#,
+      indoc! {r#"
+      .foo {
+        align-items: center;
+        justify-items: center !important;
+      }
+    "#},
+    );
+
+    minify_test(
+      r#"
+      .foo {
+        font-family: SFMono-Regular, Menlo, Monaco, Consolas, "Liberation Mono", "Courier New", monospace !important;
+      }
+    "#,
+      ".foo{font-family:SFMono-Regular,Menlo,Monaco,Consolas,Liberation Mono,Courier New,monospace!important}",
+    );
+  }
+
+  #[test]
+  fn test_calc() {
+    minify_test(".foo { width: calc(20px * 2) }", ".foo{width:40px}");
+    minify_test(".foo { font-size: calc(100vw / 35) }", ".foo{font-size:2.85714vw}");
+    minify_test(".foo { width: calc(20px * 2 * 3) }", ".foo{width:120px}");
+    minify_test(".foo { width: calc(20px + 30px) }", ".foo{width:50px}");
     minify_test(".foo { width: calc(20px + 30px + 40px) }", ".foo{width:90px}");
     minify_test(".foo { width: calc(100% - 30px) }", ".foo{width:calc(100% - 30px)}");
     minify_test(
@@ -6848,7 +7358,7 @@ mod tests {
         }
       }
       "#,
-      "\n",
+      "",
     );
 
     prefix_test(
@@ -6887,6 +7397,131 @@ mod tests {
         ..Browsers::default()
       },
     );
+
+    prefix_test(
+      r#"
+      @media (-webkit-min-device-pixel-ratio: 2), (min-resolution: 192dpi) {
+        .foo { color: yellow; }
+      }
+      "#,
+      indoc! { r#"
+        @media (min-resolution: 2x) {
+          .foo {
+            color: #ff0;
+          }
+        }
+      "#},
+      Browsers {
+        chrome: Some(100 << 16),
+        firefox: Some(100 << 16),
+        ..Browsers::default()
+      },
+    );
+    prefix_test(
+      r#"
+      @media (min-resolution: 2dppx) {
+        .foo { color: yellow; }
+      }
+      "#,
+      indoc! { r#"
+        @media (-webkit-min-device-pixel-ratio: 2), (min-resolution: 2dppx) {
+          .foo {
+            color: #ff0;
+          }
+        }
+      "#},
+      Browsers {
+        safari: Some(12 << 16),
+        ..Browsers::default()
+      },
+    );
+    prefix_test(
+      r#"
+      @media print and (max--moz-device-pixel-ratio: 1.5) {
+        .foo { color: yellow; }
+      }
+      "#,
+      indoc! { r#"
+        @media print and (-webkit-max-device-pixel-ratio: 1.5), print and (max-resolution: 1.5dppx) {
+          .foo {
+            color: #ff0;
+          }
+        }
+      "#},
+      Browsers {
+        safari: Some(12 << 16),
+        ..Browsers::default()
+      },
+    );
+    prefix_test(
+      r#"
+      @media (-webkit-min-device-pixel-ratio: 2) {
+        .foo { color: yellow; }
+      }
+      @media (min-resolution: 2dppx) {
+        .bar { color: red; }
+      }
+      "#,
+      indoc! { r#"
+        @media (min-resolution: 2x) {
+          .foo {
+            color: #ff0;
+          }
+
+          .bar {
+            color: red;
+          }
+        }
+      "#},
+      Browsers {
+        chrome: Some(100 << 16),
+        ..Browsers::default()
+      },
+    );
+    minify_test(
+      "@media (-webkit-min-device-pixel-ratio: 2) { .foo { color: yellow }}",
+      "@media (-webkit-min-device-pixel-ratio:2){.foo{color:#ff0}}",
+    );
+  }
+
+  #[test]
+  fn test_media_list_api() {
+    use crate::media_query::MediaList;
+    use cssparser::{Parser, ParserInput};
+
+    fn lower(source: &str, targets: Browsers) -> String {
+      let mut input = ParserInput::new(source);
+      let mut parser = Parser::new(&mut input);
+      let mut media = parser.parse_entirely(MediaList::parse).unwrap();
+      media.transform_resolution(targets);
+      media
+        .to_css_string(PrinterOptions {
+          minify: true,
+          targets: Some(targets),
+          ..PrinterOptions::default()
+        })
+        .unwrap()
+    }
+
+    let safari = Browsers {
+      safari: Some(12 << 16),
+      ..Browsers::default()
+    };
+    assert_eq!(
+      lower(
+        "screen and (width >= 500px), (-webkit-min-device-pixel-ratio: 2)",
+        safari
+      ),
+      "screen and (min-width:500px),(-webkit-min-device-pixel-ratio:2),(min-resolution:2dppx)"
+    );
+    let chrome = Browsers {
+      chrome: Some(100 << 16),
+      ..Browsers::default()
+    };
+    assert_eq!(
+      lower("(-webkit-min-device-pixel-ratio: 2), (min-resolution: 192dpi)", chrome),
+      "(min-resolution:2x)"
+    );
   }
 
   #[test]
@@ -7447,72 +8082,173 @@ mod tests {
         ..Browsers::default()
       },
     );
-  }
 
-  #[test]
-  fn test_merge_media_rules() {
-    test(
+    // Pseudo elements and attribute selectors are merged when supported by all targets.
+    let targets = Browsers {
+      chrome: Some(95 << 16),
+      firefox: Some(90 << 16),
+      safari: Some(14 << 16),
+      ..Browsers::default()
+    };
+    prefix_test(
       r#"
-      @media (hover) {
-        .foo {
-          color: red;
-        }
+      .foo::before {
+        color: red;
       }
-      @media (hover) {
-        .foo {
-          background: #fff;
-        }
-
-        .baz {
-          color: #fff;
-        }
+      .bar[data-foo="a b"] {
+        color: red;
       }
-      "#,
+      input[type="search" i]::placeholder {
+        color: red;
+      }
+    "#,
       indoc! {r#"
-      @media (hover) {
-        .foo {
-          color: red;
-          background: #fff;
-        }
-
-        .baz {
-          color: #fff;
-        }
+      .foo:before, .bar[data-foo="a b"], input[type="search" i]::placeholder {
+        color: red;
       }
     "#},
+      targets,
     );
-
-    test(
+    prefix_test(
       r#"
-      @media (hover) {
-        .foo {
-          color: red;
-        }
+      .foo::after {
+        content: "";
       }
-      @media (min-width: 250px) {
-        .foo {
-          background: #fff;
-        }
-
-        .baz {
-          color: #fff;
-        }
+      .bar {
+        color: red;
       }
-      "#,
+      .baz::after {
+        content: "";
+      }
+    "#,
       indoc! {r#"
-      @media (hover) {
-        .foo {
-          color: red;
-        }
+      .foo:after {
+        content: "";
       }
-      
-      @media (min-width: 250px) {
-        .foo {
-          background: #fff;
-        }
 
-        .baz {
-          color: #fff;
+      .bar {
+        color: red;
+      }
+
+      .baz:after {
+        content: "";
+      }
+    "#},
+      targets,
+    );
+    prefix_test(
+      r#"
+      .foo::-webkit-scrollbar {
+        color: red;
+      }
+      .bar {
+        color: red;
+      }
+    "#,
+      indoc! {r#"
+      .foo::-webkit-scrollbar {
+        color: red;
+      }
+
+      .bar {
+        color: red;
+      }
+    "#},
+      targets,
+    );
+
+    let mut stylesheet = StyleSheet::parse(
+      r#"
+      [data-a='1']::first-line { color: red }
+      [data-b~="x"]::first-letter { color: red }
+      .foo[data-c="a b"]::selection { color: red }
+    "#,
+      ParserOptions::default(),
+    )
+    .unwrap();
+    stylesheet
+      .minify(MinifyOptions {
+        targets: Some(targets),
+        ..MinifyOptions::default()
+      })
+      .unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        minify: true,
+        targets: Some(targets),
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(
+      res.code,
+      "[data-a=\"1\"]:first-line,[data-b~=x]:first-letter,.foo[data-c=a\\ b]::selection{color:red}"
+    );
+  }
+
+  #[test]
+  fn test_merge_media_rules() {
+    test(
+      r#"
+      @media (hover) {
+        .foo {
+          color: red;
+        }
+      }
+      @media (hover) {
+        .foo {
+          background: #fff;
+        }
+
+        .baz {
+          color: #fff;
+        }
+      }
+      "#,
+      indoc! {r#"
+      @media (hover) {
+        .foo {
+          color: red;
+          background: #fff;
+        }
+
+        .baz {
+          color: #fff;
+        }
+      }
+    "#},
+    );
+
+    test(
+      r#"
+      @media (hover) {
+        .foo {
+          color: red;
+        }
+      }
+      @media (min-width: 250px) {
+        .foo {
+          background: #fff;
+        }
+
+        .baz {
+          color: #fff;
+        }
+      }
+      "#,
+      indoc! {r#"
+      @media (hover) {
+        .foo {
+          color: red;
+        }
+      }
+      
+      @media (min-width: 250px) {
+        .foo {
+          background: #fff;
+        }
+
+        .baz {
+          color: #fff;
         }
       }
     "#},
@@ -7587,6 +8323,95 @@ mod tests {
       }
     "#},
     );
+
+    // Conditions that only differ in redundant parentheses and operand order are merged.
+    // The condition of the first rule is kept as written in non-minified output.
+    test(
+      r#"
+      @supports ((display: grid)) and ((display: flex)) {
+        .foo {
+          color: red;
+        }
+      }
+      @supports (display: flex) and (display: grid) {
+        .bar {
+          color: green;
+        }
+      }
+      "#,
+      indoc! {r#"
+      @supports ((display: grid)) and ((display: flex)) {
+        .foo {
+          color: red;
+        }
+
+        .bar {
+          color: green;
+        }
+      }
+    "#},
+    );
+
+    minify_test(
+      r#"
+      @supports ((display: grid)) and ((display: flex)) {
+        .foo {
+          color: red;
+        }
+      }
+      @supports (display: flex) and (display: grid) {
+        .bar {
+          color: green;
+        }
+      }
+      "#,
+      "@supports (display: flex) and (display: grid){.foo{color:red}.bar{color:green}}",
+    );
+    minify_test(
+      r#"
+      @supports (((a: b) or (c: d)) or (e: f)) and (g: h) {
+        .foo {
+          color: red;
+        }
+      }
+      @supports (g: h) and ((e: f) or (c: d) or (a: b) or (c: d)) {
+        .bar {
+          color: green;
+        }
+      }
+      "#,
+      "@supports ((a: b) or (c: d) or (e: f)) and (g: h){.foo{color:red}.bar{color:green}}",
+    );
+    minify_test(
+      r#"
+      @supports not ((display: grid)) {
+        .foo {
+          color: red;
+        }
+      }
+      @supports (not (display: grid)) {
+        .bar {
+          color: green;
+        }
+      }
+      "#,
+      "@supports not (display: grid){.foo{color:red}.bar{color:green}}",
+    );
+    minify_test(
+      r#"
+      @supports (display: grid) and (not (display: flex)) {
+        .foo {
+          color: red;
+        }
+      }
+      @supports (display: grid) or (not (display: flex)) {
+        .bar {
+          color: green;
+        }
+      }
+      "#,
+      "@supports (display: grid) and (not (display: flex)){.foo{color:red}}@supports (display: grid) or (not (display: flex)){.bar{color:green}}",
+    );
   }
 
   #[test]
@@ -8642,6 +9467,118 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_scroll_timeline() {
+    minify_test(".foo { animation-timeline: auto }", ".foo{animation-timeline:auto}");
+    minify_test(
+      ".foo { animation-timeline: --foo, NONE }",
+      ".foo{animation-timeline:--foo,none}",
+    );
+    minify_test(
+      ".foo { animation-timeline: scroll() }",
+      ".foo{animation-timeline:scroll()}",
+    );
+    minify_test(
+      ".foo { animation-timeline: scroll(nearest block), scroll(x root) }",
+      ".foo{animation-timeline:scroll(),scroll(root x)}",
+    );
+    minify_test(
+      ".foo { animation-timeline: view(block auto auto) }",
+      ".foo{animation-timeline:view()}",
+    );
+    minify_test(
+      ".foo { animation-timeline: view(10px 20% inline) }",
+      ".foo{animation-timeline:view(inline 10px 20%)}",
+    );
+    minify_test(".foo { animation-timeline: foo }", ".foo{animation-timeline:foo}");
+    minify_test(
+      ".foo { scroll-timeline: --foo block, --bar x }",
+      ".foo{scroll-timeline:--foo,--bar x}",
+    );
+    minify_test(
+      ".foo { scroll-timeline-name: --foo; scroll-timeline-axis: inline }",
+      ".foo{scroll-timeline-name:--foo;scroll-timeline-axis:inline}",
+    );
+    minify_test(
+      ".foo { view-timeline: --foo y 10px 10px }",
+      ".foo{view-timeline:--foo y 10px}",
+    );
+    minify_test(
+      ".foo { view-timeline-name: none; view-timeline-inset: auto 25% }",
+      ".foo{view-timeline-name:none;view-timeline-inset:auto 25%}",
+    );
+
+    // The animation shorthand resets animation-timeline, so it must not be printed after it.
+    minify_test(
+      ".foo { animation: bar 1s; animation-timeline: --foo }",
+      ".foo{animation:1s bar;animation-timeline:--foo}",
+    );
+    minify_test(
+      ".foo { animation-timeline: --foo; animation: bar 1s }",
+      ".foo{animation-timeline:--foo;animation:1s bar}",
+    );
+    minify_test(
+      r#"
+      .foo {
+        animation-timeline: --foo;
+        animation-name: bar;
+        animation-duration: 1s;
+        animation-timing-function: ease;
+        animation-iteration-count: 1;
+        animation-direction: normal;
+        animation-play-state: running;
+        animation-delay: 0s;
+        animation-fill-mode: none;
+      }
+    "#,
+      ".foo{animation:1s bar;animation-timeline:--foo}",
+    );
+
+    css_modules_test(
+      r#"
+      .foo {
+        scroll-timeline: --timeline;
+      }
+
+      .bar {
+        animation: fade 1s;
+        animation-timeline: --timeline, scroll();
+      }
+
+      @keyframes fade {
+        from { opacity: 0 }
+      }
+    "#,
+      indoc! {r#"
+      .EgL3uq_foo {
+        scroll-timeline: --EgL3uq_timeline;
+      }
+
+      .EgL3uq_bar {
+        animation: 1s EgL3uq_fade;
+        animation-timeline: --EgL3uq_timeline, scroll();
+      }
+
+      @keyframes EgL3uq_fade {
+        from {
+          opacity: 0;
+        }
+      }
+    "#},
+      map! {
+        "foo" => "EgL3uq_foo",
+        "bar" => "EgL3uq_bar",
+        "--timeline" => "--EgL3uq_timeline",
+        "fade" => "EgL3uq_fade" referenced: true
+      },
+      HashMap::new(),
+      crate::css_modules::Config {
+        dashed_idents: true,
+        ..Default::default()
+      },
+    );
+  }
+
   #[test]
   fn test_transform() {
     minify_test(
@@ -9894,9 +10831,52 @@ mod tests {
       "@font-face {unicode-range: u+????, U+1????, U+10????;}",
       "@font-face{unicode-range:U+????,U+1????,U+10????}",
     );
-  }
 
-  #[test]
+    minify_test(
+      r#"
+      @font-face { font-family: "Inter"; src: url(inter.woff2); }
+      @font-face { font-family: Inter; src: url(inter-bold.woff2); font-weight: bold }
+      @font-face { font-family: inter; src: url(inter.woff2); }
+    "#,
+      "@font-face{font-family:Inter;src:url(inter-bold.woff2);font-weight:700}@font-face{font-family:inter;src:url(inter.woff2)}",
+    );
+    minify_test(
+      r#"
+      @font-face { font-family: Inter; src: url(inter.woff2); }
+      @font-face { font-family: Inter; src: url(inter.woff2); font-weight: bold }
+    "#,
+      "@font-face{font-family:Inter;src:url(inter.woff2)}@font-face{font-family:Inter;src:url(inter.woff2);font-weight:700}",
+    );
+
+    fn font_face_warnings(source: &str) -> Vec<String> {
+      use std::sync::{Arc, RwLock};
+      let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+      StyleSheet::parse(
+        &source,
+        ParserOptions {
+          warnings: warnings.clone(),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let warnings = warnings.unwrap();
+      let warnings = warnings.read().unwrap();
+      warnings.iter().map(|w| w.kind.to_string()).collect()
+    }
+
+    assert!(font_face_warnings("@font-face { font-family: Inter; src: url(inter.woff2) }").is_empty());
+    assert!(font_face_warnings("@font-face { font-family: 'Serif Pro'; src: url(serif.woff2) }").is_empty());
+    assert_eq!(
+      font_face_warnings("@font-face { font-family: 'Serif'; src: url(serif.woff2) }"),
+      vec!["@font-face font-family \"Serif\" has the same name as a generic font family keyword"]
+    );
+    assert_eq!(
+      font_face_warnings("@font-face { font-family: monospace; src: url(mono.woff2) }"),
+      vec!["@font-face font-family monospace has the same name as a generic font family keyword"]
+    );
+  }
+
+  #[test]
   fn test_font_palette_values() {
     minify_test(
       r#"@font-palette-values --Cooler {
@@ -10133,6 +11113,134 @@ mod tests {
       }
     "#},
     );
+
+    minify_test(
+      r#"
+      @counter-style foo {
+        system: symbolic;
+        symbols: "*" "†";
+        negative: "-";
+        prefix: "";
+        suffix: ". ";
+        range: auto;
+        pad: 0 "";
+        fallback: decimal;
+        speak-as: auto;
+      }
+    "#,
+      "@counter-style foo{symbols:\"*\" \"†\"}",
+    );
+    minify_test(
+      "@counter-style foo { system: fixed 1; symbols: a b; system: fixed 3; suffix: \" \" }",
+      "@counter-style foo{symbols:a b;system:fixed 3;suffix:\" \"}",
+    );
+    minify_test(
+      "@counter-style foo { system: extends decimal; suffix: \". \"; speak-as: auto }",
+      "@counter-style foo{system:extends decimal;suffix:\". \";speak-as:auto}",
+    );
+    minify_test(
+      r#"
+      @counter-style foo {
+        system: additive;
+        additive-symbols: 10 X, 5 V, I 1;
+        range: 1 10, 20 infinite;
+        pad: "0" 3;
+        negative: "(" ")";
+        speak-as: spell-out;
+      }
+    "#,
+      "@counter-style foo{system:additive;additive-symbols:10 X,5 V,1 I;range:1 10,20 infinite;pad:3 \"0\";negative:\"(\" \")\";speak-as:spell-out}",
+    );
+    minify_test(
+      "@counter-style foo { system: numeric; symbols: url(a.png) \"1\"; fallback: bar; unknown: 1 2 }",
+      "@counter-style foo{system:numeric;symbols:url(a.png) \"1\";fallback:bar;unknown:1 2}",
+    );
+    minify_test(
+      ".foo { list-style: symbols(cyclic \"•\" \"◦\") inside }",
+      ".foo{list-style:symbols(cyclic \"•\" \"◦\") inside}",
+    );
+    minify_test(
+      ".foo { list-style-type: symbols(alphabetic \"a\" url(b.png)) }",
+      ".foo{list-style-type:symbols(alphabetic \"a\" url(b.png))}",
+    );
+
+    fn counter_style_warnings(source: &str) -> Vec<String> {
+      use std::sync::{Arc, RwLock};
+      let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+      StyleSheet::parse(
+        &source,
+        ParserOptions {
+          warnings: warnings.clone(),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let warnings = warnings.unwrap();
+      let warnings = warnings.read().unwrap();
+      warnings.iter().map(|w| w.kind.code().to_owned()).collect()
+    }
+
+    for source in [
+      "@counter-style foo { system: cyclic; symbols: a }",
+      "@counter-style foo { system: numeric; symbols: a b }",
+      "@counter-style foo { system: additive; additive-symbols: 1 I }",
+      "@counter-style foo { system: extends decimal; suffix: \")\" }",
+    ] {
+      assert!(counter_style_warnings(source).is_empty(), "{}", source);
+    }
+
+    for source in [
+      "@counter-style foo { suffix: \")\" }",
+      "@counter-style foo { system: alphabetic; symbols: a }",
+      "@counter-style foo { system: additive; symbols: a b }",
+      "@counter-style foo { system: additive; additive-symbols: 1 I, 5 V }",
+      "@counter-style foo { system: extends decimal; symbols: a }",
+    ] {
+      assert_eq!(
+        counter_style_warnings(source),
+        vec!["InvalidCounterStyle"],
+        "{}",
+        source
+      );
+    }
+
+    fn unused_counter_style_test(source: &str, expected: &str) {
+      let mut stylesheet = StyleSheet::parse(&source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          unused_symbols: vec!["foo", "bar"].iter().map(|s| String::from(*s)).collect(),
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    unused_counter_style_test(
+      "@counter-style foo { symbols: a } @counter-style bar { symbols: b } .x { list-style: bar }",
+      "@counter-style bar{symbols:b}.x{list-style:bar}",
+    );
+    unused_counter_style_test(
+      "@counter-style foo { symbols: a } @counter-style bar { symbols: b } .x::before { content: counter(x, foo) }",
+      "@counter-style foo{symbols:a}.x:before{content:counter(x,foo)}",
+    );
+    unused_counter_style_test(
+      "@counter-style foo { symbols: a } @counter-style bar { system: extends foo } .x { list-style-type: bar }",
+      "@counter-style foo{symbols:a}@counter-style bar{system:extends foo}.x{list-style-type:bar}",
+    );
+    unused_counter_style_test(
+      "@counter-style foo { symbols: a } @counter-style bar { symbols: b } .x { list-style-type: var(--style) }",
+      "@counter-style foo{symbols:a}@counter-style bar{symbols:b}.x{list-style-type:var(--style)}",
+    );
+    unused_counter_style_test(
+      "@counter-style foo { symbols: a } @counter-style bar { symbols: b } .x::before { content: counters(x, \".\", var(--style)) }",
+      "@counter-style foo{symbols:a}@counter-style bar{symbols:b}.x:before{content:counters(x,\".\",var(--style))}",
+    );
   }
 
   #[test]
@@ -13526,6 +14634,117 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_color_format() {
+    fn color_format_test(source: &str, expected: &str, color_format: ColorFormat) {
+      let stylesheet = StyleSheet::parse(&source, ParserOptions::default()).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          color_format: Some(color_format),
+          targets: Some(Browsers {
+            chrome: Some(111 << 16),
+            ..Browsers::default()
+          }),
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    let source = ".foo { color: red; background: rgb(0 0 255 / .5); border-color: hsl(120deg 100% 25%) }";
+    color_format_test(
+      source,
+      ".foo{color:#f00;background:#0000ff80;border-color:#008000}",
+      ColorFormat::Hex,
+    );
+    color_format_test(
+      source,
+      ".foo{color:rgb(255,0,0);background:rgba(0,0,255,.5);border-color:rgb(0,128,0)}",
+      ColorFormat::Rgb,
+    );
+    color_format_test(
+      source,
+      ".foo{color:hsl(0,100%,50%);background:hsla(240,100%,50%,.5);border-color:hsl(120,100%,25.098%)}",
+      ColorFormat::Hsl,
+    );
+    color_format_test(
+      ".foo { color: red; background: rgb(0 0 255 / .5) }",
+      ".foo{color:oklch(62.7955% .257683 29.2339);background:oklch(45.2014% .313214 264.052/.5)}",
+      ColorFormat::Oklch,
+    );
+
+    // currentColor and system colors are left as is, and wide gamut colors are only converted to oklch().
+    color_format_test(
+      ".foo { color: currentColor; background: Canvas; border-color: lab(50% 100 -50) }",
+      ".foo{color:currentColor;background:Canvas;border-color:lab(50% 100 -50)}",
+      ColorFormat::Rgb,
+    );
+    color_format_test(
+      ".foo { color: currentColor; border-color: lab(50% 100 -50) }",
+      ".foo{color:currentColor;border-color:oklch(61.4594% .336548 335.438)}",
+      ColorFormat::Oklch,
+    );
+  }
+
+  #[test]
+  fn test_quotes() {
+    fn quotes_test(source: &str, expected: &str, minify: bool, quotes: Option<QuoteStyle>) {
+      let stylesheet = StyleSheet::parse(&source, ParserOptions::default()).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify,
+          quotes,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    let source = r#".foo { background: url(a.png), url("a b.png"), url("a(1).png"); content: "a" "it's" 'say "hi"'; --x: "a" }"#;
+    quotes_test(
+      source,
+      r#".foo{background:url(a.png),url("a b.png"),url(a\(1\).png);content:"a" "it's" "say \"hi\"";--x:"a"}"#,
+      true,
+      None,
+    );
+    quotes_test(
+      source,
+      r#".foo{background:url("a.png"),url("a b.png"),url("a(1).png");content:"a" "it's" "say \"hi\"";--x:"a"}"#,
+      true,
+      Some(QuoteStyle::Double),
+    );
+    quotes_test(
+      source,
+      r#".foo{background:url('a.png'),url('a b.png'),url('a(1).png');content:'a' 'it\'s' 'say "hi"';--x:'a'}"#,
+      true,
+      Some(QuoteStyle::Single),
+    );
+    quotes_test(
+      source,
+      r#".foo{background:url(a.png),url("a b.png"),url("a(1).png");content:"a" "it's" 'say "hi"';--x:"a"}"#,
+      true,
+      Some(QuoteStyle::Minimal),
+    );
+    quotes_test(
+      r#".foo[title="it's"], .bar[title='say "hi"'] { background: url("a.png"); content: '\\' }"#,
+      indoc! {r#"
+        .foo[title="it's"], .bar[title='say "hi"'] {
+          background: url(a.png);
+          content: "\\";
+        }
+      "#},
+      false,
+      Some(QuoteStyle::Minimal),
+    );
+    quotes_test(
+      "@import 'a.css'; @font-face { font-family: 'A B'; src: url(a.woff2) format('woff2') }",
+      "@import 'a.css';@font-face{font-family:A B;src:url('a.woff2')format('woff2')}",
+      true,
+      Some(QuoteStyle::Single),
+    );
+  }
+
   #[test]
   fn test_relative_color() {
     fn test(input: &str, output: &str) {
@@ -18968,6 +20187,162 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_nesting_spec() {
+    fn nesting_spec_test(source: &str, expected: &str, targets: Option<Browsers>) {
+      let mut stylesheet = StyleSheet::parse(
+        &source,
+        ParserOptions {
+          nesting: true,
+          nesting_spec: NestingSpec::Current,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          targets,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          targets,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    let targets = Some(Browsers {
+      chrome: Some(95 << 16),
+      ..Browsers::default()
+    });
+
+    nesting_spec_test(
+      r#"
+        .foo {
+          color: blue;
+          .bar {
+            color: red;
+          }
+        }
+      "#,
+      indoc! {r#"
+        .foo {
+          color: #00f;
+        }
+
+        .foo .bar {
+          color: red;
+        }
+      "#},
+      targets,
+    );
+
+    nesting_spec_test(
+      r#"
+        .foo {
+          color: blue;
+          > .bar, + .baz, ~ .qux, div {
+            color: red;
+          }
+        }
+      "#,
+      indoc! {r#"
+        .foo {
+          color: #00f;
+        }
+
+        .foo > .bar, .foo + .baz, .foo ~ .qux, .foo div {
+          color: red;
+        }
+      "#},
+      targets,
+    );
+
+    nesting_spec_test(
+      r#"
+        .foo {
+          color: blue;
+          &.bar, .baz &, :is(&) .qux {
+            color: red;
+          }
+        }
+      "#,
+      indoc! {r#"
+        .foo {
+          color: #00f;
+        }
+
+        .foo.bar, .baz .foo, :is(.foo) .qux {
+          color: red;
+        }
+      "#},
+      targets,
+    );
+
+    nesting_spec_test(
+      r#"
+        .foo {
+          color: blue;
+          .bar {
+            color: red;
+          }
+        }
+      "#,
+      indoc! {r#"
+        .foo {
+          color: #00f;
+
+          & .bar {
+            color: red;
+          }
+        }
+      "#},
+      None,
+    );
+
+    nesting_test(
+      r#"
+        .foo {
+          color: blue;
+          & .bar {
+            color: red;
+          }
+        }
+      "#,
+      indoc! {r#"
+        .foo {
+          color: #00f;
+        }
+
+        .foo .bar {
+          color: red;
+        }
+      "#},
+    );
+
+    let res = StyleSheet::parse(
+      ".foo { color: blue; .bar { color: red; } }",
+      ParserOptions {
+        nesting: true,
+        ..ParserOptions::default()
+      },
+    );
+    assert!(res.is_err());
+
+    let res = StyleSheet::parse(
+      ".foo { color: blue; @nest .bar & { color: red; } }",
+      ParserOptions {
+        nesting: true,
+        nesting_spec: NestingSpec::Current,
+        ..ParserOptions::default()
+      },
+    );
+    assert!(res.is_err());
+  }
+
   #[test]
   fn test_css_modules() {
     css_modules_test(
@@ -19062,12 +20437,90 @@ mod tests {
       Default::default(),
     );
 
-    #[cfg(feature = "grid")]
     css_modules_test(
       r#"
-      body {
-        grid: [header-top] "a a a" [header-bottom]
-              [main-top] "b b b" 1fr [main-bottom]
+      @keyframes fade {
+        from { opacity: 0 }
+        to { opacity: 1 }
+      }
+
+      @media (prefers-reduced-motion) {
+        @keyframes fade {
+          from { opacity: 1 }
+          to { opacity: 1 }
+        }
+      }
+
+      .foo {
+        animation: fade 1s;
+      }
+    "#,
+      indoc! {r#"
+      @keyframes EgL3uq_fade {
+        from {
+          opacity: 0;
+        }
+
+        to {
+          opacity: 1;
+        }
+      }
+
+      @media (prefers-reduced-motion) {
+        @keyframes EgL3uq_fade {
+          from {
+            opacity: 1;
+          }
+
+          to {
+            opacity: 1;
+          }
+        }
+      }
+
+      .EgL3uq_foo {
+        animation: 1s EgL3uq_fade;
+      }
+    "#},
+      map! {
+        "fade" => "EgL3uq_fade" referenced: true,
+        "foo" => "EgL3uq_foo"
+      },
+      HashMap::new(),
+      Default::default(),
+    );
+
+    // Keyframes names are also returned separately from other exports.
+    let mut stylesheet = StyleSheet::parse(
+      r#"
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      @-webkit-keyframes spin { from { transform: rotate(0) } to { transform: rotate(360deg) } }
+      @keyframes "none" { from { opacity: 0 } to { opacity: 1 } }
+      .foo { animation: fade 1s }
+    "#,
+      ParserOptions {
+        filename: "test.css".into(),
+        css_modules: Some(Default::default()),
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    stylesheet.minify(MinifyOptions::default()).unwrap();
+    let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
+    assert_eq!(
+      res.animations.unwrap(),
+      HashMap::from([
+        ("fade".to_owned(), "EgL3uq_fade".to_owned()),
+        ("spin".to_owned(), "EgL3uq_spin".to_owned())
+      ])
+    );
+
+    #[cfg(feature = "grid")]
+    css_modules_test(
+      r#"
+      body {
+        grid: [header-top] "a a a" [header-bottom]
+              [main-top] "b b b" 1fr [main-bottom]
               / auto 1fr auto;
       }
 
@@ -19419,6 +20872,43 @@ mod tests {
       },
     );
 
+    css_modules_test(
+      r#"
+      .foo {
+        color: red;
+      }
+
+      .bar {
+        composes: foo;
+        --baz: 2px;
+        width: var(--baz);
+      }
+    "#,
+      indoc! {r#"
+      .custom-test-foo {
+        color: red;
+      }
+
+      .custom-test-bar {
+        --custom-test-baz: 2px;
+        width: var(--custom-test-baz);
+      }
+    "#},
+      map! {
+        "foo" => "custom-test-foo",
+        "bar" => "custom-test-bar" "custom-test-foo",
+        "--baz" => "--custom-test-baz" referenced: true
+      },
+      HashMap::new(),
+      crate::css_modules::Config {
+        dashed_idents: true,
+        generate_name: Some(crate::css_modules::NameGenerator::new(|local, filename, _hash| {
+          format!("custom-{}-{}", filename.trim_end_matches(".css"), local)
+        })),
+        ..Default::default()
+      },
+    );
+
     let stylesheet = StyleSheet::parse(
       r#"
         .grid {
@@ -19550,6 +21040,209 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_css_modules_hash_version() {
+    fn hash_name(filename: &str, hash_version: crate::css_modules::HashVersion) -> String {
+      let stylesheet = StyleSheet::parse(
+        ".foo { color: red; --bar: 2px; width: var(--bar) var(--baz from \"./b.css\") }",
+        ParserOptions {
+          filename: filename.into(),
+          css_modules: Some(crate::css_modules::Config {
+            dashed_idents: true,
+            hash_version,
+            ..Default::default()
+          }),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      res.code
+    }
+
+    use crate::css_modules::HashVersion;
+    assert_eq!(
+      hash_name("test.css", HashVersion::V1),
+      ".EgL3uq_foo{color:red;--EgL3uq_bar:2px;width:var(--EgL3uq_bar)var(--xhiKva)}"
+    );
+    assert_eq!(
+      hash_name("test.css", HashVersion::V2),
+      ".rbOo1W_foo{color:red;--rbOo1W_bar:2px;width:var(--rbOo1W_bar)var(--KSBkrW)}"
+    );
+
+    // Path separators are normalized, so Windows and Unix paths produce the same hashes.
+    assert_eq!(
+      hash_name("src/components/button.css", HashVersion::V2),
+      ".UKOcXG_foo{color:red;--UKOcXG_bar:2px;width:var(--UKOcXG_bar)var(--N2xwMG)}"
+    );
+    assert_eq!(
+      hash_name("src\\components\\button.css", HashVersion::V2),
+      ".UKOcXG_foo{color:red;--UKOcXG_bar:2px;width:var(--UKOcXG_bar)var(--N2xwMG)}"
+    );
+
+    // Hashes starting with a digit are prefixed with an underscore.
+    assert_eq!(
+      hash_name("app.css", HashVersion::V2),
+      "._2w2x9a_foo{color:red;--_2w2x9a_bar:2px;width:var(--_2w2x9a_bar)var(--7esF7a)}"
+    );
+  }
+
+  #[test]
+  fn test_css_modules_exports_diff() {
+    use crate::css_modules::{diff_exports, exports_hash};
+
+    fn exports(source: &str) -> CssModuleExports {
+      let stylesheet = StyleSheet::parse(
+        source,
+        ParserOptions {
+          filename: "test.css".into(),
+          css_modules: Some(Default::default()),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet.to_css(PrinterOptions::default()).unwrap().exports.unwrap()
+    }
+
+    let before = exports(".foo { color: red } .bar { composes: foo; } .baz { color: green }");
+
+    // Changes to rule bodies do not affect the exports.
+    let after = exports(".foo { color: blue } .bar { composes: foo; color: red } .baz { color: green }");
+    let diff = diff_exports(&before, &after);
+    assert!(diff.is_empty());
+    assert_eq!(exports_hash(&before), exports_hash(&after));
+
+    // Neither does whether a class is referenced in the file.
+    let after =
+      exports(".foo { color: red } .bar { composes: foo; } .baz { color: green } .foo .baz { color: red }");
+    assert!(diff_exports(&before, &after).is_empty());
+    assert_eq!(exports_hash(&before), exports_hash(&after));
+
+    let after = exports(".foo { color: red } .bar { composes: foo baz; } .qux { color: green }");
+    let diff = diff_exports(&before, &after);
+    assert_eq!(diff.added, vec!["qux".to_string()]);
+    assert_eq!(diff.removed, vec!["baz".to_string()]);
+    assert_eq!(diff.changed, vec!["bar".to_string()]);
+    assert_ne!(exports_hash(&before), exports_hash(&after));
+
+    let after = exports(".foo { color: red } .bar { composes: foo from global; } .baz { color: green }");
+    assert_eq!(diff_exports(&before, &after).changed, vec!["bar".to_string()]);
+    assert_ne!(exports_hash(&before), exports_hash(&after));
+  }
+
+  #[test]
+  fn test_css_modules_specificity() {
+    fn specificity_test(source: &str, expected: Vec<(&str, Option<[u32; 3]>)>) {
+      let stylesheet = StyleSheet::parse(
+        source,
+        ParserOptions {
+          filename: "test.css".into(),
+          css_modules: Some(crate::css_modules::Config {
+            specificity: true,
+            ..Default::default()
+          }),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
+      let exports = res.exports.unwrap();
+      for (name, specificity) in expected {
+        assert_eq!(exports[name].specificity, specificity, "{}", name);
+      }
+    }
+
+    specificity_test(
+      r#"
+      .btn { color: red }
+      #app .btn:hover { color: blue }
+      .card > .title { font-weight: bold }
+      .title:not(.active) { color: gray }
+      :where(.muted) { color: gray }
+      @media (min-width: 100px) { div.card { padding: 0 } }
+      @keyframes fade { from { opacity: 0 } }
+    "#,
+      vec![
+        ("btn", Some([1, 2, 0])),
+        ("card", Some([0, 2, 0])),
+        ("title", Some([0, 2, 0])),
+        ("active", Some([0, 2, 0])),
+        ("muted", Some([0, 0, 0])),
+        ("fade", None),
+      ],
+    );
+
+    let stylesheet = StyleSheet::parse(
+      ".foo { color: red }",
+      ParserOptions {
+        filename: "test.css".into(),
+        css_modules: Some(Default::default()),
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
+    assert_eq!(res.exports.unwrap()["foo"].specificity, None);
+  }
+
+  #[test]
+  fn test_css_modules_export_selectors() {
+    let stylesheet = StyleSheet::parse(
+      r#"
+      .btn { color: red }
+      .btn:hover, .card .btn { color: blue }
+      .btn:hover { color: green }
+      .title:not(.active) { color: gray }
+      .card { & .title { font-weight: bold } }
+    "#,
+      ParserOptions {
+        filename: "test.css".into(),
+        css_modules: Some(crate::css_modules::Config {
+          export_selectors: true,
+          ..Default::default()
+        }),
+        nesting: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        minify: true,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    let exports = res.exports.unwrap();
+    let selectors = |name: &str| exports[name].selectors.clone().unwrap();
+    assert_eq!(
+      selectors("btn"),
+      vec![".EgL3uq_btn", ".EgL3uq_btn:hover", ".EgL3uq_card .EgL3uq_btn"]
+    );
+    assert_eq!(selectors("card"), vec![".EgL3uq_card .EgL3uq_btn", ".EgL3uq_card"]);
+    assert_eq!(
+      selectors("title"),
+      vec![".EgL3uq_title:not(.EgL3uq_active)", "& .EgL3uq_title"]
+    );
+    assert_eq!(selectors("active"), vec![".EgL3uq_title:not(.EgL3uq_active)"]);
+
+    let stylesheet = StyleSheet::parse(
+      ".foo { color: red }",
+      ParserOptions {
+        filename: "test.css".into(),
+        css_modules: Some(Default::default()),
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
+    assert_eq!(res.exports.unwrap()["foo"].selectors, None);
+  }
+
   #[test]
   fn test_pseudo_replacement() {
     let source = r#"
@@ -19850,52 +21543,270 @@ mod tests {
   }
 
   #[test]
-  fn test_svg() {
-    minify_test(".foo { fill: yellow; }", ".foo{fill:#ff0}");
-    minify_test(".foo { fill: url(#foo); }", ".foo{fill:url(#foo)}");
-    minify_test(".foo { fill: url(#foo) none; }", ".foo{fill:url(#foo) none}");
-    minify_test(".foo { fill: url(#foo) yellow; }", ".foo{fill:url(#foo) #ff0}");
-    minify_test(".foo { fill: none; }", ".foo{fill:none}");
-    minify_test(".foo { fill: context-fill; }", ".foo{fill:context-fill}");
-    minify_test(".foo { fill: context-stroke; }", ".foo{fill:context-stroke}");
-
-    minify_test(".foo { stroke: yellow; }", ".foo{stroke:#ff0}");
-    minify_test(".foo { stroke: url(#foo); }", ".foo{stroke:url(#foo)}");
-    minify_test(".foo { stroke: url(#foo) none; }", ".foo{stroke:url(#foo) none}");
-    minify_test(".foo { stroke: url(#foo) yellow; }", ".foo{stroke:url(#foo) #ff0}");
-    minify_test(".foo { stroke: none; }", ".foo{stroke:none}");
-    minify_test(".foo { stroke: context-fill; }", ".foo{stroke:context-fill}");
-    minify_test(".foo { stroke: context-stroke; }", ".foo{stroke:context-stroke}");
+  fn test_unused_symbols_composes() {
+    use std::sync::{Arc, RwLock};
 
-    minify_test(".foo { marker-start: url(#foo); }", ".foo{marker-start:url(#foo)}");
+    fn unused_test(source: &str, unused: &[&str], strict: bool, expected: &str, expected_warnings: &[&str]) {
+      let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+      let mut stylesheet = StyleSheet::parse(
+        &source,
+        ParserOptions {
+          filename: "test.css".into(),
+          css_modules: Some(crate::css_modules::Config {
+            pattern: crate::css_modules::Pattern::parse("[local]").unwrap(),
+            ..Default::default()
+          }),
+          warnings: warnings.clone(),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          unused_symbols: unused.iter().map(|s| s.to_string()).collect(),
+          strict_unused_symbols: strict,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+      let warnings: Vec<String> = warnings.unwrap().read().unwrap().iter().map(|w| w.kind.to_string()).collect();
+      assert_eq!(warnings, expected_warnings);
+    }
 
-    minify_test(".foo { stroke-dasharray: 4 1 2; }", ".foo{stroke-dasharray:4 1 2}");
-    minify_test(".foo { stroke-dasharray: 4,1,2; }", ".foo{stroke-dasharray:4 1 2}");
-    minify_test(".foo { stroke-dasharray: 4, 1, 2; }", ".foo{stroke-dasharray:4 1 2}");
-    minify_test(
-      ".foo { stroke-dasharray: 4px, 1px, 2px; }",
-      ".foo{stroke-dasharray:4 1 2}",
-    );
+    let source = r#"
+      .a { composes: b; color: red }
+      .b { composes: c; color: green }
+      .c { color: blue }
+      .d { color: yellow }
+    "#;
 
-    minify_test(".foo { mask: url('foo.svg'); }", ".foo{mask:url(foo.svg)}");
-    minify_test(
-      ".foo { mask: url(masks.svg#star) luminance }",
-      ".foo{mask:url(masks.svg#star) luminance}",
+    // Composed classes are kept, including through chains of composes.
+    unused_test(
+      source,
+      &["c"],
+      false,
+      ".a{color:red;}.b{color:green;}.c{color:#00f}.d{color:#ff0}",
+      &[],
     );
-    minify_test(
-      ".foo { mask: url(masks.svg#star) 40px 20px }",
-      ".foo{mask:url(masks.svg#star) 40px 20px}",
+    unused_test(
+      source,
+      &["b", "c", "d"],
+      false,
+      ".a{color:red;}.b{color:green;}.c{color:#00f}",
+      &[],
     );
-    minify_test(
-      ".foo { mask: url(masks.svg#star) 0 0 / 50px 50px }",
-      ".foo{mask:url(masks.svg#star) 0 0/50px 50px}",
+
+    // Classes are removed if the composing class is also unused.
+    unused_test(source, &["a", "b"], false, ".c{color:#00f}.d{color:#ff0}", &[]);
+
+    // In strict mode, references to unused classes are removed with a warning.
+    unused_test(
+      source,
+      &["c"],
+      true,
+      ".a{color:red;}.b{color:green}.d{color:#ff0}",
+      &["Removed composes reference to unused class c"],
     );
-    minify_test(
-      ".foo { mask: url(masks.svg#star) repeat-x }",
-      ".foo{mask:url(masks.svg#star) repeat-x}",
+    unused_test(
+      source,
+      &["b", "c"],
+      true,
+      ".a{color:red}.d{color:#ff0}",
+      &["Removed composes reference to unused class b"],
     );
-    minify_test(
-      ".foo { mask: url(masks.svg#star) stroke-box }",
+  }
+
+  #[test]
+  fn test_removed_symbols() {
+    fn removed_test(
+      source: &str,
+      unused: Vec<&str>,
+      patterns: Vec<&str>,
+      removed: Vec<&str>,
+      unmatched: Vec<&str>,
+    ) {
+      let mut stylesheet = StyleSheet::parse(&source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          unused_symbols: unused.into_iter().map(String::from).collect(),
+          unused_symbol_patterns: patterns.into_iter().map(String::from).collect(),
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
+      assert_eq!(res.removed_symbols, removed);
+      assert_eq!(res.unmatched_symbols, unmatched);
+    }
+
+    let source = r#"
+      .foo { color: red }
+      .bar, .baz { color: green }
+      :is(.qux, .x) .y { color: blue }
+      .a { --unused: red }
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      @counter-style dots { system: cyclic; symbols: "."; }
+      @property --size { syntax: "<length>"; inherits: false; initial-value: 0px }
+    "#;
+
+    removed_test(
+      source,
+      vec!["foo", "bar", "qux", "missing"],
+      vec![],
+      vec!["foo"],
+      vec!["bar", "missing", "qux"],
+    );
+    removed_test(
+      source,
+      vec!["bar", "baz", "fade", "dots", "--size", "--unused"],
+      vec![],
+      vec!["--size", "--unused", "bar", "baz", "dots", "fade"],
+      vec![],
+    );
+    removed_test(
+      source,
+      vec!["baz"],
+      vec!["b*", "q*", "f*", "z*"],
+      vec!["b*", "baz", "f*"],
+      vec!["q*", "z*"],
+    );
+    removed_test(
+      ".foo { color: red } .bar { color: green } .foo.bar .baz { color: blue }",
+      vec!["foo"],
+      vec![],
+      vec!["foo"],
+      vec![],
+    );
+    removed_test(".foo { color: red }", vec![], vec![], vec![], vec![]);
+  }
+
+  #[test]
+  fn test_unused_symbol_patterns() {
+    fn unused_test(source: &str, unused: Vec<&str>, patterns: Vec<&str>, expected: &str) {
+      let mut stylesheet = StyleSheet::parse(&source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          unused_symbols: unused.into_iter().map(String::from).collect(),
+          unused_symbol_patterns: patterns.into_iter().map(String::from).collect(),
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    let source = r#"
+      .legacy-button { color: red }
+      .legacy-link, #legacy-id { color: green }
+      .button { color: blue }
+      .legacy-button.active { color: yellow }
+      :is(.legacy-a, .legacy-b) .x { color: purple }
+      :is(.legacy-a, .b) .x { color: orange }
+      .a:not(.legacy-a) { color: pink }
+      @keyframes legacy-fade { from { opacity: 0 } to { opacity: 1 } }
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+    "#;
+
+    unused_test(
+      source,
+      vec![],
+      vec!["legacy-*"],
+      ".button{color:#00f}:is(.legacy-a,.b) .x{color:orange}.a:not(.legacy-a){color:pink}@keyframes fade{0%{opacity:0}to{opacity:1}}",
+    );
+
+    unused_test(
+      source,
+      vec!["legacy-button", "legacy-a", "legacy-b"],
+      vec![],
+      ".legacy-link,#legacy-id{color:green}.button{color:#00f}:is(.legacy-a,.b) .x{color:orange}.a:not(.legacy-a){color:pink}@keyframes legacy-fade{0%{opacity:0}to{opacity:1}}@keyframes fade{0%{opacity:0}to{opacity:1}}",
+    );
+
+    unused_test(
+      ".foo-old { color: red } .foo-new { color: green } .a-x-b { color: blue } .a-b { color: pink } .ab-c { color: yellow }",
+      vec![],
+      vec!["*-old", "a*b"],
+      ".foo-new{color:green}.ab-c{color:#ff0}",
+    );
+
+    // Names in `unused_symbols` are matched exactly, even if they contain `*`.
+    unused_test(
+      ".legacy-a { color: red } .b { color: green }",
+      vec!["legacy-*", "b"],
+      vec![],
+      ".legacy-a{color:red}",
+    );
+
+    let mut unused = UnusedSymbols::new(
+      vec!["foo".to_owned()].into_iter().collect(),
+      vec!["legacy-*".to_owned()],
+    );
+    assert!(unused.contains("legacy-a"));
+    assert!(unused.contains("foo"));
+    assert!(!unused.contains("foo-bar"));
+    unused.remove("legacy-a");
+    unused.remove("foo");
+    assert!(!unused.contains("legacy-a"));
+    assert!(!unused.contains("foo"));
+    assert!(unused.contains("legacy-b"));
+  }
+
+  #[test]
+  fn test_svg() {
+    minify_test(".foo { fill: yellow; }", ".foo{fill:#ff0}");
+    minify_test(".foo { fill: url(#foo); }", ".foo{fill:url(#foo)}");
+    minify_test(".foo { fill: url(#foo) none; }", ".foo{fill:url(#foo) none}");
+    minify_test(".foo { fill: url(#foo) yellow; }", ".foo{fill:url(#foo) #ff0}");
+    minify_test(".foo { fill: none; }", ".foo{fill:none}");
+    minify_test(".foo { fill: context-fill; }", ".foo{fill:context-fill}");
+    minify_test(".foo { fill: context-stroke; }", ".foo{fill:context-stroke}");
+
+    minify_test(".foo { stroke: yellow; }", ".foo{stroke:#ff0}");
+    minify_test(".foo { stroke: url(#foo); }", ".foo{stroke:url(#foo)}");
+    minify_test(".foo { stroke: url(#foo) none; }", ".foo{stroke:url(#foo) none}");
+    minify_test(".foo { stroke: url(#foo) yellow; }", ".foo{stroke:url(#foo) #ff0}");
+    minify_test(".foo { stroke: none; }", ".foo{stroke:none}");
+    minify_test(".foo { stroke: context-fill; }", ".foo{stroke:context-fill}");
+    minify_test(".foo { stroke: context-stroke; }", ".foo{stroke:context-stroke}");
+
+    minify_test(".foo { marker-start: url(#foo); }", ".foo{marker-start:url(#foo)}");
+
+    minify_test(".foo { stroke-dasharray: 4 1 2; }", ".foo{stroke-dasharray:4 1 2}");
+    minify_test(".foo { stroke-dasharray: 4,1,2; }", ".foo{stroke-dasharray:4 1 2}");
+    minify_test(".foo { stroke-dasharray: 4, 1, 2; }", ".foo{stroke-dasharray:4 1 2}");
+    minify_test(
+      ".foo { stroke-dasharray: 4px, 1px, 2px; }",
+      ".foo{stroke-dasharray:4 1 2}",
+    );
+
+    minify_test(".foo { mask: url('foo.svg'); }", ".foo{mask:url(foo.svg)}");
+    minify_test(
+      ".foo { mask: url(masks.svg#star) luminance }",
+      ".foo{mask:url(masks.svg#star) luminance}",
+    );
+    minify_test(
+      ".foo { mask: url(masks.svg#star) 40px 20px }",
+      ".foo{mask:url(masks.svg#star) 40px 20px}",
+    );
+    minify_test(
+      ".foo { mask: url(masks.svg#star) 0 0 / 50px 50px }",
+      ".foo{mask:url(masks.svg#star) 0 0/50px 50px}",
+    );
+    minify_test(
+      ".foo { mask: url(masks.svg#star) repeat-x }",
+      ".foo{mask:url(masks.svg#star) repeat-x}",
+    );
+    minify_test(
+      ".foo { mask: url(masks.svg#star) stroke-box }",
       ".foo{mask:url(masks.svg#star) stroke-box}",
     );
     minify_test(
@@ -21160,6 +23071,76 @@ mod tests {
       "#},
     );
 
+    custom_media_test(
+      r#"
+      @custom-media --a (min-width: 1px);
+      @custom-media --b ((--a) and (max-width: 2px));
+      @custom-media --c (--b) or (color);
+
+      @media (--c) {
+        .a {
+          color: green;
+        }
+      }
+      "#,
+      indoc! {r#"
+      @media (((min-width: 1px) and (max-width: 2px)) or (color)) {
+        .a {
+          color: green;
+        }
+      }
+      "#},
+    );
+
+    custom_media_test(
+      r#"
+      @custom-media --a (color);
+      @custom-media --a (hover);
+
+      @media (--a) {
+        .a {
+          color: green;
+        }
+      }
+      "#,
+      indoc! {r#"
+      @media (hover) {
+        .a {
+          color: green;
+        }
+      }
+      "#},
+    );
+
+    custom_media_test(
+      r#"
+      @custom-media --a (min-width: 1px);
+
+      @media (--a) {
+        .a {
+          color: green;
+        }
+      }
+
+      @media (min-width: 1px) {
+        .b {
+          color: red;
+        }
+      }
+      "#,
+      indoc! {r#"
+      @media (min-width: 1px) {
+        .a {
+          color: green;
+        }
+
+        .b {
+          color: red;
+        }
+      }
+      "#},
+    );
+
     fn custom_media_error_test(source: &str, err: Error<MinifyErrorKind>) {
       let mut stylesheet = StyleSheet::parse(
         &source,
@@ -21388,6 +23369,11 @@ mod tests {
       Error {
         kind: MinifyErrorKind::CircularCustomMedia {
           name: "--circular-mq-a".into(),
+          cycle: vec![
+            "--circular-mq-a".into(),
+            "--circular-mq-b".into(),
+            "--circular-mq-a".into(),
+          ],
         },
         loc: Some(ErrorLocation {
           filename: "test.css".into(),
@@ -21396,6 +23382,31 @@ mod tests {
         }),
       },
     );
+
+    custom_media_error_test(
+      r#"
+      @custom-media --a (--b) and (color);
+      @custom-media --b (hover) or (--c);
+      @custom-media --c (--b);
+
+      @media (--a) {
+        body {
+          order: 3;
+        }
+      }
+      "#,
+      Error {
+        kind: MinifyErrorKind::CircularCustomMedia {
+          name: "--b".into(),
+          cycle: vec!["--b".into(), "--c".into(), "--b".into()],
+        },
+        loc: Some(ErrorLocation {
+          filename: "test.css".into(),
+          line: 5,
+          column: 7,
+        }),
+      },
+    );
   }
 
   #[test]
@@ -21522,6 +23533,123 @@ mod tests {
       "@import \"hHsogW\";.foo{color:red}",
       vec![("test.css", "hHsogW")],
     );
+
+    fn import_conditions(source: &str) -> (Option<String>, Option<String>) {
+      let stylesheet = StyleSheet::parse(&source, ParserOptions::default()).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          analyze_dependencies: Some(Default::default()),
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      match res.dependencies.unwrap().remove(0) {
+        Dependency::Import(dep) => (dep.supports, dep.media),
+        _ => unreachable!(),
+      }
+    }
+
+    assert_eq!(import_conditions("@import \"a.css\";"), (None, None));
+    assert_eq!(
+      import_conditions("@import \"a.css\" layer(x) supports(display: grid) screen and (min-width: 100px);"),
+      (
+        Some("(display: grid)".into()),
+        Some("screen and (min-width: 100px)".into())
+      )
+    );
+    assert_eq!(
+      import_conditions("@import url(a.css) supports(not (display: grid)) (width >= 500px), print;"),
+      (
+        Some("not (display: grid)".into()),
+        Some("(width >= 500px), print".into())
+      )
+    );
+    assert_eq!(
+      import_conditions("@import \"a.css\" supports(selector(a > b));"),
+      (Some("selector(a > b)".into()), None)
+    );
+
+    fn url_formats(source: &str) -> Vec<(String, Option<String>)> {
+      let stylesheet = StyleSheet::parse(&source, ParserOptions::default()).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          analyze_dependencies: Some(Default::default()),
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      res
+        .dependencies
+        .unwrap()
+        .into_iter()
+        .map(|dep| match dep {
+          Dependency::Url(dep) => (dep.url, dep.format),
+          _ => unreachable!(),
+        })
+        .collect()
+    }
+
+    assert_eq!(
+      url_formats(
+        "@font-face { font-family: Test; src: url(a.woff2) format(\"woff2\"), url(b.ttf) format(truetype), url(c.otf) }"
+      ),
+      vec![
+        ("a.woff2".into(), Some("woff2".into())),
+        ("b.ttf".into(), Some("truetype".into())),
+        ("c.otf".into(), None)
+      ]
+    );
+    assert_eq!(
+      url_formats(".foo { background: url(a.png) }"),
+      vec![("a.png".into(), None)]
+    );
+  }
+
+  #[test]
+  fn test_selector_api() {
+    use crate::rules::style::*;
+
+    let selectors =
+      parse_selectors("ul > li.item:hover::before, #a .b[href] ~ &", &ParserOptions::default()).unwrap();
+    let components = selector_components(&selectors.0[0]).unwrap();
+    assert_eq!(
+      components.iter().map(|c| (c.kind, c.value.as_str())).collect::<Vec<_>>(),
+      vec![
+        (SelectorComponentKind::Type, "ul"),
+        (SelectorComponentKind::Combinator, "child"),
+        (SelectorComponentKind::Type, "li"),
+        (SelectorComponentKind::Class, ".item"),
+        (SelectorComponentKind::PseudoClass, ":hover"),
+        (SelectorComponentKind::PseudoElement, ":before"),
+      ]
+    );
+    assert_eq!(specificity(&selectors.0[0]), (0, 2, 3));
+    assert_eq!(selector_from_components(&components), "ul > li.item:hover:before");
+
+    let components = selector_components(&selectors.0[1]).unwrap();
+    assert_eq!(selector_from_components(&components), "#a .b[href] ~ &");
+    assert_eq!(specificity(&selectors.0[1]), (1, 2, 0));
+    assert_eq!(
+      selectors_to_css_string(
+        &selectors,
+        PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        }
+      )
+      .unwrap(),
+      "ul>li.item:hover:before,#a .b[href]~&"
+    );
+
+    assert_eq!(
+      parse_selectors(".foo > > .bar", &ParserOptions::default()),
+      Err(Error {
+        kind: ParserError::SelectorError(SelectorError::DanglingCombinator),
+        loc: Some(ErrorLocation {
+          filename: "".into(),
+          line: 0,
+          column: 8
+        })
+      })
+    );
   }
 
   #[test]
@@ -21546,6 +23674,38 @@ mod tests {
     }"#}
     );
 
+    let code = ".a { color: red }\n  .b {\n    color: yellow;\n  }\n.c { color: blue } .d {}";
+    let start = code.find(".b").unwrap();
+    let end = code.find(".c").unwrap();
+    let rule = CssRule::parse_range(code, start, end, ParserOptions::default()).unwrap();
+    assert_eq!(
+      rule
+        .to_css_string(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap(),
+      ".b{color:#ff0}"
+    );
+    match rule {
+      CssRule::Style(s) => assert_eq!(
+        s.loc,
+        Location {
+          source_index: 0,
+          line: 1,
+          column: 3
+        }
+      ),
+      _ => unreachable!(),
+    }
+    let rule = CssRule::parse_range(code, code.find(".d").unwrap(), code.len(), ParserOptions::default()).unwrap();
+    match rule {
+      CssRule::Style(s) => assert_eq!((s.loc.line, s.loc.column), (4, 1)),
+      _ => unreachable!(),
+    }
+    let err = CssRule::parse_range(code, end, code.len(), ParserOptions::default()).unwrap_err();
+    assert_eq!(err.loc.map(|loc| loc.line), Some(4));
+
     let property = Property::parse_string("color".into(), "#f0f", ParserOptions::default()).unwrap();
     assert_eq!(
       property.to_css_string(false, PrinterOptions::default()).unwrap(),
@@ -21556,6 +23716,17 @@ mod tests {
       "color: #f0f !important"
     );
 
+    let may_be_valid =
+      |value| match Property::parse_string("color".into(), value, ParserOptions::default()).unwrap() {
+        Property::Unparsed(unparsed) => unparsed.may_be_valid(),
+        _ => unreachable!(),
+      };
+    assert!(!may_be_valid("12px"));
+    assert!(may_be_valid("inherit"));
+    assert!(may_be_valid("var(--foo)"));
+    assert!(may_be_valid("rgb(0 0 var(--blue))"));
+    assert!(may_be_valid("env(--foo, red)"));
+
     let code = indoc! { r#"
       .foo {
         color: green;
@@ -21963,23 +24134,1980 @@ mod tests {
   }
 
   #[test]
-  fn test_error_recovery() {
-    use std::sync::{Arc, RwLock};
-    let warnings = Some(Arc::new(RwLock::new(Vec::new())));
-    test_with_options(
-      r#"
-      h1(>h1) {
-        color: red;
-      }
+  fn test_minify_options() {
+    fn minify_with(source: &str, options: MinifyOptions) -> String {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet.minify(options).unwrap();
+      stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap()
+        .code
+    }
 
-      .foo {
-        color: red;
-      }
+    let source = r#"
+      /*! license */
+      /* comment */
+      .a { margin-top: 1px; margin-bottom: 1px; margin-left: 1px; margin-right: 1px; margin-top: 2px }
+      .b { color: red }
+      .b { background: red }
+      .c {}
+      @media print {}
+      @media screen { .d { color: red } }
+      @media screen { .e { color: red } }
+    "#;
 
-      .clearfix {
-        *zoom: 1;
-        background: red;
-      }
+    assert_eq!(
+      minify_with(source, MinifyOptions::default()),
+      ".a{margin:2px 1px 1px}.b{color:red;background:red}@media screen{.d,.e{color:red}}"
+    );
+    assert_eq!(
+      minify_with(
+        source,
+        MinifyOptions {
+          remove_comments: false,
+          ..MinifyOptions::default()
+        }
+      ),
+      "/*! license */\n.a{margin:2px 1px 1px}.b{color:red;background:red}@media screen{.d,.e{color:red}}"
+    );
+    assert_eq!(
+      minify_with(
+        source,
+        MinifyOptions {
+          merge_rules: false,
+          ..MinifyOptions::default()
+        }
+      ),
+      ".a{margin:2px 1px 1px}.b{color:red}.b{background:red}@media screen{.d{color:red}}@media screen{.e{color:red}}"
+    );
+    assert_eq!(
+      minify_with(
+        source,
+        MinifyOptions {
+          merge_longhands: false,
+          ..MinifyOptions::default()
+        }
+      ),
+      ".a{margin-top:1px;margin-bottom:1px;margin-left:1px;margin-right:1px;margin-top:2px}.b{color:red;background:red}@media screen{.d,.e{color:red}}"
+    );
+    assert_eq!(
+      minify_with(
+        source,
+        MinifyOptions {
+          remove_empty_rules: false,
+          ..MinifyOptions::default()
+        }
+      ),
+      ".a{margin:2px 1px 1px}.b{color:red;background:red}.c{}@media print{}@media screen{.d,.e{color:red}}"
+    );
+
+    // License comments are kept when the style sheet is not minified.
+    let stylesheet = StyleSheet::parse("/*! a\n b */ .foo { color: red }", ParserOptions::default()).unwrap();
+    assert_eq!(
+      stylesheet.to_css(PrinterOptions::default()).unwrap().code,
+      indoc! {r#"
+      /*! a
+       b */
+      .foo {
+        color: red;
+      }
+    "#}
+    );
+  }
+
+  #[test]
+  fn test_deduplicate_declarations() {
+    fn dedup_test(source: &str, expected: &str, targets: Option<Browsers>) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          targets,
+          deduplicate_declarations: true,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    dedup_test(".a { color: red } .a { margin: 0 }", ".a{color:red;margin:0}", None);
+    dedup_test(
+      ".a { color: red; width: 1px } .a { margin: 0; color: blue }",
+      ".a{width:1px;color:#00f;margin:0}",
+      None,
+    );
+    dedup_test(".a { color: red; color: var(--foo) }", ".a{color:var(--foo)}", None);
+    dedup_test(".a { --foo: 1px; --foo: 2px }", ".a{--foo:1px;--foo:2px}", None);
+    dedup_test(
+      ".a { -webkit-transition: opacity 1s; transition: opacity 1s; transition: color 2s }",
+      ".a{-webkit-transition:opacity 1s;transition:color 2s}",
+      None,
+    );
+
+    // Important declarations win regardless of order.
+    dedup_test(
+      ".a { color: red !important } .a { color: blue }",
+      ".a{color:red!important}",
+      None,
+    );
+    dedup_test(
+      ".a { color: red !important } .a { color: blue !important }",
+      ".a{color:#00f!important}",
+      None,
+    );
+
+    // Rules are not merged across other rules.
+    dedup_test(
+      ".a { color: red } .b { color: green } .a { color: blue }",
+      ".a{color:red}.b{color:green}.a{color:#00f}",
+      None,
+    );
+
+    // Fallbacks generated for the targets are kept.
+    dedup_test(
+      ".a { color: red } .a { color: lab(40% 56.6 39) }",
+      ".a{color:#b32323;color:lab(40% 56.6 39)}",
+      Some(Browsers {
+        chrome: Some(90 << 16),
+        safari: Some(15 << 16),
+        ..Browsers::default()
+      }),
+    );
+
+    // Shorthands override the longhands they include.
+    dedup_test(
+      ".a { margin-top: 4px; color: red; margin: 0 }",
+      ".a{color:red;margin:0}",
+      None,
+    );
+    dedup_test(".a { background: red; background: blue }", ".a{background:#00f}", None);
+    dedup_test(
+      ".a { border-top-width: 2px; border-width: 1px; border: 3px solid red }",
+      ".a{border:3px solid red}",
+      None,
+    );
+    dedup_test(".a { margin: 0; margin-top: 4px }", ".a{margin:4px 0 0}", None);
+
+    // Logical and physical properties do not override each other.
+    dedup_test(
+      ".a { margin-left: 4px; margin-inline: 0 }",
+      ".a{margin-left:4px;margin-inline:0}",
+      None,
+    );
+
+    // A normal shorthand does not override an important longhand.
+    dedup_test(
+      ".a { margin-top: 4px !important; margin: 0 }",
+      ".a{margin:0;margin-top:4px!important}",
+      None,
+    );
+    dedup_test(
+      ".a { margin-top: 4px; margin: 0 !important }",
+      ".a{margin:0!important}",
+      None,
+    );
+
+    // Custom properties and values with var() are never removed.
+    dedup_test(
+      ".a { margin-top: var(--foo); margin: 0 }",
+      ".a{margin-top:var(--foo);margin:0}",
+      None,
+    );
+
+    // Without the option, duplicates are kept.
+    let mut stylesheet =
+      StyleSheet::parse(".a { color: red } .a { color: blue }", ParserOptions::default()).unwrap();
+    stylesheet.minify(MinifyOptions::default()).unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        minify: true,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(res.code, ".a{color:red;color:#00f}");
+  }
+
+  #[test]
+  fn test_sort_declarations() {
+    fn sort_test(source: &str, expected: &str) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          sort_declarations: true,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    sort_test(
+      ".a { width: 1px; color: red; display: block }",
+      ".a{color:red;display:block;width:1px}",
+    );
+    sort_test(
+      ".a { width: 1px !important; color: red !important; display: block; align-items: center }",
+      ".a{align-items:center;display:block;color:red!important;width:1px!important}",
+    );
+
+    // Physical and logical properties of the same group keep their order.
+    sort_test(
+      ".a { margin-left: 1px; color: red; margin-inline-start: 2px }",
+      ".a{color:red;margin-left:1px;margin-inline-start:2px}",
+    );
+
+    // Custom properties and values with var() are not moved, and declarations are not moved past them.
+    sort_test(
+      ".a { z-index: 1; --x: 1; color: red; width: var(--x); background: blue }",
+      ".a{z-index:1;--x:1;color:red;width:var(--x);background:#00f}",
+    );
+
+    minify_test(
+      ".a { width: 1px; color: red; display: block }",
+      ".a{width:1px;color:red;display:block}",
+    );
+  }
+
+  #[test]
+  fn test_inline_custom_properties() {
+    fn inline_test(source: &str, expected: &str, options: InlineCustomProperties, inlined: Vec<&str>) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          inline_custom_properties: options,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+      assert_eq!(res.inlined_custom_properties, inlined);
+    }
+
+    inline_test(
+      r#"
+      :root { --spacing: 8px; --color: #ff0000 }
+      .a { margin: var(--spacing) var(--spacing); color: var(--color) }
+      .b { --gap: calc(var(--spacing) * 2); border: 1px solid rgb(0 0 0 / var(--alpha, .5)) }
+    "#,
+      ".a{margin:8px;color:red}.b{--gap:calc(8px*2);border:1px solid rgb(0 0 0/var(--alpha,.5))}",
+      InlineCustomProperties::All,
+      vec!["--spacing", "--color"],
+    );
+    inline_test(
+      r#"
+      :root { --spacing: 8px; --color: red }
+      .a { margin: var(--spacing); color: var(--color) }
+    "#,
+      ":root{--color:red}.a{margin:8px;color:var(--color)}",
+      InlineCustomProperties::Only(vec!["--spacing"].iter().map(|s| String::from(*s)).collect()),
+      vec!["--spacing"],
+    );
+    inline_test(
+      r#"
+      :root { --spacing: 8px; --color: red }
+      .a { margin: var(--spacing); color: var(--color) }
+    "#,
+      ":root{--spacing:8px;--color:red}.a{margin:var(--spacing);color:var(--color)}",
+      InlineCustomProperties::None,
+      vec![],
+    );
+    inline_test(
+      r#"
+      :root { --a: 1px; --b: 2px; --c: 3px; --d: 4px; --e: var(--a); --f: 6px !important }
+      @media (min-width: 100px) { :root { --a: 10px } }
+      .dark { --b: 20px }
+      @property --c { syntax: "<length>"; inherits: true; initial-value: 0px }
+      .x { width: var(--a); height: var(--b); top: var(--c); left: var(--d, 5px); right: var(--e); bottom: var(--f) }
+    "#,
+      ":root{--a:1px;--b:2px;--c:3px;--d:4px;--e:var(--a);--f:6px!important}@media (min-width:100px){:root{--a:10px}}.dark{--b:20px}@property --c{syntax:\"<length>\";inherits:true;initial-value:0}.x{width:var(--a);height:var(--b);top:var(--c);left:var(--d,5px);right:var(--e);bottom:var(--f)}",
+      InlineCustomProperties::All,
+      vec![],
+    );
+    inline_test(
+      r#"
+      :root { --d: 4px }
+      .x { left: var(--d, 4px) }
+      @keyframes slide { to { left: var(--d) } }
+    "#,
+      ".x{left:4px}@keyframes slide{to{left:4px}}",
+      InlineCustomProperties::All,
+      vec!["--d"],
+    );
+    inline_test(
+      r#"
+      :root { --a: 1px; --b: 2px; --c: 3px; --d: 4px; --e: 5px }
+      .x { width: var(--a); height: var(--b); top: var(--c); left: var(--d); right: var(--e) }
+      @font-face { font-family: Test; --size: var(--a) }
+      @viewport { width: var(--b) }
+      @unknown { top: var(--c) }
+      @unknown var(--d);
+    "#,
+      ":root{--a:1px;--b:2px;--c:3px;--d:4px}.x{width:var(--a);height:var(--b);top:var(--c);left:var(--d);right:5px}@font-face{font-family:Test;--size:var(--a)}@viewport{width:var(--b)}@unknown{top: var(--c)}@unknown var(--d);",
+      InlineCustomProperties::All,
+      vec!["--e"],
+    );
+    inline_test(
+      r#"
+      :root { --size: 10px; --half: calc(5px * 2) }
+      .x { padding: var(--size) var(--size) var(--size) var(--size); width: var(--half) }
+    "#,
+      ".x{padding:10px;width:10px}",
+      InlineCustomProperties::All,
+      vec!["--size", "--half"],
+    );
+  }
+
+  #[test]
+  fn test_remove_unused_custom_properties() {
+    fn remove_test(
+      source: &str,
+      expected: &str,
+      unused_symbols: Vec<&str>,
+      remove_unused_custom_properties: bool,
+    ) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          unused_symbols: unused_symbols.into_iter().map(String::from).collect(),
+          remove_unused_custom_properties,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    let source = r#"
+      :root { --old-token: red; --color: blue; --unused: 1px; --base: 2px; --size: calc(var(--base) * 2) }
+      .a { color: var(--color); width: var(--size) }
+      .b { --old-token: green !important }
+      .c { --local: 5px; margin: var(--missing, var(--local)) }
+    "#;
+
+    remove_test(
+      source,
+      ":root{--color:blue;--unused:1px;--base:2px;--size:calc(var(--base)*2)}.a{color:var(--color);width:var(--size)}.c{--local:5px;margin:var(--missing,var(--local))}",
+      vec!["--old-token"],
+      false,
+    );
+    remove_test(
+      source,
+      ":root{--color:blue;--base:2px;--size:calc(var(--base)*2)}.a{color:var(--color);width:var(--size)}.c{--local:5px;margin:var(--missing,var(--local))}",
+      vec![],
+      true,
+    );
+    remove_test(
+      ":root { --a: var(--b); --b: 1px } .x { --c: var(--a) }",
+      "",
+      vec![],
+      true,
+    );
+    remove_test(
+      ".a { --x: 1px } @media (min-width: 100px) { .b { --y: 2px } } @viewport { width: var(--x) }",
+      ".a{--x:1px}@viewport{width:var(--x)}",
+      vec![],
+      true,
+    );
+  }
+
+  #[test]
+  fn test_custom_property_fallbacks() {
+    use std::sync::{Arc, RwLock};
+
+    fn fallback_test(source: &str, expected: &str, expected_warnings: &[&str], targets: Browsers) {
+      let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+      let mut stylesheet = StyleSheet::parse(
+        source,
+        ParserOptions {
+          warnings: warnings.clone(),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          targets: Some(targets),
+          custom_property_fallbacks: true,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+      let warnings: Vec<String> = warnings
+        .unwrap()
+        .read()
+        .unwrap()
+        .iter()
+        .map(|w| w.kind.code().to_owned())
+        .collect();
+      assert_eq!(warnings, expected_warnings);
+    }
+
+    let ie = Browsers {
+      ie: Some(11 << 16),
+      ..Browsers::default()
+    };
+
+    fallback_test(
+      ":root { --color: red; --gap: 2px } .a { color: var(--color); margin: var(--gap) var(--gap, 4px) }",
+      ":root{--color:red;--gap:2px}.a{color:red;color:var(--color);margin:2px 2px;margin:var(--gap)var(--gap,4px)}",
+      &[],
+      ie,
+    );
+    fallback_test(
+      ":root { --base: 2px; --size: var(--base) } .a { width: var(--size); height: var(--missing, 5px) }",
+      ":root{--base:2px;--size:var(--base)}.a{width:2px;width:var(--size);height:5px;height:var(--missing,5px)}",
+      &[],
+      ie,
+    );
+    fallback_test(
+      ":root { --color: red } .a { color: var(--color) } :root { --color: blue }",
+      ":root{--color:red}.a{color:blue;color:var(--color)}:root{--color:blue}",
+      &[],
+      ie,
+    );
+    fallback_test(
+      ":root { --color: red } .a { --color: blue } .b { color: var(--color) }",
+      ":root{--color:red}.a{--color:blue}.b{color:var(--color)}",
+      &["UnresolvedCustomProperty"],
+      ie,
+    );
+    fallback_test(
+      ":root { --color: red } .a { color: green; color: var(--color) }",
+      ":root{--color:red}.a{color:green;color:var(--color)}",
+      &[],
+      ie,
+    );
+    fallback_test(
+      ":root { --color: red } @media (min-width: 100px) { .a { color: var(--color) } }",
+      ":root{--color:red}@media (min-width:100px){.a{color:red;color:var(--color)}}",
+      &[],
+      ie,
+    );
+    fallback_test(
+      ":root { --color: red } .a { color: var(--color) }",
+      ":root{--color:red}.a{color:var(--color)}",
+      &[],
+      Browsers {
+        chrome: Some(100 << 16),
+        ..Browsers::default()
+      },
+    );
+  }
+
+  #[test]
+  fn test_merge_non_adjacent_at_rules() {
+    fn merge_test(source: &str, expected: &str) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          merge_non_adjacent_at_rules: true,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    merge_test(
+      r#"
+      .a { color: red }
+      @media (min-width: 768px) { .a { color: blue } }
+      .b { margin: 0 }
+      @media (min-width: 768px) { .b { padding: 0 } }
+    "#,
+      ".a{color:red}@media (min-width:768px){.a{color:#00f}.b{padding:0}}.b{margin:0}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: blue } }
+      .b { margin: 0 }
+      @media (min-width: 1024px) { .c { color: red } }
+      @media (min-width: 768px) { .b { padding: 0 } .c { background: green } }
+    "#,
+      "@media (min-width:768px){.a{color:#00f}.b{padding:0}.c{background:green}}.b{margin:0}@media (min-width:1024px){.c{color:red}}",
+    );
+    merge_test(
+      r#"
+      @supports (display: grid) { .a { display: grid } }
+      .b { color: red }
+      @supports (display: grid) { .b { display: grid } }
+    "#,
+      "@supports (display: grid){.a,.b{display:grid}}.b{color:red}",
+    );
+    merge_test(
+      r#"
+      @layer foo { .a { color: red } }
+      .b { margin: 0 }
+      @layer foo { .b { color: red } }
+    "#,
+      "@layer foo{.a,.b{color:red}}.b{margin:0}",
+    );
+
+    // Rules in between that declare the same properties must keep their order.
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: blue } }
+      @media (min-width: 1024px) { .c { color: red } }
+      @media (min-width: 768px) { .c { color: green } }
+    "#,
+      "@media (min-width:768px){.a{color:#00f}}@media (min-width:1024px){.c{color:red}}@media (min-width:768px){.c{color:green}}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { padding: 0 } }
+      .b { padding: 1px }
+      @media (min-width: 768px) { .b { padding: 0 } }
+    "#,
+      "@media (min-width:768px){.a{padding:0}}.b{padding:1px}@media (min-width:768px){.b{padding:0}}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      .b { padding-left: 1px }
+      @media (min-width: 768px) { .b { padding: 0 } }
+    "#,
+      "@media (min-width:768px){.a{color:red}}.b{padding-left:1px}@media (min-width:768px){.b{padding:0}}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      .b { left: 0 }
+      @media (min-width: 768px) { .b { inset-inline-start: 1px } }
+    "#,
+      "@media (min-width:768px){.a{color:red}}.b{left:0}@media (min-width:768px){.b{inset-inline-start:1px}}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      .b { all: unset }
+      @media (min-width: 768px) { .b { padding: 0 } }
+    "#,
+      "@media (min-width:768px){.a{color:red}}.b{all:unset}@media (min-width:768px){.b{padding:0}}",
+    );
+
+    // Vendor prefixed properties override the unprefixed property, and vice versa.
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      .b { -webkit-line-clamp: 2 }
+      @media (min-width: 768px) { .b { line-clamp: 3 } }
+    "#,
+      "@media (min-width:768px){.a{color:red}}.b{-webkit-line-clamp:2}@media (min-width:768px){.b{line-clamp:3}}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      .b { text-size-adjust: none }
+      @media (min-width: 768px) { .b { -webkit-text-size-adjust: 100% } }
+    "#,
+      "@media (min-width:768px){.a{color:red}}.b{text-size-adjust:none}@media (min-width:768px){.b{-webkit-text-size-adjust:100%}}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      .b { -webkit-transition: opacity 1s }
+      @media (min-width: 768px) { .b { transition: none } }
+    "#,
+      "@media (min-width:768px){.a{color:red}}.b{-webkit-transition:opacity 1s}@media (min-width:768px){.b{transition:none}}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      .b { -webkit-tap-highlight-color: red }
+      @media (min-width: 768px) { .b { -moz-text-size-adjust: none } }
+    "#,
+      "@media (min-width:768px){.a{color:red}.b{-moz-text-size-adjust:none}}.b{-webkit-tap-highlight-color:red}",
+    );
+    merge_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      @font-face { font-family: Foo; src: url(foo.woff) }
+      @media (min-width: 768px) { .b { padding: 0 } }
+    "#,
+      "@media (min-width:768px){.a{color:red}}@font-face{font-family:Foo;src:url(foo.woff)}@media (min-width:768px){.b{padding:0}}",
+    );
+
+    // Disabled by default.
+    minify_test(
+      r#"
+      @media (min-width: 768px) { .a { color: red } }
+      .b { margin: 0 }
+      @media (min-width: 768px) { .b { padding: 0 } }
+    "#,
+      "@media (min-width:768px){.a{color:red}}.b{margin:0}@media (min-width:768px){.b{padding:0}}",
+    );
+  }
+
+  #[test]
+  fn test_deduplicate_keyframes() {
+    fn dedup_test(source: &str, expected: &str, renamed: HashMap<&str, &str>) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          deduplicate_keyframes: true,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+      let renamed: HashMap<String, String> =
+        renamed.into_iter().map(|(k, v)| (k.to_owned(), v.to_owned())).collect();
+      assert_eq!(res.renamed_keyframes, renamed);
+    }
+
+    dedup_test(
+      r#"
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      @keyframes fadeIn { from { opacity: 0 } to { opacity: 1 } }
+      .a { animation: fadeIn 1s ease }
+      .b { animation-name: fade, fadeIn }
+      @media (min-width: 768px) { .c { animation-name: fadeIn } }
+    "#,
+      "@keyframes fade{0%{opacity:0}to{opacity:1}}.a{animation:1s fade}.b{animation-name:fade,fade}@media (min-width:768px){.c{animation-name:fade}}",
+      HashMap::from([("fadeIn", "fade")]),
+    );
+    dedup_test(
+      r#"
+      @-webkit-keyframes spin { from { transform: rotate(0) } to { transform: rotate(360deg) } }
+      @keyframes rotate { from { transform: rotate(0) } to { transform: rotate(360deg) } }
+      .a { animation-name: rotate }
+    "#,
+      "@-webkit-keyframes spin{0%{transform:rotate(0)}to{transform:rotate(360deg)}}@keyframes spin{0%{transform:rotate(0)}to{transform:rotate(360deg)}}.a{animation-name:spin}",
+      HashMap::from([("rotate", "spin")]),
+    );
+    dedup_test(
+      r#"
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      @keyframes fadeIn { from { opacity: 0 } to { opacity: 1 } }
+      .a { animation: var(--duration) fadeIn }
+    "#,
+      "@keyframes fade{0%{opacity:0}to{opacity:1}}@keyframes fadeIn{0%{opacity:0}to{opacity:1}}.a{animation:var(--duration)fadeIn}",
+      HashMap::new(),
+    );
+    dedup_test(
+      r#"
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      @keyframes fadeIn { from { opacity: 0 } to { opacity: 1 } }
+      @media (prefers-reduced-motion) {
+        @keyframes fadeIn { from { opacity: 1 } to { opacity: 1 } }
+      }
+      .a { animation-name: fadeIn }
+    "#,
+      "@keyframes fade{0%{opacity:0}to{opacity:1}}@keyframes fadeIn{0%{opacity:0}to{opacity:1}}@media (prefers-reduced-motion){@keyframes fadeIn{0%{opacity:1}to{opacity:1}}}.a{animation-name:fadeIn}",
+      HashMap::new(),
+    );
+    dedup_test(
+      r#"
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      @keyframes pulse { from { opacity: 1 } to { opacity: 0 } }
+      .a { animation-name: pulse }
+    "#,
+      "@keyframes fade{0%{opacity:0}to{opacity:1}}@keyframes pulse{0%{opacity:1}to{opacity:0}}.a{animation-name:pulse}",
+      HashMap::new(),
+    );
+
+    minify_test(
+      r#"
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      @keyframes fadeIn { from { opacity: 0 } to { opacity: 1 } }
+      .a { animation-name: fadeIn }
+    "#,
+      "@keyframes fade{0%{opacity:0}to{opacity:1}}@keyframes fadeIn{0%{opacity:0}to{opacity:1}}.a{animation-name:fadeIn}",
+    );
+  }
+
+  #[test]
+  fn test_shorten_keyframes_names() {
+    fn shorten_test(
+      source: &str,
+      expected: &str,
+      preserved: Vec<&str>,
+      targets: Option<Browsers>,
+      renamed: HashMap<&str, &str>,
+    ) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          targets,
+          shorten_keyframes_names: true,
+          preserved_keyframes_names: preserved.iter().map(|s| String::from(*s)).collect(),
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          targets,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+      let renamed: HashMap<String, String> =
+        renamed.into_iter().map(|(k, v)| (k.to_owned(), v.to_owned())).collect();
+      assert_eq!(res.renamed_keyframes, renamed);
+    }
+
+    shorten_test(
+      r#"
+      @keyframes slide-in-from-the-left-panel { from { left: -100px } to { left: 0 } }
+      @keyframes "fade out" { to { opacity: 0 } }
+      .a { animation: slide-in-from-the-left-panel 1s ease }
+      .b { animation-name: "fade out", slide-in-from-the-left-panel }
+      @media (min-width: 768px) { .c { animation-name: "fade out" } }
+    "#,
+      "@keyframes a{0%{left:-100px}to{left:0}}@keyframes b{to{opacity:0}}.a{animation:1s a}.b{animation-name:b,a}@media (min-width:768px){.c{animation-name:b}}",
+      vec![],
+      None,
+      HashMap::from([("slide-in-from-the-left-panel", "a"), ("fade out", "b")]),
+    );
+
+    // Preserved names, names defined within conditional rules, and names used in unparsed values are kept.
+    // Generated names skip names that are kept or referenced from elsewhere.
+    shorten_test(
+      r#"
+      @keyframes a { to { opacity: 0 } }
+      @keyframes spin { to { transform: rotate(1turn) } }
+      @keyframes pulse { to { opacity: .5 } }
+      @media (min-width: 768px) { @keyframes pulse { to { opacity: .8 } } }
+      @keyframes bounce { to { top: 0 } }
+      @keyframes wiggle { to { left: 0 } }
+      .a { animation-name: a, spin, pulse, bounce, wiggle, b }
+      .b { animation: var(--duration) bounce }
+    "#,
+      "@keyframes a{to{opacity:0}}@keyframes spin{to{transform:rotate(1turn)}}@keyframes pulse{to{opacity:.5}}@media (min-width:768px){@keyframes pulse{to{opacity:.8}}}@keyframes bounce{to{top:0}}@keyframes c{to{left:0}}.a{animation-name:a,spin,pulse,bounce,c,b}.b{animation:var(--duration)bounce}",
+      vec!["spin"],
+      None,
+      HashMap::from([("wiggle", "c")]),
+    );
+
+    shorten_test(
+      r#"
+      @keyframes fade-in { from { opacity: 0 } to { opacity: 1 } }
+      .a { animation: fade-in 1s }
+    "#,
+      "@-webkit-keyframes a{0%{opacity:0}to{opacity:1}}@keyframes a{0%{opacity:0}to{opacity:1}}.a{-webkit-animation:1s a;animation:1s a}",
+      vec![],
+      Some(Browsers {
+        safari: Some(4 << 16),
+        ..Browsers::default()
+      }),
+      HashMap::from([("fade-in", "a")]),
+    );
+  }
+
+  #[test]
+  fn test_pretty_print() {
+    fn pretty_test(source: &str, expected: &str, indent: &str, max_line_width: Option<usize>) {
+      let stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          indent,
+          max_line_width,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    pretty_test(
+      "@media (min-width: 100px) { .a { color: red } }",
+      "@media (min-width: 100px) {\n\t.a {\n\t\tcolor: red;\n\t}\n}\n",
+      "\t",
+      None,
+    );
+    pretty_test(
+      ".a { color: red; grid-template-areas: \"a a\" \"b b\" }",
+      indoc! {r#"
+      .a {
+          color: red;
+          grid-template-areas: "a a"
+                               "b b";
+      }
+    "#},
+      "    ",
+      None,
+    );
+    pretty_test(
+      ".foo-bar-baz, .foo-bar-qux, .foo-bar-quux { transition: opacity .2s, transform .3s, color 1s }",
+      indoc! {r#"
+      .foo-bar-baz,
+      .foo-bar-qux,
+      .foo-bar-quux {
+        transition: opacity .2s,
+          transform .3s,
+          color 1s;
+      }
+    "#},
+      "  ",
+      Some(30),
+    );
+    pretty_test(
+      ".a, .b { transition: opacity .2s, transform .3s }",
+      indoc! {r#"
+      .a, .b {
+        transition: opacity .2s, transform .3s;
+      }
+    "#},
+      "  ",
+      Some(80),
+    );
+
+    // Minified output is never wrapped.
+    let stylesheet =
+      StyleSheet::parse(".foo-bar-baz, .foo-bar-qux { color: red }", ParserOptions::default()).unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        minify: true,
+        max_line_width: Some(10),
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(res.code, ".foo-bar-baz,.foo-bar-qux{color:red}");
+
+    let stylesheet = StyleSheet::parse(
+      r#"
+      .a { color: red; & .b { color: blue } }
+      @media print { .c { color: red } .d { color: red } }
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      "#,
+      ParserOptions {
+        nesting: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        indent: "\t",
+        blank_lines_between_rules: false,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(
+      res.code,
+      ".a {\n\tcolor: red;\n\t& .b {\n\t\tcolor: #00f;\n\t}\n}\n@media print {\n\t.c {\n\t\tcolor: red;\n\t}\n\t.d {\n\t\tcolor: red;\n\t}\n}\n@keyframes fade {\n\tfrom {\n\t\topacity: 0;\n\t}\n\tto {\n\t\topacity: 1;\n\t}\n}\n"
+    );
+  }
+
+  #[test]
+  fn test_precision() {
+    fn precision_test(source: &str, expected: &str, precision: crate::printer::Precision) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet.minify(MinifyOptions::default()).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          precision,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    let all = |decimals| crate::printer::Precision {
+      lengths: Some(decimals),
+      percentages: Some(decimals),
+      times: Some(decimals),
+      angles: Some(decimals),
+      numbers: Some(decimals),
+    };
+
+    precision_test(
+      ".a { transition-duration: 0.30000000000000004s; width: 1e3px; opacity: 0.123456789 }",
+      ".a{width:1000px;opacity:.123457;transition-duration:.3s}",
+      Default::default(),
+    );
+    precision_test(
+      ".a { transition-duration: 0.30000000000000004s; width: 1e3px; opacity: 0.123456789 }",
+      ".a{width:1000px;opacity:.12;transition-duration:.3s}",
+      all(2),
+    );
+    precision_test(
+      ".a { left: 123456789px; top: -0.25px; rotate: 33.3333333deg; line-height: 1.0000001 }",
+      ".a{line-height:1.0000001;top:-.25px;left:123456790px;rotate:33.333332deg}",
+      all(8),
+    );
+    precision_test(
+      ".a { width: 33.333333%; margin: 1.23456789em; transition-delay: 0.0123456s; grid-template-columns: 1.5555fr 2fr }",
+      ".a{width:33.3333%;grid-template-columns:1.5555fr 2fr;margin:1.23em;transition-delay:12.3456ms}",
+      crate::printer::Precision {
+        lengths: Some(2),
+        ..Default::default()
+      },
+    );
+    precision_test(
+      ".a { width: 33.333333%; margin: 1.23456789em; transition-delay: 0.0123456s; grid-template-columns: 1.5555fr 2fr }",
+      ".a{width:33%;grid-template-columns:1.6fr 2fr;margin:1.23457em;transition-delay:12.346ms}",
+      crate::printer::Precision {
+        percentages: Some(0),
+        times: Some(3),
+        numbers: Some(1),
+        ..Default::default()
+      },
+    );
+    precision_test(
+      ".a { --x: 1.23456789em 1e1px 12.3456789 }",
+      ".a{--x:1.235em 10px 12.346}",
+      all(3),
+    );
+    precision_test(
+      ".a { height: 1e39px; width: calc(1e38px * 10); flex: 3e38 }",
+      ".a{height:3.4028235e38px;width:3.4028235e38px;flex:3e38}",
+      all(2),
+    );
+    precision_test(
+      ".a { width: calc(1e38px * 10) }",
+      ".a{width:3.40282e38px}",
+      Default::default(),
+    );
+  }
+
+  #[test]
+  fn test_include_exclude_features() {
+    use crate::targets::Features;
+
+    fn features_test(
+      source: &str,
+      expected: &str,
+      targets: Option<Browsers>,
+      include: Features,
+      exclude: Features,
+      features_used: Features,
+    ) {
+      let mut stylesheet = StyleSheet::parse(
+        source,
+        ParserOptions {
+          nesting: true,
+          custom_media: true,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          targets,
+          include,
+          exclude,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          targets,
+          include,
+          exclude,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+      assert_eq!(res.features_used, features_used);
+    }
+
+    let source = r#"
+      @custom-media --narrow (width <= 600px);
+      .foo { color: #ff000080; & .bar { color: red } }
+      @media (--narrow) { .a { color: red } }
+    "#;
+    let chrome = Some(Browsers {
+      chrome: Some(90 << 16),
+      ..Browsers::default()
+    });
+
+    features_test(
+      source,
+      "@custom-media --narrow (width<=600px);.foo{color:#ff000080;& .bar{color:red}}@media (--narrow){.a{color:red}}",
+      None,
+      Features::empty(),
+      Features::empty(),
+      Features::empty(),
+    );
+    features_test(
+      source,
+      ".foo{color:#ff000080}.foo .bar{color:red}@media (max-width:600px){.a{color:red}}",
+      chrome,
+      Features::empty(),
+      Features::empty(),
+      Features::NESTING | Features::MEDIA_RANGE_SYNTAX | Features::CUSTOM_MEDIA_QUERIES,
+    );
+    features_test(
+      source,
+      ".foo{color:rgba(255,0,0,.5)}.foo .bar{color:red}@media (max-width:600px){.a{color:red}}",
+      None,
+      Features::all(),
+      Features::empty(),
+      Features::NESTING
+        | Features::MEDIA_RANGE_SYNTAX
+        | Features::CUSTOM_MEDIA_QUERIES
+        | Features::HEX_ALPHA_COLORS,
+    );
+    features_test(
+      source,
+      ".foo{color:#ff000080;& .bar{color:red}}@media (width<=600px){.a{color:red}}",
+      chrome,
+      Features::empty(),
+      Features::NESTING | Features::MEDIA_RANGE_SYNTAX,
+      Features::CUSTOM_MEDIA_QUERIES,
+    );
+    features_test(
+      source,
+      "@custom-media --narrow (max-width:600px);.foo{color:#ff000080}.foo .bar{color:red}@media (--narrow){.a{color:red}}",
+      chrome,
+      Features::NESTING,
+      Features::CUSTOM_MEDIA_QUERIES,
+      Features::NESTING | Features::MEDIA_RANGE_SYNTAX,
+    );
+
+    features_test(
+      ".foo { padding-inline: 10px; margin-inline-start: 5px }",
+      ".foo{padding-left:10px;padding-right:10px}.foo:dir(ltr){margin-left:5px}.foo:dir(rtl){margin-right:5px}",
+      None,
+      Features::LOGICAL_PROPERTIES,
+      Features::empty(),
+      Features::LOGICAL_PROPERTIES,
+    );
+    features_test(
+      ".foo { padding-inline: 10px; margin-inline-start: 5px }",
+      ".foo{margin-inline-start:5px;padding-inline:10px}",
+      Some(Browsers {
+        safari: Some(8 << 16),
+        ..Browsers::default()
+      }),
+      Features::empty(),
+      Features::LOGICAL_PROPERTIES,
+      Features::empty(),
+    );
+  }
+
+  #[test]
+  fn test_minify_families() {
+    fn minify_with(
+      parser_options: ParserOptions,
+      options: MinifyOptions,
+      printer_options: PrinterOptions,
+    ) -> String {
+      let source = r#"
+        .a[data-foo="bar"] {
+          color: #ff0000;
+          width: calc(1px + 2px);
+          margin-top: 1px;
+          margin-right: 1px;
+          margin-bottom: 1px;
+          margin-left: 1px;
+        }
+      "#;
+      let mut stylesheet = StyleSheet::parse(source, parser_options).unwrap();
+      stylesheet.minify(options).unwrap();
+      stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..printer_options
+        })
+        .unwrap()
+        .code
+    }
+
+    assert_eq!(
+      minify_with(
+        ParserOptions::default(),
+        MinifyOptions::default(),
+        PrinterOptions::default()
+      ),
+      ".a[data-foo=bar]{color:red;width:3px;margin:1px}"
+    );
+    assert_eq!(
+      minify_with(
+        ParserOptions::default(),
+        MinifyOptions::default(),
+        PrinterOptions {
+          minify_colors: false,
+          ..PrinterOptions::default()
+        }
+      ),
+      ".a[data-foo=bar]{color:#ff0000;width:3px;margin:1px}"
+    );
+    assert_eq!(
+      minify_with(
+        ParserOptions {
+          preserve_calc: true,
+          ..ParserOptions::default()
+        },
+        MinifyOptions::default(),
+        PrinterOptions::default()
+      ),
+      ".a[data-foo=bar]{color:red;width:calc(1px + 2px);margin:1px}"
+    );
+    assert_eq!(
+      minify_with(
+        ParserOptions::default(),
+        MinifyOptions {
+          merge_longhands: false,
+          ..MinifyOptions::default()
+        },
+        PrinterOptions::default()
+      ),
+      ".a[data-foo=bar]{color:red;width:3px;margin-top:1px;margin-right:1px;margin-bottom:1px;margin-left:1px}"
+    );
+    assert_eq!(
+      minify_with(
+        ParserOptions::default(),
+        MinifyOptions::default(),
+        PrinterOptions {
+          minify_selectors: false,
+          ..PrinterOptions::default()
+        }
+      ),
+      ".a[data-foo=\"bar\"]{color:red;width:3px;margin:1px}"
+    );
+
+    // Colors are printed in full even when the shortest form would use an alpha channel or a name.
+    let stylesheet = StyleSheet::parse(
+      ".foo { color: #ff000080; background: navy; border-color: #aabbcc }",
+      ParserOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(
+      stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          minify_colors: false,
+          ..PrinterOptions::default()
+        })
+        .unwrap()
+        .code,
+      ".foo{color:#ff000080;background:#000080;border-color:#aabbcc}"
+    );
+
+    // Math functions nested in other values are preserved as well.
+    let stylesheet = StyleSheet::parse(
+      ".foo { transform: translate(calc(10px * 2), max(1px, 2px)); height: 10px }",
+      ParserOptions {
+        preserve_calc: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    assert_eq!(
+      stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap()
+        .code,
+      ".foo{transform:translate(calc(10px*2),max(1px,2px));height:10px}"
+    );
+  }
+
+  #[test]
+  fn test_preserve_var_fallbacks() {
+    fn preserve_test(source: &str, expected: &str, minify: bool) {
+      let mut stylesheet = StyleSheet::parse(
+        &source,
+        ParserOptions {
+          preserve_var_fallbacks: true,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet.minify(MinifyOptions::default()).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    minify_test(
+      ".foo { background: var(--bg, linear-gradient(to right, #FF0000 0%, rgba(0, 0, 255, 1) 100%)) }",
+      ".foo{background:var(--bg,linear-gradient(to right,red 0%,#00f 100%))}",
+    );
+    preserve_test(
+      ".foo { background: var(--bg, linear-gradient(to right, #FF0000 0%, rgba(0, 0, 255, 1) 100%)) }",
+      ".foo{background:var(--bg,linear-gradient(to right, #FF0000 0%, rgba(0, 0, 255, 1) 100%))}",
+      true,
+    );
+    preserve_test(
+      ".foo { --x: var(--y, 1px  /* one */ 2px ); color: var(--a, var(--b, #FF0000)) }",
+      indoc! {r#"
+        .foo {
+          --x: var(--y, 1px  /* one */ 2px);
+          color: var(--a, var(--b, #FF0000));
+        }
+      "#},
+      false,
+    );
+
+    let mut stylesheet = StyleSheet::parse(
+      ":root { --b: red } .foo { color: var(--a, var(--b)) }",
+      ParserOptions {
+        preserve_var_fallbacks: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    stylesheet
+      .minify(MinifyOptions {
+        inline_custom_properties: InlineCustomProperties::All,
+        ..MinifyOptions::default()
+      })
+      .unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        minify: true,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(res.code, ":root{--b:red}.foo{color:var(--a,var(--b))}");
+  }
+
+  #[test]
+  fn test_ignore_properties() {
+    fn ignore_test(source: &str, expected: &str) {
+      let mut stylesheet = StyleSheet::parse(
+        &source,
+        ParserOptions {
+          ignore_properties: Some(HashSet::from(["-webkit-box-orient".into(), "x-foo".into()])),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          targets: Some(Browsers {
+            chrome: Some(100 << 16),
+            ..Browsers::default()
+          }),
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    prefix_test(
+      ".foo { display: -webkit-box; -webkit-box-orient: vertical; -webkit-line-clamp: 2 }",
+      indoc! {r#"
+        .foo {
+          -webkit-line-clamp: 2;
+          -webkit-box-orient: vertical;
+          display: -webkit-box;
+        }
+      "#},
+      Browsers {
+        chrome: Some(100 << 16),
+        ..Browsers::default()
+      },
+    );
+    ignore_test(
+      ".foo { display: -webkit-box; -webkit-box-orient: vertical; -webkit-line-clamp: 2 }",
+      ".foo{-webkit-box-orient:vertical;-webkit-line-clamp:2;display:-webkit-box}",
+    );
+    ignore_test(
+      ".foo { -WEBKIT-BOX-ORIENT: Vertical /* keep */ !important; -webkit-box-orient: horizontal }",
+      ".foo{-webkit-box-orient:horizontal;-WEBKIT-BOX-ORIENT:Vertical /* keep */!important}",
+    );
+    ignore_test(
+      ".foo { x-foo: #FF0000  calc(1px + 2px) url( 'a.png' ); x-foo: rgba(0, 0, 255, 1); x-bar: #FF0000 }",
+      ".foo{x-foo:#FF0000  calc(1px + 2px) url( 'a.png' );x-foo:rgba(0, 0, 255, 1);x-bar:red}",
+    );
+  }
+
+  #[test]
+  fn test_verbatim_values() {
+    fn verbatim_test(source: &str, expected: &str) {
+      let stylesheet = StyleSheet::parse(
+        &source,
+        ParserOptions {
+          verbatim_values: true,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let res = stylesheet.to_css(PrinterOptions::whitespace_only()).unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    // Every byte of the declarations other than whitespace and comments is kept.
+    fn strip(source: &str) -> String {
+      let mut res = String::new();
+      let mut rest = source;
+      while let Some(start) = rest.find("/*") {
+        res.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        rest = &rest[rest.find("*/").unwrap() + 2..];
+      }
+      res.push_str(rest);
+      res.chars().filter(|c| !c.is_whitespace()).collect()
+    }
+
+    let declarations = [
+      "color: #FF0000",
+      "COLOR: RED !important",
+      "margin: 0px 0px 0.50em /* x */ 0",
+      "width: calc( 1px  +  2px )",
+      "background: url( 'a.png' ) no-repeat , linear-gradient( to right , #FFF 0% , rgba(0,0,0,1.0) 100% )",
+      "transition: opacity .3s ease-in-out,transform 0.30s",
+      "font: 12px/1.5 'Helvetica Neue', Arial, sans-serif",
+      "grid-template-areas: 'a b'\n    'c d'",
+      "--foo: { a: b } , [ c ]",
+      "-webkit-box-orient: vertical",
+      "display: -webkit-box",
+      "color: var( --foo , #FFFFFF )",
+    ];
+    for declaration in declarations {
+      let source = format!(".foo {{ {} }}", declaration);
+      let stylesheet = StyleSheet::parse(
+        &source,
+        ParserOptions {
+          verbatim_values: true,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let res = stylesheet.to_css(PrinterOptions::whitespace_only()).unwrap();
+      assert_eq!(strip(&res.code), strip(&source));
+    }
+
+    verbatim_test(
+      ".foo { color: #FF0000; margin: 0px 0px 0.50em /* x */ 0; width: calc( 1px  +  2px ) }",
+      ".foo{color:#FF0000;margin:0px 0px 0.50em 0;width:calc(1px + 2px)}",
+    );
+    verbatim_test(
+      ".foo { background: url( 'a.png' ) no-repeat , linear-gradient( to right , #FFF 0% , rgba(0,0,0,1.0) 100% ) }",
+      ".foo{background:url('a.png') no-repeat,linear-gradient(to right,#FFF 0%,rgba(0,0,0,1.0) 100%)}",
+    );
+    verbatim_test(
+      ".foo { margin-top: 1px; margin: 0; margin-top: 1px; COLOR: RED ! important; color: red }",
+      ".foo{margin-top:1px;margin:0;margin-top:1px;color:red;COLOR:RED!important}",
+    );
+    verbatim_test(
+      ".foo { x: a/**/b; --foo:  a , b/**/c  ; --bar: { a: b } }",
+      ".foo{x:a b;--foo:a , b c;--bar:{a: b}}",
+    );
+    verbatim_test(
+      ".foo { display: -webkit-box; -webkit-box-orient: vertical } .foo::before { content: 'a  b' }",
+      ".foo{display:-webkit-box;-webkit-box-orient:vertical}.foo:before{content:'a  b'}",
+    );
+  }
+
+  #[test]
+  fn test_strict() {
+    fn strict_options<'i>(error_recovery: bool, preserve_calc: bool) -> ParserOptions<'static, 'i> {
+      ParserOptions {
+        strict: true,
+        strict_exceptions: Some(HashSet::from(["-webkit-line-clamp".into(), "@tailwind".into()])),
+        error_recovery,
+        preserve_calc,
+        ..ParserOptions::default()
+      }
+    }
+
+    fn strict_test(source: &str, expected: &str) {
+      let stylesheet = StyleSheet::parse(&source, strict_options(false, false)).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    fn strict_error_test(source: &str, error: ParserError, line: u32, column: u32) {
+      for error_recovery in [false, true] {
+        match StyleSheet::parse(&source, strict_options(error_recovery, false)) {
+          Ok(_) => unreachable!(),
+          Err(e) => {
+            assert_eq!(e.kind, error);
+            let loc = e.loc.unwrap();
+            assert_eq!((loc.line, loc.column), (line, column));
+          }
+        }
+      }
+    }
+
+    strict_test(
+      ".a { color: red; --foo: 12px; width: var(--w); height: inherit; margin: env(safe-area-inset-top) }",
+      ".a{color:red;--foo:12px;width:var(--w);height:inherit;margin:env(safe-area-inset-top)}",
+    );
+    strict_test(
+      ".a { display: -webkit-box; -webkit-line-clamp: 2 }",
+      ".a{display:-webkit-box;-webkit-line-clamp:2}",
+    );
+    strict_test("@tailwind base; .a { color: red }", "@tailwind base;.a{color:red}");
+
+    strict_error_test(".a { color: red;\n  colr: red }", ParserError::InvalidDeclaration, 1, 9);
+    strict_error_test(".a { color: 12px }", ParserError::InvalidValue, 0, 13);
+    strict_error_test(".a { -webkit-text-size: 2px }", ParserError::InvalidDeclaration, 0, 25);
+    strict_error_test("@apply foo;", ParserError::AtRuleInvalid("apply".into()), 0, 7);
+
+    assert!(StyleSheet::parse(".a { width: calc(1px + 2px) }", strict_options(false, true)).is_ok());
+    assert!(StyleSheet::parse(".a { width: calc(1px + red) }", strict_options(false, true)).is_ok());
+    assert!(StyleSheet::parse(".a { width: 1px red }", strict_options(false, true)).is_err());
+  }
+
+  #[test]
+  fn test_empty_input() {
+    for source in ["", " \n\t ", "/* comment */", "\n/* a */ /* b */\n"] {
+      for minify in [false, true] {
+        for css_modules in [false, true] {
+          let mut stylesheet = StyleSheet::parse(
+            source,
+            ParserOptions {
+              filename: "test.css".into(),
+              css_modules: if css_modules { Some(Default::default()) } else { None },
+              ..ParserOptions::default()
+            },
+          )
+          .unwrap();
+          stylesheet.minify(MinifyOptions::default()).unwrap();
+          let mut sm = parcel_sourcemap::SourceMap::new("/");
+          sm.add_source("test.css");
+          sm.set_source_content(0, source).unwrap();
+          let res = stylesheet
+            .to_css(PrinterOptions {
+              minify,
+              source_map: Some(&mut sm),
+              ..PrinterOptions::default()
+            })
+            .unwrap();
+          assert_eq!(res.code, "");
+          assert_eq!(res.exports, if css_modules { Some(HashMap::new()) } else { None });
+          let map: serde_json::Value = serde_json::from_str(&sm.to_json(None).unwrap()).unwrap();
+          assert_eq!(map["version"], 3);
+          assert_eq!(map["mappings"], "");
+          assert_eq!(map["sources"], serde_json::json!(["test.css"]));
+          assert_eq!(map["sourcesContent"], serde_json::json!([source]));
+        }
+      }
+
+      let mut attr = StyleAttribute::parse(source, ParserOptions::default()).unwrap();
+      attr.minify(MinifyOptions::default());
+      assert_eq!(attr.to_css(PrinterOptions::default()).unwrap().code, "");
+    }
+
+    let stylesheet = StyleSheet::parse("/*! license */", ParserOptions::default()).unwrap();
+    let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
+    assert_eq!(res.code, "/*! license */\n");
+  }
+
+  #[test]
+  fn test_comments() {
+    fn comments_test(source: &str, parse: Comments, print: Comments, minify: bool, expected: &str) {
+      let stylesheet = StyleSheet::parse(
+        source,
+        ParserOptions {
+          comments: parse,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify,
+          comments: print,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    let source = r#"
+      /*! banner */
+      .a { color: red }
+      /* internal note */
+      /*! normalize.css v8 | MIT License */
+      .b { color: red }
+      @media print {
+        /** @license Apache-2.0 */
+        .c { color: red }
+        /* trailing */
+      }
+    "#;
+
+    comments_test(
+      source,
+      Comments::None,
+      Comments::All,
+      true,
+      "/*! banner */\n.a{color:red}.b{color:red}@media print{.c{color:red}}",
+    );
+    comments_test(
+      source,
+      Comments::License,
+      Comments::License,
+      true,
+      "/*! banner */\n.a{color:red}/*! normalize.css v8 | MIT License */.b{color:red}@media print{/** @license Apache-2.0 */.c{color:red}}",
+    );
+    comments_test(
+      source,
+      Comments::All,
+      Comments::All,
+      false,
+      indoc! {r#"
+        /*! banner */
+        .a {
+          color: red;
+        }
+
+        /* internal note */
+        /*! normalize.css v8 | MIT License */
+        .b {
+          color: red;
+        }
+
+        @media print {
+          /** @license Apache-2.0 */
+          .c {
+            color: red;
+          }
+
+          /* trailing */
+        }
+      "#},
+    );
+    comments_test("/* only */", Comments::All, Comments::License, false, "");
+    comments_test(
+      ".a { color: red }\n/* note */\n/*# sourceMappingURL=a.css.map */",
+      Comments::All,
+      Comments::All,
+      true,
+      ".a{color:red}/* note */",
+    );
+
+    let source = r#"
+      /* Buttons */
+      .btn {
+        /* Base */
+        color: red;
+        /* Spacing */
+        padding: 0 /* inside */ 4px;
+        /* Nested */
+        &:hover {
+          color: blue; /* Hover */
+        }
+        /* After nested */
+      }
+      /* Footer */
+    "#;
+    let stylesheet = StyleSheet::parse(
+      source,
+      ParserOptions {
+        nesting: true,
+        comments: Comments::All,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        comments: Comments::All,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(
+      res.code,
+      indoc! {r#"
+        /* Buttons */
+        .btn {
+          /* Base */
+          color: red;
+          /* Spacing */
+          padding: 0 4px;
+
+          /* Nested */
+          &:hover {
+            color: #00f;
+            /* Hover */
+          }
+
+          /* After nested */
+        }
+
+        /* Footer */
+      "#}
+    );
+
+    let attr = StyleAttribute::parse(
+      "/* a */ color: red; /* b */ width: 10px /* c */; /* d */",
+      ParserOptions {
+        comments: Comments::All,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let res = attr
+      .to_css(PrinterOptions {
+        comments: Comments::All,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(res.code, "/* a */ color: red; /* b */ width: 10px /* d */");
+  }
+
+  #[test]
+  fn test_declaration_comments_minify() {
+    fn comments_test(source: &str, options: MinifyOptions, expected: &str) {
+      let mut stylesheet = StyleSheet::parse(
+        source,
+        ParserOptions {
+          comments: Comments::All,
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      stylesheet.minify(options).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          comments: Comments::All,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    // Declarations are sorted between comments, and not moved across them.
+    comments_test(
+      ".a { width: 1px; color: red; /* b */ height: 2px; background: blue }",
+      MinifyOptions {
+        sort_declarations: true,
+        ..MinifyOptions::default()
+      },
+      ".a{color:red;width:1px;/* b */background:#00f;height:2px}",
+    );
+    comments_test(
+      ".a { color: red; /* i */ width: 1px !important; /* j */ height: 1px !important; background: red !important; /* end */ }",
+      MinifyOptions {
+        sort_declarations: true,
+        ..MinifyOptions::default()
+      },
+      ".a{color:red;/* i */width:1px!important;/* j */background:red!important;height:1px!important/* end */}",
+    );
+
+    // Longhands are not combined across comments.
+    comments_test(
+      ".a { margin-top: 0; margin-right: 0; /* b */ margin-bottom: 0; margin-left: 0 }",
+      MinifyOptions::default(),
+      ".a{margin-top:0;margin-right:0;/* b */margin-bottom:0;margin-left:0}",
+    );
+    comments_test(
+      ".a { margin-top: 0; margin-right: 0; margin-bottom: 0; margin-left: 0; /* b */ }",
+      MinifyOptions::default(),
+      ".a{margin:0/* b */}",
+    );
+
+    // Comments stay before the same declarations when others are removed.
+    comments_test(
+      ".a { color: red; /* b */ color: blue; /* c */ width: 1px; width: 2px !important; /* d */ }",
+      MinifyOptions {
+        deduplicate_declarations: true,
+        ..MinifyOptions::default()
+      },
+      ".a{/* b */color:#00f;/* c */width:2px!important/* d */}",
+    );
+    comments_test(
+      ".a { color: red; --unused: 1px !important; /* b */ width: 1px !important }",
+      MinifyOptions {
+        remove_unused_custom_properties: true,
+        ..MinifyOptions::default()
+      },
+      ".a{color:red;/* b */width:1px!important}",
+    );
+
+    // Comments are kept when merging rules with the same selectors.
+    comments_test(
+      ".a { color: red; /* x */ } .a { /* y */ width: 1px !important; /* z */ }",
+      MinifyOptions::default(),
+      ".a{color:red;/* x *//* y */width:1px!important/* z */}",
+    );
+  }
+
+  #[test]
+  fn test_source_map_url() {
+    fn source_map_url_test(source: &str, minify: bool, expected: &str) {
+      let stylesheet = StyleSheet::parse(&source, ParserOptions::default()).unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify,
+          source_map_url: Some("../maps/out.css.map"),
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    source_map_url_test(
+      ".foo { color: red }",
+      false,
+      ".foo {\n  color: red;\n}\n/*# sourceMappingURL=../maps/out.css.map */\n",
+    );
+    source_map_url_test(
+      ".foo { color: red }",
+      true,
+      ".foo{color:red}\n/*# sourceMappingURL=../maps/out.css.map */\n",
+    );
+    source_map_url_test("", true, "/*# sourceMappingURL=../maps/out.css.map */\n");
+  }
+
+  #[test]
+  #[cfg(feature = "serde")]
+  fn test_snapshot_hash() {
+    fn hash(source: &str) -> String {
+      StyleSheet::parse(source, ParserOptions::default()).unwrap().snapshot_hash()
+    }
+
+    let corpus = [
+      (
+        ".foo { color: red; background: url(a.png) }",
+        "/* header */\n.foo {\n  /* color */ color: red;\n  background: url(a.png);\n}\n",
+        ".foo { color: blue; background: url(a.png) }",
+      ),
+      (
+        "@media (min-width: 100px) { .a > .b { margin: 0 auto } }",
+        "@media (min-width:100px){.a>.b{margin:0 auto}}",
+        "@media (min-width: 200px) { .a > .b { margin: 0 auto } }",
+      ),
+      (
+        "@keyframes fade { from { opacity: 0 } to { opacity: 1 } }",
+        "\n\n@keyframes fade {\n  from {\n    opacity: 0;\n  }\n  /* end */\n  to {\n    opacity: 1;\n  }\n}",
+        "@keyframes fade { from { opacity: 0 } to { opacity: .5 } }",
+      ),
+      (
+        ".foo { --bar: calc(1px + 2px); width: var(--bar) }",
+        ".foo{--bar:calc(1px + 2px);width:var(--bar)}",
+        ".foo { --bar: calc(1px + 3px); width: var(--bar) }",
+      ),
+    ];
+
+    for (source, reformatted, changed) in corpus {
+      let expected = hash(source);
+      assert_eq!(expected.len(), 16);
+      assert_eq!(hash(source), expected);
+      assert_eq!(hash(reformatted), expected);
+      assert_ne!(hash(changed), expected);
+    }
+  }
+
+  #[test]
+  #[cfg(feature = "serde")]
+  fn test_ast_round_trip() {
+    let source = r#"
+      @import "a.css" screen;
+      @custom-selector :--heading h1, h2;
+      @media (min-width: 100px) and (max-width: 200px), print {
+        .foo {
+          color: red;
+          & .bar { margin: 0 auto }
+          @media (orientation: landscape) { & { width: calc(100% - 2px) } }
+        }
+      }
+      @supports (display: grid) and (not (display: inline-grid)) {
+        .grid { display: grid; grid-template-areas: "a b" "c d" }
+      }
+      @unknown-rule foo bar { .baz { color: blue } }
+      @unknown-statement url(x.css);
+      @keyframes fade { from { opacity: 0 } to { opacity: 1 } }
+      @font-face { font-family: Foo; src: url(foo.woff2) format("woff2") }
+      .x:not(.y) > .z::before { content: "x"; --custom: { a: b }; transform: rotate(45deg) }
+    "#;
+
+    let mut stylesheet = StyleSheet::parse(
+      source,
+      ParserOptions {
+        nesting: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    stylesheet.minify(MinifyOptions::default()).unwrap();
+
+    let json = serde_json::to_string(&stylesheet).unwrap();
+    let deserialized: StyleSheet = serde_json::from_str(&json).unwrap();
+
+    for minify in [false, true] {
+      let options = || PrinterOptions {
+        minify,
+        ..PrinterOptions::default()
+      };
+      assert_eq!(
+        deserialized.to_css(options()).unwrap().code,
+        stylesheet.to_css(options()).unwrap().code
+      );
+    }
+
+    assert_eq!(
+      deserialized
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap()
+        .code,
+      concat!(
+        "@import \"a.css\" screen;",
+        "@custom-selector :--heading h1,h2;",
+        "@media (min-width:100px) and (max-width:200px),print{",
+        ".foo{color:red;& .bar{margin:0 auto}@media (orientation:landscape){&{width:calc(100% - 2px)}}}}",
+        "@supports (display: grid) and (not (display: inline-grid)){.grid{grid-template-areas:\"a b\"\"c d\";display:grid}}",
+        "@unknown-rule foo bar{.baz { color: blue }}",
+        "@unknown-statement url(x.css);",
+        "@keyframes fade{0%{opacity:0}to{opacity:1}}",
+        "@font-face{font-family:Foo;src:url(foo.woff2)format(\"woff2\")}",
+        ".x:not(.y)>.z:before{content:\"x\";--custom:{ a: b };transform:rotate(45deg)}"
+      )
+    );
+  }
+
+  #[test]
+  fn test_error_codes() {
+    let err = StyleSheet::parse(".foo { color: red } @import \"a.css\";", ParserOptions::default()).unwrap_err();
+    assert_eq!(err.kind.code(), "UnexpectedImportRule");
+
+    let err = StyleSheet::parse(".foo::bar::baz { color: red }", ParserOptions::default()).unwrap_err();
+    assert_eq!(err.kind.code(), "SelectorError");
+
+    let mut stylesheet = StyleSheet::parse(
+      "@media (--not-defined) { .a { color: green } }",
+      ParserOptions {
+        custom_media: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let err = stylesheet
+      .minify(MinifyOptions {
+        targets: Some(Browsers {
+          chrome: Some(95 << 16),
+          ..Browsers::default()
+        }),
+        ..MinifyOptions::default()
+      })
+      .unwrap_err();
+    assert_eq!(err.kind.code(), "CustomMediaNotDefined");
+
+    let stylesheet = StyleSheet::parse(
+      ".foo .bar { composes: baz }",
+      ParserOptions {
+        css_modules: Some(Default::default()),
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let err = stylesheet.to_css(PrinterOptions::default()).unwrap_err();
+    assert_eq!(err.kind.code(), "InvalidComposesSelector");
+
+    assert_eq!(ParserError::AtRuleInvalid("foo".into()).severity(), Severity::Info);
+    assert_eq!(ParserError::InvalidDeclaration.severity(), Severity::Warning);
+  }
+
+  #[test]
+  fn test_printer_warnings() {
+    use std::sync::{Arc, RwLock};
+    let stylesheet = StyleSheet::parse(
+      ".foo { color: red }\n.bar:has(.baz) { color: red }",
+      ParserOptions {
+        filename: "test.css".into(),
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let print = |safari: u32| {
+      let warnings = Arc::new(RwLock::new(Vec::new()));
+      stylesheet
+        .to_css(PrinterOptions {
+          targets: Some(Browsers {
+            safari: Some(safari << 16),
+            ..Browsers::default()
+          }),
+          warnings: Some(warnings.clone()),
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      Arc::try_unwrap(warnings).unwrap().into_inner().unwrap()
+    };
+
+    assert_eq!(
+      print(14),
+      vec![Error {
+        kind: PrinterErrorKind::UnsupportedFeature {
+          feature: ":has()".into()
+        },
+        loc: Some(ErrorLocation {
+          filename: "test.css".into(),
+          line: 1,
+          column: 1
+        })
+      }]
+    );
+    assert_eq!(print(16), vec![]);
+  }
+
+  #[test]
+  fn test_error_recovery() {
+    use std::sync::{Arc, RwLock};
+    let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+    test_with_options(
+      r#"
+      h1(>h1) {
+        color: red;
+      }
+
+      .foo {
+        color: red;
+      }
+
+      .clearfix {
+        *zoom: 1;
+        background: red;
+      }
 
       @media (hover) {
         h1(>h1) {
@@ -21996,12 +26124,237 @@ mod tests {
         color: red;
       }
 
-      .clearfix {
-        background: red;
+      .clearfix {
+        background: red;
+      }
+
+      @media (hover) {
+        .bar {
+          color: red;
+        }
+      }
+    "#},
+      ParserOptions {
+        filename: "test.css".into(),
+        error_recovery: true,
+        warnings: warnings.clone(),
+        ..ParserOptions::default()
+      },
+    );
+    let w = warnings.unwrap();
+    let warnings = w.read().unwrap();
+    assert_eq!(
+      *warnings,
+      vec![
+        Error {
+          kind: ParserError::SelectorError(SelectorError::EmptySelector),
+          loc: Some(ErrorLocation {
+            filename: "test.css".into(),
+            line: 1,
+            column: 7
+          })
+        },
+        Error {
+          kind: ParserError::UnexpectedToken(Token::Delim('*')),
+          loc: Some(ErrorLocation {
+            filename: "test.css".into(),
+            line: 10,
+            column: 9
+          })
+        },
+        Error {
+          kind: ParserError::SelectorError(SelectorError::EmptySelector),
+          loc: Some(ErrorLocation {
+            filename: "test.css".into(),
+            line: 15,
+            column: 9
+          })
+        },
+      ]
+    )
+  }
+
+  #[test]
+  fn test_warnings_limit() {
+    use std::sync::atomic::Ordering;
+    use std::sync::{Arc, RwLock};
+    let source = r#"
+      h1(>h1) { color: red }
+      .foo { *zoom: 1 }
+      h2(>h2) { color: red }
+      .bar { color: red }
+    "#;
+
+    let test = |limit: Option<usize>| {
+      let options = ParserOptions {
+        filename: "test.css".into(),
+        error_recovery: true,
+        warnings: Some(Arc::new(RwLock::new(Vec::new()))),
+        warnings_limit: limit,
+        ..ParserOptions::default()
+      };
+      test_with_options(source, ".bar {\n  color: red;\n}\n", options.clone());
+      let count = options.warnings.unwrap().read().unwrap().len();
+      (count, options.warnings_truncated.load(Ordering::Relaxed))
+    };
+
+    assert_eq!(test(None), (3, false));
+    assert_eq!(test(Some(3)), (3, false));
+    assert_eq!(test(Some(2)), (2, true));
+    assert_eq!(test(Some(0)), (0, true));
+  }
+
+  #[test]
+  fn test_error_recovery_declaration_values() {
+    use std::sync::{Arc, RwLock};
+    fn test(source: &str, expected: &str, expected_warnings: &[(u32, u32)], nesting: bool) {
+      let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+      let stylesheet = StyleSheet::parse(
+        source,
+        ParserOptions {
+          filename: "test.css".into(),
+          nesting,
+          error_recovery: true,
+          warnings: warnings.clone(),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+      let warnings = warnings.unwrap();
+      let warnings = warnings.read().unwrap();
+      assert_eq!(
+        warnings
+          .iter()
+          .map(|w| {
+            assert_eq!(w.kind, ParserError::InvalidValue);
+            let loc = w.loc.as_ref().unwrap();
+            (loc.line, loc.column)
+          })
+          .collect::<Vec<_>>(),
+        expected_warnings
+      );
+    }
+
+    // {} blocks are only valid in custom properties.
+    test(
+      ".foo { color: red; width: 10px {} auto; height: 10px }",
+      ".foo{color:red;height:10px}",
+      &[(0, 32)],
+      false,
+    );
+    test(
+      ".foo { color: red; --foo: {}; height: 10px }",
+      ".foo{color:red;--foo:{};height:10px}",
+      &[],
+      false,
+    );
+    // A {} block ends at its own closing brace, so parsing resumes at the first `;` inside it.
+    test(
+      ".foo { color: red; width: 10px {; height: 10px } .bar { color: blue }",
+      ".foo{color:red;height:10px}.bar{color:#00f}",
+      &[(0, 32)],
+      false,
+    );
+
+    // An unterminated string ends at the end of the line.
+    test(
+      ".foo {\n  color: red;\n  content: \"abc;\n  height: 10px;\n}\n.bar { color: blue }",
+      ".foo{color:red;height:10px}.bar{color:#00f}",
+      &[(2, 12)],
+      false,
+    );
+    test(
+      ".foo { color: red; background: url(foo bar); height: 10px }",
+      ".foo{color:red;height:10px}",
+      &[(0, 32)],
+      false,
+    );
+
+    // Stray closing brackets.
+    test(
+      ".foo { color: red; width: 10px); height: 10px }",
+      ".foo{color:red;height:10px}",
+      &[(0, 31)],
+      false,
+    );
+    test(
+      ".foo { color: red; --foo: a]; height: 10px }",
+      ".foo{color:red;height:10px}",
+      &[(0, 28)],
+      false,
+    );
+
+    // An unclosed function would consume the rest of the style sheet.
+    test(
+      ".foo { color: red; width: calc(1px + (2px; height: 10px } .bar { color: blue }",
+      ".foo{color:red;height:10px}.bar{color:#00f}",
+      &[(0, 27)],
+      false,
+    );
+    test(
+      ".foo { color: red; width: rgb(1, 2 } .bar { color: blue }",
+      ".foo{color:red}.bar{color:#00f}",
+      &[(0, 27)],
+      false,
+    );
+
+    // With nesting, declarations before nested rules are kept.
+    test(
+      ".foo { color: red; width: rgb(1, 2; height: 10px; & .bar { color: blue } }",
+      ".foo{color:red;height:10px;& .bar{color:#00f}}",
+      &[(0, 27)],
+      true,
+    );
+  }
+
+  #[test]
+  fn test_error_recovery_preludes() {
+    use std::sync::{Arc, RwLock};
+    let source = r#"
+      @media screen and and (hover) {
+        .foo {
+          color: red;
+        }
+      }
+
+      @supports display: grid {
+        .bar {
+          color: red;
+        }
+      }
+
+      @container my-layout width > 10px {
+        .baz {
+          color: red;
+        }
+      }
+    "#;
+
+    let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+    test_with_options(
+      source,
+      indoc! { r#"
+      @media screen and and (hover) {
+        .foo {
+          color: red;
+        }
+      }
+
+      @supports display: grid {
+        .bar {
+          color: red;
+        }
       }
 
-      @media (hover) {
-        .bar {
+      @container my-layout width > 10px {
+        .baz {
           color: red;
         }
       }
@@ -22013,37 +26366,21 @@ mod tests {
         ..ParserOptions::default()
       },
     );
-    let w = warnings.unwrap();
-    let warnings = w.read().unwrap();
-    assert_eq!(
-      *warnings,
-      vec![
-        Error {
-          kind: ParserError::SelectorError(SelectorError::EmptySelector),
-          loc: Some(ErrorLocation {
-            filename: "test.css".into(),
-            line: 1,
-            column: 7
-          })
-        },
-        Error {
-          kind: ParserError::UnexpectedToken(Token::Delim('*')),
-          loc: Some(ErrorLocation {
-            filename: "test.css".into(),
-            line: 10,
-            column: 9
-          })
-        },
-        Error {
-          kind: ParserError::SelectorError(SelectorError::EmptySelector),
-          loc: Some(ErrorLocation {
-            filename: "test.css".into(),
-            line: 15,
-            column: 9
-          })
-        },
-      ]
-    )
+    assert_eq!(warnings.unwrap().read().unwrap().len(), 3);
+
+    let warnings = Some(Arc::new(RwLock::new(Vec::new())));
+    test_with_options(
+      source,
+      "",
+      ParserOptions {
+        filename: "test.css".into(),
+        error_recovery: true,
+        strict_preludes: true,
+        warnings: warnings.clone(),
+        ..ParserOptions::default()
+      },
+    );
+    assert_eq!(warnings.unwrap().read().unwrap().len(), 3);
   }
 
   #[test]
@@ -22295,6 +26632,171 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_scope() {
+    let targets = Some(Browsers {
+      chrome: Some(95 << 16),
+      ..Browsers::default()
+    });
+
+    scope_test(
+      r#"
+      @scope (.card) to (.content) {
+        img {
+          border: 1px solid black;
+        }
+      }
+    "#,
+      "@scope(.card) to (.content){img{border:1px solid #000}}",
+      None,
+    );
+    scope_test(
+      r#"
+      @scope (.card) to (.content) {
+        img {
+          border: 1px solid black;
+        }
+      }
+    "#,
+      ":where(.card) img:not(:where(.card) :where(.content),:where(.card) :where(.content) *){border:1px solid #000}",
+      targets,
+    );
+    scope_test(
+      r#"
+      @scope (.card) to (.content) {
+        .title::before {
+          content: "";
+        }
+      }
+    "#,
+      ":where(.card) .title:not(:where(.card) :where(.content),:where(.card) :where(.content) *):before{content:\"\"}",
+      targets,
+    );
+    scope_test(
+      r#"
+      @scope (.card, .panel) {
+        :scope {
+          color: red;
+        }
+        :scope > .title, .footer {
+          color: blue;
+        }
+      }
+    "#,
+      "@scope(.card,.panel){:scope{color:red}:scope>.title,.footer{color:#00f}}",
+      None,
+    );
+    scope_test(
+      r#"
+      @scope (.card, .panel) {
+        :scope {
+          color: red;
+        }
+        :scope > .title, .footer {
+          color: blue;
+        }
+      }
+    "#,
+      ":is(.card,.panel){color:red}:is(.card,.panel)>.title,:where(.card,.panel) .footer{color:#00f}",
+      targets,
+    );
+    // `:scope` keeps the specificity of a class, while the implicit scope does not add any.
+    scope_test(
+      r#"
+      @scope (.card) {
+        :scope .title {
+          color: red;
+        }
+        .title {
+          color: blue;
+        }
+      }
+    "#,
+      ":is(.card) .title{color:red}:where(.card) .title{color:#00f}",
+      targets,
+    );
+    scope_test(
+      r#"
+      @scope (.card) {
+        @media (min-width: 500px) {
+          .title {
+            color: red;
+          }
+        }
+      }
+    "#,
+      "@media (min-width:500px){:where(.card) .title{color:red}}",
+      targets,
+    );
+
+    // Without a scope start, the scope is the parent of the owner node, so the rule is always kept.
+    for targets in [None, targets] {
+      scope_test(
+        r#"
+        @scope {
+          img {
+            border: 1px solid black;
+          }
+        }
+      "#,
+        "@scope{img{border:1px solid #000}}",
+        targets,
+      );
+      scope_test(
+        r#"
+        @scope to (.content) {
+          img {
+            border: 1px solid black;
+          }
+        }
+      "#,
+        "@scope to (.content){img{border:1px solid #000}}",
+        targets,
+      );
+    }
+
+    scope_test(
+      r#"
+      @scope (.card) {
+        .title {
+          color: red;
+        }
+      }
+      @scope (.card) {
+        .footer {
+          color: blue;
+        }
+      }
+    "#,
+      "@scope(.card){.title{color:red}.footer{color:#00f}}",
+      None,
+    );
+
+    let mut stylesheet = StyleSheet::parse(
+      "@scope (.card) { .title { color: red } }",
+      ParserOptions {
+        scope: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    stylesheet
+      .minify(MinifyOptions {
+        include: crate::targets::Features::SCOPE,
+        ..MinifyOptions::default()
+      })
+      .unwrap();
+    let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
+    assert_eq!(res.code, ":where(.card) .title {\n  color: red;\n}\n");
+    assert_eq!(res.features_used, crate::targets::Features::SCOPE);
+
+    // @scope is only parsed when enabled.
+    minify_test(
+      "@scope (.card) { .title { color: red } }",
+      "@scope (.card){.title { color: red }}",
+    );
+  }
+
   #[test]
   fn test_unknown_at_rules() {
     minify_test("@foo;", "@foo;");
@@ -22388,4 +26890,195 @@ mod tests {
       },
     );
   }
+
+  #[test]
+  fn test_to_css_writer() {
+    let source = ".foo { color: red; & .bar { width: 10px } }";
+    let stylesheet = StyleSheet::parse(
+      source,
+      ParserOptions {
+        nesting: true,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    let options = || PrinterOptions {
+      minify: true,
+      targets: Some(Browsers {
+        chrome: Some(90 << 16),
+        ..Browsers::default()
+      }),
+      ..PrinterOptions::default()
+    };
+    let mut dest = String::from("/* header */");
+    let res = stylesheet.to_css_writer(&mut dest, options()).unwrap();
+    assert_eq!(res.code, "");
+    assert_eq!(res.features_used, crate::targets::Features::NESTING);
+    assert_eq!(
+      dest,
+      format!("/* header */{}", stylesheet.to_css(options()).unwrap().code)
+    );
+    assert_eq!(dest, "/* header */.foo{color:red}.foo .bar{width:10px}");
+
+    let attr = StyleAttribute::parse("color: yellow; width: 10px", ParserOptions::default()).unwrap();
+    let mut dest = String::new();
+    attr.to_css_writer(&mut dest, options()).unwrap();
+    assert_eq!(dest, "color:#ff0;width:10px");
+  }
+
+  #[test]
+  fn test_prefix_only() {
+    fn prefix_only_test(source: &str, expected: &str, targets: Browsers) {
+      let mut stylesheet = StyleSheet::parse(source, ParserOptions::default()).unwrap();
+      stylesheet
+        .minify(MinifyOptions {
+          targets: Some(targets),
+          prefix_only: true,
+          ..MinifyOptions::default()
+        })
+        .unwrap();
+      let res = stylesheet
+        .to_css(PrinterOptions {
+          minify: true,
+          ..PrinterOptions::default()
+        })
+        .unwrap();
+      assert_eq!(res.code, expected);
+    }
+
+    let safari = Browsers {
+      safari: Some(8 << 16),
+      ..Browsers::default()
+    };
+
+    prefix_only_test(
+      ".a { user-select: none; color: lab(50% 20 30); inset-inline-start: 0 }",
+      ".a{-webkit-user-select:none;user-select:none;color:lab(50% 20 30);inset-inline-start:0}",
+      safari,
+    );
+    prefix_only_test(
+      ".a { -webkit-user-select: none; user-select: none; margin-top: 0; margin-right: 0; margin-bottom: 0; margin-left: 0 } .a { color: red }",
+      ".a{-webkit-user-select:none;user-select:none;margin-top:0;margin-right:0;margin-bottom:0;margin-left:0}.a{color:red}",
+      safari,
+    );
+    prefix_only_test(
+      "@media (min-width: 100px) { .a { user-select: none !important } }",
+      "@media (min-width:100px){.a{-webkit-user-select:none!important;user-select:none!important}}",
+      safari,
+    );
+    prefix_only_test(
+      ".a { user-select: none }",
+      ".a{user-select:none}",
+      Browsers {
+        chrome: Some(100 << 16),
+        ..Browsers::default()
+      },
+    );
+
+    let mut attr = StyleAttribute::parse("user-select: none; color: red", ParserOptions::default()).unwrap();
+    attr.minify(MinifyOptions {
+      targets: Some(safari),
+      prefix_only: true,
+      ..MinifyOptions::default()
+    });
+    let res = attr
+      .to_css(PrinterOptions {
+        minify: true,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(res.code, "-webkit-user-select:none;user-select:none;color:red");
+
+    let mut stylesheet = StyleSheet::parse(
+      ".a { color: red; /* a */ user-select: none; /* b */ }",
+      ParserOptions {
+        comments: Comments::All,
+        ..ParserOptions::default()
+      },
+    )
+    .unwrap();
+    stylesheet
+      .minify(MinifyOptions {
+        targets: Some(safari),
+        prefix_only: true,
+        ..MinifyOptions::default()
+      })
+      .unwrap();
+    let res = stylesheet
+      .to_css(PrinterOptions {
+        minify: true,
+        comments: Comments::All,
+        ..PrinterOptions::default()
+      })
+      .unwrap();
+    assert_eq!(
+      res.code,
+      ".a{color:red;/* a */-webkit-user-select:none;user-select:none/* b */}"
+    );
+  }
+
+  #[test]
+  fn test_error_on_unsupported() {
+    fn unsupported_test(source: &str, targets: Browsers, expected: Result<(), Error<MinifyErrorKind>>) {
+      let mut stylesheet = StyleSheet::parse(
+        source,
+        ParserOptions {
+          filename: "test.css".into(),
+          ..ParserOptions::default()
+        },
+      )
+      .unwrap();
+      let res = stylesheet.minify(MinifyOptions {
+        targets: Some(targets),
+        error_on_unsupported: true,
+        ..MinifyOptions::default()
+      });
+      assert_eq!(res, expected);
+    }
+
+    let targets = Browsers {
+      chrome: Some(100 << 16),
+      safari: Some((14 << 16) | (1 << 8)),
+      ..Browsers::default()
+    };
+
+    unsupported_test(
+      ".a { color: red }\n.b:has(> .c) { color: red }",
+      targets,
+      Err(Error {
+        kind: MinifyErrorKind::UnsupportedFeature {
+          feature: ":has()".into(),
+          targets: vec!["chrome 100".into(), "safari 14.1".into()],
+        },
+        loc: Some(ErrorLocation {
+          filename: "test.css".into(),
+          line: 1,
+          column: 1,
+        }),
+      }),
+    );
+    unsupported_test(
+      "@media (min-width: 100px) { .a:is(.b, :focus-visible) { color: red } }",
+      targets,
+      Err(Error {
+        kind: MinifyErrorKind::UnsupportedFeature {
+          feature: ":focus-visible".into(),
+          targets: vec!["safari 14.1".into()],
+        },
+        loc: Some(ErrorLocation {
+          filename: "test.css".into(),
+          line: 0,
+          column: 29,
+        }),
+      }),
+    );
+    unsupported_test(
+      ".a:has(.b) { color: red }",
+      Browsers {
+        chrome: Some(110 << 16),
+        ..Browsers::default()
+      },
+      Ok(()),
+    );
+  }
 }
@@ -29,6 +29,9 @@ struct CliArgs {
   /// Enable parsing custom media queries
   #[clap(long, value_parser)]
   custom_media: bool,
+  /// Enable parsing @scope rules
+  #[clap(long, value_parser)]
+  scope: bool,
   /// Enable CSS modules in output.
   /// If no filename is provided, <output_file>.json will be used.
   /// If no --output-file is specified, code and exports will be printed to stdout as JSON.
@@ -45,6 +48,10 @@ struct CliArgs {
   bundle: bool,
   #[clap(short, long, value_parser)]
   targets: Vec<String>,
+  /// Read the targets from a browserslist config: a package.json or .browserslistrc file,
+  /// or a directory to find one in. Ignored when --targets is given.
+  #[clap(long, value_parser)]
+  browserslist_config_path: Option<String>,
   #[clap(long, value_parser)]
   error_recovery: bool,
 }
@@ -102,18 +109,41 @@ pub fn main() -> Result<(), std::io::Error> {
     None
   };
 
+  let map_filename = match &cli_args.output_file {
+    Some(output_file) if cli_args.sourcemap => Some(output_file.to_owned() + ".map"),
+    _ => None,
+  };
+
   let res = {
     let mut options = ParserOptions {
       nesting: cli_args.nesting,
       css_modules,
       custom_media: cli_args.custom_media,
+      scope: cli_args.scope,
       error_recovery: cli_args.error_recovery,
       warnings: warnings.clone(),
       ..ParserOptions::default()
     };
 
+    let targets = if !cli_args.targets.is_empty() {
+      Browsers::from_browserslist(cli_args.targets).unwrap()
+    } else if let Some(path) = &cli_args.browserslist_config_path {
+      if Path::new(path).exists() {
+        Browsers::from_browserslist_config(path).unwrap()
+      } else {
+        eprintln!(
+          "Warning: browserslist config {} does not exist, compiling without targets",
+          path
+        );
+        None
+      }
+    } else {
+      None
+    };
+
     let mut stylesheet = if cli_args.bundle {
       let mut bundler = Bundler::new(&fs, source_map.as_mut(), options);
+      bundler.targets(targets);
       bundler.bundle(Path::new(&cli_args.input_file)).unwrap()
     } else {
       if let Some(sm) = &mut source_map {
@@ -124,11 +154,6 @@ pub fn main() -> Result<(), std::io::Error> {
       StyleSheet::parse(&source, options).unwrap()
     };
 
-    let targets = if cli_args.targets.is_empty() {
-      None
-    } else {
-      Browsers::from_browserslist(cli_args.targets).unwrap()
-    };
     stylesheet
       .minify(MinifyOptions {
         targets,
@@ -141,6 +166,7 @@ pub fn main() -> Result<(), std::io::Error> {
         minify: cli_args.minify,
         source_map: source_map.as_mut(),
         targets,
+        source_map_url: map_filename.as_deref(),
         ..PrinterOptions::default()
       })
       .unwrap()
@@ -173,16 +199,11 @@ pub fn main() -> Result<(), std::io::Error> {
   }
 
   if let Some(output_file) = &cli_args.output_file {
-    let mut code = res.code;
-    if cli_args.sourcemap {
-      if let Some(map_buf) = map {
-        let map_filename: String = output_file.to_owned() + ".map";
-        code += &format!("\n/*# sourceMappingURL={} */\n", map_filename);
-        fs::write(map_filename, map_buf)?;
-      }
+    if let (Some(map_filename), Some(map_buf)) = (map_filename, map) {
+      fs::write(map_filename, map_buf)?;
     }
 
-    fs::write(output_file, code.as_bytes())?;
+    fs::write(output_file, res.code.as_bytes())?;
 
     if let Some(css_modules) = cli_args.css_modules {
       let css_modules_filename = if let Some(name) = css_modules {
@@ -3,15 +3,18 @@
 use crate::compat::Feature;
 use crate::error::{ErrorWithLocation, MinifyError, MinifyErrorKind, ParserError, PrinterError};
 use crate::macros::enum_property;
+use crate::prefixes::Feature as PrefixFeature;
 use crate::printer::Printer;
 use crate::rules::custom_media::CustomMediaRule;
 use crate::rules::Location;
+use crate::targets::{Browsers, Features};
 use crate::traits::{Parse, ToCss};
 use crate::values::number::CSSNumber;
 use crate::values::string::CowArcStr;
 use crate::values::{length::Length, ratio::Ratio, resolution::Resolution};
+use crate::vendor_prefix::VendorPrefix;
 use cssparser::*;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 /// A [media query list](https://drafts.csswg.org/mediaqueries/#mq-list).
 #[derive(Clone, Debug, PartialEq)]
@@ -28,6 +31,17 @@ impl<'i> MediaList<'i> {
     MediaList { media_queries: vec![] }
   }
 
+  /// Creates a media query list from an unparsed query list, which is preserved as written.
+  pub(crate) fn unknown(raw: CowArcStr<'i>) -> Self {
+    MediaList {
+      media_queries: vec![MediaQuery {
+        qualifier: None,
+        media_type: MediaType::All,
+        condition: Some(MediaCondition::Unknown(raw)),
+      }],
+    }
+  }
+
   /// Parse a media query list from CSS.
   pub fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
     let mut media_queries = vec![];
@@ -63,6 +77,39 @@ impl<'i> MediaList<'i> {
     Ok(())
   }
 
+  /// Converts legacy vendor prefixed `device-pixel-ratio` features to standard `resolution`
+  /// features in `dppx`, and adds prefixed fallback queries only when the targets require them.
+  /// Queries that become identical after conversion are deduplicated.
+  pub fn transform_resolution(&mut self, targets: Browsers) {
+    let prefixes = PrefixFeature::AtResolution.prefixes_for(targets);
+    let mut media_queries: Vec<MediaQuery<'i>> = Vec::with_capacity(self.media_queries.len());
+    for mut query in self.media_queries.drain(..) {
+      let has_resolution = match &mut query.condition {
+        Some(condition) => condition.normalize_resolution(),
+        None => false,
+      };
+
+      if has_resolution {
+        // Opera's ratio based syntax is only parsed, not generated.
+        for prefix in [VendorPrefix::WebKit, VendorPrefix::Moz] {
+          if prefixes.contains(prefix) {
+            let mut prefixed = query.clone();
+            if prefixed.condition.as_mut().unwrap().prefix_resolution(prefix) && !media_queries.contains(&prefixed)
+            {
+              media_queries.push(prefixed);
+            }
+          }
+        }
+      }
+
+      if !media_queries.contains(&query) {
+        media_queries.push(query);
+      }
+    }
+
+    self.media_queries = media_queries;
+  }
+
   /// Returns whether the media query list always matches.
   pub fn always_matches(&self) -> bool {
     // If the media list is empty, it always matches.
@@ -227,7 +274,7 @@ impl<'i> MediaQuery<'i> {
         &mut self.media_type,
         &mut self.qualifier,
         condition,
-        &mut HashSet::new(),
+        &mut Vec::new(),
       )?;
       if !used {
         self.condition = None;
@@ -251,6 +298,13 @@ impl<'i> MediaQuery<'i> {
   ///
   /// Returns an error if the boolean logic is not possible.
   pub fn and<'a>(&mut self, b: &MediaQuery<'i>) -> Result<(), ()> {
+    // Unparsed queries cannot be combined with anything else.
+    if matches!(self.condition, Some(MediaCondition::Unknown(_)))
+      || matches!(b.condition, Some(MediaCondition::Unknown(_)))
+    {
+      return Err(());
+    }
+
     let at = (&self.qualifier, &self.media_type);
     let bt = (&b.qualifier, &b.media_type);
     let (qualifier, media_type) = match (at, bt) {
@@ -378,6 +432,9 @@ pub enum MediaCondition<'i> {
   Operation(Vec<MediaCondition<'i>>, Operator),
   /// A condition wrapped in parenthesis.
   InParens(Box<MediaCondition<'i>>),
+  /// A media query list that could not be parsed, preserved as written.
+  /// Only produced when error recovery is enabled.
+  Unknown(CowArcStr<'i>),
 }
 
 impl<'i> MediaCondition<'i> {
@@ -440,6 +497,31 @@ impl<'i> MediaCondition<'i> {
       Ok(MediaCondition::Feature(feature))
     })
   }
+
+  /// Normalizes all resolution features within the condition, and returns whether any were found.
+  fn normalize_resolution(&mut self) -> bool {
+    match self {
+      MediaCondition::Feature(f) => f.normalize_resolution(),
+      MediaCondition::Not(c) | MediaCondition::InParens(c) => c.normalize_resolution(),
+      MediaCondition::Operation(conditions, _) => {
+        conditions.iter_mut().fold(false, |found, c| c.normalize_resolution() || found)
+      }
+      MediaCondition::Unknown(_) => false,
+    }
+  }
+
+  /// Replaces all resolution features within the condition with the vendor prefixed
+  /// `device-pixel-ratio` equivalent, and returns whether any were replaced.
+  fn prefix_resolution(&mut self, prefix: VendorPrefix) -> bool {
+    match self {
+      MediaCondition::Feature(f) => f.prefix_resolution(prefix),
+      MediaCondition::Not(c) | MediaCondition::InParens(c) => c.prefix_resolution(prefix),
+      MediaCondition::Operation(conditions, _) => conditions
+        .iter_mut()
+        .fold(false, |found, c| c.prefix_resolution(prefix) || found),
+      MediaCondition::Unknown(_) => false,
+    }
+  }
 }
 
 impl<'i> ToCss for MediaCondition<'i> {
@@ -469,6 +551,7 @@ impl<'i> ToCss for MediaCondition<'i> {
         }
         Ok(())
       }
+      MediaCondition::Unknown(ref raw) => dest.write_str(raw),
     }
   }
 }
@@ -633,6 +716,113 @@ impl<'i> MediaFeature<'i> {
       Ok(MediaFeature::Range { name, operator, value })
     }
   }
+
+  /// Converts a legacy vendor prefixed `device-pixel-ratio` feature to the equivalent
+  /// `resolution` feature, and converts `resolution` values to `dppx`. Returns whether
+  /// this is a resolution feature.
+  fn normalize_resolution(&mut self) -> bool {
+    match self {
+      MediaFeature::Plain { name, value } => {
+        if let Some((_, operator)) = parse_device_pixel_ratio(name) {
+          let dppx = match value {
+            MediaFeatureValue::Number(n) => *n,
+            MediaFeatureValue::Ratio(Ratio(a, b)) if *b != 0.0 => *a / *b,
+            _ => return false,
+          };
+
+          *self = MediaFeature::Plain {
+            name: match operator {
+              MediaFeatureComparison::GreaterThanEqual => "min-resolution",
+              MediaFeatureComparison::LessThanEqual => "max-resolution",
+              _ => "resolution",
+            }
+            .into(),
+            value: MediaFeatureValue::Resolution(Resolution::Dppx(dppx)),
+          };
+          return true;
+        }
+
+        match_ignore_ascii_case! { &*name,
+          "resolution" | "min-resolution" | "max-resolution" => normalize_resolution_value(value),
+          _ => false
+        }
+      }
+      MediaFeature::Range { name, value, .. } if name.eq_ignore_ascii_case("resolution") => {
+        normalize_resolution_value(value)
+      }
+      _ => false,
+    }
+  }
+
+  /// Replaces a `resolution` feature with the vendor prefixed `device-pixel-ratio` equivalent.
+  /// Returns false if the feature cannot be expressed with the prefixed syntax.
+  fn prefix_resolution(&mut self, prefix: VendorPrefix) -> bool {
+    let (operator, dppx) = match self {
+      MediaFeature::Plain {
+        name,
+        value: MediaFeatureValue::Resolution(Resolution::Dppx(dppx)),
+      } => {
+        let operator = match_ignore_ascii_case! { &*name,
+          "resolution" => MediaFeatureComparison::Equal,
+          "min-resolution" => MediaFeatureComparison::GreaterThanEqual,
+          "max-resolution" => MediaFeatureComparison::LessThanEqual,
+          _ => return false
+        };
+        (operator, *dppx)
+      }
+      MediaFeature::Range {
+        name,
+        operator:
+          operator @ (MediaFeatureComparison::Equal
+          | MediaFeatureComparison::GreaterThanEqual
+          | MediaFeatureComparison::LessThanEqual),
+        value: MediaFeatureValue::Resolution(Resolution::Dppx(dppx)),
+      } if name.eq_ignore_ascii_case("resolution") => (*operator, *dppx),
+      _ => return false,
+    };
+
+    let name = match (prefix, operator) {
+      (VendorPrefix::WebKit, MediaFeatureComparison::GreaterThanEqual) => "-webkit-min-device-pixel-ratio",
+      (VendorPrefix::WebKit, MediaFeatureComparison::LessThanEqual) => "-webkit-max-device-pixel-ratio",
+      (VendorPrefix::WebKit, _) => "-webkit-device-pixel-ratio",
+      (VendorPrefix::Moz, MediaFeatureComparison::GreaterThanEqual) => "min--moz-device-pixel-ratio",
+      (VendorPrefix::Moz, MediaFeatureComparison::LessThanEqual) => "max--moz-device-pixel-ratio",
+      (VendorPrefix::Moz, _) => "-moz-device-pixel-ratio",
+      _ => return false,
+    };
+
+    *self = MediaFeature::Plain {
+      name: name.into(),
+      value: MediaFeatureValue::Number(dppx),
+    };
+    true
+  }
+}
+
+/// Parses the name of a legacy vendor prefixed `device-pixel-ratio` media feature,
+/// returning the vendor prefix and the comparison implied by the `min-` or `max-` prefix.
+fn parse_device_pixel_ratio(name: &str) -> Option<(VendorPrefix, MediaFeatureComparison)> {
+  Some(match_ignore_ascii_case! { name,
+    "-webkit-device-pixel-ratio" => (VendorPrefix::WebKit, MediaFeatureComparison::Equal),
+    "-webkit-min-device-pixel-ratio" => (VendorPrefix::WebKit, MediaFeatureComparison::GreaterThanEqual),
+    "-webkit-max-device-pixel-ratio" => (VendorPrefix::WebKit, MediaFeatureComparison::LessThanEqual),
+    "-moz-device-pixel-ratio" => (VendorPrefix::Moz, MediaFeatureComparison::Equal),
+    "min--moz-device-pixel-ratio" => (VendorPrefix::Moz, MediaFeatureComparison::GreaterThanEqual),
+    "max--moz-device-pixel-ratio" => (VendorPrefix::Moz, MediaFeatureComparison::LessThanEqual),
+    "-o-device-pixel-ratio" => (VendorPrefix::O, MediaFeatureComparison::Equal),
+    "-o-min-device-pixel-ratio" => (VendorPrefix::O, MediaFeatureComparison::GreaterThanEqual),
+    "-o-max-device-pixel-ratio" => (VendorPrefix::O, MediaFeatureComparison::LessThanEqual),
+    _ => return None
+  })
+}
+
+fn normalize_resolution_value(value: &mut MediaFeatureValue) -> bool {
+  if let MediaFeatureValue::Resolution(res) = value {
+    *res = Resolution::Dppx(res.to_dppx());
+    true
+  } else {
+    false
+  }
 }
 
 impl<'i> ToCss for MediaFeature<'i> {
@@ -653,10 +843,8 @@ impl<'i> ToCss for MediaFeature<'i> {
       }
       MediaFeature::Range { name, operator, value } => {
         // If range syntax is unsupported, use min/max prefix if possible.
-        if let Some(targets) = dest.targets {
-          if !Feature::MediaRangeSyntax.is_compatible(targets) {
-            return write_min_max(operator, name, value, dest);
-          }
+        if dest.should_compile(Features::MEDIA_RANGE_SYNTAX, Feature::MediaRangeSyntax) {
+          return write_min_max(operator, name, value, dest);
         }
 
         serialize_identifier(name, dest)?;
@@ -852,7 +1040,7 @@ fn process_condition<'i>(
   media_type: &mut MediaType<'i>,
   qualifier: &mut Option<Qualifier>,
   condition: &mut MediaCondition<'i>,
-  seen: &mut HashSet<CowArcStr<'i>>,
+  seen: &mut Vec<CowArcStr<'i>>,
 ) -> Result<bool, MinifyError> {
   match condition {
     MediaCondition::Not(cond) => {
@@ -906,9 +1094,16 @@ fn process_condition<'i>(
         return Ok(true);
       }
 
-      if seen.contains(name) {
+      // `seen` holds the chain of custom media currently being expanded, in reference order,
+      // so that the full cycle can be reported.
+      if let Some(index) = seen.iter().position(|n| n == name) {
+        let mut cycle: Vec<String> = seen[index..].iter().map(|n| n.to_string()).collect();
+        cycle.push(name.to_string());
         return Err(ErrorWithLocation {
-          kind: MinifyErrorKind::CircularCustomMedia { name: name.to_string() },
+          kind: MinifyErrorKind::CircularCustomMedia {
+            name: name.to_string(),
+            cycle,
+          },
           loc,
         });
       }
@@ -918,7 +1113,7 @@ fn process_condition<'i>(
         loc,
       })?;
 
-      seen.insert(name.clone());
+      seen.push(name.clone());
 
       let mut res = Ok(true);
       let mut conditions: Vec<MediaCondition> = rule
@@ -966,7 +1161,7 @@ fn process_condition<'i>(
         })
         .collect();
 
-      seen.remove(name);
+      seen.pop();
 
       if res.is_err() {
         return res;
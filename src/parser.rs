@@ -6,12 +6,17 @@ use crate::rules::container::{ContainerName, ContainerRule};
 use crate::rules::font_palette_values::FontPaletteValuesRule;
 use crate::rules::layer::{LayerBlockRule, LayerStatementRule};
 use crate::rules::property::PropertyRule;
+use crate::rules::scope::ScopeRule;
 use crate::rules::viewport::ViewportRule;
 use crate::rules::{
+  comment::CommentRule,
   counter_style::CounterStyleRule,
+  custom_at_rule::{
+    CustomAtRule, CustomAtRuleBody, CustomAtRuleBodySyntax, CustomAtRuleDefinition, CustomAtRulePrelude,
+  },
   custom_media::CustomMediaRule,
   document::MozDocumentRule,
-  font_face::{FontFaceDeclarationParser, FontFaceRule},
+  font_face::FontFaceRule,
   import::ImportRule,
   keyframes::{KeyframeListParser, KeyframesName, KeyframesRule},
   layer::LayerName,
@@ -24,14 +29,18 @@ use crate::rules::{
   unknown::UnknownAtRule,
   CssRule, CssRuleList, Location,
 };
-use crate::selector::{SelectorParser, Selectors};
+use crate::selector::{contains_nesting, prepend_nesting, SelectorParser, Selectors};
 use crate::traits::Parse;
 use crate::values::ident::{CustomIdent, DashedIdent};
 use crate::values::string::CowArcStr;
 use crate::vendor_prefix::VendorPrefix;
 use cssparser::*;
-use parcel_selectors::{parser::NestingRequirement, SelectorList};
-use std::collections::HashMap;
+use parcel_selectors::{
+  parser::{Combinator, NestingRequirement},
+  SelectorList,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
 /// CSS parsing options.
@@ -41,25 +50,180 @@ pub struct ParserOptions<'o, 'i> {
   pub filename: String,
   /// Whether the enable the [CSS nesting](https://www.w3.org/TR/css-nesting-1/) draft syntax.
   pub nesting: bool,
+  /// Which version of the CSS nesting spec to follow when `nesting` is enabled.
+  pub nesting_spec: NestingSpec,
   /// Whether to enable the [custom media](https://drafts.csswg.org/mediaqueries-5/#custom-mq) draft syntax.
   pub custom_media: bool,
+  /// Whether to enable the [@scope](https://drafts.csswg.org/css-cascade-6/#scope-atrule) draft syntax.
+  pub scope: bool,
   /// Whether the enable [CSS modules](https://github.com/css-modules/css-modules).
   pub css_modules: Option<crate::css_modules::Config<'o>>,
   /// The source index to assign to all parsed rules. Impacts the source map when
   /// the style sheet is serialized.
   pub source_index: u32,
-  /// Whether to ignore invalid rules and declarations rather than erroring.
+  /// Whether to ignore invalid rules and declarations rather than erroring. Declarations with an
+  /// unterminated string, unmatched brackets, or an unclosed function are skipped without losing
+  /// the declarations after them.
   pub error_recovery: bool,
+  /// With error recovery, `@media`, `@supports`, and `@container` rules with an invalid prelude
+  /// are kept with the prelude as written, and their block is parsed normally. When this is
+  /// enabled, the whole rule is dropped instead.
+  pub strict_preludes: bool,
+  /// Whether to return an error for unknown properties, invalid values of known properties, and unknown
+  /// at-rules, which are otherwise kept as written. This takes precedence over `error_recovery`.
+  pub strict: bool,
+  /// Names of properties, and of at-rules including the `@`, that are allowed in `strict` mode even
+  /// though they are unknown or have an invalid value.
+  pub strict_exceptions: Option<HashSet<String>>,
+  /// Whether to keep math functions such as `calc()` as written. By default, they are
+  /// simplified while parsing, e.g. `calc(1px + 2px)` becomes `3px`. When enabled, declarations
+  /// containing math functions are stored as unparsed token lists instead.
+  pub preserve_calc: bool,
+  /// Whether to keep the fallbacks of `var()` references exactly as written. By default, fallbacks
+  /// are parsed and minified along with the rest of the value.
+  pub preserve_var_fallbacks: bool,
+  /// Whether to parse the non-standard `:deep()`, `::v-deep`, and `::ng-deep` selectors, and the legacy
+  /// `>>>` and `/deep/` combinators, used by component frameworks such as Vue and Angular to style the
+  /// children of scoped components. The combinators are printed as written, and never merged.
+  pub deep_selectors: bool,
+  /// Names of properties to parse as unknown properties, and print exactly as written. This is useful
+  /// for made-up properties consumed by a runtime, or to prevent a known property from being changed.
+  pub ignore_properties: Option<HashSet<String>>,
+  /// Definitions of at-rules that are not part of CSS, e.g. the `@tailwind` and `@apply` directives, by name
+  /// without the `@`. These are parsed into [CustomAtRule](crate::rules::custom_at_rule::CustomAtRule)s
+  /// and printed as written. They may also be used within style rules, even without `nesting`.
+  pub custom_at_rules: Option<HashMap<String, CustomAtRuleDefinition>>,
+  /// Whether to parse all declarations as unknown properties, and print their values as written with
+  /// only whitespace and comments compacted. Combined with printing a style sheet that has not been
+  /// [minified](crate::stylesheet::StyleSheet::minify), this guarantees that no value is rewritten.
+  pub verbatim_values: bool,
   /// A list that will be appended to when a warning occurs.
   pub warnings: Option<Arc<RwLock<Vec<Error<ParserError<'i>>>>>>,
+  /// The maximum number of warnings to append to `warnings`. Once it is reached, further
+  /// warnings are dropped, and `warnings_truncated` is set.
+  pub warnings_limit: Option<usize>,
+  /// Set when a warning is dropped because `warnings_limit` was reached.
+  pub warnings_truncated: Arc<AtomicBool>,
+  /// Which comments between rules and declarations to keep as [CommentRule](crate::rules::comment::CommentRule)s.
+  /// License comments before the first rule of a style sheet are always kept separately. Comments within
+  /// values and preludes are not kept.
+  pub comments: Comments,
+}
+
+/// Which comments to keep while parsing, or to print.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comments {
+  /// No comments.
+  None,
+  /// License comments, i.e. those starting with `!`, or containing a `@license` or `@preserve` annotation.
+  License,
+  /// All comments.
+  All,
+}
+
+impl Default for Comments {
+  fn default() -> Comments {
+    Comments::None
+  }
+}
+
+/// A version of the [CSS nesting](https://www.w3.org/TR/css-nesting-1/) spec.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NestingSpec {
+  /// Nested style rules must start with `&`. Other selectors must be nested using `@nest`.
+  Legacy,
+  /// Nested selectors that do not contain `&` are relative to the parent rule, as if
+  /// prefixed with `& `, and may start with a combinator. `@nest` is not supported.
+  Current,
+}
+
+impl Default for NestingSpec {
+  fn default() -> NestingSpec {
+    NestingSpec::Legacy
+  }
 }
 
 impl<'o, 'i> ParserOptions<'o, 'i> {
   #[inline]
   pub(crate) fn warn(&self, warning: ParseError<'i, ParserError<'i>>) {
+    self.push_warning(|| Error::from(warning, self.filename.clone()));
+  }
+
+  /// Returns whether the property with the given name is listed in `ignore_properties`,
+  /// or `verbatim_values` is enabled.
+  pub(crate) fn is_ignored_property(&self, name: &str) -> bool {
+    if self.verbatim_values {
+      return true;
+    }
+    match &self.ignore_properties {
+      Some(properties) => properties.contains(name) || properties.contains(&name.to_ascii_lowercase()),
+      None => false,
+    }
+  }
+
+  /// Returns whether the property or at-rule with the given name is listed in `strict_exceptions`.
+  pub(crate) fn is_strict_exception(&self, name: &str) -> bool {
+    match &self.strict_exceptions {
+      Some(exceptions) => exceptions.contains(name) || exceptions.contains(&name.to_ascii_lowercase()),
+      None => false,
+    }
+  }
+
+  /// Parses the prelude of an at-rule defined in `custom_at_rules`, or returns `None` if there is no
+  /// definition with the given name.
+  pub(crate) fn parse_custom_at_rule_prelude<'t>(
+    &self,
+    name: &CowRcStr<'i>,
+    input: &mut Parser<'i, 't>,
+  ) -> Option<Result<AtRulePrelude<'i>, ParseError<'i, ParserError<'i>>>> {
+    let definition = self.custom_at_rules.as_ref()?.get(name.as_ref())?;
+    Some(
+      CustomAtRulePrelude::parse(input, definition.prelude)
+        .map(|prelude| AtRulePrelude::Custom(name.into(), prelude, definition.body)),
+    )
+  }
+
+  /// Appends a warning to the `warnings` list, unless the `warnings_limit` has been reached.
+  /// The warning is only created when it is kept.
+  pub(crate) fn push_warning(&self, warning: impl FnOnce() -> Error<ParserError<'i>>) {
     if let Some(warnings) = &self.warnings {
       if let Ok(mut warnings) = warnings.write() {
-        warnings.push(Error::from(warning, self.filename.clone()));
+        if matches!(self.warnings_limit, Some(limit) if warnings.len() >= limit) {
+          self.warnings_truncated.store(true, Ordering::Relaxed);
+        } else {
+          warnings.push(warning());
+        }
+      }
+    }
+  }
+
+  /// Parses a conditional at-rule prelude. If it is invalid and error recovery is enabled,
+  /// a warning is emitted, and `unknown` is called with the prelude as written so that
+  /// the block is preserved rather than dropped.
+  fn parse_prelude<'t, T>(
+    &self,
+    input: &mut Parser<'i, 't>,
+    parse: impl FnOnce(&mut Parser<'i, 't>) -> Result<T, ParseError<'i, ParserError<'i>>>,
+    unknown: impl FnOnce(CowArcStr<'i>) -> T,
+  ) -> Result<T, ParseError<'i, ParserError<'i>>> {
+    if !self.error_recovery || self.strict_preludes || self.strict {
+      return parse(input);
+    }
+
+    input.skip_whitespace();
+    let start = input.position();
+    let result = input.try_parse(|input| {
+      let result = parse(input)?;
+      input.expect_exhausted()?;
+      Ok(result)
+    });
+
+    match result {
+      Ok(result) => Ok(result),
+      Err(e) => {
+        self.warn(e);
+        while input.next().is_ok() {}
+        Ok(unknown(input.slice_from(start).trim_end().into()))
       }
     }
   }
@@ -146,8 +310,12 @@ pub enum AtRulePrelude<'i> {
   Property(DashedIdent<'i>),
   /// A @container prelude.
   Container(Option<ContainerName<'i>>, MediaCondition<'i>),
+  /// A @scope prelude.
+  Scope(Option<SelectorList<'i, Selectors>>, Option<SelectorList<'i, Selectors>>),
   /// An unknown prelude.
   Unknown(CowArcStr<'i>, TokenList<'i>),
+  /// A prelude of an at-rule defined by the `custom_at_rules` option.
+  Custom(CowArcStr<'i>, Option<CustomAtRulePrelude<'i>>, CustomAtRuleBodySyntax),
 }
 
 impl<'a, 'o, 'i> AtRuleParser<'i> for TopLevelRuleParser<'a, 'o, 'i> {
@@ -280,6 +448,10 @@ impl<'a, 'o, 'i> AtRuleParser<'i> for TopLevelRuleParser<'a, 'o, 'i> {
         AtRuleParser::rule_without_block(&mut self.nested(), prelude, start)?
       }
       AtRulePrelude::Charset => CssRule::Ignored,
+      AtRulePrelude::Custom(..) => {
+        self.state = State::Body;
+        AtRuleParser::rule_without_block(&mut self.nested(), prelude, start)?
+      }
       AtRulePrelude::Unknown(name, prelude) => CssRule::Unknown(UnknownAtRule {
         name,
         prelude,
@@ -339,12 +511,18 @@ impl<'a, 'o, 'b, 'i> NestedRuleParser<'a, 'o, 'i> {
 
     let mut iter = RuleListParser::new_for_nested_rule(input, nested_parser);
     let mut rules = Vec::new();
-    while let Some(result) = iter.next() {
+    loop {
+      parse_comments(iter.input, self.options, &mut rules);
+      let result = match iter.next() {
+        Some(result) => result,
+        None => break,
+      };
+
       match result {
         Ok(CssRule::Ignored) => {}
         Ok(rule) => rules.push(rule),
         Err((e, _)) => {
-          if self.options.error_recovery {
+          if self.options.error_recovery && !self.options.strict {
             self.options.warn(e);
             continue;
           }
@@ -378,11 +556,11 @@ impl<'a, 'o, 'b, 'i> AtRuleParser<'i> for NestedRuleParser<'a, 'o, 'i> {
   ) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
     match_ignore_ascii_case! { &*name,
       "media" => {
-        let media = MediaList::parse(input)?;
+        let media = self.options.parse_prelude(input, MediaList::parse, MediaList::unknown)?;
         Ok(AtRulePrelude::Media(media))
       },
       "supports" => {
-        let cond = SupportsCondition::parse(input)?;
+        let cond = self.options.parse_prelude(input, SupportsCondition::parse, SupportsCondition::Unknown)?;
         Ok(AtRulePrelude::Supports(cond))
       },
       "font-face" => {
@@ -461,12 +639,47 @@ impl<'a, 'o, 'b, 'i> AtRuleParser<'i> for NestedRuleParser<'a, 'o, 'i> {
         Ok(AtRulePrelude::Layer(names))
       },
       "container" => {
-        let name = input.try_parse(ContainerName::parse).ok();
-        let condition = MediaCondition::parse(input, true)?;
+        let (name, condition) = self.options.parse_prelude(
+          input,
+          |input| {
+            let name = input.try_parse(ContainerName::parse).ok();
+            let condition = MediaCondition::parse(input, true)?;
+            Ok((name, condition))
+          },
+          |raw| (None, MediaCondition::Unknown(raw)),
+        )?;
         Ok(AtRulePrelude::Container(name, condition))
       },
+      "scope" if self.options.scope => {
+        let selector_parser = SelectorParser {
+          default_namespace: self.default_namespace,
+          namespace_prefixes: self.namespace_prefixes,
+          is_nesting_allowed: false,
+          options: &self.options,
+        };
+        let mut parse_selectors = |input: &mut Parser<'i, '_>| -> Result<_, ParseError<'i, Self::Error>> {
+          input.expect_parenthesis_block()?;
+          input.parse_nested_block(|input| SelectorList::parse(&selector_parser, input, NestingRequirement::None))
+        };
+        // Both the scope start and end are optional, e.g. `@scope`, `@scope (.a)`, and `@scope to (.b)`.
+        let scope_start = input.try_parse(&mut parse_selectors).ok();
+        let scope_end = if input.try_parse(|input| input.expect_ident_matching("to")).is_ok() {
+          Some(parse_selectors(input)?)
+        } else {
+          None
+        };
+        Ok(AtRulePrelude::Scope(scope_start, scope_end))
+      },
       _ => {
-        self.options.warn(input.new_error(BasicParseErrorKind::AtRuleInvalid(name.clone())));
+        if let Some(prelude) = self.options.parse_custom_at_rule_prelude(&name, input) {
+          return prelude;
+        }
+
+        let error = input.new_error(BasicParseErrorKind::AtRuleInvalid(name.clone()));
+        if self.options.strict && !self.options.is_strict_exception(&format!("@{}", name)) {
+          return Err(error);
+        }
+        self.options.warn(error);
         input.skip_whitespace();
         let tokens = TokenList::parse(input, &self.options, 0)?;
         Ok(AtRulePrelude::Unknown(name.into(), tokens))
@@ -482,16 +695,7 @@ impl<'a, 'o, 'b, 'i> AtRuleParser<'i> for NestedRuleParser<'a, 'o, 'i> {
   ) -> Result<CssRule<'i>, ParseError<'i, Self::Error>> {
     let loc = self.loc(start);
     match prelude {
-      AtRulePrelude::FontFace => {
-        let mut parser = DeclarationListParser::new(input, FontFaceDeclarationParser);
-        let mut properties = vec![];
-        while let Some(decl) = parser.next() {
-          if let Ok(decl) = decl {
-            properties.push(decl);
-          }
-        }
-        Ok(CssRule::FontFace(FontFaceRule { properties, loc }))
-      }
+      AtRulePrelude::FontFace => Ok(CssRule::FontFace(FontFaceRule::parse(input, loc, self.options)?)),
       // AtRuleBlockPrelude::FontFeatureValues(family_names) => {
       //     let context = ParserContext::new_with_rule_type(
       //         self.context,
@@ -512,11 +716,12 @@ impl<'a, 'o, 'b, 'i> AtRuleParser<'i> for NestedRuleParser<'a, 'o, 'i> {
         let rule = FontPaletteValuesRule::parse(name, input, loc)?;
         Ok(CssRule::FontPaletteValues(rule))
       }
-      AtRulePrelude::CounterStyle(name) => Ok(CssRule::CounterStyle(CounterStyleRule {
+      AtRulePrelude::CounterStyle(name) => Ok(CssRule::CounterStyle(CounterStyleRule::parse(
         name,
-        declarations: DeclarationBlock::parse(input, self.options)?,
+        input,
         loc,
-      })),
+        self.options,
+      )?)),
       AtRulePrelude::Media(query) => Ok(CssRule::Media(MediaRule {
         query,
         rules: self.parse_nested_rules(input)?,
@@ -533,6 +738,12 @@ impl<'a, 'o, 'b, 'i> AtRuleParser<'i> for NestedRuleParser<'a, 'o, 'i> {
         rules: self.parse_nested_rules(input)?,
         loc,
       })),
+      AtRulePrelude::Scope(scope_start, scope_end) => Ok(CssRule::Scope(ScopeRule {
+        scope_start,
+        scope_end,
+        rules: self.parse_nested_rules(input)?,
+        loc,
+      })),
       AtRulePrelude::Viewport(vendor_prefix) => {
         Ok(CssRule::Viewport(ViewportRule {
           vendor_prefix,
@@ -590,6 +801,16 @@ impl<'a, 'o, 'b, 'i> AtRuleParser<'i> for NestedRuleParser<'a, 'o, 'i> {
         block: Some(TokenList::parse(input, &self.options, 0)?),
         loc,
       })),
+      AtRulePrelude::Custom(name, prelude, body) => {
+        let options = self.options;
+        let body = parse_custom_at_rule_body(body, input, options, |input| self.parse_nested_rules(input))?;
+        Ok(CssRule::Custom(CustomAtRule {
+          name,
+          prelude,
+          body: Some(body),
+          loc,
+        }))
+      }
     }
   }
 
@@ -610,6 +831,12 @@ impl<'a, 'o, 'b, 'i> AtRuleParser<'i> for NestedRuleParser<'a, 'o, 'i> {
         block: None,
         loc,
       })),
+      AtRulePrelude::Custom(name, prelude, CustomAtRuleBodySyntax::None) => Ok(CssRule::Custom(CustomAtRule {
+        name,
+        prelude,
+        body: None,
+        loc,
+      })),
       _ => Err(()),
     }
   }
@@ -655,6 +882,176 @@ impl<'a, 'o, 'b, 'i> QualifiedRuleParser<'i> for NestedRuleParser<'a, 'o, 'i> {
   }
 }
 
+/// Consumes the whitespace and comments before the next rule, and appends the comments
+/// to keep according to the `comments` option to `rules`.
+pub(crate) fn parse_comments<'i, 't>(
+  input: &mut Parser<'i, 't>,
+  options: &ParserOptions<'_, 'i>,
+  rules: &mut Vec<CssRule<'i>>,
+) {
+  parse_comments_with(input, options, |comment| rules.push(CssRule::Comment(comment)))
+}
+
+/// Consumes the whitespace and comments before the next declaration, and appends the comments to keep
+/// to `comments`, along with the number of declarations that precede them.
+pub(crate) fn parse_declaration_comments<'i, 't>(
+  input: &mut Parser<'i, 't>,
+  options: &ParserOptions<'_, 'i>,
+  index: usize,
+  comments: &mut Vec<(usize, CommentRule<'i>)>,
+) {
+  parse_comments_with(input, options, |comment| comments.push((index, comment)))
+}
+
+/// With error recovery, skips the next declaration if its value contains tokens that are never valid:
+/// an unterminated string or url, an unmatched `)` or `]`, or a `{}` block outside a custom property.
+/// Some of these also change where the declaration ends, e.g. an unclosed function consumes the rest
+/// of the block, so parsing resumes where the following declarations are not lost: after an unterminated
+/// string, which ends at the end of the line, or before the first `;` or `}` within an unclosed block
+/// or a `{}` block. Returns whether a declaration was skipped, after emitting a warning.
+///
+/// With nesting, a `{}` block may be the start of a nested rule, so it is left to the parser.
+pub(crate) fn skip_invalid_declaration<'i, 't>(
+  input: &mut Parser<'i, 't>,
+  options: &ParserOptions<'_, 'i>,
+  nesting: bool,
+) -> bool {
+  let start = input.state();
+  match find_invalid_value(input, nesting) {
+    Some((location, resume)) => {
+      options.warn(ParseError {
+        kind: ParseErrorKind::Custom(ParserError::InvalidValue),
+        location,
+      });
+      input.reset(&resume);
+      true
+    }
+    None => {
+      input.reset(&start);
+      false
+    }
+  }
+}
+
+/// Returns the location of the first invalid token in the value of a declaration, and where to resume parsing.
+fn find_invalid_value<'i, 't>(input: &mut Parser<'i, 't>, nesting: bool) -> Option<(SourceLocation, ParserState)> {
+  let is_custom_property = match input.next() {
+    Ok(Token::Ident(name)) => name.starts_with("--"),
+    _ => return None,
+  };
+  input.expect_colon().ok()?;
+
+  let mut invalid = None;
+  loop {
+    input.skip_whitespace();
+    let state = input.state();
+    let token = match input.next() {
+      Ok(token) => token.clone(),
+      Err(_) => break,
+    };
+    let location = state.source_location();
+    match token {
+      Token::Semicolon => {
+        input.reset(&state);
+        break;
+      }
+      Token::BadString(_) => return Some((location, input.state())),
+      Token::BadUrl(_) | Token::CloseParenthesis | Token::CloseSquareBracket => {
+        invalid.get_or_insert(location);
+      }
+      Token::CurlyBracketBlock if !is_custom_property => {
+        if nesting {
+          return None;
+        }
+        match find_nested_block_end(input) {
+          Some(resume) => return Some((location, resume)),
+          None => {
+            invalid.get_or_insert(location);
+          }
+        }
+      }
+      Token::Function(_) | Token::ParenthesisBlock | Token::SquareBracketBlock | Token::CurlyBracketBlock => {
+        let closing = match token {
+          Token::SquareBracketBlock => ']',
+          Token::CurlyBracketBlock => '}',
+          _ => ')',
+        };
+        let resume = find_nested_block_end(input);
+        if !input.slice_from(state.position()).ends_with(closing) {
+          if let Some(resume) = resume {
+            return Some((location, resume));
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  invalid.map(|location| (location, input.state()))
+}
+
+/// Consumes the block that was just started, and returns the position of the first `;` or `}`
+/// within it, including within nested blocks.
+fn find_nested_block_end<'i, 't>(input: &mut Parser<'i, 't>) -> Option<ParserState> {
+  let result = input.parse_nested_block(|input| {
+    let mut end = None;
+    loop {
+      let state = input.state();
+      match input.next() {
+        Ok(Token::Semicolon) | Ok(Token::CloseCurlyBracket) => {
+          end.get_or_insert(state);
+        }
+        Ok(Token::Function(_))
+        | Ok(Token::ParenthesisBlock)
+        | Ok(Token::SquareBracketBlock)
+        | Ok(Token::CurlyBracketBlock) => {
+          if let Some(state) = find_nested_block_end(input) {
+            end.get_or_insert(state);
+          }
+        }
+        Ok(_) => {}
+        Err(_) => return Ok::<_, ParseError<'i, ()>>(end),
+      }
+    }
+  });
+  result.ok().flatten()
+}
+
+fn parse_comments_with<'i, 't, F: FnMut(CommentRule<'i>)>(
+  input: &mut Parser<'i, 't>,
+  options: &ParserOptions<'_, 'i>,
+  mut f: F,
+) {
+  if options.comments == Comments::None {
+    return;
+  }
+
+  loop {
+    let state = input.state();
+    match input.next_including_whitespace_and_comments() {
+      Ok(Token::WhiteSpace(..)) => {}
+      Ok(Token::Comment(comment)) => {
+        let loc = state.source_location();
+        let comment = CommentRule {
+          comment: (*comment).into(),
+          loc: Location {
+            source_index: options.source_index,
+            line: loc.line,
+            column: loc.column,
+          },
+        };
+        if comment.is_included(options.comments) {
+          f(comment);
+        }
+      }
+      _ => {
+        input.reset(&state);
+        return;
+      }
+    }
+  }
+}
+
 fn parse_declarations_and_nested_rules<'a, 'o, 'i, 't>(
   input: &mut Parser<'i, 't>,
   default_namespace: &'a Option<CowArcStr<'i>>,
@@ -663,6 +1060,7 @@ fn parse_declarations_and_nested_rules<'a, 'o, 'i, 't>(
 ) -> Result<(DeclarationBlock<'i>, CssRuleList<'i>), ParseError<'i, ParserError<'i>>> {
   let mut important_declarations = DeclarationList::new();
   let mut declarations = DeclarationList::new();
+  let mut at_rules = Vec::new();
   let mut rules = CssRuleList(vec![]);
   let parser = StyleRuleParser {
     default_namespace,
@@ -670,27 +1068,55 @@ fn parse_declarations_and_nested_rules<'a, 'o, 'i, 't>(
     options,
     declarations: &mut declarations,
     important_declarations: &mut important_declarations,
+    at_rules: &mut at_rules,
     rules: &mut rules,
   };
 
+  let mut comments = Vec::new();
   let mut declaration_parser = DeclarationListParser::new(input, parser);
-  let mut last = declaration_parser.input.state();
-  while let Some(decl) = declaration_parser.next() {
-    match decl {
-      Ok(_) => {}
-      _ => {
+  loop {
+    let parser = &declaration_parser.parser;
+    let index = parser.declarations.len() + parser.important_declarations.len();
+    let last = declaration_parser.input.state();
+    let comments_len = comments.len();
+    parse_declaration_comments(declaration_parser.input, options, index, &mut comments);
+    if options.error_recovery
+      && !options.strict
+      && skip_invalid_declaration(declaration_parser.input, options, options.nesting)
+    {
+      continue;
+    }
+    match declaration_parser.next() {
+      Some(Ok(_)) => {}
+      Some(Err((err, _))) if !options.nesting => {
+        // Without nesting, this is only reached for the rule lists of custom at-rules within declaration
+        // blocks, so the error is an invalid declaration rather than the start of a nested rule.
+        if options.error_recovery && !options.strict {
+          options.warn(err);
+          continue;
+        }
+        return Err(err);
+      }
+      Some(Err(_)) => {
+        // Comments before the first nested rule are kept along with the rules.
+        comments.truncate(comments_len);
         declaration_parser.input.reset(&last);
         break;
       }
+      None => break,
     }
-
-    last = declaration_parser.input.state();
   }
 
   let mut iter = RuleListParser::new_for_nested_rule(declaration_parser.input, declaration_parser.parser);
-  while let Some(result) = iter.next() {
+  loop {
+    parse_comments(iter.input, options, &mut iter.parser.rules.0);
+    let result = match iter.next() {
+      Some(result) => result,
+      None => break,
+    };
+
     if let Err((err, _)) = result {
-      if options.error_recovery {
+      if options.error_recovery && !options.strict {
         options.warn(err);
         continue;
       }
@@ -702,6 +1128,8 @@ fn parse_declarations_and_nested_rules<'a, 'o, 'i, 't>(
     DeclarationBlock {
       declarations,
       important_declarations,
+      comments,
+      at_rules,
     },
     rules,
   ))
@@ -713,6 +1141,7 @@ pub struct StyleRuleParser<'a, 'o, 'i> {
   options: &'a ParserOptions<'o, 'i>,
   declarations: &'a mut DeclarationList<'i>,
   important_declarations: &'a mut DeclarationList<'i>,
+  at_rules: &'a mut Vec<(usize, CustomAtRule<'i>)>,
   rules: &'a mut CssRuleList<'i>,
 }
 
@@ -751,15 +1180,18 @@ impl<'a, 'o, 'i> AtRuleParser<'i> for StyleRuleParser<'a, 'o, 'i> {
     input: &mut Parser<'i, 't>,
   ) -> Result<Self::Prelude, ParseError<'i, Self::Error>> {
     match_ignore_ascii_case! { &*name,
-      "media" => {
-        let media = MediaList::parse(input)?;
+      "media" if self.options.nesting => {
+        let media = self.options.parse_prelude(input, MediaList::parse, MediaList::unknown)?;
         Ok(AtRulePrelude::Media(media))
       },
-      "supports" => {
-        let cond = SupportsCondition::parse(input)?;
+      "supports" if self.options.nesting => {
+        let cond = self.options.parse_prelude(input, SupportsCondition::parse, SupportsCondition::Unknown)?;
         Ok(AtRulePrelude::Supports(cond))
       },
-      "nest" => {
+      "nest" if self.options.nesting => {
+        if self.options.nesting_spec == NestingSpec::Current {
+          return Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name)))
+        }
         let selector_parser = SelectorParser {
           default_namespace: self.default_namespace,
           namespace_prefixes: self.namespace_prefixes,
@@ -769,7 +1201,10 @@ impl<'a, 'o, 'i> AtRuleParser<'i> for StyleRuleParser<'a, 'o, 'i> {
         let selectors = SelectorList::parse(&selector_parser, input, NestingRequirement::Contained)?;
         Ok(AtRulePrelude::Nest(selectors))
       },
-      _ => Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name)))
+      _ => match self.options.parse_custom_at_rule_prelude(&name, input) {
+        Some(prelude) => prelude,
+        None => Err(input.new_error(BasicParseErrorKind::AtRuleInvalid(name)))
+      }
     }
   }
 
@@ -833,11 +1268,105 @@ impl<'a, 'o, 'i> AtRuleParser<'i> for StyleRuleParser<'a, 'o, 'i> {
         }));
         Ok(())
       }
+      // Custom at-rules are kept with the declarations, even after nested rules.
+      AtRulePrelude::Custom(..) => {
+        let (source_index, default_namespace, namespace_prefixes, options) = (
+          self.options.source_index,
+          self.default_namespace,
+          self.namespace_prefixes,
+          self.options,
+        );
+        let rule = parse_custom_at_rule(prelude, start, input, options, |input| {
+          parse_nested_at_rule(input, source_index, default_namespace, namespace_prefixes, options)
+        })?;
+        self.at_rules.push((self.declarations.len(), rule));
+        Ok(())
+      }
       _ => {
         unreachable!()
       }
     }
   }
+
+  fn rule_without_block(&mut self, prelude: AtRulePrelude<'i>, start: &ParserState) -> Result<(), ()> {
+    let rule = custom_at_rule_without_block(prelude, start, self.options)?;
+    self.at_rules.push((self.declarations.len(), rule));
+    Ok(())
+  }
+}
+
+/// Parses a custom at-rule with a block within a declaration list, using `parse_rules` for rule lists.
+pub(crate) fn parse_custom_at_rule<'i, 't>(
+  prelude: AtRulePrelude<'i>,
+  start: &ParserState,
+  input: &mut Parser<'i, 't>,
+  options: &ParserOptions<'_, 'i>,
+  parse_rules: impl FnOnce(&mut Parser<'i, 't>) -> Result<CssRuleList<'i>, ParseError<'i, ParserError<'i>>>,
+) -> Result<CustomAtRule<'i>, ParseError<'i, ParserError<'i>>> {
+  let loc = start.source_location();
+  match prelude {
+    AtRulePrelude::Custom(name, prelude, body) => Ok(CustomAtRule {
+      name,
+      prelude,
+      body: Some(parse_custom_at_rule_body(body, input, options, parse_rules)?),
+      loc: Location {
+        source_index: options.source_index,
+        line: loc.line,
+        column: loc.column,
+      },
+    }),
+    _ => Err(input.new_error(BasicParseErrorKind::AtRuleBodyInvalid)),
+  }
+}
+
+/// Returns a custom at-rule without a block within a declaration list, or an error for other at-rules.
+pub(crate) fn custom_at_rule_without_block<'i>(
+  prelude: AtRulePrelude<'i>,
+  start: &ParserState,
+  options: &ParserOptions<'_, 'i>,
+) -> Result<CustomAtRule<'i>, ()> {
+  let loc = start.source_location();
+  match prelude {
+    AtRulePrelude::Custom(name, prelude, CustomAtRuleBodySyntax::None) => Ok(CustomAtRule {
+      name,
+      prelude,
+      body: None,
+      loc: Location {
+        source_index: options.source_index,
+        line: loc.line,
+        column: loc.column,
+      },
+    }),
+    _ => Err(()),
+  }
+}
+
+/// Parses the rule list of a custom at-rule within a declaration block without nesting, e.g.
+/// `@screen md { ... }` within a style rule. Like an `@media` rule nested within a style rule,
+/// declarations may be used directly within it.
+pub(crate) fn parse_rules_in_declarations<'i, 't>(
+  input: &mut Parser<'i, 't>,
+  options: &ParserOptions<'_, 'i>,
+) -> Result<CssRuleList<'i>, ParseError<'i, ParserError<'i>>> {
+  parse_nested_at_rule(input, options.source_index, &None, &HashMap::new(), options)
+}
+
+/// Parses the block of an at-rule defined by the `custom_at_rules` option. Rule lists are parsed
+/// with `parse_rules`, which depends on whether the at-rule is nested within a style rule.
+fn parse_custom_at_rule_body<'i, 't>(
+  syntax: CustomAtRuleBodySyntax,
+  input: &mut Parser<'i, 't>,
+  options: &ParserOptions<'_, 'i>,
+  parse_rules: impl FnOnce(&mut Parser<'i, 't>) -> Result<CssRuleList<'i>, ParseError<'i, ParserError<'i>>>,
+) -> Result<CustomAtRuleBody<'i>, ParseError<'i, ParserError<'i>>> {
+  match syntax {
+    CustomAtRuleBodySyntax::None => Err(input.new_error(BasicParseErrorKind::AtRuleBodyInvalid)),
+    CustomAtRuleBodySyntax::DeclarationList => Ok(CustomAtRuleBody::DeclarationList(DeclarationBlock::parse(
+      input, options,
+    )?)),
+    CustomAtRuleBodySyntax::RuleList => Ok(CustomAtRuleBody::RuleList(parse_rules(input)?)),
+    CustomAtRuleBodySyntax::Raw => Ok(CustomAtRuleBody::Raw(TokenList::parse(input, options, 0)?)),
+  }
 }
 
 #[inline]
@@ -860,7 +1389,7 @@ fn parse_nested_at_rule<'a, 'o, 'i, 't>(
   let (declarations, mut rules) =
     parse_declarations_and_nested_rules(input, default_namespace, namespace_prefixes, options)?;
 
-  if declarations.declarations.len() > 0 {
+  if declarations.declarations.len() > 0 || !declarations.at_rules.is_empty() {
     rules.0.insert(
       0,
       CssRule::Style(StyleRule {
@@ -878,6 +1407,42 @@ fn parse_nested_at_rule<'a, 'o, 'i, 't>(
   Ok(rules)
 }
 
+/// Parses a nested selector list following the current nesting spec. Selectors that do not
+/// contain `&`, or that start with a combinator, are made relative to the parent rule.
+fn parse_relative_selectors<'i, 't>(
+  selector_parser: &SelectorParser<'_, '_, 'i>,
+  input: &mut Parser<'i, 't>,
+) -> Result<SelectorList<'i, Selectors>, ParseError<'i, ParserError<'i>>> {
+  let selectors = input.parse_comma_separated(|input| {
+    let combinator = input
+      .try_parse(|input| match input.next()? {
+        Token::Delim('>') => Ok(Combinator::Child),
+        Token::Delim('+') => Ok(Combinator::NextSibling),
+        Token::Delim('~') => Ok(Combinator::LaterSibling),
+        t => {
+          let t = t.clone();
+          Err(input.new_basic_unexpected_token_error(t))
+        }
+      })
+      .ok();
+    let SelectorList(selectors) = SelectorList::parse(selector_parser, input, NestingRequirement::None)?;
+    Ok(
+      selectors
+        .into_iter()
+        .map(|selector| {
+          if combinator.is_some() || !contains_nesting(&selector) {
+            prepend_nesting(&selector, combinator.unwrap_or(Combinator::Descendant))
+          } else {
+            selector
+          }
+        })
+        .collect::<Vec<_>>(),
+    )
+  })?;
+
+  Ok(SelectorList(selectors.into_iter().flatten().collect()))
+}
+
 impl<'a, 'o, 'b, 'i> QualifiedRuleParser<'i> for StyleRuleParser<'a, 'o, 'i> {
   type Prelude = SelectorList<'i, Selectors>;
   type QualifiedRule = ();
@@ -893,7 +1458,10 @@ impl<'a, 'o, 'b, 'i> QualifiedRuleParser<'i> for StyleRuleParser<'a, 'o, 'i> {
       is_nesting_allowed: true,
       options: &self.options,
     };
-    SelectorList::parse(&selector_parser, input, NestingRequirement::Prefixed)
+    match self.options.nesting_spec {
+      NestingSpec::Legacy => SelectorList::parse(&selector_parser, input, NestingRequirement::Prefixed),
+      NestingSpec::Current => parse_relative_selectors(&selector_parser, input),
+    }
   }
 
   fn parse_block<'t>(
@@ -1,16 +1,20 @@
 //! CSS serialization and source map generation.
 
+use crate::compat::Feature;
 use crate::css_modules::CssModule;
 use crate::dependencies::{Dependency, DependencyOptions};
 use crate::error::{Error, ErrorLocation, PrinterError, PrinterErrorKind};
+use crate::parser::Comments;
 use crate::rules::Location;
-use crate::targets::Browsers;
+use crate::targets::{should_compile, Browsers, Features};
+use crate::traits::ToCss;
+use crate::values::length::LengthValue;
 use crate::vendor_prefix::VendorPrefix;
 use cssparser::{serialize_identifier, serialize_name};
 use parcel_sourcemap::{OriginalLocation, SourceMap};
+use std::sync::{Arc, RwLock};
 
 /// Options that control how CSS is serialized to a string.
-#[derive(Default)]
 pub struct PrinterOptions<'a> {
   /// Whether to minify the CSS, i.e. remove white space.
   pub minify: bool,
@@ -29,6 +33,146 @@ pub struct PrinterOptions<'a> {
   /// A mapping of pseudo classes to replace with class names that can be applied
   /// from JavaScript. Useful for polyfills, for example.
   pub pseudo_classes: Option<PseudoClasses<'a>>,
+  /// A URL to reference in a trailing `/*# sourceMappingURL=... */` comment.
+  /// The URL is written exactly as given, e.g. relative to the output CSS file.
+  pub source_map_url: Option<&'a str>,
+  /// Whether to print colors in their shortest form, e.g. `red` instead of `#ff0000`.
+  /// When disabled, RGB colors are always printed as six or eight digit hex colors.
+  pub minify_colors: bool,
+  /// Which format to print colors in, regardless of the browser targets. Colors outside the
+  /// sRGB gamut are only converted to `oklch()`, and `currentColor` is never converted.
+  /// By default, colors are printed in the shortest form supported by the targets.
+  pub color_format: Option<ColorFormat>,
+  /// Which quotes to print strings and `url()` values with. By default, strings are printed with double quotes,
+  /// and `url()` values are printed without quotes when minifying if that is shorter.
+  pub quotes: Option<QuoteStyle>,
+  /// Whether to print attribute selector values without quotes where possible.
+  /// Only applies when `minify` is enabled.
+  pub minify_selectors: bool,
+  /// The string written for each level of indentation when `minify` is disabled, e.g. `"\t"`.
+  /// Defaults to two spaces.
+  pub indent: &'a str,
+  /// The maximum line width when `minify` is disabled. Selector lists and comma separated values
+  /// that would extend past it are written with one item per line.
+  pub max_line_width: Option<usize>,
+  /// Whether to write an empty line between rules when `minify` is disabled. Defaults to `true`.
+  pub blank_lines_between_rules: bool,
+  /// The maximum number of decimal places to print for each kind of numeric value.
+  pub precision: Precision,
+  /// Features to compile regardless of the browser targets.
+  pub include: Features,
+  /// Features to never compile, even if the browser targets do not support them.
+  pub exclude: Features,
+  /// Which comments between rules and declarations to print, including when `minify` is enabled. Comments are only
+  /// available when retained by the `comments` option of [ParserOptions](crate::stylesheet::ParserOptions).
+  pub comments: Comments,
+  /// A list that will be appended to when a warning occurs while printing, e.g. when a feature that
+  /// is not supported by the browser targets cannot be compiled. Warnings while parsing and minifying
+  /// are appended to the `warnings` of [ParserOptions](crate::stylesheet::ParserOptions) instead.
+  pub warnings: Option<Arc<RwLock<Vec<PrinterError>>>>,
+}
+
+impl<'a> Default for PrinterOptions<'a> {
+  fn default() -> Self {
+    PrinterOptions {
+      minify: false,
+      source_map: None,
+      targets: None,
+      analyze_dependencies: None,
+      pseudo_classes: None,
+      source_map_url: None,
+      minify_colors: true,
+      color_format: None,
+      quotes: None,
+      minify_selectors: true,
+      indent: "  ",
+      max_line_width: None,
+      blank_lines_between_rules: true,
+      precision: Precision::default(),
+      include: Features::empty(),
+      exclude: Features::empty(),
+      comments: Comments::None,
+      warnings: None,
+    }
+  }
+}
+
+impl<'a> PrinterOptions<'a> {
+  /// Returns options that only remove whitespace and comments, without rewriting any value.
+  /// This is intended for style sheets parsed with the `verbatim_values` option of
+  /// [ParserOptions](crate::stylesheet::ParserOptions), and printed without calling
+  /// [minify](crate::stylesheet::StyleSheet::minify) first. No browser targets are applied.
+  pub fn whitespace_only() -> Self {
+    PrinterOptions {
+      minify: true,
+      minify_colors: false,
+      minify_selectors: false,
+      ..PrinterOptions::default()
+    }
+  }
+}
+
+/// The maximum number of decimal places to print for each kind of numeric value.
+///
+/// See [PrinterOptions](PrinterOptions). Values are rounded to the given number of decimal places,
+/// and printed in the shortest form that represents the rounded value. When no precision is set
+/// for a kind of value, it is printed with up to six significant digits.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct Precision {
+  /// The precision of lengths, e.g. `px` or `em`.
+  pub lengths: Option<u8>,
+  /// The precision of percentages.
+  pub percentages: Option<u8>,
+  /// The precision of times, i.e. `s` and `ms`.
+  pub times: Option<u8>,
+  /// The precision of angles, e.g. `deg` or `turn`.
+  pub angles: Option<u8>,
+  /// The precision of unitless numbers, and of dimensions with other units, e.g. `fr` or `dppx`.
+  pub numbers: Option<u8>,
+}
+
+impl Precision {
+  /// Returns the precision of a dimension with the given unit.
+  pub(crate) fn for_unit(&self, unit: &str) -> Option<u8> {
+    if LengthValue::is_unit(unit) {
+      self.lengths
+    } else if unit.eq_ignore_ascii_case("s") || unit.eq_ignore_ascii_case("ms") {
+      self.times
+    } else if ["deg", "grad", "rad", "turn"].iter().any(|u| unit.eq_ignore_ascii_case(u)) {
+      self.angles
+    } else {
+      self.numbers
+    }
+  }
+}
+
+/// A format to print colors in.
+///
+/// See [PrinterOptions](PrinterOptions).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorFormat {
+  /// Hex colors, e.g. `#ff0000`, or `#ff000080` with an alpha channel.
+  Hex,
+  /// The `rgb()` function, or `rgba()` with an alpha channel.
+  Rgb,
+  /// The `hsl()` function, or `hsla()` with an alpha channel.
+  Hsl,
+  /// The `oklch()` function.
+  Oklch,
+}
+
+/// Which quotes to print strings and `url()` values with.
+///
+/// See [PrinterOptions](PrinterOptions).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuoteStyle {
+  /// Double quotes. `url()` values are always quoted.
+  Double,
+  /// Single quotes. `url()` values are always quoted.
+  Single,
+  /// Double quotes, unless single quotes require fewer escapes. `url()` values are printed
+  /// without quotes unless that requires escaping characters such as spaces or parentheses.
+  Minimal,
 }
 
 /// A mapping of user action pseudo classes to replace with class names.
@@ -64,10 +208,24 @@ pub struct Printer<'a, 'b, 'c, W> {
   pub(crate) source_maps: Vec<Option<SourceMap>>,
   pub(crate) loc: Location,
   indent: u8,
+  indent_str: &'a str,
+  align: u8,
+  max_line_width: Option<usize>,
+  blank_lines_between_rules: bool,
   line: u32,
   col: u32,
   pub(crate) minify: bool,
+  pub(crate) minify_colors: bool,
+  pub(crate) color_format: Option<ColorFormat>,
+  pub(crate) quotes: Option<QuoteStyle>,
+  pub(crate) minify_selectors: bool,
+  pub(crate) precision: Precision,
   pub(crate) targets: Option<Browsers>,
+  include: Features,
+  exclude: Features,
+  /// The features that were compiled while printing.
+  pub(crate) features_used: Features,
+  pub(crate) comments: Comments,
   /// Vendor prefix override. When non-empty, it overrides
   /// the vendor prefix of whatever is being printed.
   pub(crate) vendor_prefix: VendorPrefix,
@@ -76,6 +234,8 @@ pub struct Printer<'a, 'b, 'c, W> {
   pub(crate) dependencies: Option<Vec<Dependency>>,
   pub(crate) remove_imports: bool,
   pub(crate) pseudo_classes: Option<PseudoClasses<'a>>,
+  capture: Option<String>,
+  warnings: Option<Arc<RwLock<Vec<PrinterError>>>>,
 }
 
 impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
@@ -92,10 +252,23 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
         column: 1,
       },
       indent: 0,
+      indent_str: options.indent,
+      align: 0,
+      max_line_width: options.max_line_width,
+      blank_lines_between_rules: options.blank_lines_between_rules,
       line: 0,
       col: 0,
       minify: options.minify,
+      minify_colors: options.minify_colors,
+      color_format: options.color_format,
+      quotes: options.quotes,
+      minify_selectors: options.minify_selectors,
+      precision: options.precision,
       targets: options.targets,
+      include: options.include,
+      exclude: options.exclude,
+      features_used: Features::empty(),
+      comments: options.comments,
       vendor_prefix: VendorPrefix::empty(),
       in_calc: false,
       css_module: None,
@@ -106,9 +279,21 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
       },
       remove_imports: matches!(&options.analyze_dependencies, Some(d) if d.remove_imports),
       pseudo_classes: options.pseudo_classes,
+      capture: None,
+      warnings: options.warnings,
     }
   }
 
+  /// Returns whether the given feature should be compiled, based on the browser targets
+  /// and the included and excluded features. Compiled features are recorded in `features_used`.
+  pub(crate) fn should_compile(&mut self, feature: Features, compat: Feature) -> bool {
+    let compile = should_compile(self.targets, self.include, self.exclude, feature, compat);
+    if compile {
+      self.features_used |= feature;
+    }
+    compile
+  }
+
   /// Returns the current source filename that is being printed.
   pub fn filename(&self) -> &'c str {
     if let Some(sources) = self.sources {
@@ -128,6 +313,9 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
   /// If such a string is written, it will break source maps.
   pub fn write_str(&mut self, s: &str) -> Result<(), PrinterError> {
     self.col += s.len() as u32;
+    if let Some(capture) = &mut self.capture {
+      capture.push_str(s);
+    }
     self.dest.write_str(s)?;
     Ok(())
   }
@@ -140,6 +328,9 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
     } else {
       self.col += 1;
     }
+    if let Some(capture) = &mut self.capture {
+      capture.push(c);
+    }
     self.dest.write_char(c)?;
     Ok(())
   }
@@ -166,6 +357,16 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
     self.whitespace()
   }
 
+  /// Writes an empty line between two rules, without indentation. If the `minify` option
+  /// is enabled, or `blank_lines_between_rules` is disabled, then nothing is printed.
+  pub(crate) fn blank_line(&mut self) -> Result<(), PrinterError> {
+    if self.minify || !self.blank_lines_between_rules {
+      return Ok(());
+    }
+
+    self.write_char('\n')
+  }
+
   /// Writes a newline character followed by indentation.
   /// If the `minify` option is enabled, then nothing is printed.
   pub fn newline(&mut self) -> Result<(), PrinterError> {
@@ -174,36 +375,127 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
     }
 
     self.write_char('\n')?;
-    if self.indent > 0 {
-      self.write_str(&" ".repeat(self.indent as usize))?;
+    let indent_str = self.indent_str;
+    for _ in 0..self.indent {
+      self.write_str(indent_str)?;
+    }
+
+    if self.align > 0 {
+      self.write_str(&" ".repeat(self.align as usize))?;
     }
 
     Ok(())
   }
 
+  /// Returns whether the output of the given function would extend past the maximum line width,
+  /// if any, when written at the current position. This is always false when minifying.
+  pub(crate) fn exceeds_max_line_width<F>(&self, f: F) -> Result<bool, PrinterError>
+  where
+    F: FnOnce(&mut Printer<String>) -> Result<(), PrinterError>,
+  {
+    let max_line_width = match self.max_line_width {
+      Some(max_line_width) if !self.minify => max_line_width,
+      _ => return Ok(false),
+    };
+
+    // CSS module names and other state that depends on the destination are not included,
+    // so this is an approximation.
+    let mut s = String::new();
+    let mut printer = Printer::new(
+      &mut s,
+      PrinterOptions {
+        targets: self.targets,
+        minify_colors: self.minify_colors,
+        color_format: self.color_format,
+        quotes: self.quotes,
+        minify_selectors: self.minify_selectors,
+        include: self.include,
+        exclude: self.exclude,
+        ..PrinterOptions::default()
+      },
+    );
+    printer.vendor_prefix = self.vendor_prefix;
+    printer.in_calc = self.in_calc;
+    f(&mut printer)?;
+    Ok(self.col as usize + printer.col as usize > max_line_width)
+  }
+
+  /// Calls `f`, and returns everything it writes to the destination in addition to writing it as usual.
+  pub(crate) fn capture<F>(&mut self, f: F) -> Result<String, PrinterError>
+  where
+    F: FnOnce(&mut Self) -> Result<(), PrinterError>,
+  {
+    let outer = self.capture.replace(String::new());
+    let res = f(self);
+    let captured = std::mem::replace(&mut self.capture, outer).unwrap_or_default();
+    if let Some(outer) = &mut self.capture {
+      outer.push_str(&captured);
+    }
+    res.map(|_| captured)
+  }
+
+  /// Writes a comma separated list of values. If they would extend past the maximum line width,
+  /// each value after the first is written on its own line, indented by one level.
+  pub(crate) fn write_comma_separated<T: ToCss>(&mut self, values: &[T]) -> Result<(), PrinterError> {
+    let wrap = values.len() > 1 && self.exceeds_max_line_width(|dest| dest.write_comma_separated(values))?;
+    if wrap {
+      self.indent();
+    }
+
+    for (idx, val) in values.iter().enumerate() {
+      if idx > 0 {
+        if wrap {
+          self.write_char(',')?;
+          self.newline()?;
+        } else {
+          self.delim(',', false)?;
+        }
+      }
+      val.to_css(self)?;
+    }
+
+    if wrap {
+      self.dedent();
+    }
+    Ok(())
+  }
+
+  /// Writes a `sourceMappingURL` comment on its own line, followed by a newline.
+  pub(crate) fn write_source_map_url(&mut self, url: &str) -> Result<(), PrinterError> {
+    if self.col > 0 {
+      self.write_char('\n')?;
+    }
+
+    self.write_str("/*# sourceMappingURL=")?;
+    self.write_str(url)?;
+    self.write_str(" */")?;
+    self.write_char('\n')
+  }
+
   /// Increases the current indent level.
   pub fn indent(&mut self) {
-    self.indent += 2;
+    self.indent += 1;
   }
 
   /// Decreases the current indent level.
   pub fn dedent(&mut self) {
-    self.indent -= 2;
+    self.indent -= 1;
   }
 
-  /// Increases the current indent level by the given number of characters.
+  /// Increases the current indent by the given number of spaces, e.g. to align
+  /// with a previous line. This is written after the indent levels.
   pub fn indent_by(&mut self, amt: u8) {
-    self.indent += amt;
+    self.align += amt;
   }
 
-  /// Decreases the current indent level by the given number of characters.
+  /// Decreases the current indent by the given number of spaces.
   pub fn dedent_by(&mut self, amt: u8) {
-    self.indent -= amt;
+    self.align -= amt;
   }
 
   /// Returns whether the indent level is greater than one.
   pub fn is_nested(&self) -> bool {
-    self.indent > 2
+    self.indent > 1
   }
 
   /// Adds a mapping to the source map, if any.
@@ -254,8 +546,9 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
   pub fn write_ident(&mut self, ident: &str) -> Result<(), PrinterError> {
     if let Some(css_module) = &mut self.css_module {
       let dest = &mut self.dest;
+      let capture = &mut self.capture;
       let mut first = true;
-      css_module.config.pattern.write(
+      css_module.config.write_name(
         &css_module.hashes[self.loc.source_index as usize],
         &css_module.sources[self.loc.source_index as usize],
         ident,
@@ -263,8 +556,14 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
           self.col += s.len() as u32;
           if first {
             first = false;
+            if let Some(capture) = capture {
+              serialize_identifier(s, capture)?;
+            }
             serialize_identifier(s, dest)
           } else {
+            if let Some(capture) = capture {
+              serialize_name(s, capture)?;
+            }
             serialize_name(s, dest)
           }
         },
@@ -284,12 +583,16 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
     match &mut self.css_module {
       Some(css_module) if css_module.config.dashed_idents => {
         let dest = &mut self.dest;
-        css_module.config.pattern.write(
+        let capture = &mut self.capture;
+        css_module.config.write_name(
           &css_module.hashes[self.loc.source_index as usize],
           &css_module.sources[self.loc.source_index as usize],
           &ident[2..],
           |s| {
             self.col += s.len() as u32;
+            if let Some(capture) = capture {
+              serialize_name(s, capture)?;
+            }
             serialize_name(s, dest)
           },
         )?;
@@ -306,6 +609,22 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
     Ok(())
   }
 
+  /// Writes a quoted string, with the quotes chosen by the `quotes` option.
+  pub fn write_string(&mut self, s: &str) -> Result<(), PrinterError> {
+    let quote = self.quote_for(s);
+    serialize_quoted_string(s, quote, self)?;
+    Ok(())
+  }
+
+  /// Returns the quote character to write the given string with.
+  pub(crate) fn quote_for(&self, s: &str) -> char {
+    match self.quotes {
+      Some(QuoteStyle::Single) => '\'',
+      Some(QuoteStyle::Minimal) if s.matches('"').count() > s.matches('\'').count() => '\'',
+      _ => '"',
+    }
+  }
+
   /// Returns an error of the given kind at the provided location in the current source file.
   pub fn error(&self, kind: PrinterErrorKind, loc: crate::dependencies::Location) -> Error<PrinterErrorKind> {
     Error {
@@ -317,11 +636,50 @@ impl<'a, 'b, 'c, W: std::fmt::Write + Sized> Printer<'a, 'b, 'c, W> {
       }),
     }
   }
+
+  /// Appends a warning of the given kind at the location of the rule being printed to the `warnings` list.
+  pub(crate) fn warn(&self, kind: PrinterErrorKind) {
+    if let Some(warnings) = &self.warnings {
+      if let Ok(mut warnings) = warnings.write() {
+        warnings.push(Error {
+          kind,
+          loc: Some(ErrorLocation::new(self.loc, self.filename().into())),
+        });
+      }
+    }
+  }
 }
 
 impl<'a, 'b, 'c, W: std::fmt::Write + Sized> std::fmt::Write for Printer<'a, 'b, 'c, W> {
   fn write_str(&mut self, s: &str) -> std::fmt::Result {
     self.col += s.len() as u32;
+    if let Some(capture) = &mut self.capture {
+      capture.push_str(s);
+    }
     self.dest.write_str(s)
   }
 }
+
+/// Writes a string surrounded by the given quote character, escaping it along with backslashes and control characters.
+pub(crate) fn serialize_quoted_string<W: std::fmt::Write>(s: &str, quote: char, dest: &mut W) -> std::fmt::Result {
+  dest.write_char(quote)?;
+  let mut start = 0;
+  for (i, c) in s.char_indices() {
+    if c == quote || c == '\\' {
+      dest.write_str(&s[start..i])?;
+      dest.write_char('\\')?;
+      dest.write_char(c)?;
+    } else if c == '\0' {
+      dest.write_str(&s[start..i])?;
+      dest.write_char('\u{FFFD}')?;
+    } else if matches!(c, '\x01'..='\x1F' | '\x7F') {
+      dest.write_str(&s[start..i])?;
+      write!(dest, "\\{:x} ", c as u32)?;
+    } else {
+      continue;
+    }
+    start = i + c.len_utf8();
+  }
+  dest.write_str(&s[start..])?;
+  dest.write_char(quote)
+}
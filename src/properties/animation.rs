@@ -9,9 +9,15 @@ use crate::printer::Printer;
 use crate::properties::{Property, PropertyId, VendorPrefix};
 use crate::targets::Browsers;
 use crate::traits::{Parse, PropertyHandler, Shorthand, ToCss, Zero};
+use crate::values::length::LengthPercentageOrAuto;
 use crate::values::number::CSSNumber;
+use crate::values::size::Size2D;
 use crate::values::string::CowArcStr;
-use crate::values::{easing::EasingFunction, ident::CustomIdent, time::Time};
+use crate::values::{
+  easing::EasingFunction,
+  ident::{CustomIdent, DashedIdent},
+  time::Time,
+};
 use cssparser::*;
 use itertools::izip;
 use smallvec::SmallVec;
@@ -70,7 +76,7 @@ impl<'i> ToCss for AnimationName<'i> {
         // CSS-wide keywords and `none` cannot remove quotes.
         match_ignore_ascii_case! { &*s,
           "none" | "initial" | "inherit" | "unset" | "default" | "revert" | "revert-layer" => {
-            serialize_string(&s, dest)?;
+            dest.write_string(&s)?;
             Ok(())
           },
           _ => {
@@ -319,6 +325,372 @@ impl<'i> Animation<'i> {
 /// A list of animations.
 pub type AnimationList<'i> = SmallVec<[Animation<'i>; 1]>;
 
+enum_property! {
+  /// A scroll axis, used in the [scroll-timeline-axis](https://drafts.csswg.org/scroll-animations-1/#scroll-timeline-axis),
+  /// and [view-timeline-axis](https://drafts.csswg.org/scroll-animations-1/#view-timeline-axis) properties.
+  pub enum ScrollAxis {
+    /// The block axis of the scroll container.
+    "block": Block,
+    /// The inline axis of the scroll container.
+    "inline": Inline,
+    /// The horizontal axis of the scroll container.
+    "x": X,
+    /// The vertical axis of the scroll container.
+    "y": Y,
+  }
+}
+
+impl Default for ScrollAxis {
+  fn default() -> Self {
+    ScrollAxis::Block
+  }
+}
+
+enum_property! {
+  /// The scroll container referenced by a [scroll()](https://drafts.csswg.org/scroll-animations-1/#scroll-notation) function.
+  pub enum Scroller {
+    /// The document viewport.
+    "root": Root,
+    /// The nearest ancestor scroll container.
+    "nearest": Nearest,
+    /// The element itself.
+    "self": SelfElement,
+  }
+}
+
+impl Default for Scroller {
+  fn default() -> Self {
+    Scroller::Nearest
+  }
+}
+
+/// A value for the [view-timeline-inset](https://drafts.csswg.org/scroll-animations-1/#view-timeline-inset) property,
+/// i.e. the start and end insets of the view progress visibility range.
+pub type ViewTimelineInset = Size2D<LengthPercentageOrAuto>;
+
+fn is_default_inset(inset: &ViewTimelineInset) -> bool {
+  inset.0 == LengthPercentageOrAuto::Auto && inset.1 == LengthPercentageOrAuto::Auto
+}
+
+/// A [scroll()](https://drafts.csswg.org/scroll-animations-1/#scroll-notation) function, which references
+/// an anonymous scroll progress timeline.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScrollFunction {
+  /// The scroll container.
+  pub scroller: Scroller,
+  /// The scroll axis.
+  pub axis: ScrollAxis,
+}
+
+impl<'i> Parse<'i> for ScrollFunction {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    input.expect_function_matching("scroll")?;
+    input.parse_nested_block(|input| {
+      let mut scroller = None;
+      let mut axis = None;
+      loop {
+        if scroller.is_none() {
+          scroller = input.try_parse(Scroller::parse).ok();
+        }
+        if axis.is_none() {
+          axis = input.try_parse(ScrollAxis::parse).ok();
+          if axis.is_some() {
+            continue;
+          }
+        }
+        break;
+      }
+
+      Ok(ScrollFunction {
+        scroller: scroller.unwrap_or_default(),
+        axis: axis.unwrap_or_default(),
+      })
+    })
+  }
+}
+
+impl ToCss for ScrollFunction {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    dest.write_str("scroll(")?;
+    let mut needs_space = false;
+    if self.scroller != Scroller::default() {
+      self.scroller.to_css(dest)?;
+      needs_space = true;
+    }
+    if self.axis != ScrollAxis::default() {
+      if needs_space {
+        dest.write_char(' ')?;
+      }
+      self.axis.to_css(dest)?;
+    }
+    dest.write_char(')')
+  }
+}
+
+/// A [view()](https://drafts.csswg.org/scroll-animations-1/#view-notation) function, which references
+/// an anonymous view progress timeline.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ViewFunction {
+  /// The scroll axis.
+  pub axis: ScrollAxis,
+  /// The view timeline inset.
+  pub inset: ViewTimelineInset,
+}
+
+impl<'i> Parse<'i> for ViewFunction {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    input.expect_function_matching("view")?;
+    input.parse_nested_block(|input| {
+      let mut axis = None;
+      let mut inset = None;
+      loop {
+        if axis.is_none() {
+          axis = input.try_parse(ScrollAxis::parse).ok();
+        }
+        if inset.is_none() {
+          inset = input.try_parse(ViewTimelineInset::parse).ok();
+          if inset.is_some() {
+            continue;
+          }
+        }
+        break;
+      }
+
+      Ok(ViewFunction {
+        axis: axis.unwrap_or_default(),
+        inset: inset.unwrap_or(Size2D(LengthPercentageOrAuto::Auto, LengthPercentageOrAuto::Auto)),
+      })
+    })
+  }
+}
+
+impl ToCss for ViewFunction {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    dest.write_str("view(")?;
+    let mut needs_space = false;
+    if self.axis != ScrollAxis::default() {
+      self.axis.to_css(dest)?;
+      needs_space = true;
+    }
+    if !is_default_inset(&self.inset) {
+      if needs_space {
+        dest.write_char(' ')?;
+      }
+      self.inset.to_css(dest)?;
+    }
+    dest.write_char(')')
+  }
+}
+
+/// A value for the [animation-timeline](https://drafts.csswg.org/css-animations-2/#animation-timeline) property.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum AnimationTimeline<'i> {
+  /// The animation is driven by the document timeline.
+  Auto,
+  /// The animation is not associated with a timeline.
+  None,
+  /// The name of a scroll or view progress timeline.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  DashedIdent(DashedIdent<'i>),
+  /// An anonymous scroll progress timeline.
+  Scroll(ScrollFunction),
+  /// An anonymous view progress timeline.
+  View(ViewFunction),
+}
+
+impl<'i> Default for AnimationTimeline<'i> {
+  fn default() -> Self {
+    AnimationTimeline::Auto
+  }
+}
+
+impl<'i> Parse<'i> for AnimationTimeline<'i> {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    if input.try_parse(|input| input.expect_ident_matching("auto")).is_ok() {
+      return Ok(AnimationTimeline::Auto);
+    }
+
+    if input.try_parse(|input| input.expect_ident_matching("none")).is_ok() {
+      return Ok(AnimationTimeline::None);
+    }
+
+    if let Ok(scroll) = input.try_parse(ScrollFunction::parse) {
+      return Ok(AnimationTimeline::Scroll(scroll));
+    }
+
+    if let Ok(view) = input.try_parse(ViewFunction::parse) {
+      return Ok(AnimationTimeline::View(view));
+    }
+
+    Ok(AnimationTimeline::DashedIdent(DashedIdent::parse(input)?))
+  }
+}
+
+impl<'i> ToCss for AnimationTimeline<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    match self {
+      AnimationTimeline::Auto => dest.write_str("auto"),
+      AnimationTimeline::None => dest.write_str("none"),
+      // A reference to a timeline declared elsewhere, so it is not added to the CSS module exports.
+      AnimationTimeline::DashedIdent(name) => dest.write_dashed_ident(&name.0, false),
+      AnimationTimeline::Scroll(scroll) => scroll.to_css(dest),
+      AnimationTimeline::View(view) => view.to_css(dest),
+    }
+  }
+}
+
+/// A value for the [scroll-timeline-name](https://drafts.csswg.org/scroll-animations-1/#scroll-timeline-name)
+/// and [view-timeline-name](https://drafts.csswg.org/scroll-animations-1/#view-timeline-name) properties.
+///
+/// In CSS modules, timeline names are scoped when the `dashed_idents` option is enabled.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum TimelineName<'i> {
+  /// The `none` keyword.
+  None,
+  /// The name of the timeline.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  DashedIdent(DashedIdent<'i>),
+}
+
+impl<'i> Default for TimelineName<'i> {
+  fn default() -> Self {
+    TimelineName::None
+  }
+}
+
+impl<'i> Parse<'i> for TimelineName<'i> {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    if input.try_parse(|input| input.expect_ident_matching("none")).is_ok() {
+      return Ok(TimelineName::None);
+    }
+
+    Ok(TimelineName::DashedIdent(DashedIdent::parse(input)?))
+  }
+}
+
+impl<'i> ToCss for TimelineName<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    match self {
+      TimelineName::None => dest.write_str("none"),
+      TimelineName::DashedIdent(name) => name.to_css(dest),
+    }
+  }
+}
+
+define_list_shorthand! {
+  /// A value for the [scroll-timeline](https://drafts.csswg.org/scroll-animations-1/#scroll-timeline-shorthand) shorthand property.
+  pub struct ScrollTimeline<'i> {
+    /// The name of the timeline.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    name: ScrollTimelineName(TimelineName<'i>),
+    /// The scroll axis.
+    axis: ScrollTimelineAxis(ScrollAxis),
+  }
+}
+
+impl<'i> Parse<'i> for ScrollTimeline<'i> {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    let name = TimelineName::parse(input)?;
+    let axis = input.try_parse(ScrollAxis::parse).unwrap_or_default();
+    Ok(ScrollTimeline { name, axis })
+  }
+}
+
+impl<'i> ToCss for ScrollTimeline<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    self.name.to_css(dest)?;
+    if self.axis != ScrollAxis::default() {
+      dest.write_char(' ')?;
+      self.axis.to_css(dest)?;
+    }
+    Ok(())
+  }
+}
+
+define_list_shorthand! {
+  /// A value for the [view-timeline](https://drafts.csswg.org/scroll-animations-1/#view-timeline-shorthand) shorthand property.
+  pub struct ViewTimeline<'i> {
+    /// The name of the timeline.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    name: ViewTimelineName(TimelineName<'i>),
+    /// The scroll axis.
+    axis: ViewTimelineAxis(ScrollAxis),
+    /// The view timeline inset.
+    inset: ViewTimelineInset(ViewTimelineInset),
+  }
+}
+
+impl<'i> Parse<'i> for ViewTimeline<'i> {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    let name = TimelineName::parse(input)?;
+    let mut axis = None;
+    let mut inset = None;
+    loop {
+      if axis.is_none() {
+        axis = input.try_parse(ScrollAxis::parse).ok();
+      }
+      if inset.is_none() {
+        inset = input.try_parse(ViewTimelineInset::parse).ok();
+        if inset.is_some() {
+          continue;
+        }
+      }
+      break;
+    }
+
+    Ok(ViewTimeline {
+      name,
+      axis: axis.unwrap_or_default(),
+      inset: inset.unwrap_or(Size2D(LengthPercentageOrAuto::Auto, LengthPercentageOrAuto::Auto)),
+    })
+  }
+}
+
+impl<'i> ToCss for ViewTimeline<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    self.name.to_css(dest)?;
+    if self.axis != ScrollAxis::default() {
+      dest.write_char(' ')?;
+      self.axis.to_css(dest)?;
+    }
+    if !is_default_inset(&self.inset) {
+      dest.write_char(' ')?;
+      self.inset.to_css(dest)?;
+    }
+    Ok(())
+  }
+}
+
 #[derive(Default)]
 pub(crate) struct AnimationHandler<'i> {
   targets: Option<Browsers>,
@@ -330,6 +702,7 @@ pub(crate) struct AnimationHandler<'i> {
   play_states: Option<(SmallVec<[AnimationPlayState; 1]>, VendorPrefix)>,
   delays: Option<(SmallVec<[Time; 1]>, VendorPrefix)>,
   fill_modes: Option<(SmallVec<[AnimationFillMode; 1]>, VendorPrefix)>,
+  timelines: Option<SmallVec<[AnimationTimeline<'i>; 1]>>,
   has_any: bool,
 }
 
@@ -387,7 +760,17 @@ impl<'i> PropertyHandler<'i> for AnimationHandler<'i> {
       AnimationPlayState(val, vp) => property!(play_states, val, vp),
       AnimationDelay(val, vp) => property!(delays, val, vp),
       AnimationFillMode(val, vp) => property!(fill_modes, val, vp),
+      AnimationTimeline(val) => {
+        self.timelines = Some(val.clone());
+        self.has_any = true;
+      }
       Animation(val, vp) => {
+        // The shorthand resets animation-timeline, so a timeline declared before it must stay
+        // before it, rather than be printed after the shorthand on flush.
+        if self.timelines.is_some() {
+          self.flush(dest);
+        }
+
         let names = val.iter().map(|b| b.name.clone()).collect();
         maybe_flush!(names, &names, vp);
 
@@ -421,6 +804,10 @@ impl<'i> PropertyHandler<'i> for AnimationHandler<'i> {
         property!(delays, &delays, vp);
         property!(fill_modes, &fill_modes, vp);
       }
+      Unparsed(val) if val.property_id == PropertyId::AnimationTimeline => {
+        self.flush(dest);
+        dest.push(property.clone());
+      }
       Unparsed(val) if is_animation_property(&val.property_id) => {
         self.flush(dest);
         dest.push(Property::Unparsed(val.get_prefixed(self.targets, Feature::Animation)));
@@ -452,6 +839,7 @@ impl<'i> AnimationHandler<'i> {
     let mut play_states = std::mem::take(&mut self.play_states);
     let mut delays = std::mem::take(&mut self.delays);
     let mut fill_modes = std::mem::take(&mut self.fill_modes);
+    let timelines = std::mem::take(&mut self.timelines);
 
     if let (
       Some((names, names_vp)),
@@ -558,6 +946,11 @@ impl<'i> AnimationHandler<'i> {
     prop!(play_states, AnimationPlayState);
     prop!(delays, AnimationDelay);
     prop!(fill_modes, AnimationFillMode);
+
+    // Printed last so that an animation shorthand does not reset it.
+    if let Some(timelines) = timelines {
+      dest.push(Property::AnimationTimeline(timelines));
+    }
   }
 }
 
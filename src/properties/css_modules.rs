@@ -120,7 +120,7 @@ impl<'i> ToCss for Specifier<'i> {
   {
     match self {
       Specifier::Global => dest.write_str("global")?,
-      Specifier::File(file) => serialize_string(&file, dest)?,
+      Specifier::File(file) => dest.write_string(&file)?,
       Specifier::SourceIndex(..) => {}
     }
     Ok(())
@@ -1,24 +1,31 @@
 //! CSS custom properties and unparsed token values.
 
 use crate::compat;
-use crate::error::{ParserError, PrinterError, PrinterErrorKind};
+use crate::declaration::DeclarationBlock;
+use crate::error::{Error, ErrorLocation, ParserError, PrinterError, PrinterErrorKind};
 use crate::prefixes::Feature;
-use crate::printer::Printer;
-use crate::properties::PropertyId;
+use crate::printer::{Printer, PrinterOptions};
+use crate::properties::css_modules::Specifier;
+use crate::properties::{Property, PropertyId};
+use crate::rules::custom_at_rule::CustomAtRuleBody;
+use crate::rules::font_face::FontFaceProperty;
 use crate::rules::supports::SupportsCondition;
-use crate::stylesheet::ParserOptions;
+use crate::rules::{CssRule, CssRuleList, Location};
+use crate::stylesheet::{InlineCustomProperties, ParserOptions};
 use crate::targets::Browsers;
 use crate::traits::{Parse, ParseWithOptions, ToCss};
 use crate::values::color::{
   parse_hsl_hwb_components, parse_rgb_components, ColorFallbackKind, ComponentParser, CssColor,
 };
-use crate::values::ident::DashedIdentReference;
+use crate::values::ident::{DashedIdent, DashedIdentReference};
 use crate::values::length::serialize_dimension;
 use crate::values::percentage::Percentage;
 use crate::values::string::CowArcStr;
 use crate::values::url::Url;
 use crate::vendor_prefix::VendorPrefix;
 use cssparser::*;
+use parcel_selectors::parser::Component;
+use std::collections::{HashMap, HashSet};
 
 /// A CSS custom property, representing any unknown property.
 #[derive(Debug, Clone, PartialEq)]
@@ -29,6 +36,11 @@ pub struct CustomProperty<'i> {
   pub name: CowArcStr<'i>,
   /// The property value, stored as a raw token list.
   pub value: TokenList<'i>,
+  /// The value exactly as written in the source, when the property is listed in the
+  /// `ignore_properties` parser option, or with whitespace and comments compacted when
+  /// the `verbatim_values` parser option is enabled. If present, it is printed instead of `value`.
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+  pub verbatim_value: Option<CowArcStr<'i>>,
 }
 
 impl<'i> CustomProperty<'i> {
@@ -38,11 +50,87 @@ impl<'i> CustomProperty<'i> {
     input: &mut Parser<'i, 't>,
     options: &ParserOptions,
   ) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    let start = input.position();
     let value = input.parse_until_before(Delimiter::Bang | Delimiter::Semicolon, |input| {
       TokenList::parse(input, options, 0)
     })?;
-    Ok(CustomProperty { name, value })
+    let verbatim_value = if options.verbatim_values {
+      Some(compact_whitespace(input.slice_from(start), name.starts_with("--")).into())
+    } else if options.is_ignored_property(&name) {
+      Some(input.slice_from(start).trim().into())
+    } else {
+      None
+    };
+    Ok(CustomProperty {
+      name,
+      value,
+      verbatim_value,
+    })
+  }
+
+  /// Serializes the value of the property.
+  pub(crate) fn value_to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    if let Some(value) = &self.verbatim_value {
+      return dest.write_str(value);
+    }
+    self.value.to_css(dest, self.name.starts_with("--"))
+  }
+}
+
+/// Removes comments from a value as written, and collapses whitespace to a single space where it
+/// separates two tokens. Whitespace at the start and end of blocks is removed, as well as around
+/// commas unless the value belongs to a custom property, where it is significant. Tokens are copied
+/// from the source exactly.
+fn compact_whitespace(source: &str, is_custom_property: bool) -> String {
+  fn compact<'i, 't>(input: &mut Parser<'i, 't>, dest: &mut String, is_custom_property: bool) {
+    let mut has_space = false;
+    let mut first = true;
+    let mut after_comma = false;
+    loop {
+      let start = input.position();
+      let token = match input.next_including_whitespace_and_comments() {
+        Ok(token) => token.clone(),
+        Err(_) => break,
+      };
+
+      let closing = match &token {
+        cssparser::Token::WhiteSpace(_) | cssparser::Token::Comment(_) => {
+          has_space = true;
+          continue;
+        }
+        cssparser::Token::Function(_) | cssparser::Token::ParenthesisBlock => Some(')'),
+        cssparser::Token::SquareBracketBlock => Some(']'),
+        cssparser::Token::CurlyBracketBlock => Some('}'),
+        _ => None,
+      };
+
+      let is_comma = token == cssparser::Token::Comma;
+      if has_space && !first && (is_custom_property || !(is_comma || after_comma)) {
+        dest.push(' ');
+      }
+      has_space = false;
+      first = false;
+      after_comma = is_comma;
+      dest.push_str(input.slice_from(start));
+
+      if let Some(closing) = closing {
+        let _ = input.parse_nested_block(|input| {
+          compact(input, dest, is_custom_property);
+          Ok::<_, ParseError<'i, ()>>(())
+        });
+        dest.push(closing);
+      }
+    }
   }
+
+  let mut input = ParserInput::new(source);
+  let mut parser = Parser::new(&mut input);
+  let mut dest = String::with_capacity(source.len());
+  compact(&mut parser, &mut dest, is_custom_property);
+  dest
 }
 
 /// A known property with an unparsed value.
@@ -90,6 +178,26 @@ impl<'i> UnparsedProperty<'i> {
       value: self.value.clone(),
     }
   }
+
+  /// Returns whether the value may be valid for the property, i.e. it is a CSS-wide keyword or
+  /// contains `var()` or `env()` references that are substituted at computed-value time.
+  /// Otherwise, the value of a known property could not be parsed because it is invalid.
+  pub fn may_be_valid(&self) -> bool {
+    let tokens: Vec<_> = self.value.0.iter().filter(|token| !token.is_whitespace()).collect();
+    if let [TokenOrValue::Token(Token::Ident(ident))] = tokens.as_slice() {
+      if matches!(
+        ident.as_ref().to_ascii_lowercase().as_str(),
+        "initial" | "inherit" | "unset" | "default" | "revert" | "revert-layer"
+      ) {
+        return true;
+      }
+    }
+
+    has_variables(&self.value)
+      || tokens
+        .iter()
+        .any(|token| matches!(token, TokenOrValue::Token(Token::Function(f)) if f.eq_ignore_ascii_case("env")))
+  }
 }
 
 /// A raw list of CSS tokens, with embedded parsed values.
@@ -631,7 +739,7 @@ impl<'a> ToCss for Token<'a> {
       Token::AtKeyword(x) => cssparser::Token::AtKeyword(x.as_ref().into()).to_css(dest)?,
       Token::Hash(x) => cssparser::Token::Hash(x.as_ref().into()).to_css(dest)?,
       Token::IDHash(x) => cssparser::Token::IDHash(x.as_ref().into()).to_css(dest)?,
-      Token::String(x) => cssparser::Token::QuotedString(x.as_ref().into()).to_css(dest)?,
+      Token::String(x) => dest.write_string(x)?,
       Token::UnquotedUrl(x) => cssparser::Token::UnquotedUrl(x.as_ref().into()).to_css(dest)?,
       Token::Function(x) => cssparser::Token::Function(x.as_ref().into()).to_css(dest)?,
       Token::BadUrl(x) => cssparser::Token::BadUrl(x.as_ref().into()).to_css(dest)?,
@@ -694,6 +802,112 @@ impl<'a> ToCss for Token<'a> {
 }
 
 impl<'i> TokenList<'i> {
+  /// Returns a copy of the token list that does not borrow from the source, e.g. when it was parsed
+  /// from a temporary string. Comments are dropped and whitespace is collapsed to a single space.
+  pub(crate) fn into_owned<'x>(self) -> TokenList<'x> {
+    fn owned<'x>(s: CowArcStr) -> CowArcStr<'x> {
+      s.to_string().into()
+    }
+
+    let tokens = self
+      .0
+      .into_iter()
+      .filter(|token| !matches!(token, TokenOrValue::Token(Token::Comment(_))))
+      .map(|token| match token {
+        TokenOrValue::Token(token) => TokenOrValue::Token(match token {
+          Token::Ident(s) => Token::Ident(owned(s)),
+          Token::AtKeyword(s) => Token::AtKeyword(owned(s)),
+          Token::Hash(s) => Token::Hash(owned(s)),
+          Token::IDHash(s) => Token::IDHash(owned(s)),
+          Token::String(s) => Token::String(owned(s)),
+          Token::UnquotedUrl(s) => Token::UnquotedUrl(owned(s)),
+          Token::Function(s) => Token::Function(owned(s)),
+          Token::BadUrl(s) => Token::BadUrl(owned(s)),
+          Token::BadString(s) => Token::BadString(owned(s)),
+          Token::Dimension {
+            has_sign,
+            value,
+            int_value,
+            unit,
+          } => Token::Dimension {
+            has_sign,
+            value,
+            int_value,
+            unit: owned(unit),
+          },
+          Token::WhiteSpace(_) | Token::Comment(_) => Token::WhiteSpace(" "),
+          Token::Delim(c) => Token::Delim(c),
+          Token::Number {
+            has_sign,
+            value,
+            int_value,
+          } => Token::Number {
+            has_sign,
+            value,
+            int_value,
+          },
+          Token::Percentage {
+            has_sign,
+            unit_value,
+            int_value,
+          } => Token::Percentage {
+            has_sign,
+            unit_value,
+            int_value,
+          },
+          Token::Colon => Token::Colon,
+          Token::Semicolon => Token::Semicolon,
+          Token::Comma => Token::Comma,
+          Token::IncludeMatch => Token::IncludeMatch,
+          Token::DashMatch => Token::DashMatch,
+          Token::PrefixMatch => Token::PrefixMatch,
+          Token::SuffixMatch => Token::SuffixMatch,
+          Token::SubstringMatch => Token::SubstringMatch,
+          Token::CDO => Token::CDO,
+          Token::CDC => Token::CDC,
+          Token::ParenthesisBlock => Token::ParenthesisBlock,
+          Token::SquareBracketBlock => Token::SquareBracketBlock,
+          Token::CurlyBracketBlock => Token::CurlyBracketBlock,
+          Token::CloseParenthesis => Token::CloseParenthesis,
+          Token::CloseSquareBracket => Token::CloseSquareBracket,
+          Token::CloseCurlyBracket => Token::CloseCurlyBracket,
+        }),
+        TokenOrValue::Color(color) => TokenOrValue::Color(color),
+        TokenOrValue::UnresolvedColor(color) => TokenOrValue::UnresolvedColor(match color {
+          UnresolvedColor::RGB { r, g, b, alpha } => UnresolvedColor::RGB {
+            r,
+            g,
+            b,
+            alpha: alpha.into_owned(),
+          },
+          UnresolvedColor::HSL { h, s, l, alpha } => UnresolvedColor::HSL {
+            h,
+            s,
+            l,
+            alpha: alpha.into_owned(),
+          },
+        }),
+        TokenOrValue::Url(url) => TokenOrValue::Url(Url {
+          url: owned(url.url),
+          loc: url.loc,
+        }),
+        TokenOrValue::Var(var) => TokenOrValue::Var(Variable {
+          name: DashedIdentReference {
+            ident: DashedIdent(owned(var.name.ident.0)),
+            from: var.name.from.map(|from| match from {
+              Specifier::Global => Specifier::Global,
+              Specifier::File(file) => Specifier::File(owned(file)),
+              Specifier::SourceIndex(index) => Specifier::SourceIndex(index),
+            }),
+          },
+          fallback: var.fallback.map(|fallback| fallback.into_owned()),
+          verbatim_fallback: var.verbatim_fallback.map(owned),
+        }),
+      })
+      .collect();
+    TokenList(tokens)
+  }
+
   pub(crate) fn get_necessary_fallbacks(&self, targets: Browsers) -> ColorFallbackKind {
     let mut fallbacks = ColorFallbackKind::empty();
     for token in &self.0 {
@@ -760,6 +974,10 @@ pub struct Variable<'i> {
   pub name: DashedIdentReference<'i>,
   /// A fallback value in case the variable is not defined.
   pub fallback: Option<TokenList<'i>>,
+  /// The fallback exactly as written in the source, when parsed with `preserve_var_fallbacks`.
+  /// If present, it is printed instead of `fallback`.
+  #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+  pub verbatim_fallback: Option<CowArcStr<'i>>,
 }
 
 impl<'i> Variable<'i> {
@@ -770,13 +988,23 @@ impl<'i> Variable<'i> {
   ) -> Result<Self, ParseError<'i, ParserError<'i>>> {
     let name = DashedIdentReference::parse_with_options(input, options)?;
 
+    let mut verbatim_fallback = None;
     let fallback = if input.try_parse(|input| input.expect_comma()).is_ok() {
-      Some(TokenList::parse(input, options, depth)?)
+      let start = input.position();
+      let fallback = TokenList::parse(input, options, depth)?;
+      if options.preserve_var_fallbacks {
+        verbatim_fallback = Some(input.slice_from(start).trim().into());
+      }
+      Some(fallback)
     } else {
       None
     };
 
-    Ok(Variable { name, fallback })
+    Ok(Variable {
+      name,
+      fallback,
+      verbatim_fallback,
+    })
   }
 
   fn to_css<W>(&self, dest: &mut Printer<W>, is_custom_property: bool) -> Result<(), PrinterError>
@@ -785,7 +1013,10 @@ impl<'i> Variable<'i> {
   {
     dest.write_str("var(")?;
     self.name.to_css(dest)?;
-    if let Some(fallback) = &self.fallback {
+    if let Some(fallback) = &self.verbatim_fallback {
+      dest.delim(',', false)?;
+      dest.write_str(fallback)?;
+    } else if let Some(fallback) = &self.fallback {
       dest.delim(',', false)?;
       fallback.to_css(dest, is_custom_property)?;
     }
@@ -923,3 +1154,631 @@ impl<'i> UnresolvedColor<'i> {
     }
   }
 }
+
+/// Inlines custom properties that are defined exactly once in a top-level `:root` rule,
+/// replacing their `var()` references with the defined value and removing the definition.
+///
+/// Custom properties are left alone if they are defined anywhere else, registered with `@property`,
+/// defined with `!important`, referenced with a fallback that differs from the defined value,
+/// or referenced within a fallback that is preserved as written.
+/// Returns the names of the inlined properties, in definition order.
+pub(crate) fn inline_custom_properties<'i>(
+  rules: &mut CssRuleList<'i>,
+  options: &InlineCustomProperties,
+  parser_options: &ParserOptions,
+) -> Vec<String> {
+  if *options == InlineCustomProperties::None {
+    return Vec::new();
+  }
+
+  let mut definitions: HashMap<String, usize> = HashMap::new();
+  let mut values: Vec<(String, TokenList<'i>)> = Vec::new();
+  let mut excluded = HashSet::new();
+  let mut fallbacks = Vec::new();
+  collect_registered_properties(rules, &mut excluded);
+  collect_other_variable_names(rules, &mut excluded);
+  visit_declarations(rules, true, &mut |declarations, is_root| {
+    for (property, important) in declarations.iter() {
+      match property {
+        Property::Custom(custom) => {
+          if custom.name.starts_with("--") {
+            *definitions.entry(custom.name.to_string()).or_default() += 1;
+            if is_root && !important {
+              values.push((custom.name.to_string(), custom.value.clone()));
+            }
+          }
+          collect_variables(&custom.value, &mut excluded, &mut fallbacks);
+        }
+        Property::Unparsed(unparsed) => collect_variables(&unparsed.value, &mut excluded, &mut fallbacks),
+        _ => {}
+      }
+    }
+  });
+
+  let mut inlined: HashMap<String, TokenList<'i>> = HashMap::new();
+  let mut names = Vec::new();
+  for (name, value) in values {
+    if definitions[&name] == 1 && !excluded.contains(&name) && options.includes(&name) && !has_variables(&value) {
+      names.push(name.clone());
+      inlined.insert(name, value);
+    }
+  }
+
+  for (name, fallback) in fallbacks {
+    if matches!(inlined.get(&name), Some(value) if *value != fallback) {
+      inlined.remove(&name);
+    }
+  }
+
+  names.retain(|name| inlined.contains_key(name));
+  if names.is_empty() {
+    return names;
+  }
+
+  visit_declarations(rules, true, &mut |declarations, is_root| {
+    if is_root {
+      declarations.retain_declarations(
+        |property| !matches!(property, Property::Custom(custom) if inlined.contains_key(custom.name.as_ref())),
+      );
+    }
+
+    for property in declarations.iter_mut() {
+      match property {
+        Property::Custom(CustomProperty { value, .. }) => substitute_variables(value, &inlined),
+        Property::Unparsed(UnparsedProperty { property_id, value }) => {
+          substitute_variables(value, &inlined);
+          minify_substituted_value(property_id, value, parser_options);
+        }
+        _ => {}
+      }
+    }
+  });
+
+  names
+}
+
+/// Re-parses a value that no longer references any variables as the typed property, and replaces it with
+/// the minified value of that property, e.g. `margin: 8px 8px` becomes `margin: 8px`.
+fn minify_substituted_value<'i>(property_id: &PropertyId<'i>, value: &mut TokenList<'i>, options: &ParserOptions) {
+  if has_variables(value) {
+    return;
+  }
+
+  let mut css = String::new();
+  let mut printer = Printer::new(&mut css, PrinterOptions::default());
+  if value.to_css(&mut printer, false).is_err() {
+    return;
+  }
+
+  let mut input = ParserInput::new(&css);
+  let mut parser = Parser::new(&mut input);
+  let minified = match Property::parse(property_id.clone(), &mut parser, options) {
+    Ok(Property::Unparsed(_)) | Ok(Property::Custom(_)) | Err(_) => return,
+    Ok(property) => match property.value_to_css_string(PrinterOptions {
+      minify: true,
+      ..PrinterOptions::default()
+    }) {
+      Ok(minified) => minified,
+      Err(_) => return,
+    },
+  };
+
+  let mut input = ParserInput::new(&minified);
+  let mut parser = Parser::new(&mut input);
+  let tokens = TokenList::parse(&mut parser, options, 0);
+  if let Ok(tokens) = tokens {
+    *value = tokens.into_owned();
+  }
+}
+
+/// Adds a fallback before each declaration that references custom properties, for browsers that do not
+/// support them. The fallback has the same property, with each `var()` replaced by the value of the custom
+/// property if it is only defined in top-level `:root` rules, or by the `var()` fallback if the custom property
+/// is not defined at all. Declarations that reference other custom properties are left alone, and a warning
+/// is emitted. No fallback is added if the previous declaration of the same property has no `var()` references,
+/// since it is already a fallback.
+pub(crate) fn add_custom_property_fallbacks<'i>(
+  rules: &mut CssRuleList<'i>,
+  options: &ParserOptions<'_, 'i>,
+  sources: &[String],
+) {
+  let mut defined = HashSet::new();
+  let mut root_values: HashMap<String, TokenList<'i>> = HashMap::new();
+  let mut dynamic = HashSet::new();
+  visit_declarations(rules, true, &mut |declarations, is_root| {
+    for (property, important) in declarations.iter() {
+      if let Property::Custom(custom) = property {
+        if custom.name.starts_with("--") {
+          defined.insert(custom.name.to_string());
+          if is_root && !important {
+            // The last definition wins.
+            root_values.insert(custom.name.to_string(), custom.value.clone());
+          } else {
+            dynamic.insert(custom.name.to_string());
+          }
+        }
+      }
+    }
+  });
+
+  // Values may reference other custom properties, which are resolved first. Cycles are never resolved.
+  let mut values: HashMap<String, TokenList<'i>> = HashMap::new();
+  loop {
+    let mut changed = false;
+    for (name, value) in &root_values {
+      if dynamic.contains(name) || values.contains_key(name) {
+        continue;
+      }
+      if let Ok(resolved) = resolve_variables(value, &values, &defined) {
+        values.insert(name.clone(), resolved);
+        changed = true;
+      }
+    }
+    if !changed {
+      break;
+    }
+  }
+
+  add_fallbacks(rules, &values, &defined, options, sources);
+}
+
+fn add_fallbacks<'i>(
+  rules: &mut CssRuleList<'i>,
+  values: &HashMap<String, TokenList<'i>>,
+  defined: &HashSet<String>,
+  options: &ParserOptions<'_, 'i>,
+  sources: &[String],
+) {
+  let add = |declarations: &mut DeclarationBlock<'i>, loc: Location| {
+    // The new index of each normal declaration, for the custom at-rules that precede it.
+    let mut indices = Vec::with_capacity(declarations.declarations.len() + 1);
+    for (important, list) in [
+      (false, &mut declarations.declarations),
+      (true, &mut declarations.important_declarations),
+    ] {
+      let mut result: Vec<Property<'i>> = Vec::with_capacity(list.len());
+      for property in list.drain(..) {
+        if !important {
+          indices.push(result.len());
+        }
+        if let Property::Unparsed(unparsed) = &property {
+          let has_fallback = matches!(
+            result.last(),
+            Some(prev) if prev.property_id() == unparsed.property_id
+              && !matches!(prev, Property::Unparsed(UnparsedProperty { value, .. }) if contains_var(value))
+          );
+          if contains_var(&unparsed.value) && !has_fallback {
+            match resolve_variables(&unparsed.value, values, defined) {
+              Ok(value) => result.push(Property::Unparsed(UnparsedProperty {
+                property_id: unparsed.property_id.clone(),
+                value,
+              })),
+              Err(name) => options.push_warning(|| Error {
+                kind: ParserError::UnresolvedCustomProperty(name.into()),
+                loc: Some(ErrorLocation::new(loc, sources[loc.source_index as usize].clone())),
+              }),
+            }
+          }
+        }
+        result.push(property);
+      }
+      if !important {
+        indices.push(result.len());
+      }
+      *list = result;
+    }
+    for (index, _) in &mut declarations.at_rules {
+      *index = indices[(*index).min(indices.len() - 1)];
+    }
+  };
+
+  for rule in &mut rules.0 {
+    let rules = match rule {
+      CssRule::Style(style) => {
+        add(&mut style.declarations, style.loc);
+        &mut style.rules
+      }
+      CssRule::Nesting(nesting) => {
+        add(&mut nesting.style.declarations, nesting.style.loc);
+        &mut nesting.style.rules
+      }
+      CssRule::Keyframes(keyframes) => {
+        for keyframe in &mut keyframes.keyframes {
+          add(&mut keyframe.declarations, keyframes.loc);
+        }
+        continue;
+      }
+      CssRule::Page(page) => {
+        add(&mut page.declarations, page.loc);
+        continue;
+      }
+      CssRule::Custom(custom) => match &mut custom.body {
+        Some(CustomAtRuleBody::DeclarationList(declarations)) => {
+          add(declarations, custom.loc);
+          continue;
+        }
+        Some(CustomAtRuleBody::RuleList(rules)) => rules,
+        _ => continue,
+      },
+      CssRule::Media(media) => &mut media.rules,
+      CssRule::Supports(supports) => &mut supports.rules,
+      CssRule::Container(container) => &mut container.rules,
+      CssRule::Scope(scope) => &mut scope.rules,
+      CssRule::LayerBlock(layer) => &mut layer.rules,
+      CssRule::MozDocument(document) => &mut document.rules,
+      _ => continue,
+    };
+    add_fallbacks(rules, values, defined, options, sources);
+  }
+}
+
+/// Returns whether a token list contains a `var()` reference, including within unresolved colors.
+fn contains_var(tokens: &TokenList) -> bool {
+  tokens.0.iter().any(|token| match token {
+    TokenOrValue::Var(_) => true,
+    TokenOrValue::UnresolvedColor(UnresolvedColor::RGB { alpha, .. })
+    | TokenOrValue::UnresolvedColor(UnresolvedColor::HSL { alpha, .. }) => contains_var(alpha),
+    _ => false,
+  })
+}
+
+/// Returns a copy of the token list with each `var()` replaced by the given value of the variable,
+/// or by its fallback if the variable is not defined. Returns the name of the first variable that
+/// cannot be resolved.
+fn resolve_variables<'i>(
+  tokens: &TokenList<'i>,
+  values: &HashMap<String, TokenList<'i>>,
+  defined: &HashSet<String>,
+) -> Result<TokenList<'i>, String> {
+  replace_variables(tokens, &mut |var| {
+    let name = var.name.ident.0.as_ref();
+    match (values.get(name), &var.fallback) {
+      (Some(value), _) if var.name.from.is_none() => Ok(Some(value.clone())),
+      (None, Some(fallback)) if var.name.from.is_none() && !defined.contains(name) => {
+        resolve_variables(fallback, values, defined).map(Some)
+      }
+      _ => Err(name.to_owned()),
+    }
+  })
+}
+
+/// Removes declarations of custom properties that are not referenced by a `var()` in the rules, either
+/// directly or through the values of other custom properties that are referenced.
+pub(crate) fn remove_unused_custom_properties(rules: &mut CssRuleList) {
+  let mut declared = HashSet::new();
+  let mut used = HashSet::new();
+  let mut dependencies: HashMap<String, HashSet<String>> = HashMap::new();
+  collect_other_variable_names(rules, &mut used);
+  visit_declarations(rules, true, &mut |declarations, _| {
+    for (property, _) in declarations.iter() {
+      match property {
+        Property::Custom(custom) if custom.name.starts_with("--") => {
+          declared.insert(custom.name.to_string());
+          collect_variable_names(&custom.value, dependencies.entry(custom.name.to_string()).or_default());
+        }
+        Property::Custom(CustomProperty { value, .. }) | Property::Unparsed(UnparsedProperty { value, .. }) => {
+          collect_variable_names(value, &mut used)
+        }
+        _ => {}
+      }
+    }
+  });
+
+  let mut stack: Vec<String> = used.iter().cloned().collect();
+  while let Some(name) = stack.pop() {
+    for dependency in dependencies.get(&name).into_iter().flatten() {
+      if used.insert(dependency.clone()) {
+        stack.push(dependency.clone());
+      }
+    }
+  }
+
+  declared.retain(|name| !used.contains(name));
+  if declared.is_empty() {
+    return;
+  }
+
+  let is_used =
+    |property: &Property| !matches!(property, Property::Custom(custom) if declared.contains(custom.name.as_ref()));
+  visit_declarations(rules, true, &mut |declarations, _| {
+    declarations.retain_declarations(is_used);
+    declarations.retain_important_declarations(is_used);
+  });
+}
+
+/// Collects the names of variables referenced in rules that are not visited by [visit_declarations].
+fn collect_other_variable_names(rules: &CssRuleList, names: &mut HashSet<String>) {
+  for rule in &rules.0 {
+    let rules = match rule {
+      CssRule::Viewport(viewport) => {
+        for (property, _) in viewport.declarations.iter() {
+          if let Property::Custom(CustomProperty { value, .. })
+          | Property::Unparsed(UnparsedProperty { value, .. }) = property
+          {
+            collect_variable_names(value, names);
+          }
+        }
+        continue;
+      }
+      CssRule::FontFace(font_face) => {
+        for property in &font_face.properties {
+          if let FontFaceProperty::Custom(custom) = property {
+            collect_variable_names(&custom.value, names);
+          }
+        }
+        continue;
+      }
+      CssRule::Unknown(unknown) => {
+        collect_variable_names(&unknown.prelude, names);
+        if let Some(block) = &unknown.block {
+          collect_variable_names(block, names);
+        }
+        continue;
+      }
+      CssRule::Custom(custom) => match &custom.body {
+        Some(CustomAtRuleBody::Raw(tokens)) => {
+          collect_variable_names(tokens, names);
+          continue;
+        }
+        Some(CustomAtRuleBody::DeclarationList(declarations)) => {
+          collect_at_rule_variable_names(declarations, names);
+          continue;
+        }
+        Some(CustomAtRuleBody::RuleList(rules)) => rules,
+        None => continue,
+      },
+      CssRule::Style(style) => {
+        collect_at_rule_variable_names(&style.declarations, names);
+        &style.rules
+      }
+      CssRule::Nesting(nesting) => {
+        collect_at_rule_variable_names(&nesting.style.declarations, names);
+        &nesting.style.rules
+      }
+      CssRule::Media(media) => &media.rules,
+      CssRule::Supports(supports) => &supports.rules,
+      CssRule::Container(container) => &container.rules,
+      CssRule::Scope(scope) => &scope.rules,
+      CssRule::LayerBlock(layer) => &layer.rules,
+      CssRule::MozDocument(document) => &document.rules,
+      _ => continue,
+    };
+    collect_other_variable_names(rules, names);
+  }
+}
+
+/// Collects the names of variables referenced in the custom at-rules of a declaration block that are not
+/// visited by [visit_declarations].
+fn collect_at_rule_variable_names(declarations: &DeclarationBlock, names: &mut HashSet<String>) {
+  for (_, rule) in &declarations.at_rules {
+    match &rule.body {
+      Some(CustomAtRuleBody::Raw(tokens)) => collect_variable_names(tokens, names),
+      Some(CustomAtRuleBody::RuleList(rules)) => collect_other_variable_names(rules, names),
+      Some(CustomAtRuleBody::DeclarationList(declarations)) => collect_at_rule_variable_names(declarations, names),
+      None => {}
+    }
+  }
+}
+
+/// Calls the given function for each declaration block in the rules, along with whether
+/// the block belongs to a top-level `:root` rule.
+fn visit_declarations<'i, F: FnMut(&mut DeclarationBlock<'i>, bool)>(
+  rules: &mut CssRuleList<'i>,
+  top_level: bool,
+  f: &mut F,
+) {
+  for rule in &mut rules.0 {
+    let rules = match rule {
+      CssRule::Style(style) => {
+        let is_root = top_level
+          && style.selectors.0.len() == 1
+          && style.selectors.0[0].len() == 1
+          && matches!(
+            style.selectors.0[0].iter_raw_match_order().next(),
+            Some(Component::Root)
+          );
+        visit_block(&mut style.declarations, is_root, f);
+        &mut style.rules
+      }
+      CssRule::Nesting(nesting) => {
+        visit_block(&mut nesting.style.declarations, false, f);
+        &mut nesting.style.rules
+      }
+      CssRule::Keyframes(keyframes) => {
+        for keyframe in &mut keyframes.keyframes {
+          visit_block(&mut keyframe.declarations, false, f);
+        }
+        continue;
+      }
+      CssRule::Page(page) => {
+        visit_block(&mut page.declarations, false, f);
+        continue;
+      }
+      CssRule::Custom(custom) => match &mut custom.body {
+        Some(CustomAtRuleBody::DeclarationList(declarations)) => {
+          visit_block(declarations, false, f);
+          continue;
+        }
+        Some(CustomAtRuleBody::RuleList(rules)) => rules,
+        _ => continue,
+      },
+      CssRule::Media(media) => &mut media.rules,
+      CssRule::Supports(supports) => &mut supports.rules,
+      CssRule::Container(container) => &mut container.rules,
+      CssRule::Scope(scope) => &mut scope.rules,
+      CssRule::LayerBlock(layer) => &mut layer.rules,
+      CssRule::MozDocument(document) => &mut document.rules,
+      _ => continue,
+    };
+    visit_declarations(rules, false, f);
+  }
+}
+
+/// Calls the given function for a declaration block, and the declaration blocks within its custom at-rules.
+fn visit_block<'i, F: FnMut(&mut DeclarationBlock<'i>, bool)>(
+  declarations: &mut DeclarationBlock<'i>,
+  is_root: bool,
+  f: &mut F,
+) {
+  f(declarations, is_root);
+  for (_, rule) in &mut declarations.at_rules {
+    match &mut rule.body {
+      Some(CustomAtRuleBody::DeclarationList(declarations)) => visit_block(declarations, false, f),
+      Some(CustomAtRuleBody::RuleList(rules)) => visit_declarations(rules, false, f),
+      _ => {}
+    }
+  }
+}
+
+fn collect_registered_properties(rules: &CssRuleList, registered: &mut HashSet<String>) {
+  for rule in &rules.0 {
+    let rules = match rule {
+      CssRule::Property(property) => {
+        registered.insert(property.name.0.to_string());
+        continue;
+      }
+      CssRule::Style(style) => &style.rules,
+      CssRule::Nesting(nesting) => &nesting.style.rules,
+      CssRule::Media(media) => &media.rules,
+      CssRule::Supports(supports) => &supports.rules,
+      CssRule::Container(container) => &container.rules,
+      CssRule::Scope(scope) => &scope.rules,
+      CssRule::LayerBlock(layer) => &layer.rules,
+      CssRule::MozDocument(document) => &document.rules,
+      _ => continue,
+    };
+    collect_registered_properties(rules, registered);
+  }
+}
+
+/// Collects the fallbacks of the variables referenced in a token list. Variables that
+/// reference another file via CSS modules are excluded from inlining.
+fn collect_variables<'i>(
+  tokens: &TokenList<'i>,
+  excluded: &mut HashSet<String>,
+  fallbacks: &mut Vec<(String, TokenList<'i>)>,
+) {
+  for token in &tokens.0 {
+    match token {
+      TokenOrValue::Var(var) => {
+        if var.name.from.is_some() {
+          excluded.insert(var.name.ident.0.to_string());
+        }
+        if let Some(fallback) = &var.fallback {
+          fallbacks.push((var.name.ident.0.to_string(), fallback.clone()));
+          if var.verbatim_fallback.is_some() {
+            collect_variable_names(fallback, excluded);
+          } else {
+            collect_variables(fallback, excluded, fallbacks);
+          }
+        }
+      }
+      TokenOrValue::UnresolvedColor(UnresolvedColor::RGB { alpha, .. })
+      | TokenOrValue::UnresolvedColor(UnresolvedColor::HSL { alpha, .. }) => {
+        collect_variables(alpha, excluded, fallbacks)
+      }
+      _ => {}
+    }
+  }
+}
+
+/// Collects the names of all variables referenced in a token list, including within fallbacks.
+fn collect_variable_names(tokens: &TokenList, names: &mut HashSet<String>) {
+  for token in &tokens.0 {
+    match token {
+      TokenOrValue::Var(var) => {
+        names.insert(var.name.ident.0.to_string());
+        if let Some(fallback) = &var.fallback {
+          collect_variable_names(fallback, names);
+        }
+      }
+      TokenOrValue::UnresolvedColor(UnresolvedColor::RGB { alpha, .. })
+      | TokenOrValue::UnresolvedColor(UnresolvedColor::HSL { alpha, .. }) => collect_variable_names(alpha, names),
+      _ => {}
+    }
+  }
+}
+
+fn has_variables(tokens: &TokenList) -> bool {
+  tokens
+    .0
+    .iter()
+    .any(|token| matches!(token, TokenOrValue::Var(_) | TokenOrValue::UnresolvedColor(_)))
+}
+
+fn substitute_variables<'i>(tokens: &mut TokenList<'i>, values: &HashMap<String, TokenList<'i>>) {
+  if !has_variables(tokens) {
+    return;
+  }
+
+  if let Ok(result) = replace_variables(tokens, &mut |var| Ok(values.get(var.name.ident.0.as_ref()).cloned())) {
+    *tokens = result;
+  }
+}
+
+/// Returns a copy of the token list with each `var()` replaced by the value returned for it by `replace`.
+/// Variables for which `None` is returned are kept, with their fallbacks replaced in turn.
+fn replace_variables<'i, F>(tokens: &TokenList<'i>, replace: &mut F) -> Result<TokenList<'i>, String>
+where
+  F: FnMut(&Variable<'i>) -> Result<Option<TokenList<'i>>, String>,
+{
+  let mut result: Vec<TokenOrValue<'i>> = Vec::with_capacity(tokens.0.len());
+  let mut iter = tokens.0.iter().peekable();
+  while let Some(token) = iter.next() {
+    match token {
+      TokenOrValue::Var(var) => match replace(var)? {
+        Some(value) => {
+          // Whitespace around a var() is not preserved during parsing, so it must be added back
+          // where the substituted value would otherwise merge with the adjacent tokens.
+          if matches!(result.last(), Some(prev) if is_value_token(prev) && !matches!(prev, TokenOrValue::Token(Token::Function(_))))
+          {
+            result.push(Token::WhiteSpace(" ").into());
+          }
+          result.extend(value.0);
+          if matches!(iter.peek(), Some(next) if is_value_token(next) && !matches!(next, TokenOrValue::Token(Token::CloseParenthesis)))
+          {
+            result.push(Token::WhiteSpace(" ").into());
+          }
+        }
+        None => {
+          let mut var = var.clone();
+          if let Some(fallback) = &mut var.fallback {
+            *fallback = replace_variables(fallback, replace)?;
+          }
+          result.push(TokenOrValue::Var(var));
+        }
+      },
+      TokenOrValue::UnresolvedColor(color) => {
+        let mut color = color.clone();
+        match &mut color {
+          UnresolvedColor::RGB { alpha, .. } | UnresolvedColor::HSL { alpha, .. } => {
+            *alpha = replace_variables(alpha, replace)?
+          }
+        }
+        result.push(TokenOrValue::UnresolvedColor(color));
+      }
+      token => result.push(token.clone()),
+    }
+  }
+  Ok(TokenList(result))
+}
+
+fn is_value_token(token: &TokenOrValue) -> bool {
+  match token {
+    TokenOrValue::Token(token) => matches!(
+      token,
+      Token::Ident(_)
+        | Token::AtKeyword(_)
+        | Token::Hash(_)
+        | Token::IDHash(_)
+        | Token::String(_)
+        | Token::UnquotedUrl(_)
+        | Token::Number { .. }
+        | Token::Percentage { .. }
+        | Token::Dimension { .. }
+        | Token::Function(_)
+        | Token::CloseParenthesis
+    ),
+    _ => true,
+  }
+}
@@ -1,7 +1,5 @@
 //! CSS properties related to fonts.
 
-use std::collections::HashSet;
-
 use super::{Property, PropertyId};
 use crate::compat::Feature;
 use crate::context::PropertyHandlerContext;
@@ -395,6 +393,28 @@ impl<'i> Parse<'i> for FontFamily<'i> {
   }
 }
 
+impl<'i> FontFamily<'i> {
+  /// Returns whether two font families refer to the same family. Family names are
+  /// [matched](https://www.w3.org/TR/css-fonts-4/#font-family-casing) ASCII case-insensitively,
+  /// regardless of whether they were quoted.
+  pub fn matches(&self, other: &FontFamily) -> bool {
+    match (self, other) {
+      (FontFamily::FamilyName(a), FontFamily::FamilyName(b)) => a.eq_ignore_ascii_case(b),
+      (FontFamily::Generic(a), FontFamily::Generic(b)) => a == b,
+      _ => false,
+    }
+  }
+
+  /// Returns whether this is a generic family keyword such as `serif`, or a family name that
+  /// is spelled the same way as one, and must therefore be quoted.
+  pub(crate) fn is_generic_keyword(&self) -> bool {
+    match self {
+      FontFamily::Generic(_) => true,
+      FontFamily::FamilyName(name) => GenericFontFamily::parse_string(name).is_ok(),
+    }
+  }
+}
+
 impl<'i> ToCss for FontFamily<'i> {
   fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
   where
@@ -421,7 +441,7 @@ impl<'i> ToCss for FontFamily<'i> {
             return dest.write_str(&id);
           }
         }
-        serialize_string(&val, dest)?;
+        dest.write_string(&val)?;
         Ok(())
       }
     }
@@ -857,9 +877,15 @@ impl<'i> PropertyHandler<'i> for FontHandler<'i> {
 
     if let Some(family) = &mut family {
       if family.len() > 1 {
-        // Dedupe.
-        let mut seen = HashSet::new();
-        family.retain(|f| seen.insert(f.clone()));
+        // Dedupe, keeping the first occurrence of each family as written.
+        let mut seen: Vec<FontFamily> = Vec::with_capacity(family.len());
+        family.retain(|f| {
+          if seen.iter().any(|s| s.matches(f)) {
+            return false;
+          }
+          seen.push(f.clone());
+          true
+        });
       }
     }
 
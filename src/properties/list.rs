@@ -59,7 +59,7 @@ impl ToCss for ListStyleType<'_> {
       ListStyleType::None => dest.write_str("none"),
       ListStyleType::CounterStyle(style) => style.to_css(dest),
       ListStyleType::String(s) => {
-        serialize_string(&s, dest)?;
+        dest.write_string(&s)?;
         Ok(())
       }
     }
@@ -170,6 +170,15 @@ impl<'i> Parse<'i> for CounterStyle<'i> {
           symbols.push(s);
         }
 
+        // Alphabetic and numeric systems require at least two symbols, and all others at least one.
+        let min = match t {
+          SymbolsType::Alphabetic | SymbolsType::Numeric => 2,
+          _ => 1,
+        };
+        if symbols.len() < min {
+          return Err(input.new_custom_error(ParserError::InvalidValue));
+        }
+
         Ok(CounterStyle::Symbols(t, symbols))
       });
     }
@@ -264,7 +273,7 @@ impl<'i> ToCss for Symbol<'i> {
   {
     match self {
       Symbol::String(s) => {
-        serialize_string(&s, dest)?;
+        dest.write_string(&s)?;
         Ok(())
       }
       Symbol::Image(img) => img.to_css(dest),
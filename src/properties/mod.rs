@@ -320,7 +320,8 @@ macro_rules! define_properties {
     }
 
     impl<'i> PropertyId<'i> {
-      fn prefix(&self) -> VendorPrefix {
+      /// Returns the vendor prefixes for this property.
+      pub fn prefix(&self) -> VendorPrefix {
         use PropertyId::*;
         match self {
           $(
@@ -343,7 +344,8 @@ macro_rules! define_properties {
         }
       }
 
-      fn with_prefix(&self, prefix: VendorPrefix) -> PropertyId<'i> {
+      /// Returns the property with the given vendor prefixes, if it supports them.
+      pub fn with_prefix(&self, prefix: VendorPrefix) -> PropertyId<'i> {
         use PropertyId::*;
         match self {
           $(
@@ -365,7 +367,7 @@ macro_rules! define_properties {
         }
       }
 
-      fn set_prefixes_for_targets(&mut self, targets: Option<Browsers>) {
+      pub(crate) fn set_prefixes_for_targets(&mut self, targets: Option<Browsers>) {
         match self {
           $(
             $(#[$meta])*
@@ -546,6 +548,16 @@ macro_rules! define_properties {
       pub fn parse<'t>(property_id: PropertyId<'i>, input: &mut Parser<'i, 't>, options: &ParserOptions) -> Result<Property<'i>, ParseError<'i, ParserError<'i>>> {
         let state = input.state();
 
+        // Math functions are evaluated while parsing, so skip straight to an unparsed
+        // property to print them as written.
+        if options.preserve_calc && !matches!(property_id, PropertyId::Custom(_)) {
+          let has_math_function = crate::values::calc::contains_math_function(input);
+          input.reset(&state);
+          if has_math_function {
+            return Ok(Property::Unparsed(UnparsedProperty::parse(property_id, input, options)?))
+          }
+        }
+
         match property_id {
           $(
             $(#[$meta])*
@@ -583,6 +595,25 @@ macro_rules! define_properties {
         }
       }
 
+      /// Returns a copy of the property with the given vendor prefix. Properties that
+      /// do not support vendor prefixes are returned unchanged.
+      #[allow(unused_variables)]
+      pub(crate) fn with_prefix(&self, prefix: VendorPrefix) -> Property<'i> {
+        use Property::*;
+
+        match self {
+          $(
+            $(#[$meta])*
+            $property(val, $(vp_name!($vp, _p))?) => $property(val.clone(), $(vp_name!($vp, prefix))?),
+          )+
+          Unparsed(unparsed) => Unparsed(UnparsedProperty {
+            property_id: unparsed.property_id.with_prefix(prefix),
+            value: unparsed.value.clone(),
+          }),
+          Custom(custom) => Custom(custom.clone())
+        }
+      }
+
       /// Parses a CSS property from a string.
       pub fn parse_string(property_id: PropertyId<'i>, input: &'i str, options: ParserOptions) -> Result<Self, ParseError<'i, ParserError<'i>>> {
         let mut input = ParserInput::new(input);
@@ -605,7 +636,7 @@ macro_rules! define_properties {
             unparsed.value.to_css(dest, false)
           }
           Custom(custom) => {
-            custom.value.to_css(dest, custom.name.starts_with("--"))
+            custom.value_to_css(dest)
           }
         }
       }
@@ -1032,6 +1063,16 @@ define_properties! {
   "animation-fill-mode": AnimationFillMode(SmallVec<[AnimationFillMode; 1]>, VendorPrefix) / WebKit / Moz / O,
   "animation": Animation(AnimationList<'i>, VendorPrefix) / WebKit / Moz / O shorthand: true,
 
+  // https://drafts.csswg.org/scroll-animations-1/
+  "animation-timeline": AnimationTimeline(SmallVec<[AnimationTimeline<'i>; 1]>),
+  "scroll-timeline-name": ScrollTimelineName(SmallVec<[TimelineName<'i>; 1]>),
+  "scroll-timeline-axis": ScrollTimelineAxis(SmallVec<[ScrollAxis; 1]>),
+  "scroll-timeline": ScrollTimeline(SmallVec<[ScrollTimeline<'i>; 1]>) shorthand: true,
+  "view-timeline-name": ViewTimelineName(SmallVec<[TimelineName<'i>; 1]>),
+  "view-timeline-axis": ViewTimelineAxis(SmallVec<[ScrollAxis; 1]>),
+  "view-timeline-inset": ViewTimelineInset(SmallVec<[ViewTimelineInset; 1]>),
+  "view-timeline": ViewTimeline(SmallVec<[ViewTimeline<'i>; 1]>) shorthand: true,
+
   // https://drafts.csswg.org/css-transforms-2/
   "transform": Transform(TransformList, VendorPrefix) / WebKit / Moz / Ms / O,
   "transform-origin": TransformOrigin(Position, VendorPrefix) / WebKit / Moz / Ms / O, // TODO: handle z offset syntax
@@ -1189,14 +1230,7 @@ impl<T: smallvec::Array<Item = V>, V: ToCss> ToCss for SmallVec<T> {
   where
     W: std::fmt::Write,
   {
-    let len = self.len();
-    for (idx, val) in self.iter().enumerate() {
-      val.to_css(dest)?;
-      if idx < len - 1 {
-        dest.delim(',', false)?;
-      }
-    }
-    Ok(())
+    dest.write_comma_separated(self)
   }
 }
 
@@ -1211,13 +1245,6 @@ impl<T: ToCss> ToCss for Vec<T> {
   where
     W: std::fmt::Write,
   {
-    let len = self.len();
-    for (idx, val) in self.iter().enumerate() {
-      val.to_css(dest)?;
-      if idx < len - 1 {
-        dest.delim(',', false)?;
-      }
-    }
-    Ok(())
+    dest.write_comma_separated(self)
   }
 }
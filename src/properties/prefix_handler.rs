@@ -148,7 +148,7 @@ macro_rules! define_fallbacks {
           )+
           Property::Custom(custom) => {
             let mut custom = custom.clone();
-            if context.context != DeclarationContext::Keyframes {
+            if context.context != DeclarationContext::Keyframes && custom.verbatim_value.is_none() {
               if let Some(targets) = self.targets {
                 let fallbacks = custom.value.get_fallbacks(targets);
                 for (condition, fallback) in fallbacks {
@@ -156,7 +156,8 @@ macro_rules! define_fallbacks {
                     condition,
                     Property::Custom(CustomProperty {
                       name: custom.name.clone(),
-                      value: fallback
+                      value: fallback,
+                      verbatim_value: None,
                     })
                   );
                 }
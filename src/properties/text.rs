@@ -770,7 +770,7 @@ impl<'i> ToCss for TextEmphasisStyle<'i> {
     match self {
       TextEmphasisStyle::None => dest.write_str("none"),
       TextEmphasisStyle::String(s) => {
-        serialize_string(&s, dest)?;
+        dest.write_string(&s)?;
         Ok(())
       }
       TextEmphasisStyle::Keyword { fill, shape } => {
@@ -0,0 +1,75 @@
+//! Comments between rules.
+
+use super::Location;
+use crate::error::PrinterError;
+use crate::parser::Comments;
+use crate::printer::Printer;
+use crate::traits::ToCss;
+use crate::values::string::CowArcStr;
+
+/// A comment between rules, retained according to the `comments` option of
+/// [ParserOptions](crate::stylesheet::ParserOptions).
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CommentRule<'i> {
+  /// The text of the comment, without the `/*` and `*/`.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  pub comment: CowArcStr<'i>,
+  /// The location of the rule in the source file.
+  pub loc: Location,
+}
+
+impl<'i> CommentRule<'i> {
+  /// Returns whether this is a license comment, i.e. one starting with `!`,
+  /// or containing a `@license` or `@preserve` annotation.
+  pub fn is_license(&self) -> bool {
+    is_license_comment(&self.comment)
+  }
+
+  /// Returns whether the comment should be kept for the given `comments` option.
+  /// Source map annotations are never kept, because they refer to the input file.
+  pub(crate) fn is_included(&self, comments: Comments) -> bool {
+    if is_source_map_comment(&self.comment) {
+      return false;
+    }
+
+    match comments {
+      Comments::None => false,
+      Comments::License => self.is_license(),
+      Comments::All => true,
+    }
+  }
+}
+
+pub(crate) fn is_license_comment(comment: &str) -> bool {
+  comment.starts_with('!') || comment.contains("@license") || comment.contains("@preserve")
+}
+
+/// Returns whether a comment is a `# sourceMappingURL=` or `# sourceURL=` annotation.
+fn is_source_map_comment(comment: &str) -> bool {
+  match comment.strip_prefix('#').or_else(|| comment.strip_prefix('@')) {
+    Some(annotation) => {
+      let annotation = annotation.trim_start();
+      annotation.starts_with("sourceMappingURL=") || annotation.starts_with("sourceURL=")
+    }
+    None => false,
+  }
+}
+
+impl<'i> ToCss for CommentRule<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    dest.add_mapping(self.loc);
+    dest.write_str("/*")?;
+    // Write newlines separately so that line numbers in the source map stay correct.
+    for (i, line) in self.comment.split('\n').enumerate() {
+      if i > 0 {
+        dest.write_char('\n')?;
+      }
+      dest.write_str(line)?;
+    }
+    dest.write_str("*/")
+  }
+}
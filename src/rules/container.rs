@@ -57,7 +57,7 @@ impl<'i> ContainerRule<'i> {
     parent_is_unused: bool,
   ) -> Result<bool, MinifyError> {
     self.rules.minify(context, parent_is_unused)?;
-    Ok(self.rules.0.is_empty())
+    Ok(context.remove_empty_rules && self.rules.0.is_empty())
   }
 }
 
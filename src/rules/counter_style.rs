@@ -1,11 +1,21 @@
 //! The `@counter-style` rule.
 
-use super::Location;
+use super::custom_at_rule::CustomAtRuleBody;
+use super::{CssRule, CssRuleList, Location};
 use crate::declaration::DeclarationBlock;
-use crate::error::PrinterError;
+use crate::error::{ParserError, PrinterError};
 use crate::printer::Printer;
-use crate::traits::ToCss;
+use crate::properties::custom::{CustomProperty, Token as CustomToken, TokenList, TokenOrValue};
+use crate::properties::list::{CounterStyle, ListStyleType, PredefinedCounterStyle, Symbol};
+use crate::properties::{Property, PropertyId};
+use crate::stylesheet::ParserOptions;
+use crate::traits::{Parse, ToCss};
 use crate::values::ident::CustomIdent;
+use crate::values::image::Image;
+use crate::values::number::CSSInteger;
+use crate::values::string::CowArcStr;
+use cssparser::*;
+use std::collections::HashSet;
 
 /// A [@counter-style](https://drafts.csswg.org/css-counter-styles/#the-counter-style-rule) rule.
 #[derive(Debug, PartialEq, Clone)]
@@ -14,13 +24,588 @@ pub struct CounterStyleRule<'i> {
   /// The name of the counter style to declare.
   #[cfg_attr(feature = "serde", serde(borrow))]
   pub name: CustomIdent<'i>,
-  // TODO: eventually parse these properties
-  /// Declarations in the `@counter-style` rule.
-  pub declarations: DeclarationBlock<'i>,
+  /// Descriptors in the `@counter-style` rule.
+  pub declarations: Vec<CounterStyleProperty<'i>>,
   /// The location of the rule in the source file.
   pub loc: Location,
 }
 
+/// A descriptor within a `@counter-style` rule.
+///
+/// See [CounterStyleRule](CounterStyleRule).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum CounterStyleProperty<'i> {
+  /// The [system](https://drafts.csswg.org/css-counter-styles/#counter-style-system) descriptor.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  System(CounterSystem<'i>),
+  /// The [negative](https://drafts.csswg.org/css-counter-styles/#counter-style-negative) descriptor,
+  /// with the symbols to add before and optionally after a negative value.
+  Negative(CounterStyleSymbol<'i>, Option<CounterStyleSymbol<'i>>),
+  /// The [prefix](https://drafts.csswg.org/css-counter-styles/#counter-style-prefix) descriptor.
+  Prefix(CounterStyleSymbol<'i>),
+  /// The [suffix](https://drafts.csswg.org/css-counter-styles/#counter-style-suffix) descriptor.
+  Suffix(CounterStyleSymbol<'i>),
+  /// The [range](https://drafts.csswg.org/css-counter-styles/#counter-style-range) descriptor.
+  Range(CounterRange),
+  /// The [pad](https://drafts.csswg.org/css-counter-styles/#counter-style-pad) descriptor,
+  /// with the minimum length and the symbol to pad with.
+  Pad(CSSInteger, CounterStyleSymbol<'i>),
+  /// The [fallback](https://drafts.csswg.org/css-counter-styles/#counter-style-fallback) descriptor.
+  Fallback(CounterStyle<'i>),
+  /// The [symbols](https://drafts.csswg.org/css-counter-styles/#counter-style-symbols) descriptor.
+  Symbols(Vec<CounterStyleSymbol<'i>>),
+  /// The [additive-symbols](https://drafts.csswg.org/css-counter-styles/#counter-style-symbols) descriptor.
+  AdditiveSymbols(Vec<AdditiveSymbol<'i>>),
+  /// The [speak-as](https://drafts.csswg.org/css-counter-styles/#counter-style-speak-as) descriptor.
+  SpeakAs(SpeakAs<'i>),
+  /// An unknown descriptor, or a descriptor with an invalid value.
+  Custom(CustomProperty<'i>),
+}
+
+/// A value for the [system](https://drafts.csswg.org/css-counter-styles/#counter-style-system)
+/// descriptor in a `@counter-style` rule.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum CounterSystem<'i> {
+  /// Cycles through the symbols repeatedly.
+  Cyclic,
+  /// Interprets the symbols as digits of a place-value numbering system.
+  Numeric,
+  /// Interprets the symbols as digits of an alphabetic numbering system.
+  Alphabetic,
+  /// Cycles through the symbols, doubling, tripling, etc. them on each pass.
+  Symbolic,
+  /// Represents values as a sum of the weighted additive symbols.
+  Additive,
+  /// Runs through the symbols once, starting at the given value.
+  Fixed(CSSInteger),
+  /// Uses the algorithm of another counter style, overriding some of its descriptors.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  Extends(CounterStyle<'i>),
+}
+
+/// A value for the [range](https://drafts.csswg.org/css-counter-styles/#counter-style-range)
+/// descriptor in a `@counter-style` rule.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum CounterRange {
+  /// The full range supported by the counter system.
+  Auto,
+  /// A list of ranges, each with a lower and upper bound.
+  Ranges(Vec<(CounterBound, CounterBound)>),
+}
+
+/// A bound of a range in the [range](https://drafts.csswg.org/css-counter-styles/#counter-style-range)
+/// descriptor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum CounterBound {
+  /// An integer bound.
+  Integer(CSSInteger),
+  /// An infinite bound, i.e. negative infinity for a lower bound, and positive infinity for an upper bound.
+  Infinite,
+}
+
+/// A weighted symbol in the [additive-symbols](https://drafts.csswg.org/css-counter-styles/#counter-style-symbols)
+/// descriptor.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdditiveSymbol<'i> {
+  /// The weight of the symbol.
+  pub weight: CSSInteger,
+  /// The symbol.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  pub symbol: CounterStyleSymbol<'i>,
+}
+
+/// A [symbol](https://drafts.csswg.org/css-counter-styles/#typedef-symbol) in a `@counter-style` descriptor.
+/// Unlike [Symbol](Symbol), which is used in the `symbols()` function, this may also be an identifier.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum CounterStyleSymbol<'i> {
+  /// A string.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  String(CowArcStr<'i>),
+  /// An image.
+  Image(Image<'i>),
+  /// An identifier.
+  Ident(CustomIdent<'i>),
+}
+
+impl<'i> Parse<'i> for CounterStyleSymbol<'i> {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    if let Ok(symbol) = input.try_parse(Symbol::parse) {
+      return Ok(match symbol {
+        Symbol::String(s) => CounterStyleSymbol::String(s),
+        Symbol::Image(image) => CounterStyleSymbol::Image(image),
+      });
+    }
+
+    Ok(CounterStyleSymbol::Ident(CustomIdent::parse(input)?))
+  }
+}
+
+impl<'i> ToCss for CounterStyleSymbol<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    match self {
+      CounterStyleSymbol::String(s) => {
+        dest.write_string(&s)?;
+        Ok(())
+      }
+      CounterStyleSymbol::Image(image) => image.to_css(dest),
+      // Symbols are not renamed by CSS modules, so write the identifier directly.
+      CounterStyleSymbol::Ident(ident) => {
+        serialize_identifier(&ident.0, dest)?;
+        Ok(())
+      }
+    }
+  }
+}
+
+/// A value for the [speak-as](https://drafts.csswg.org/css-counter-styles/#counter-style-speak-as)
+/// descriptor in a `@counter-style` rule.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum SpeakAs<'i> {
+  /// Determined by the counter system.
+  Auto,
+  /// Spoken as a bullet.
+  Bullets,
+  /// Spoken as a number.
+  Numbers,
+  /// Spoken as the generated text.
+  Words,
+  /// Spoken letter by letter.
+  SpellOut,
+  /// Spoken as the given counter style would be.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  CounterStyle(CounterStyle<'i>),
+}
+
+pub(crate) struct CounterStyleDeclarationParser;
+
+impl<'i> cssparser::DeclarationParser<'i> for CounterStyleDeclarationParser {
+  type Declaration = CounterStyleProperty<'i>;
+  type Error = ParserError<'i>;
+
+  fn parse_value<'t>(
+    &mut self,
+    name: CowRcStr<'i>,
+    input: &mut cssparser::Parser<'i, 't>,
+  ) -> Result<Self::Declaration, cssparser::ParseError<'i, Self::Error>> {
+    macro_rules! property {
+      ($property: ident, $parse: expr) => {
+        if let Ok(value) = input.try_parse(|input| {
+          let value = $parse(input)?;
+          input.expect_exhausted()?;
+          Ok::<_, ParseError<'i, ParserError<'i>>>(value)
+        }) {
+          return Ok(CounterStyleProperty::$property(value));
+        }
+      };
+    }
+
+    match_ignore_ascii_case! { &name,
+      "system" => property!(System, CounterSystem::parse),
+      "prefix" => property!(Prefix, CounterStyleSymbol::parse),
+      "suffix" => property!(Suffix, CounterStyleSymbol::parse),
+      "range" => property!(Range, CounterRange::parse),
+      "fallback" => property!(Fallback, parse_counter_style_name),
+      "symbols" => property!(Symbols, parse_symbols),
+      "additive-symbols" => property!(AdditiveSymbols, parse_additive_symbols),
+      "speak-as" => property!(SpeakAs, SpeakAs::parse),
+      "negative" => {
+        if let Ok((before, after)) = input.try_parse(|input| {
+          let before = CounterStyleSymbol::parse(input)?;
+          let after = input.try_parse(CounterStyleSymbol::parse).ok();
+          input.expect_exhausted()?;
+          Ok::<_, ParseError<'i, ParserError<'i>>>((before, after))
+        }) {
+          return Ok(CounterStyleProperty::Negative(before, after))
+        }
+      },
+      "pad" => {
+        if let Ok((length, symbol)) = input.try_parse(|input| {
+          let (length, symbol) = if let Ok(length) = input.try_parse(CSSInteger::parse) {
+            (length, CounterStyleSymbol::parse(input)?)
+          } else {
+            let symbol = CounterStyleSymbol::parse(input)?;
+            (CSSInteger::parse(input)?, symbol)
+          };
+          if length.is_negative() {
+            return Err(input.new_custom_error(ParserError::InvalidValue));
+          }
+          input.expect_exhausted()?;
+          Ok((length, symbol))
+        }) {
+          return Ok(CounterStyleProperty::Pad(length, symbol))
+        }
+      },
+      _ => {}
+    }
+
+    Ok(CounterStyleProperty::Custom(CustomProperty::parse(
+      name.into(),
+      input,
+      &Default::default(),
+    )?))
+  }
+}
+
+/// Default methods reject all at rules.
+impl<'i> AtRuleParser<'i> for CounterStyleDeclarationParser {
+  type Prelude = ();
+  type AtRule = CounterStyleProperty<'i>;
+  type Error = ParserError<'i>;
+}
+
+impl<'i> CounterStyleRule<'i> {
+  pub(crate) fn parse<'t>(
+    name: CustomIdent<'i>,
+    input: &mut Parser<'i, 't>,
+    loc: Location,
+    options: &ParserOptions<'_, 'i>,
+  ) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    let location = input.current_source_location();
+    let mut parser = DeclarationListParser::new(input, CounterStyleDeclarationParser);
+    let mut declarations = vec![];
+    while let Some(decl) = parser.next() {
+      if let Ok(decl) = decl {
+        declarations.push(decl);
+      }
+    }
+
+    let rule = CounterStyleRule {
+      name,
+      declarations,
+      loc,
+    };
+    if !rule.is_valid() {
+      options.warn(location.new_custom_error(ParserError::InvalidCounterStyle(rule.name.0.clone())));
+    }
+
+    Ok(rule)
+  }
+
+  /// Returns whether the rule defines a counter style, i.e. whether the `symbols` and `additive-symbols`
+  /// descriptors are valid for the counter system. Otherwise, the rule is ignored by browsers.
+  pub fn is_valid(&self) -> bool {
+    let mut system = &CounterSystem::Symbolic;
+    let mut symbols = None;
+    let mut additive_symbols = None;
+    for property in &self.declarations {
+      match property {
+        CounterStyleProperty::System(s) => system = s,
+        CounterStyleProperty::Symbols(s) => symbols = Some(s.len()),
+        CounterStyleProperty::AdditiveSymbols(s) => additive_symbols = Some(s.len()),
+        _ => {}
+      }
+    }
+
+    match system {
+      CounterSystem::Cyclic | CounterSystem::Fixed(_) | CounterSystem::Symbolic => symbols.is_some(),
+      CounterSystem::Alphabetic | CounterSystem::Numeric => matches!(symbols, Some(len) if len >= 2),
+      CounterSystem::Additive => additive_symbols.is_some(),
+      CounterSystem::Extends(_) => symbols.is_none() && additive_symbols.is_none(),
+    }
+  }
+
+  pub(crate) fn minify(&mut self) {
+    // Only the last declaration of each descriptor applies.
+    let mut seen = HashSet::new();
+    let mut properties = Vec::with_capacity(self.declarations.len());
+    for property in self.declarations.drain(..).rev() {
+      if seen.insert(property.name().to_ascii_lowercase()) {
+        properties.push(property);
+      }
+    }
+    properties.reverse();
+
+    // Descriptors with their initial value can be removed, unless they override those of an extended counter style.
+    if !properties
+      .iter()
+      .any(|property| matches!(property, CounterStyleProperty::System(CounterSystem::Extends(_))))
+    {
+      properties.retain(|property| !property.is_initial());
+    }
+
+    self.declarations = properties;
+  }
+}
+
+impl<'i> CounterStyleProperty<'i> {
+  fn name(&self) -> &str {
+    match self {
+      CounterStyleProperty::System(_) => "system",
+      CounterStyleProperty::Negative(..) => "negative",
+      CounterStyleProperty::Prefix(_) => "prefix",
+      CounterStyleProperty::Suffix(_) => "suffix",
+      CounterStyleProperty::Range(_) => "range",
+      CounterStyleProperty::Pad(..) => "pad",
+      CounterStyleProperty::Fallback(_) => "fallback",
+      CounterStyleProperty::Symbols(_) => "symbols",
+      CounterStyleProperty::AdditiveSymbols(_) => "additive-symbols",
+      CounterStyleProperty::SpeakAs(_) => "speak-as",
+      CounterStyleProperty::Custom(custom) => custom.name.as_ref(),
+    }
+  }
+
+  fn is_initial(&self) -> bool {
+    match self {
+      CounterStyleProperty::System(system) => *system == CounterSystem::Symbolic,
+      CounterStyleProperty::Negative(CounterStyleSymbol::String(before), None) => before.as_ref() == "-",
+      CounterStyleProperty::Prefix(CounterStyleSymbol::String(prefix)) => prefix.is_empty(),
+      CounterStyleProperty::Suffix(CounterStyleSymbol::String(suffix)) => suffix.as_ref() == ". ",
+      CounterStyleProperty::Range(range) => *range == CounterRange::Auto,
+      CounterStyleProperty::Pad(length, CounterStyleSymbol::String(symbol)) => *length == 0 && symbol.is_empty(),
+      CounterStyleProperty::Fallback(fallback) => {
+        *fallback == CounterStyle::Predefined(PredefinedCounterStyle::Decimal)
+      }
+      CounterStyleProperty::SpeakAs(speak_as) => *speak_as == SpeakAs::Auto,
+      _ => false,
+    }
+  }
+}
+
+fn parse_counter_style_name<'i, 't>(
+  input: &mut Parser<'i, 't>,
+) -> Result<CounterStyle<'i>, ParseError<'i, ParserError<'i>>> {
+  if input.try_parse(|input| input.expect_ident_matching("none")).is_ok() {
+    return Err(input.new_custom_error(ParserError::InvalidValue));
+  }
+
+  match CounterStyle::parse(input)? {
+    CounterStyle::Symbols(..) => Err(input.new_custom_error(ParserError::InvalidValue)),
+    name => Ok(name),
+  }
+}
+
+fn parse_symbols<'i, 't>(
+  input: &mut Parser<'i, 't>,
+) -> Result<Vec<CounterStyleSymbol<'i>>, ParseError<'i, ParserError<'i>>> {
+  let mut symbols = vec![CounterStyleSymbol::parse(input)?];
+  while let Ok(symbol) = input.try_parse(CounterStyleSymbol::parse) {
+    symbols.push(symbol);
+  }
+  Ok(symbols)
+}
+
+fn parse_additive_symbols<'i, 't>(
+  input: &mut Parser<'i, 't>,
+) -> Result<Vec<AdditiveSymbol<'i>>, ParseError<'i, ParserError<'i>>> {
+  let symbols = input.parse_comma_separated(AdditiveSymbol::parse)?;
+  // Weights must be in strictly descending order.
+  if symbols.windows(2).any(|w| w[0].weight <= w[1].weight) {
+    return Err(input.new_custom_error(ParserError::InvalidValue));
+  }
+  Ok(symbols)
+}
+
+impl<'i> Parse<'i> for CounterSystem<'i> {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    let location = input.current_source_location();
+    let ident = input.expect_ident()?;
+    match_ignore_ascii_case! { &*ident,
+      "cyclic" => Ok(CounterSystem::Cyclic),
+      "numeric" => Ok(CounterSystem::Numeric),
+      "alphabetic" => Ok(CounterSystem::Alphabetic),
+      "symbolic" => Ok(CounterSystem::Symbolic),
+      "additive" => Ok(CounterSystem::Additive),
+      "fixed" => Ok(CounterSystem::Fixed(input.try_parse(CSSInteger::parse).unwrap_or(1))),
+      "extends" => Ok(CounterSystem::Extends(parse_counter_style_name(input)?)),
+      _ => Err(location.new_unexpected_token_error(Token::Ident(ident.clone())))
+    }
+  }
+}
+
+impl<'i> ToCss for CounterSystem<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    match self {
+      CounterSystem::Cyclic => dest.write_str("cyclic"),
+      CounterSystem::Numeric => dest.write_str("numeric"),
+      CounterSystem::Alphabetic => dest.write_str("alphabetic"),
+      CounterSystem::Symbolic => dest.write_str("symbolic"),
+      CounterSystem::Additive => dest.write_str("additive"),
+      CounterSystem::Fixed(first) => {
+        dest.write_str("fixed")?;
+        if *first != 1 {
+          dest.write_char(' ')?;
+          first.to_css(dest)?;
+        }
+        Ok(())
+      }
+      CounterSystem::Extends(name) => {
+        dest.write_str("extends ")?;
+        name.to_css(dest)
+      }
+    }
+  }
+}
+
+impl<'i> Parse<'i> for CounterRange {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    if input.try_parse(|input| input.expect_ident_matching("auto")).is_ok() {
+      return Ok(CounterRange::Auto);
+    }
+
+    let ranges = input.parse_comma_separated(|input| {
+      let start = CounterBound::parse(input)?;
+      let end = CounterBound::parse(input)?;
+      // The lower bound must not be greater than the upper bound.
+      if let (CounterBound::Integer(start), CounterBound::Integer(end)) = (start, end) {
+        if start > end {
+          return Err(input.new_custom_error(ParserError::InvalidValue));
+        }
+      }
+      Ok((start, end))
+    })?;
+    Ok(CounterRange::Ranges(ranges))
+  }
+}
+
+impl ToCss for CounterRange {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    match self {
+      CounterRange::Auto => dest.write_str("auto"),
+      CounterRange::Ranges(ranges) => {
+        let mut first = true;
+        for (start, end) in ranges {
+          if first {
+            first = false;
+          } else {
+            dest.delim(',', false)?;
+          }
+          start.to_css(dest)?;
+          dest.write_char(' ')?;
+          end.to_css(dest)?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl<'i> Parse<'i> for CounterBound {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    if input.try_parse(|input| input.expect_ident_matching("infinite")).is_ok() {
+      return Ok(CounterBound::Infinite);
+    }
+
+    Ok(CounterBound::Integer(CSSInteger::parse(input)?))
+  }
+}
+
+impl ToCss for CounterBound {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    match self {
+      CounterBound::Integer(i) => i.to_css(dest),
+      CounterBound::Infinite => dest.write_str("infinite"),
+    }
+  }
+}
+
+impl<'i> Parse<'i> for AdditiveSymbol<'i> {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    let (weight, symbol) = if let Ok(weight) = input.try_parse(CSSInteger::parse) {
+      (weight, CounterStyleSymbol::parse(input)?)
+    } else {
+      let symbol = CounterStyleSymbol::parse(input)?;
+      (CSSInteger::parse(input)?, symbol)
+    };
+
+    if weight.is_negative() {
+      return Err(input.new_custom_error(ParserError::InvalidValue));
+    }
+
+    Ok(AdditiveSymbol { weight, symbol })
+  }
+}
+
+impl<'i> ToCss for AdditiveSymbol<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    self.weight.to_css(dest)?;
+    dest.write_char(' ')?;
+    self.symbol.to_css(dest)
+  }
+}
+
+impl<'i> Parse<'i> for SpeakAs<'i> {
+  fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    if let Ok(speak_as) = input.try_parse(|input| -> Result<_, ParseError<'i, ParserError<'i>>> {
+      let location = input.current_source_location();
+      let ident = input.expect_ident()?;
+      match_ignore_ascii_case! { &*ident,
+        "auto" => Ok(SpeakAs::Auto),
+        "bullets" => Ok(SpeakAs::Bullets),
+        "numbers" => Ok(SpeakAs::Numbers),
+        "words" => Ok(SpeakAs::Words),
+        "spell-out" => Ok(SpeakAs::SpellOut),
+        _ => Err(location.new_unexpected_token_error(Token::Ident(ident.clone())))
+      }
+    }) {
+      return Ok(speak_as);
+    }
+
+    Ok(SpeakAs::CounterStyle(parse_counter_style_name(input)?))
+  }
+}
+
+impl<'i> ToCss for SpeakAs<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    match self {
+      SpeakAs::Auto => dest.write_str("auto"),
+      SpeakAs::Bullets => dest.write_str("bullets"),
+      SpeakAs::Numbers => dest.write_str("numbers"),
+      SpeakAs::Words => dest.write_str("words"),
+      SpeakAs::SpellOut => dest.write_str("spell-out"),
+      SpeakAs::CounterStyle(name) => name.to_css(dest),
+    }
+  }
+}
+
 impl<'i> ToCss for CounterStyleRule<'i> {
   fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
   where
@@ -29,6 +614,229 @@ impl<'i> ToCss for CounterStyleRule<'i> {
     dest.add_mapping(self.loc);
     dest.write_str("@counter-style ")?;
     self.name.to_css(dest)?;
-    self.declarations.to_css_block(dest)
+    dest.whitespace()?;
+    dest.write_char('{')?;
+    dest.indent();
+    let len = self.declarations.len();
+    for (i, prop) in self.declarations.iter().enumerate() {
+      dest.newline()?;
+      prop.to_css(dest)?;
+      if i != len - 1 || !dest.minify {
+        dest.write_char(';')?;
+      }
+    }
+    dest.dedent();
+    dest.newline()?;
+    dest.write_char('}')
+  }
+}
+
+impl<'i> ToCss for CounterStyleProperty<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    macro_rules! property {
+      ($prop: literal, $value: expr) => {{
+        dest.write_str($prop)?;
+        dest.delim(':', false)?;
+        $value.to_css(dest)
+      }};
+    }
+
+    macro_rules! symbols {
+      ($prop: literal, $symbols: expr) => {{
+        dest.write_str($prop)?;
+        dest.delim(':', false)?;
+        let mut first = true;
+        for symbol in $symbols {
+          if first {
+            first = false;
+          } else {
+            dest.write_char(' ')?;
+          }
+          symbol.to_css(dest)?;
+        }
+        Ok(())
+      }};
+    }
+
+    match self {
+      CounterStyleProperty::System(system) => property!("system", system),
+      CounterStyleProperty::Negative(before, after) => symbols!("negative", std::iter::once(before).chain(after)),
+      CounterStyleProperty::Prefix(prefix) => property!("prefix", prefix),
+      CounterStyleProperty::Suffix(suffix) => property!("suffix", suffix),
+      CounterStyleProperty::Range(range) => property!("range", range),
+      CounterStyleProperty::Pad(length, symbol) => {
+        property!("pad", length)?;
+        dest.write_char(' ')?;
+        symbol.to_css(dest)
+      }
+      CounterStyleProperty::Fallback(fallback) => property!("fallback", fallback),
+      CounterStyleProperty::Symbols(symbols) => symbols!("symbols", symbols),
+      CounterStyleProperty::AdditiveSymbols(symbols) => {
+        dest.write_str("additive-symbols")?;
+        dest.delim(':', false)?;
+        let mut first = true;
+        for symbol in symbols {
+          if first {
+            first = false;
+          } else {
+            dest.delim(',', false)?;
+          }
+          symbol.to_css(dest)?;
+        }
+        Ok(())
+      }
+      CounterStyleProperty::SpeakAs(speak_as) => property!("speak-as", speak_as),
+      CounterStyleProperty::Custom(custom) => {
+        dest.write_str(custom.name.as_ref())?;
+        dest.delim(':', false)?;
+        custom.value_to_css(dest)
+      }
+    }
+  }
+}
+
+/// Collects the names of the counter styles referenced by `list-style`, `counter()`, and `counters()`
+/// in the given rules, as well as by the descriptors of `@counter-style` rules. Returns `None` if
+/// a reference may be hidden behind a `var()`.
+pub(crate) fn referenced_counter_styles(rules: &CssRuleList) -> Option<HashSet<String>> {
+  let mut names = HashSet::new();
+  if collect_rule_references(rules, &mut names) {
+    Some(names)
+  } else {
+    None
+  }
+}
+
+fn collect_rule_references(rules: &CssRuleList, names: &mut HashSet<String>) -> bool {
+  for rule in &rules.0 {
+    let complete = match rule {
+      CssRule::Style(style) => {
+        collect_declaration_references(&style.declarations, names) && collect_rule_references(&style.rules, names)
+      }
+      CssRule::Nesting(nesting) => {
+        collect_declaration_references(&nesting.style.declarations, names)
+          && collect_rule_references(&nesting.style.rules, names)
+      }
+      CssRule::Keyframes(keyframes) => keyframes
+        .keyframes
+        .iter()
+        .all(|keyframe| collect_declaration_references(&keyframe.declarations, names)),
+      CssRule::Page(page) => collect_declaration_references(&page.declarations, names),
+      CssRule::Custom(custom) => match &custom.body {
+        Some(CustomAtRuleBody::DeclarationList(declarations)) => {
+          collect_declaration_references(declarations, names)
+        }
+        Some(CustomAtRuleBody::RuleList(rules)) => collect_rule_references(rules, names),
+        _ => true,
+      },
+      CssRule::CounterStyle(counter_style) => {
+        for property in &counter_style.declarations {
+          match property {
+            CounterStyleProperty::System(CounterSystem::Extends(name))
+            | CounterStyleProperty::Fallback(name)
+            | CounterStyleProperty::SpeakAs(SpeakAs::CounterStyle(name)) => collect_name(name, names),
+            _ => {}
+          }
+        }
+        true
+      }
+      CssRule::Media(media) => collect_rule_references(&media.rules, names),
+      CssRule::Supports(supports) => collect_rule_references(&supports.rules, names),
+      CssRule::Container(container) => collect_rule_references(&container.rules, names),
+      CssRule::Scope(scope) => collect_rule_references(&scope.rules, names),
+      CssRule::LayerBlock(layer) => collect_rule_references(&layer.rules, names),
+      CssRule::MozDocument(document) => collect_rule_references(&document.rules, names),
+      _ => true,
+    };
+
+    if !complete {
+      return false;
+    }
+  }
+
+  true
+}
+
+fn collect_declaration_references(declarations: &DeclarationBlock, names: &mut HashSet<String>) -> bool {
+  for (property, _) in declarations.iter() {
+    let complete = match property {
+      Property::ListStyleType(ListStyleType::CounterStyle(name)) => {
+        collect_name(name, names);
+        true
+      }
+      Property::ListStyle(list_style) => {
+        if let ListStyleType::CounterStyle(name) = &list_style.list_style_type {
+          collect_name(name, names);
+        }
+        true
+      }
+      Property::Unparsed(unparsed) => {
+        !matches!(unparsed.property_id, PropertyId::ListStyle | PropertyId::ListStyleType)
+          && collect_token_references(&unparsed.value, names)
+      }
+      Property::Custom(custom) => collect_token_references(&custom.value, names),
+      _ => true,
+    };
+
+    if !complete {
+      return false;
+    }
+  }
+
+  declarations.at_rules.iter().all(|(_, rule)| match &rule.body {
+    Some(CustomAtRuleBody::DeclarationList(declarations)) => collect_declaration_references(declarations, names),
+    Some(CustomAtRuleBody::RuleList(rules)) => collect_rule_references(rules, names),
+    _ => true,
+  })
+}
+
+fn collect_token_references(tokens: &TokenList, names: &mut HashSet<String>) -> bool {
+  // The nesting depth within a counter() or counters() function, or zero if outside of one.
+  let mut depth = 0;
+  for token in &tokens.0 {
+    match token {
+      TokenOrValue::Token(CustomToken::Function(f)) => {
+        if depth > 0 || f.eq_ignore_ascii_case("counter") || f.eq_ignore_ascii_case("counters") {
+          depth += 1;
+        }
+      }
+      TokenOrValue::Token(CustomToken::ParenthesisBlock)
+      | TokenOrValue::Token(CustomToken::SquareBracketBlock)
+      | TokenOrValue::Token(CustomToken::CurlyBracketBlock)
+        if depth > 0 =>
+      {
+        depth += 1
+      }
+      TokenOrValue::Token(CustomToken::CloseParenthesis)
+      | TokenOrValue::Token(CustomToken::CloseSquareBracket)
+      | TokenOrValue::Token(CustomToken::CloseCurlyBracket)
+        if depth > 0 =>
+      {
+        depth -= 1
+      }
+      TokenOrValue::Token(CustomToken::Ident(name)) if depth > 0 => {
+        names.insert(name.to_string());
+      }
+      TokenOrValue::Var(_) if depth > 0 => return false,
+      TokenOrValue::Var(var) => {
+        if let Some(fallback) = &var.fallback {
+          if !collect_token_references(fallback, names) {
+            return false;
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  true
+}
+
+fn collect_name(name: &CounterStyle, names: &mut HashSet<String>) {
+  if let CounterStyle::Name(name) = name {
+    names.insert(name.0.to_string());
   }
 }
@@ -0,0 +1,204 @@
+//! At-rules defined by the `custom_at_rules` parser option.
+
+use super::Location;
+use super::{CssRuleList, MinifyContext};
+use crate::declaration::DeclarationBlock;
+use crate::error::{MinifyError, ParserError, PrinterError};
+use crate::printer::Printer;
+use crate::properties::custom::TokenList;
+use crate::rules::{StyleContext, ToCssWithContext};
+use crate::traits::{Parse, ToCss};
+use crate::values::ident::{CustomIdent, CustomIdentList};
+use crate::values::string::CowArcStr;
+use cssparser::*;
+
+/// The definition of a custom at-rule, used to parse at-rules that are not part of CSS,
+/// e.g. `@tailwind` or `@apply`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CustomAtRuleDefinition {
+  /// The syntax of the prelude, or `None` if the rule has no prelude.
+  pub prelude: Option<CustomAtRulePreludeSyntax>,
+  /// The syntax of the block.
+  pub body: CustomAtRuleBodySyntax,
+}
+
+/// The syntax of the prelude of a custom at-rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CustomAtRulePreludeSyntax {
+  /// A single identifier, e.g. `@tailwind utilities`.
+  Ident,
+  /// A string, e.g. `@config "./tailwind.config.js"`.
+  String,
+  /// A space separated list of identifiers, e.g. `@apply flex items-center`.
+  CustomIdentList,
+}
+
+/// The syntax of the block of a custom at-rule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CustomAtRuleBodySyntax {
+  /// No block. The rule ends with a semicolon.
+  None,
+  /// A list of declarations, which are parsed and minified like those of a style rule.
+  DeclarationList,
+  /// A list of rules, like the block of an `@media` rule.
+  RuleList,
+  /// Any tokens, which are kept as written.
+  Raw,
+}
+
+/// An at-rule defined by the `custom_at_rules` parser option.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CustomAtRule<'i> {
+  /// The name of the at-rule (without the @).
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  pub name: CowArcStr<'i>,
+  /// The prelude of the rule, if any.
+  pub prelude: Option<CustomAtRulePrelude<'i>>,
+  /// The block of the rule, if any.
+  pub body: Option<CustomAtRuleBody<'i>>,
+  /// The location of the rule in the source file.
+  pub loc: Location,
+}
+
+/// The prelude of a [CustomAtRule](CustomAtRule).
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum CustomAtRulePrelude<'i> {
+  /// An identifier.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  Ident(CowArcStr<'i>),
+  /// A string.
+  String(CowArcStr<'i>),
+  /// A list of identifiers.
+  CustomIdentList(CustomIdentList<'i>),
+}
+
+/// The block of a [CustomAtRule](CustomAtRule).
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(tag = "type", content = "value", rename_all = "kebab-case")
+)]
+pub enum CustomAtRuleBody<'i> {
+  /// A list of declarations.
+  #[cfg_attr(feature = "serde", serde(borrow))]
+  DeclarationList(DeclarationBlock<'i>),
+  /// A list of rules.
+  RuleList(CssRuleList<'i>),
+  /// Tokens kept as written.
+  Raw(TokenList<'i>),
+}
+
+impl<'i> CustomAtRulePrelude<'i> {
+  pub(crate) fn parse<'t>(
+    input: &mut Parser<'i, 't>,
+    syntax: Option<CustomAtRulePreludeSyntax>,
+  ) -> Result<Option<Self>, ParseError<'i, ParserError<'i>>> {
+    let prelude = match syntax {
+      None => None,
+      Some(CustomAtRulePreludeSyntax::Ident) => Some(CustomAtRulePrelude::Ident(input.expect_ident()?.into())),
+      Some(CustomAtRulePreludeSyntax::String) => Some(CustomAtRulePrelude::String(input.expect_string()?.into())),
+      Some(CustomAtRulePreludeSyntax::CustomIdentList) => {
+        let mut idents = CustomIdentList::new();
+        idents.push(CustomIdent::parse(input)?);
+        while let Ok(ident) = input.try_parse(CustomIdent::parse) {
+          idents.push(ident);
+        }
+        Some(CustomAtRulePrelude::CustomIdentList(idents))
+      }
+    };
+    input.expect_exhausted()?;
+    Ok(prelude)
+  }
+}
+
+impl<'i> CustomAtRule<'i> {
+  pub(crate) fn minify(
+    &mut self,
+    context: &mut MinifyContext<'_, 'i>,
+    parent_is_unused: bool,
+  ) -> Result<(), MinifyError> {
+    match &mut self.body {
+      Some(CustomAtRuleBody::DeclarationList(declarations)) => {
+        declarations.minify(context.handler, context.important_handler, context.handler_context);
+        for (_, rule) in &mut declarations.at_rules {
+          rule.minify(context, parent_is_unused)?;
+        }
+      }
+      Some(CustomAtRuleBody::RuleList(rules)) => rules.minify(context, parent_is_unused)?,
+      Some(CustomAtRuleBody::Raw(_)) | None => {}
+    }
+    Ok(())
+  }
+}
+
+impl<'i> ToCss for CustomAtRulePrelude<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    // Identifiers are written as is, without CSS module hashing, because they are
+    // interpreted by the tool that defines the rule.
+    match self {
+      CustomAtRulePrelude::Ident(ident) => serialize_identifier(ident, dest)?,
+      CustomAtRulePrelude::String(string) => serialize_string(string, dest)?,
+      CustomAtRulePrelude::CustomIdentList(idents) => {
+        let mut first = true;
+        for ident in idents {
+          if first {
+            first = false;
+          } else {
+            dest.write_char(' ')?;
+          }
+          serialize_identifier(&ident.0, dest)?;
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+impl<'a, 'i> ToCssWithContext<'a, 'i> for CustomAtRule<'i> {
+  fn to_css_with_context<W>(
+    &self,
+    dest: &mut Printer<W>,
+    context: Option<&StyleContext<'a, 'i>>,
+  ) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    dest.add_mapping(self.loc);
+    dest.write_char('@')?;
+    dest.write_str(&self.name)?;
+
+    if let Some(prelude) = &self.prelude {
+      dest.write_char(' ')?;
+      prelude.to_css(dest)?;
+    }
+
+    let body = match &self.body {
+      None => return dest.write_char(';'),
+      Some(CustomAtRuleBody::DeclarationList(declarations)) => return declarations.to_css_block(dest),
+      Some(body) => body,
+    };
+
+    dest.whitespace()?;
+    dest.write_char('{')?;
+    dest.indent();
+    dest.newline()?;
+    match body {
+      CustomAtRuleBody::RuleList(rules) => rules.to_css_with_context(dest, context)?,
+      CustomAtRuleBody::Raw(tokens) => tokens.to_css(dest, false)?,
+      CustomAtRuleBody::DeclarationList(..) => {}
+    }
+    dest.dedent();
+    dest.newline()?;
+    dest.write_char('}')
+  }
+}
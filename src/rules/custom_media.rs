@@ -8,6 +8,9 @@ use crate::traits::ToCss;
 use crate::values::ident::DashedIdent;
 
 /// A [@custom-media](https://drafts.csswg.org/mediaqueries-5/#custom-mq) rule.
+///
+/// When compiled away, the last rule declaring a given name wins. The query may reference
+/// other custom media, which are substituted recursively; circular references are an error.
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CustomMediaRule<'i> {
@@ -1,9 +1,11 @@
 //! The `@font-face` rule.
 
-use super::Location;
+use super::{CssRule, Location};
+use crate::dependencies::Dependency;
 use crate::error::{ParserError, PrinterError};
 use crate::macros::enum_property;
-use crate::printer::Printer;
+use crate::parser::ParserOptions;
+use crate::printer::{Printer, PrinterOptions};
 use crate::properties::custom::CustomProperty;
 use crate::properties::font::{FontFamily, FontStretch, FontStyle, FontWeight};
 use crate::traits::{Parse, ToCss};
@@ -24,6 +26,69 @@ pub struct FontFaceRule<'i> {
   pub loc: Location,
 }
 
+impl<'i> FontFaceRule<'i> {
+  pub(crate) fn parse<'t>(
+    input: &mut Parser<'i, 't>,
+    loc: Location,
+    options: &ParserOptions<'_, 'i>,
+  ) -> Result<Self, ParseError<'i, ParserError<'i>>> {
+    let location = input.current_source_location();
+    let mut parser = DeclarationListParser::new(input, FontFaceDeclarationParser);
+    let mut properties = vec![];
+    while let Some(decl) = parser.next() {
+      if let Ok(decl) = decl {
+        properties.push(decl);
+      }
+    }
+
+    let rule = FontFaceRule { properties, loc };
+    if let Some(family) = rule.font_family().filter(|family| family.is_generic_keyword()) {
+      let name = family.to_css_string(PrinterOptions::default()).unwrap_or_default();
+      options.warn(location.new_custom_error(ParserError::FontFamilyShadowsGeneric(name.into())));
+    }
+
+    Ok(rule)
+  }
+
+  /// Returns the value of the `font-family` descriptor, if any.
+  pub fn font_family(&self) -> Option<&FontFamily<'i>> {
+    self.properties.iter().rev().find_map(|property| match property {
+      FontFaceProperty::FontFamily(family) => Some(family),
+      _ => None,
+    })
+  }
+
+  /// Returns whether two rules declare the same font face, i.e. all of their descriptors are equal,
+  /// and their family names [match](FontFamily::matches).
+  pub fn is_equivalent(&self, other: &FontFaceRule) -> bool {
+    self.properties.len() == other.properties.len()
+      && self.properties.iter().zip(other.properties.iter()).all(|(a, b)| match (a, b) {
+        (FontFaceProperty::FontFamily(a), FontFaceProperty::FontFamily(b)) => a.matches(b),
+        _ => a == b,
+      })
+  }
+}
+
+/// Removes `@font-face` rules that are equivalent to a later rule in the same list. The last
+/// rule is kept, since it takes precedence over earlier rules with the same descriptors.
+pub(crate) fn deduplicate_font_faces(rules: &mut Vec<CssRule>) {
+  let duplicates: Vec<bool> = rules
+    .iter()
+    .enumerate()
+    .map(|(idx, rule)| match rule {
+      CssRule::FontFace(font_face) => rules[idx + 1..]
+        .iter()
+        .any(|rule| matches!(rule, CssRule::FontFace(other) if other.is_equivalent(font_face))),
+      _ => false,
+    })
+    .collect();
+
+  if duplicates.contains(&true) {
+    let mut duplicates = duplicates.into_iter();
+    rules.retain(|_| !duplicates.next().unwrap());
+  }
+}
+
 /// A property within an `@font-face` rule.
 ///
 /// See [FontFaceRule](FontFaceRule).
@@ -141,7 +206,16 @@ impl<'i> ToCss for UrlSource<'i> {
   where
     W: std::fmt::Write,
   {
+    let dep_count = dest.dependencies.as_ref().map_or(0, |d| d.len());
     self.url.to_css(dest)?;
+    if let (Some(format), Some(dependencies)) = (&self.format, &mut dest.dependencies) {
+      if dependencies.len() > dep_count {
+        if let Some(Dependency::Url(dep)) = dependencies.last_mut() {
+          dep.format = Some(format.as_str().to_owned());
+        }
+      }
+    }
+
     if let Some(format) = &self.format {
       dest.whitespace()?;
       dest.write_str("format(")?;
@@ -205,13 +279,11 @@ impl<'i> Parse<'i> for FontFormat<'i> {
   }
 }
 
-impl<'i> ToCss for FontFormat<'i> {
-  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
-  where
-    W: std::fmt::Write,
-  {
+impl<'i> FontFormat<'i> {
+  /// Returns the name of the font format, as written in the `format()` function.
+  pub fn as_str(&self) -> &str {
     use FontFormat::*;
-    let s = match self {
+    match self {
       WOFF => "woff",
       WOFF2 => "woff2",
       TrueType => "truetype",
@@ -219,11 +291,19 @@ impl<'i> ToCss for FontFormat<'i> {
       EmbeddedOpenType => "embedded-opentype",
       Collection => "collection",
       SVG => "svg",
-      String(s) => &s,
-    };
+      String(s) => s,
+    }
+  }
+}
+
+impl<'i> ToCss for FontFormat<'i> {
+  fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
     // Browser support for keywords rather than strings is very limited.
     // https://developer.mozilla.org/en-US/docs/Web/CSS/@font-face/src
-    serialize_string(&s, dest)?;
+    dest.write_string(self.as_str())?;
     Ok(())
   }
 }
@@ -458,7 +538,7 @@ impl<'i> ToCss for FontFaceProperty<'i> {
       Custom(custom) => {
         dest.write_str(custom.name.as_ref())?;
         dest.delim(':', false)?;
-        custom.value.to_css(dest, true)
+        custom.value_to_css(dest)
       }
     }
   }
@@ -309,6 +309,7 @@ impl<'i> FontPaletteValuesRule<'i> {
         FontPaletteValuesProperty::Custom(custom) => FontPaletteValuesProperty::Custom(CustomProperty {
           name: custom.name.clone(),
           value: custom.value.get_fallback(kind),
+          verbatim_value: custom.verbatim_value.clone(),
         }),
         _ => property.clone(),
       })
@@ -370,7 +371,7 @@ impl<'i> ToCss for FontPaletteValuesProperty<'i> {
       FontPaletteValuesProperty::Custom(custom) => {
         dest.write_str(custom.name.as_ref())?;
         dest.delim(':', false)?;
-        custom.value.to_css(dest, true)
+        custom.value_to_css(dest)
       }
     }
   }
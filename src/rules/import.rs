@@ -48,7 +48,7 @@ impl<'i> ToCss for ImportRule<'i> {
         dependencies.push(Dependency::Import(dep))
       }
     } else {
-      serialize_string(&self.url, dest)?;
+      dest.write_string(&self.url)?;
     }
 
     if let Some(layer) = &self.layer {
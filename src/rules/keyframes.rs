@@ -8,7 +8,10 @@ use crate::declaration::DeclarationBlock;
 use crate::error::{ParserError, PrinterError};
 use crate::parser::ParserOptions;
 use crate::printer::Printer;
-use crate::properties::custom::{CustomProperty, UnparsedProperty};
+use crate::properties::animation::AnimationName;
+use crate::properties::custom::{
+  CustomProperty, Token as CustomToken, TokenList, TokenOrValue, UnparsedProperty, Variable,
+};
 use crate::properties::Property;
 use crate::targets::Browsers;
 use crate::traits::{Parse, ToCss};
@@ -18,6 +21,7 @@ use crate::values::percentage::Percentage;
 use crate::values::string::CowArcStr;
 use crate::vendor_prefix::VendorPrefix;
 use cssparser::*;
+use std::collections::{HashMap, HashSet};
 
 /// A [@keyframes](https://drafts.csswg.org/css-animations/#keyframes) rule.
 #[derive(Debug, PartialEq, Clone)]
@@ -82,7 +86,7 @@ impl<'i> ToCss for KeyframesName<'i> {
         // CSS-wide keywords and `none` cannot remove quotes.
         match_ignore_ascii_case! { &*s,
           "none" | "initial" | "inherit" | "unset" | "default" | "revert" | "revert-layer" => {
-            serialize_string(&s, dest)?;
+            dest.write_string(&s)?;
           },
           _ => {
             dest.write_ident(s.as_ref())?;
@@ -167,6 +171,7 @@ impl<'i> KeyframesRule<'i> {
               Property::Custom(custom) => Property::Custom(CustomProperty {
                 name: custom.name.clone(),
                 value: custom.value.get_fallback(kind),
+                verbatim_value: custom.verbatim_value.clone(),
               }),
               Property::Unparsed(unparsed) => Property::Unparsed(UnparsedProperty {
                 property_id: unparsed.property_id.clone(),
@@ -175,6 +180,8 @@ impl<'i> KeyframesRule<'i> {
               _ => property.clone(),
             })
             .collect(),
+          comments: keyframe.declarations.comments.clone(),
+          at_rules: keyframe.declarations.at_rules.clone(),
         },
       })
       .collect();
@@ -206,9 +213,7 @@ impl<'i> ToCss for KeyframesRule<'i> {
           if first_rule {
             first_rule = false;
           } else {
-            if !dest.minify {
-              dest.write_char('\n')?; // no indent
-            }
+            dest.blank_line()?;
             dest.newline()?;
           }
           dest.write_char('@')?;
@@ -222,8 +227,8 @@ impl<'i> ToCss for KeyframesRule<'i> {
           for keyframe in &self.keyframes {
             if first {
               first = false;
-            } else if !dest.minify {
-              dest.write_char('\n')?; // no indent
+            } else {
+              dest.blank_line()?;
             }
             dest.newline()?;
             keyframe.to_css(dest)?;
@@ -239,6 +244,10 @@ impl<'i> ToCss for KeyframesRule<'i> {
     write_prefix!(Moz);
     write_prefix!(O);
     write_prefix!(None);
+
+    if let Some(css_module) = &mut dest.css_module {
+      css_module.add_animation(self.name.as_str(), self.loc.source_index);
+    }
     Ok(())
   }
 }
@@ -368,3 +377,324 @@ impl<'a, 'i> QualifiedRuleParser<'i> for KeyframeListParser {
     })
   }
 }
+
+impl<'i> KeyframesName<'i> {
+  /// Returns the name, whether it was written as an identifier or a string.
+  pub(crate) fn as_str(&self) -> &str {
+    match self {
+      KeyframesName::Ident(ident) => ident.0.as_ref(),
+      KeyframesName::Custom(s) => s.as_ref(),
+    }
+  }
+
+  fn to_animation_name(&self) -> AnimationName<'i> {
+    match self {
+      KeyframesName::Ident(ident) => AnimationName::Ident(ident.clone()),
+      KeyframesName::Custom(s) => AnimationName::String(s.clone()),
+    }
+  }
+}
+
+/// Removes top-level `@keyframes` rules with the same keyframes as an earlier rule, merging their
+/// vendor prefixes, and renames references in `animation` and `animation-name` declarations.
+/// Returns a map from each removed name to the name it was replaced with.
+///
+/// Names that are also defined within conditional rules, defined more than once with different
+/// keyframes, or that appear in unparsed values (e.g. containing `var()`), are left as is.
+pub(crate) fn deduplicate_keyframes<'i>(rules: &mut CssRuleList<'i>) -> HashMap<String, String> {
+  let mut excluded = HashSet::new();
+  for rule in &rules.0 {
+    collect_excluded_names(rule, &mut excluded);
+  }
+
+  let mut first_definitions: HashMap<&str, &Vec<Keyframe<'i>>> = HashMap::new();
+  for rule in &rules.0 {
+    if let CssRule::Keyframes(keyframes) = rule {
+      let name = keyframes.name.as_str();
+      match first_definitions.get(name) {
+        Some(existing) if **existing != keyframes.keyframes => {
+          excluded.insert(name.to_owned());
+        }
+        Some(_) => {}
+        None => {
+          first_definitions.insert(name, &keyframes.keyframes);
+        }
+      }
+    }
+  }
+
+  // Find the earliest identical rule to merge each rule into.
+  let mut kept: Vec<usize> = Vec::new();
+  let mut merges = Vec::new();
+  for (idx, rule) in rules.0.iter().enumerate() {
+    if let CssRule::Keyframes(keyframes) = rule {
+      if excluded.contains(keyframes.name.as_str()) {
+        continue;
+      }
+
+      let existing = kept.iter().find(|i| match &rules.0[**i] {
+        CssRule::Keyframes(k) => k.keyframes == keyframes.keyframes,
+        _ => false,
+      });
+
+      match existing {
+        Some(existing) => merges.push((idx, *existing)),
+        None => kept.push(idx),
+      }
+    }
+  }
+
+  let mut renames = HashMap::new();
+  let mut names = HashMap::new();
+  for (idx, existing) in &merges {
+    let removed = std::mem::replace(&mut rules.0[*idx], CssRule::Ignored);
+    if let (CssRule::Keyframes(removed), CssRule::Keyframes(existing)) = (removed, &mut rules.0[*existing]) {
+      existing.vendor_prefix |= removed.vendor_prefix;
+      if removed.name.as_str() != existing.name.as_str() {
+        renames.insert(removed.name.as_str().to_owned(), existing.name.as_str().to_owned());
+        names.insert(removed.name.as_str().to_owned(), existing.name.to_animation_name());
+      }
+    }
+  }
+
+  rules.0.retain(|rule| !matches!(rule, CssRule::Ignored));
+  if !names.is_empty() {
+    rename_animations(rules, &names);
+  }
+
+  renames
+}
+
+/// Renames top-level `@keyframes` rules to short generated names (`a`, `b`, ...), and updates references
+/// in `animation` and `animation-name` declarations. Returns a map from each original name to its new name.
+///
+/// Names in `preserved`, names that are also defined within conditional rules, and names that appear
+/// in unparsed values (e.g. containing `var()`), are left as is. Generated names never collide with
+/// a name that is kept or referenced without being defined in this style sheet.
+pub(crate) fn shorten_keyframes_names<'i>(
+  rules: &mut CssRuleList<'i>,
+  preserved: &HashSet<String>,
+) -> HashMap<String, String> {
+  let mut excluded = preserved.clone();
+  for rule in &rules.0 {
+    collect_excluded_names(rule, &mut excluded);
+  }
+
+  let mut names = Vec::new();
+  for rule in &rules.0 {
+    if let CssRule::Keyframes(keyframes) = rule {
+      let name = keyframes.name.as_str();
+      if !excluded.contains(name) && !names.iter().any(|n| n == name) {
+        names.push(name.to_owned());
+      }
+    }
+  }
+
+  if names.is_empty() {
+    return HashMap::new();
+  }
+
+  // Names that are kept, or referenced by animations but not defined here, must not be generated.
+  let mut used = excluded;
+  collect_animation_names(rules, &mut used);
+  for name in &names {
+    used.remove(name);
+  }
+
+  let mut renames = HashMap::new();
+  let mut animation_names = HashMap::new();
+  let mut index = 0;
+  for name in names {
+    let short_name = loop {
+      let candidate = generate_name(index);
+      index += 1;
+      if !used.contains(&candidate) && !RESERVED_NAMES.contains(&candidate.as_str()) {
+        break candidate;
+      }
+    };
+
+    if short_name != name {
+      animation_names.insert(
+        name.clone(),
+        AnimationName::Ident(CustomIdent(short_name.clone().into())),
+      );
+      renames.insert(name, short_name);
+    }
+  }
+
+  for rule in &mut rules.0 {
+    if let CssRule::Keyframes(keyframes) = rule {
+      if let Some(short_name) = renames.get(keyframes.name.as_str()) {
+        keyframes.name = KeyframesName::Ident(CustomIdent(short_name.clone().into()));
+      }
+    }
+  }
+
+  rename_animations(rules, &animation_names);
+  renames
+}
+
+/// Keywords that cannot be used as a `@keyframes` name, or would be ambiguous in the `animation` shorthand.
+const RESERVED_NAMES: &[&str] = &[
+  "none",
+  "initial",
+  "inherit",
+  "unset",
+  "default",
+  "revert",
+  "auto",
+  "ease",
+  "linear",
+  "infinite",
+  "normal",
+  "reverse",
+  "alternate",
+  "forwards",
+  "backwards",
+  "both",
+  "running",
+  "paused",
+];
+
+/// Returns the generated name at the given index: `a` to `z`, then `aa`, `ab`, and so on.
+fn generate_name(mut index: usize) -> String {
+  let mut name = Vec::new();
+  loop {
+    name.push(b'a' + (index % 26) as u8);
+    if index < 26 {
+      break;
+    }
+    index = index / 26 - 1;
+  }
+  name.reverse();
+  String::from_utf8(name).unwrap()
+}
+
+fn collect_animation_names(rules: &CssRuleList, names: &mut HashSet<String>) {
+  for rule in &rules.0 {
+    let (declarations, rules) = match rule {
+      CssRule::Style(style) => (Some(&style.declarations), &style.rules),
+      CssRule::Nesting(nesting) => (Some(&nesting.style.declarations), &nesting.style.rules),
+      CssRule::Media(media) => (None, &media.rules),
+      CssRule::Supports(supports) => (None, &supports.rules),
+      CssRule::Container(container) => (None, &container.rules),
+      CssRule::Scope(scope) => (None, &scope.rules),
+      CssRule::LayerBlock(layer) => (None, &layer.rules),
+      CssRule::MozDocument(document) => (None, &document.rules),
+      _ => continue,
+    };
+
+    if let Some(declarations) = declarations {
+      let mut insert = |name: &AnimationName| match name {
+        AnimationName::Ident(ident) => {
+          names.insert(ident.0.to_string());
+        }
+        AnimationName::String(s) => {
+          names.insert(s.to_string());
+        }
+        AnimationName::None => {}
+      };
+
+      for (property, _) in declarations.iter() {
+        match property {
+          Property::AnimationName(animation_names, _) => animation_names.iter().for_each(&mut insert),
+          Property::Animation(animations, _) => animations.iter().for_each(|a| insert(&a.name)),
+          _ => {}
+        }
+      }
+    }
+
+    collect_animation_names(rules, names);
+  }
+}
+
+fn collect_excluded_names(rule: &CssRule, excluded: &mut HashSet<String>) {
+  let rules = match rule {
+    CssRule::Style(style) => {
+      for (property, _) in style.declarations.iter() {
+        match property {
+          Property::Unparsed(UnparsedProperty { value, .. }) | Property::Custom(CustomProperty { value, .. }) => {
+            collect_token_names(value, excluded)
+          }
+          _ => {}
+        }
+      }
+      &style.rules
+    }
+    CssRule::Nesting(nesting) => return collect_excluded_names(&CssRule::Style(nesting.style.clone()), excluded),
+    CssRule::Media(media) => &media.rules,
+    CssRule::Supports(supports) => &supports.rules,
+    CssRule::Container(container) => &container.rules,
+    CssRule::Scope(scope) => &scope.rules,
+    CssRule::LayerBlock(layer) => &layer.rules,
+    CssRule::MozDocument(document) => &document.rules,
+    _ => return,
+  };
+
+  for rule in &rules.0 {
+    if let CssRule::Keyframes(keyframes) = rule {
+      excluded.insert(keyframes.name.as_str().to_owned());
+    } else {
+      collect_excluded_names(rule, excluded);
+    }
+  }
+}
+
+fn collect_token_names(tokens: &TokenList, excluded: &mut HashSet<String>) {
+  for token in &tokens.0 {
+    match token {
+      TokenOrValue::Token(CustomToken::Ident(s)) | TokenOrValue::Token(CustomToken::String(s)) => {
+        excluded.insert(s.to_string());
+      }
+      TokenOrValue::Var(Variable {
+        fallback: Some(fallback),
+        ..
+      }) => collect_token_names(fallback, excluded),
+      _ => {}
+    }
+  }
+}
+
+fn rename_animations<'i>(rules: &mut CssRuleList<'i>, names: &HashMap<String, AnimationName<'i>>) {
+  for rule in &mut rules.0 {
+    let rules = match rule {
+      CssRule::Style(style) => {
+        rename_declarations(&mut style.declarations, names);
+        &mut style.rules
+      }
+      CssRule::Nesting(nesting) => {
+        rename_declarations(&mut nesting.style.declarations, names);
+        &mut nesting.style.rules
+      }
+      CssRule::Media(media) => &mut media.rules,
+      CssRule::Supports(supports) => &mut supports.rules,
+      CssRule::Container(container) => &mut container.rules,
+      CssRule::Scope(scope) => &mut scope.rules,
+      CssRule::LayerBlock(layer) => &mut layer.rules,
+      CssRule::MozDocument(document) => &mut document.rules,
+      _ => continue,
+    };
+    rename_animations(rules, names);
+  }
+}
+
+fn rename_declarations<'i>(declarations: &mut DeclarationBlock<'i>, names: &HashMap<String, AnimationName<'i>>) {
+  let rename = |name: &mut AnimationName<'i>| {
+    let new_name = match name {
+      AnimationName::Ident(ident) => names.get(ident.0.as_ref()),
+      AnimationName::String(s) => names.get(s.as_ref()),
+      AnimationName::None => None,
+    };
+    if let Some(new_name) = new_name {
+      *name = new_name.clone();
+    }
+  };
+
+  for property in declarations.iter_mut() {
+    match property {
+      Property::AnimationName(animation_names, _) => animation_names.iter_mut().for_each(rename),
+      Property::Animation(animations, _) => animations.iter_mut().for_each(|a| rename(&mut a.name)),
+      _ => {}
+    }
+  }
+}
@@ -1,6 +1,6 @@
 //! The `@layer` rule.
 
-use super::{CssRuleList, Location, MinifyContext};
+use super::{CssRule, CssRuleList, Location, MinifyContext};
 use crate::error::{MinifyError, ParserError, PrinterError};
 use crate::printer::Printer;
 use crate::traits::{Parse, ToCss};
@@ -121,7 +121,7 @@ impl<'i> LayerBlockRule<'i> {
   ) -> Result<bool, MinifyError> {
     self.rules.minify(context, parent_is_unused)?;
 
-    Ok(self.rules.0.is_empty())
+    Ok(context.remove_empty_rules && self.rules.0.is_empty())
   }
 }
 
@@ -147,3 +147,55 @@ impl<'i> ToCss for LayerBlockRule<'i> {
     dest.write_char('}')
   }
 }
+
+/// Adds the names of the layers declared by a rule to the list, in order. Returns false if the
+/// layer order cannot be determined statically from this rule onward, because it contains an
+/// anonymous layer or a layer within a conditional rule.
+pub(crate) fn collect_layer_names<'i>(
+  rule: &CssRule<'i>,
+  parent: &[CowArcStr<'i>],
+  names: &mut Vec<LayerName<'i>>,
+) -> bool {
+  let prefixed = |name: &LayerName<'i>| LayerName(parent.iter().chain(name.0.iter()).cloned().collect());
+  match rule {
+    CssRule::LayerStatement(statement) => {
+      for name in &statement.names {
+        add_layer_name(names, prefixed(name));
+      }
+      true
+    }
+    CssRule::LayerBlock(LayerBlockRule {
+      name: Some(name),
+      rules,
+      ..
+    }) => {
+      let name = prefixed(name);
+      add_layer_name(names, name.clone());
+      rules.0.iter().all(|rule| collect_layer_names(rule, &name.0, names))
+    }
+    rule => !contains_layers(rule),
+  }
+}
+
+pub(crate) fn add_layer_name<'i>(names: &mut Vec<LayerName<'i>>, name: LayerName<'i>) {
+  // Declaring a nested layer also declares its parents.
+  if !names.iter().any(|existing| existing.0.starts_with(&name.0)) {
+    names.push(name);
+  }
+}
+
+fn contains_layers(rule: &CssRule) -> bool {
+  let rules = match rule {
+    CssRule::LayerStatement(_) | CssRule::LayerBlock(_) => return true,
+    CssRule::Style(style) => &style.rules,
+    CssRule::Nesting(nesting) => &nesting.style.rules,
+    CssRule::Media(media) => &media.rules,
+    CssRule::Supports(supports) => &supports.rules,
+    CssRule::Container(container) => &container.rules,
+    CssRule::Scope(scope) => &scope.rules,
+    CssRule::MozDocument(document) => &document.rules,
+    _ => return false,
+  };
+
+  rules.0.iter().any(contains_layers)
+}
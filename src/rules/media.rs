@@ -28,12 +28,7 @@ impl<'i> MediaRule<'i> {
     parent_is_unused: bool,
   ) -> Result<bool, MinifyError> {
     self.rules.minify(context, parent_is_unused)?;
-
-    if let Some(custom_media) = &context.custom_media {
-      self.query.transform_custom_media(self.loc, custom_media)?;
-    }
-
-    Ok(self.rules.0.is_empty() || self.query.never_matches())
+    Ok((context.remove_empty_rules && self.rules.0.is_empty()) || self.query.never_matches())
   }
 }
 
@@ -36,8 +36,10 @@
 
 #![deny(missing_docs)]
 
+pub mod comment;
 pub mod container;
 pub mod counter_style;
+pub mod custom_at_rule;
 pub mod custom_media;
 pub mod document;
 pub mod font_face;
@@ -50,6 +52,7 @@ pub mod namespace;
 pub mod nesting;
 pub mod page;
 pub mod property;
+pub mod scope;
 pub mod style;
 pub mod supports;
 pub mod unknown;
@@ -61,29 +64,32 @@ use self::property::PropertyRule;
 use crate::context::PropertyHandlerContext;
 use crate::declaration::DeclarationHandler;
 use crate::dependencies::{Dependency, ImportDependency};
-use crate::error::{MinifyError, ParserError, PrinterError};
+use crate::error::{Error, MinifyError, ParserError, PrinterError};
 use crate::parser::TopLevelRuleParser;
 use crate::prefixes::Feature;
 use crate::printer::Printer;
 use crate::rules::keyframes::KeyframesName;
 use crate::selector::{downlevel_selectors, get_prefix, is_equivalent};
-use crate::stylesheet::ParserOptions;
+use crate::stylesheet::{ParserOptions, UnusedSymbols};
 use crate::targets::Browsers;
 use crate::traits::ToCss;
 use crate::values::string::CowArcStr;
 use crate::vendor_prefix::VendorPrefix;
+use comment::CommentRule;
 use container::ContainerRule;
 use counter_style::CounterStyleRule;
 use cssparser::{parse_one_rule, ParseError, Parser, ParserInput};
+use custom_at_rule::{CustomAtRule, CustomAtRuleBody};
 use custom_media::CustomMediaRule;
 use document::MozDocumentRule;
-use font_face::FontFaceRule;
+use font_face::{deduplicate_font_faces, FontFaceRule};
 use import::ImportRule;
 use keyframes::KeyframesRule;
 use media::MediaRule;
 use namespace::NamespaceRule;
 use nesting::NestingRule;
 use page::PageRule;
+use scope::ScopeRule;
 use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 use style::StyleRule;
@@ -164,10 +170,16 @@ pub enum CssRule<'i> {
   Property(PropertyRule<'i>),
   /// A `@container` rule.
   Container(ContainerRule<'i>),
+  /// A `@scope` rule.
+  Scope(ScopeRule<'i>),
+  /// A comment between rules.
+  Comment(CommentRule<'i>),
   /// A placeholder for a rule that was removed.
   Ignored,
   /// An unknown at-rule.
   Unknown(UnknownAtRule<'i>),
+  /// An at-rule defined by the `custom_at_rules` parser option.
+  Custom(CustomAtRule<'i>),
 }
 
 impl<'a, 'i> ToCssWithContext<'a, 'i> for CssRule<'i> {
@@ -198,7 +210,10 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for CssRule<'i> {
       CssRule::LayerBlock(layer) => layer.to_css(dest),
       CssRule::Property(property) => property.to_css(dest),
       CssRule::Container(container) => container.to_css_with_context(dest, context),
+      CssRule::Scope(scope) => scope.to_css_with_context(dest, context),
+      CssRule::Comment(comment) => comment.to_css(dest),
       CssRule::Unknown(unknown) => unknown.to_css(dest),
+      CssRule::Custom(custom) => custom.to_css_with_context(dest, context),
       CssRule::Ignored => Ok(()),
     }
   }
@@ -223,6 +238,41 @@ impl<'i> CssRule<'i> {
     let mut parser = Parser::new(&mut input);
     Self::parse(&mut parser, &options)
   }
+
+  /// Parse the single rule between the byte offsets `start` and `end` of `code`, e.g. to re-parse
+  /// a rule after it was edited without parsing the rest of the style sheet.
+  ///
+  /// Line numbers are relative to the whole `code`. Columns on the first line of the rule are as well,
+  /// unless the rule is preceded by something other than whitespace on that line, in which case
+  /// they are relative to `start`. Panics if the offsets are out of bounds or not on a character boundary.
+  pub fn parse_range(
+    code: &'i str,
+    start: usize,
+    end: usize,
+    options: ParserOptions<'_, 'i>,
+  ) -> Result<Self, Error<ParserError<'i>>> {
+    let is_newline = |c| matches!(c, '\n' | '\r' | '\x0C');
+    let line_start = code[..start].rfind(is_newline).map_or(0, |i| i + 1);
+    let first = if code[line_start..start].chars().all(char::is_whitespace) {
+      line_start
+    } else {
+      start
+    };
+
+    // Count lines the same way as the tokenizer, where `\r\n` is a single newline.
+    let bytes = code.as_bytes();
+    let line = (0..first)
+      .filter(|&i| match bytes[i] {
+        b'\n' | b'\x0C' => true,
+        b'\r' => bytes.get(i + 1) != Some(&b'\n'),
+        _ => false,
+      })
+      .count();
+
+    let mut input = ParserInput::new_with_line_number_offset(&code[first..end], line as u32);
+    let mut parser = Parser::new(&mut input);
+    Self::parse(&mut parser, &options).map_err(|e| Error::from(e, options.filename.clone()))
+  }
 }
 
 impl<'i> ToCss for CssRule<'i> {
@@ -244,9 +294,15 @@ pub(crate) struct MinifyContext<'a, 'i> {
   pub handler: &'a mut DeclarationHandler<'i>,
   pub important_handler: &'a mut DeclarationHandler<'i>,
   pub handler_context: &'a mut PropertyHandlerContext<'i, 'a>,
-  pub unused_symbols: &'a HashSet<String>,
+  pub unused_symbols: &'a UnusedSymbols,
+  pub referenced_counter_styles: Option<HashSet<String>>,
   pub custom_media: Option<HashMap<CowArcStr<'i>, CustomMediaRule<'i>>>,
   pub css_modules: bool,
+  pub merge_rules: bool,
+  pub merge_non_adjacent_at_rules: bool,
+  pub remove_empty_rules: bool,
+  pub strip_deep_selectors: bool,
+  pub error_on_unsupported: bool,
 }
 
 impl<'i> CssRuleList<'i> {
@@ -255,15 +311,23 @@ impl<'i> CssRuleList<'i> {
     context: &mut MinifyContext<'_, 'i>,
     parent_is_unused: bool,
   ) -> Result<(), MinifyError> {
+    if context.merge_rules {
+      deduplicate_font_faces(&mut self.0);
+    }
+
     let mut keyframe_rules = HashMap::new();
     let mut rules = Vec::new();
     for mut rule in self.0.drain(..) {
+      remove_conditional_keyframes(&rule, &mut keyframe_rules);
+
       match &mut rule {
         CssRule::Keyframes(keyframes) => {
-          if context.unused_symbols.contains(match &keyframes.name {
+          let name = match &keyframes.name {
             KeyframesName::Ident(ident) => ident.0.as_ref(),
             KeyframesName::Custom(string) => string.as_ref(),
-          }) {
+          };
+          if context.unused_symbols.contains(name) {
+            context.handler_context.add_removed_symbol(name);
             continue;
           }
           keyframes.minify(context);
@@ -280,7 +344,7 @@ impl<'i> CssRuleList<'i> {
 
           // If there is an existing rule with the same name and identical keyframes,
           // merge the vendor prefixes from this rule into it.
-          if let Some(existing_idx) = keyframe_rules.get(&keyframes.name) {
+          if let Some(existing_idx) = keyframe_rules.get(&keyframes.name).filter(|_| context.merge_rules) {
             if let Some(CssRule::Keyframes(existing)) = &mut rules.get_mut(*existing_idx) {
               if existing.keyframes == keyframes.keyframes {
                 existing.vendor_prefix |= keyframes.vendor_prefix;
@@ -306,7 +370,19 @@ impl<'i> CssRuleList<'i> {
           }
         }
         CssRule::Media(media) => {
-          if let Some(CssRule::Media(last_rule)) = rules.last_mut() {
+          // Substitute custom media before anything else looks at the query, so that
+          // merging and the passes below only ever see concrete conditions.
+          if let Some(custom_media) = &context.custom_media {
+            media.query.transform_custom_media(media.loc, custom_media)?;
+          }
+
+          // Normalize legacy resolution features first so that queries which differ only
+          // by vendor prefix can be merged below.
+          if let Some(targets) = context.targets {
+            media.query.transform_resolution(*targets);
+          }
+
+          if let Some(CssRule::Media(last_rule)) = rules.last_mut().filter(|_| context.merge_rules) {
             if last_rule.query == media.query {
               last_rule.rules.0.extend(media.rules.0.drain(..));
               last_rule.minify(context, parent_is_unused)?;
@@ -314,26 +390,55 @@ impl<'i> CssRuleList<'i> {
             }
           }
 
+          if let Some(idx) = find_non_adjacent(
+            &rules,
+            &media.rules,
+            context,
+            |rule| matches!(rule, CssRule::Media(m) if m.query == media.query),
+          ) {
+            if let CssRule::Media(existing) = &mut rules[idx] {
+              existing.rules.0.extend(media.rules.0.drain(..));
+              existing.minify(context, parent_is_unused)?;
+              continue;
+            }
+          }
+
           if media.minify(context, parent_is_unused)? {
             continue;
           }
         }
         CssRule::Supports(supports) => {
-          if let Some(CssRule::Supports(last_rule)) = rules.last_mut() {
-            if last_rule.condition == supports.condition {
+          if let Some(CssRule::Supports(last_rule)) = rules.last_mut().filter(|_| context.merge_rules) {
+            // Compare normalized conditions so that equivalent conditions written differently are merged.
+            if last_rule.condition == supports.condition
+              || last_rule.condition.normalize() == supports.condition.normalize()
+            {
               last_rule.rules.0.extend(supports.rules.0.drain(..));
               last_rule.minify(context, parent_is_unused)?;
               continue;
             }
           }
 
+          if let Some(idx) = find_non_adjacent(
+            &rules,
+            &supports.rules,
+            context,
+            |rule| matches!(rule, CssRule::Supports(s) if s.condition.normalize() == supports.condition.normalize()),
+          ) {
+            if let CssRule::Supports(existing) = &mut rules[idx] {
+              existing.rules.0.extend(supports.rules.0.drain(..));
+              existing.minify(context, parent_is_unused)?;
+              continue;
+            }
+          }
+
           supports.minify(context, parent_is_unused)?;
-          if supports.rules.0.is_empty() {
+          if context.remove_empty_rules && supports.rules.0.is_empty() {
             continue;
           }
         }
         CssRule::Container(container) => {
-          if let Some(CssRule::Container(last_rule)) = rules.last_mut() {
+          if let Some(CssRule::Container(last_rule)) = rules.last_mut().filter(|_| context.merge_rules) {
             if last_rule.name == container.name && last_rule.condition == container.condition {
               last_rule.rules.0.extend(container.rules.0.drain(..));
               last_rule.minify(context, parent_is_unused)?;
@@ -341,23 +446,69 @@ impl<'i> CssRuleList<'i> {
             }
           }
 
+          if let Some(idx) = find_non_adjacent(
+            &rules,
+            &container.rules,
+            context,
+            |rule| matches!(rule, CssRule::Container(c) if c.name == container.name && c.condition == container.condition),
+          ) {
+            if let CssRule::Container(existing) = &mut rules[idx] {
+              existing.rules.0.extend(container.rules.0.drain(..));
+              existing.minify(context, parent_is_unused)?;
+              continue;
+            }
+          }
+
           if container.minify(context, parent_is_unused)? {
             continue;
           }
         }
+        CssRule::Scope(scope) => {
+          if let Some(mut lowered) = scope.lower(context) {
+            lowered.minify(context, parent_is_unused)?;
+            rules.extend(lowered.0);
+            continue;
+          }
+
+          if let Some(CssRule::Scope(last_rule)) = rules.last_mut().filter(|_| context.merge_rules) {
+            if last_rule.scope_start == scope.scope_start && last_rule.scope_end == scope.scope_end {
+              last_rule.rules.0.extend(scope.rules.0.drain(..));
+              last_rule.minify(context, parent_is_unused)?;
+              continue;
+            }
+          }
+
+          if scope.minify(context, parent_is_unused)? {
+            continue;
+          }
+        }
         CssRule::LayerBlock(layer) => {
-          if let Some(CssRule::LayerBlock(last_rule)) = rules.last_mut() {
+          if let Some(CssRule::LayerBlock(last_rule)) = rules.last_mut().filter(|_| context.merge_rules) {
             if last_rule.name == layer.name {
               last_rule.rules.0.extend(layer.rules.0.drain(..));
               last_rule.minify(context, parent_is_unused)?;
               continue;
             }
           }
+
+          if let Some(idx) = find_non_adjacent(
+            &rules,
+            &layer.rules,
+            context,
+            |rule| matches!(rule, CssRule::LayerBlock(l) if l.name == layer.name),
+          ) {
+            if let CssRule::LayerBlock(existing) = &mut rules[idx] {
+              existing.rules.0.extend(layer.rules.0.drain(..));
+              existing.minify(context, parent_is_unused)?;
+              continue;
+            }
+          }
           if layer.minify(context, parent_is_unused)? {
             continue;
           }
         }
         CssRule::MozDocument(document) => document.minify(context)?,
+        CssRule::Custom(custom) => custom.minify(context, parent_is_unused)?,
         CssRule::Style(style) => {
           if parent_is_unused || style.minify(context, parent_is_unused)? {
             continue;
@@ -372,7 +523,7 @@ impl<'i> CssRuleList<'i> {
 
           // Attempt to merge the new rule with the last rule we added.
           let mut merged = false;
-          if let Some(CssRule::Style(last_style_rule)) = rules.last_mut() {
+          if let Some(CssRule::Style(last_style_rule)) = rules.last_mut().filter(|_| context.merge_rules) {
             if merge_style_rules(style, last_style_rule, context) {
               // If that was successful, then the last rule has been updated to include the
               // selectors/declarations of the new rule. This might mean that we can merge it
@@ -396,7 +547,7 @@ impl<'i> CssRuleList<'i> {
 
           let supports = context.handler_context.get_supports_rules(&style);
           let logical = context.handler_context.get_logical_rules(&style);
-          if !merged && !style.is_empty() {
+          if !merged && (!style.is_empty() || !context.remove_empty_rules) {
             rules.push(rule);
           }
 
@@ -410,9 +561,17 @@ impl<'i> CssRuleList<'i> {
           continue;
         }
         CssRule::CounterStyle(counter_style) => {
-          if context.unused_symbols.contains(counter_style.name.0.as_ref()) {
+          // Counter styles that are still referenced are kept. If a reference may be
+          // hidden behind a var(), all counter styles are kept.
+          let name = counter_style.name.0.as_ref();
+          if context.unused_symbols.contains(name)
+            && matches!(&context.referenced_counter_styles, Some(referenced) if !referenced.contains(name))
+          {
+            context.handler_context.add_removed_symbol(name);
             continue;
           }
+
+          counter_style.minify();
         }
         CssRule::Nesting(nesting) => {
           if nesting.minify(context, parent_is_unused)? {
@@ -421,6 +580,7 @@ impl<'i> CssRuleList<'i> {
         }
         CssRule::FontPaletteValues(f) => {
           if context.unused_symbols.contains(f.name.0.as_ref()) {
+            context.handler_context.add_removed_symbol(f.name.0.as_ref());
             continue;
           }
 
@@ -435,6 +595,7 @@ impl<'i> CssRuleList<'i> {
         }
         CssRule::Property(property) => {
           if context.unused_symbols.contains(property.name.0.as_ref()) {
+            context.handler_context.add_removed_symbol(property.name.0.as_ref());
             continue;
           }
         }
@@ -447,6 +608,149 @@ impl<'i> CssRuleList<'i> {
     self.0 = rules;
     Ok(())
   }
+
+  /// Adds vendor prefixed copies of the declarations that need them for the given browser targets,
+  /// in all style rules, including nested rules, keyframes, and `@page` rules.
+  pub(crate) fn add_prefixes(&mut self, targets: Browsers) {
+    for rule in &mut self.0 {
+      let rules = match rule {
+        CssRule::Style(style) => {
+          style.declarations.add_prefixes(targets);
+          &mut style.rules
+        }
+        CssRule::Nesting(nesting) => {
+          nesting.style.declarations.add_prefixes(targets);
+          &mut nesting.style.rules
+        }
+        CssRule::Keyframes(keyframes) => {
+          for keyframe in &mut keyframes.keyframes {
+            keyframe.declarations.add_prefixes(targets);
+          }
+          continue;
+        }
+        CssRule::Page(page) => {
+          page.declarations.add_prefixes(targets);
+          continue;
+        }
+        CssRule::Custom(custom) => match &mut custom.body {
+          Some(CustomAtRuleBody::DeclarationList(declarations)) => {
+            declarations.add_prefixes(targets);
+            continue;
+          }
+          Some(CustomAtRuleBody::RuleList(rules)) => rules,
+          _ => continue,
+        },
+        CssRule::Media(media) => &mut media.rules,
+        CssRule::Supports(supports) => &mut supports.rules,
+        CssRule::Container(container) => &mut container.rules,
+        CssRule::Scope(scope) => &mut scope.rules,
+        CssRule::LayerBlock(layer) => &mut layer.rules,
+        CssRule::MozDocument(document) => &mut document.rules,
+        _ => continue,
+      };
+      rules.add_prefixes(targets);
+    }
+  }
+}
+
+/// Returns the index of the closest earlier rule matching the given predicate, if the contents
+/// of a later rule can be moved into it without changing the cascade. This is the case when none
+/// of the rules in between declare any of the same properties as the moved rules.
+fn find_non_adjacent<'i>(
+  rules: &[CssRule<'i>],
+  contents: &CssRuleList<'i>,
+  context: &MinifyContext<'_, 'i>,
+  predicate: impl Fn(&CssRule<'i>) -> bool,
+) -> Option<usize> {
+  if !context.merge_rules || !context.merge_non_adjacent_at_rules {
+    return None;
+  }
+
+  let idx = rules.iter().rposition(predicate)?;
+  let mut moved = HashSet::new();
+  let mut between = HashSet::new();
+  if !collect_property_families(&contents.0, &mut moved)
+    || !collect_property_families(&rules[idx + 1..], &mut between)
+    || moved.contains("all")
+    || between.contains("all")
+    || !moved.is_disjoint(&between)
+  {
+    return None;
+  }
+
+  Some(idx)
+}
+
+/// Collects the families of the properties declared within the given rules. Returns false if
+/// the rules contain anything else whose order may matter, e.g. `@keyframes` or `@font-face`.
+fn collect_property_families<'i>(rules: &[CssRule<'i>], families: &mut HashSet<String>) -> bool {
+  rules.iter().all(|rule| match rule {
+    CssRule::Style(style) => {
+      for (property, _) in style.declarations.iter() {
+        families.insert(property_family(property.property_id().name()).to_owned());
+      }
+      style.declarations.at_rules.is_empty() && collect_property_families(&style.rules.0, families)
+    }
+    CssRule::Media(media) => collect_property_families(&media.rules.0, families),
+    CssRule::Supports(supports) => collect_property_families(&supports.rules.0, families),
+    CssRule::Container(container) => collect_property_families(&container.rules.0, families),
+    CssRule::Scope(scope) => collect_property_families(&scope.rules.0, families),
+    CssRule::LayerBlock(layer) => collect_property_families(&layer.rules.0, families),
+    CssRule::Comment(..) | CssRule::Ignored => true,
+    _ => false,
+  })
+}
+
+/// Groups properties which may override one another, e.g. shorthands and their longhands,
+/// logical and physical properties, and legacy aliases. This is conservative: properties
+/// in the same family do not necessarily interact.
+fn property_family(name: &str) -> &str {
+  if name.starts_with("--") {
+    return name;
+  }
+
+  // Vendor prefixed properties are in the same family as the unprefixed property.
+  let name = match name.strip_prefix('-').and_then(|name| name.split_once('-')) {
+    Some((_, name)) => name,
+    None => name,
+  };
+
+  match name.split('-').next().unwrap_or(name) {
+    "top" | "right" | "bottom" | "left" | "inset" => "inset",
+    "width" | "height" | "inline" | "block" | "min" | "max" => "size",
+    "font" | "line" => "font",
+    "gap" | "row" | "column" | "columns" | "grid" => "grid",
+    "place" | "align" | "justify" => "align",
+    "page" | "break" => "break",
+    "word" | "overflow" => "overflow",
+    family => family,
+  }
+}
+
+/// Keyframes defined within a conditional rule override earlier ones with the same name
+/// when the condition matches, so a later identical rule cannot be merged into an earlier one.
+fn remove_conditional_keyframes<'i>(rule: &CssRule<'i>, keyframe_rules: &mut HashMap<KeyframesName<'i>, usize>) {
+  if keyframe_rules.is_empty() {
+    return;
+  }
+
+  let rules = match rule {
+    CssRule::Media(media) => &media.rules,
+    CssRule::Supports(supports) => &supports.rules,
+    CssRule::Container(container) => &container.rules,
+    CssRule::Scope(scope) => &scope.rules,
+    CssRule::LayerBlock(layer) => &layer.rules,
+    CssRule::MozDocument(document) => &document.rules,
+    _ => return,
+  };
+
+  for rule in &rules.0 {
+    if let CssRule::Keyframes(keyframes) = rule {
+      keyframe_rules.remove(&keyframes.name);
+    } else {
+      remove_conditional_keyframes(rule, keyframe_rules);
+    }
+  }
 }
 
 fn merge_style_rules<'i>(
@@ -460,16 +764,11 @@ fn merge_style_rules<'i>(
     && last_style_rule.is_compatible(*context.targets)
     && style.rules.0.is_empty()
     && last_style_rule.rules.0.is_empty()
+    && style.declarations.at_rules.is_empty()
+    && last_style_rule.declarations.at_rules.is_empty()
     && (!context.css_modules || style.loc.source_index == last_style_rule.loc.source_index)
   {
-    last_style_rule
-      .declarations
-      .declarations
-      .extend(style.declarations.declarations.drain(..));
-    last_style_rule
-      .declarations
-      .important_declarations
-      .extend(style.declarations.important_declarations.drain(..));
+    last_style_rule.declarations.append(&mut style.declarations);
     last_style_rule
       .declarations
       .minify(context.handler, context.important_handler, context.handler_context);
@@ -477,6 +776,7 @@ fn merge_style_rules<'i>(
   } else if style.declarations == last_style_rule.declarations
     && style.rules.0.is_empty()
     && last_style_rule.rules.0.is_empty()
+    && style.declarations.at_rules.is_empty()
   {
     // Append the selectors to the last rule if the declarations are the same, and all selectors are compatible.
     if style.is_compatible(*context.targets) && last_style_rule.is_compatible(*context.targets) {
@@ -524,10 +824,13 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for CssRuleList<'i> {
   {
     let mut first = true;
     let mut last_without_block = false;
+    let mut last_comment = false;
 
     for rule in &self.0 {
-      if let CssRule::Ignored = &rule {
-        continue;
+      match rule {
+        CssRule::Ignored => continue,
+        CssRule::Comment(comment) if !comment.is_included(dest.comments) => continue,
+        _ => {}
       }
 
       // Skip @import rules if collecting dependencies.
@@ -549,22 +852,29 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for CssRuleList<'i> {
       if first {
         first = false;
       } else {
-        if !dest.minify
+        if !last_comment
           && !(last_without_block
             && matches!(
               rule,
-              CssRule::Import(..) | CssRule::Namespace(..) | CssRule::LayerStatement(..)
+              CssRule::Import(..)
+                | CssRule::Namespace(..)
+                | CssRule::LayerStatement(..)
+                | CssRule::Custom(CustomAtRule { body: None, .. })
             ))
         {
-          dest.write_char('\n')?;
+          dest.blank_line()?;
         }
         dest.newline()?;
       }
       rule.to_css_with_context(dest, context)?;
       last_without_block = matches!(
         rule,
-        CssRule::Import(..) | CssRule::Namespace(..) | CssRule::LayerStatement(..)
+        CssRule::Import(..)
+          | CssRule::Namespace(..)
+          | CssRule::LayerStatement(..)
+          | CssRule::Custom(CustomAtRule { body: None, .. })
       );
+      last_comment = matches!(rule, CssRule::Comment(..));
     }
 
     Ok(())
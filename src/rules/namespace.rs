@@ -33,7 +33,7 @@ impl<'i> ToCss for NamespaceRule<'i> {
       dest.write_char(' ')?;
     }
 
-    serialize_string(&self.url, dest)?;
+    dest.write_string(&self.url)?;
     dest.write_char(';')
   }
 }
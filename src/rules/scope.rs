@@ -0,0 +1,136 @@
+//! The `@scope` rule.
+
+use super::Location;
+use super::{CssRule, CssRuleList, MinifyContext};
+use crate::compat::Feature;
+use crate::error::{MinifyError, PrinterError};
+use crate::printer::Printer;
+use crate::rules::{StyleContext, ToCssWithContext};
+use crate::selector::{scope_selector, Selectors};
+use crate::targets::{should_compile, Features};
+use parcel_selectors::SelectorList;
+
+#[cfg(feature = "serde")]
+use crate::selector::{deserialize_optional_selectors, serialize_optional_selectors};
+
+/// A [@scope](https://drafts.csswg.org/css-cascade-6/#scope-atrule) rule.
+///
+/// When the targets do not support `@scope`, rules with a scope start are compiled to style rules whose
+/// selectors only match within the scope during minification. Rules without a scope start are scoped to the parent of the
+/// style sheet's owner node, which cannot be expressed as a selector, so they are always kept.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScopeRule<'i> {
+  /// The selectors of the scoping roots, e.g. `.card` in `@scope (.card) to (.content)`.
+  #[cfg_attr(
+    feature = "serde",
+    serde(
+      serialize_with = "serialize_optional_selectors",
+      deserialize_with = "deserialize_optional_selectors",
+      borrow
+    )
+  )]
+  pub scope_start: Option<SelectorList<'i, Selectors>>,
+  /// The selectors of the scoping limits, e.g. `.content` in `@scope (.card) to (.content)`.
+  #[cfg_attr(
+    feature = "serde",
+    serde(
+      serialize_with = "serialize_optional_selectors",
+      deserialize_with = "deserialize_optional_selectors"
+    )
+  )]
+  pub scope_end: Option<SelectorList<'i, Selectors>>,
+  /// The rules within the `@scope` rule.
+  pub rules: CssRuleList<'i>,
+  /// The location of the rule in the source file.
+  pub loc: Location,
+}
+
+impl<'i> ScopeRule<'i> {
+  pub(crate) fn minify(
+    &mut self,
+    context: &mut MinifyContext<'_, 'i>,
+    parent_is_unused: bool,
+  ) -> Result<bool, MinifyError> {
+    self.rules.minify(context, parent_is_unused)?;
+    Ok(context.remove_empty_rules && self.rules.0.is_empty())
+  }
+
+  /// Returns the rules with their selectors rewritten to match only within the scope, if the targets
+  /// do not support `@scope` and the rule has a scope start.
+  pub(crate) fn lower(&mut self, context: &mut MinifyContext<'_, 'i>) -> Option<CssRuleList<'i>> {
+    let start = self.scope_start.as_ref()?;
+    if !should_compile(
+      *context.targets,
+      context.handler_context.include,
+      context.handler_context.exclude,
+      Features::SCOPE,
+      Feature::ScopeRule,
+    ) {
+      return None;
+    }
+
+    context.handler_context.features_used |= Features::SCOPE;
+    let mut rules = CssRuleList(std::mem::take(&mut self.rules.0));
+    lower_scope(&mut rules, start, self.scope_end.as_ref());
+    Some(rules)
+  }
+}
+
+/// Rewrites the selectors of style rules so that they only match within the scope.
+fn lower_scope<'i>(
+  rules: &mut CssRuleList<'i>,
+  start: &SelectorList<'i, Selectors>,
+  end: Option<&SelectorList<'i, Selectors>>,
+) {
+  for rule in &mut rules.0 {
+    match rule {
+      CssRule::Style(style) => {
+        for selector in style.selectors.0.iter_mut() {
+          *selector = scope_selector(selector, start, end);
+        }
+      }
+      CssRule::Media(media) => lower_scope(&mut media.rules, start, end),
+      CssRule::Supports(supports) => lower_scope(&mut supports.rules, start, end),
+      CssRule::Container(container) => lower_scope(&mut container.rules, start, end),
+      CssRule::LayerBlock(layer) => lower_scope(&mut layer.rules, start, end),
+      _ => {}
+    }
+  }
+}
+
+impl<'a, 'i> ToCssWithContext<'a, 'i> for ScopeRule<'i> {
+  fn to_css_with_context<W>(
+    &self,
+    dest: &mut Printer<W>,
+    context: Option<&StyleContext<'a, 'i>>,
+  ) -> Result<(), PrinterError>
+  where
+    W: std::fmt::Write,
+  {
+    dest.add_mapping(self.loc);
+    dest.write_str("@scope")?;
+    if let Some(start) = &self.scope_start {
+      dest.whitespace()?;
+      dest.write_char('(')?;
+      start.to_css_with_context(dest, context)?;
+      dest.write_char(')')?;
+    }
+
+    if let Some(end) = &self.scope_end {
+      // A space is required so that `to(` is not parsed as a function.
+      dest.write_str(" to (")?;
+      end.to_css_with_context(dest, context)?;
+      dest.write_char(')')?;
+    }
+
+    dest.whitespace()?;
+    dest.write_char('{')?;
+    dest.indent();
+    dest.newline()?;
+    self.rules.to_css_with_context(dest, context)?;
+    dest.dedent();
+    dest.newline()?;
+    dest.write_char('}')
+  }
+}
@@ -8,11 +8,14 @@ use crate::compat::Feature;
 use crate::context::DeclarationContext;
 use crate::declaration::DeclarationBlock;
 use crate::error::ParserError;
-use crate::error::{MinifyError, PrinterError, PrinterErrorKind};
+use crate::error::{MinifyError, MinifyErrorKind, PrinterError, PrinterErrorKind};
 use crate::printer::Printer;
 use crate::rules::{CssRuleList, StyleContext, ToCssWithContext};
-use crate::selector::{is_compatible, is_unused, Selectors};
-use crate::targets::Browsers;
+use crate::selector::{
+  for_each_unused_symbol, is_compatible, is_unused, strip_deep_selectors, unsupported_feature, write_selectors,
+  Selectors,
+};
+use crate::targets::{incompatible_targets, Browsers, Features};
 use crate::traits::ToCss;
 use crate::vendor_prefix::VendorPrefix;
 use cssparser::*;
@@ -21,6 +24,11 @@ use parcel_selectors::SelectorList;
 #[cfg(feature = "serde")]
 use crate::selector::{deserialize_selectors, serialize_selectors};
 
+pub use crate::selector::{
+  parse_selectors, selector_components, selector_from_components, selectors_to_css_string, specificity,
+  SelectorComponent, SelectorComponentKind,
+};
+
 /// A CSS [style rule](https://drafts.csswg.org/css-syntax/#style-rules).
 #[derive(Debug, PartialEq, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -52,25 +60,52 @@ impl<'i> StyleRule<'i> {
     context: &mut MinifyContext<'_, 'i>,
     parent_is_unused: bool,
   ) -> Result<bool, MinifyError> {
+    if context.strip_deep_selectors {
+      strip_deep_selectors(&mut self.selectors);
+    }
+
     let mut unused = false;
     if !context.unused_symbols.is_empty() {
       if is_unused(&mut self.selectors.0.iter(), &context.unused_symbols, parent_is_unused) {
+        for_each_unused_symbol(&self.selectors.0, &context.unused_symbols, &mut |name| {
+          context.handler_context.add_removed_symbol(name)
+        });
+
         if self.rules.0.is_empty() {
           return Ok(true);
         }
 
         self.declarations.declarations.clear();
         self.declarations.important_declarations.clear();
+        self.declarations.comments.clear();
         unused = true;
       }
     }
 
+    if context.error_on_unsupported && !unused {
+      if let Some(targets) = *context.targets {
+        if let Some((name, feature)) = unsupported_feature(self.selectors.0.iter(), targets) {
+          return Err(MinifyError {
+            kind: MinifyErrorKind::UnsupportedFeature {
+              feature: name.into(),
+              targets: incompatible_targets(targets, feature),
+            },
+            loc: self.loc,
+          });
+        }
+      }
+    }
+
     context.handler_context.context = DeclarationContext::StyleRule;
     self
       .declarations
       .minify(context.handler, context.important_handler, context.handler_context);
     context.handler_context.context = DeclarationContext::None;
 
+    for (_, rule) in &mut self.declarations.at_rules {
+      rule.minify(context, unused)?;
+    }
+
     if !self.rules.0.is_empty() {
       self.rules.minify(context, unused)?;
       if unused && self.rules.0.is_empty() {
@@ -174,9 +209,7 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for StyleRule<'i> {
             if first_rule {
               first_rule = false;
             } else {
-              if !dest.minify {
-                dest.write_char('\n')?; // no indent
-              }
+              dest.blank_line()?;
               dest.newline()?;
             }
             dest.vendor_prefix = VendorPrefix::$prefix;
@@ -207,23 +240,40 @@ impl<'a, 'i> StyleRule<'i> {
     W: std::fmt::Write,
   {
     // If supported, or there are no targets, preserve nesting. Otherwise, write nested rules after parent.
-    let supports_nesting = self.rules.0.is_empty()
-      || dest.targets.is_none()
-      || Feature::CssNesting.is_compatible(dest.targets.unwrap());
+    // Custom at-rules are kept with the declarations, within the rule.
+    let supports_nesting = self.rules.0.is_empty() || !dest.should_compile(Features::NESTING, Feature::CssNesting);
     let len = self.declarations.declarations.len() + self.declarations.important_declarations.len();
-    let has_declarations = supports_nesting || len > 0 || self.rules.0.is_empty();
+    let has_at_rules = !self.declarations.at_rules.is_empty();
+    let has_declarations = supports_nesting || len > 0 || has_at_rules || self.rules.0.is_empty();
 
     if has_declarations {
       dest.add_mapping(self.loc);
-      self.selectors.to_css_with_context(dest, context)?;
+      let mut printed = match &dest.css_module {
+        Some(css_module) if css_module.config.export_selectors => Some(Vec::new()),
+        _ => None,
+      };
+      write_selectors(&self.selectors, dest, context, printed.as_mut())?;
+      if let Some(css_module) = &mut dest.css_module {
+        css_module.add_specificity(&self.selectors, self.loc.source_index);
+        if let Some(printed) = printed {
+          css_module.add_selectors(&self.selectors, printed, self.loc.source_index);
+        }
+      }
       dest.whitespace()?;
       dest.write_char('{')?;
       dest.indent();
 
       let mut i = 0;
+      let mut index = 0;
       macro_rules! write {
         ($decls: ident, $important: literal) => {
-          for decl in &self.declarations.$decls {
+          for (position, decl) in self.declarations.$decls.iter().enumerate() {
+            self.declarations.write_comments(index..=index, dest)?;
+            if !$important {
+              self.declarations.write_at_rules(position..=position, dest)?;
+            }
+            index += 1;
+
             // The CSS modules `composes` property is handled specially, and omitted during printing.
             // We need to add the classes it references to the list for the selectors in this rule.
             if let crate::properties::Property::Composes(composes) = &decl {
@@ -241,7 +291,12 @@ impl<'a, 'i> StyleRule<'i> {
 
             dest.newline()?;
             decl.to_css(dest, $important)?;
-            if i != len - 1 || !dest.minify {
+            // The last semicolon can only be omitted if no nested rules or custom at-rules follow.
+            if i != len - 1
+              || !dest.minify
+              || (supports_nesting && !self.rules.0.is_empty())
+              || self.declarations.has_trailing_at_rules()
+            {
               dest.write_char(';')?;
             }
 
@@ -251,14 +306,16 @@ impl<'a, 'i> StyleRule<'i> {
       }
 
       write!(declarations, false);
+      self.declarations.write_at_rules(self.declarations.declarations.len().., dest)?;
       write!(important_declarations, true);
+      self.declarations.write_comments(index.., dest)?;
     }
 
     macro_rules! newline {
       () => {
-        if !dest.minify && (supports_nesting || len > 0) && !self.rules.0.is_empty() {
-          if len > 0 {
-            dest.write_char('\n')?;
+        if !dest.minify && (supports_nesting || len > 0 || has_at_rules) && !self.rules.0.is_empty() {
+          if len > 0 || has_at_rules {
+            dest.blank_line()?;
           }
           dest.newline()?;
         }
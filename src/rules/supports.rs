@@ -2,9 +2,11 @@
 
 use super::Location;
 use super::{CssRuleList, MinifyContext};
+use crate::compat::Feature;
 use crate::error::{MinifyError, ParserError, PrinterError};
 use crate::printer::Printer;
 use crate::rules::{StyleContext, ToCssWithContext};
+use crate::targets::Browsers;
 use crate::traits::{Parse, ToCss};
 use crate::values::string::CowArcStr;
 use cssparser::*;
@@ -43,7 +45,11 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for SupportsRule<'i> {
   {
     dest.add_mapping(self.loc);
     dest.write_str("@supports ")?;
-    self.condition.to_css(dest)?;
+    if dest.minify {
+      self.condition.normalize().to_css(dest)?;
+    } else {
+      self.condition.to_css(dest)?;
+    }
     dest.whitespace()?;
     dest.write_char('{')?;
     dest.indent();
@@ -94,6 +100,109 @@ impl<'i> SupportsCondition<'i> {
     }
   }
 
+  /// Returns a canonical form of the condition, with redundant parentheses removed and the
+  /// operands of `and` and `or` expressions flattened, de-duplicated, and sorted. Equivalent
+  /// conditions that differ only in these ways normalize to the same value.
+  pub fn normalize(&self) -> SupportsCondition<'i> {
+    match self {
+      SupportsCondition::Parens(condition) => condition.normalize(),
+      SupportsCondition::Not(condition) => SupportsCondition::Not(Box::new(condition.normalize().into_parens())),
+      SupportsCondition::And(conditions) => Self::normalize_list(conditions, true),
+      SupportsCondition::Or(conditions) => Self::normalize_list(conditions, false),
+      _ => self.clone(),
+    }
+  }
+
+  fn normalize_list(conditions: &Vec<SupportsCondition<'i>>, is_and: bool) -> SupportsCondition<'i> {
+    let mut operands = Vec::with_capacity(conditions.len());
+    for condition in conditions {
+      match condition.normalize() {
+        SupportsCondition::And(nested) if is_and => operands.extend(nested),
+        SupportsCondition::Or(nested) if !is_and => operands.extend(nested),
+        condition => operands.push(condition.into_parens()),
+      }
+    }
+
+    // Sort by the serialized form, which is unique for normalized conditions.
+    let mut keyed: Vec<(String, SupportsCondition<'i>)> = operands
+      .into_iter()
+      .map(|condition| {
+        (
+          condition.to_css_string(Default::default()).unwrap_or_default(),
+          condition,
+        )
+      })
+      .collect();
+    keyed.sort_by(|a, b| a.0.cmp(&b.0));
+    keyed.dedup_by(|a, b| a.0 == b.0);
+
+    let mut operands: Vec<_> = keyed.into_iter().map(|(_, condition)| condition).collect();
+    if operands.len() == 1 {
+      return match operands.remove(0) {
+        SupportsCondition::Parens(condition) => *condition,
+        condition => condition,
+      };
+    }
+
+    if is_and {
+      SupportsCondition::And(operands)
+    } else {
+      SupportsCondition::Or(operands)
+    }
+  }
+
+  fn into_parens(self) -> SupportsCondition<'i> {
+    match self {
+      SupportsCondition::And(_) | SupportsCondition::Or(_) | SupportsCondition::Not(_) => {
+        SupportsCondition::Parens(Box::new(self))
+      }
+      _ => self,
+    }
+  }
+
+  /// Evaluates the condition against the given browser targets. Returns `Some(true)` if every
+  /// target supports the condition, `Some(false)` if no version of any target does, and `None`
+  /// if the result depends on the browser or the condition cannot be evaluated statically.
+  pub fn evaluate(&self, targets: Browsers) -> Option<bool> {
+    match self {
+      SupportsCondition::Not(condition) => condition.evaluate(targets).map(|result| !result),
+      SupportsCondition::And(conditions) => {
+        let mut result = Some(true);
+        for condition in conditions {
+          match condition.evaluate(targets) {
+            Some(false) => return Some(false),
+            None => result = None,
+            Some(true) => {}
+          }
+        }
+        result
+      }
+      SupportsCondition::Or(conditions) => {
+        let mut result = Some(false);
+        for condition in conditions {
+          match condition.evaluate(targets) {
+            Some(true) => return Some(true),
+            None => result = None,
+            Some(false) => {}
+          }
+        }
+        result
+      }
+      SupportsCondition::Parens(condition) => condition.evaluate(targets),
+      SupportsCondition::Declaration(declaration) => {
+        let feature = declaration_feature(declaration)?;
+        if feature.is_compatible(targets) {
+          Some(true)
+        } else if is_never_supported(feature, targets) {
+          Some(false)
+        } else {
+          None
+        }
+      }
+      SupportsCondition::Selector(_) | SupportsCondition::Unknown(_) => None,
+    }
+  }
+
   /// Combines the given supports condition into this one with an `or` expression.
   pub fn or(&mut self, b: &SupportsCondition<'i>) {
     if let SupportsCondition::Or(a) = self {
@@ -106,6 +215,53 @@ impl<'i> SupportsCondition<'i> {
   }
 }
 
+/// Returns the compatibility feature that determines whether a `<supports-decl>` is supported,
+/// for the declarations that can be evaluated against browser targets.
+fn declaration_feature(declaration: &str) -> Option<Feature> {
+  let (name, value) = declaration.split_once(':')?;
+  let name = name.trim().to_ascii_lowercase();
+  let value = value.trim().to_ascii_lowercase();
+  match name.as_str() {
+    "display" if value == "grid" || value == "inline-grid" => Some(Feature::CssGrid),
+    "place-items" => Some(Feature::PlaceItems),
+    "place-content" => Some(Feature::PlaceContent),
+    "place-self" => Some(Feature::PlaceSelf),
+    _ if name.ends_with("color") => {
+      if value.starts_with("lab(") || value.starts_with("lch(") {
+        Some(Feature::LabColors)
+      } else if value.starts_with("oklab(") || value.starts_with("oklch(") {
+        Some(Feature::OklabColors)
+      } else if value.starts_with("color(") {
+        Some(Feature::ColorFunction)
+      } else {
+        None
+      }
+    }
+    _ => None,
+  }
+}
+
+/// Returns whether no version of any of the given browsers supports the feature.
+fn is_never_supported(feature: Feature, targets: Browsers) -> bool {
+  macro_rules! check {
+    ($($browser: ident),*) => {
+      $(
+        if targets.$browser.is_some()
+          && feature.is_compatible(Browsers {
+            $browser: Some(u32::MAX),
+            ..Browsers::default()
+          })
+        {
+          return false;
+        }
+      )*
+    };
+  }
+
+  check!(android, chrome, edge, firefox, ie, ios_saf, opera, safari, samsung);
+  true
+}
+
 impl<'i> Parse<'i> for SupportsCondition<'i> {
   fn parse<'t>(input: &mut Parser<'i, 't>) -> Result<Self, ParseError<'i, ParserError<'i>>> {
     if input.try_parse(|input| input.expect_ident_matching("not")).is_ok() {
@@ -1,9 +1,10 @@
 use crate::compat::Feature;
-use crate::error::{ParserError, PrinterError};
-use crate::printer::Printer;
+use crate::css_modules::CssModule;
+use crate::error::{Error, ParserError, PrinterError, PrinterErrorKind};
+use crate::printer::{serialize_quoted_string, Printer};
 use crate::properties::custom::TokenList;
 use crate::rules::{StyleContext, ToCssWithContext};
-use crate::stylesheet::{ParserOptions, PrinterOptions};
+use crate::stylesheet::{ParserOptions, PrinterOptions, UnusedSymbols};
 use crate::targets::Browsers;
 use crate::traits::{Parse, ToCss};
 use crate::vendor_prefix::VendorPrefix;
@@ -12,11 +13,10 @@ use cssparser::*;
 use parcel_selectors::parser::SelectorParseErrorKind;
 use parcel_selectors::{
   attr::{AttrSelectorOperator, ParsedAttrSelectorOperation, ParsedCaseSensitivity},
-  parser::{Combinator, Component, Selector, SelectorImpl},
+  parser::{Combinator, Component, NestingRequirement, Selector, SelectorImpl},
   SelectorList,
 };
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::fmt;
 use std::fmt::Write;
 
@@ -218,6 +218,7 @@ impl<'a, 'o, 'i> parcel_selectors::parser::Parser<'i> for SelectorParser<'a, 'o,
       "dir" => Dir(Direction::parse(parser)?),
       "local" if self.options.css_modules.is_some() => Local(Box::new(parcel_selectors::parser::Selector::parse(self, parser)?)),
       "global" if self.options.css_modules.is_some() => Global(Box::new(parcel_selectors::parser::Selector::parse(self, parser)?)),
+      "deep" if self.options.deep_selectors => Deep(Box::new(parcel_selectors::parser::Selector::parse(self, parser)?)),
       _ => {
         if !name.starts_with('-') {
           self.options.warn(parser.new_custom_error(SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name.clone())));
@@ -271,6 +272,9 @@ impl<'a, 'o, 'i> parcel_selectors::parser::Parser<'i> for SelectorParser<'a, 'o,
       "-webkit-scrollbar-corner" => WebKitScrollbar(WebKitScrollbarPseudoElement::Corner),
       "-webkit-resizer" => WebKitScrollbar(WebKitScrollbarPseudoElement::Resizer),
 
+      "v-deep" if self.options.deep_selectors => VDeep,
+      "ng-deep" if self.options.deep_selectors => NgDeep,
+
       _ => {
         if !name.starts_with('-') {
           self.options.warn(loc.new_custom_error(SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name.clone())));
@@ -291,6 +295,7 @@ impl<'a, 'o, 'i> parcel_selectors::parser::Parser<'i> for SelectorParser<'a, 'o,
     let pseudo_element = match_ignore_ascii_case! { &name,
       "cue" => CueFunction(Box::new(Selector::parse(self, arguments)?)),
       "cue-region" => CueRegionFunction(Box::new(Selector::parse(self, arguments)?)),
+      "v-deep" if self.options.deep_selectors => VDeepFunction(Box::new(Selector::parse(self, arguments)?)),
       _ => {
         if !name.starts_with('-') {
           self.options.warn(arguments.new_custom_error(SelectorParseErrorKind::UnsupportedPseudoClassOrElement(name.clone())));
@@ -322,6 +327,11 @@ impl<'a, 'o, 'i> parcel_selectors::parser::Parser<'i> for SelectorParser<'a, 'o,
     true
   }
 
+  #[inline]
+  fn parse_deep_combinators(&self) -> bool {
+    self.options.deep_selectors
+  }
+
   fn default_namespace(&self) -> Option<SelectorIdent<'i>> {
     self.default_namespace.clone().map(SelectorIdent)
   }
@@ -412,6 +422,9 @@ pub enum PseudoClass<'i> {
   Local(Box<parcel_selectors::parser::Selector<'i, Selectors>>),
   Global(Box<parcel_selectors::parser::Selector<'i, Selectors>>),
 
+  // Non-standard deep selectors, e.g. Vue
+  Deep(Box<parcel_selectors::parser::Selector<'i, Selectors>>),
+
   // https://webkit.org/blog/363/styling-scrollbars/
   WebKitScrollbar(WebKitScrollbarPseudoClass),
 
@@ -620,6 +633,11 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for PseudoClass<'i> {
         dest.css_module = css_module;
         Ok(())
       }
+      Deep(selector) => {
+        dest.write_str(":deep(")?;
+        serialize_deep_selector(selector, dest, context)?;
+        dest.write_char(')')
+      }
 
       // https://webkit.org/blog/363/styling-scrollbars/
       WebKitScrollbar(s) => {
@@ -710,6 +728,9 @@ pub enum PseudoElement<'i> {
   CueRegion,
   CueFunction(Box<Selector<'i, Selectors>>),
   CueRegionFunction(Box<Selector<'i, Selectors>>),
+  VDeep,
+  NgDeep,
+  VDeepFunction(Box<Selector<'i, Selectors>>),
   Custom(CowArcStr<'i>),
   CustomFunction(CowArcStr<'i>, TokenList<'i>),
 }
@@ -822,6 +843,13 @@ impl<'i> ToCss for PseudoElement<'i> {
           Resizer => "::-webkit-resizer",
         })
       }
+      VDeep => dest.write_str("::v-deep"),
+      NgDeep => dest.write_str("::ng-deep"),
+      VDeepFunction(selector) => {
+        dest.write_str("::v-deep(")?;
+        serialize_deep_selector(selector, dest, None)?;
+        dest.write_char(')')
+      }
       Custom(val) => {
         dest.write_str("::")?;
         return dest.write_str(val);
@@ -862,6 +890,13 @@ impl<'i> parcel_selectors::parser::PseudoElement<'i> for PseudoElement<'i> {
   fn is_webkit_scrollbar(&self) -> bool {
     matches!(*self, PseudoElement::WebKitScrollbar(..))
   }
+
+  fn valid_before_combinator(&self) -> bool {
+    matches!(
+      *self,
+      PseudoElement::VDeep | PseudoElement::NgDeep | PseudoElement::VDeepFunction(..)
+    )
+  }
 }
 
 impl<'i> PseudoElement<'i> {
@@ -908,8 +943,40 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for SelectorList<'i, Selectors> {
   where
     W: fmt::Write,
   {
-    serialize_selector_list(self.0.iter(), dest, context, false)
+    write_selectors(self, dest, context, None)
+  }
+}
+
+/// Serializes a selector list, and pushes the printed form of each selector to `printed` if provided.
+pub(crate) fn write_selectors<'a, 'i, W>(
+  selectors: &SelectorList<'i, Selectors>,
+  dest: &mut Printer<W>,
+  context: Option<&StyleContext<'a, 'i>>,
+  mut printed: Option<&mut Vec<String>>,
+) -> Result<(), PrinterError>
+where
+  W: fmt::Write,
+{
+  // Write one selector per line if the list does not fit within the maximum line width.
+  let wrap = selectors.0.len() > 1
+    && dest.exceeds_max_line_width(|dest| serialize_selector_list(selectors.0.iter(), dest, context, false))?;
+  for (idx, selector) in selectors.0.iter().enumerate() {
+    if idx > 0 {
+      if wrap {
+        dest.write_char(',')?;
+        dest.newline()?;
+      } else {
+        dest.delim(',', false)?;
+      }
+    }
+
+    if let Some(printed) = &mut printed {
+      printed.push(dest.capture(|dest| serialize_selector(selector, dest, context, false))?);
+    } else {
+      serialize_selector(selector, dest, context, false)?;
+    }
   }
+  Ok(())
 }
 
 impl ToCss for Combinator {
@@ -922,6 +989,16 @@ impl ToCss for Combinator {
       Combinator::Descendant => dest.write_str(" "),
       Combinator::NextSibling => dest.delim('+', true),
       Combinator::LaterSibling => dest.delim('~', true),
+      Combinator::Deep => {
+        dest.whitespace()?;
+        dest.write_str(">>>")?;
+        dest.whitespace()
+      }
+      Combinator::DeepDescendant => {
+        dest.whitespace()?;
+        dest.write_str("/deep/")?;
+        dest.whitespace()
+      }
       Combinator::PseudoElement | Combinator::Part | Combinator::SlotAssignment => Ok(()),
     }
   }
@@ -964,10 +1041,16 @@ where
   // sequence and then reverse.
 
   let mut combinators = selector.iter_raw_match_order().rev().filter_map(|x| x.as_combinator());
-  let compound_selectors = selector.iter_raw_match_order().as_slice().split(|x| x.is_combinator()).rev();
+  let mut compound_selectors = selector
+    .iter_raw_match_order()
+    .as_slice()
+    .split(|x| x.is_combinator())
+    .rev()
+    .peekable();
 
   let mut combinators_exhausted = false;
-  for mut compound in compound_selectors {
+  let mut deep_css_module = None;
+  while let Some(mut compound) = compound_selectors.next() {
     debug_assert!(!combinators_exhausted);
 
     // Skip implicit :scope in relative selectors (e.g. :has(:scope > foo) -> :has(> foo))
@@ -981,6 +1064,16 @@ where
 
     // https://drafts.csswg.org/cssom/#serializing-selectors
     if compound.is_empty() {
+      // An empty compound may precede a pseudo-element that is followed by another combinator,
+      // e.g. `.a ::v-deep .b`. Consume its combinator so that the rest stay aligned.
+      if let Some(next) = compound_selectors.peek() {
+        if next
+          .iter()
+          .any(|c| matches!(c, Component::PseudoElement(p) if p.valid_before_combinator()))
+        {
+          combinators.next();
+        }
+      }
       continue;
     }
 
@@ -1096,6 +1189,14 @@ where
       None => combinators_exhausted = true,
     };
 
+    // Class names and ids after ::v-deep, ::ng-deep, >>> or /deep/ are not scoped, like within :global().
+    if deep_css_module.is_none()
+      && (compound.iter().any(is_deep_combinator)
+        || matches!(next_combinator, Some(Combinator::Deep | Combinator::DeepDescendant)))
+    {
+      deep_css_module = take_deep_css_module(dest);
+    }
+
     // 4. If this is the last part of the chain of the selector and
     //    there is a pseudo-element, append "::" followed by the name of
     //    the pseudo-element, to s.
@@ -1103,6 +1204,34 @@ where
     // (we handle this above)
   }
 
+  if deep_css_module.is_some() {
+    dest.css_module = deep_css_module;
+  }
+
+  Ok(())
+}
+
+/// Takes the CSS module from the printer, unless the `scope_deep_selectors` option is enabled.
+fn take_deep_css_module<'a, 'b, 'c, W>(dest: &mut Printer<'a, 'b, 'c, W>) -> Option<CssModule<'a, 'b, 'c>> {
+  match &dest.css_module {
+    Some(css_module) if !css_module.config.scope_deep_selectors => dest.css_module.take(),
+    _ => None,
+  }
+}
+
+fn serialize_deep_selector<'a, 'i, W>(
+  selector: &parcel_selectors::parser::Selector<'i, Selectors>,
+  dest: &mut Printer<W>,
+  context: Option<&StyleContext<'a, 'i>>,
+) -> Result<(), PrinterError>
+where
+  W: fmt::Write,
+{
+  let css_module = take_deep_css_module(dest);
+  selector.to_css_with_context(dest, context)?;
+  if css_module.is_some() {
+    dest.css_module = css_module;
+  }
   Ok(())
 }
 
@@ -1130,25 +1259,21 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for Component<'i, Selectors> {
         local_name.to_css(dest)?;
         cssparser::ToCss::to_css(operator, dest)?;
 
-        if dest.minify {
+        if dest.minify && dest.minify_selectors {
           // Serialize as both an identifier and a string and choose the shorter one.
           let mut id = String::new();
           value.write_identifier(&mut id)?;
 
           let mut s = String::new();
-          value.to_css(&mut s)?;
+          serialize_quoted_string(&value.0, dest.quote_for(&value.0), &mut s)?;
 
-          if id.len() > 0 && id.len() < s.len() + 2 {
+          if id.len() > 0 && id.len() < s.len() {
             dest.write_str(&id)?;
           } else {
-            dest.write_char('"')?;
             dest.write_str(&s)?;
-            dest.write_char('"')?;
           }
         } else {
-          dest.write_char('"')?;
-          value.to_css(dest)?;
-          dest.write_char('"')?;
+          dest.write_string(&value.0)?;
         }
 
         match case_sensitivity {
@@ -1184,6 +1309,13 @@ impl<'a, 'i> ToCssWithContext<'a, 'i> for Component<'i, Selectors> {
         dest.write_str(")")
       }
       Has(ref list) => {
+        if let Some(targets) = dest.targets {
+          if !Feature::CssHas.is_compatible(targets) {
+            dest.warn(PrinterErrorKind::UnsupportedFeature {
+              feature: ":has()".into(),
+            });
+          }
+        }
         dest.write_str(":has(")?;
         serialize_selector_list(list.iter(), dest, context, true)?;
         dest.write_str(")")
@@ -1323,6 +1455,14 @@ where
 
 pub fn is_compatible(selectors: &SelectorList<Selectors>, targets: Option<Browsers>) -> bool {
   for selector in &selectors.0 {
+    // Deep combinators are opaque, so rules containing them are never merged.
+    if selector
+      .iter_raw_match_order()
+      .any(|c| matches!(c, Component::Combinator(Combinator::Deep | Combinator::DeepDescendant)))
+    {
+      return false;
+    }
+
     let iter = selector.iter();
     for component in iter {
       let feature = match component {
@@ -1454,6 +1594,8 @@ pub fn is_compatible(selectors: &SelectorList<Selectors>, targets: Option<Browse
             | PseudoClass::UserValid
             | PseudoClass::Defined => return false,
 
+            PseudoClass::Deep(_) => continue,
+
             PseudoClass::Custom(_) | _ => return false,
           }
         }
@@ -1468,6 +1610,8 @@ pub fn is_compatible(selectors: &SelectorList<Selectors>, targets: Option<Browse
           PseudoElement::Backdrop(prefix) if *prefix == VendorPrefix::None => Feature::Dialog,
           PseudoElement::Cue => Feature::Cue,
           PseudoElement::CueFunction(_) => Feature::CueFunction,
+          // Deep selectors are expected to be compiled away by a framework.
+          PseudoElement::VDeep | PseudoElement::NgDeep | PseudoElement::VDeepFunction(_) => continue,
           PseudoElement::Custom(_) | _ => return false,
         },
 
@@ -1491,6 +1635,36 @@ pub fn is_compatible(selectors: &SelectorList<Selectors>, targets: Option<Browse
   true
 }
 
+/// Returns the name and compatibility data of the first feature in a list of selectors that is not
+/// supported by the browser targets, and cannot be compiled, e.g. `:has()`.
+pub(crate) fn unsupported_feature<'a, 'i: 'a, I: Iterator<Item = &'a Selector<'i, Selectors>>>(
+  selectors: I,
+  targets: Browsers,
+) -> Option<(&'static str, Feature)> {
+  for selector in selectors {
+    for component in selector.iter_raw_match_order() {
+      let (name, feature) = match component {
+        Component::Has(_) => (":has()", Feature::CssHas),
+        Component::NonTSPseudoClass(PseudoClass::FocusVisible) => (":focus-visible", Feature::CssFocusVisible),
+        Component::NonTSPseudoClass(PseudoClass::FocusWithin) => (":focus-within", Feature::CssFocusWithin),
+        Component::Is(list) | Component::Where(list) | Component::Negation(list) | Component::Any(_, list) => {
+          match unsupported_feature(list.iter(), targets) {
+            Some(unsupported) => return Some(unsupported),
+            None => continue,
+          }
+        }
+        _ => continue,
+      };
+
+      if !feature.is_compatible(targets) {
+        return Some((name, feature));
+      }
+    }
+  }
+
+  None
+}
+
 /// Returns whether two selector lists are equivalent, i.e. the same minus any vendor prefix differences.
 pub fn is_equivalent<'i>(selectors: &SelectorList<'i, Selectors>, other: &SelectorList<'i, Selectors>) -> bool {
   if selectors.0.len() != other.0.len() {
@@ -1639,7 +1813,7 @@ fn downlevel_dir<'i>(dir: Direction, targets: Browsers) -> Component<'i, Selecto
 /// A selector is considered unused if it contains a class or id component that exists in the set of unused symbols.
 pub fn is_unused(
   selectors: &mut std::slice::Iter<Selector<Selectors>>,
-  unused_symbols: &HashSet<String>,
+  unused_symbols: &UnusedSymbols,
   parent_is_unused: bool,
 ) -> bool {
   if unused_symbols.is_empty() {
@@ -1650,7 +1824,7 @@ pub fn is_unused(
     for component in selector.iter_raw_match_order() {
       match component {
         Component::Class(name) | Component::ID(name) => {
-          if unused_symbols.contains(&name.0.to_string()) {
+          if unused_symbols.contains(name.0.as_ref()) {
             return true;
           }
         }
@@ -1672,6 +1846,371 @@ pub fn is_unused(
   })
 }
 
+/// Calls `f` with each class or id in a selector list that is in the set of unused symbols,
+/// including within pseudo classes such as `:is()` when all of their selectors are unused.
+pub(crate) fn for_each_unused_symbol(
+  selectors: &[Selector<Selectors>],
+  unused_symbols: &UnusedSymbols,
+  f: &mut impl FnMut(&str),
+) {
+  for selector in selectors {
+    for component in selector.iter_raw_match_order() {
+      match component {
+        Component::Class(name) | Component::ID(name) => {
+          if unused_symbols.contains(name.0.as_ref()) {
+            f(name.0.as_ref());
+          }
+        }
+        Component::Is(is) | Component::Where(is) | Component::Any(_, is) => {
+          if is_unused(&mut is.iter(), unused_symbols, false) {
+            for_each_unused_symbol(is, unused_symbols, f)
+          }
+        }
+        _ => {}
+      }
+    }
+  }
+}
+
+/// Returns whether a selector contains a nesting selector (`&`), including within
+/// pseudo classes such as `:is()`.
+pub(crate) fn contains_nesting(selector: &Selector<Selectors>) -> bool {
+  selector.iter_raw_match_order().any(|component| match component {
+    Component::Nesting => true,
+    Component::Negation(selectors)
+    | Component::Is(selectors)
+    | Component::Where(selectors)
+    | Component::Has(selectors)
+    | Component::Any(_, selectors) => selectors.iter().any(contains_nesting),
+    _ => false,
+  })
+}
+
+/// Makes a selector relative to its parent rule by prepending a nesting selector
+/// and the given combinator, e.g. `.foo` becomes `& .foo`.
+pub(crate) fn prepend_nesting<'i>(
+  selector: &Selector<'i, Selectors>,
+  combinator: Combinator,
+) -> Selector<'i, Selectors> {
+  let mut components = vec![Component::Nesting, Component::Combinator(combinator)];
+  let mut combinators = selector.iter_raw_match_order().rev().filter_map(|x| x.as_combinator());
+  for compound in selector.iter_raw_match_order().as_slice().split(|x| x.is_combinator()).rev() {
+    components.extend(compound.iter().cloned());
+    if let Some(combinator) = combinators.next() {
+      components.push(Component::Combinator(combinator));
+    }
+  }
+  Selector::from_vec2(components)
+}
+
+/// Rewrites a selector within an `@scope` rule so that it only matches within the scope, for browsers
+/// that do not support `@scope`. `:scope` is replaced with `:is(<scope-start>)`, or the selector is
+/// prefixed with `:where(<scope-start>)` if `:scope` is not used, since the implicit scope does not add
+/// specificity. Note that `:is()` takes the specificity of the most specific scope start selector rather
+/// than the (0,1,0) of `:scope`, so the two only match for a single class, attribute or pseudo class. Elements within a scoping limit are excluded with `:not()`,
+/// e.g. `@scope (.a) to (.b) { .c {} }` becomes `:where(.a) .c:not(:where(.a) :where(.b), :where(.a) :where(.b) *)`.
+/// This does not account for scope proximity, so it is an approximation when scopes are nested.
+pub(crate) fn scope_selector<'i>(
+  selector: &Selector<'i, Selectors>,
+  start: &SelectorList<'i, Selectors>,
+  end: Option<&SelectorList<'i, Selectors>>,
+) -> Selector<'i, Selectors> {
+  let root = || Component::Where(start.0.to_vec().into_boxed_slice());
+  let scope = || Component::Is(start.0.to_vec().into_boxed_slice());
+  let mut components = Vec::new();
+  let mut combinators = selector.iter_raw_match_order().rev().filter_map(|x| x.as_combinator());
+  let mut has_scope = false;
+  for compound in selector.iter_raw_match_order().as_slice().split(|x| x.is_combinator()).rev() {
+    for component in compound {
+      if matches!(component, Component::Scope) {
+        has_scope = true;
+        components.push(scope());
+      } else {
+        components.push(component.clone());
+      }
+    }
+    if let Some(combinator) = combinators.next() {
+      components.push(Component::Combinator(combinator));
+    }
+  }
+
+  if !has_scope {
+    components.splice(0..0, [root(), Component::Combinator(Combinator::Descendant)]);
+  }
+
+  if let Some(end) = end {
+    let limit = |descendant: bool| {
+      let mut components = vec![
+        root(),
+        Component::Combinator(Combinator::Descendant),
+        Component::Where(end.0.to_vec().into_boxed_slice()),
+      ];
+      if descendant {
+        components.push(Component::Combinator(Combinator::Descendant));
+        components.push(Component::ExplicitUniversalType);
+      }
+      Selector::from_vec2(components)
+    };
+
+    // The negation applies to the subject of the selector, so insert it before a pseudo element.
+    let subject_end = components
+      .iter()
+      .rposition(|c| c.is_combinator() && !matches!(c, Component::Combinator(Combinator::PseudoElement)))
+      .map_or(0, |i| i + 1);
+    let index = components[subject_end..]
+      .iter()
+      .position(|c| matches!(c, Component::Combinator(Combinator::PseudoElement)))
+      .map_or(components.len(), |i| subject_end + i);
+    components.insert(
+      index,
+      Component::Negation(vec![limit(false), limit(true)].into_boxed_slice()),
+    );
+  }
+
+  Selector::from_vec2(components)
+}
+
+/// Removes the non-standard `:deep()`, `::v-deep`, and `::ng-deep` selectors and `>>>` and `/deep/`
+/// combinators, replacing them with a descendant combinator, e.g. `.a :deep(.b)`, `.a::v-deep .b`
+/// and `.a >>> .b` all become `.a .b`.
+pub(crate) fn strip_deep_selectors(selectors: &mut SelectorList<Selectors>) {
+  for selector in selectors.0.iter_mut() {
+    if selector.iter_raw_match_order().any(|c| {
+      is_deep_selector(c) || matches!(c, Component::Combinator(Combinator::Deep | Combinator::DeepDescendant))
+    }) {
+      let mut components = Vec::new();
+      push_without_deep_selectors(selector, &mut components);
+      while matches!(components.last(), Some(Component::Combinator(..))) {
+        components.pop();
+      }
+      if components.is_empty() {
+        components.push(Component::ExplicitUniversalType);
+      }
+      *selector = Selector::from_vec2(components);
+    }
+  }
+}
+
+fn is_deep_combinator(component: &Component<Selectors>) -> bool {
+  matches!(
+    component,
+    Component::PseudoElement(PseudoElement::VDeep | PseudoElement::NgDeep)
+  )
+}
+
+fn is_deep_selector(component: &Component<Selectors>) -> bool {
+  matches!(
+    component,
+    Component::NonTSPseudoClass(PseudoClass::Deep(..))
+      | Component::PseudoElement(PseudoElement::VDeep | PseudoElement::NgDeep | PseudoElement::VDeepFunction(..))
+  )
+}
+
+/// Appends the components of a selector to a list in parse order, replacing deep selectors.
+fn push_without_deep_selectors<'i>(
+  selector: &Selector<'i, Selectors>,
+  components: &mut Vec<Component<'i, Selectors>>,
+) {
+  // Whether the next combinator should replace the descendant combinator added for a deep selector.
+  let mut replace_descendant = false;
+  let mut combinators = selector.iter_raw_match_order().rev().filter_map(|x| x.as_combinator());
+  for compound in selector.iter_raw_match_order().as_slice().split(|x| x.is_combinator()).rev() {
+    for component in compound {
+      if !is_deep_selector(component) {
+        components.push(component.clone());
+        replace_descendant = false;
+        continue;
+      }
+
+      if matches!(
+        components.last(),
+        Some(Component::Combinator(Combinator::PseudoElement))
+      ) {
+        components.pop();
+      }
+      if matches!(components.last(), Some(c) if !c.is_combinator()) {
+        components.push(Component::Combinator(Combinator::Descendant));
+      }
+
+      match component {
+        Component::NonTSPseudoClass(PseudoClass::Deep(selector))
+        | Component::PseudoElement(PseudoElement::VDeepFunction(selector)) => {
+          push_without_deep_selectors(selector, components);
+          replace_descendant = false;
+        }
+        _ => replace_descendant = true,
+      }
+    }
+
+    if let Some(combinator) = combinators.next() {
+      if components.is_empty() {
+        continue;
+      }
+      if replace_descendant && matches!(components.last(), Some(Component::Combinator(Combinator::Descendant))) {
+        components.pop();
+      }
+      replace_descendant = false;
+      let combinator = match combinator {
+        Combinator::Deep | Combinator::DeepDescendant => Combinator::Descendant,
+        combinator => combinator,
+      };
+      components.push(Component::Combinator(combinator));
+    }
+  }
+}
+
+/// Parses a comma separated list of selectors on its own, e.g. for tooling that inspects selectors.
+/// Nesting selectors are allowed, and namespace prefixes are not.
+pub fn parse_selectors<'i>(
+  code: &'i str,
+  options: &ParserOptions<'_, 'i>,
+) -> Result<SelectorList<'i, Selectors>, Error<ParserError<'i>>> {
+  let selector_parser = SelectorParser {
+    default_namespace: &None,
+    namespace_prefixes: &HashMap::new(),
+    is_nesting_allowed: true,
+    options,
+  };
+  let mut input = ParserInput::new(code);
+  let mut parser = Parser::new(&mut input);
+  parser
+    .parse_entirely(|input| {
+      SelectorList::parse(&selector_parser, input, NestingRequirement::None)
+        .map_err(ParseError::<ParserError>::from)
+    })
+    .map_err(|e| Error::from(e, options.filename.clone()))
+}
+
+/// Serializes a list of selectors to a string.
+pub fn selectors_to_css_string(
+  selectors: &SelectorList<Selectors>,
+  options: PrinterOptions,
+) -> Result<String, PrinterError> {
+  let mut dest = String::new();
+  let mut printer = Printer::new(&mut dest, options);
+  selectors.to_css_with_context(&mut printer, None)?;
+  Ok(dest)
+}
+
+/// Returns the [specificity](https://drafts.csswg.org/selectors/#specificity-rules) of a selector,
+/// as the number of ID selectors, class-like selectors, and type-like selectors it contains.
+pub fn specificity(selector: &Selector<Selectors>) -> (u32, u32, u32) {
+  let specificity = selector.specificity();
+  (specificity >> 20, (specificity >> 10) & 0x3ff, specificity & 0x3ff)
+}
+
+/// The kind of a [SelectorComponent].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+  feature = "serde",
+  derive(serde::Serialize, serde::Deserialize),
+  serde(rename_all = "kebab-case")
+)]
+pub enum SelectorComponentKind {
+  /// A combinator. Its value is `descendant`, `child`, `next-sibling`, `later-sibling`, or one of
+  /// the non-standard `deep` (`>>>`) and `deep-descendant` (`/deep/`) combinators.
+  Combinator,
+  /// A namespace prefix, e.g. `svg|`.
+  Namespace,
+  /// The universal selector, `*`.
+  Universal,
+  /// A type selector, e.g. `div`.
+  Type,
+  /// An ID selector, e.g. `#foo`.
+  Id,
+  /// A class selector, e.g. `.foo`.
+  Class,
+  /// An attribute selector, e.g. `[href]`.
+  Attribute,
+  /// A pseudo class, e.g. `:hover` or `:not(.foo)`.
+  PseudoClass,
+  /// A pseudo element, e.g. `::before`.
+  PseudoElement,
+  /// The nesting selector, `&`.
+  Nesting,
+}
+
+/// A simple selector or combinator within a selector, as returned by [selector_components].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SelectorComponent {
+  /// The kind of component.
+  #[cfg_attr(feature = "serde", serde(rename = "type"))]
+  pub kind: SelectorComponentKind,
+  /// The component as printed, e.g. `.foo`, or the name of a combinator.
+  pub value: String,
+}
+
+/// Returns the simple selectors and combinators of a selector, in source order.
+pub fn selector_components(selector: &Selector<Selectors>) -> Result<Vec<SelectorComponent>, PrinterError> {
+  // Compound selectors are stored in reverse order, with their contents in source order.
+  let mut combinators = selector.iter_raw_match_order().rev().filter_map(|x| x.as_combinator());
+  let compound_selectors = selector.iter_raw_match_order().as_slice().split(|x| x.is_combinator()).rev();
+
+  let mut components = Vec::new();
+  for compound in compound_selectors {
+    for component in compound {
+      let kind = match component {
+        Component::DefaultNamespace(_) => continue,
+        Component::ExplicitAnyNamespace | Component::ExplicitNoNamespace | Component::Namespace(..) => {
+          SelectorComponentKind::Namespace
+        }
+        Component::ExplicitUniversalType => SelectorComponentKind::Universal,
+        Component::LocalName(_) => SelectorComponentKind::Type,
+        Component::ID(_) => SelectorComponentKind::Id,
+        Component::Class(_) => SelectorComponentKind::Class,
+        Component::AttributeInNoNamespaceExists { .. }
+        | Component::AttributeInNoNamespace { .. }
+        | Component::AttributeOther(_) => SelectorComponentKind::Attribute,
+        Component::PseudoElement(_) | Component::Slotted(_) | Component::Part(_) => {
+          SelectorComponentKind::PseudoElement
+        }
+        Component::Nesting => SelectorComponentKind::Nesting,
+        _ => SelectorComponentKind::PseudoClass,
+      };
+
+      let mut value = String::new();
+      let mut printer = Printer::new(&mut value, PrinterOptions::default());
+      component.to_css_with_context(&mut printer, None)?;
+      components.push(SelectorComponent { kind, value });
+    }
+
+    let value = match combinators.next() {
+      Some(Combinator::Descendant) => "descendant",
+      Some(Combinator::Child) => "child",
+      Some(Combinator::NextSibling) => "next-sibling",
+      Some(Combinator::LaterSibling) => "later-sibling",
+      Some(Combinator::Deep) => "deep",
+      Some(Combinator::DeepDescendant) => "deep-descendant",
+      // Pseudo element, slot, and part combinators are implied by the following component.
+      Some(Combinator::PseudoElement | Combinator::SlotAssignment | Combinator::Part) | None => continue,
+    };
+    components.push(SelectorComponent {
+      kind: SelectorComponentKind::Combinator,
+      value: value.into(),
+    });
+  }
+
+  Ok(components)
+}
+
+/// Builds the source of a selector from the components returned by [selector_components].
+pub fn selector_from_components(components: &[SelectorComponent]) -> String {
+  let mut dest = String::new();
+  for component in components {
+    match (component.kind, component.value.as_str()) {
+      (SelectorComponentKind::Combinator, "child") => dest.push_str(" > "),
+      (SelectorComponentKind::Combinator, "next-sibling") => dest.push_str(" + "),
+      (SelectorComponentKind::Combinator, "later-sibling") => dest.push_str(" ~ "),
+      (SelectorComponentKind::Combinator, "deep") => dest.push_str(" >>> "),
+      (SelectorComponentKind::Combinator, "deep-descendant") => dest.push_str(" /deep/ "),
+      (SelectorComponentKind::Combinator, _) => dest.push(' '),
+      (_, value) => dest.push_str(value),
+    }
+  }
+  dest
+}
+
 #[cfg(feature = "serde")]
 pub fn serialize_selectors<S>(selectors: &SelectorList<Selectors>, s: S) -> Result<S::Ok, S::Error>
 where
@@ -1698,11 +2237,16 @@ where
 {
   use serde::Deserialize;
 
+  // Selectors are serialized as printed, which may include nesting and deep selectors.
+  let options = ParserOptions {
+    deep_selectors: true,
+    ..ParserOptions::default()
+  };
   let selector_parser = SelectorParser {
     default_namespace: &None,
     namespace_prefixes: &HashMap::new(),
-    is_nesting_allowed: false,
-    options: &ParserOptions::default(),
+    is_nesting_allowed: true,
+    options: &options,
   };
 
   let selectors = Vec::<&'i str>::deserialize(deserializer)?
@@ -1710,8 +2254,45 @@ where
     .map(|selector| {
       let mut input = ParserInput::new(selector);
       let mut parser = Parser::new(&mut input);
-      Selector::parse(&selector_parser, &mut parser).unwrap()
+      Selector::parse(&selector_parser, &mut parser)
+        .map_err(|_| serde::de::Error::custom(format!("invalid selector: {}", selector)))
     })
-    .collect();
+    .collect::<Result<_, _>>()?;
   Ok(SelectorList(selectors))
 }
+
+#[cfg(feature = "serde")]
+pub fn serialize_optional_selectors<S>(
+  selectors: &Option<SelectorList<Selectors>>,
+  s: S,
+) -> Result<S::Ok, S::Error>
+where
+  S: serde::Serializer,
+{
+  match selectors {
+    Some(selectors) => serialize_selectors(selectors, s),
+    None => s.serialize_none(),
+  }
+}
+
+#[cfg(feature = "serde")]
+pub fn deserialize_optional_selectors<'i, 'de: 'i, D>(
+  deserializer: D,
+) -> Result<Option<SelectorList<'i, Selectors>>, D::Error>
+where
+  D: serde::Deserializer<'de>,
+{
+  struct Wrapper<'i>(SelectorList<'i, Selectors>);
+
+  impl<'i, 'de: 'i> serde::Deserialize<'de> for Wrapper<'i> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+      D: serde::Deserializer<'de>,
+    {
+      deserialize_selectors(deserializer).map(Wrapper)
+    }
+  }
+
+  use serde::Deserialize;
+  Ok(Option::<Wrapper<'i>>::deserialize(deserializer)?.map(|wrapper| wrapper.0))
+}
@@ -5,22 +5,35 @@
 
 use crate::compat::Feature;
 use crate::context::{DeclarationContext, PropertyHandlerContext};
-use crate::css_modules::{CssModule, CssModuleExports, CssModuleReferences};
+use crate::css_modules::{
+  composed_unused_symbols, remove_unused_composes, CssModule, CssModuleExports, CssModuleReferences,
+};
 use crate::declaration::{DeclarationBlock, DeclarationHandler};
 use crate::dependencies::Dependency;
 use crate::error::{Error, ErrorLocation, MinifyErrorKind, ParserError, PrinterError, PrinterErrorKind};
-use crate::parser::TopLevelRuleParser;
+use crate::parser::{parse_comments, TopLevelRuleParser};
 use crate::printer::Printer;
-use crate::rules::{CssRule, CssRuleList, MinifyContext};
-use crate::targets::Browsers;
+use crate::properties::custom::{
+  add_custom_property_fallbacks, inline_custom_properties, remove_unused_custom_properties,
+};
+use crate::rules::comment::{is_license_comment, CommentRule};
+use crate::rules::counter_style::referenced_counter_styles;
+use crate::rules::keyframes::{deduplicate_keyframes, shorten_keyframes_names};
+use crate::rules::layer::{collect_layer_names, LayerBlockRule, LayerName, LayerStatementRule};
+use crate::rules::{CssRule, CssRuleList, Location, MinifyContext};
+use crate::targets::{should_compile, Browsers, Features};
 use crate::traits::ToCss;
-use cssparser::{Parser, ParserInput, RuleListParser};
+use crate::values::string::CowArcStr;
+use cssparser::{Parser, ParserInput, RuleListParser, Token};
 use parcel_sourcemap::SourceMap;
 use std::collections::{HashMap, HashSet};
 
-pub use crate::parser::ParserOptions;
+pub use crate::parser::{Comments, NestingSpec, ParserOptions};
+pub use crate::printer::ColorFormat;
+pub use crate::printer::Precision;
 pub use crate::printer::PrinterOptions;
 pub use crate::printer::PseudoClasses;
+pub use crate::printer::QuoteStyle;
 
 /// A CSS style sheet, representing a `.css` file or inline `<style>` element.
 ///
@@ -56,7 +69,7 @@ pub use crate::printer::PseudoClasses;
 /// let res = stylesheet.to_css(PrinterOptions::default()).unwrap();
 /// assert_eq!(res.code, ".foo, .bar {\n  color: red;\n}\n");
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StyleSheet<'i, 'o> {
   /// A list of top-level rules within the style sheet.
@@ -66,38 +79,299 @@ pub struct StyleSheet<'i, 'o> {
   /// Sources are referenced by index in the `loc` property of each rule.
   pub sources: Vec<String>,
   /// The source map URL extracted from the original style sheet.
+  #[cfg_attr(feature = "serde", serde(default))]
   pub(crate) source_map_urls: Vec<Option<String>>,
+  /// License comments (`/*! ... */`) before the first rule, without the `/*` and `*/`.
+  /// These are printed at the top of the output unless removed during minification.
+  #[cfg_attr(feature = "serde", serde(borrow, default))]
+  pub license_comments: Vec<CowArcStr<'i>>,
+  /// Keyframes names that were replaced during minification by `deduplicate_keyframes` or `shorten_keyframes_names`.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  renamed_keyframes: HashMap<String, String>,
+  /// Custom properties that were inlined during minification by `inline_custom_properties`.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  inlined_custom_properties: Vec<String>,
+  /// Entries of `unused_symbols` that caused a rule or declaration to be removed during minification.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  removed_symbols: Vec<String>,
+  /// Entries of `unused_symbols` that did not cause anything to be removed during minification.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  unmatched_symbols: Vec<String>,
+  /// Features that were compiled during minification.
+  #[cfg_attr(feature = "serde", serde(skip))]
+  features_used: Features,
   #[cfg_attr(feature = "serde", serde(skip))]
   /// The options the style sheet was originally parsed with.
-  options: ParserOptions<'o, 'i>,
+  pub(crate) options: ParserOptions<'o, 'i>,
 }
 
 /// Options for the `minify` function of a [StyleSheet](StyleSheet)
 /// or [StyleAttribute](StyleAttribute).
-#[derive(Default)]
 pub struct MinifyOptions {
   /// Browser targets to compile the CSS for.
   pub targets: Option<Browsers>,
-  /// A list of known unused symbols, including CSS class names,
-  /// ids, and `@keyframe` names. The declarations of these will be removed.
+  /// A list of known unused symbols, including CSS class names, ids, custom property names (e.g. `--color`),
+  /// `@keyframe`, and `@counter-style` names. The declarations of these will be removed.
+  /// Counter styles that are referenced elsewhere in the style sheet, or that may be referenced
+  /// via `var()`, are kept.
   pub unused_symbols: HashSet<String>,
+  /// A list of glob-style patterns matching known unused symbols, in which `*` matches any sequence
+  /// of characters, e.g. `legacy-*`. Matching symbols are removed as if they were listed in `unused_symbols`.
+  pub unused_symbol_patterns: Vec<String>,
+  /// Whether to remove license comments (`/*! ... */`). Other comments are always removed.
+  pub remove_comments: bool,
+  /// Whether to merge adjacent rules with the same selectors, declarations, or conditions.
+  pub merge_rules: bool,
+  /// Whether to combine longhand declarations into shorthands, and remove overridden declarations.
+  /// When disabled, declarations are only transformed individually, e.g. for the browser targets.
+  pub merge_longhands: bool,
+  /// Whether to remove rules with no declarations or nested rules.
+  pub remove_empty_rules: bool,
+  /// Whether to remove declarations that are overridden by a later declaration of the same property,
+  /// or a shorthand that includes it, within a rule, e.g. after merging adjacent rules with the same selectors.
+  /// Disabled by default, because it also removes fallbacks for older browsers, such as `display: block; display: grid`.
+  pub deduplicate_declarations: bool,
+  /// Whether to sort the declarations within each rule alphabetically, which improves compression.
+  /// Declarations are only reordered when their relative order does not matter, so shorthands, longhands
+  /// and logical properties of the same property, as well as custom properties and values with `var()`,
+  /// keep their order.
+  pub sort_declarations: bool,
+  /// Whether to also merge `@media`, `@supports`, `@container`, and `@layer` blocks with the same
+  /// condition or name when they are not adjacent. This is only done when the rules in between
+  /// do not declare any of the same properties, so that the cascade order is preserved.
+  pub merge_non_adjacent_at_rules: bool,
+  /// Whether to remove `@keyframes` rules that are identical to an earlier rule with a different name,
+  /// and update `animation` and `animation-name` declarations to reference the remaining rule.
+  /// The replaced names are returned in [ToCssResult](ToCssResult). Ignored when CSS modules are enabled.
+  pub deduplicate_keyframes: bool,
+  /// Whether to rename `@keyframes` rules to short generated names (`a`, `b`, ...), and update `animation`
+  /// and `animation-name` declarations to match. The new names are returned in [ToCssResult](ToCssResult).
+  /// Ignored when CSS modules are enabled.
+  pub shorten_keyframes_names: bool,
+  /// A list of `@keyframes` names that are not renamed by `shorten_keyframes_names`, e.g. because
+  /// they are referenced from JavaScript.
+  pub preserved_keyframes_names: HashSet<String>,
+  /// Whether classes in `unused_symbols` are removed even when they are composed by a class that is kept.
+  /// By default, such classes are kept, because they are still referenced by the exports of the composing
+  /// class. When enabled, they are removed along with the `composes` references to them, and a warning is emitted.
+  pub strict_unused_symbols: bool,
+  /// Whether to remove the non-standard `:deep()`, `::v-deep`, and `::ng-deep` selectors parsed with the
+  /// `deep_selectors` parser option, replacing them with a descendant combinator, e.g. `.a :deep(.b)`
+  /// becomes `.a .b`. This is useful for production output, once component styles have been scoped.
+  pub strip_deep_selectors: bool,
+  /// Which custom properties defined on `:root` to inline, replacing their `var()` references with the
+  /// defined value. Only custom properties that are defined once, in a top-level `:root` rule, are inlined.
+  /// This assumes the properties are not changed at runtime, e.g. from JavaScript. The inlined names are
+  /// returned in [ToCssResult](ToCssResult).
+  pub inline_custom_properties: InlineCustomProperties,
+  /// Whether to remove declarations of custom properties that are not referenced by any `var()` in the
+  /// style sheet, directly or through other custom properties that are referenced. Rules that become empty
+  /// are removed along with them. This assumes the properties are not read from JavaScript or other
+  /// style sheets.
+  pub remove_unused_custom_properties: bool,
+  /// Whether to add a fallback before each declaration that references custom properties, when the browser
+  /// targets include browsers that do not support them. In the fallback, each `var()` is replaced by the
+  /// value of the custom property, if it is only defined in top-level `:root` rules. Otherwise, a warning
+  /// is emitted. Declarations that are already preceded by a fallback are left alone. When bundling, this
+  /// applies to the whole bundle, so custom properties defined in other files are resolved.
+  pub custom_property_fallbacks: bool,
+  /// Features to compile regardless of the browser targets.
+  pub include: Features,
+  /// Features to never compile, even if the browser targets do not support them.
+  pub exclude: Features,
+  /// Whether to only add vendor prefixes for the browser targets, e.g. as a replacement for Autoprefixer.
+  /// Prefixed copies of properties that need them, such as `-webkit-user-select`, are added before the
+  /// unprefixed declaration, unless already present. No other transforms are applied, so values, selectors
+  /// and at-rules are left unchanged, and all other options are ignored. The result should be printed
+  /// without browser targets, otherwise the printer may still compile values and selectors.
+  pub prefix_only: bool,
+  /// Whether to return an error when a feature that is not supported by the browser targets, and cannot be
+  /// compiled, is used, rather than leaving it as is. Currently, this applies to the `:has()`, `:focus-visible`,
+  /// and `:focus-within` selectors. The error lists the feature and the targets that do not support it.
+  pub error_on_unsupported: bool,
+}
+
+/// The known unused symbols during minification, from the `unused_symbols` and
+/// `unused_symbol_patterns` fields of [MinifyOptions](MinifyOptions).
+///
+/// Symbols are matched by exact name, or by a glob-style pattern in which `*` matches any
+/// sequence of characters, e.g. `legacy-*`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UnusedSymbols {
+  names: HashSet<String>,
+  patterns: Vec<String>,
+  excluded: HashSet<String>,
+}
+
+impl UnusedSymbols {
+  pub(crate) fn new(names: HashSet<String>, patterns: Vec<String>) -> UnusedSymbols {
+    UnusedSymbols {
+      names,
+      patterns,
+      excluded: HashSet::new(),
+    }
+  }
+
+  /// Removes a symbol, so that it is no longer considered unused even if it matches a pattern.
+  pub(crate) fn remove(&mut self, name: &str) {
+    self.names.remove(name);
+    if !self.patterns.is_empty() {
+      self.excluded.insert(name.to_owned());
+    }
+  }
+
+  /// Returns whether the given name is an unused symbol.
+  pub fn contains(&self, name: &str) -> bool {
+    self.matching_entry(name).is_some()
+  }
+
+  /// Returns the name or pattern in the set that matches the given name, if any.
+  pub(crate) fn matching_entry(&self, name: &str) -> Option<&str> {
+    if let Some(name) = self.names.get(name) {
+      return Some(name);
+    }
+
+    if self.excluded.contains(name) {
+      return None;
+    }
+
+    self
+      .patterns
+      .iter()
+      .find(|pattern| matches_pattern(pattern, name))
+      .map(|pattern| pattern.as_str())
+  }
+
+  /// Returns the names and patterns in the set.
+  pub(crate) fn entries(&self) -> impl Iterator<Item = &String> {
+    self.names.iter().chain(self.patterns.iter())
+  }
+
+  /// Returns whether there are no unused symbols.
+  pub fn is_empty(&self) -> bool {
+    self.names.is_empty() && self.patterns.is_empty()
+  }
+}
+
+/// Matches a name against a glob-style pattern, in which `*` matches any sequence of characters.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+  let pattern = pattern.as_bytes();
+  let name = name.as_bytes();
+  let (mut p, mut n) = (0, 0);
+  // The position of the last `*` in the pattern, and the position in the name it was matched at.
+  let mut star = None;
+  while n < name.len() {
+    if p < pattern.len() && pattern[p] == b'*' {
+      star = Some((p, n));
+      p += 1;
+    } else if p < pattern.len() && pattern[p] == name[n] {
+      p += 1;
+      n += 1;
+    } else if let Some((star_p, star_n)) = star {
+      // Let the last `*` match one more character, and try again.
+      p = star_p + 1;
+      n = star_n + 1;
+      star = Some((star_p, star_n + 1));
+    } else {
+      return false;
+    }
+  }
+
+  pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Which custom properties to inline, in [MinifyOptions](MinifyOptions).
+#[derive(Debug, Clone, PartialEq)]
+pub enum InlineCustomProperties {
+  /// No custom properties are inlined.
+  None,
+  /// All custom properties that can be safely inlined are inlined.
+  All,
+  /// Only the listed custom properties are inlined, if they can be safely inlined.
+  Only(HashSet<String>),
+}
+
+impl Default for InlineCustomProperties {
+  fn default() -> InlineCustomProperties {
+    InlineCustomProperties::None
+  }
+}
+
+impl InlineCustomProperties {
+  pub(crate) fn includes(&self, name: &str) -> bool {
+    match self {
+      InlineCustomProperties::None => false,
+      InlineCustomProperties::All => true,
+      InlineCustomProperties::Only(names) => names.contains(name),
+    }
+  }
+}
+
+impl Default for MinifyOptions {
+  fn default() -> MinifyOptions {
+    MinifyOptions {
+      targets: None,
+      unused_symbols: HashSet::new(),
+      unused_symbol_patterns: Vec::new(),
+      remove_comments: true,
+      merge_rules: true,
+      merge_longhands: true,
+      remove_empty_rules: true,
+      deduplicate_declarations: false,
+      sort_declarations: false,
+      merge_non_adjacent_at_rules: false,
+      deduplicate_keyframes: false,
+      shorten_keyframes_names: false,
+      preserved_keyframes_names: HashSet::new(),
+      strict_unused_symbols: false,
+      strip_deep_selectors: false,
+      inline_custom_properties: InlineCustomProperties::None,
+      remove_unused_custom_properties: false,
+      custom_property_fallbacks: false,
+      include: Features::empty(),
+      exclude: Features::empty(),
+      prefix_only: false,
+      error_on_unsupported: false,
+    }
+  }
 }
 
 /// A result returned from `to_css`, including the serialize CSS
 /// and other metadata depending on the input options.
 #[derive(Debug)]
 pub struct ToCssResult {
-  /// Serialized CSS code.
+  /// Serialized CSS code. This is empty when the CSS was written to a writer
+  /// with `to_css_writer` instead.
   pub code: String,
   /// A map of CSS module exports, if the `css_modules` option was
   /// enabled during parsing.
   pub exports: Option<CssModuleExports>,
+  /// A map of `@keyframes` names to their scoped names, if the `css_modules` option was
+  /// enabled during parsing. These are also included in `exports`.
+  pub animations: Option<HashMap<String, String>>,
   /// A map of CSS module references, if the `css_modules` config
-  /// had `dashed_idents` enabled.
+  /// had `dashed_idents` enabled. When bundling, classes composed from
+  /// other files are also included, keyed by their compiled name.
   pub references: Option<CssModuleReferences>,
   /// A list of dependencies (e.g. `@import` or `url()`) found in
   /// the style sheet, if the `analyze_dependencies` option is enabled.
   pub dependencies: Option<Vec<Dependency>>,
+  /// A map from removed or renamed `@keyframes` names to the names they were replaced with,
+  /// if the `deduplicate_keyframes` or `shorten_keyframes_names` minify options were enabled.
+  pub renamed_keyframes: HashMap<String, String>,
+  /// The names of the custom properties that were inlined, if the `inline_custom_properties`
+  /// minify option was enabled.
+  pub inlined_custom_properties: Vec<String>,
+  /// The names and patterns in the `unused_symbols` minify option that caused at least one rule
+  /// or declaration to be removed, in sorted order.
+  pub removed_symbols: Vec<String>,
+  /// The names and patterns in the `unused_symbols` minify option that did not cause anything
+  /// to be removed, in sorted order. These either do not appear in the style sheet, or were kept,
+  /// e.g. because they are composed by a class that is used.
+  pub unmatched_symbols: Vec<String>,
+  /// The features that were compiled for the browser targets, or because they were included,
+  /// during minification and printing.
+  pub features_used: Features,
 }
 
 impl<'i, 'o> StyleSheet<'i, 'o> {
@@ -106,6 +380,12 @@ impl<'i, 'o> StyleSheet<'i, 'o> {
     StyleSheet {
       sources,
       source_map_urls: Vec::new(),
+      license_comments: Vec::new(),
+      renamed_keyframes: HashMap::new(),
+      inlined_custom_properties: Vec::new(),
+      removed_symbols: Vec::new(),
+      unmatched_symbols: Vec::new(),
+      features_used: Features::empty(),
       rules,
       options,
     }
@@ -115,15 +395,50 @@ impl<'i, 'o> StyleSheet<'i, 'o> {
   pub fn parse(code: &'i str, options: ParserOptions<'o, 'i>) -> Result<Self, Error<ParserError<'i>>> {
     let mut input = ParserInput::new(&code);
     let mut parser = Parser::new(&mut input);
-    let rule_list_parser = RuleListParser::new_for_stylesheet(&mut parser, TopLevelRuleParser::new(&options));
 
+    let mut license_comments = Vec::new();
     let mut rules = vec![];
-    for rule in rule_list_parser {
+    loop {
+      let state = parser.state();
+      match parser.next_including_whitespace_and_comments() {
+        Ok(Token::WhiteSpace(..)) => {}
+        Ok(Token::Comment(comment)) => {
+          if is_license_comment(comment) {
+            license_comments.push((*comment).into());
+          } else if options.comments == Comments::All {
+            // Other comments before the first rule are kept along with the comments between rules.
+            let loc = state.source_location();
+            rules.push(CssRule::Comment(CommentRule {
+              comment: (*comment).into(),
+              loc: Location {
+                source_index: options.source_index,
+                line: loc.line,
+                column: loc.column,
+              },
+            }));
+          }
+        }
+        _ => {
+          parser.reset(&state);
+          break;
+        }
+      }
+    }
+
+    let mut rule_list_parser = RuleListParser::new_for_stylesheet(&mut parser, TopLevelRuleParser::new(&options));
+
+    loop {
+      parse_comments(rule_list_parser.input, &options, &mut rules);
+      let rule = match rule_list_parser.next() {
+        Some(rule) => rule,
+        None => break,
+      };
+
       let rule = match rule {
         Ok((_, CssRule::Ignored)) => continue,
         Ok((_, rule)) => rule,
         Err((e, _)) => {
-          if options.error_recovery {
+          if options.error_recovery && !options.strict {
             options.warn(e);
             continue;
           }
@@ -138,6 +453,12 @@ impl<'i, 'o> StyleSheet<'i, 'o> {
     Ok(StyleSheet {
       sources: vec![options.filename.clone()],
       source_map_urls: vec![parser.current_source_map_url().map(|s| s.to_owned())],
+      license_comments,
+      renamed_keyframes: HashMap::new(),
+      inlined_custom_properties: Vec::new(),
+      removed_symbols: Vec::new(),
+      unmatched_symbols: Vec::new(),
+      features_used: Features::empty(),
       rules: CssRuleList(rules),
       options,
     })
@@ -154,23 +475,76 @@ impl<'i, 'o> StyleSheet<'i, 'o> {
   }
 
   /// Minify and transform the style sheet for the provided browser targets.
-  pub fn minify(&mut self, options: MinifyOptions) -> Result<(), Error<MinifyErrorKind>> {
-    let mut context = PropertyHandlerContext::new(options.targets, &options.unused_symbols);
+  pub fn minify(&mut self, mut options: MinifyOptions) -> Result<(), Error<MinifyErrorKind>> {
+    if options.prefix_only {
+      if let Some(targets) = options.targets {
+        self.rules.add_prefixes(targets);
+      }
+      return Ok(());
+    }
+
+    let mut unused_symbols = UnusedSymbols::new(
+      std::mem::take(&mut options.unused_symbols),
+      std::mem::take(&mut options.unused_symbol_patterns),
+    );
+    let unused_entries: HashSet<String> = unused_symbols.entries().cloned().collect();
+
+    // Classes referenced by `composes` are exported along with the composing class, so they must not be removed.
+    if self.options.css_modules.is_some() && !unused_symbols.is_empty() {
+      if options.strict_unused_symbols {
+        remove_unused_composes(&mut self.rules, &unused_symbols, &self.options, &self.sources);
+      } else {
+        for name in composed_unused_symbols(&self.rules, &unused_symbols) {
+          unused_symbols.remove(&name);
+        }
+      }
+    }
+
+    self.inlined_custom_properties =
+      inline_custom_properties(&mut self.rules, &options.inline_custom_properties, &self.options);
+    if options.custom_property_fallbacks
+      && matches!(options.targets, Some(targets) if !Feature::CssVariables.is_compatible(targets))
+    {
+      add_custom_property_fallbacks(&mut self.rules, &self.options, &self.sources);
+    }
+    if options.remove_unused_custom_properties {
+      remove_unused_custom_properties(&mut self.rules);
+    }
+
+    let mut context = PropertyHandlerContext::new(options.targets, &unused_symbols);
+    context.merge_longhands = options.merge_longhands;
+    context.deduplicate_declarations = options.deduplicate_declarations;
+    context.sort_declarations = options.sort_declarations;
+    context.include = options.include;
+    context.exclude = options.exclude;
     let mut handler = DeclarationHandler::new(options.targets);
     let mut important_handler = DeclarationHandler::new(options.targets);
 
+    if options.remove_comments {
+      self.license_comments.clear();
+    }
+
     // @custom-media rules may be defined after they are referenced, but may only be defined at the top level
     // of a stylesheet. Do a pre-scan here and create a lookup table by name.
+    // If a name is defined more than once, the last definition in document order wins, per spec.
+    // References between custom media rules are resolved recursively during substitution.
     let custom_media = if self.options.custom_media
-      && options.targets.is_some()
-      && !Feature::CustomMediaQueries.is_compatible(options.targets.unwrap())
-    {
+      && should_compile(
+        options.targets,
+        options.include,
+        options.exclude,
+        Features::CUSTOM_MEDIA_QUERIES,
+        Feature::CustomMediaQueries,
+      ) {
       let mut custom_media = HashMap::new();
       for rule in &self.rules.0 {
         if let CssRule::CustomMedia(rule) = rule {
           custom_media.insert(rule.name.0.clone(), rule.clone());
         }
       }
+      if !custom_media.is_empty() {
+        self.features_used |= Features::CUSTOM_MEDIA_QUERIES;
+      }
       Some(custom_media)
     } else {
       None
@@ -181,9 +555,19 @@ impl<'i, 'o> StyleSheet<'i, 'o> {
       handler: &mut handler,
       important_handler: &mut important_handler,
       handler_context: &mut context,
-      unused_symbols: &options.unused_symbols,
+      unused_symbols: &unused_symbols,
+      referenced_counter_styles: if unused_symbols.is_empty() {
+        Some(HashSet::new())
+      } else {
+        referenced_counter_styles(&self.rules)
+      },
       custom_media,
       css_modules: self.options.css_modules.is_some(),
+      merge_rules: options.merge_rules,
+      merge_non_adjacent_at_rules: options.merge_non_adjacent_at_rules,
+      remove_empty_rules: options.remove_empty_rules,
+      strip_deep_selectors: options.strip_deep_selectors,
+      error_on_unsupported: options.error_on_unsupported,
     };
 
     self.rules.minify(&mut ctx, false).map_err(|e| Error {
@@ -194,6 +578,30 @@ impl<'i, 'o> StyleSheet<'i, 'o> {
       )),
     })?;
 
+    let removed_symbols = std::mem::take(&mut ctx.handler_context.removed_symbols);
+    self.unmatched_symbols = unused_entries
+      .into_iter()
+      .filter(|entry| !removed_symbols.contains(entry))
+      .collect();
+    self.unmatched_symbols.sort();
+    self.removed_symbols = removed_symbols.into_iter().collect();
+    self.removed_symbols.sort();
+    self.features_used |= ctx.handler_context.features_used;
+
+    if options.deduplicate_keyframes && self.options.css_modules.is_none() {
+      self.renamed_keyframes = deduplicate_keyframes(&mut self.rules);
+    }
+
+    if options.shorten_keyframes_names && self.options.css_modules.is_none() {
+      let shortened = shorten_keyframes_names(&mut self.rules, &options.preserved_keyframes_names);
+      for name in self.renamed_keyframes.values_mut() {
+        if let Some(short_name) = shortened.get(name) {
+          *name = short_name.clone();
+        }
+      }
+      self.renamed_keyframes.extend(shortened);
+    }
+
     Ok(())
   }
 
@@ -201,42 +609,244 @@ impl<'i, 'o> StyleSheet<'i, 'o> {
   pub fn to_css(&self, options: PrinterOptions) -> Result<ToCssResult, Error<PrinterErrorKind>> {
     // Make sure we always have capacity > 0: https://github.com/napi-rs/napi-rs/issues/1124.
     let mut dest = String::with_capacity(1);
-    let mut printer = Printer::new(&mut dest, options);
+    let mut res = self.to_css_writer(&mut dest, options)?;
+    res.code = dest;
+    Ok(res)
+  }
+
+  /// Serialize the style sheet to the given writer, e.g. to stream large outputs into a file or a
+  /// preallocated buffer. The `code` in the returned result is empty.
+  pub fn to_css_writer<W: std::fmt::Write>(
+    &self,
+    dest: &mut W,
+    options: PrinterOptions,
+  ) -> Result<ToCssResult, Error<PrinterErrorKind>> {
+    let source_map_url = options.source_map_url;
+    let mut printer = Printer::new(dest, options);
 
     printer.sources = Some(&self.sources);
     if printer.source_map.is_some() {
       printer.source_maps = self.sources.iter().enumerate().map(|(i, _)| self.source_map(i)).collect();
     }
 
+    for comment in &self.license_comments {
+      printer.write_str("/*")?;
+      for (i, line) in comment.split('\n').enumerate() {
+        if i > 0 {
+          printer.write_char('\n')?;
+        }
+        printer.write_str(line)?;
+      }
+      printer.write_str("*/")?;
+      printer.write_char('\n')?;
+    }
+
+    // Empty style sheets produce empty output, rather than a single newline.
+    let has_rules = self.rules.0.iter().any(|rule| match rule {
+      CssRule::Ignored => false,
+      CssRule::Comment(comment) => comment.is_included(printer.comments),
+      _ => true,
+    });
     if let Some(config) = &self.options.css_modules {
       let mut references = HashMap::new();
       printer.css_module = Some(CssModule::new(config, &self.sources, &mut references));
 
       self.rules.to_css(&mut printer)?;
-      printer.newline()?;
+      if has_rules {
+        printer.newline()?;
+      }
+      if let Some(url) = source_map_url {
+        printer.write_source_map_url(url)?;
+      }
 
+      let mut css_module = printer.css_module.unwrap();
       Ok(ToCssResult {
         dependencies: printer.dependencies,
-        exports: Some(std::mem::take(
-          &mut printer.css_module.unwrap().exports_by_source_index[0],
-        )),
-        code: dest,
+        features_used: self.features_used | printer.features_used,
+        exports: Some(std::mem::take(&mut css_module.exports_by_source_index[0])),
+        animations: Some(std::mem::take(&mut css_module.animations_by_source_index[0])),
+        code: String::new(),
         references: Some(references),
+        renamed_keyframes: HashMap::new(),
+        inlined_custom_properties: self.inlined_custom_properties.clone(),
+        removed_symbols: self.removed_symbols.clone(),
+        unmatched_symbols: self.unmatched_symbols.clone(),
       })
     } else {
       self.rules.to_css(&mut printer)?;
-      printer.newline()?;
+      if has_rules {
+        printer.newline()?;
+      }
+      if let Some(url) = source_map_url {
+        printer.write_source_map_url(url)?;
+      }
 
       Ok(ToCssResult {
         dependencies: printer.dependencies,
-        code: dest,
+        features_used: self.features_used | printer.features_used,
+        code: String::new(),
         exports: None,
+        animations: None,
         references: None,
+        renamed_keyframes: self.renamed_keyframes.clone(),
+        inlined_custom_properties: self.inlined_custom_properties.clone(),
+        removed_symbols: self.removed_symbols.clone(),
+        unmatched_symbols: self.unmatched_symbols.clone(),
+      })
+    }
+  }
+
+  /// Splits the style sheet by cascade layer, so that each layer can be served and cached independently.
+  ///
+  /// The first chunk is a preamble containing any `@import` and `@namespace` rules, and a single `@layer`
+  /// statement declaring the named layers in order. It is followed by a chunk for each top-level named layer,
+  /// in the order they were declared, and finally a chunk of the remaining rules. Empty chunks are omitted.
+  /// Since the layer order is declared by the preamble, concatenating the chunks does not change the cascade.
+  /// Layers are only declared up front until the first anonymous layer or layer within a conditional rule,
+  /// since their position in the layer order depends on where they appear. These, and any rules after them,
+  /// are kept in order in the last chunk, along with the unlayered rules. This style sheet is not modified.
+  pub fn split_by_layer(&self) -> Vec<(Option<LayerName<'i>>, StyleSheet<'i, 'o>)> {
+    let mut names = Vec::new();
+    let mut preamble = Vec::new();
+    let mut layers: Vec<LayerBlockRule<'i>> = Vec::new();
+    let mut unlayered = Vec::new();
+    let mut loc = None;
+    let mut is_static = true;
+    for rule in &self.rules.0 {
+      if is_static {
+        is_static = collect_layer_names(rule, &[], &mut names);
+      }
+
+      match rule {
+        CssRule::Import(..) | CssRule::Namespace(..) => preamble.push(rule.clone()),
+        CssRule::LayerStatement(statement) if is_static => {
+          loc.get_or_insert(statement.loc);
+        }
+        CssRule::LayerBlock(block) if block.name.is_some() && (is_static || declares_no_layers(rule, &names)) => {
+          loc.get_or_insert(block.loc);
+          // Blocks of the same layer become adjacent, so they can be merged.
+          match layers.iter_mut().find(|existing| existing.name == block.name) {
+            Some(existing) => existing.rules.0.extend(block.rules.0.iter().cloned()),
+            None => layers.push(block.clone()),
+          }
+        }
+        CssRule::Ignored => {}
+        rule => unlayered.push(rule.clone()),
+      }
+    }
+
+    if let Some(loc) = loc {
+      preamble.push(CssRule::LayerStatement(LayerStatementRule { names, loc }));
+    }
+
+    let mut chunks: Vec<(Option<LayerName<'i>>, Vec<CssRule<'i>>)> = Vec::with_capacity(layers.len() + 2);
+    chunks.push((None, preamble));
+    chunks.extend(
+      layers
+        .into_iter()
+        .map(|block| (block.name.clone(), vec![CssRule::LayerBlock(block)])),
+    );
+    chunks.push((None, unlayered));
+    chunks.retain(|(_, rules)| !rules.is_empty());
+
+    let mut license_comments = self.license_comments.clone();
+    chunks
+      .into_iter()
+      .map(|(name, rules)| {
+        let mut stylesheet = StyleSheet::new(self.sources.clone(), CssRuleList(rules), self.options.clone());
+        stylesheet.source_map_urls = self.source_map_urls.clone();
+        stylesheet.license_comments = std::mem::take(&mut license_comments);
+        (name, stylesheet)
       })
+      .collect()
+  }
+
+  /// Returns a hash of the parsed rules in the style sheet, as a 16 character hex string.
+  ///
+  /// The hash is computed from a canonical serialization of the AST rather than the printed output,
+  /// so it is not affected by comments, whitespace between tokens, or source locations. It can be
+  /// used to detect whether a style sheet parses identically across versions of this crate.
+  /// Hashes are stable within a major version: a change in the hash of the same input
+  /// indicates that the way it is parsed has changed.
+  #[cfg(feature = "serde")]
+  pub fn snapshot_hash(&self) -> String {
+    let mut value = serde_json::to_value(&self.rules).unwrap();
+    remove_locations(&mut value);
+    let mut canonical = String::new();
+    write_canonical(&value, &mut canonical);
+    format!("{:016x}", fnv1a(canonical.as_bytes()))
+  }
+}
+
+/// Returns whether a rule only uses layers that are already declared, so that moving it earlier
+/// does not change the layer order.
+fn declares_no_layers<'i>(rule: &CssRule<'i>, names: &[LayerName<'i>]) -> bool {
+  let mut declared = names.to_vec();
+  collect_layer_names(rule, &[], &mut declared) && declared.len() == names.len()
+}
+
+/// Removes all source locations from a serialized AST.
+#[cfg(feature = "serde")]
+fn remove_locations(value: &mut serde_json::Value) {
+  match value {
+    serde_json::Value::Object(map) => {
+      map.remove("loc");
+      for v in map.values_mut() {
+        remove_locations(v);
+      }
     }
+    serde_json::Value::Array(items) => {
+      for v in items {
+        remove_locations(v);
+      }
+    }
+    _ => {}
   }
 }
 
+/// Writes a serialized AST as JSON with object keys in sorted order.
+#[cfg(feature = "serde")]
+fn write_canonical(value: &serde_json::Value, dest: &mut String) {
+  match value {
+    serde_json::Value::Object(map) => {
+      let mut keys: Vec<&String> = map.keys().collect();
+      keys.sort();
+      dest.push('{');
+      for (i, key) in keys.into_iter().enumerate() {
+        if i > 0 {
+          dest.push(',');
+        }
+        dest.push_str(&serde_json::Value::String(key.clone()).to_string());
+        dest.push(':');
+        write_canonical(&map[key], dest);
+      }
+      dest.push('}');
+    }
+    serde_json::Value::Array(items) => {
+      dest.push('[');
+      for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+          dest.push(',');
+        }
+        write_canonical(item, dest);
+      }
+      dest.push(']');
+    }
+    _ => dest.push_str(&value.to_string()),
+  }
+}
+
+/// 64-bit FNV-1a. Used instead of the standard library hasher, whose output is not guaranteed
+/// to be stable between Rust releases.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+  let mut hash: u64 = 0xcbf29ce484222325;
+  for byte in bytes {
+    hash ^= *byte as u64;
+    hash = hash.wrapping_mul(0x100000001b3);
+  }
+  hash
+}
+
 /// An inline style attribute, as in HTML or SVG.
 ///
 /// Style attributes can be parsed from a string, minified and transformed
@@ -282,9 +892,30 @@ impl<'i> StyleAttribute<'i> {
     })
   }
 
+  /// Creates a style attribute from a declaration block, e.g. one built from individually parsed properties.
+  pub fn new(declarations: DeclarationBlock<'i>) -> StyleAttribute<'i> {
+    StyleAttribute {
+      declarations,
+      sources: vec![String::new()],
+    }
+  }
+
   /// Minify and transform the style attribute for the provided browser targets.
   pub fn minify(&mut self, options: MinifyOptions) {
-    let mut context = PropertyHandlerContext::new(options.targets, &options.unused_symbols);
+    if options.prefix_only {
+      if let Some(targets) = options.targets {
+        self.declarations.add_prefixes(targets);
+      }
+      return;
+    }
+
+    let unused_symbols = UnusedSymbols::new(options.unused_symbols, options.unused_symbol_patterns);
+    let mut context = PropertyHandlerContext::new(options.targets, &unused_symbols);
+    context.merge_longhands = options.merge_longhands;
+    context.deduplicate_declarations = options.deduplicate_declarations;
+    context.sort_declarations = options.sort_declarations;
+    context.include = options.include;
+    context.exclude = options.exclude;
     let mut handler = DeclarationHandler::new(options.targets);
     let mut important_handler = DeclarationHandler::new(options.targets);
     context.context = DeclarationContext::StyleAttribute;
@@ -293,23 +924,40 @@ impl<'i> StyleAttribute<'i> {
 
   /// Serializes the style attribute to a CSS string.
   pub fn to_css(&self, options: PrinterOptions) -> Result<ToCssResult, PrinterError> {
+    // Make sure we always have capacity > 0: https://github.com/napi-rs/napi-rs/issues/1124.
+    let mut dest = String::with_capacity(1);
+    let mut res = self.to_css_writer(&mut dest, options)?;
+    res.code = dest;
+    Ok(res)
+  }
+
+  /// Serializes the style attribute to the given writer. The `code` in the returned result is empty.
+  pub fn to_css_writer<W: std::fmt::Write>(
+    &self,
+    dest: &mut W,
+    options: PrinterOptions,
+  ) -> Result<ToCssResult, PrinterError> {
     assert!(
-      options.source_map.is_none(),
+      options.source_map.is_none() && options.source_map_url.is_none(),
       "Source maps are not supported for style attributes"
     );
 
-    // Make sure we always have capacity > 0: https://github.com/napi-rs/napi-rs/issues/1124.
-    let mut dest = String::with_capacity(1);
-    let mut printer = Printer::new(&mut dest, options);
+    let mut printer = Printer::new(dest, options);
     printer.sources = Some(&self.sources);
 
     self.declarations.to_css(&mut printer)?;
 
     Ok(ToCssResult {
       dependencies: printer.dependencies,
-      code: dest,
+      features_used: printer.features_used,
+      code: String::new(),
       exports: None,
+      animations: None,
       references: None,
+      renamed_keyframes: HashMap::new(),
+      inlined_custom_properties: Vec::new(),
+      removed_symbols: Vec::new(),
+      unmatched_symbols: Vec::new(),
     })
   }
 }
@@ -1,6 +1,8 @@
 //! Browser target options.
 // This file is autogenerated by build-prefixes.js. DO NOT EDIT!
 
+use crate::compat::Feature;
+use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
 
 /// Browser versions to compile CSS for.
@@ -43,41 +45,70 @@ impl Browsers {
     use browserslist::{resolve, Opts};
 
     let res = resolve(query, &Opts::new())?;
+    Ok(from_distribs(res))
+  }
 
-    let mut browsers = Browsers::default();
-    let mut has_any = false;
-    for distrib in res {
-      macro_rules! browser {
-        ($browser: ident) => {{
-          if let Some(v) = parse_version(distrib.version()) {
-            if browsers.$browser.is_none() || v < browsers.$browser.unwrap() {
-              browsers.$browser = Some(v);
-              has_any = true;
-            }
-          }
-        }};
-      }
+  /// Reads the browserslist configuration from a `package.json` or `.browserslistrc` file, or from the
+  /// closest one to a directory, and resolves it into Lightning CSS targets. Other files are read as
+  /// `.browserslistrc` files. As in browserslist, the `defaults` query is used if no configuration is found.
+  pub fn from_browserslist_config<P: AsRef<std::path::Path>>(
+    path: P,
+  ) -> Result<Option<Browsers>, browserslist::Error> {
+    use browserslist::{execute, Opts};
 
-      match distrib.name() {
-        "android" => browser!(android),
-        "chrome" | "and_chr" => browser!(chrome),
-        "edge" => browser!(edge),
-        "firefox" | "and_ff" => browser!(firefox),
-        "ie" => browser!(ie),
-        "ios_saf" => browser!(ios_saf),
-        "opera" | "op_mob" => browser!(opera),
-        "safari" => browser!(safari),
-        "samsung" => browser!(samsung),
-        _ => {}
-      }
+    let path = path.as_ref();
+    let mut opts = Opts::new();
+    let is_config_file = matches!(
+      path.file_name().and_then(|name| name.to_str()),
+      Some("package.json" | ".browserslistrc" | "browserslist")
+    );
+    if path.is_dir() || is_config_file {
+      opts.path(&*path.to_string_lossy());
+    } else {
+      opts.config(&*path.to_string_lossy());
     }
 
-    if !has_any {
-      return Ok(None);
+    let res = execute(&opts)?;
+    Ok(from_distribs(res))
+  }
+}
+
+/// Converts resolved browserslist queries into targets, with the lowest version of each browser.
+#[cfg(feature = "browserslist")]
+fn from_distribs(distribs: Vec<browserslist::Distrib>) -> Option<Browsers> {
+  let mut browsers = Browsers::default();
+  let mut has_any = false;
+  for distrib in distribs {
+    macro_rules! browser {
+      ($browser: ident) => {{
+        if let Some(v) = parse_version(distrib.version()) {
+          if browsers.$browser.is_none() || v < browsers.$browser.unwrap() {
+            browsers.$browser = Some(v);
+            has_any = true;
+          }
+        }
+      }};
     }
 
-    Ok(Some(browsers))
+    match distrib.name() {
+      "android" => browser!(android),
+      "chrome" | "and_chr" => browser!(chrome),
+      "edge" => browser!(edge),
+      "firefox" | "and_ff" => browser!(firefox),
+      "ie" => browser!(ie),
+      "ios_saf" => browser!(ios_saf),
+      "opera" | "op_mob" => browser!(opera),
+      "safari" => browser!(safari),
+      "samsung" => browser!(samsung),
+      _ => {}
+    }
   }
+
+  if !has_any {
+    return None;
+  }
+
+  Some(browsers)
 }
 
 #[cfg(feature = "browserslist")]
@@ -98,3 +129,85 @@ fn parse_version(version: &str) -> Option<u32> {
 
   None
 }
+
+bitflags! {
+  /// Features that can be compiled or preserved regardless of the browser targets.
+  ///
+  /// Features in the `include` option of [PrinterOptions](crate::printer::PrinterOptions) and
+  /// [MinifyOptions](crate::stylesheet::MinifyOptions) are always compiled, even if all targets
+  /// support them or no targets are set. Features in the `exclude` option are never compiled,
+  /// even if some targets do not support them.
+  #[derive(Default)]
+  pub struct Features: u32 {
+    /// Nested style rules, which are compiled to separate rules.
+    const NESTING = 1 << 0;
+    /// Media query range syntax, e.g. `(width >= 600px)`, which is compiled to `min-` and `max-` features.
+    const MEDIA_RANGE_SYNTAX = 1 << 1;
+    /// `@custom-media` rules, which are substituted into the media queries that reference them.
+    const CUSTOM_MEDIA_QUERIES = 1 << 2;
+    /// `#rrggbbaa` hex colors, which are compiled to `rgba()`.
+    const HEX_ALPHA_COLORS = 1 << 3;
+    /// Logical properties, e.g. `margin-inline-start`, which are compiled to physical properties,
+    /// with `:dir()` rules for values that depend on the direction.
+    const LOGICAL_PROPERTIES = 1 << 4;
+    /// `@scope` rules with a scope start, which are compiled to style rules with `:where()` selectors.
+    const SCOPE = 1 << 5;
+  }
+}
+
+/// Returns the browser targets that do not support a feature, e.g. `safari 14.1`.
+pub(crate) fn incompatible_targets(targets: Browsers, feature: Feature) -> Vec<String> {
+  let mut result = Vec::new();
+  macro_rules! browser {
+    ($browser: ident) => {
+      if let Some(version) = targets.$browser {
+        let browsers = Browsers {
+          $browser: Some(version),
+          ..Browsers::default()
+        };
+        if !feature.is_compatible(browsers) {
+          result.push(format!("{} {}", stringify!($browser), format_version(version)));
+        }
+      }
+    };
+  }
+
+  browser!(android);
+  browser!(chrome);
+  browser!(edge);
+  browser!(firefox);
+  browser!(ie);
+  browser!(ios_saf);
+  browser!(opera);
+  browser!(safari);
+  browser!(samsung);
+  result
+}
+
+/// Formats a version as `major[.minor[.patch]]`.
+fn format_version(version: u32) -> String {
+  let (major, minor, patch) = (version >> 16, (version >> 8) & 0xff, version & 0xff);
+  if patch != 0 {
+    format!("{}.{}.{}", major, minor, patch)
+  } else if minor != 0 {
+    format!("{}.{}", major, minor)
+  } else {
+    major.to_string()
+  }
+}
+
+/// Returns whether a feature should be compiled for the given targets, unless it is
+/// explicitly included or excluded.
+pub(crate) fn should_compile(
+  targets: Option<Browsers>,
+  include: Features,
+  exclude: Features,
+  feature: Features,
+  compat: Feature,
+) -> bool {
+  if exclude.contains(feature) {
+    return false;
+  }
+
+  include.contains(feature) || matches!(targets, Some(targets) if !compat.is_compatible(targets))
+}
@@ -82,6 +82,43 @@ fn modulo(a: f32, b: f32) -> f32 {
   ((a % b) + b) % b
 }
 
+/// Returns whether the remaining input contains a math function, at any level of nesting.
+pub(crate) fn contains_math_function<'i, 't>(input: &mut Parser<'i, 't>) -> bool {
+  loop {
+    let is_block = match input.next() {
+      Ok(Token::Function(name)) => {
+        if is_math_function(name) {
+          return true;
+        }
+        true
+      }
+      Ok(Token::ParenthesisBlock) | Ok(Token::SquareBracketBlock) | Ok(Token::CurlyBracketBlock) => true,
+      Ok(_) => false,
+      Err(_) => return false,
+    };
+
+    if is_block {
+      let res = input.parse_nested_block(|input| {
+        let res = contains_math_function(input);
+        // Nested blocks must be consumed entirely.
+        while input.next().is_ok() {}
+        Ok::<_, ParseError<'i, ()>>(res)
+      });
+      if res.unwrap_or(false) {
+        return true;
+      }
+    }
+  }
+}
+
+fn is_math_function(name: &str) -> bool {
+  match_ignore_ascii_case! { name,
+    "calc" | "min" | "max" | "clamp" | "round" | "rem" | "mod" | "sin" | "cos" | "tan" | "asin" | "acos" | "atan"
+      | "atan2" | "pow" | "log" | "sqrt" | "exp" | "hypot" | "abs" | "sign" => true,
+    _ => false
+  }
+}
+
 impl<V: ToCss + std::ops::Mul<f32, Output = V> + TrySign + Clone + std::fmt::Debug> ToCss for MathFunction<V> {
   fn to_css<W>(&self, dest: &mut Printer<W>) -> Result<(), PrinterError>
   where
@@ -9,9 +9,9 @@ use super::percentage::Percentage;
 use crate::compat::Feature;
 use crate::error::{ParserError, PrinterError};
 use crate::macros::enum_property;
-use crate::printer::Printer;
+use crate::printer::{ColorFormat, Printer};
 use crate::rules::supports::SupportsCondition;
-use crate::targets::Browsers;
+use crate::targets::{Browsers, Features};
 use crate::traits::{FallbackValues, Parse, ToCss};
 use bitflags::bitflags;
 use cssparser::*;
@@ -354,11 +354,29 @@ impl ToCss for CssColor {
   where
     W: std::fmt::Write,
   {
+    match (self, dest.color_format) {
+      (CssColor::RGBA(color), Some(format)) => return write_rgba_format(color, format, dest),
+      (CssColor::Float(float), Some(format)) => {
+        return write_rgba_format(&SRGB::from(**float).into(), format, dest)
+      }
+      (CssColor::LAB(..) | CssColor::Predefined(..), Some(ColorFormat::Oklch)) => {
+        // Other formats cannot represent colors outside the sRGB gamut, so they are kept as is.
+        let oklch = OKLCH::from(self);
+        return write_components("oklch", oklch.l, oklch.c, oklch.h, oklch.alpha, dest);
+      }
+      _ => {}
+    }
+
     match self {
       CssColor::CurrentColor => dest.write_str("currentColor"),
       CssColor::RGBA(color) => {
         if color.alpha == 255 {
           let hex: u32 = ((color.red as u32) << 16) | ((color.green as u32) << 8) | (color.blue as u32);
+          if !dest.minify_colors {
+            write!(dest, "#{:06x}", hex)?;
+            return Ok(());
+          }
+
           if let Some(name) = short_color_name(hex) {
             return dest.write_str(name);
           }
@@ -371,45 +389,23 @@ impl ToCss for CssColor {
           }
         } else {
           // If the #rrggbbaa syntax is not supported by the browser targets, output rgba()
-          if let Some(targets) = dest.targets {
-            if !Feature::CssRrggbbaa.is_compatible(targets) {
-              // If the browser doesn't support `#rrggbbaa` color syntax, it is converted to `transparent` when compressed(minify = true).
-              // https://www.w3.org/TR/css-color-4/#transparent-black
-              if dest.minify && color.red == 0 && color.green == 0 && color.blue == 0 && color.alpha == 0 {
-                return dest.write_str("transparent");
-              } else {
-                dest.write_str("rgba(")?;
-                write!(dest, "{}", color.red)?;
-                dest.delim(',', false)?;
-                write!(dest, "{}", color.green)?;
-                dest.delim(',', false)?;
-                write!(dest, "{}", color.blue)?;
-                dest.delim(',', false)?;
-
-                // Try first with two decimal places, then with three.
-                let mut rounded_alpha = (color.alpha_f32() * 100.0).round() / 100.0;
-                let clamped = (rounded_alpha * 255.0).round().max(0.).min(255.0) as u8;
-                if clamped != color.alpha {
-                  rounded_alpha = (color.alpha_f32() * 1000.).round() / 1000.;
-                }
-
-                rounded_alpha.to_css(dest)?;
-                dest.write_char(')')?;
-                return Ok(());
-              }
+          if dest.should_compile(Features::HEX_ALPHA_COLORS, Feature::CssRrggbbaa) {
+            // If the browser doesn't support `#rrggbbaa` color syntax, it is converted to `transparent` when compressed(minify = true).
+            // https://www.w3.org/TR/css-color-4/#transparent-black
+            if dest.minify
+              && dest.minify_colors
+              && color.red == 0
+              && color.green == 0
+              && color.blue == 0
+              && color.alpha == 0
+            {
+              return dest.write_str("transparent");
+            } else {
+              return write_rgb_function(color, dest);
             }
           }
 
-          let hex: u32 = ((color.red as u32) << 24)
-            | ((color.green as u32) << 16)
-            | ((color.blue as u32) << 8)
-            | (color.alpha as u32);
-          let compact = compact_hex(hex);
-          if hex == expand_hex(compact) {
-            write!(dest, "#{:04x}", compact)?;
-          } else {
-            write!(dest, "#{:08x}", hex)?;
-          }
+          write_hex_alpha(color, dest)?;
         }
         Ok(())
       }
@@ -429,6 +425,98 @@ impl ToCss for CssColor {
   }
 }
 
+fn write_hex_alpha<W>(color: &RGBA, dest: &mut Printer<W>) -> Result<(), PrinterError>
+where
+  W: std::fmt::Write,
+{
+  let hex: u32 =
+    ((color.red as u32) << 24) | ((color.green as u32) << 16) | ((color.blue as u32) << 8) | (color.alpha as u32);
+  let compact = compact_hex(hex);
+  if dest.minify_colors && hex == expand_hex(compact) {
+    write!(dest, "#{:04x}", compact)?;
+  } else {
+    write!(dest, "#{:08x}", hex)?;
+  }
+  Ok(())
+}
+
+/// Writes a color using the legacy comma separated `rgb()` or `rgba()` syntax, which all browsers support.
+fn write_rgb_function<W>(color: &RGBA, dest: &mut Printer<W>) -> Result<(), PrinterError>
+where
+  W: std::fmt::Write,
+{
+  dest.write_str(if color.alpha == 255 { "rgb(" } else { "rgba(" })?;
+  write!(dest, "{}", color.red)?;
+  dest.delim(',', false)?;
+  write!(dest, "{}", color.green)?;
+  dest.delim(',', false)?;
+  write!(dest, "{}", color.blue)?;
+  if color.alpha != 255 {
+    dest.delim(',', false)?;
+    write_alpha(color, dest)?;
+  }
+  dest.write_char(')')
+}
+
+fn write_alpha<W>(color: &RGBA, dest: &mut Printer<W>) -> Result<(), PrinterError>
+where
+  W: std::fmt::Write,
+{
+  rounded_alpha(color).to_css(dest)
+}
+
+/// Returns the shortest alpha value that rounds to the same byte.
+fn rounded_alpha(color: &RGBA) -> f32 {
+  // Try first with two decimal places, then with three.
+  let rounded_alpha = (color.alpha_f32() * 100.0).round() / 100.0;
+  let clamped = (rounded_alpha * 255.0).round().max(0.).min(255.0) as u8;
+  if clamped != color.alpha {
+    return (color.alpha_f32() * 1000.).round() / 1000.;
+  }
+  rounded_alpha
+}
+
+/// Writes an RGB color in the format given by the `color_format` printer option,
+/// regardless of the browser targets.
+fn write_rgba_format<W>(color: &RGBA, format: ColorFormat, dest: &mut Printer<W>) -> Result<(), PrinterError>
+where
+  W: std::fmt::Write,
+{
+  match format {
+    ColorFormat::Hex if color.alpha == 255 => {
+      let hex: u32 = ((color.red as u32) << 16) | ((color.green as u32) << 8) | (color.blue as u32);
+      let compact = compact_hex(hex);
+      if dest.minify_colors && hex == expand_hex(compact) {
+        write!(dest, "#{:03x}", compact)?;
+      } else {
+        write!(dest, "#{:06x}", hex)?;
+      }
+      Ok(())
+    }
+    ColorFormat::Hex => write_hex_alpha(color, dest),
+    ColorFormat::Rgb => write_rgb_function(color, dest),
+    ColorFormat::Hsl => {
+      let hsl = HSL::from(*color);
+      dest.write_str(if color.alpha == 255 { "hsl(" } else { "hsla(" })?;
+      // Achromatic colors have a missing hue, which the legacy syntax does not support.
+      (if hsl.h.is_nan() { 0.0 } else { hsl.h }).to_css(dest)?;
+      dest.delim(',', false)?;
+      Percentage(hsl.s).to_css(dest)?;
+      dest.delim(',', false)?;
+      Percentage(hsl.l).to_css(dest)?;
+      if color.alpha != 255 {
+        dest.delim(',', false)?;
+        write_alpha(color, dest)?;
+      }
+      dest.write_char(')')
+    }
+    ColorFormat::Oklch => {
+      let oklch = OKLCH::from(*color);
+      write_components("oklch", oklch.l, oklch.c, oklch.h, rounded_alpha(color), dest)
+    }
+  }
+}
+
 // From esbuild: https://github.com/evanw/esbuild/blob/18e13bdfdca5cd3c7a2fae1a8bd739f8f891572c/internal/css_parser/css_decls_color.go#L218
 // 0xAABBCCDD => 0xABCD
 fn compact_hex(v: u32) -> u32 {
@@ -472,7 +472,7 @@ impl<'i> ImageSetOption<'i> {
             dependencies.push(Dependency::Url(dep))
           }
         } else {
-          serialize_string(&url.url, dest)?;
+          dest.write_string(&url.url)?;
         }
       }
       _ => self.image.to_css(dest)?,
@@ -492,7 +492,7 @@ impl<'i> ImageSetOption<'i> {
 
     if let Some(file_type) = &self.file_type {
       dest.write_str(" type(")?;
-      serialize_string(&file_type, dest)?;
+      dest.write_string(&file_type)?;
       dest.write_char(')')?;
     }
 
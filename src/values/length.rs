@@ -2,7 +2,7 @@
 
 use super::angle::impl_try_from_angle;
 use super::calc::{Calc, MathFunction};
-use super::number::CSSNumber;
+use super::number::{serialize_rounded, CSSNumber};
 use super::percentage::DimensionPercentage;
 use crate::error::{ParserError, PrinterError};
 use crate::printer::Printer;
@@ -124,6 +124,14 @@ macro_rules! define_length_units {
     }
 
     impl LengthValue {
+      /// Returns whether the given unit is a length unit.
+      pub(crate) fn is_unit(unit: &str) -> bool {
+        $(
+          unit.eq_ignore_ascii_case(stringify!($name)) ||
+        )+
+        false
+      }
+
       /// Returns the numeric value and unit string for the length value.
       pub fn to_unit_value(&self) -> (CSSNumber, &str) {
         match self {
@@ -413,6 +421,16 @@ where
   W: std::fmt::Write,
 {
   use cssparser::ToCss;
+  if let Some(decimals) = dest.precision.for_unit(unit) {
+    // Units such as `e-x` are written by cssparser with an escape, so they are not confused with an exponent.
+    let is_exponent_like = unit.eq_ignore_ascii_case("e") || unit.starts_with("e-") || unit.starts_with("E-");
+    if !is_exponent_like {
+      serialize_rounded(value, decimals, dest)?;
+      serialize_identifier(unit, dest)?;
+      return Ok(());
+    }
+  }
+
   let int_value = if value.fract() == 0.0 { Some(value as i32) } else { None };
   let token = Token::Dimension {
     has_sign: value < 0.0,
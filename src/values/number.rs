@@ -35,9 +35,13 @@ impl ToCss for CSSNumber {
     W: std::fmt::Write,
   {
     let number = *self;
+    if let Some(decimals) = dest.precision.numbers {
+      return serialize_rounded(number, decimals, dest);
+    }
+
     if number != 0.0 && number.abs() < 1.0 {
       let mut s = String::new();
-      cssparser::ToCss::to_css(self, &mut s)?;
+      cssparser::ToCss::to_css(&number, &mut s)?;
       if number < 0.0 {
         dest.write_char('-')?;
         dest.write_str(s.trim_start_matches("-0"))
@@ -45,12 +49,57 @@ impl ToCss for CSSNumber {
         dest.write_str(s.trim_start_matches('0'))
       }
     } else {
-      cssparser::ToCss::to_css(self, dest)?;
+      cssparser::ToCss::to_css(&number, dest)?;
       Ok(())
     }
   }
 }
 
+/// Replaces values that cannot be serialized: NaN with zero, and infinities with the largest finite value.
+/// This is only applied when rounding to a precision, so that the default output is unchanged.
+pub(crate) fn finite(value: f32) -> f32 {
+  if value.is_nan() {
+    0.0
+  } else {
+    value.clamp(f32::MIN, f32::MAX)
+  }
+}
+
+/// Writes a number rounded to at most `decimals` decimal places, in the shortest form that
+/// represents the rounded value. Exponential notation is only used for very large values.
+pub(crate) fn serialize_rounded<W>(value: f32, decimals: u8, dest: &mut Printer<W>) -> Result<(), PrinterError>
+where
+  W: std::fmt::Write,
+{
+  // Round the shortest decimal representation of the value rather than its exact binary value,
+  // so that e.g. 0.3 is not printed as 0.30000001.
+  let shortest: f64 = finite(value).to_string().parse().unwrap();
+  let mut s = if shortest.abs() >= 1e21 {
+    format!("{:e}", shortest)
+  } else {
+    let mut s = format!("{:.*}", decimals as usize, shortest);
+    if s.contains('.') {
+      s.truncate(s.trim_end_matches('0').trim_end_matches('.').len());
+    }
+    s
+  };
+
+  if s == "-0" {
+    s = "0".into();
+  }
+
+  // Omit the leading zero, e.g. .5 rather than 0.5.
+  if let Some(fract) = s.strip_prefix("0.") {
+    dest.write_char('.')?;
+    dest.write_str(fract)
+  } else if let Some(fract) = s.strip_prefix("-0.") {
+    dest.write_str("-.")?;
+    dest.write_str(fract)
+  } else {
+    dest.write_str(&s)
+  }
+}
+
 impl std::convert::Into<Calc<CSSNumber>> for CSSNumber {
   fn into(self) -> Calc<CSSNumber> {
     Calc::Value(Box::new(self))
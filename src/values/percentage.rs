@@ -2,7 +2,7 @@
 
 use super::angle::{impl_try_from_angle, Angle};
 use super::calc::{Calc, MathFunction};
-use super::number::CSSNumber;
+use super::number::{finite, serialize_rounded, CSSNumber};
 use crate::error::{ParserError, PrinterError};
 use crate::printer::Printer;
 use crate::traits::private::AddInternal;
@@ -37,6 +37,11 @@ impl ToCss for Percentage {
     W: std::fmt::Write,
   {
     use cssparser::ToCss;
+    if let Some(decimals) = dest.precision.percentages {
+      serialize_rounded(finite(self.0) * 100.0, decimals, dest)?;
+      return dest.write_char('%');
+    }
+
     let int_value = if (self.0 * 100.0).fract() == 0.0 {
       Some(self.0 as i32)
     } else {
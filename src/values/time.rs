@@ -2,6 +2,7 @@
 
 use super::angle::impl_try_from_angle;
 use super::calc::Calc;
+use super::length::serialize_dimension;
 use super::number::CSSNumber;
 use crate::error::{ParserError, PrinterError};
 use crate::printer::Printer;
@@ -83,20 +84,16 @@ impl ToCss for Time {
     match self {
       Time::Seconds(s) => {
         if *s > 0.0 && *s < 0.1 {
-          (*s * 1000.0).to_css(dest)?;
-          dest.write_str("ms")
+          serialize_dimension(*s * 1000.0, "ms", dest)
         } else {
-          s.to_css(dest)?;
-          dest.write_str("s")
+          serialize_dimension(*s, "s", dest)
         }
       }
       Time::Milliseconds(ms) => {
         if *ms == 0.0 || *ms >= 100.0 {
-          (*ms / 1000.0).to_css(dest)?;
-          dest.write_str("s")
+          serialize_dimension(*ms / 1000.0, "s", dest)
         } else {
-          ms.to_css(dest)?;
-          dest.write_str("ms")
+          serialize_dimension(*ms, "ms", dest)
         }
       }
     }
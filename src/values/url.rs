@@ -2,7 +2,7 @@
 
 use crate::dependencies::{Dependency, Location, UrlDependency};
 use crate::error::{ParserError, PrinterError};
-use crate::printer::Printer;
+use crate::printer::{Printer, QuoteStyle};
 use crate::traits::{Parse, ToCss};
 use crate::values::string::CowArcStr;
 use cssparser::*;
@@ -58,30 +58,41 @@ impl<'i> ToCss for Url<'i> {
     }
 
     use cssparser::ToCss;
-    if dest.minify {
-      let mut buf = String::new();
-      Token::UnquotedUrl(CowRcStr::from(self.url.as_ref())).to_css(&mut buf)?;
-
-      // If the unquoted url is longer than it would be quoted (e.g. `url("...")`)
-      // then serialize as a string and choose the shorter version.
-      if buf.len() > self.url.len() + 7 {
-        let mut buf2 = String::new();
-        serialize_string(&self.url, &mut buf2)?;
-        if buf2.len() + 5 < buf.len() {
-          dest.write_str("url(")?;
-          dest.write_str(&buf2)?;
-          return dest.write_char(')');
+    match dest.quotes {
+      Some(QuoteStyle::Minimal) => {
+        let mut buf = String::new();
+        Token::UnquotedUrl(CowRcStr::from(self.url.as_ref())).to_css(&mut buf)?;
+
+        // Only write the url without quotes if no characters needed to be escaped.
+        if !self.url.is_empty() && buf.len() == self.url.len() + 5 {
+          return dest.write_str(&buf);
         }
       }
+      Some(_) => {}
+      None if dest.minify => {
+        let mut buf = String::new();
+        Token::UnquotedUrl(CowRcStr::from(self.url.as_ref())).to_css(&mut buf)?;
+
+        // If the unquoted url is longer than it would be quoted (e.g. `url("...")`)
+        // then serialize as a string and choose the shorter version.
+        if buf.len() > self.url.len() + 7 {
+          let mut buf2 = String::new();
+          serialize_string(&self.url, &mut buf2)?;
+          if buf2.len() + 5 < buf.len() {
+            dest.write_str("url(")?;
+            dest.write_str(&buf2)?;
+            return dest.write_char(')');
+          }
+        }
 
-      dest.write_str(&buf)?;
-    } else {
-      dest.write_str("url(")?;
-      serialize_string(&self.url, dest)?;
-      dest.write_char(')')?;
+        return dest.write_str(&buf);
+      }
+      None => {}
     }
 
-    Ok(())
+    dest.write_str("url(")?;
+    dest.write_string(&self.url)?;
+    dest.write_char(')')
   }
 }
 
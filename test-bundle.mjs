@@ -130,6 +130,200 @@ await (async function testOnlyCustomResolve() {
   if (code !== expected) throw new Error(`\`testOnlyCustomResolve()\` failed. Expected:\n${expected}\n\nGot:\n${code}`);
 })();
 
+await (async function testGenerateName() {
+  const inMemoryFs = new Map(Object.entries({
+    'foo.css': `
+ @import 'bar.css';
+
+ .foo { color: red; }
+ .foo:hover { color: blue; }
+         `.trim(),
+
+    'bar.css': `
+ .bar { color: green; }
+         `.trim(),
+  }));
+
+  const calls = [];
+  const { code: buffer, exports } = await css.bundleAsync({
+    filename: 'foo.css',
+    minify: true,
+    cssModules: {
+      generateName(name, filename) {
+        calls.push(`${filename}:${name}`);
+        return `${path.basename(filename, '.css')}__${name}`;
+      },
+    },
+    resolver: {
+      read(file) {
+        return inMemoryFs.get(path.normalize(file));
+      },
+    },
+  });
+  const code = buffer.toString('utf-8').trim();
+
+  const expected = '.bar__bar{color:green}.foo__foo{color:red}.foo__foo:hover{color:#00f}';
+  if (code !== expected) throw new Error(`\`testGenerateName()\` failed. Expected:\n${expected}\n\nGot:\n${code}`);
+  if (exports.foo.name !== 'foo__foo') throw new Error(`\`testGenerateName()\` failed. Expected export name \`foo__foo\`, got \`${exports.foo.name}\`.`);
+  // Each name is only generated once per file.
+  if (new Set(calls).size !== calls.length) throw new Error(`\`testGenerateName()\` failed. Expected each name to be generated once, got ${calls.join(', ')}.`);
+})();
+
+await (async function testSyncResolver() {
+  const inMemoryFs = new Map(Object.entries({
+    'foo.css': `
+ @import 'root:bar.css';
+
+ .foo { color: red; }
+         `.trim(),
+
+    'bar.css': `
+ .bar { color: green; }
+         `.trim(),
+  }));
+
+  const { code: buffer } = css.bundle({
+    filename: 'foo.css',
+    resolver: {
+      read(file) {
+        return inMemoryFs.get(path.normalize(file));
+      },
+
+      resolve(specifier) {
+        return specifier.slice('root:'.length);
+      },
+    },
+  });
+  const code = buffer.toString('utf-8').trim();
+
+  const expected = `
+.bar {
+  color: green;
+}
+
+.foo {
+  color: red;
+}
+     `.trim();
+  if (code !== expected) throw new Error(`\`testSyncResolver()\` failed. Expected:\n${expected}\n\nGot:\n${code}`);
+})();
+
+await (async function testCreateBundler() {
+  const inMemoryFs = new Map(Object.entries({
+    'foo.css': `
+ @import 'bar.css';
+
+ .foo { color: red; }
+         `.trim(),
+
+    'bar.css': `
+ .bar { color: green; }
+         `.trim(),
+  }));
+
+  let reads = 0;
+  const options = {
+    filename: 'foo.css',
+    minify: true,
+    resolver: {
+      read(file) {
+        reads++;
+        return inMemoryFs.get(path.normalize(file));
+      },
+    },
+  };
+
+  const bundler = css.createBundler();
+  const check = (expected, expectedReads) => {
+    reads = 0;
+    const code = bundler.bundle(options).code.toString('utf-8');
+    if (code !== expected) throw new Error(`\`testCreateBundler()\` failed. Expected:\n${expected}\n\nGot:\n${code}`);
+    if (reads !== expectedReads) throw new Error(`\`testCreateBundler()\` failed. Expected ${expectedReads} reads, got ${reads}.`);
+  };
+
+  check('.bar{color:green}.foo{color:red}', 2);
+  check('.bar{color:green}.foo{color:red}', 0);
+
+  inMemoryFs.set('bar.css', '.bar { color: blue; }');
+  bundler.invalidate('bar.css');
+  check('.bar{color:#00f}.foo{color:red}', 1);
+})();
+
+await (async function testSyncResolverReturnPromise() {
+  let error = undefined;
+  try {
+    css.bundle({
+      filename: 'foo.css',
+      resolver: {
+        async read() {
+          return '.foo { color: red }';
+        }
+      },
+    });
+  } catch (err) {
+    error = err;
+  }
+
+  if (!error) throw new Error(`\`testSyncResolverReturnPromise()\` failed. Expected \`bundle()\` to throw, but it did not.`);
+  if (!error.message.includes('must be synchronous')) {
+    throw new Error(`\`testSyncResolverReturnPromise()\` failed. Expected \`bundle()\` to throw a specific error message, but it threw a different error:\n${error.message}`);
+  }
+})();
+
+await (async function testSyncReadThrow() {
+  let error = undefined;
+  let reads = 0;
+  try {
+    css.bundle({
+      filename: 'foo.css',
+      resolver: {
+        read(file) {
+          reads++;
+          if (file === 'foo.css') {
+            return '@import "bar.css"; @import "baz.css"; .foo { color: red }';
+          }
+          throw new Error(`Oh noes! Failed to read \`${file}\`.`);
+        }
+      },
+    });
+  } catch (err) {
+    error = err;
+  }
+
+  if (!error) throw new Error(`\`testSyncReadThrow()\` failed. Expected \`bundle()\` to throw, but it did not.`);
+  if (!error.message.includes('Oh noes! Failed to read')) {
+    throw new Error(`\`testSyncReadThrow()\` failed. Expected \`bundle()\` to throw the error from \`read()\`, but it threw a different error:\n${error.message}`);
+  }
+  // The resolver is not called again once it has thrown.
+  if (reads !== 2) throw new Error(`\`testSyncReadThrow()\` failed. Expected 2 reads, got ${reads}.`);
+})();
+
+await (async function testSingleThread() {
+  // A deep import tree must still complete when only one thread is available
+  // to read and parse files.
+  const depth = 50;
+  const inMemoryFs = new Map();
+  for (let i = 0; i < depth; i++) {
+    inMemoryFs.set(`${i}.css`, `@import '${i + 1}.css';\n.a${i} { color: red; }`);
+  }
+  inMemoryFs.set(`${depth}.css`, `.a${depth} { color: red; }`);
+
+  const { code: buffer } = await css.bundleAsync({
+    filename: '0.css',
+    threads: 1,
+    minify: true,
+    resolver: {
+      async read(file) {
+        return inMemoryFs.get(path.normalize(file));
+      },
+    },
+  });
+  const code = buffer.toString('utf-8').trim();
+
+  const expected = Array.from({ length: depth + 1 }, (_, i) => `.a${depth - i}{color:red}`).join('');
+  if (code !== expected) throw new Error(`\`testSingleThread()\` failed. Expected:\n${expected}\n\nGot:\n${code}`);
+})();
+
 await (async function testAsyncRead() {
   const root = path.join('tests', 'testdata');
   const { code: buffer } = await css.bundleAsync({
@@ -293,4 +487,47 @@ await (async function testResolveReturnNonString() {
   }
 })();
 
+await (async function testAsyncReadRejectMessage() {
+  let error = undefined;
+  try {
+    await css.bundleAsync({
+      filename: 'foo.css',
+      resolver: {
+        async read(file) {
+          throw new Error(`Oh noes! Failed to read \`${file}\`.`);
+        }
+      },
+    });
+  } catch (err) {
+    error = err;
+  }
+
+  if (!error) throw new Error(`\`testAsyncReadRejectMessage()\` failed. Expected \`bundleAsync()\` to throw, but it did not.`);
+  if (!error.message.includes('Oh noes! Failed to read `foo.css`.')) {
+    throw new Error(`\`testAsyncReadRejectMessage()\` failed. Expected \`bundleAsync()\` to throw the rejection message, but it threw a different error:\n${error.message}`);
+  }
+})();
+
+await (async function testResolveTimeout() {
+  let error = undefined;
+  try {
+    await css.bundleAsync({
+      filename: 'foo.css',
+      resolveTimeoutMs: 50,
+      resolver: {
+        read() {
+          return new Promise(() => {}); // Never settles.
+        }
+      },
+    });
+  } catch (err) {
+    error = err;
+  }
+
+  if (!error) throw new Error(`\`testResolveTimeout()\` failed. Expected \`bundleAsync()\` to throw, but it did not.`);
+  if (!error.message.includes('Resolver timed out after 50ms while reading foo.css')) {
+    throw new Error(`\`testResolveTimeout()\` failed. Expected \`bundleAsync()\` to throw a timeout error, but it threw a different error:\n${error.message}`);
+  }
+})();
+
 console.log('PASSED!');
@@ -0,0 +1,267 @@
+import css from './node/index.js';
+import adapters from './node/adapters.js';
+
+await (async function testWarningsLimit() {
+  const code = Buffer.from('h1(>h1) { color: red } .foo { *zoom: 1 } h2(>h2) { color: red } .bar { color: red }');
+  const transform = (warningsLimit) => css.transform({
+    filename: 'test.css',
+    code,
+    errorRecovery: true,
+    warningsLimit,
+  });
+
+  let { warnings, warningsTruncated } = transform(undefined);
+  if (warnings.length !== 3 || warningsTruncated) {
+    throw new Error(`\`testWarningsLimit()\` failed. Expected all 3 warnings without a limit, got ${warnings.length} (truncated: ${warningsTruncated}).`);
+  }
+
+  ({ warnings, warningsTruncated } = transform(2));
+  if (warnings.length !== 2 || !warningsTruncated) {
+    throw new Error(`\`testWarningsLimit()\` failed. Expected 2 warnings with a limit of 2, got ${warnings.length} (truncated: ${warningsTruncated}).`);
+  }
+
+  ({ warnings, warningsTruncated } = transform(3));
+  if (warnings.length !== 3 || warningsTruncated) {
+    throw new Error(`\`testWarningsLimit()\` failed. Expected 3 warnings with a limit of 3, got ${warnings.length} (truncated: ${warningsTruncated}).`);
+  }
+})();
+
+await (async function testInlineImports() {
+  const inMemoryFs = new Map(Object.entries({
+    'bar.css': '.bar { color: green }',
+  }));
+
+  const { code } = css.transform({
+    filename: 'foo.css',
+    code: Buffer.from('@import "bar.css";\n.foo { color: red }'),
+    minify: true,
+    inlineImports: true,
+    resolver: {
+      read(file) {
+        const result = inMemoryFs.get(file);
+        if (!result) throw new Error(`Could not find ${file} in ${Array.from(inMemoryFs.keys()).join(', ')}.`);
+        return result;
+      },
+    },
+  });
+
+  const result = code.toString();
+  const expected = '.bar{color:green}.foo{color:red}';
+  if (result !== expected) throw new Error(`\`testInlineImports()\` failed. Expected:\n${expected}\n\nGot:\n${result}`);
+})();
+
+await (async function testReturnAst() {
+  const { code, ast } = css.transform({
+    filename: 'test.css',
+    code: Buffer.from('.foo { color: red }'),
+    minify: true,
+    returnAst: true,
+  });
+
+  if (!ast || ast.rules.length !== 1 || ast.rules[0].type !== 'style') {
+    throw new Error(`\`testReturnAst()\` failed. Expected an AST with a single style rule, got:\n${JSON.stringify(ast)}`);
+  }
+
+  // The AST can be printed again with `transformFromAst()`.
+  const res = css.transformFromAst({ ast, minify: true });
+  if (res.code.toString() !== code.toString()) {
+    throw new Error(`\`testReturnAst()\` failed. Expected \`transformFromAst()\` to print:\n${code}\n\nGot:\n${res.code}`);
+  }
+})();
+
+await (async function testTransformFromAstInvalid() {
+  let error = undefined;
+  try {
+    css.transformFromAst({ ast: { rules: [{ type: 'style', value: 42 }], sources: [] } });
+  } catch (err) {
+    error = err;
+  }
+
+  if (!error) throw new Error(`\`testTransformFromAstInvalid()\` failed. Expected \`transformFromAst()\` to throw for a malformed AST, but it did not.`);
+})();
+
+await (async function testValidate() {
+  const code = Buffer.from('h1(>h1) { color: red } .foo { color: red }');
+  let { warnings, errors } = css.validate({ filename: 'test.css', code, errorRecovery: true });
+  if (warnings.length !== 1 || errors.length !== 0) {
+    throw new Error(`\`testValidate()\` failed. Expected 1 warning and no errors with error recovery, got ${warnings.length} warnings and ${errors.length} errors.`);
+  }
+
+  ({ warnings, errors } = css.validate({ filename: 'test.css', code }));
+  if (warnings.length !== 0 || errors.length !== 1 || errors[0].loc?.line !== 1) {
+    throw new Error(`\`testValidate()\` failed. Expected a single error on line 1 without error recovery, got:\n${JSON.stringify(errors)}`);
+  }
+})();
+
+await (async function testVerifyOutput() {
+  const { verificationErrors } = css.transform({
+    filename: 'test.css',
+    code: Buffer.from('.foo { color: red }'),
+    minify: true,
+    verifyOutput: true,
+  });
+  if (!Array.isArray(verificationErrors) || verificationErrors.length !== 0) {
+    throw new Error(`\`testVerifyOutput()\` failed. Expected no verification errors, got:\n${JSON.stringify(verificationErrors)}`);
+  }
+
+  const attr = css.transformStyleAttribute({
+    code: Buffer.from('color: red'),
+    minify: true,
+    verifyOutput: true,
+  });
+  if (!Array.isArray(attr.verificationErrors) || attr.verificationErrors.length !== 0) {
+    throw new Error(`\`testVerifyOutput()\` failed. Expected no verification errors for a style attribute, got:\n${JSON.stringify(attr.verificationErrors)}`);
+  }
+})();
+
+await (async function testTargetsValidation() {
+  let error = undefined;
+  try {
+    css.transform({
+      filename: 'test.css',
+      code: Buffer.from('.foo { color: red }'),
+      targets: { chorme: 95 << 16 },
+    });
+  } catch (err) {
+    error = err;
+  }
+
+  if (!(error instanceof TypeError) || !error.message.includes('Unknown browser target "chorme"')) {
+    throw new Error(`\`testTargetsValidation()\` failed. Expected a TypeError for an unknown browser, got:\n${error}`);
+  }
+
+  // Versions may be given as strings.
+  const transform = (targets) => css.transform({
+    filename: 'test.css',
+    code: Buffer.from('.foo { color: #ff000080 }'),
+    minify: true,
+    targets,
+  }).code.toString();
+  const encoded = transform({ chrome: 60 << 16 });
+  const string = transform({ chrome: '60' });
+  if (encoded !== string || !string.includes('rgba')) {
+    throw new Error(`\`testTargetsValidation()\` failed. Expected string and encoded versions to produce the same output, got:\n${string}\n${encoded}`);
+  }
+})();
+
+await (async function testEncoding() {
+  // `.foo{content:"é"}` encoded as latin1.
+  const latin1 = Buffer.from('.foo { content: "\xe9" }', 'latin1');
+  let { code } = css.transform({ filename: 'test.css', code: latin1, minify: true, encoding: 'latin1' });
+  if (code.toString() !== '.foo{content:"é"}') {
+    throw new Error(`\`testEncoding()\` failed. Expected latin1 code to be decoded, got:\n${code}`);
+  }
+
+  let error = undefined;
+  try {
+    css.transform({ filename: 'test.css', code: latin1, minify: true });
+  } catch (err) {
+    error = err;
+  }
+  if (!error || !error.message.includes('Invalid UTF-8 sequence at byte offset 17')) {
+    throw new Error(`\`testEncoding()\` failed. Expected an error with the offset of the invalid byte, got:\n${error}`);
+  }
+
+  // A UTF-8 byte order mark is removed.
+  const bom = Buffer.concat([Buffer.from([0xef, 0xbb, 0xbf]), Buffer.from('.foo { color: red }')]);
+  ({ code } = css.transform({ filename: 'test.css', code: bom, minify: true }));
+  if (code.toString() !== '.foo{color:red}') {
+    throw new Error(`\`testEncoding()\` failed. Expected the byte order mark to be removed, got:\n${code}`);
+  }
+})();
+
+await (async function testStyleAttributeHtml() {
+  const { code } = css.transformStyleAttribute({
+    code: Buffer.from('content: &quot;a&quot;;&#10;color: red'),
+    minify: true,
+    decodeHtmlEntities: true,
+  });
+  if (code.toString() !== 'content:"a";color:red') {
+    throw new Error(`\`testStyleAttributeHtml()\` failed. Expected HTML entities to be decoded, got:\n${code}`);
+  }
+
+  // Locations are reported relative to the file the attribute came from.
+  const transform = (sourceOffset) => css.transformStyleAttribute({
+    filename: 'index.html',
+    code: Buffer.from('color: red; foo bar'),
+    errorRecovery: true,
+    sourceOffset,
+  }).warnings;
+  const [relative] = transform(undefined);
+  const [absolute] = transform({ line: 10, column: 5 });
+  if (!relative || !absolute || absolute.loc.line !== relative.loc.line + 9 || absolute.loc.column !== relative.loc.column + 4) {
+    throw new Error(`\`testStyleAttributeHtml()\` failed. Expected warnings to be offset by the source offset, got:\n${JSON.stringify(relative)}\n${JSON.stringify(absolute)}`);
+  }
+})();
+
+await (async function testTransformForBundler() {
+  let { code, diagnostics } = adapters.transformForBundler({
+    filename: 'test.css',
+    code: Buffer.from('.foo { color: red }'),
+    minify: true,
+  });
+  if (code !== '.foo{color:red}' || diagnostics.length !== 0) {
+    throw new Error(`\`testTransformForBundler()\` failed. Expected the compiled code without diagnostics, got:\n${code}\n${JSON.stringify(diagnostics)}`);
+  }
+
+  // Errors are returned as diagnostics rather than thrown.
+  ({ code, diagnostics } = adapters.transformForBundler({
+    filename: 'test.css',
+    code: Buffer.from('.foo { color: red }\n.bar > { color: red }'),
+  }));
+  const [diagnostic] = diagnostics;
+  if (code !== '' || diagnostics.length !== 1 || diagnostic.severity !== 'error' || diagnostic.file !== 'test.css' || diagnostic.start?.line !== 2) {
+    throw new Error(`\`testTransformForBundler()\` failed. Expected a single error diagnostic on line 2, got:\n${JSON.stringify(diagnostics)}`);
+  }
+})();
+
+await (async function testVisitor() {
+  const code = Buffer.from(`
+    .icon-a { background: red; color: red }
+    .b { background: blue; opacity: .5 }
+    @media (min-width: 10px) { .icon-b { color: green } }
+  `);
+  let rules = 0;
+  let declarations = 0;
+  const transform = (filter, Declaration = () => {}) => css.transform({
+    filename: 'test.css',
+    code,
+    minify: true,
+    visitor: {
+      Rule() { rules++; },
+      Declaration(declaration) { declarations++; return Declaration(declaration); },
+      filter,
+    },
+  }).code.toString();
+
+  transform(undefined);
+  if (rules !== 4 || declarations !== 5) {
+    throw new Error(`\`testVisitor()\` failed. Expected every rule and declaration to be visited without a filter, got ${rules} rules and ${declarations} declarations.`);
+  }
+
+  // Nodes that do not match the filter are not passed to JS.
+  rules = declarations = 0;
+  transform({ rules: { selectorContains: '.icon-' }, declarations: { properties: ['background'] } });
+  if (rules !== 2 || declarations !== 2) {
+    throw new Error(`\`testVisitor()\` failed. Expected only matching rules and declarations to be visited, got ${rules} rules and ${declarations} declarations.`);
+  }
+
+  // A returned node replaces the visited one.
+  const result = transform({ declarations: { properties: ['opacity'] } }, () => ({ property: 'opacity', value: 1 }));
+  if (!result.includes('.b{opacity:1;')) {
+    throw new Error(`\`testVisitor()\` failed. Expected the returned declaration to replace the visited one, got:\n${result}`);
+  }
+
+  // Exceptions thrown by a visitor function are rethrown.
+  let error = undefined;
+  try {
+    transform(undefined, () => { throw new Error('Oh noes!'); });
+  } catch (err) {
+    error = err;
+  }
+  if (error?.message !== 'Oh noes!') {
+    throw new Error(`\`testVisitor()\` failed. Expected the exception thrown by the visitor, got:\n${error}`);
+  }
+})();
+
+console.log('PASSED!');
@@ -0,0 +1,84 @@
+import css from './node/pkg/lightningcss_node.js';
+
+// Run `yarn wasm:build` first to build the WebAssembly module.
+
+await (async function testWasmBundle() {
+  const inMemoryFs = new Map(Object.entries({
+    'foo.css': `
+ @import 'bar.css';
+ @import 'baz.css';
+
+ .foo { color: red; }
+         `.trim(),
+
+    'bar.css': `
+ @import 'baz.css';
+
+ .bar { color: green; }
+         `.trim(),
+
+    'baz.css': `
+ .baz { color: blue; }
+         `.trim(),
+  }));
+
+  const { code } = css.bundle({
+    filename: 'foo.css',
+    minify: true,
+    resolver: {
+      read(file) {
+        const result = inMemoryFs.get(file);
+        if (!result) throw new Error(`Could not find ${file} in ${Array.from(inMemoryFs.keys()).join(', ')}.`);
+        return result;
+      },
+    },
+  });
+
+  const result = new TextDecoder().decode(code);
+  const expected = '.baz{color:#00f}.bar{color:green}.foo{color:red}';
+  if (result !== expected) throw new Error(`\`testWasmBundle()\` failed. Expected:\n${expected}\n\nGot:\n${result}`);
+})();
+
+await (async function testWasmBundleReadThrow() {
+  let error = undefined;
+  try {
+    css.bundle({
+      filename: 'foo.css',
+      resolver: {
+        read(file) {
+          throw new Error(`Oh noes! Failed to read \`${file}\`.`);
+        },
+      },
+    });
+  } catch (err) {
+    error = err;
+  }
+
+  if (!error) throw new Error(`\`testWasmBundleReadThrow()\` failed. Expected \`bundle()\` to throw, but it did not.`);
+  if (!String(error.message ?? error).includes('Oh noes! Failed to read')) {
+    throw new Error(`\`testWasmBundleReadThrow()\` failed. Expected \`bundle()\` to throw the error from \`read()\`, but it threw a different error:\n${error.message ?? error}`);
+  }
+})();
+
+await (async function testWasmCodeFrame() {
+  let error = undefined;
+  try {
+    css.transform({
+      filename: 'test.css',
+      code: Buffer.from('.a { color: red }\n.b > { color: red }\n.c { color: red }'),
+    });
+  } catch (err) {
+    error = err;
+  }
+
+  if (!error) throw new Error(`\`testWasmCodeFrame()\` failed. Expected \`transform()\` to throw, but it did not.`);
+  if (error.loc?.line !== 2 || error.fileName !== 'test.css') {
+    throw new Error(`\`testWasmCodeFrame()\` failed. Expected the error on line 2 of test.css, got ${JSON.stringify(error.loc)} in ${error.fileName}.`);
+  }
+  const lines = String(error.codeFrame).split('\n');
+  if (lines.length !== 4 || lines[1] !== '> 2 | .b > { color: red }' || !/^ {2} {2}\| +\^$/.test(lines[2])) {
+    throw new Error(`\`testWasmCodeFrame()\` failed. Expected a code frame pointing at line 2, got:\n${error.codeFrame}`);
+  }
+})();
+
+console.log('PASSED!');
@@ -367,6 +367,33 @@ fn targets() -> Result<(), Box<dyn std::error::Error>> {
   Ok(())
 }
 
+#[test]
+fn browserslist_config_path() -> Result<(), Box<dyn std::error::Error>> {
+  let dir = assert_fs::TempDir::new()?;
+  let file = dir.child("test.css");
+  file.write_str(".a { user-select: none }")?;
+  dir.child(".browserslistrc").write_str("safari 8")?;
+
+  let mut cmd = Command::cargo_bin("lightningcss")?;
+  cmd.arg(file.path());
+  cmd.arg("--browserslist-config-path").arg(dir.path());
+  cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("-webkit-user-select: none;"));
+
+  let mut cmd = Command::cargo_bin("lightningcss")?;
+  cmd.arg(file.path());
+  cmd.arg("--browserslist-config-path").arg(dir.child("missing").path());
+  cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("-webkit-user-select").not())
+    .stderr(predicate::str::contains("does not exist"));
+
+  Ok(())
+}
+
 #[test]
 fn preserve_custom_media() -> Result<(), Box<dyn std::error::Error>> {
   let file = assert_fs::NamedTempFile::new("test.css")?;